@@ -1,4 +1,5 @@
 //! A client for interacting with Wadm.
+use std::collections::BTreeMap;
 use std::path::PathBuf;
 use std::sync::{Arc, OnceLock};
 
@@ -211,11 +212,24 @@ impl Client {
         &self,
         name: &str,
         version: Option<&str>,
+    ) -> Result<(String, Option<String>)> {
+        self.deploy_manifest_with_variables(name, version, BTreeMap::new())
+            .await
+    }
+
+    /// Deploys a manifest to the lattice like [`deploy_manifest`](Self::deploy_manifest), replacing
+    /// any `${VAR}` placeholders in the manifest with the given variables
+    pub async fn deploy_manifest_with_variables(
+        &self,
+        name: &str,
+        version: Option<&str>,
+        variables: BTreeMap<String, String>,
     ) -> Result<(String, Option<String>)> {
         let topic = self.topics.model_deploy_topic(name);
-        let body = if let Some(version) = version {
+        let body = if version.is_some() || !variables.is_empty() {
             serde_json::to_vec(&DeployModelRequest {
-                version: Some(version.to_string()),
+                version: version.map(ToString::to_string),
+                variables,
             })
             .map_err(SerializationError::from)?
         } else {
@@ -75,7 +75,11 @@ impl TopicGenerator {
     /// Returns the full topic for WADM status subscriptions
     pub fn wadm_status_topic(&self, app_name: &str) -> String {
         // Extract just the lattice name from topic_prefix
-        let lattice = self.topic_prefix.split('.').last().unwrap_or("default");
+        let lattice = self
+            .topic_prefix
+            .split('.')
+            .next_back()
+            .unwrap_or("default");
         format!("{}.{}.{}", WADM_STATUS_API_PREFIX, lattice, app_name)
     }
 }
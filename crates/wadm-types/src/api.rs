@@ -1,3 +1,5 @@
+use std::collections::BTreeMap;
+
 use serde::{Deserialize, Serialize};
 
 use crate::Manifest;
@@ -124,9 +126,15 @@ pub enum DeleteResult {
 ///
 /// If the given version is empty (or the body is empty), it will deploy the latest version. If the
 /// version is set to "latest", it will also deploy the latest version
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Default, Serialize, Deserialize)]
 pub struct DeployModelRequest {
     pub version: Option<String>,
+    /// Values for the `${VAR}` placeholders in the manifest. These are stored with the deployed
+    /// version and replace any variables it was previously deployed with. If none are given, the
+    /// previously stored variables are used. Deploying fails if the manifest references a variable
+    /// that isn't defined
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub variables: BTreeMap<String, String>,
 }
 
 /// A response from a deploy or undeploy request
@@ -150,6 +158,115 @@ pub enum DeployResult {
     NotFound,
 }
 
+/// A request to pause or resume one of the scalers of a deployed model
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PauseScalerRequest {
+    /// The ID of the scaler, as reported in the status of the model
+    pub scaler_id: String,
+}
+
+/// The response to pausing or resuming part of wadm, such as reaping for a lattice
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PauseResponse {
+    pub result: PauseResult,
+    #[serde(default)]
+    pub message: String,
+}
+
+/// All possible outcomes of a pause or resume operation
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum PauseResult {
+    Error,
+    Acknowledged,
+    NotFound,
+}
+
+/// A request to apply a partial change to the latest version of a model. The patched manifest is
+/// stored as a new version and, if the patched version was deployed, deployed in its place. Only
+/// the scalers affected by the patch are rebuilt
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct PatchModelRequest {
+    /// The changes to make to the components of the model
+    #[serde(default)]
+    pub components: Vec<ComponentPatch>,
+    /// RFC 6902 JSON Patch operations to apply to the manifest, after any component changes. This
+    /// allows changes that can't be expressed as a [`ComponentPatch`]
+    #[serde(default)]
+    pub operations: Vec<JsonPatchOperation>,
+    /// The version to give the patched manifest. If not set, a new version is generated
+    #[serde(default)]
+    pub version: Option<String>,
+}
+
+/// A single [RFC 6902](https://datatracker.ietf.org/doc/html/rfc6902) JSON Patch operation, applied
+/// to the JSON form of a manifest. Paths are JSON pointers, such as
+/// `/spec/components/0/traits/0/properties/instances`
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(tag = "op", rename_all = "lowercase")]
+pub enum JsonPatchOperation {
+    /// Adds a value to an object or inserts it into an array, replacing any existing member
+    Add {
+        path: String,
+        value: serde_json::Value,
+    },
+    /// Removes the value at the path, which must exist
+    Remove { path: String },
+    /// Replaces the value at the path, which must exist
+    Replace {
+        path: String,
+        value: serde_json::Value,
+    },
+    /// Removes the value at `from` and adds it at the path
+    Move { from: String, path: String },
+    /// Adds a copy of the value at `from` at the path
+    Copy { from: String, path: String },
+    /// Checks that the value at the path is equal to the given value, failing the whole patch if
+    /// it isn't
+    Test {
+        path: String,
+        value: serde_json::Value,
+    },
+}
+
+/// A partial change to a single component of a model, matched by name. Any field that isn't set
+/// is left as is
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct ComponentPatch {
+    /// The name of the component to change
+    pub name: String,
+    /// The new image reference for the component
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub image: Option<String>,
+    /// The new number of instances for the component's scaler
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub instances: Option<usize>,
+}
+
+/// A response from a patch request
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PatchModelResponse {
+    pub result: PatchResult,
+    #[serde(default)]
+    pub message: String,
+    #[serde(default)]
+    pub name: String,
+    #[serde(default)]
+    pub version: Option<String>,
+}
+
+/// All possible outcomes of a patch operation
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum PatchResult {
+    Error,
+    /// The patched manifest was stored as a new version, but not deployed
+    NewVersion,
+    /// The patched manifest was stored as a new version and deployed
+    Deployed,
+    NotFound,
+}
+
 /// A request to undeploy a model
 ///
 /// Right now this is just an empty struct, but it is reserved for future use
@@ -246,6 +363,10 @@ pub struct StatusInfo {
     pub status_type: StatusType,
     #[serde(skip_serializing_if = "String::is_empty", default)]
     pub message: String,
+    /// How far along the scaler is, as the number of instances achieved out of the number desired.
+    /// Only set by scalers that can measure their progress
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub progress: Option<(u32, u32)>,
 }
 
 impl StatusInfo {
@@ -253,6 +374,7 @@ impl StatusInfo {
         StatusInfo {
             status_type: StatusType::Undeployed,
             message: message.to_owned(),
+            progress: None,
         }
     }
 
@@ -260,6 +382,7 @@ impl StatusInfo {
         StatusInfo {
             status_type: StatusType::Deployed,
             message: message.to_owned(),
+            progress: None,
         }
     }
 
@@ -267,6 +390,7 @@ impl StatusInfo {
         StatusInfo {
             status_type: StatusType::Failed,
             message: message.to_owned(),
+            progress: None,
         }
     }
 
@@ -274,6 +398,7 @@ impl StatusInfo {
         StatusInfo {
             status_type: StatusType::Reconciling,
             message: message.to_owned(),
+            progress: None,
         }
     }
 
@@ -281,6 +406,7 @@ impl StatusInfo {
         StatusInfo {
             status_type: StatusType::Waiting,
             message: message.to_owned(),
+            progress: None,
         }
     }
 
@@ -288,8 +414,23 @@ impl StatusInfo {
         StatusInfo {
             status_type: StatusType::Unhealthy,
             message: message.to_owned(),
+            progress: None,
+        }
+    }
+
+    pub fn paused(message: &str) -> Self {
+        StatusInfo {
+            status_type: StatusType::Paused,
+            message: message.to_owned(),
+            progress: None,
         }
     }
+
+    /// Sets how many instances have been achieved out of the number desired
+    pub fn with_progress(mut self, achieved: u32, desired: u32) -> Self {
+        self.progress = Some((achieved, desired));
+        self
+    }
 }
 
 /// All possible status types
@@ -305,6 +446,8 @@ pub enum StatusType {
     Deployed,
     Failed,
     Unhealthy,
+    /// An operator has paused reconciliation
+    Paused,
 }
 
 // Implementing add makes it easy for use to get an aggregate status by summing all of them together
@@ -330,6 +473,10 @@ impl std::ops::Add for StatusType {
             // If anything is waiting, the whole thing is
             (Self::Waiting, _) => Self::Waiting,
             (_, Self::Waiting) => Self::Waiting,
+            // Paused scalers won't make progress until resumed, so that takes priority over
+            // anything that is still changing
+            (Self::Paused, _) => Self::Paused,
+            (_, Self::Paused) => Self::Paused,
             (Self::Reconciling, _) => Self::Reconciling,
             (_, Self::Reconciling) => Self::Reconciling,
             (Self::Unhealthy, _) => Self::Unhealthy,
@@ -415,6 +562,18 @@ mod test {
             StatusType::Reconciling
         ));
 
+        assert!(matches!(
+            [StatusType::Reconciling, StatusType::Paused]
+                .into_iter()
+                .sum(),
+            StatusType::Paused
+        ));
+
+        assert!(matches!(
+            [StatusType::Paused, StatusType::Failed].into_iter().sum(),
+            StatusType::Failed
+        ));
+
         let empty: Vec<StatusType> = Vec::new();
         assert!(matches!(empty.into_iter().sum(), StatusType::Undeployed));
     }
@@ -227,6 +227,14 @@ impl From<SpreadScalerProperty> for wadm::types::SpreadscalerProperty {
         wadm::types::SpreadscalerProperty {
             instances: property.instances as u32,
             spread: property.spread.into_iter().map(|s| s.into()).collect(),
+            topology_key: property.topology_key,
+            min_count: property.min_count.map(|c| c as u32),
+            max_count: property.max_count.map(|c| c as u32),
+            anti_affinity: property.anti_affinity,
+            weight_key: property.weight_key,
+            selector: property.selector,
+            reconcile_interval: property.reconcile_interval,
+            instances_per_host: property.instances_per_host.map(|i| i as u32),
         }
     }
 }
@@ -293,6 +301,7 @@ impl From<StatusType> for wadm::types::StatusType {
             StatusType::Failed => wadm::types::StatusType::Failed,
             StatusType::Waiting => wadm::types::StatusType::Waiting,
             StatusType::Unhealthy => wadm::types::StatusType::Unhealthy,
+            StatusType::Paused => wadm::types::StatusType::Paused,
         }
     }
 }
@@ -308,6 +317,7 @@ impl From<wadm::types::StatusType> for StatusType {
             wadm::types::StatusType::Failed => StatusType::Failed,
             wadm::types::StatusType::Waiting => StatusType::Waiting,
             wadm::types::StatusType::Unhealthy => StatusType::Unhealthy,
+            wadm::types::StatusType::Paused => StatusType::Paused,
         }
     }
 }
@@ -317,6 +327,8 @@ impl From<wadm::types::StatusInfo> for StatusInfo {
         StatusInfo {
             status_type: info.status_type.into(),
             message: info.message,
+            // Progress isn't part of the WIT interface
+            progress: None,
         }
     }
 }
@@ -550,6 +562,14 @@ impl From<wadm::types::SpreadscalerProperty> for SpreadScalerProperty {
         SpreadScalerProperty {
             instances: property.instances as usize,
             spread: property.spread.into_iter().map(|s| s.into()).collect(),
+            topology_key: property.topology_key,
+            min_count: property.min_count.map(|c| c as usize),
+            max_count: property.max_count.map(|c| c as usize),
+            anti_affinity: property.anti_affinity,
+            weight_key: property.weight_key,
+            selector: property.selector,
+            reconcile_interval: property.reconcile_interval,
+            instances_per_host: property.instances_per_host.map(|i| i as usize),
         }
     }
 }
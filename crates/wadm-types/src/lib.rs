@@ -27,10 +27,53 @@ pub const VERSION_ANNOTATION_KEY: &str = "version";
 pub const DESCRIPTION_ANNOTATION_KEY: &str = "description";
 /// The annotation key for shared applications
 pub const SHARED_ANNOTATION_KEY: &str = "experimental.wasmcloud.dev/shared";
+/// The annotation key for a comma separated list of component names whose scalers should run in
+/// shadow mode, reporting the commands they would issue without ever acting on them
+pub const SHADOW_ANNOTATION_KEY: &str = "experimental.wasmcloud.dev/shadow";
+/// The annotation key for a comma separated list of daily UTC windows (e.g. `22:00-02:00`) during
+/// which non-urgent changes are allowed to be made to the manifest's resources
+pub const MAINTENANCE_WINDOWS_ANNOTATION_KEY: &str =
+    "experimental.wasmcloud.dev/maintenance-windows";
+/// The annotation key that, when set to `true`, marks a manifest's scalers as failed while any of
+/// its components are running more than one version at the same time
+pub const UNIFORM_VERSIONS_ANNOTATION_KEY: &str = "experimental.wasmcloud.dev/uniform-versions";
+/// The annotation key for how instances are chosen to be stopped when a component is scaled down.
+/// One of `any` (the default), `newest`, `oldest` or `even-hosts`
+pub const SCALE_DOWN_POLICY_ANNOTATION_KEY: &str = "experimental.wasmcloud.dev/scale-down-policy";
+/// The annotation key that, when set to `true`, leaves the tag out of image references that are
+/// pinned to a digest when computing scaler IDs, so re-tagging the same image doesn't recreate the
+/// scalers for it
+pub const STABLE_SCALER_IDS_ANNOTATION_KEY: &str = "experimental.wasmcloud.dev/stable-scaler-ids";
+/// The annotation key for how long (in seconds) a provider spread scaler avoids a host after the
+/// provider failed to start on it, retrying on a different eligible host instead
+pub const PROVIDER_RETRY_COOLDOWN_ANNOTATION_KEY: &str =
+    "experimental.wasmcloud.dev/provider-retry-cooldown";
+/// The annotation key for how many instances a component spread can be over or under its desired
+/// count before wadm corrects it
+pub const COUNT_TOLERANCE_ANNOTATION_KEY: &str = "experimental.wasmcloud.dev/count-tolerance";
+/// The annotation key for the most commands each of a manifest's scalers can issue in a single
+/// pass. Large changes are then made in batches over several passes
+pub const COMMAND_BUDGET_ANNOTATION_KEY: &str = "experimental.wasmcloud.dev/command-budget";
+/// The annotation key that, when set to `true`, only places components on hosts running providers
+/// for every capability the component's claims require
+pub const CHECK_CAPABILITIES_ANNOTATION_KEY: &str = "experimental.wasmcloud.dev/check-capabilities";
+/// The annotation key that, when set to `true`, has component spread scalers issue commands with
+/// the absolute number of instances each host should run, rather than the change from what they
+/// observed. Instances are spread evenly across eligible hosts, so spreadscalers in such a manifest
+/// can't set `anti_affinity`, `topology_key` or `weight_key`
+pub const DECLARATIVE_COMMANDS_ANNOTATION_KEY: &str =
+    "experimental.wasmcloud.dev/declarative-commands";
+/// The annotation key for the oldest version of wadm (e.g. `0.21.0`) that can deploy a manifest.
+/// Older versions of wadm refuse to deploy it rather than risk misreading it
+pub const MIN_WADM_VERSION_ANNOTATION_KEY: &str = "experimental.wasmcloud.dev/min-wadm-version";
+/// The annotation key wadm sets to the time (in RFC 3339 format) a manifest version was deployed
+pub const DEPLOYED_AT_ANNOTATION_KEY: &str = "wasmcloud.dev/deployed-at";
 /// The identifier for the builtin spreadscaler trait type
 pub const SPREADSCALER_TRAIT: &str = "spreadscaler";
 /// The identifier for the builtin daemonscaler trait type
 pub const DAEMONSCALER_TRAIT: &str = "daemonscaler";
+/// The identifier for the builtin metricscaler trait type
+pub const METRICSCALER_TRAIT: &str = "metricscaler";
 /// The identifier for the builtin linkdef trait type
 pub const LINK_TRAIT: &str = "link";
 /// The string used for indicating a latest version. It is explicitly forbidden to use as a version
@@ -80,6 +123,165 @@ impl Manifest {
             .is_some_and(|v| v.parse::<bool>().unwrap_or(false))
     }
 
+    /// Returns the names of the components whose scalers should run in shadow mode
+    pub fn shadowed_components(&self) -> Vec<&str> {
+        self.metadata
+            .annotations
+            .get(SHADOW_ANNOTATION_KEY)
+            .map(|v| {
+                v.split(',')
+                    .map(str::trim)
+                    .filter(|name| !name.is_empty())
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// Returns the raw maintenance windows for the manifest, if it has any
+    pub fn maintenance_windows(&self) -> Option<&str> {
+        self.metadata
+            .annotations
+            .get(MAINTENANCE_WINDOWS_ANNOTATION_KEY)
+            .map(|v| v.as_str())
+    }
+
+    /// Returns true if the manifest requires every component to run a single version at a time
+    pub fn requires_uniform_versions(&self) -> bool {
+        self.metadata
+            .annotations
+            .get(UNIFORM_VERSIONS_ANNOTATION_KEY)
+            .is_some_and(|v| v.trim().eq_ignore_ascii_case("true"))
+    }
+
+    /// Returns true if the manifest only wants components placed on hosts that have providers for
+    /// the capabilities they require
+    pub fn checks_capabilities(&self) -> bool {
+        self.metadata
+            .annotations
+            .get(CHECK_CAPABILITIES_ANNOTATION_KEY)
+            .is_some_and(|v| v.trim().eq_ignore_ascii_case("true"))
+    }
+
+    /// Returns true if the manifest wants component spread scalers to issue declarative commands
+    pub fn uses_declarative_commands(&self) -> bool {
+        self.metadata
+            .annotations
+            .get(DECLARATIVE_COMMANDS_ANNOTATION_KEY)
+            .is_some_and(|v| v.trim().eq_ignore_ascii_case("true"))
+    }
+
+    /// Returns true if the manifest wants scaler IDs that don't change when only the tag of a
+    /// digest pinned image reference changes
+    pub fn uses_stable_scaler_ids(&self) -> bool {
+        self.metadata
+            .annotations
+            .get(STABLE_SCALER_IDS_ANNOTATION_KEY)
+            .is_some_and(|v| v.trim().eq_ignore_ascii_case("true"))
+    }
+
+    /// Returns the raw scale down policy for the manifest, if it has one
+    pub fn scale_down_policy(&self) -> Option<&str> {
+        self.metadata
+            .annotations
+            .get(SCALE_DOWN_POLICY_ANNOTATION_KEY)
+            .map(|v| v.as_str())
+    }
+
+    /// Returns the raw provider retry cooldown for the manifest, if it has one
+    pub fn provider_retry_cooldown(&self) -> Option<&str> {
+        self.metadata
+            .annotations
+            .get(PROVIDER_RETRY_COOLDOWN_ANNOTATION_KEY)
+            .map(|v| v.as_str())
+    }
+
+    /// Returns the raw count tolerance for the manifest, if it has one
+    pub fn count_tolerance(&self) -> Option<&str> {
+        self.metadata
+            .annotations
+            .get(COUNT_TOLERANCE_ANNOTATION_KEY)
+            .map(|v| v.as_str())
+    }
+
+    /// Returns the raw command budget for the manifest's scalers, if it has one
+    pub fn command_budget(&self) -> Option<&str> {
+        self.metadata
+            .annotations
+            .get(COMMAND_BUDGET_ANNOTATION_KEY)
+            .map(|v| v.as_str())
+    }
+
+    /// Returns the raw minimum version of wadm needed to deploy the manifest, if it has one
+    pub fn min_wadm_version(&self) -> Option<&str> {
+        self.metadata
+            .annotations
+            .get(MIN_WADM_VERSION_ANNOTATION_KEY)
+            .map(|v| v.as_str())
+    }
+
+    /// Returns when the manifest was last deployed, if wadm has deployed it
+    pub fn deployed_at(&self) -> Option<&str> {
+        self.metadata
+            .annotations
+            .get(DEPLOYED_AT_ANNOTATION_KEY)
+            .map(|v| v.as_str())
+    }
+
+    /// Returns a copy of this manifest with the given component patches merged in. The version
+    /// annotation is removed so the patched manifest can be stored as a new version. Returns an
+    /// error if a patch names a component that doesn't exist or sets the instances of a component
+    /// without a scaler
+    pub fn patched(&self, patches: &[api::ComponentPatch]) -> anyhow::Result<Manifest> {
+        let mut manifest = self.clone();
+        manifest.metadata.annotations.remove(VERSION_ANNOTATION_KEY);
+        for patch in patches {
+            let component = manifest
+                .spec
+                .components
+                .iter_mut()
+                .find(|component| component.name == patch.name)
+                .ok_or_else(|| anyhow::anyhow!("Component {} does not exist", patch.name))?;
+            if let Some(image) = &patch.image {
+                match &mut component.properties {
+                    Properties::Component { properties } => {
+                        properties.image = Some(image.to_owned())
+                    }
+                    Properties::Capability { properties } => {
+                        properties.image = Some(image.to_owned())
+                    }
+                }
+            }
+            if let Some(instances) = patch.instances {
+                let scaler = component
+                    .traits
+                    .iter_mut()
+                    .flatten()
+                    .filter(|trt| trt.is_scaler())
+                    .find_map(|trt| match &mut trt.properties {
+                        TraitProperty::SpreadScaler(props) => Some(props),
+                        _ => None,
+                    })
+                    .ok_or_else(|| {
+                        anyhow::anyhow!("Component {} does not have a scaler", patch.name)
+                    })?;
+                scaler.instances = instances;
+            }
+        }
+        Ok(manifest)
+    }
+
+    /// Returns a copy of this manifest with the given RFC 6902 JSON Patch operations applied in
+    /// order. Returns an error if any operation fails or the patched document is no longer a valid
+    /// manifest, in which case none of the operations are applied
+    pub fn json_patched(&self, operations: &[api::JsonPatchOperation]) -> anyhow::Result<Manifest> {
+        let mut doc = serde_json::to_value(self)?;
+        for operation in operations {
+            apply_json_patch_operation(&mut doc, operation)?;
+        }
+        serde_json::from_value(doc)
+            .map_err(|e| anyhow::anyhow!("Patched manifest is not valid: {e}"))
+    }
+
     /// Returns the components in the manifest
     pub fn components(&self) -> impl Iterator<Item = &Component> {
         self.spec.components.iter()
@@ -603,6 +805,57 @@ pub struct SpreadScalerProperty {
     /// Requirements for spreading those instances
     #[serde(default, skip_serializing_if = "Vec::is_empty")]
     pub spread: Vec<Spread>,
+    /// An optional host label (e.g. `zone`) to use as a failure domain. Instances for each spread
+    /// are distributed evenly across the distinct values of this label on eligible hosts, rather
+    /// than just across hosts. Only used by component spreadscalers
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub topology_key: Option<String>,
+    /// An optional lower bound for the number of instances. Only used by component spreadscalers
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub min_count: Option<usize>,
+    /// An optional upper bound for the number of instances, which guards against misconfigured
+    /// manifests requesting far too many instances. A value of 0 means there is no upper bound.
+    /// Only used by component spreadscalers
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_count: Option<usize>,
+    /// Host label keys (e.g. `rack`) that no two instances may share a value for. Each instance is
+    /// placed on its own host, and no two of those hosts have the same value for any of these
+    /// labels. Cannot be combined with `topology_key`. Only used by component spreadscalers
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub anti_affinity: Vec<String>,
+    /// An optional host label (e.g. `weight`) holding an integer weight for each host. Instances
+    /// for each spread are divided between eligible hosts in proportion to their weights rather
+    /// than evenly. Hosts without a valid weight count as 1. Cannot be combined with `topology_key`
+    /// or `anti_affinity`. Only used by component spreadscalers
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub weight_key: Option<String>,
+    /// Set-based host label selectors, e.g. `zone In [us-east-1, us-east-2]` or
+    /// `tier NotIn [spare]`. Hosts must satisfy every selector to be eligible. An empty `In` list
+    /// matches no hosts, and hosts without the label always satisfy `NotIn`. Only used by
+    /// daemonscalers
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub selector: Vec<String>,
+    /// An optional number of seconds between periodic reconciles of this scaler, on top of the
+    /// reconciles triggered by events. This corrects drift left behind by missed events. Disabled
+    /// when unset or 0
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reconcile_interval: Option<u64>,
+    /// The most instances of the provider to run on each host, for hosts that can run more than
+    /// one instance of the same provider. Instances are spread across eligible hosts before a host
+    /// runs more than one. Defaults to 1. Only used by provider spreadscalers
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub instances_per_host: Option<usize>,
+}
+
+impl SpreadScalerProperty {
+    /// Returns the number of instances to run after clamping the requested instances between
+    /// `min_count` and `max_count`
+    pub fn bounded_instances(&self) -> usize {
+        let max = self.max_count.filter(|max| *max > 0).unwrap_or(usize::MAX);
+        self.instances
+            .max(self.min_count.unwrap_or_default())
+            .min(max)
+    }
 }
 
 /// Configuration for various spreading requirements
@@ -629,6 +882,91 @@ impl Default for Spread {
     }
 }
 
+/// Applies a single JSON Patch operation to the given document
+fn apply_json_patch_operation(
+    doc: &mut serde_json::Value,
+    operation: &api::JsonPatchOperation,
+) -> anyhow::Result<()> {
+    use api::JsonPatchOperation;
+    match operation {
+        JsonPatchOperation::Add { path, value } => json_patch_add(doc, path, value.to_owned()),
+        JsonPatchOperation::Remove { path } => json_patch_remove(doc, path).map(|_| ()),
+        JsonPatchOperation::Replace { path, value } => {
+            let target = doc
+                .pointer_mut(path)
+                .ok_or_else(|| anyhow::anyhow!("Path {path} does not exist"))?;
+            *target = value.to_owned();
+            Ok(())
+        }
+        JsonPatchOperation::Move { from, path } => {
+            if path.starts_with(&format!("{from}/")) {
+                anyhow::bail!("Cannot move {from} into one of its own children");
+            }
+            let value = json_patch_remove(doc, from)?;
+            json_patch_add(doc, path, value)
+        }
+        JsonPatchOperation::Copy { from, path } => {
+            let value = doc
+                .pointer(from)
+                .cloned()
+                .ok_or_else(|| anyhow::anyhow!("Path {from} does not exist"))?;
+            json_patch_add(doc, path, value)
+        }
+        JsonPatchOperation::Test { path, value } => {
+            if doc.pointer(path) != Some(value) {
+                anyhow::bail!("Test failed, value at {path} is not {value}");
+            }
+            Ok(())
+        }
+    }
+}
+
+/// Splits a JSON pointer into the pointer to its parent and its unescaped last token
+fn split_json_pointer(path: &str) -> anyhow::Result<(&str, String)> {
+    let (parent, token) = path
+        .rsplit_once('/')
+        .ok_or_else(|| anyhow::anyhow!("Invalid JSON pointer {path}"))?;
+    Ok((parent, token.replace("~1", "/").replace("~0", "~")))
+}
+
+fn json_patch_add(
+    doc: &mut serde_json::Value,
+    path: &str,
+    value: serde_json::Value,
+) -> anyhow::Result<()> {
+    if path.is_empty() {
+        *doc = value;
+        return Ok(());
+    }
+    let (parent, token) = split_json_pointer(path)?;
+    match doc.pointer_mut(parent) {
+        Some(serde_json::Value::Object(map)) => {
+            map.insert(token, value);
+        }
+        Some(serde_json::Value::Array(list)) if token == "-" => list.push(value),
+        Some(serde_json::Value::Array(list)) => match token.parse::<usize>() {
+            Ok(idx) if idx <= list.len() => list.insert(idx, value),
+            _ => anyhow::bail!("Invalid array index in {path}"),
+        },
+        Some(_) => anyhow::bail!("Cannot add to {parent} as it is not an object or array"),
+        None => anyhow::bail!("Path {parent} does not exist"),
+    }
+    Ok(())
+}
+
+fn json_patch_remove(doc: &mut serde_json::Value, path: &str) -> anyhow::Result<serde_json::Value> {
+    let (parent, token) = split_json_pointer(path)?;
+    let removed = match doc.pointer_mut(parent) {
+        Some(serde_json::Value::Object(map)) => map.remove(&token),
+        Some(serde_json::Value::Array(list)) => match token.parse::<usize>() {
+            Ok(idx) if idx < list.len() => Some(list.remove(idx)),
+            _ => None,
+        },
+        _ => None,
+    };
+    removed.ok_or_else(|| anyhow::anyhow!("Path {path} does not exist"))
+}
+
 #[cfg(test)]
 mod test {
     use std::io::BufReader;
@@ -810,6 +1148,14 @@ mod test {
         let spreadscalerprop = SpreadScalerProperty {
             instances: 4,
             spread: spread_vec,
+            topology_key: None,
+            min_count: None,
+            max_count: None,
+            anti_affinity: Vec::new(),
+            weight_key: None,
+            selector: Vec::new(),
+            reconcile_interval: None,
+            instances_per_host: None,
         };
         let trait_item = Trait::new_spreadscaler(spreadscalerprop);
         trait_vec.push(trait_item);
@@ -875,6 +1221,14 @@ mod test {
         let spreadscalerprop = SpreadScalerProperty {
             instances: 1,
             spread: spread_vec,
+            topology_key: None,
+            min_count: None,
+            max_count: None,
+            anti_affinity: Vec::new(),
+            weight_key: None,
+            selector: Vec::new(),
+            reconcile_interval: None,
+            instances_per_host: None,
         };
         let mut trait_vec: Vec<Trait> = Vec::new();
         let trait_item = Trait::new_spreadscaler(spreadscalerprop);
@@ -984,4 +1338,73 @@ mod test {
             panic!("trait property was not a link definition");
         };
     }
+
+    #[test]
+    fn test_json_patched() {
+        let manifest: Manifest = serde_yaml::from_str(
+            r#"
+apiVersion: core.oam.dev/v1beta1
+kind: Application
+metadata:
+  name: patchme
+  annotations:
+    version: v0.0.1
+spec:
+  components:
+    - name: hello
+      type: component
+      properties:
+        image: ghcr.io/wasmcloud/components/http-hello-world-rust:0.1.0
+      traits:
+        - type: spreadscaler
+          properties:
+            instances: 1
+"#,
+        )
+        .expect("Should be able to parse");
+
+        let patched = manifest
+            .json_patched(&[
+                api::JsonPatchOperation::Test {
+                    path: "/spec/components/0/name".to_string(),
+                    value: serde_json::json!("hello"),
+                },
+                api::JsonPatchOperation::Replace {
+                    path: "/spec/components/0/traits/0/properties/instances".to_string(),
+                    value: serde_json::json!(4),
+                },
+                api::JsonPatchOperation::Add {
+                    path: "/metadata/annotations/experimental.wasmcloud.dev~1shared".to_string(),
+                    value: serde_json::json!("true"),
+                },
+            ])
+            .expect("Should be able to apply patch");
+        let Some(TraitProperty::SpreadScaler(props)) = patched.spec.components[0]
+            .traits
+            .as_ref()
+            .map(|traits| &traits[0].properties)
+        else {
+            panic!("Should have a spreadscaler");
+        };
+        assert_eq!(props.instances, 4);
+        assert!(patched.shared(), "Escaped keys should be unescaped");
+
+        let err = manifest
+            .json_patched(&[
+                api::JsonPatchOperation::Remove {
+                    path: "/spec/components/0/traits".to_string(),
+                },
+                api::JsonPatchOperation::Test {
+                    path: "/spec/components/0/name".to_string(),
+                    value: serde_json::json!("world"),
+                },
+            ])
+            .expect_err("A failed test should fail the patch");
+        assert!(err.to_string().contains("Test failed"));
+        assert!(manifest
+            .json_patched(&[api::JsonPatchOperation::Remove {
+                path: "/spec".to_string(),
+            }])
+            .is_err());
+    }
 }
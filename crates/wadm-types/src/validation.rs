@@ -202,7 +202,7 @@ pub struct ValidationFailure {
 }
 
 impl ValidationFailure {
-    fn new(level: ValidationFailureLevel, msg: String) -> Self {
+    pub fn new(level: ValidationFailureLevel, msg: String) -> Self {
         ValidationFailure { level, msg }
     }
 }
@@ -257,6 +257,35 @@ impl ValidationOutput for Vec<ValidationFailure> {
     }
 }
 
+/// A report of whether a manifest can be deployed, with the failures split into the errors that
+/// would stop it from deploying and warnings about things that may not work as intended
+#[derive(Debug, Default, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub struct ValidationReport {
+    pub errors: Vec<ValidationFailure>,
+    pub warnings: Vec<ValidationFailure>,
+}
+
+impl FromIterator<ValidationFailure> for ValidationReport {
+    fn from_iter<T: IntoIterator<Item = ValidationFailure>>(iter: T) -> Self {
+        let (errors, warnings) = iter
+            .into_iter()
+            .partition(|failure| failure.level == ValidationFailureLevel::Error);
+        ValidationReport { errors, warnings }
+    }
+}
+
+impl ValidationOutput for ValidationReport {
+    fn valid(&self) -> bool {
+        self.errors.is_empty()
+    }
+    fn warnings(&self) -> Vec<&ValidationFailure> {
+        self.warnings.iter().collect()
+    }
+    fn errors(&self) -> Vec<&ValidationFailure> {
+        self.errors.iter().collect()
+    }
+}
+
 /// Validate a WADM application manifest, returning a list of validation failures
 ///
 /// At present this can check for:
@@ -348,6 +377,7 @@ pub async fn validate_manifest(manifest: &Manifest) -> Result<Vec<ValidationFail
     failures.extend(validate_policies(manifest));
     failures.extend(ensure_no_custom_traits(manifest));
     failures.extend(validate_component_properties(manifest));
+    failures.extend(validate_scaler_properties(manifest));
     failures.extend(check_duplicate_links(manifest));
     failures.extend(validate_link_configs(manifest));
     Ok(failures)
@@ -694,6 +724,57 @@ pub fn validate_component_properties(application: &Manifest) -> Vec<ValidationFa
     failures
 }
 
+/// Ensure that no scaler trait combines spreading options that conflict with each other, such as a
+/// `min_count` above its `max_count` or more than one way of choosing hosts for instances
+pub fn validate_scaler_properties(manifest: &Manifest) -> Vec<ValidationFailure> {
+    let mut failures = Vec::new();
+    for component in manifest.components() {
+        let scalers = component
+            .traits
+            .iter()
+            .flatten()
+            .filter(|trt| trt.is_scaler())
+            .filter_map(|trt| match &trt.properties {
+                TraitProperty::SpreadScaler(props) => Some(props),
+                _ => None,
+            });
+        for props in scalers {
+            let mut conflict = |msg: &str| {
+                failures.push(ValidationFailure::new(
+                    ValidationFailureLevel::Error,
+                    format!("Scaler for component '{}' {msg}", component.name),
+                ))
+            };
+            if let (Some(min), Some(max)) = (props.min_count, props.max_count) {
+                if max > 0 && min > max {
+                    conflict(&format!(
+                        "has a 'min_count' of {min} which is greater than its 'max_count' of {max}"
+                    ));
+                }
+            }
+            if !props.anti_affinity.is_empty() && props.topology_key.is_some() {
+                conflict("cannot set both 'anti_affinity' and 'topology_key'");
+            }
+            if props.weight_key.is_some()
+                && (props.topology_key.is_some() || !props.anti_affinity.is_empty())
+            {
+                conflict("cannot set 'weight_key' with 'topology_key' or 'anti_affinity'");
+            }
+            // Declarative commands place instances on their own, so they can't be combined with
+            // another way of choosing hosts
+            if manifest.uses_declarative_commands()
+                && matches!(component.properties, Properties::Component { .. })
+                && (!props.anti_affinity.is_empty()
+                    || props.topology_key.is_some()
+                    || props.weight_key.is_some())
+            {
+                conflict("cannot set 'anti_affinity', 'topology_key' or 'weight_key' when the manifest uses declarative commands");
+            }
+        }
+    }
+    failures
+}
+
 /// Validates link configs in a WADM application manifest.
 ///
 /// At present this can check for:
@@ -878,7 +959,8 @@ fn check_duplicate_links(manifest: &Manifest) -> Vec<ValidationFailure> {
 
 #[cfg(test)]
 mod tests {
-    use super::is_valid_manifest_name;
+    use super::{is_valid_manifest_name, validate_scaler_properties};
+    use crate::{Manifest, DECLARATIVE_COMMANDS_ANNOTATION_KEY};
 
     const VALID_MANIFEST_NAMES: [&str; 4] = [
         "mymanifest",
@@ -905,4 +987,73 @@ mod tests {
             assert!(!is_valid_manifest_name(invalid))
         }
     }
+
+    /// Ensure scalers with conflicting spreading options are rejected
+    #[test]
+    fn scaler_properties_conflicting() {
+        let manifest = |properties: &str| -> Manifest {
+            serde_yaml::from_str(&format!(
+                r#"
+apiVersion: core.oam.dev/v1beta1
+kind: Application
+metadata:
+  name: conflicts
+  annotations:
+    version: v0.0.1
+spec:
+  components:
+    - name: http-component
+      type: component
+      properties:
+        image: ghcr.io/wasmcloud/components/http-hello-world-rust:0.1.0
+      traits:
+        - type: spreadscaler
+          properties:
+            instances: 3
+{properties}
+"#
+            ))
+            .expect("manifest should parse")
+        };
+
+        assert!(validate_scaler_properties(&manifest("            topology_key: zone")).is_empty());
+        for properties in [
+            "            min_count: 4\n            max_count: 2",
+            "            topology_key: zone\n            anti_affinity: [rack]",
+            "            weight_key: weight\n            topology_key: zone",
+            "            weight_key: weight\n            anti_affinity: [rack]",
+        ] {
+            assert_eq!(
+                validate_scaler_properties(&manifest(properties)).len(),
+                1,
+                "{properties} should be rejected"
+            );
+        }
+        // A max_count of 0 means there is no upper bound
+        assert!(validate_scaler_properties(&manifest(
+            "            min_count: 4\n            max_count: 0"
+        ))
+        .is_empty());
+
+        let declarative = |properties: &str| {
+            let mut manifest = manifest(properties);
+            manifest.metadata.annotations.insert(
+                DECLARATIVE_COMMANDS_ANNOTATION_KEY.to_string(),
+                "true".to_string(),
+            );
+            manifest
+        };
+        assert!(validate_scaler_properties(&declarative("")).is_empty());
+        for properties in [
+            "            topology_key: zone",
+            "            anti_affinity: [rack]",
+            "            weight_key: weight",
+        ] {
+            assert_eq!(
+                validate_scaler_properties(&declarative(properties)).len(),
+                1,
+                "{properties} should be rejected with declarative commands"
+            );
+        }
+    }
 }
@@ -7,10 +7,14 @@ use std::{
 };
 
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
 use wasmcloud_control_interface::Link;
 
 use crate::{
-    events::{ComponentScaleFailed, ComponentScaled, Event, ProviderStartFailed, ProviderStarted},
+    events::{
+        ComponentScaleFailed, ComponentScaled, ConfigDeleted, ConfigSet, Event, LinkdefDeleted,
+        ProviderStartFailed, ProviderStarted,
+    },
     workers::insert_managed_annotations,
 };
 
@@ -28,6 +32,7 @@ macro_rules! from_impl {
 #[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
 pub enum Command {
     ScaleComponent(ScaleComponent),
+    UpdateComponent(UpdateComponent),
     StartProvider(StartProvider),
     StopProvider(StopProvider),
     PutLink(PutLink),
@@ -37,6 +42,47 @@ pub enum Command {
 }
 
 impl Command {
+    /// Returns a deterministic identifier for this command, computed as the sha256 digest of the
+    /// serialized command. The same command will always have the same ID, which allows the results
+    /// of command execution to be correlated with the scaler that issued them
+    pub fn id(&self) -> String {
+        // NOTE: Serializing to a `Value` first sorts any map keys so the output is stable
+        let serialized = serde_json::to_value(self)
+            .map(|v| v.to_string())
+            .unwrap_or_default();
+        let mut hasher = Sha256::new();
+        hasher.update(serialized.as_bytes());
+        format!("{:x}", hasher.finalize())
+    }
+
+    /// Returns the name of the model that generated this command, if the command type tracks it
+    pub fn model_name(&self) -> Option<&str> {
+        match self {
+            Command::ScaleComponent(ScaleComponent { model_name, .. })
+            | Command::UpdateComponent(UpdateComponent { model_name, .. })
+            | Command::StartProvider(StartProvider { model_name, .. })
+            | Command::StopProvider(StopProvider { model_name, .. })
+            | Command::PutLink(PutLink { model_name, .. })
+            | Command::DeleteLink(DeleteLink { model_name, .. }) => Some(model_name),
+            Command::PutConfig(_) | Command::DeleteConfig(_) => None,
+        }
+    }
+
+    /// Adds the given annotations to the component or provider started by this command. Annotations
+    /// already set on the command are kept. Other command types are left unchanged
+    pub fn add_annotations(&mut self, extra: &BTreeMap<String, String>) {
+        if let Command::ScaleComponent(ScaleComponent { annotations, .. })
+        | Command::UpdateComponent(UpdateComponent { annotations, .. })
+        | Command::StartProvider(StartProvider { annotations, .. }) = self
+        {
+            for (key, value) in extra {
+                annotations
+                    .entry(key.to_owned())
+                    .or_insert_with(|| value.to_owned());
+            }
+        }
+    }
+
     /// Generates the corresponding event for a [Command](Command) in the form of a two-tuple ([Event](Event), Option<Event>)
     ///
     /// # Arguments
@@ -107,6 +153,60 @@ impl Command {
                     })),
                 ))
             }
+            Command::UpdateComponent(UpdateComponent {
+                component_id,
+                host_id,
+                new_reference,
+                annotations,
+                model_name,
+            }) => {
+                let mut annotations = annotations.to_owned();
+                insert_managed_annotations(&mut annotations, model_name);
+                // Hosts report a successful update as a scale to the new reference. A failed update
+                // is only reported in the command response, so there is no failure event
+                Some((
+                    Event::ComponentScaled(ComponentScaled {
+                        component_id: component_id.to_owned(),
+                        host_id: host_id.to_owned(),
+                        // The instance count doesn't change, and isn't used when matching events
+                        max_instances: 0,
+                        image_ref: new_reference.to_owned(),
+                        annotations,
+                        // We don't know this field from the command
+                        claims: None,
+                    }),
+                    None,
+                ))
+            }
+            // Hosts don't report config failures with an event, only in the command response
+            Command::PutConfig(PutConfig { config_name, .. }) => Some((
+                Event::ConfigSet(ConfigSet {
+                    config_name: config_name.to_owned(),
+                }),
+                None,
+            )),
+            Command::DeleteConfig(DeleteConfig { config_name }) => Some((
+                Event::ConfigDeleted(ConfigDeleted {
+                    config_name: config_name.to_owned(),
+                }),
+                None,
+            )),
+            // Hosts don't report link failures with an event, only in the command response
+            Command::DeleteLink(DeleteLink {
+                source_id,
+                wit_namespace,
+                wit_package,
+                link_name,
+                ..
+            }) => Some((
+                Event::LinkdefDeleted(LinkdefDeleted {
+                    source_id: source_id.to_owned(),
+                    name: link_name.to_owned(),
+                    wit_namespace: wit_namespace.to_owned(),
+                    wit_package: wit_package.to_owned(),
+                }),
+                None,
+            )),
             _ => None,
         }
     }
@@ -145,6 +245,24 @@ impl PartialEq for ScaleComponent {
     }
 }
 
+/// Struct for the UpdateComponent command, which updates a running component to a new reference in
+/// place without stopping it
+#[derive(Clone, Debug, Serialize, Deserialize, Default, Eq, PartialEq)]
+pub struct UpdateComponent {
+    /// The ID of the running component to update
+    pub component_id: String,
+    /// The host id on which to update the component
+    pub host_id: String,
+    /// The OCI or bindle reference to update the component to
+    pub new_reference: String,
+    /// The name of the model/manifest that generated this command
+    pub model_name: String,
+    /// Additional annotations to attach on this command
+    pub annotations: BTreeMap<String, String>,
+}
+
+from_impl!(UpdateComponent);
+
 /// Struct for the StartProvider command
 #[derive(Clone, Debug, Eq, Serialize, Deserialize, Default)]
 pub struct StartProvider {
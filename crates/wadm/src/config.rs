@@ -6,7 +6,12 @@ use std::path::PathBuf;
 use clap::Parser;
 use wadm_types::api::DEFAULT_WADM_TOPIC_PREFIX;
 
-use crate::nats::StreamPersistence;
+use crate::{
+    consumers::DEFAULT_PRIORITY_BUFFER,
+    nats::StreamPersistence,
+    workers::{DeployConflictPolicy, SplitBrainPolicy},
+    DEFAULT_COMMAND_SUBJECT_TEMPLATE, DEFAULT_STATUS_SUBJECT_TEMPLATE,
+};
 
 #[derive(Clone, Debug)]
 #[cfg_attr(feature = "cli", derive(Parser))]
@@ -64,6 +69,12 @@ pub struct WadmConfig {
     )]
     pub max_jobs: Option<usize>,
 
+    /// (Advanced) The maximum number of lattices to manage. Events for any lattice found after this
+    /// many are logged and ignored, which protects against unbounded growth on a shared NATS
+    /// cluster carrying events for many unexpected lattices. Unlimited by default
+    #[cfg_attr(feature = "cli", arg(long = "max-lattices", env = "WADM_MAX_LATTICES"))]
+    pub max_lattices: Option<usize>,
+
     /// The URL of the nats server you want to connect to
     #[cfg_attr(
         feature = "cli",
@@ -245,6 +256,33 @@ pub struct WadmConfig {
         hide = true
     ))]
     pub max_status_stream_bytes: i64,
+    /// Maximum bytes to keep for the deploy event stream
+    #[cfg_attr(
+        feature = "cli", arg(
+        long = "deploy-event-stream-max-bytes",
+        env = "WADM_DEPLOY_EVENT_STREAM_MAX_BYTES",
+        default_value_t = -1,
+        hide = true
+    ))]
+    pub max_deploy_event_stream_bytes: i64,
+    /// Maximum bytes to keep for the shadow stream
+    #[cfg_attr(
+        feature = "cli", arg(
+        long = "shadow-stream-max-bytes",
+        env = "WADM_SHADOW_STREAM_MAX_BYTES",
+        default_value_t = -1,
+        hide = true
+    ))]
+    pub max_shadow_stream_bytes: i64,
+    /// Maximum bytes to keep for the dry run stream
+    #[cfg_attr(
+        feature = "cli", arg(
+        long = "dry-run-stream-max-bytes",
+        env = "WADM_DRY_RUN_STREAM_MAX_BYTES",
+        default_value_t = -1,
+        hide = true
+    ))]
+    pub max_dry_run_stream_bytes: i64,
     /// Maximum bytes to keep for the notify stream
     #[cfg_attr(
         feature = "cli", arg(
@@ -264,9 +302,494 @@ pub struct WadmConfig {
     ))]
     pub max_wasmbus_event_stream_bytes: i64,
 
+    /// (Advanced) Deduplicate commands across multiple wadm instances by claiming each command in
+    /// the state bucket for the given number of seconds before publishing it. Disabled by default
+    #[cfg_attr(
+        feature = "cli",
+        arg(long = "command-dedup-window", env = "WADM_COMMAND_DEDUP_WINDOW")
+    )]
+    pub command_dedup_window: Option<u64>,
+
+    /// (Advanced) Have each wadm instance renew a lease in the state bucket that expires after the
+    /// given number of seconds, so that instances can detect when they lose contact with each
+    /// other. Disabled by default
+    #[cfg_attr(
+        feature = "cli",
+        arg(long = "instance-lease-ttl", env = "WADM_INSTANCE_LEASE_TTL")
+    )]
+    pub instance_lease_ttl: Option<u64>,
+
+    /// (Advanced) What to do when this instance can't renew its lease and has likely been cut off
+    /// from the other instances. `log` keeps publishing commands, while `read-only` stops
+    /// publishing commands until the lease can be renewed. Only used when leases are enabled
+    #[cfg_attr(
+        feature = "cli",
+        arg(
+            long = "split-brain-policy",
+            env = "WADM_SPLIT_BRAIN_POLICY",
+            default_value_t = SplitBrainPolicy::Log
+        )
+    )]
+    pub split_brain_policy: SplitBrainPolicy,
+
+    /// (Advanced) Queue commands and publish them in batches of this size. Disabled by default
+    #[cfg_attr(
+        feature = "cli",
+        arg(long = "command-batch-size", env = "WADM_COMMAND_BATCH_SIZE")
+    )]
+    pub command_batch_size: Option<usize>,
+
+    /// (Advanced) The maximum time in milliseconds a command can be queued before its batch is
+    /// published, even if the batch isn't full. Only used when command batching is enabled
+    #[cfg_attr(
+        feature = "cli",
+        arg(
+            long = "command-batch-max-age",
+            env = "WADM_COMMAND_BATCH_MAX_AGE",
+            default_value = "500"
+        )
+    )]
+    pub command_batch_max_age: u64,
+
+    /// (Advanced) The maximum number of commands published each second for each lattice. Commands
+    /// over the limit are delayed rather than dropped. Disabled by default
+    #[cfg_attr(
+        feature = "cli",
+        arg(long = "command-rate-limit", env = "WADM_COMMAND_RATE_LIMIT")
+    )]
+    pub command_rate_limit: Option<f64>,
+
+    /// (Advanced) The number of commands that can be published at once for each lattice before the
+    /// command rate limit applies. Only used when the command rate limit is enabled
+    #[cfg_attr(
+        feature = "cli",
+        arg(
+            long = "command-rate-burst",
+            env = "WADM_COMMAND_RATE_BURST",
+            default_value = "10"
+        )
+    )]
+    pub command_rate_burst: u32,
+
+    /// (Advanced) Queue event acks and send them in batches of this size, which cuts down on
+    /// round trips to NATS under heavy event load. Nacks are always sent right away. Disabled by
+    /// default
+    #[cfg_attr(
+        feature = "cli",
+        arg(long = "event-ack-batch-size", env = "WADM_EVENT_ACK_BATCH_SIZE")
+    )]
+    pub event_ack_batch_size: Option<usize>,
+
+    /// (Advanced) The maximum time in milliseconds an event ack can be queued before its batch is
+    /// sent, even if the batch isn't full. Only used when ack batching is enabled. This must be
+    /// well under the 2 second ack wait of the event consumer
+    #[cfg_attr(
+        feature = "cli",
+        arg(
+            long = "event-ack-batch-max-age",
+            env = "WADM_EVENT_ACK_BATCH_MAX_AGE",
+            default_value = "100"
+        )
+    )]
+    pub event_ack_batch_max_age: u64,
+
+    /// (Advanced) The number of lattice events pulled from NATS at a time for each lattice. Events
+    /// that have to be handled in order are never reordered, but more important events (like a
+    /// host starting) can skip ahead of waiting health checks within this many events. A value of
+    /// 1 disables reordering
+    #[cfg_attr(
+        feature = "cli",
+        arg(
+            long = "event-priority-window",
+            env = "WADM_EVENT_PRIORITY_WINDOW",
+            default_value = "10"
+        )
+    )]
+    pub event_priority_window: usize,
+
+    /// Run scalers and publish statuses as usual, but only log the commands they would execute and
+    /// publish them under the `wadm.dry_run` subject prefix rather than actually executing them.
+    /// Useful for validating what wadm would do with a manifest
+    #[cfg_attr(
+        feature = "cli",
+        arg(long = "dry-run", default_value = "false", env = "WADM_DRY_RUN")
+    )]
+    pub dry_run: bool,
+
+    /// Run a self-test on startup that exercises the full control loop (the state store, a command
+    /// round trip through the command stream and publishing a status) and refuse to start if any
+    /// part of it is broken
+    #[cfg_attr(
+        feature = "cli",
+        arg(long = "self-test", default_value = "false", env = "WADM_SELF_TEST")
+    )]
+    pub self_test: bool,
+
+    /// (Advanced) Keep a history of the last N status transitions of each model in the state
+    /// store, which helps with debugging deployments that keep flapping. Identical statuses
+    /// reported one after another are coalesced. Disabled by default
+    #[cfg_attr(
+        feature = "cli",
+        arg(long = "status-history-size", env = "WADM_STATUS_HISTORY_SIZE")
+    )]
+    pub status_history_size: Option<usize>,
+
+    /// (Advanced) Keep every command sent for each model in the state store for this many seconds,
+    /// so the commands can be reviewed and replayed (e.g. after a control interface outage dropped
+    /// them). Disabled by default
+    #[cfg_attr(
+        feature = "cli",
+        arg(
+            long = "command-history-retention",
+            env = "WADM_COMMAND_HISTORY_RETENTION"
+        )
+    )]
+    pub command_history_retention: Option<u64>,
+
+    /// (Advanced) The maximum number of providers that can be started on a single host at the same
+    /// time. Starts on different hosts are not limited by this. Provider starts only run at the same
+    /// time when command scale concurrency is enabled. Unlimited by default
+    #[cfg_attr(
+        feature = "cli",
+        arg(
+            long = "max-provider-starts-per-host",
+            env = "WADM_MAX_PROVIDER_STARTS_PER_HOST"
+        )
+    )]
+    pub max_provider_starts_per_host: Option<usize>,
+
+    /// (Advanced) Number of seconds after a command is published that it is discarded instead of
+    /// executed. This keeps a backed up command stream from acting on state that a newer reconcile
+    /// has already replaced. Commands never expire by default
+    #[cfg_attr(feature = "cli", arg(long = "command-ttl", env = "WADM_COMMAND_TTL"))]
+    pub command_ttl: Option<u64>,
+
+    /// (Advanced) The number of times a command is sent before it is given up on when the control
+    /// interface can't be reached. It is redelivered later once given up on
+    #[cfg_attr(
+        feature = "cli",
+        arg(
+            long = "command-retry-attempts",
+            env = "WADM_COMMAND_RETRY_ATTEMPTS",
+            default_value = "3"
+        )
+    )]
+    pub command_retry_attempts: usize,
+
+    /// (Advanced) The time in milliseconds to wait before retrying a command that couldn't reach
+    /// the control interface. The wait doubles for each retry after that
+    #[cfg_attr(
+        feature = "cli",
+        arg(
+            long = "command-retry-delay",
+            env = "WADM_COMMAND_RETRY_DELAY",
+            default_value = "250"
+        )
+    )]
+    pub command_retry_delay: u64,
+
+    /// (Advanced) The number of consecutive failures to reach the control interface of a lattice
+    /// before wadm stops executing commands for it and waits for the control interface to recover
+    #[cfg_attr(
+        feature = "cli",
+        arg(
+            long = "ctl-failure-threshold",
+            env = "WADM_CTL_FAILURE_THRESHOLD",
+            default_value = "5"
+        )
+    )]
+    pub ctl_failure_threshold: usize,
+
+    /// (Advanced) The time in milliseconds to wait before first checking whether an unavailable
+    /// control interface has recovered. The wait doubles after each failed check
+    #[cfg_attr(
+        feature = "cli",
+        arg(
+            long = "ctl-initial-backoff",
+            env = "WADM_CTL_INITIAL_BACKOFF",
+            default_value = "1000"
+        )
+    )]
+    pub ctl_initial_backoff: u64,
+
+    /// (Advanced) The maximum time in milliseconds to wait between checks of an unavailable
+    /// control interface
+    #[cfg_attr(
+        feature = "cli",
+        arg(
+            long = "ctl-max-backoff",
+            env = "WADM_CTL_MAX_BACKOFF",
+            default_value = "30000"
+        )
+    )]
+    pub ctl_max_backoff: u64,
+
+    /// (Advanced) Run up to this many waiting component scale and provider start commands at the
+    /// same time instead of one after another. Commands for the same component or provider on the
+    /// same host still run in order. Disabled by default
+    #[cfg_attr(
+        feature = "cli",
+        arg(
+            long = "command-scale-concurrency",
+            env = "WADM_COMMAND_SCALE_CONCURRENCY"
+        )
+    )]
+    pub command_scale_concurrency: Option<usize>,
+
+    /// Publish a `command_executed` event for every command that succeeds rather than only for
+    /// failed commands and link commands. This is needed for shadow manifests to compare their
+    /// commands against the ones that were executed, so it is disabled by default to avoid
+    /// doubling the event traffic for every command
+    #[cfg_attr(
+        feature = "cli",
+        arg(
+            long = "publish-command-successes",
+            default_value = "false",
+            env = "WADM_PUBLISH_COMMAND_SUCCESSES"
+        )
+    )]
+    pub publish_command_successes: bool,
+
+    /// (Advanced) Compute the commands of component spread scalers on a pool of blocking threads,
+    /// running at most this many computations at once, instead of inline with event processing.
+    /// This keeps large reconciles from holding up events. Disabled by default
+    #[cfg_attr(
+        feature = "cli",
+        arg(
+            long = "reconcile-compute-threads",
+            env = "WADM_RECONCILE_COMPUTE_THREADS"
+        )
+    )]
+    pub reconcile_compute_threads: Option<usize>,
+
+    /// (Advanced) Number of seconds to wait after a host starts before placing anything on it. This
+    /// gives new hosts time to finish starting up and report their state. Disabled by default
+    #[cfg_attr(
+        feature = "cli",
+        arg(
+            long = "host-quiet-period",
+            default_value = "0",
+            env = "WADM_HOST_QUIET_PERIOD"
+        )
+    )]
+    pub host_quiet_period: u64,
+
+    /// (Advanced) Number of seconds after component instances start on a host during which they
+    /// are never chosen to be stopped when scaling down. Disabled by default
+    #[cfg_attr(
+        feature = "cli",
+        arg(
+            long = "component-start-cooldown",
+            default_value = "0",
+            env = "WADM_COMPONENT_START_COOLDOWN"
+        )
+    )]
+    pub component_start_cooldown: u64,
+
+    /// (Advanced) The number of component instances each host can run. When set, wadm divides this
+    /// capacity fairly between all models that want it, rather than letting whichever model
+    /// reconciles first take it all. Unlimited by default
+    #[cfg_attr(
+        feature = "cli",
+        arg(long = "max-instances-per-host", env = "WADM_MAX_INSTANCES_PER_HOST")
+    )]
+    pub max_instances_per_host: Option<usize>,
+
+    /// (Advanced) Tell other wadm instances about received events with a compact fingerprint of
+    /// the event rather than the full event, which makes notifications smaller. Only enable this
+    /// once all wadm instances are on a version that understands fingerprints
+    #[cfg_attr(
+        feature = "cli",
+        arg(
+            long = "compact-notifications",
+            default_value = "false",
+            env = "WADM_COMPACT_NOTIFICATIONS"
+        )
+    )]
+    pub compact_notifications: bool,
+
+    /// (Advanced) Number of seconds after a model is undeployed that events for it are only used to
+    /// update state, without running any scalers. This avoids reconciling every model for events
+    /// caused by the teardown. Set to 0 to disable
+    #[cfg_attr(
+        feature = "cli",
+        arg(
+            long = "teardown-period",
+            default_value = "30",
+            env = "WADM_TEARDOWN_PERIOD"
+        )
+    )]
+    pub teardown_period: u64,
+
+    /// (Advanced) Only replay the last N lattice events when an event consumer is first created for
+    /// a lattice, rather than every event in the stream. This makes startup faster at the cost of
+    /// state completeness. Cannot be combined with `--event-replay-max-age`
+    #[cfg_attr(
+        feature = "cli",
+        arg(long = "event-replay-max-events", env = "WADM_EVENT_REPLAY_MAX_EVENTS")
+    )]
+    pub event_replay_max_events: Option<u64>,
+
+    /// (Advanced) Only replay lattice events from the last N seconds when an event consumer is
+    /// first created for a lattice, rather than every event in the stream. This makes startup
+    /// faster at the cost of state completeness. Cannot be combined with
+    /// `--event-replay-max-events`
+    #[cfg_attr(
+        feature = "cli",
+        arg(long = "event-replay-max-age", env = "WADM_EVENT_REPLAY_MAX_AGE")
+    )]
+    pub event_replay_max_age: Option<u64>,
+
+    /// (Advanced) The template for the subject commands are published on. Must contain
+    /// `{lattice_id}` and can contain `{model_name}` to publish the commands of each model on their
+    /// own subject, which allows NATS permissions to be scoped per tenant
+    #[cfg_attr(
+        feature = "cli",
+        arg(
+            long = "command-subject-template",
+            env = "WADM_COMMAND_SUBJECT_TEMPLATE",
+            default_value = DEFAULT_COMMAND_SUBJECT_TEMPLATE
+        )
+    )]
+    pub command_subject_template: String,
+
+    /// (Advanced) The template for the subject model statuses are published on. Must contain both
+    /// `{lattice_id}` and `{model_name}`. NOTE: Clients that read status directly from NATS expect
+    /// the default subject
+    #[cfg_attr(
+        feature = "cli",
+        arg(
+            long = "status-subject-template",
+            env = "WADM_STATUS_SUBJECT_TEMPLATE",
+            default_value = DEFAULT_STATUS_SUBJECT_TEMPLATE
+        )
+    )]
+    pub status_subject_template: String,
+
+    /// (Advanced) What to do when a manifest is deployed while the previous version is still
+    /// converging. `preempt` switches to the new version right away, while `queue` waits for the
+    /// previous deploy to finish first
+    #[cfg_attr(
+        feature = "cli",
+        arg(
+            long = "deploy-conflict-policy",
+            env = "WADM_DEPLOY_CONFLICT_POLICY",
+            default_value_t = DeployConflictPolicy::Preempt
+        )
+    )]
+    pub deploy_conflict_policy: DeployConflictPolicy,
+
+    /// (Advanced) Number of seconds a version can wait for the previous deploy to converge with
+    /// the `queue` deploy conflict policy before it preempts the previous deploy anyway
+    #[cfg_attr(
+        feature = "cli",
+        arg(
+            long = "deploy-queue-timeout",
+            default_value = "300",
+            env = "WADM_DEPLOY_QUEUE_TIMEOUT"
+        )
+    )]
+    pub deploy_queue_timeout: u64,
+
+    /// (Advanced) Confirm with the host inventory that a component or provider has actually stopped
+    /// before removing it from state. This catches stops that only partially succeeded, at the
+    /// cost of an extra request to the host for every stop
+    #[cfg_attr(
+        feature = "cli",
+        arg(
+            long = "confirm-stops",
+            env = "WADM_CONFIRM_STOPS",
+            default_value = "false"
+        )
+    )]
+    pub confirm_stops: bool,
+
+    /// (Advanced) Update the host, provider and component state from a heartbeat at the same time
+    /// rather than one after another. This lowers the time it takes to handle heartbeats from
+    /// large hosts
+    #[cfg_attr(
+        feature = "cli",
+        arg(
+            long = "concurrent-heartbeats",
+            env = "WADM_CONCURRENT_HEARTBEATS",
+            default_value = "false"
+        )
+    )]
+    pub concurrent_heartbeats: bool,
+
+    /// (Advanced) The most scalers that can handle an event at the same time when an event affects
+    /// every model in a lattice, like a host or provider stopping. Lowering this keeps lattices
+    /// with many models from overwhelming the state store and control interface. Unlimited by
+    /// default
+    #[cfg_attr(
+        feature = "cli",
+        arg(long = "scaler-concurrency", env = "WADM_SCALER_CONCURRENCY")
+    )]
+    pub scaler_concurrency: Option<usize>,
+
+    /// (Advanced) Extra annotations, in the form KEY=VALUE, to add to every component and provider
+    /// wadm starts. Annotations set by wadm itself always take precedence over these
+    #[cfg_attr(
+        feature = "cli",
+        arg(
+            long = "instance-annotation",
+            env = "WADM_INSTANCE_ANNOTATIONS",
+            value_delimiter = ','
+        )
+    )]
+    pub instance_annotations: Vec<String>,
+
+    /// (Advanced) Annotate every component and provider wadm starts with the version of the
+    /// manifest it belongs to and when that version was deployed
+    #[cfg_attr(
+        feature = "cli",
+        arg(
+            long = "instance-deploy-metadata",
+            env = "WADM_INSTANCE_DEPLOY_METADATA",
+            default_value = "false"
+        )
+    )]
+    pub instance_deploy_metadata: bool,
+
+    /// (Advanced) Where to send the commands wadm issues and the model status changes it makes, in
+    /// addition to their usual subjects. Each sink is `nats:SUBJECT_PREFIX`, `webhook:URL` or
+    /// `kafka:TOPIC_URL` (a Kafka REST proxy topic). Webhook and Kafka sinks require the
+    /// `external_sinks` feature
+    #[cfg_attr(
+        feature = "cli",
+        arg(
+            long = "state-change-sink",
+            env = "WADM_STATE_CHANGE_SINKS",
+            value_delimiter = ','
+        )
+    )]
+    pub state_change_sinks: Vec<String>,
+
+    /// (Advanced) A NATS subject to listen on for runtime config updates, such as
+    /// `{"cleanup_interval": 30}`. Updates change the cleanup interval, host quiet period and
+    /// teardown period without restarting wadm. Disabled by default
+    #[cfg_attr(
+        feature = "cli",
+        arg(long = "config-reload-subject", env = "WADM_CONFIG_RELOAD_SUBJECT")
+    )]
+    pub config_reload_subject: Option<String>,
+
+    /// (Advanced) Compress values in the state store that are larger than this many bytes, to
+    /// stay under the NATS KV value size limit. Disabled by default
+    #[cfg(feature = "kv_compression")]
+    #[cfg_attr(
+        feature = "cli",
+        arg(
+            long = "kv-compression-threshold",
+            env = "WADM_KV_COMPRESSION_THRESHOLD"
+        )
+    )]
+    pub kv_compression_threshold: Option<usize>,
+
     #[cfg(feature = "http_admin")]
     #[cfg_attr(feature = "cli", clap(long = "http-admin", env = "WADM_HTTP_ADMIN"))]
-    /// HTTP administration endpoint address
+    /// HTTP administration endpoint address. When set, a Prometheus recorder is installed and its
+    /// metrics are served on `/metrics`
     pub http_admin: Option<SocketAddr>,
 }
 
@@ -276,6 +799,7 @@ impl Default for WadmConfig {
             host_id: None,
             domain: None,
             max_jobs: None,
+            max_lattices: None,
             nats_server: "127.0.0.1:4222".to_string(),
             nats_seed: None,
             nats_jwt: None,
@@ -294,11 +818,58 @@ impl Default for WadmConfig {
             max_event_stream_bytes: -1,
             max_event_consumer_stream_bytes: -1,
             max_status_stream_bytes: -1,
+            max_deploy_event_stream_bytes: -1,
+            max_shadow_stream_bytes: -1,
+            max_dry_run_stream_bytes: -1,
             max_notify_stream_bytes: -1,
             max_wasmbus_event_stream_bytes: -1,
             structured_logging: false,
             tracing_enabled: false,
             tracing_endpoint: None,
+            command_dedup_window: None,
+            instance_lease_ttl: None,
+            split_brain_policy: SplitBrainPolicy::default(),
+            command_batch_size: None,
+            command_batch_max_age: 500,
+            command_rate_limit: None,
+            command_rate_burst: 10,
+            event_ack_batch_size: None,
+            event_ack_batch_max_age: 100,
+            event_priority_window: DEFAULT_PRIORITY_BUFFER,
+            dry_run: false,
+            self_test: false,
+            status_history_size: None,
+            command_history_retention: None,
+            max_provider_starts_per_host: None,
+            command_ttl: None,
+            command_scale_concurrency: None,
+            publish_command_successes: false,
+            command_retry_attempts: 3,
+            command_retry_delay: 250,
+            ctl_failure_threshold: 5,
+            ctl_initial_backoff: 1000,
+            ctl_max_backoff: 30000,
+            reconcile_compute_threads: None,
+            host_quiet_period: 0,
+            component_start_cooldown: 0,
+            max_instances_per_host: None,
+            compact_notifications: false,
+            teardown_period: 30,
+            event_replay_max_events: None,
+            event_replay_max_age: None,
+            command_subject_template: DEFAULT_COMMAND_SUBJECT_TEMPLATE.to_string(),
+            status_subject_template: DEFAULT_STATUS_SUBJECT_TEMPLATE.to_string(),
+            deploy_conflict_policy: DeployConflictPolicy::default(),
+            deploy_queue_timeout: 300,
+            confirm_stops: false,
+            concurrent_heartbeats: false,
+            scaler_concurrency: None,
+            instance_annotations: Vec::new(),
+            instance_deploy_metadata: false,
+            state_change_sinks: Vec::new(),
+            config_reload_subject: None,
+            #[cfg(feature = "kv_compression")]
+            kv_compression_threshold: None,
             #[cfg(feature = "http_admin")]
             http_admin: None,
         }
@@ -0,0 +1,179 @@
+//! Batching of message acks so high throughput consumers don't pay for a round trip per message
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use async_nats::jetstream::Message;
+use tokio::sync::Mutex;
+use tracing::{trace, warn};
+
+/// Limits used when batching acks before sending them
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AckBatchConfig {
+    /// The number of queued acks that will cause the batch to be sent immediately
+    pub max_size: usize,
+    /// The longest an ack can be queued before the batch is sent, even if it isn't full. This
+    /// should be well under the ack wait of the consumer so messages aren't redelivered while
+    /// their ack is queued
+    pub max_age: Duration,
+}
+
+/// Something that can acknowledge a whole batch of messages at once
+#[async_trait::async_trait]
+pub trait AckSender<M>: Send + Sync {
+    /// Acks all of the given messages, in order. Failures should be logged, as any message that
+    /// couldn't be acked will be redelivered once its ack wait expires
+    async fn send_acks(&self, messages: Vec<M>);
+}
+
+/// Acks JetStream messages with a single round trip per batch. Every message but the last is acked
+/// without waiting for a response, and the last one is double acked. Acks are handled in order by
+/// the server, so a response for the last ack means the whole batch was received
+pub struct JetStreamAckSender;
+
+#[async_trait::async_trait]
+impl AckSender<Message> for JetStreamAckSender {
+    async fn send_acks(&self, mut messages: Vec<Message>) {
+        let Some(last) = messages.pop() else {
+            return;
+        };
+        for msg in messages {
+            if let Err(e) = msg.ack().await {
+                warn!(error = %e, "Error when sending batched ack, message will be redelivered");
+            }
+        }
+        if let Err(e) = last.double_ack().await {
+            warn!(error = %e, "Didn't receive a response for batched acks, some messages may be redelivered");
+        }
+    }
+}
+
+struct AckBatch<M> {
+    config: AckBatchConfig,
+    pending: Mutex<Vec<M>>,
+    sender: Box<dyn AckSender<M>>,
+}
+
+/// Queues acks and sends them in batches. A batch is sent as soon as it is full or once its oldest
+/// ack has been queued for the configured max age, whichever comes first. Clones of this batcher
+/// share the same batch
+pub struct AckBatcher<M = Message> {
+    batch: Arc<AckBatch<M>>,
+}
+
+// NOTE: Implemented manually so the message type doesn't need to be `Clone`
+impl<M> Clone for AckBatcher<M> {
+    fn clone(&self) -> Self {
+        AckBatcher {
+            batch: self.batch.clone(),
+        }
+    }
+}
+
+impl AckBatcher<Message> {
+    /// Returns a new batcher for JetStream messages using the given limits
+    pub fn new(config: AckBatchConfig) -> AckBatcher<Message> {
+        AckBatcher::with_sender(config, JetStreamAckSender)
+    }
+}
+
+impl<M: Send + 'static> AckBatcher<M> {
+    /// Returns a new batcher that sends its batches with the given sender
+    pub fn with_sender(
+        config: AckBatchConfig,
+        sender: impl AckSender<M> + 'static,
+    ) -> AckBatcher<M> {
+        AckBatcher {
+            batch: Arc::new(AckBatch {
+                config,
+                pending: Mutex::new(Vec::new()),
+                sender: Box::new(sender),
+            }),
+        }
+    }
+
+    /// Queues an ack for the given message, sending the batch if it is now full
+    pub async fn ack(&self, message: M) {
+        let (ready, start_timer) = {
+            let mut pending = self.batch.pending.lock().await;
+            let start_timer = pending.is_empty();
+            pending.push(message);
+            if pending.len() >= self.batch.config.max_size {
+                (std::mem::take(&mut *pending), false)
+            } else {
+                (Vec::new(), start_timer)
+            }
+        };
+
+        // The first ack in a batch starts the clock on sending it. If the batch fills up before
+        // then, this may send part of the next batch early, which is harmless
+        if start_timer {
+            let batch = self.batch.clone();
+            tokio::spawn(async move {
+                tokio::time::sleep(batch.config.max_age).await;
+                let messages = std::mem::take(&mut *batch.pending.lock().await);
+                if messages.is_empty() {
+                    return;
+                }
+                trace!(num_acks = %messages.len(), "Sending ack batch after reaching max age");
+                batch.sender.send_acks(messages).await;
+            });
+        }
+
+        if !ready.is_empty() {
+            trace!(num_acks = %ready.len(), "Sending full ack batch");
+            self.batch.sender.send_acks(ready).await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[derive(Clone, Default)]
+    struct RecordingSender {
+        batches: Arc<std::sync::Mutex<Vec<Vec<u64>>>>,
+    }
+
+    #[async_trait::async_trait]
+    impl AckSender<u64> for RecordingSender {
+        async fn send_acks(&self, messages: Vec<u64>) {
+            self.batches.lock().unwrap().push(messages);
+        }
+    }
+
+    #[tokio::test]
+    async fn acks_are_sent_in_batches() {
+        let sender = RecordingSender::default();
+        let batcher = AckBatcher::with_sender(
+            AckBatchConfig {
+                max_size: 3,
+                max_age: Duration::from_millis(50),
+            },
+            sender.clone(),
+        );
+
+        for message in 1..=7 {
+            batcher.ack(message).await;
+        }
+        assert_eq!(
+            *sender.batches.lock().unwrap(),
+            vec![vec![1, 2, 3], vec![4, 5, 6]],
+            "Full batches should be sent right away"
+        );
+
+        tokio::time::sleep(Duration::from_millis(150)).await;
+        let batches = sender.batches.lock().unwrap().clone();
+        assert_eq!(
+            batches,
+            vec![vec![1, 2, 3], vec![4, 5, 6], vec![7]],
+            "The partial batch should be sent once it reaches its max age"
+        );
+        assert_eq!(
+            batches.into_iter().flatten().collect::<Vec<_>>(),
+            (1..=7).collect::<Vec<_>>(),
+            "Every message should be acked exactly once and in order"
+        );
+    }
+}
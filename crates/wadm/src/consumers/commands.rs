@@ -2,11 +2,15 @@
 
 use std::collections::HashMap;
 use std::pin::Pin;
+use std::sync::Arc;
 use std::task::{Context, Poll};
 
 use async_nats::{
     jetstream::{
-        consumer::pull::{Config as PullConfig, Stream as MessageStream},
+        consumer::{
+            pull::{Config as PullConfig, Stream as MessageStream},
+            PullConsumer,
+        },
         stream::Stream as JsStream,
     },
     Error as NatsError,
@@ -14,7 +18,10 @@ use async_nats::{
 use futures::{Stream, TryStreamExt};
 use tracing::{error, warn};
 
-use super::{CreateConsumer, ScopedMessage, LATTICE_METADATA_KEY, MULTITENANT_METADATA_KEY};
+use super::{
+    ConsumerStats, CreateConsumer, ReplayWindow, ScopedMessage, LATTICE_METADATA_KEY,
+    MULTITENANT_METADATA_KEY,
+};
 use crate::commands::*;
 
 /// The name of the durable NATS stream and consumer that contains incoming lattice events
@@ -23,6 +30,7 @@ pub const COMMANDS_CONSUMER_PREFIX: &str = "wadm_commands";
 /// A stream of all commands in a lattice, consumed from a durable NATS stream and consumer
 pub struct CommandConsumer {
     stream: MessageStream,
+    consumer: PullConsumer,
     lattice_id: String,
 }
 
@@ -38,6 +46,18 @@ impl CommandConsumer {
         topic: &str,
         lattice_id: &str,
         multitenant_prefix: Option<&str>,
+    ) -> Result<CommandConsumer, NatsError> {
+        CommandConsumer::new_with_batch_size(stream, topic, lattice_id, multitenant_prefix, 1).await
+    }
+
+    /// Same as [`CommandConsumer::new`], but pulls up to `batch_size` commands from the server at
+    /// a time rather than one
+    pub async fn new_with_batch_size(
+        stream: JsStream,
+        topic: &str,
+        lattice_id: &str,
+        multitenant_prefix: Option<&str>,
+        batch_size: usize,
     ) -> Result<CommandConsumer, NatsError> {
         if !topic.contains(lattice_id) {
             return Err(format!("Topic {topic} does not match for lattice ID {lattice_id}").into());
@@ -78,11 +98,12 @@ impl CommandConsumer {
             .await?;
         let messages = consumer
             .stream()
-            .max_messages_per_batch(1)
+            .max_messages_per_batch(batch_size.max(1))
             .messages()
             .await?;
         Ok(CommandConsumer {
             stream: messages,
+            consumer,
             lattice_id: lattice_id.to_owned(),
         })
     }
@@ -123,10 +144,13 @@ impl Stream for CommandConsumer {
                 // NOTE(thomastaylor312): Ideally we'd consume `msg.payload` above with a
                 // `Cursor` and `from_reader` and then manually reconstruct the acking using the
                 // message context, but I didn't want to waste time optimizing yet
+                let published = msg.info().ok().map(|info| info.published.into());
                 Poll::Ready(Some(Ok(ScopedMessage {
                     lattice_id: self.lattice_id.clone(),
                     inner: cmd,
                     acker: Some(msg),
+                    ack_batcher: None,
+                    published,
                 })))
             }
             Poll::Pending => Poll::Pending,
@@ -146,4 +170,26 @@ impl CreateConsumer for CommandConsumer {
     ) -> Result<Self::Output, NatsError> {
         CommandConsumer::new(stream, topic, lattice_id, multitenant_prefix).await
     }
+
+    async fn create_with_batch_size(
+        stream: async_nats::jetstream::stream::Stream,
+        topic: &str,
+        lattice_id: &str,
+        multitenant_prefix: Option<&str>,
+        _replay: ReplayWindow,
+        batch_size: usize,
+    ) -> Result<Self::Output, NatsError> {
+        CommandConsumer::new_with_batch_size(
+            stream,
+            topic,
+            lattice_id,
+            multitenant_prefix,
+            batch_size,
+        )
+        .await
+    }
+
+    fn stats(&self) -> Option<Arc<dyn ConsumerStats>> {
+        Some(Arc::new(self.consumer.clone()))
+    }
 }
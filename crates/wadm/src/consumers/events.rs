@@ -3,11 +3,16 @@
 use std::collections::HashMap;
 use std::convert::TryFrom;
 use std::pin::Pin;
+use std::sync::Arc;
 use std::task::{Context, Poll};
+use std::time::SystemTime;
 
 use async_nats::{
     jetstream::{
-        consumer::pull::{Config as PullConfig, Stream as MessageStream},
+        consumer::{
+            pull::{Config as PullConfig, Stream as MessageStream},
+            PullConsumer,
+        },
         stream::Stream as JsStream,
     },
     Error as NatsError,
@@ -15,7 +20,10 @@ use async_nats::{
 use futures::{Stream, TryStreamExt};
 use tracing::{debug, error, warn};
 
-use super::{CreateConsumer, ScopedMessage, LATTICE_METADATA_KEY, MULTITENANT_METADATA_KEY};
+use super::{
+    AckBatchConfig, AckBatcher, ConsumerStats, CreateConsumer, ReplayWindow, ScopedMessage,
+    LATTICE_METADATA_KEY, MULTITENANT_METADATA_KEY,
+};
 use crate::events::*;
 
 /// The name of the durable NATS stream and consumer that contains incoming lattice events
@@ -24,7 +32,9 @@ pub const EVENTS_CONSUMER_PREFIX: &str = "wadm_event_consumer";
 /// A stream of all events of a lattice, consumed from a durable NATS stream and consumer
 pub struct EventConsumer {
     stream: MessageStream,
+    consumer: PullConsumer,
     lattice_id: String,
+    ack_batcher: Option<AckBatcher>,
 }
 
 impl EventConsumer {
@@ -39,6 +49,47 @@ impl EventConsumer {
         topic: &str,
         lattice_id: &str,
         multitenant_prefix: Option<&str>,
+    ) -> Result<EventConsumer, NatsError> {
+        EventConsumer::new_with_replay(
+            stream,
+            topic,
+            lattice_id,
+            multitenant_prefix,
+            ReplayWindow::All,
+        )
+        .await
+    }
+
+    /// Same as [`new`](EventConsumer::new), but a newly created consumer only replays the given
+    /// window of the stream rather than every event in it. An existing consumer resumes where it
+    /// left off no matter the window
+    pub async fn new_with_replay(
+        stream: JsStream,
+        topic: &str,
+        lattice_id: &str,
+        multitenant_prefix: Option<&str>,
+        replay: ReplayWindow,
+    ) -> Result<EventConsumer, NatsError> {
+        EventConsumer::new_with_batch_size(
+            stream,
+            topic,
+            lattice_id,
+            multitenant_prefix,
+            replay,
+            super::DEFAULT_PRIORITY_BUFFER,
+        )
+        .await
+    }
+
+    /// Same as [`new_with_replay`](EventConsumer::new_with_replay), but pulls up to `batch_size`
+    /// messages from the server at a time
+    pub async fn new_with_batch_size(
+        mut stream: JsStream,
+        topic: &str,
+        lattice_id: &str,
+        multitenant_prefix: Option<&str>,
+        replay: ReplayWindow,
+        batch_size: usize,
     ) -> Result<EventConsumer, NatsError> {
         if !topic.contains(lattice_id) {
             return Err(format!("Topic {topic} does not match for lattice ID {lattice_id}").into());
@@ -57,6 +108,10 @@ impl EventConsumer {
                 HashMap::from([(LATTICE_METADATA_KEY.to_string(), lattice_id.to_string())]),
             )
         };
+        let last_sequence = match replay {
+            ReplayWindow::LastMessages(_) => stream.info().await?.state.last_sequence,
+            _ => 0,
+        };
         let consumer = stream
             .get_or_create_consumer(
                 &consumer_name,
@@ -69,21 +124,25 @@ impl EventConsumer {
                     ack_policy: async_nats::jetstream::consumer::AckPolicy::Explicit,
                     ack_wait: super::DEFAULT_ACK_TIME,
                     max_deliver: 3,
-                    deliver_policy: async_nats::jetstream::consumer::DeliverPolicy::All,
+                    deliver_policy: replay.deliver_policy(last_sequence, SystemTime::now()),
                     filter_subject: topic.to_owned(),
                     metadata,
                     ..Default::default()
                 },
             )
             .await?;
+        // Pull enough messages at a time for the priority lanes to have something to reorder. The
+        // lanes keep every pulled message alive while it waits, so it isn't redelivered
         let messages = consumer
             .stream()
-            .max_messages_per_batch(1)
+            .max_messages_per_batch(batch_size.max(1))
             .messages()
             .await?;
         Ok(EventConsumer {
             stream: messages,
+            consumer,
             lattice_id: lattice_id.to_owned(),
+            ack_batcher: None,
         })
     }
 }
@@ -139,10 +198,13 @@ impl Stream for EventConsumer {
                 // NOTE(thomastaylor312): Ideally we'd consume `msg.payload` above with a
                 // `Cursor` and `from_reader` and then manually reconstruct the acking using the
                 // message context, but I didn't want to waste time optimizing yet
+                let published = msg.info().ok().map(|info| info.published.into());
                 Poll::Ready(Some(Ok(ScopedMessage {
                     lattice_id: self.lattice_id.clone(),
                     inner: evt,
                     acker: Some(msg),
+                    ack_batcher: self.ack_batcher.clone(),
+                    published,
                 })))
             }
             Poll::Pending => Poll::Pending,
@@ -162,4 +224,41 @@ impl CreateConsumer for EventConsumer {
     ) -> Result<Self::Output, NatsError> {
         EventConsumer::new(stream, topic, lattice_id, multitenant_prefix).await
     }
+
+    async fn create_with_replay(
+        stream: async_nats::jetstream::stream::Stream,
+        topic: &str,
+        lattice_id: &str,
+        multitenant_prefix: Option<&str>,
+        replay: ReplayWindow,
+    ) -> Result<Self::Output, NatsError> {
+        EventConsumer::new_with_replay(stream, topic, lattice_id, multitenant_prefix, replay).await
+    }
+
+    async fn create_with_batch_size(
+        stream: async_nats::jetstream::stream::Stream,
+        topic: &str,
+        lattice_id: &str,
+        multitenant_prefix: Option<&str>,
+        replay: ReplayWindow,
+        batch_size: usize,
+    ) -> Result<Self::Output, NatsError> {
+        EventConsumer::new_with_batch_size(
+            stream,
+            topic,
+            lattice_id,
+            multitenant_prefix,
+            replay,
+            batch_size,
+        )
+        .await
+    }
+
+    fn stats(&self) -> Option<Arc<dyn ConsumerStats>> {
+        Some(Arc::new(self.consumer.clone()))
+    }
+
+    fn set_ack_batching(&mut self, config: AckBatchConfig) {
+        self.ack_batcher = Some(AckBatcher::new(config));
+    }
 }
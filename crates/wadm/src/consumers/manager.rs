@@ -2,7 +2,8 @@ use std::fmt::Debug;
 use std::{collections::HashMap, marker::PhantomData, sync::Arc};
 
 use async_nats::jetstream::stream::Stream as NatsStream;
-use futures::{Stream, StreamExt};
+use futures::{FutureExt, Stream, StreamExt};
+use metrics::gauge;
 use tokio::{
     sync::{RwLock, Semaphore},
     task::JoinHandle,
@@ -11,11 +12,13 @@ use tracing::{error, instrument, trace, warn, Instrument};
 
 use crate::consumers::{LATTICE_METADATA_KEY, MULTITENANT_METADATA_KEY};
 
-use super::{CreateConsumer, ScopedMessage};
+use super::{AckBatchConfig, ConsumerStats, CreateConsumer, ReplayWindow, ScopedMessage};
 
 /// A convenience type for returning work results
 pub type WorkResult<T> = Result<T, WorkError>;
 type WorkHandles = Arc<RwLock<HashMap<String, JoinHandle<WorkResult<()>>>>>;
+/// Consumer stats keyed by topic, along with the lattice ID the consumer is for
+type StatsHandles = Arc<RwLock<HashMap<String, (String, Arc<dyn ConsumerStats>)>>>;
 
 /// An error that describes possible work failures when performing actions based on incoming messages
 #[derive(Debug, thiserror::Error)]
@@ -68,6 +71,24 @@ pub trait Worker {
     /// that work should stop. Any worker MUST handle acking the message (or passing it to another
     /// worker). By default, when a [`ScopedMessage`] is dropped, it will nack it
     async fn do_work(&self, message: ScopedMessage<Self::Message>) -> WorkResult<()>;
+
+    /// The most messages that can be handed to [`do_batch`](Worker::do_batch) at once. Messages
+    /// that are already waiting in the consumer are only batched together when this is more than 1
+    fn max_batch_size(&self) -> usize {
+        1
+    }
+
+    /// Process a batch of messages that were all waiting in the consumer at the same time,
+    /// returning the result for each message in the same order. Each message still has to be
+    /// acked on its own. By default, the messages are handled one after another with
+    /// [`do_work`](Worker::do_work)
+    async fn do_batch(&self, messages: Vec<ScopedMessage<Self::Message>>) -> Vec<WorkResult<()>> {
+        let mut results = Vec::with_capacity(messages.len());
+        for message in messages {
+            results.push(self.do_work(message).await);
+        }
+        results
+    }
 }
 
 /// A trait used for dynamically creating workers.
@@ -93,14 +114,33 @@ pub trait WorkerCreator {
 ///
 /// NOTE: We use a work permit semaphore pool here to make sure in large, multi-tenant deployments,
 /// we aren't trying to simultaneously handle every single lattice event and command consumer
-#[derive(Clone)]
 pub struct ConsumerManager<C> {
     handles: WorkHandles,
+    stats: StatsHandles,
     permits: Arc<Semaphore>,
     stream: NatsStream,
+    replay: ReplayWindow,
+    ack_batch: Option<AckBatchConfig>,
+    priority_window: Option<usize>,
     phantom: PhantomData<C>,
 }
 
+// NOTE: Implemented manually so the consumer type doesn't need to be `Clone`
+impl<C> Clone for ConsumerManager<C> {
+    fn clone(&self) -> Self {
+        ConsumerManager {
+            handles: self.handles.clone(),
+            stats: self.stats.clone(),
+            permits: self.permits.clone(),
+            stream: self.stream.clone(),
+            replay: self.replay,
+            ack_batch: self.ack_batch,
+            priority_window: self.priority_window,
+            phantom: PhantomData,
+        }
+    }
+}
+
 impl<C> ConsumerManager<C> {
     /// Returns a new consumer manager set up to use the given permit pool. This meant to use a
     /// shared pool of permits with other consumer managers to manage the amount of simultaneous
@@ -115,6 +155,68 @@ impl<C> ConsumerManager<C> {
         worker_generator: F,
         multitenant: bool,
     ) -> ConsumerManager<C>
+    where
+        W: Worker + Send + Sync + 'static,
+        C: Stream<Item = Result<ScopedMessage<W::Message>, async_nats::Error>>
+            + CreateConsumer<Output = C>
+            + Send
+            + Unpin
+            + 'static,
+        F: WorkerCreator<Output = W>,
+    {
+        ConsumerManager::new_with_ack_batching(
+            permit_pool,
+            stream,
+            worker_generator,
+            multitenant,
+            None,
+        )
+        .await
+    }
+
+    /// Same as [`new`](ConsumerManager::new), but every consumer started by this manager (including
+    /// the existing ones it is populated with) acks messages in batches with the given limits. This
+    /// cuts down on round trips to NATS under heavy load. Nacks are still sent right away
+    pub async fn new_with_ack_batching<W, F>(
+        permit_pool: Arc<Semaphore>,
+        stream: NatsStream,
+        worker_generator: F,
+        multitenant: bool,
+        ack_batch: Option<AckBatchConfig>,
+    ) -> ConsumerManager<C>
+    where
+        W: Worker + Send + Sync + 'static,
+        C: Stream<Item = Result<ScopedMessage<W::Message>, async_nats::Error>>
+            + CreateConsumer<Output = C>
+            + Send
+            + Unpin
+            + 'static,
+        F: WorkerCreator<Output = W>,
+    {
+        ConsumerManager::new_with_priority_window(
+            permit_pool,
+            stream,
+            worker_generator,
+            multitenant,
+            ack_batch,
+            None,
+        )
+        .await
+    }
+
+    /// Same as [`new_with_ack_batching`](ConsumerManager::new_with_ack_batching), but every
+    /// consumer started by this manager pulls `priority_window` messages at a time, which is also
+    /// how many waiting messages a [`PriorityLanes`](super::PriorityLanes) consumer looks through
+    /// for one that can skip ahead. Otherwise consumers pull as many messages as their worker
+    /// handles in a batch
+    pub async fn new_with_priority_window<W, F>(
+        permit_pool: Arc<Semaphore>,
+        stream: NatsStream,
+        worker_generator: F,
+        multitenant: bool,
+        ack_batch: Option<AckBatchConfig>,
+        priority_window: Option<usize>,
+    ) -> ConsumerManager<C>
     where
         W: Worker + Send + Sync + 'static,
         C: Stream<Item = Result<ScopedMessage<W::Message>, async_nats::Error>>
@@ -126,8 +228,12 @@ impl<C> ConsumerManager<C> {
     {
         let mut manager = ConsumerManager {
             handles: Arc::new(RwLock::new(HashMap::default())),
+            stats: Arc::new(RwLock::new(HashMap::default())),
             permits: permit_pool,
             stream,
+            replay: ReplayWindow::All,
+            ack_batch,
+            priority_window,
             phantom: PhantomData,
         };
 
@@ -196,6 +302,13 @@ impl<C> ConsumerManager<C> {
         manager
     }
 
+    /// Sets how much of the stream consumers created by this manager replay. This only affects
+    /// consumers that don't exist yet, as existing durable consumers resume where they left off
+    pub fn with_replay_window(mut self, replay: ReplayWindow) -> Self {
+        self.replay = replay;
+        self
+    }
+
     /// Starts a new consumer for the given topic. This method will only fail if there was an error
     /// setting up the consumer.
     ///
@@ -242,12 +355,39 @@ impl<C> ConsumerManager<C> {
             + Unpin
             + 'static,
     {
-        let consumer =
-            C::create(self.stream.clone(), topic, lattice_id, multitenant_prefix).await?;
+        let mut consumer = C::create_with_batch_size(
+            self.stream.clone(),
+            topic,
+            lattice_id,
+            multitenant_prefix,
+            self.replay,
+            self.priority_window
+                .unwrap_or_default()
+                .max(worker.max_batch_size()),
+        )
+        .await?;
+        if let Some(config) = self.ack_batch {
+            consumer.set_ack_batching(config);
+        }
+        if let Some(stats) = consumer.stats() {
+            self.stats
+                .write()
+                .await
+                .insert(topic.to_owned(), (lattice_id.to_owned(), stats));
+        }
         let permits = self.permits.clone();
-        Ok(tokio::spawn(work_fn(consumer, permits, worker).instrument(
-            tracing::info_span!("consumer_worker", %topic, worker_type = %std::any::type_name::<W>()),
-        )))
+        let stats = self.stats.clone();
+        let stats_topic = topic.to_owned();
+        Ok(tokio::spawn(
+            async move {
+                let res = work_fn(consumer, permits, worker).await;
+                remove_stats(&stats, &stats_topic).await;
+                res
+            }
+            .instrument(
+                tracing::info_span!("consumer_worker", %topic, worker_type = %std::any::type_name::<W>()),
+            ),
+        ))
     }
 
     /// Checks if this manager has a consumer for the given topic. Returns `false` if it doesn't
@@ -267,6 +407,26 @@ impl<C> ConsumerManager<C> {
             .unwrap_or(false)
     }
 
+    /// Returns the number of messages that have yet to be handled for each lattice, keyed by
+    /// lattice ID. A consistently high or growing number means wadm isn't keeping up with the
+    /// stream. Any consumer that can't report its stats is skipped
+    pub async fn consumer_lag(&self) -> HashMap<String, u64> {
+        let stats = self
+            .stats
+            .read()
+            .await
+            .values()
+            .cloned()
+            .collect::<Vec<_>>();
+        lag_per_lattice(stats).await
+    }
+
+    /// Records the [`consumer_lag`](Self::consumer_lag) of every lattice to the
+    /// `wadm_event_consumer_pending` gauge, tagged with the lattice ID
+    pub async fn record_consumer_lag(&self) {
+        record_lag(self.consumer_lag().await);
+    }
+
     // NOTE(thomastaylor312): We could add a supervisory element to this by starting a notifier
     // thread that can restart work if a fatal error is received (or a join handle finishes), but
     // that is not necessary now
@@ -274,10 +434,11 @@ impl<C> ConsumerManager<C> {
 
 async fn work_fn<C, W>(mut consumer: C, permits: Arc<Semaphore>, worker: W) -> WorkResult<()>
 where
-    W: Worker + Send,
+    W: Worker + Send + Sync,
     C: Stream<Item = Result<ScopedMessage<W::Message>, async_nats::Error>> + Unpin,
 {
-    loop {
+    let mut stopped = false;
+    while !stopped {
         // Get next value from stream, returning error if the consumer stopped
         let res = consumer.next().await.ok_or(WorkError::ConsumerStopped)?;
 
@@ -285,24 +446,92 @@ where
         trace!("Getting work permit");
         let _permit = permits.acquire().await?;
         trace!("Received work permit, attempting to pull from consumer");
-        let res = match res {
+        let mut batch = match res {
             Ok(msg) => {
                 trace!(message = ?msg, "Got message from consumer");
-                worker.do_work(msg).await
+                vec![msg]
             }
             Err(e) => {
                 error!(error = %e, "Got error from stream when reading from consumer. Will try again");
                 continue;
             }
         };
+        // Pick up any other messages that are already waiting, for workers that can handle them
+        // together
+        while batch.len() < worker.max_batch_size() {
+            match consumer.next().now_or_never() {
+                Some(Some(Ok(msg))) => {
+                    trace!(message = ?msg, "Got message from consumer");
+                    batch.push(msg);
+                }
+                Some(Some(Err(e))) => {
+                    error!(error = %e, "Got error from stream when batching messages");
+                    break;
+                }
+                Some(None) => {
+                    stopped = true;
+                    break;
+                }
+                None => break,
+            }
+        }
+        // Work can wait on things like the command rate limit or a command batch, so keep the
+        // messages from being redelivered while it runs
+        let _keep_alive = batch
+            .iter()
+            .filter_map(ScopedMessage::keep_alive)
+            .collect::<Vec<_>>();
+        let results = if batch.len() == 1 {
+            vec![worker.do_work(batch.remove(0)).await]
+        } else {
+            worker.do_batch(batch).await
+        };
+        for res in results {
+            match res {
+                // Return fatal errors if they occur
+                Err(e) if matches!(e, WorkError::Fatal(_)) => return Err(e),
+                // For the rest of the errors, right now we just log. Could do nicer retry behavior as this evolves
+                Err(e) => error!(error = ?e, "Got error from worker"),
+                _ => (),
+            }
+        }
+    }
+    Err(WorkError::ConsumerStopped)
+}
+
+/// Stops reporting lag for the consumer of the given topic once it has stopped. If it was the last
+/// consumer for its lattice, the lattice's lag is reset so a stale value isn't left behind
+async fn remove_stats(stats: &StatsHandles, topic: &str) {
+    let mut stats = stats.write().await;
+    let Some((lattice_id, _)) = stats.remove(topic) else {
+        return;
+    };
+    if !stats.values().any(|(id, _)| *id == lattice_id) {
+        gauge!("wadm_event_consumer_pending", "lattice" => lattice_id).set(0.0);
+    }
+}
+
+fn record_lag(lag: HashMap<String, u64>) {
+    for (lattice_id, pending) in lag {
+        gauge!("wadm_event_consumer_pending", "lattice" => lattice_id).set(pending as f64);
+    }
+}
+
+async fn lag_per_lattice(stats: Vec<(String, Arc<dyn ConsumerStats>)>) -> HashMap<String, u64> {
+    let results = futures::future::join_all(
+        stats
+            .iter()
+            .map(|(lattice_id, stats)| async move { (lattice_id, stats.pending().await) }),
+    )
+    .await;
+    let mut lag = HashMap::new();
+    for (lattice_id, res) in results {
         match res {
-            // Return fatal errors if they occur
-            Err(e) if matches!(e, WorkError::Fatal(_)) => return Err(e),
-            // For the rest of the errors, right now we just log. Could do nicer retry behavior as this evolves
-            Err(e) => error!(error = ?e, "Got error from worker"),
-            _ => (),
+            Ok(pending) => *lag.entry(lattice_id.to_owned()).or_default() += pending,
+            Err(e) => warn!(error = %e, %lattice_id, "Unable to fetch consumer stats"),
         }
     }
+    lag
 }
 
 /// Extracts the lattice ID and multitenant prefix from a consumer name in the form of either:
@@ -325,7 +554,188 @@ fn extract_lattice_and_multitenant(consumer_name: &str) -> (Option<String>, Opti
 
 #[cfg(test)]
 mod test {
-    use super::extract_lattice_and_multitenant;
+    use std::sync::Arc;
+
+    use tokio::sync::{Mutex, RwLock, Semaphore};
+
+    use super::{
+        extract_lattice_and_multitenant, lag_per_lattice, record_lag, remove_stats, work_fn,
+        ConsumerStats, StatsHandles, WorkError, WorkResult, Worker,
+    };
+    use crate::consumers::ScopedMessage;
+
+    /// Consumer stats that always report the same pending count, or an error if there is none
+    struct MockStats(Option<u64>);
+
+    #[async_trait::async_trait]
+    impl ConsumerStats for MockStats {
+        async fn pending(&self) -> Result<u64, async_nats::Error> {
+            self.0.ok_or_else(|| "consumer is unavailable".into())
+        }
+    }
+
+    #[tokio::test]
+    async fn reports_pending_count_as_lag() {
+        let stats: Vec<(String, Arc<dyn ConsumerStats>)> = vec![
+            ("default".to_string(), Arc::new(MockStats(Some(42)))),
+            ("other".to_string(), Arc::new(MockStats(Some(0)))),
+            ("other".to_string(), Arc::new(MockStats(Some(3)))),
+            ("broken".to_string(), Arc::new(MockStats(None))),
+        ];
+
+        let lag = lag_per_lattice(stats).await;
+        assert_eq!(lag.get("default"), Some(&42));
+        assert_eq!(
+            lag.get("other"),
+            Some(&3),
+            "Lag should be summed across all consumers for a lattice"
+        );
+        assert!(
+            !lag.contains_key("broken"),
+            "Consumers that can't report stats should be skipped"
+        );
+
+        let handles: StatsHandles = Arc::new(RwLock::new(
+            [
+                ("events.default", "default", 42),
+                ("events.other", "other", 3),
+            ]
+            .into_iter()
+            .map(|(topic, lattice_id, pending)| {
+                let stats: Arc<dyn ConsumerStats> = Arc::new(MockStats(Some(pending)));
+                (topic.to_string(), (lattice_id.to_string(), stats))
+            })
+            .collect(),
+        ));
+        remove_stats(&handles, "events.default").await;
+        let remaining = handles.read().await.values().cloned().collect();
+        let lag = lag_per_lattice(remaining).await;
+        assert!(
+            !lag.contains_key("default"),
+            "Lag should no longer be reported for a lattice once its consumer stops"
+        );
+        assert_eq!(lag.get("other"), Some(&3));
+    }
+
+    #[test]
+    fn records_lag_to_gauge_and_clears_it_when_consumer_stops() {
+        use metrics::{Key, Label};
+        use metrics_util::{
+            debugging::{DebugValue, DebuggingRecorder},
+            CompositeKey, MetricKind,
+        };
+
+        let recorder = DebuggingRecorder::new();
+        let snapshotter = recorder.snapshotter();
+        let gauge = |lattice_id: &'static str| {
+            let snapshot = snapshotter.snapshot().into_hashmap();
+            snapshot
+                .get(&CompositeKey::new(
+                    MetricKind::Gauge,
+                    Key::from_parts(
+                        "wadm_event_consumer_pending",
+                        vec![Label::new("lattice", lattice_id)],
+                    ),
+                ))
+                .and_then(|(_, _, value)| match value {
+                    DebugValue::Gauge(value) => Some(value.into_inner()),
+                    _ => None,
+                })
+        };
+
+        let handles: StatsHandles = Arc::new(RwLock::new(
+            [("events.default".to_string(), {
+                let stats: Arc<dyn ConsumerStats> = Arc::new(MockStats(Some(42)));
+                ("default".to_string(), stats)
+            })]
+            .into_iter()
+            .collect(),
+        ));
+        metrics::with_local_recorder(&recorder, || {
+            tokio::runtime::Builder::new_current_thread()
+                .build()
+                .unwrap()
+                .block_on(async {
+                    let stats = handles.read().await.values().cloned().collect();
+                    record_lag(lag_per_lattice(stats).await);
+                })
+        });
+        assert_eq!(
+            gauge("default"),
+            Some(42.0),
+            "Lag should be recorded to the gauge for the lattice"
+        );
+
+        metrics::with_local_recorder(&recorder, || {
+            tokio::runtime::Builder::new_current_thread()
+                .build()
+                .unwrap()
+                .block_on(remove_stats(&handles, "events.default"))
+        });
+        assert_eq!(
+            gauge("default"),
+            Some(0.0),
+            "Gauge should be zeroed once the last consumer for a lattice stops"
+        );
+    }
+
+    /// A worker that handles messages in batches, recording the messages in each batch
+    #[derive(Default)]
+    struct BatchRecorder {
+        batches: Arc<Mutex<Vec<Vec<usize>>>>,
+    }
+
+    #[async_trait::async_trait]
+    impl Worker for BatchRecorder {
+        type Message = usize;
+
+        async fn do_work(&self, message: ScopedMessage<usize>) -> WorkResult<()> {
+            self.batches.lock().await.push(vec![message.inner]);
+            Ok(())
+        }
+
+        fn max_batch_size(&self) -> usize {
+            3
+        }
+
+        async fn do_batch(&self, messages: Vec<ScopedMessage<usize>>) -> Vec<WorkResult<()>> {
+            self.batches
+                .lock()
+                .await
+                .push(messages.iter().map(|message| message.inner).collect());
+            messages.iter().map(|_| Ok(())).collect()
+        }
+    }
+
+    #[tokio::test]
+    async fn waiting_messages_are_handled_in_batches() {
+        let messages = (0..7)
+            .map(|inner| {
+                Ok(ScopedMessage {
+                    lattice_id: "default".to_string(),
+                    inner,
+                    acker: None,
+                    ack_batcher: None,
+                    published: None,
+                })
+            })
+            .collect::<Vec<Result<_, async_nats::Error>>>();
+        let worker = BatchRecorder::default();
+        let batches = worker.batches.clone();
+
+        let res = work_fn(
+            futures::stream::iter(messages),
+            Arc::new(Semaphore::new(1)),
+            worker,
+        )
+        .await;
+        assert!(matches!(res, Err(WorkError::ConsumerStopped)));
+        assert_eq!(
+            *batches.lock().await,
+            vec![vec![0, 1, 2], vec![3, 4, 5], vec![6]],
+            "Waiting messages should be batched up to the worker's batch size"
+        );
+    }
 
     #[test]
     fn can_extract_lattice_and_multitenant() {
@@ -3,15 +3,21 @@
 
 use std::fmt::Debug;
 use std::ops::{Deref, DerefMut};
-use std::time::Duration;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
-use async_nats::jetstream::{AckKind, Message};
+use async_nats::jetstream::{
+    consumer::{DeliverPolicy, PullConsumer},
+    AckKind, Message,
+};
 use async_nats::Error as NatsError;
 use tracing::{error, warn};
 
+mod ack;
 mod commands;
 mod events;
 pub mod manager;
+mod priority;
 
 /// The default time given for a command to ack. This is longer than events due to the possible need for more processing time
 pub const DEFAULT_ACK_TIME: Duration = Duration::from_secs(2);
@@ -19,8 +25,14 @@ pub const DEFAULT_ACK_TIME: Duration = Duration::from_secs(2);
 pub const LATTICE_METADATA_KEY: &str = "lattice";
 pub const MULTITENANT_METADATA_KEY: &str = "multitenant_prefix";
 
+/// How often a message that is kept alive tells the server it is still being worked on. This is
+/// well within [`DEFAULT_ACK_TIME`] so a single slow progress ack doesn't cause a redelivery
+const KEEP_ALIVE_INTERVAL: Duration = Duration::from_millis(500);
+
+pub use ack::*;
 pub use commands::*;
 pub use events::*;
+pub use priority::*;
 
 /// An message that is scoped to a specific lattice. This allows to distinguish between items
 /// from different lattices and to handle acking. It can support any inner type.
@@ -37,16 +49,32 @@ pub struct ScopedMessage<T> {
     pub(crate) inner: T,
     // Wrapped in an option so we only do it once
     pub(crate) acker: Option<Message>,
+    // Where acks are queued when they are sent in batches. Nacks are always sent right away
+    pub(crate) ack_batcher: Option<AckBatcher>,
+    // When the message was originally published to the stream, if known. This doesn't change
+    // when the message is redelivered
+    pub(crate) published: Option<SystemTime>,
 }
 
 impl<T> ScopedMessage<T> {
+    /// Returns when this message was originally published to the stream, if known
+    pub fn published(&self) -> Option<SystemTime> {
+        self.published
+    }
+
     /// Acks this Event. This should be called when all work related to this event has been
     /// completed. If this is called before work is done (e.g. like sending a command), instability
     /// could occur. Calling this function again (or after nacking) is a noop.
     ///
     /// This function will only error after it has tried up to 3 times to ack the request. If it
-    /// doesn't receive a response after those 3 times, this will return an error.
+    /// doesn't receive a response after those 3 times, this will return an error. If acks are
+    /// batched, the ack is only queued and this never errors. Failing to send the batch means the
+    /// message is redelivered.
     pub async fn ack(&mut self) -> Result<(), NatsError> {
+        if let (Some(batcher), Some(msg)) = (&self.ack_batcher, self.acker.take()) {
+            batcher.ack(msg).await;
+            return Ok(());
+        }
         // We want to double ack so we are sure that the server has marked this task as done
         if let Some(msg) = self.acker.take() {
             // Starting at 1 for humans/logging
@@ -87,6 +115,24 @@ impl<T> ScopedMessage<T> {
         }
     }
 
+    /// Keeps the server from redelivering this message while it waits to be handled or while work
+    /// on it takes longer than its ack deadline. In progress acks are sent until the returned
+    /// guard is dropped. Returns None for messages that can't be acked
+    pub fn keep_alive(&self) -> Option<KeepAlive> {
+        let msg = self.acker.clone()?;
+        Some(KeepAlive(tokio::spawn(async move {
+            let mut interval = tokio::time::interval(KEEP_ALIVE_INTERVAL);
+            // The first tick completes right away and the message was just delivered
+            interval.tick().await;
+            loop {
+                interval.tick().await;
+                if let Err(e) = msg.ack_with(AckKind::Progress).await {
+                    warn!(error = %e, "Unable to send in progress ack, message may be redelivered");
+                }
+            }
+        })))
+    }
+
     /// Nacks this Event. This should be called if there was an error when processing. By default,
     /// this is called when a [`ScopedMessage`] is dropped. Calling this again is a noop.
     ///
@@ -100,6 +146,16 @@ impl<T> ScopedMessage<T> {
     }
 }
 
+/// Sends in progress acks for a [`ScopedMessage`] until it is dropped. See
+/// [`ScopedMessage::keep_alive`]
+pub struct KeepAlive(tokio::task::JoinHandle<()>);
+
+impl Drop for KeepAlive {
+    fn drop(&mut self) {
+        self.0.abort();
+    }
+}
+
 impl<T> Drop for ScopedMessage<T> {
     fn drop(&mut self) {
         // self.nack escapes current lifetime, so just manually take the message
@@ -152,6 +208,40 @@ impl<T: Debug> Debug for ScopedMessage<T> {
     }
 }
 
+/// How much of the existing stream a newly created consumer replays. Durable consumers that
+/// already exist always pick up where they left off, so this only applies the first time a consumer
+/// is created for a lattice (or after it has been deleted)
+///
+/// Replaying less of the stream makes startup faster at the cost of state completeness, as anything
+/// that happened before the window won't be reflected in the rebuilt state
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ReplayWindow {
+    /// Replay the whole stream from the beginning, fully rebuilding state
+    #[default]
+    All,
+    /// Replay only the last N messages in the stream
+    LastMessages(u64),
+    /// Replay only the messages received within the given duration of now
+    Since(Duration),
+}
+
+impl ReplayWindow {
+    /// Returns the deliver policy for a new consumer on a stream whose last message has the given
+    /// sequence number
+    pub fn deliver_policy(&self, last_sequence: u64, now: SystemTime) -> DeliverPolicy {
+        match self {
+            ReplayWindow::All => DeliverPolicy::All,
+            // Stream sequences start at 1, so the last N messages start at `last - N + 1`
+            ReplayWindow::LastMessages(count) => DeliverPolicy::ByStartSequence {
+                start_sequence: last_sequence.saturating_sub(*count).saturating_add(1),
+            },
+            ReplayWindow::Since(window) => DeliverPolicy::ByStartTime {
+                start_time: now.checked_sub(*window).unwrap_or(UNIX_EPOCH).into(),
+            },
+        }
+    }
+}
+
 /// A helper trait to allow for constructing any consumer
 #[async_trait::async_trait]
 pub trait CreateConsumer {
@@ -164,4 +254,136 @@ pub trait CreateConsumer {
         lattice_id: &str,
         multitenant_prefix: Option<&str>,
     ) -> Result<Self::Output, NatsError>;
+
+    /// Same as [`create`](CreateConsumer::create), but only replays the given window of the stream
+    /// if the underlying consumer has to be created. Consumers that don't support replay windows
+    /// ignore it and replay everything
+    async fn create_with_replay(
+        stream: async_nats::jetstream::stream::Stream,
+        topic: &str,
+        lattice_id: &str,
+        multitenant_prefix: Option<&str>,
+        _replay: ReplayWindow,
+    ) -> Result<Self::Output, NatsError> {
+        Self::create(stream, topic, lattice_id, multitenant_prefix).await
+    }
+
+    /// Same as [`create_with_replay`](CreateConsumer::create_with_replay), but pulls up to
+    /// `batch_size` messages from the server at a time, so that enough of them are waiting for a
+    /// worker that handles messages in batches (see
+    /// [`Worker::max_batch_size`](manager::Worker::max_batch_size)). Consumers that don't support
+    /// it ignore the batch size
+    async fn create_with_batch_size(
+        stream: async_nats::jetstream::stream::Stream,
+        topic: &str,
+        lattice_id: &str,
+        multitenant_prefix: Option<&str>,
+        replay: ReplayWindow,
+        _batch_size: usize,
+    ) -> Result<Self::Output, NatsError> {
+        Self::create_with_replay(stream, topic, lattice_id, multitenant_prefix, replay).await
+    }
+
+    /// Returns a handle that can be used to query statistics about the underlying durable
+    /// consumer, if the consumer supports it. The handle stays valid after the consumer has been
+    /// moved into a worker
+    fn stats(&self) -> Option<Arc<dyn ConsumerStats>> {
+        None
+    }
+
+    /// Configures the consumer to batch the acks of the messages it returns with the given limits.
+    /// Consumers that don't support batched acks ignore it and ack each message on its own
+    fn set_ack_batching(&mut self, _config: AckBatchConfig) {}
+}
+
+/// A trait for fetching statistics about a durable consumer
+#[async_trait::async_trait]
+pub trait ConsumerStats: Send + Sync {
+    /// Returns the number of messages that have not been handled by the consumer yet. This
+    /// includes messages that haven't been delivered yet as well as messages that were delivered
+    /// but haven't been acked
+    async fn pending(&self) -> Result<u64, NatsError>;
+}
+
+#[async_trait::async_trait]
+impl ConsumerStats for PullConsumer {
+    async fn pending(&self) -> Result<u64, NatsError> {
+        let mut consumer = self.clone();
+        let info = consumer.info().await?;
+        Ok(info.num_pending + info.num_ack_pending as u64)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// Returns the sequence numbers of the given `(sequence, timestamp)` messages that JetStream
+    /// would deliver to a new consumer with the given policy
+    fn replayed(policy: DeliverPolicy, messages: &[(u64, SystemTime)]) -> Vec<u64> {
+        messages
+            .iter()
+            .filter(|(seq, timestamp)| match &policy {
+                DeliverPolicy::All => true,
+                DeliverPolicy::ByStartSequence { start_sequence } => seq >= start_sequence,
+                DeliverPolicy::ByStartTime { start_time } => {
+                    time_of(*timestamp) >= time_of((*start_time).into())
+                }
+                other => panic!("Unexpected deliver policy {other:?}"),
+            })
+            .map(|(seq, _)| *seq)
+            .collect()
+    }
+
+    fn time_of(timestamp: SystemTime) -> Duration {
+        timestamp.duration_since(UNIX_EPOCH).unwrap()
+    }
+
+    #[test]
+    fn only_replays_events_within_window() {
+        let now = SystemTime::now();
+        // One event a minute for the last 10 minutes, with the newest last
+        let messages = (1..=10)
+            .map(|seq| (seq, now - Duration::from_secs(60 * (10 - seq))))
+            .collect::<Vec<_>>();
+        let last_sequence = 10;
+
+        assert_eq!(
+            replayed(
+                ReplayWindow::All.deliver_policy(last_sequence, now),
+                &messages
+            ),
+            (1..=10).collect::<Vec<_>>(),
+            "A full rebuild should replay every event"
+        );
+        assert_eq!(
+            replayed(
+                ReplayWindow::LastMessages(3).deliver_policy(last_sequence, now),
+                &messages
+            ),
+            vec![8, 9, 10],
+            "Only the last 3 events should be replayed"
+        );
+        assert_eq!(
+            replayed(
+                ReplayWindow::LastMessages(100).deliver_policy(last_sequence, now),
+                &messages
+            ),
+            (1..=10).collect::<Vec<_>>(),
+            "A window larger than the stream should replay every event"
+        );
+        assert_eq!(
+            replayed(
+                ReplayWindow::Since(Duration::from_secs(150)).deliver_policy(last_sequence, now),
+                &messages
+            ),
+            vec![8, 9, 10],
+            "Only events from the last 2.5 minutes should be replayed"
+        );
+        assert_eq!(
+            ReplayWindow::LastMessages(5).deliver_policy(0, now),
+            DeliverPolicy::ByStartSequence { start_sequence: 1 },
+            "An empty stream should start from the first message"
+        );
+    }
 }
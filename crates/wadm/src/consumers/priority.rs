@@ -0,0 +1,374 @@
+//! A consumer adapter that lets more important messages skip ahead of buffered messages they don't
+//! depend on
+
+use std::collections::VecDeque;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+
+use async_nats::Error as NatsError;
+use futures::{Stream, StreamExt};
+
+use super::{
+    AckBatchConfig, ConsumerStats, CreateConsumer, KeepAlive, ReplayWindow, ScopedMessage,
+};
+use crate::events::Event;
+
+/// The default number of buffered messages a [`PriorityLanes`] consumer looks through for a more
+/// important message. This is also the default number of messages the event consumer pulls at a
+/// time
+pub const DEFAULT_PRIORITY_BUFFER: usize = 10;
+
+/// The lane a message is processed in. A message can only skip ahead of buffered messages in a
+/// lower priority lane that it doesn't conflict with
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Priority {
+    Normal,
+    Low,
+}
+
+/// Any message type that can be sorted into a priority lane
+pub trait Prioritized {
+    fn priority(&self) -> Priority;
+
+    /// Returns whether handling this message before `earlier`, which was received first, could
+    /// change the outcome of handling them. Conflicting messages are never reordered
+    fn conflicts_with(&self, earlier: &Self) -> bool;
+}
+
+impl Prioritized for Event {
+    fn priority(&self) -> Priority {
+        match self {
+            // Health checks are sent frequently and only ever update the status of a provider
+            Event::ProviderHealthCheckPassed(_)
+            | Event::ProviderHealthCheckFailed(_)
+            | Event::ProviderHealthCheckStatus(_) => Priority::Low,
+            _ => Priority::Normal,
+        }
+    }
+
+    fn conflicts_with(&self, earlier: &Self) -> bool {
+        // Only health checks are ever skipped, and only by events for a different host and a
+        // different provider. Everything else, like a heartbeat followed by a lifecycle event for
+        // the same host, has to be handled in the order it happened
+        match (health_check_target(earlier), event_target(self)) {
+            (Some((provider_id, host_id)), Some((other_host, other_provider))) => {
+                host_id == other_host || Some(provider_id) == other_provider
+            }
+            _ => true,
+        }
+    }
+}
+
+/// Returns the provider and host IDs of a health check event
+fn health_check_target(event: &Event) -> Option<(&str, &str)> {
+    match event {
+        Event::ProviderHealthCheckPassed(e) => Some((&e.data.provider_id, &e.data.host_id)),
+        Event::ProviderHealthCheckFailed(e) => Some((&e.data.provider_id, &e.data.host_id)),
+        Event::ProviderHealthCheckStatus(e) => Some((&e.data.provider_id, &e.data.host_id)),
+        _ => None,
+    }
+}
+
+/// Returns the host an event is about and the provider it is about, if any. Events that aren't
+/// about a single host return None
+fn event_target(event: &Event) -> Option<(&str, Option<&str>)> {
+    match event {
+        Event::HostStarted(e) => Some((&e.id, None)),
+        Event::HostStopped(e) => Some((&e.id, None)),
+        Event::HostHeartbeat(e) => Some((&e.host_id, None)),
+        Event::HostReaped(e) => Some((&e.host_id, None)),
+        Event::ComponentScaled(e) => Some((&e.host_id, None)),
+        Event::ComponentScaleFailed(e) => Some((&e.host_id, None)),
+        Event::ProviderStarted(e) => Some((&e.host_id, Some(&e.provider_id))),
+        Event::ProviderStopped(e) => Some((&e.host_id, Some(&e.provider_id))),
+        Event::ProviderStartFailed(e) => Some((&e.host_id, Some(&e.provider_id))),
+        _ => health_check_target(event).map(|(provider_id, host_id)| (host_id, Some(provider_id))),
+    }
+}
+
+/// A message waiting in a [`PriorityLanes`] buffer, which is kept from being redelivered while it
+/// waits
+struct Buffered<T> {
+    msg: ScopedMessage<T>,
+    _keep_alive: Option<KeepAlive>,
+}
+
+/// A consumer that wraps another consumer and buffers every message that is ready, returning the
+/// buffered message with the highest [`Priority`] that doesn't conflict with any message received
+/// before it. Otherwise messages are returned in the order they were received
+pub struct PriorityLanes<C, T> {
+    inner: C,
+    buffer: VecDeque<Buffered<T>>,
+    window: usize,
+    inner_done: bool,
+}
+
+impl<C, T> PriorityLanes<C, T> {
+    /// Wraps the given consumer, looking through at most `window` buffered messages for one that
+    /// can skip ahead. A value of 1 (or 0) disables reordering entirely
+    pub fn new(inner: C, window: usize) -> PriorityLanes<C, T> {
+        PriorityLanes {
+            inner,
+            buffer: VecDeque::new(),
+            window: window.max(1),
+            inner_done: false,
+        }
+    }
+}
+
+impl<C, T: Prioritized> PriorityLanes<C, T> {
+    /// Returns the index of the next message to handle
+    fn next_index(&self) -> Option<usize> {
+        let mut next: Option<(usize, Priority)> = None;
+        for (idx, candidate) in self.buffer.iter().take(self.window).enumerate() {
+            let priority = candidate.msg.priority();
+            if next.is_some_and(|(_, best)| best <= priority) {
+                continue;
+            }
+            let can_skip = self.buffer.iter().take(idx).all(|earlier| {
+                earlier.msg.priority() > priority && !candidate.msg.conflicts_with(&earlier.msg)
+            });
+            if can_skip {
+                next = Some((idx, priority));
+            }
+        }
+        next.map(|(idx, _)| idx)
+    }
+}
+
+impl<C, T> Stream for PriorityLanes<C, T>
+where
+    C: Stream<Item = Result<ScopedMessage<T>, NatsError>> + Unpin,
+    T: Prioritized + Unpin,
+{
+    type Item = Result<ScopedMessage<T>, NatsError>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        // Pull everything that has already been delivered, up to the window. Its ack deadline is
+        // already running, so it is better off buffered here where it is kept alive
+        while !this.inner_done && this.buffer.len() < this.window {
+            match this.inner.poll_next_unpin(cx) {
+                Poll::Ready(Some(Ok(msg))) => {
+                    let keep_alive = msg.keep_alive();
+                    this.buffer.push_back(Buffered {
+                        msg,
+                        _keep_alive: keep_alive,
+                    });
+                }
+                // Errors aren't tied to a message, so there is no reason to hold them back
+                Poll::Ready(Some(Err(e))) => return Poll::Ready(Some(Err(e))),
+                Poll::Ready(None) => this.inner_done = true,
+                Poll::Pending => break,
+            }
+        }
+
+        if let Some(buffered) = this.next_index().and_then(|idx| this.buffer.remove(idx)) {
+            return Poll::Ready(Some(Ok(buffered.msg)));
+        }
+
+        if this.inner_done {
+            Poll::Ready(None)
+        } else {
+            Poll::Pending
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl<C, T> CreateConsumer for PriorityLanes<C, T>
+where
+    C: CreateConsumer<Output = C> + Send + Unpin,
+    T: Unpin,
+{
+    type Output = PriorityLanes<C, T>;
+
+    async fn create(
+        stream: async_nats::jetstream::stream::Stream,
+        topic: &str,
+        lattice_id: &str,
+        multitenant_prefix: Option<&str>,
+    ) -> Result<Self::Output, NatsError> {
+        C::create(stream, topic, lattice_id, multitenant_prefix)
+            .await
+            .map(|inner| PriorityLanes::new(inner, DEFAULT_PRIORITY_BUFFER))
+    }
+
+    async fn create_with_replay(
+        stream: async_nats::jetstream::stream::Stream,
+        topic: &str,
+        lattice_id: &str,
+        multitenant_prefix: Option<&str>,
+        replay: ReplayWindow,
+    ) -> Result<Self::Output, NatsError> {
+        C::create_with_replay(stream, topic, lattice_id, multitenant_prefix, replay)
+            .await
+            .map(|inner| PriorityLanes::new(inner, DEFAULT_PRIORITY_BUFFER))
+    }
+
+    /// Pulls `batch_size` messages at a time from the inner consumer and looks through all of them
+    /// for one that can skip ahead
+    async fn create_with_batch_size(
+        stream: async_nats::jetstream::stream::Stream,
+        topic: &str,
+        lattice_id: &str,
+        multitenant_prefix: Option<&str>,
+        replay: ReplayWindow,
+        batch_size: usize,
+    ) -> Result<Self::Output, NatsError> {
+        C::create_with_batch_size(
+            stream,
+            topic,
+            lattice_id,
+            multitenant_prefix,
+            replay,
+            batch_size,
+        )
+        .await
+        .map(|inner| PriorityLanes::new(inner, batch_size))
+    }
+
+    fn stats(&self) -> Option<Arc<dyn ConsumerStats>> {
+        self.inner.stats()
+    }
+
+    fn set_ack_batching(&mut self, config: AckBatchConfig) {
+        self.inner.set_ack_batching(config)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::events::{
+        HostHeartbeat, HostStarted, ProviderHealthCheckInfo, ProviderHealthCheckPassed,
+        ProviderStopped,
+    };
+
+    fn scoped(inner: Event) -> Result<ScopedMessage<Event>, NatsError> {
+        Ok(ScopedMessage {
+            lattice_id: "priority".to_string(),
+            inner,
+            acker: None,
+            ack_batcher: None,
+            published: None,
+        })
+    }
+
+    fn health_check(host_id: &str) -> Event {
+        Event::ProviderHealthCheckPassed(ProviderHealthCheckPassed {
+            data: ProviderHealthCheckInfo {
+                provider_id: "provider".to_string(),
+                host_id: host_id.to_string(),
+            },
+        })
+    }
+
+    fn host_started(id: &str) -> Event {
+        Event::HostStarted(HostStarted {
+            labels: Default::default(),
+            friendly_name: id.to_string(),
+            id: id.to_string(),
+        })
+    }
+
+    async fn drain(backlog: Vec<Result<ScopedMessage<Event>, NatsError>>) -> Vec<Event> {
+        PriorityLanes::new(futures::stream::iter(backlog), DEFAULT_PRIORITY_BUFFER)
+            .map(|msg| msg.expect("Should not be an error").inner.clone())
+            .collect()
+            .await
+    }
+
+    #[tokio::test]
+    async fn lifecycle_events_skip_unrelated_health_check_backlog() {
+        let mut backlog: Vec<_> = (0..5).map(|_| scoped(health_check("host"))).collect();
+        backlog.push(scoped(host_started("other-host")));
+        backlog.push(scoped(health_check("host")));
+
+        let handled = drain(backlog).await;
+        assert!(
+            matches!(handled[0], Event::HostStarted(_)),
+            "Lifecycle event should be processed before the health check backlog"
+        );
+        assert_eq!(handled.len(), 7, "No messages should be dropped");
+    }
+
+    #[tokio::test]
+    async fn related_events_keep_their_order() {
+        let heartbeat = Event::HostHeartbeat(HostHeartbeat {
+            components: Vec::new(),
+            providers: Vec::new(),
+            host_id: "host".to_string(),
+            issuer: String::new(),
+            friendly_name: "host".to_string(),
+            labels: Default::default(),
+            version: semver::Version::new(1, 0, 0),
+            uptime_human: "1s".to_string(),
+            uptime_seconds: 1,
+            utilization: None,
+        });
+        let stopped = Event::ProviderStopped(ProviderStopped {
+            annotations: Default::default(),
+            provider_id: "provider".to_string(),
+            reason: String::new(),
+            host_id: "other-host".to_string(),
+        });
+        let backlog = vec![
+            scoped(heartbeat.clone()),
+            scoped(health_check("host")),
+            scoped(host_started("host")),
+            scoped(stopped.clone()),
+        ];
+
+        assert_eq!(
+            drain(backlog).await,
+            vec![
+                heartbeat,
+                health_check("host"),
+                host_started("host"),
+                stopped
+            ],
+            "Heartbeats should never be skipped and health checks should only be skipped by events for other hosts and providers"
+        );
+    }
+
+    #[tokio::test]
+    async fn single_buffer_preserves_order() {
+        let backlog = vec![
+            scoped(health_check("host")),
+            scoped(host_started("other-host")),
+        ];
+        let mut lanes = PriorityLanes::new(futures::stream::iter(backlog), 1);
+
+        let first = lanes.next().await.unwrap().unwrap();
+        assert!(
+            matches!(first.inner, Event::ProviderHealthCheckPassed(_)),
+            "Messages should not be reordered without a buffer"
+        );
+    }
+
+    #[tokio::test]
+    async fn buffer_never_grows_past_window() {
+        let pulled = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let counter = pulled.clone();
+        let backlog = (0..10)
+            .map(|_| scoped(health_check("host")))
+            .collect::<Vec<_>>();
+        let mut lanes = PriorityLanes::new(
+            futures::stream::iter(backlog).inspect(move |_| {
+                counter.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+            }),
+            3,
+        );
+
+        lanes.next().await.unwrap().unwrap();
+        assert_eq!(
+            pulled.load(std::sync::atomic::Ordering::Relaxed),
+            3,
+            "No more messages than the window should be pulled from the inner consumer"
+        );
+        assert_eq!(lanes.count().await, 9, "No messages should be dropped");
+    }
+}
@@ -11,11 +11,12 @@ use std::{
 use cloudevents::{AttributesReader, Data, Event as CloudEvent, EventBuilder, EventBuilderV10};
 use serde::{Deserialize, Serialize};
 use thiserror::Error;
-use wasmcloud_control_interface::{ComponentDescription, Link, ProviderDescription};
+use wasmcloud_control_interface::{ComponentDescription, HostInventory, Link, ProviderDescription};
 
 use wadm_types::Manifest;
 
 use super::data::*;
+use crate::commands::Command;
 
 /// The source used for cloud events that wadm emits
 pub const WADM_SOURCE: &str = "wadm";
@@ -43,7 +44,7 @@ macro_rules! event_impl {
 
             fn try_from(mut value: cloudevents::Event) -> Result<Self, Self::Error> {
                 if $t::TYPE != value.ty() {
-                    return Err(ConversionError::WrongEvent(value));
+                    return Err(ConversionError::WrongEvent(Box::new(value)));
                 }
                 let (_, _, data) = value.take_data();
                 let data = data.ok_or(ConversionError::NoData)?;
@@ -67,7 +68,7 @@ macro_rules! event_impl {
 
             fn try_from(mut value: cloudevents::Event) -> Result<Self, Self::Error> {
                 if $t::TYPE != value.ty() {
-                    return Err(ConversionError::WrongEvent(value));
+                    return Err(ConversionError::WrongEvent(Box::new(value)));
                 }
                 let (_, _, data) = value.take_data();
                 let data = data.ok_or(ConversionError::NoData)?;
@@ -112,6 +113,8 @@ pub enum Event {
     // for now to have them here even though they aren't technically lattice events
     ManifestPublished(ManifestPublished),
     ManifestUnpublished(ManifestUnpublished),
+    CommandExecuted(CommandExecuted),
+    HostReaped(HostReaped),
 }
 
 impl Display for Event {
@@ -134,6 +137,8 @@ impl Display for Event {
             Event::ConfigDeleted(_) => write!(f, "ConfigDeleted"),
             Event::ManifestPublished(_) => write!(f, "ManifestPublished"),
             Event::ManifestUnpublished(_) => write!(f, "ManifestUnpublished"),
+            Event::CommandExecuted(_) => write!(f, "CommandExecuted"),
+            Event::HostReaped(_) => write!(f, "HostReaped"),
         }
     }
 }
@@ -174,7 +179,9 @@ impl TryFrom<CloudEvent> for Event {
             ManifestUnpublished::TYPE => {
                 ManifestUnpublished::try_from(value).map(Event::ManifestUnpublished)
             }
-            _ => Err(ConversionError::WrongEvent(value)),
+            CommandExecuted::TYPE => CommandExecuted::try_from(value).map(Event::CommandExecuted),
+            HostReaped::TYPE => HostReaped::try_from(value).map(Event::HostReaped),
+            _ => Err(ConversionError::WrongEvent(Box::new(value))),
         }
     }
 }
@@ -201,6 +208,8 @@ impl TryFrom<Event> for CloudEvent {
             Event::ConfigDeleted(_) => ConfigDeleted::TYPE,
             Event::ManifestPublished(_) => ManifestPublished::TYPE,
             Event::ManifestUnpublished(_) => ManifestUnpublished::TYPE,
+            Event::CommandExecuted(_) => CommandExecuted::TYPE,
+            Event::HostReaped(_) => HostReaped::TYPE,
         };
 
         EventBuilderV10::new()
@@ -238,6 +247,8 @@ impl Serialize for Event {
             Event::ConfigDeleted(evt) => evt.serialize(serializer),
             Event::ManifestPublished(evt) => evt.serialize(serializer),
             Event::ManifestUnpublished(evt) => evt.serialize(serializer),
+            Event::CommandExecuted(evt) => evt.serialize(serializer),
+            Event::HostReaped(evt) => evt.serialize(serializer),
         }
     }
 }
@@ -249,7 +260,7 @@ impl Event {
     }
 
     /// Returns the underlying raw cloudevent type for the event
-    pub fn raw_type(&self) -> &str {
+    pub fn raw_type(&self) -> &'static str {
         match self {
             Event::ComponentScaled(_) => ComponentScaled::TYPE,
             Event::ComponentScaleFailed(_) => ComponentScaleFailed::TYPE,
@@ -268,6 +279,8 @@ impl Event {
             Event::ConfigDeleted(_) => ConfigDeleted::TYPE,
             Event::ManifestPublished(_) => ManifestPublished::TYPE,
             Event::ManifestUnpublished(_) => ManifestUnpublished::TYPE,
+            Event::CommandExecuted(_) => CommandExecuted::TYPE,
+            Event::HostReaped(_) => HostReaped::TYPE,
         }
     }
 }
@@ -279,7 +292,7 @@ pub enum ConversionError {
     /// An unrecognized event was found when trying to convert it to the event type. Returns the
     /// original cloudevents event
     #[error("Wrong event type")]
-    WrongEvent(CloudEvent),
+    WrongEvent(Box<CloudEvent>),
     /// If an event of the right type was found, but no data was contained within that event
     #[error("No data found")]
     NoData,
@@ -506,6 +519,18 @@ pub struct HostHeartbeat {
     pub uptime_human: String,
     /// The host uptime in seconds
     pub uptime_seconds: u64,
+    /// The resource utilization of the host, if the host reports it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub utilization: Option<HostUtilization>,
+}
+
+/// Resource utilization reported by a host, as percentages of the host's total capacity
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct HostUtilization {
+    /// How much of the host's CPU is in use
+    pub cpu_percent: u32,
+    /// How much of the host's memory is in use
+    pub memory_percent: u32,
 }
 
 event_impl!(
@@ -515,12 +540,36 @@ event_impl!(
     host_id
 );
 
+/// Builds a heartbeat from the inventory of a host, for when the current state of a host is needed
+/// without waiting for it to heartbeat. Inventories don't include the issuer or utilization of the
+/// host, so those are left empty
+impl From<&HostInventory> for HostHeartbeat {
+    fn from(inventory: &HostInventory) -> HostHeartbeat {
+        HostHeartbeat {
+            components: inventory.components().to_owned(),
+            providers: inventory.providers().to_owned(),
+            host_id: inventory.host_id().to_owned(),
+            issuer: String::new(),
+            friendly_name: inventory.friendly_name().to_owned(),
+            labels: inventory.labels().clone().into_iter().collect(),
+            version: semver::Version::parse(inventory.version())
+                .unwrap_or_else(|_| semver::Version::new(0, 0, 0)),
+            uptime_human: inventory.uptime_human().to_owned(),
+            uptime_seconds: inventory.uptime_seconds(),
+            utilization: None,
+        }
+    }
+}
+
 // Manifest Events
 
 #[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
 pub struct ManifestPublished {
     #[serde(flatten)]
     pub manifest: Manifest,
+    /// Variables used to replace `${VAR}` placeholders in the manifest before it is deployed
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub variables: BTreeMap<String, String>,
 }
 
 event_impl!(ManifestPublished, "com.wadm.manifest_published");
@@ -532,6 +581,41 @@ pub struct ManifestUnpublished {
 
 event_impl!(ManifestUnpublished, "com.wadm.manifest_unpublished");
 
+// Command Events
+
+/// The result of executing a [`Command`] against the lattice, as reported by the control interface
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct CommandExecuted {
+    /// The deterministic ID of the command, see [`Command::id`]
+    pub command_id: String,
+    /// The command that was executed
+    pub command: Command,
+    /// Whether or not the command was accepted by the lattice
+    pub success: bool,
+    /// The message returned in the response, or the error encountered when sending the command
+    #[serde(default)]
+    pub message: String,
+}
+
+event_impl!(CommandExecuted, "com.wadm.command_executed");
+
+// Reaper Events
+
+/// Published by the reaper when it removes a host that hasn't been seen for too long
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct HostReaped {
+    pub host_id: String,
+    pub friendly_name: String,
+    #[serde(default)]
+    pub labels: HashMap<String, String>,
+    /// The number of components that were running on the host and are removed along with it
+    pub components_removed: usize,
+    /// The number of providers that were running on the host and are removed along with it
+    pub providers_removed: usize,
+}
+
+event_impl!(HostReaped, "com.wadm.host_reaped");
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -1,14 +1,15 @@
 use std::sync::Arc;
 use std::time::Duration;
 
-use anyhow::Result;
+use anyhow::{Context as _, Result};
 use async_nats::jetstream::{stream::Stream, Context};
 use config::WadmConfig;
-use tokio::{sync::Semaphore, task::JoinSet};
-use tracing::log::debug;
+use tokio::{
+    sync::{watch, Semaphore},
+    task::JoinSet,
+};
+use tracing::log::{debug, info};
 
-#[cfg(feature = "http_admin")]
-use anyhow::Context as _;
 #[cfg(feature = "http_admin")]
 use hyper::body::Bytes;
 #[cfg(feature = "http_admin")]
@@ -22,11 +23,29 @@ use crate::{
         manager::{ConsumerManager, WorkerCreator},
         *,
     },
+    events::Event,
     nats_utils::LatticeIdParser,
-    scaler::manager::{ScalerManager, WADM_NOTIFY_PREFIX},
-    server::{ManifestNotifier, Server},
-    storage::{nats_kv::NatsKvStore, reaper::Reaper},
-    workers::{CommandPublisher, CommandWorker, EventWorker, StatusPublisher},
+    reload::RuntimeConfig,
+    scaler::{
+        manager::{ScalerManager, ScalerManagerOptions, WADM_NOTIFY_PREFIX},
+        registry::ScalerRegistry,
+        shadowscaler::WADM_SHADOW_PREFIX,
+        ComputePool,
+    },
+    server::{ApiSubjects, ManifestNotifier, Server},
+    sink::{CommandHistorySink, SharedSink, SinkConfig},
+    storage::{
+        nats_kv::NatsKvStore,
+        reaper::{HostWarningCallback, Reaper},
+        Host,
+    },
+    workers::{
+        CommandBatchConfig, CommandPublisher, CommandRateLimit, CommandWorker,
+        ControlInterfaceBreaker, DeployConflictPolicy, DeployEventPublisher, EventWorker,
+        HostConcurrencyLimit, InstanceAnnotations, LeaseKeeper, RetryPolicy, SplitBrainPolicy,
+        StatusPublisher, StoreCommandClaimer, SubjectTemplate, DEPLOYED_SUBJECT_PREFIX,
+        DRY_RUN_SUBJECT_PREFIX, MODEL_NAME_PLACEHOLDER,
+    },
 };
 
 pub use nats::StreamPersistence;
@@ -37,8 +56,11 @@ pub mod consumers;
 pub mod events;
 pub mod nats_utils;
 pub mod publisher;
+pub mod reload;
 pub mod scaler;
+pub mod selftest;
 pub mod server;
+pub mod sink;
 pub mod storage;
 pub mod workers;
 
@@ -52,6 +74,12 @@ pub mod test_util;
 /// Default amount of time events should stay in the stream. This is the 2x heartbeat interval, plus
 /// some wiggle room. Exported to make setting defaults easy
 pub const DEFAULT_EXPIRY_TIME: Duration = Duration::from_secs(70);
+/// How often the number of events waiting to be handled for each lattice is recorded
+#[cfg(feature = "http_admin")]
+const CONSUMER_LAG_INTERVAL: Duration = Duration::from_secs(15);
+/// How often histograms held by the Prometheus recorder are drained
+#[cfg(feature = "http_admin")]
+const METRICS_UPKEEP_INTERVAL: Duration = Duration::from_secs(5);
 /// Default topic to listen to for all lattice events
 pub const DEFAULT_EVENTS_TOPIC: &str = "wasmbus.evt.*.>";
 /// Default topic to listen to for all lattice events in a multitenant deployment
@@ -60,6 +88,11 @@ pub const DEFAULT_MULTITENANT_EVENTS_TOPIC: &str = "*.wasmbus.evt.*.>";
 pub const DEFAULT_COMMANDS_TOPIC: &str = "wadm.cmd.*";
 /// Default topic to listen to for all status updates. wadm.status.<lattice_id>.<manifest_name>
 pub const DEFAULT_STATUS_TOPIC: &str = "wadm.status.*.*";
+/// Default template for the subject commands are published on, see [`workers::SubjectTemplate`]
+pub const DEFAULT_COMMAND_SUBJECT_TEMPLATE: &str = "wadm.cmd.{lattice_id}";
+/// Default template for the subject model statuses are published on, see
+/// [`workers::SubjectTemplate`]
+pub const DEFAULT_STATUS_SUBJECT_TEMPLATE: &str = "wadm.status.{lattice_id}.{model_name}";
 /// Default topic to listen to for all wadm event updates
 pub const DEFAULT_WADM_EVENTS_TOPIC: &str = "wadm.evt.*.>";
 /// Default internal wadm event consumer listen topic for the merged wadm and wasmbus events stream.
@@ -84,6 +117,12 @@ pub const DEFAULT_COMMAND_STREAM_NAME: &str = "wadm_commands";
 pub const DEFAULT_STATUS_STREAM_NAME: &str = "wadm_status";
 /// Default stream name for wadm notifications
 pub const DEFAULT_NOTIFY_STREAM_NAME: &str = "wadm_notify";
+/// Default stream name for wadm model deployed events
+pub const DEFAULT_DEPLOY_EVENT_STREAM_NAME: &str = "wadm_deploy_events";
+/// Default stream name for commands published by shadow scalers
+pub const DEFAULT_SHADOW_STREAM_NAME: &str = "wadm_shadow";
+/// Default stream name for commands published in dry run mode
+pub const DEFAULT_DRY_RUN_STREAM_NAME: &str = "wadm_dry_run";
 /// Default stream name for wasmbus events
 pub const DEFAULT_WASMBUS_EVENT_STREAM_NAME: &str = "wasmbus_events";
 
@@ -125,6 +164,113 @@ pub const DEFAULT_WASMBUS_EVENT_STREAM_NAME: &str = "wasmbus_events";
 /// };
 /// ```
 pub async fn start_wadm(config: WadmConfig) -> Result<JoinSet<Result<()>>> {
+    start_wadm_with_scalers(config, ScalerRegistry::default()).await
+}
+
+/// Start wadm with the provided [WadmConfig] in the same way as [start_wadm], using the given
+/// [ScalerRegistry] to construct scalers for any custom trait types found in manifests
+pub async fn start_wadm_with_scalers(
+    config: WadmConfig,
+    scaler_registry: ScalerRegistry,
+) -> Result<JoinSet<Result<()>>> {
+    start_wadm_with_extensions(
+        config,
+        WadmExtensions::default().with_scaler_registry(scaler_registry),
+    )
+    .await
+}
+
+/// Hooks for applications embedding wadm that can't be set from a [WadmConfig], for use with
+/// [start_wadm_with_extensions]
+#[derive(Clone, Default)]
+pub struct WadmExtensions {
+    scaler_registry: ScalerRegistry,
+    host_warning_callback: Option<HostWarningCallback>,
+}
+
+impl WadmExtensions {
+    /// Sets the [ScalerRegistry] used to construct scalers for custom trait types found in
+    /// manifests
+    pub fn with_scaler_registry(mut self, scaler_registry: ScalerRegistry) -> Self {
+        self.scaler_registry = scaler_registry;
+        self
+    }
+
+    /// Sets a callback invoked with the lattice ID and host when a host stops heartbeating and
+    /// will be reaped at the next check. See [`Reaper::with_host_warning_callback`]
+    pub fn with_host_warning_callback(
+        mut self,
+        callback: impl Fn(&str, &Host) + Send + Sync + 'static,
+    ) -> Self {
+        self.host_warning_callback = Some(Arc::new(callback));
+        self
+    }
+}
+
+impl std::fmt::Debug for WadmExtensions {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("WadmExtensions")
+            .field("scaler_registry", &self.scaler_registry)
+            .field(
+                "host_warning_callback",
+                &self.host_warning_callback.is_some(),
+            )
+            .finish()
+    }
+}
+
+/// Start wadm with the provided [WadmConfig] in the same way as [start_wadm], with the hooks set
+/// in the given [WadmExtensions]
+pub async fn start_wadm_with_extensions(
+    config: WadmConfig,
+    extensions: WadmExtensions,
+) -> Result<JoinSet<Result<()>>> {
+    let WadmExtensions {
+        scaler_registry,
+        host_warning_callback,
+    } = extensions;
+    let command_subject: SubjectTemplate = config
+        .command_subject_template
+        .parse()
+        .context("Invalid command subject template")?;
+    let status_subject: SubjectTemplate = config
+        .status_subject_template
+        .parse()
+        .context("Invalid status subject template")?;
+    if !status_subject.has_model_name() {
+        anyhow::bail!("Status subject template must contain {MODEL_NAME_PLACEHOLDER}");
+    }
+    let instance_annotations = InstanceAnnotations {
+        static_annotations: config
+            .instance_annotations
+            .iter()
+            .map(|pair| {
+                pair.split_once('=')
+                    .map(|(key, value)| (key.trim().to_owned(), value.trim().to_owned()))
+                    .with_context(|| {
+                        format!("Invalid instance annotation '{pair}', expected KEY=VALUE")
+                    })
+            })
+            .collect::<Result<_>>()?,
+        deploy_metadata: config.instance_deploy_metadata,
+    };
+    let replay_window = match (config.event_replay_max_events, config.event_replay_max_age) {
+        (Some(_), Some(_)) => anyhow::bail!(
+            "Only one of the event replay max events or max age can be set at the same time"
+        ),
+        (Some(count), None) => ReplayWindow::LastMessages(count),
+        (None, Some(secs)) => ReplayWindow::Since(Duration::from_secs(secs)),
+        (None, None) => ReplayWindow::All,
+    };
+    let sink_configs = config
+        .state_change_sinks
+        .iter()
+        .map(|sink| sink.parse::<SinkConfig>())
+        .collect::<Result<Vec<_>>>()
+        .context("Invalid state change sink")?;
+    // Settings that can be changed without restarting wadm
+    let (runtime_config, runtime_config_rx) = watch::channel(RuntimeConfig::from(&config));
+
     // Build storage adapter for lattice state (on by default)
     let (client, context) = nats::get_client_and_context(
         config.nats_server.clone(),
@@ -136,6 +282,11 @@ pub async fn start_wadm(config: WadmConfig) -> Result<JoinSet<Result<()>>> {
     )
     .await?;
 
+    let mut sinks = sink_configs
+        .iter()
+        .map(|sink| sink.build(client.clone()))
+        .collect::<Result<Vec<_>>>()?;
+
     // TODO: We will probably need to set up all the flags (like lattice prefix and topic prefix) down the line
     let connection_pool = ControlClientConstructor::new(client.clone(), None);
 
@@ -151,6 +302,22 @@ pub async fn start_wadm(config: WadmConfig) -> Result<JoinSet<Result<()>>> {
     .await?;
 
     let state_storage = NatsKvStore::new(store);
+    #[cfg(feature = "kv_compression")]
+    let state_storage = match config.kv_compression_threshold {
+        Some(threshold) => state_storage.with_compression(threshold),
+        None => state_storage,
+    };
+
+    if let Some(retention) = config.command_history_retention {
+        sinks.push(Arc::new(CommandHistorySink::new(
+            state_storage.clone(),
+            Duration::from_secs(retention),
+        )));
+    }
+    let state_change_sink: Option<SharedSink> = match sinks.len() {
+        0 | 1 => sinks.pop(),
+        _ => Some(Arc::new(sinks)),
+    };
 
     let manifest_storage = nats::ensure_kv_bucket(
         &context,
@@ -194,7 +361,7 @@ pub async fn start_wadm(config: WadmConfig) -> Result<JoinSet<Result<()>>> {
     let command_stream = nats::ensure_stream(
         &context,
         internal_stream_name(DEFAULT_COMMAND_STREAM_NAME),
-        vec![DEFAULT_COMMANDS_TOPIC.to_owned()],
+        vec![command_subject.stream_subject()],
         Some("A stream that stores all commands for wadm".to_string()),
         config.max_command_stream_bytes,
         config.stream_persistence.into(),
@@ -204,12 +371,54 @@ pub async fn start_wadm(config: WadmConfig) -> Result<JoinSet<Result<()>>> {
     let status_stream = nats::ensure_status_stream(
         &context,
         internal_stream_name(DEFAULT_STATUS_STREAM_NAME),
-        vec![DEFAULT_STATUS_TOPIC.to_owned()],
+        vec![status_subject.stream_subject()],
         config.max_status_stream_bytes,
         config.stream_persistence.into(),
     )
     .await?;
 
+    debug!("Ensuring deploy event stream");
+
+    nats::ensure_limits_stream(
+        &context,
+        internal_stream_name(DEFAULT_DEPLOY_EVENT_STREAM_NAME),
+        vec![format!("{DEPLOYED_SUBJECT_PREFIX}.>")],
+        Some(
+            "A stream that stores an event for each model version that finished deploying"
+                .to_string(),
+        ),
+        config.max_deploy_event_stream_bytes,
+        config.stream_persistence.into(),
+    )
+    .await?;
+
+    debug!("Ensuring shadow stream");
+
+    nats::ensure_limits_stream(
+        &context,
+        internal_stream_name(DEFAULT_SHADOW_STREAM_NAME),
+        vec![format!("{WADM_SHADOW_PREFIX}.>")],
+        Some(
+            "A stream that stores the commands shadow scalers would have sent and how they compared to the executed commands"
+                .to_string(),
+        ),
+        config.max_shadow_stream_bytes,
+        config.stream_persistence.into(),
+    )
+    .await?;
+
+    debug!("Ensuring dry run stream");
+
+    nats::ensure_limits_stream(
+        &context,
+        internal_stream_name(DEFAULT_DRY_RUN_STREAM_NAME),
+        vec![format!("{DRY_RUN_SUBJECT_PREFIX}.>")],
+        Some("A stream that stores the commands wadm would have sent in dry run mode".to_string()),
+        config.max_dry_run_stream_bytes,
+        config.stream_persistence.into(),
+    )
+    .await?;
+
     debug!("Ensuring wasmbus event stream");
 
     // Remove the previous wadm_(multitenant)_mirror streams so that they don't
@@ -267,32 +476,121 @@ pub async fn start_wadm(config: WadmConfig) -> Result<JoinSet<Result<()>>> {
     )
     .await?;
 
+    if config.self_test {
+        info!("Running self-test");
+        // Each instance uses its own lattice so concurrent self-tests don't consume each other's
+        // commands
+        let self_test_lattice = format!("wadm_self_test_{}", uuid::Uuid::new_v4().simple());
+        let round_trip = selftest::JetStreamRoundTrip::new(
+            context.clone(),
+            command_stream.clone(),
+            command_subject.render(&self_test_lattice, Some(selftest::SELF_TEST_NAME)),
+        );
+        let status_publisher =
+            StatusPublisher::new(context.clone(), Some(status_stream.clone()), "wadm.status")
+                .with_subject_template(&self_test_lattice, status_subject.clone());
+        selftest::run_self_test(
+            &state_storage,
+            &round_trip,
+            &status_publisher,
+            &self_test_lattice,
+        )
+        .await?;
+    }
+
     debug!("Creating event consumer manager");
 
     let permit_pool = Arc::new(Semaphore::new(
         config.max_jobs.unwrap_or(Semaphore::MAX_PERMITS),
     ));
+    // Used to tell this instance apart from others when deduplicating commands and renewing leases
+    let instance_id = config
+        .host_id
+        .clone()
+        .unwrap_or_else(|| uuid::Uuid::new_v4().to_string());
     let event_worker_creator = EventWorkerCreator {
         state_store: state_storage.clone(),
         manifest_store: manifest_storage.clone(),
         pool: connection_pool.clone(),
-        command_topic_prefix: DEFAULT_COMMANDS_TOPIC.trim_matches(trimmer).to_owned(),
+        command_subject: command_subject.clone(),
+        status_subject: status_subject.clone(),
         publisher: context.clone(),
         notify_stream,
         status_stream: status_stream.clone(),
+        command_dedup: config
+            .command_dedup_window
+            .map(|window| (instance_id.clone(), Duration::from_secs(window))),
+        instance_lease: config.instance_lease_ttl.map(|ttl| {
+            (
+                instance_id.clone(),
+                Duration::from_secs(ttl),
+                config.split_brain_policy,
+            )
+        }),
+        command_batch: config
+            .command_batch_size
+            .map(|max_size| CommandBatchConfig {
+                max_size,
+                max_age: Duration::from_millis(config.command_batch_max_age),
+            }),
+        command_rate_limit: config
+            .command_rate_limit
+            .map(|per_second| CommandRateLimit {
+                per_second,
+                burst: config.command_rate_burst,
+            }),
+        scaler_registry,
+        deploy_conflict_policy: config.deploy_conflict_policy,
+        deploy_queue_timeout: Duration::from_secs(config.deploy_queue_timeout),
+        confirm_stops: config.confirm_stops,
+        concurrent_heartbeats: config.concurrent_heartbeats,
+        scaler_concurrency: config.scaler_concurrency,
+        dry_run: config.dry_run,
+        status_history_size: config.status_history_size,
+        instance_annotations,
+        // A single pool is shared by all lattices so the bound applies to the whole process
+        compute_pool: config.reconcile_compute_threads.map(ComputePool::new),
+        max_instances_per_host: config.max_instances_per_host,
+        compact_notifications: config.compact_notifications,
+        runtime_config: runtime_config_rx.clone(),
+        state_change_sink,
     };
-    let events_manager: ConsumerManager<EventConsumer> = ConsumerManager::new(
-        permit_pool.clone(),
-        event_consumer_stream,
-        event_worker_creator.clone(),
-        config.multitenant,
-    )
-    .await;
+    let events_manager: ConsumerManager<PriorityLanes<EventConsumer, Event>> =
+        ConsumerManager::new_with_priority_window(
+            permit_pool.clone(),
+            event_consumer_stream,
+            event_worker_creator.clone(),
+            config.multitenant,
+            config.event_ack_batch_size.map(|max_size| AckBatchConfig {
+                max_size,
+                max_age: Duration::from_millis(config.event_ack_batch_max_age),
+            }),
+            Some(config.event_priority_window),
+        )
+        .await
+        .with_replay_window(replay_window);
+    #[cfg(feature = "http_admin")]
+    let lag_manager = events_manager.clone();
 
     debug!("Creating command consumer manager");
 
     let command_worker_creator = CommandWorkerCreator {
         pool: connection_pool,
+        publisher: context.clone(),
+        result_topic_prefix: DEFAULT_WADM_EVENTS_TOPIC.trim_matches(trimmer).to_owned(),
+        provider_start_limit: config
+            .max_provider_starts_per_host
+            .map(HostConcurrencyLimit::new),
+        command_ttl: config.command_ttl.map(Duration::from_secs),
+        scale_concurrency: config.command_scale_concurrency,
+        publish_successes: config.publish_command_successes,
+        retry_policy: RetryPolicy::new(
+            config.command_retry_attempts,
+            Duration::from_millis(config.command_retry_delay),
+        ),
+        ctl_failure_threshold: config.ctl_failure_threshold,
+        ctl_initial_backoff: Duration::from_millis(config.ctl_initial_backoff),
+        ctl_max_backoff: Duration::from_millis(config.ctl_max_backoff),
     };
     let commands_manager: ConsumerManager<CommandConsumer> = ConsumerManager::new(
         permit_pool.clone(),
@@ -307,11 +605,16 @@ pub async fn start_wadm(config: WadmConfig) -> Result<JoinSet<Result<()>>> {
     // off that tick, resulting in multiple people handling. We could maybe get it to work with the
     // right duplicate window, but we have no idea when each process could fire a tick. Worst case
     // scenario right now is that multiple fire simultaneously and a few of them just delete nothing
-    let reaper = Reaper::new(
+    let mut reaper = Reaper::new(
         state_storage.clone(),
+        context.clone(),
         Duration::from_secs(config.cleanup_interval / 2),
         [],
     );
+    if let Some(callback) = host_warning_callback {
+        reaper =
+            reaper.with_host_warning_callback(move |lattice_id, host| callback(lattice_id, host));
+    }
 
     let wadm_event_prefix = DEFAULT_WADM_EVENTS_TOPIC.trim_matches(trimmer);
 
@@ -325,19 +628,24 @@ pub async fn start_wadm(config: WadmConfig) -> Result<JoinSet<Result<()>>> {
         client: client.clone(),
         command_worker_creator,
         event_worker_creator,
+        command_subject,
+        api: ApiSubjects::new(Some(&config.api_prefix), config.multitenant)?,
+        lattices: observer::ObservedLattices::new(config.max_lattices),
+        runtime_config: runtime_config_rx,
     };
 
     debug!("Subscribing to API topic");
 
     let server = Server::new(
         manifest_storage,
-        client,
+        client.clone(),
         Some(&config.api_prefix),
         config.multitenant,
         status_stream,
         ManifestNotifier::new(wadm_event_prefix, context),
     )
-    .await?;
+    .await?
+    .with_status_subject(status_subject);
 
     let mut tasks = JoinSet::new();
 
@@ -347,8 +655,42 @@ pub async fn start_wadm(config: WadmConfig) -> Result<JoinSet<Result<()>>> {
         let socket = TcpListener::bind(addr)
             .await
             .context("failed to bind on HTTP administation endpoint")?;
+        // Metrics are only exposed through the administration endpoint, so that is the only time a
+        // recorder is installed. Embedders that already installed their own recorder keep it
+        let metrics = match metrics_exporter_prometheus::PrometheusBuilder::new().install_recorder()
+        {
+            Ok(handle) => {
+                let upkeep = handle.clone();
+                tasks.spawn(async move {
+                    let mut ticker = tokio::time::interval(METRICS_UPKEEP_INTERVAL);
+                    loop {
+                        ticker.tick().await;
+                        upkeep.run_upkeep();
+                    }
+                });
+                // Consumer lag has to be fetched from the stream, so it is recorded on a timer
+                // rather than as events are handled. There is no point in polling it when nothing
+                // will read it
+                tasks.spawn(async move {
+                    let mut ticker = tokio::time::interval(CONSUMER_LAG_INTERVAL);
+                    loop {
+                        ticker.tick().await;
+                        lag_manager.record_consumer_lag().await;
+                    }
+                });
+                Some(handle)
+            }
+            Err(err) => {
+                tracing::warn!(
+                    ?err,
+                    "failed to install metrics recorder, `/metrics` will not be served"
+                );
+                None
+            }
+        };
         let svc = hyper::service::service_fn(move |req| {
             const OK: &str = r#"{"status":"ok"}"#;
+            let metrics = metrics.clone();
             async move {
                 let (http::request::Parts { method, uri, .. }, _) = req.into_parts();
                 match (method.as_str(), uri.path()) {
@@ -370,6 +712,19 @@ pub async fn start_wadm(config: WadmConfig) -> Result<JoinSet<Result<()>>> {
                         .body(http_body_util::Full::new(Bytes::from(format!(
                             "method `{method}` not supported for path `/readyz`"
                         )))),
+                    ("GET", "/metrics") if metrics.is_some() => {
+                        Ok(http::Response::new(http_body_util::Full::new(Bytes::from(
+                            metrics
+                                .as_ref()
+                                .map(|handle| handle.render())
+                                .unwrap_or_default(),
+                        ))))
+                    }
+                    (method, "/metrics") if metrics.is_some() => http::Response::builder()
+                        .status(http::StatusCode::METHOD_NOT_ALLOWED)
+                        .body(http_body_util::Full::new(Bytes::from(format!(
+                            "method `{method}` not supported for path `/metrics`"
+                        )))),
                     (.., path) => http::Response::builder()
                         .status(http::StatusCode::NOT_FOUND)
                         .body(http_body_util::Full::new(Bytes::from(format!(
@@ -388,7 +743,10 @@ pub async fn start_wadm(config: WadmConfig) -> Result<JoinSet<Result<()>>> {
                         continue;
                     }
                 };
-                if let Err(err) = srv.serve_connection(TokioIo::new(stream), svc).await {
+                if let Err(err) = srv
+                    .serve_connection(TokioIo::new(stream), svc.clone())
+                    .await
+                {
                     tracing::error!(?err, "failed to serve HTTP administration connection");
                 }
             }
@@ -399,6 +757,9 @@ pub async fn start_wadm(config: WadmConfig) -> Result<JoinSet<Result<()>>> {
     tasks.spawn(server.serve());
     // Observe and handle events
     tasks.spawn(observer.observe(wasmbus_event_subjects));
+    if let Some(subject) = config.config_reload_subject {
+        tasks.spawn(reload::listen_for_updates(client, subject, runtime_config));
+    }
 
     Ok(tasks)
 }
@@ -406,11 +767,24 @@ pub async fn start_wadm(config: WadmConfig) -> Result<JoinSet<Result<()>>> {
 #[derive(Clone)]
 struct CommandWorkerCreator {
     pool: ControlClientConstructor,
+    publisher: Context,
+    result_topic_prefix: String,
+    /// Shared between all lattices so the limit applies no matter which worker starts a provider
+    provider_start_limit: Option<HostConcurrencyLimit>,
+    command_ttl: Option<Duration>,
+    scale_concurrency: Option<usize>,
+    publish_successes: bool,
+    retry_policy: RetryPolicy,
+    /// Each lattice gets its own breaker configured with these, so an unavailable control
+    /// interface in one lattice doesn't pause the others
+    ctl_failure_threshold: usize,
+    ctl_initial_backoff: Duration,
+    ctl_max_backoff: Duration,
 }
 
 #[async_trait::async_trait]
 impl WorkerCreator for CommandWorkerCreator {
-    type Output = CommandWorker;
+    type Output = CommandWorker<Context>;
 
     async fn create(
         &self,
@@ -419,7 +793,30 @@ impl WorkerCreator for CommandWorkerCreator {
     ) -> anyhow::Result<Self::Output> {
         let client = self.pool.get_connection(lattice_id, multitenant_prefix);
 
-        Ok(CommandWorker::new(client))
+        let worker = CommandWorker::new(
+            client,
+            self.publisher.clone(),
+            &format!("{}.{lattice_id}.command_executed", self.result_topic_prefix),
+        )
+        .with_breaker(ControlInterfaceBreaker::new(
+            self.ctl_failure_threshold,
+            self.ctl_initial_backoff,
+            self.ctl_max_backoff,
+        ))
+        .with_retry_policy(self.retry_policy)
+        .with_success_results(self.publish_successes);
+        let worker = match &self.provider_start_limit {
+            Some(limit) => worker.with_provider_start_limit(limit.clone()),
+            None => worker,
+        };
+        let worker = match self.command_ttl {
+            Some(ttl) => worker.with_command_ttl(ttl),
+            None => worker,
+        };
+        Ok(match self.scale_concurrency {
+            Some(max_concurrent) => worker.with_scale_concurrency(max_concurrent),
+            None => worker,
+        })
     }
 }
 
@@ -428,10 +825,33 @@ struct EventWorkerCreator<StateStore> {
     state_store: StateStore,
     manifest_store: async_nats::jetstream::kv::Store,
     pool: ControlClientConstructor,
-    command_topic_prefix: String,
+    command_subject: SubjectTemplate,
+    status_subject: SubjectTemplate,
     publisher: Context,
     notify_stream: Stream,
     status_stream: Stream,
+    /// The owner ID and claim duration used to deduplicate commands, if enabled
+    command_dedup: Option<(String, Duration)>,
+    /// The instance ID, lease TTL and split-brain policy used to detect lost instances, if enabled
+    instance_lease: Option<(String, Duration, SplitBrainPolicy)>,
+    command_batch: Option<CommandBatchConfig>,
+    command_rate_limit: Option<CommandRateLimit>,
+    scaler_registry: ScalerRegistry,
+    deploy_conflict_policy: DeployConflictPolicy,
+    deploy_queue_timeout: Duration,
+    confirm_stops: bool,
+    concurrent_heartbeats: bool,
+    scaler_concurrency: Option<usize>,
+    dry_run: bool,
+    status_history_size: Option<usize>,
+    instance_annotations: InstanceAnnotations,
+    compute_pool: Option<ComputePool>,
+    max_instances_per_host: Option<usize>,
+    compact_notifications: bool,
+    /// Settings that can change while wadm is running
+    runtime_config: watch::Receiver<RuntimeConfig>,
+    /// Where state changes are sent in addition to the command and status subjects, if configured
+    state_change_sink: Option<SharedSink>,
 }
 
 #[async_trait::async_trait]
@@ -447,15 +867,48 @@ where
         multitenant_prefix: Option<&str>,
     ) -> anyhow::Result<Self::Output> {
         let client = self.pool.get_connection(lattice_id, multitenant_prefix);
-        let command_publisher = CommandPublisher::new(
+        let mut command_publisher = CommandPublisher::new(
             self.publisher.clone(),
-            &format!("{}.{lattice_id}", self.command_topic_prefix),
-        );
-        let status_publisher = StatusPublisher::new(
+            &self.command_subject.render(lattice_id, None),
+        )
+        .with_subject_template(lattice_id, self.command_subject.clone());
+        if let Some((owner, window)) = &self.command_dedup {
+            command_publisher = command_publisher.with_claimer(StoreCommandClaimer::new(
+                self.state_store.clone(),
+                lattice_id,
+                owner,
+                *window,
+            ));
+        }
+        if let Some((instance_id, ttl, policy)) = &self.instance_lease {
+            let lease = LeaseKeeper::new(
+                self.state_store.clone(),
+                lattice_id,
+                instance_id,
+                *ttl,
+                *policy,
+            );
+            // Renew well before the lease expires so a single slow renewal doesn't look like a
+            // lost instance
+            lease.spawn_renewal(*ttl / 3);
+            command_publisher = command_publisher.with_lease(lease);
+        }
+        if let Some(batch) = self.command_batch {
+            command_publisher = command_publisher.with_batching(batch);
+        }
+        if let Some(limit) = self.command_rate_limit {
+            command_publisher = command_publisher.with_rate_limit(limit);
+        }
+        let mut status_publisher = StatusPublisher::new(
             self.publisher.clone(),
             Some(self.status_stream.clone()),
             &format!("wadm.status.{lattice_id}"),
-        );
+        )
+        .with_subject_template(lattice_id, self.status_subject.clone());
+        if let Some(sink) = &self.state_change_sink {
+            command_publisher = command_publisher.with_sink(lattice_id, sink.clone());
+            status_publisher = status_publisher.with_sink(lattice_id, sink.clone());
+        }
         let manager = ScalerManager::new(
             self.publisher.clone(),
             self.notify_stream.clone(),
@@ -466,14 +919,38 @@ where
             command_publisher.clone(),
             status_publisher.clone(),
             client.clone(),
+            ScalerManagerOptions {
+                registry: self.scaler_registry.clone(),
+                instance_annotations: self.instance_annotations.clone(),
+                compute_pool: self.compute_pool.clone(),
+                max_instances_per_host: self.max_instances_per_host,
+                compact_notifications: self.compact_notifications,
+                runtime_config: self.runtime_config.clone(),
+            },
         )
         .await?;
-        Ok(EventWorker::new(
+        let mut worker = EventWorker::new(
             self.state_store.clone(),
             client,
             command_publisher,
             status_publisher,
             manager,
-        ))
+        )
+        .with_deploy_conflict_policy(self.deploy_conflict_policy)
+        .with_deploy_queue_timeout(self.deploy_queue_timeout)
+        .with_stop_confirmation(self.confirm_stops)
+        .with_concurrent_heartbeats(self.concurrent_heartbeats)
+        .with_dry_run(self.dry_run)
+        .with_deploy_events(DeployEventPublisher::new(
+            self.publisher.clone(),
+            DEPLOYED_SUBJECT_PREFIX,
+        ));
+        if let Some(max_transitions) = self.status_history_size {
+            worker = worker.with_status_history(max_transitions);
+        }
+        if let Some(limit) = self.scaler_concurrency {
+            worker = worker.with_scaler_concurrency(limit);
+        }
+        Ok(worker)
     }
 }
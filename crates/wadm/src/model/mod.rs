@@ -1,8 +1,11 @@
 //! Contains the internal storage definition of a manifest
+use std::collections::BTreeMap;
+
+use chrono::Utc;
 use indexmap::IndexMap;
 use serde::{Deserialize, Serialize};
 
-use wadm_types::{Manifest, LATEST_VERSION, VERSION_ANNOTATION_KEY};
+use wadm_types::{Manifest, DEPLOYED_AT_ANNOTATION_KEY, LATEST_VERSION, VERSION_ANNOTATION_KEY};
 
 /// This struct represents a single manifest, with its version history. Internally these are stored
 /// as an indexmap keyed by version name
@@ -15,6 +18,9 @@ pub(crate) struct StoredManifest {
     manifests: IndexMap<String, Manifest>,
     // Set only if a version is deployed
     deployed_version: Option<String>,
+    // The values for the `${VAR}` placeholders in the deployed version
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    variables: BTreeMap<String, String>,
 }
 
 impl StoredManifest {
@@ -81,9 +87,10 @@ impl StoredManifest {
     /// Attempts to deploy the given version. If none is passed or the version is "latest", it will
     /// deploy the latest version.
     ///
-    /// Returns true if it was deployed, false otherwise
+    /// Returns true if it was deployed, false otherwise. The deployed version is annotated with the
+    /// time it was deployed
     pub fn deploy(&mut self, version: Option<String>) -> bool {
-        match version {
+        let deployed = match version {
             Some(v) if v == LATEST_VERSION => {
                 self.deployed_version = Some(self.current_version().to_owned());
                 true
@@ -100,7 +107,19 @@ impl StoredManifest {
                     false
                 }
             }
+        };
+        if let Some(manifest) = self
+            .deployed_version
+            .as_ref()
+            .and_then(|v| self.manifests.get_mut(v))
+            .filter(|_| deployed)
+        {
+            manifest.metadata.annotations.insert(
+                DEPLOYED_AT_ANNOTATION_KEY.to_owned(),
+                Utc::now().to_rfc3339(),
+            );
         }
+        deployed
     }
 
     /// Returns a reference to the current manifest
@@ -130,6 +149,23 @@ impl StoredManifest {
             .and_then(|v| self.manifests.get(v))
     }
 
+    /// Returns the variables the deployed version was deployed with
+    pub fn variables(&self) -> &BTreeMap<String, String> {
+        &self.variables
+    }
+
+    /// Sets the variables used to resolve the placeholders in the deployed version
+    pub fn set_variables(&mut self, variables: BTreeMap<String, String>) {
+        self.variables = variables;
+    }
+
+    /// Returns the deployed version of the manifest (if it is deployed) with its variables
+    /// substituted. This is what scalers should be built from
+    pub fn get_deployed_resolved(&self) -> Option<anyhow::Result<Manifest>> {
+        self.get_deployed()
+            .map(|manifest| crate::workers::template_manifest(manifest, &self.variables))
+    }
+
     /// Returns whether or not this is a new (empty) manifest
     pub fn is_empty(&self) -> bool {
         self.manifests.is_empty()
@@ -189,4 +225,44 @@ mod test {
             "Adding duplicate version should fail"
         );
     }
+
+    #[test]
+    fn test_deployed_variables_are_stored_and_resolved() {
+        let mut manifest = deserialize_yaml("../../tests/fixtures/manifests/simple2.yaml")
+            .expect("Should be able to parse");
+        manifest.metadata.annotations.insert(
+            "description".to_string(),
+            "deployed to ${ENVIRONMENT}".to_string(),
+        );
+        let mut stored = StoredManifest::default();
+        stored.add_version(manifest);
+        stored.deploy(None);
+        stored.set_variables(BTreeMap::from([(
+            "ENVIRONMENT".to_string(),
+            "staging".to_string(),
+        )]));
+
+        // Variables have to survive being written to and read back from the store
+        let stored: StoredManifest =
+            serde_json::from_slice(&serde_json::to_vec(&stored).unwrap()).unwrap();
+        let resolved = stored
+            .get_deployed_resolved()
+            .expect("Should be deployed")
+            .expect("Should resolve");
+        assert_eq!(
+            resolved.metadata.annotations.get("description").unwrap(),
+            "deployed to staging"
+        );
+        assert_eq!(
+            stored
+                .get_deployed()
+                .unwrap()
+                .metadata
+                .annotations
+                .get("description")
+                .unwrap(),
+            "deployed to ${ENVIRONMENT}",
+            "The stored manifest should keep its placeholders"
+        );
+    }
 }
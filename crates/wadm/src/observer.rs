@@ -1,18 +1,24 @@
 //! Types for observing a nats cluster for new lattices
 
+use std::collections::HashSet;
+
 use async_nats::Subscriber;
 use futures::{stream::SelectAll, StreamExt, TryFutureExt};
+use tokio::sync::watch;
 use tracing::{debug, error, instrument, trace, warn};
 
 use crate::{
     consumers::{
         manager::{ConsumerManager, WorkerCreator},
-        CommandConsumer, EventConsumer,
+        CommandConsumer, EventConsumer, PriorityLanes,
     },
-    events::{EventType, HostHeartbeat, HostStarted, ManifestPublished},
+    events::{Event, EventType, HostHeartbeat, HostStarted, ManifestPublished},
     nats_utils::LatticeIdParser,
+    reload::RuntimeConfig,
+    server::ApiSubjects,
     storage::{nats_kv::NatsKvStore, reaper::Reaper, Store},
-    DEFAULT_COMMANDS_TOPIC, DEFAULT_WADM_EVENT_CONSUMER_TOPIC,
+    workers::SubjectTemplate,
+    DEFAULT_WADM_EVENT_CONSUMER_TOPIC,
 };
 
 use super::{CommandWorkerCreator, EventWorkerCreator};
@@ -20,11 +26,62 @@ use super::{CommandWorkerCreator, EventWorkerCreator};
 pub(crate) struct Observer<StateStore> {
     pub(crate) parser: LatticeIdParser,
     pub(crate) command_manager: ConsumerManager<CommandConsumer>,
-    pub(crate) event_manager: ConsumerManager<EventConsumer>,
+    pub(crate) event_manager: ConsumerManager<PriorityLanes<EventConsumer, Event>>,
     pub(crate) client: async_nats::Client,
-    pub(crate) reaper: Reaper<NatsKvStore>,
+    pub(crate) reaper: Reaper<NatsKvStore, async_nats::jetstream::Context>,
     pub(crate) event_worker_creator: EventWorkerCreator<StateStore>,
     pub(crate) command_worker_creator: CommandWorkerCreator,
+    pub(crate) command_subject: SubjectTemplate,
+    pub(crate) api: ApiSubjects,
+    pub(crate) lattices: ObservedLattices,
+    pub(crate) runtime_config: watch::Receiver<RuntimeConfig>,
+}
+
+/// The lattices the observer is managing, which stops it from picking up new lattices once the
+/// configured maximum is reached
+#[derive(Debug, Default)]
+pub(crate) struct ObservedLattices {
+    max: Option<usize>,
+    lattices: HashSet<String>,
+}
+
+/// Returned when a newly found lattice can't be managed because the maximum number of lattices has
+/// already been reached
+#[derive(Debug, thiserror::Error, PartialEq, Eq)]
+#[error("Already managing the maximum of {max} lattices, ignoring events for new lattice")]
+pub(crate) struct LatticeLimitReached {
+    pub(crate) max: usize,
+}
+
+impl ObservedLattices {
+    pub(crate) fn new(max: Option<usize>) -> ObservedLattices {
+        ObservedLattices {
+            max,
+            lattices: HashSet::new(),
+        }
+    }
+
+    /// Records that events were seen for the given lattice, returning an error if it is a new
+    /// lattice and the maximum has been reached. Lattices that already have consumers (such as
+    /// those picked back up on startup) are always accepted, as they are already being managed
+    pub(crate) fn observe(
+        &mut self,
+        lattice_id: &str,
+        has_consumers: bool,
+    ) -> Result<(), LatticeLimitReached> {
+        if self.lattices.contains(lattice_id) {
+            return Ok(());
+        }
+        match self.max {
+            Some(max) if !has_consumers && self.lattices.len() >= max => {
+                Err(LatticeLimitReached { max })
+            }
+            _ => {
+                self.lattices.insert(lattice_id.to_owned());
+                Ok(())
+            }
+        }
+    }
 }
 
 impl<StateStore> Observer<StateStore>
@@ -38,8 +95,38 @@ where
     #[instrument(level = "info", skip(self))]
     pub(crate) async fn observe(mut self, subscribe_topics: Vec<String>) -> anyhow::Result<()> {
         let mut sub = get_subscriber(&self.client, subscribe_topics.clone()).await?;
+        // Reaper requests are handled by every instance, not just the API server that replies
+        let reaper_topic = self.api.category_filter("reaper");
+        let mut reaper_sub = get_subscriber(&self.client, vec![reaper_topic.clone()]).await?;
         loop {
-            match sub.next().await {
+            let next = tokio::select! {
+                next = sub.next() => next,
+                control = reaper_sub.next() => {
+                    match control {
+                        Some(msg) => {
+                            let handled = self.api.parse(&msg.subject).is_ok_and(|parsed| {
+                                parsed.object_name.is_none()
+                                    && self.reaper.handle_control(parsed.lattice_id, parsed.operation)
+                            });
+                            if !handled {
+                                warn!(subject = %msg.subject, "Ignoring invalid reaper request");
+                            }
+                        }
+                        None => {
+                            warn!("Reaper control subscriber hang up. Attempting to restart");
+                            reaper_sub = get_subscriber(&self.client, vec![reaper_topic.clone()]).await?;
+                        }
+                    }
+                    continue;
+                }
+                Ok(()) = self.runtime_config.changed() => {
+                    // The reaper checks twice per cleanup interval, as it does on startup
+                    let cleanup_interval = self.runtime_config.borrow_and_update().cleanup_interval;
+                    self.reaper.set_interval(cleanup_interval / 2);
+                    continue;
+                }
+            };
+            match next {
                 Some(msg) => {
                     if !is_event_we_care_about(&msg.payload) {
                         continue;
@@ -53,14 +140,22 @@ where
                     let multitenant_prefix = lattice_info.multitenant_prefix();
                     let event_subject = lattice_info.event_subject();
 
-                    // Create the reaper for this lattice. This operation returns early if it is
-                    // already running
-                    self.reaper.observe(lattice_id);
-
-                    let command_topic = DEFAULT_COMMANDS_TOPIC.replace('*', lattice_id);
+                    let command_topic = self.command_subject.lattice_filter(lattice_id);
                     let events_topic = DEFAULT_WADM_EVENT_CONSUMER_TOPIC.replace('*', lattice_id);
                     let needs_command = !self.command_manager.has_consumer(&command_topic).await;
                     let needs_event = !self.event_manager.has_consumer(&events_topic).await;
+
+                    if let Err(e) = self
+                        .lattices
+                        .observe(lattice_id, !needs_command && !needs_event)
+                    {
+                        warn!(error = %e, %lattice_id, "Ignoring event for lattice");
+                        continue;
+                    }
+
+                    // Create the reaper for this lattice. This operation returns early if it is
+                    // already running
+                    self.reaper.observe(lattice_id);
                     if needs_command {
                         debug!(%lattice_id, subject = %event_subject, mapped_subject = %command_topic, "Found unmonitored lattice, adding command consumer");
                         let worker = match self
@@ -138,3 +233,33 @@ async fn get_subscriber(
         .collect::<Result<_, anyhow::Error>>()?;
     Ok(futures::stream::select_all(subs))
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn ignores_lattices_over_the_maximum() {
+        let mut lattices = ObservedLattices::new(Some(2));
+
+        assert_eq!(lattices.observe("lattice1", false), Ok(()));
+        assert_eq!(lattices.observe("lattice2", false), Ok(()));
+        for _ in 0..3 {
+            assert_eq!(
+                lattices.observe("lattice3", false),
+                Err(LatticeLimitReached { max: 2 }),
+                "Events for lattices over the maximum should be ignored"
+            );
+        }
+        // Lattices that are already managed keep working
+        assert_eq!(lattices.observe("lattice1", false), Ok(()));
+        assert_eq!(lattices.observe("lattice2", false), Ok(()));
+        // Lattices that already have consumers are always accepted
+        assert_eq!(lattices.observe("existing", true), Ok(()));
+
+        let mut unlimited = ObservedLattices::default();
+        for i in 0..100 {
+            assert_eq!(unlimited.observe(&format!("lattice{i}"), false), Ok(()));
+        }
+    }
+}
@@ -2,7 +2,11 @@
 //! into various structs in wadm. Often times this is used for testing, but it also allows for
 //! flexibility for others who may want to publish to other sources
 
+use std::{collections::HashMap, future::IntoFuture, sync::Arc, time::Duration};
+
 use async_nats::{jetstream::Context, Client};
+use tokio::sync::Mutex;
+use tracing::{trace, warn};
 
 #[async_trait::async_trait]
 pub trait Publisher {
@@ -12,6 +16,23 @@ pub trait Publisher {
     /// The destination is optional for two reasons: Sometimes a client cannot be scoped to a
     /// specific topic and also, some implementations may not use subject/topic based delivery
     async fn publish(&self, data: Vec<u8>, destination: Option<&str>) -> anyhow::Result<()>;
+
+    /// Publishes all of the given data, each to its own destination, returning an error if any of
+    /// them failed. By default everything is published concurrently, implementors can override
+    /// this if they have a more efficient way to send many messages at once
+    async fn publish_batch(&self, messages: Vec<(Vec<u8>, String)>) -> anyhow::Result<()>
+    where
+        Self: Sync,
+    {
+        futures::future::join_all(
+            messages
+                .iter()
+                .map(|(data, destination)| self.publish(data.clone(), Some(destination))),
+        )
+        .await
+        .into_iter()
+        .collect()
+    }
 }
 
 /// The publisher implementation for a normal NATS client constrained to the given topic. This only
@@ -47,4 +68,143 @@ impl Publisher for Context {
             .map(|_| ())
             .map_err(|e| anyhow::anyhow!("Unable to verify receipt of message").context(e))
     }
+
+    /// Sends every message before waiting on any of the acks, so the whole batch only takes a
+    /// single round trip to the stream
+    async fn publish_batch(&self, messages: Vec<(Vec<u8>, String)>) -> anyhow::Result<()> {
+        let mut acks = Vec::with_capacity(messages.len());
+        for (data, destination) in messages {
+            acks.push(
+                self.publish(destination, data.into())
+                    .await
+                    .map_err(|e| anyhow::anyhow!("Unable to publish message").context(e))?,
+            );
+        }
+        futures::future::try_join_all(acks.into_iter().map(IntoFuture::into_future))
+            .await
+            .map(|_| ())
+            .map_err(|e| anyhow::anyhow!("Unable to verify receipt of message").context(e))
+    }
+}
+
+/// A publisher that debounces publishes to each destination. The first publish to a destination
+/// starts a window, and only the most recent data published to that destination during the window
+/// is sent once it elapses. The last data in a burst is always sent and different destinations
+/// never replace each other's data. This is meant for data where only the latest value matters,
+/// like statuses, which can be wrapped with
+/// [`StatusPublisher::new`](crate::workers::StatusPublisher::new).
+///
+/// Publishing only fails if the data can't be queued. Failures when the data is actually sent are
+/// logged, as they happen after the publish has returned. Clones of this publisher share the same
+/// pending data
+pub struct CoalescingPublisher<P> {
+    inner: Arc<P>,
+    window: Duration,
+    pending: Arc<Mutex<HashMap<Option<String>, Vec<u8>>>>,
+}
+
+// NOTE: Implemented manually so the wrapped publisher doesn't need to be `Clone`
+impl<P> Clone for CoalescingPublisher<P> {
+    fn clone(&self) -> Self {
+        CoalescingPublisher {
+            inner: self.inner.clone(),
+            window: self.window,
+            pending: self.pending.clone(),
+        }
+    }
+}
+
+impl<P> CoalescingPublisher<P> {
+    /// Wraps the given publisher, sending only the latest data for each destination once per
+    /// window
+    pub fn new(inner: P, window: Duration) -> CoalescingPublisher<P> {
+        CoalescingPublisher {
+            inner: Arc::new(inner),
+            window,
+            pending: Arc::default(),
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl<P: Publisher + Send + Sync + 'static> Publisher for CoalescingPublisher<P> {
+    async fn publish(&self, data: Vec<u8>, destination: Option<&str>) -> anyhow::Result<()> {
+        let destination = destination.map(ToOwned::to_owned);
+        let start_window = self
+            .pending
+            .lock()
+            .await
+            .insert(destination.clone(), data)
+            .is_none();
+        if !start_window {
+            trace!(?destination, "Replaced pending data for destination");
+            return Ok(());
+        }
+
+        let inner = self.inner.clone();
+        let pending = self.pending.clone();
+        let window = self.window;
+        tokio::spawn(async move {
+            tokio::time::sleep(window).await;
+            // Anything published after this starts a new window, so nothing is lost
+            let Some(data) = pending.lock().await.remove(&destination) else {
+                return;
+            };
+            if let Err(e) = inner.publish(data, destination.as_deref()).await {
+                warn!(error = %e, ?destination, "Unable to publish coalesced data");
+            }
+        });
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use wadm_types::api::{Status, StatusInfo};
+
+    use super::*;
+    use crate::{test_util::RecorderPublisher, workers::StatusPublisher};
+
+    #[tokio::test]
+    async fn publishes_latest_status_per_subject() {
+        let recorder = RecorderPublisher::<Status> {
+            received: Arc::default(),
+        };
+        let publisher = StatusPublisher::new(
+            CoalescingPublisher::new(recorder.clone(), Duration::from_millis(50)),
+            None,
+            "wadm.status.default",
+        );
+        let status = |message: String| Status::new(StatusInfo::reconciling(&message), Vec::new());
+
+        for count in 0..10 {
+            publisher
+                .publish_status("first", status(format!("first {count}")))
+                .await
+                .unwrap();
+        }
+        publisher
+            .publish_status("second", status("second 0".to_string()))
+            .await
+            .unwrap();
+        assert!(
+            recorder.received.read().await.is_empty(),
+            "Nothing should be published until the window elapses"
+        );
+
+        tokio::time::sleep(Duration::from_millis(150)).await;
+        let mut received = recorder
+            .received
+            .read()
+            .await
+            .iter()
+            .map(|status| status.info.message.clone())
+            .collect::<Vec<_>>();
+        received.sort();
+        assert_eq!(
+            received,
+            vec!["first 9".to_string(), "second 0".to_string()],
+            "Only the last status for each subject should be published"
+        );
+    }
 }
@@ -0,0 +1,163 @@
+//! Settings that can be changed while wadm is running, along with a listener that applies updates
+//! sent over NATS
+
+use std::time::Duration;
+
+use anyhow::bail;
+use futures::StreamExt;
+use serde::{Deserialize, Serialize};
+use tokio::sync::watch;
+use tracing::{info, warn};
+
+use crate::config::WadmConfig;
+
+/// The settings that can be changed without restarting wadm. Running components read these from a
+/// [`watch::Receiver`], so an update takes effect without dropping any state or consumers
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RuntimeConfig {
+    /// How long hosts have to heartbeat before they are removed. The reaper checks for hosts to
+    /// remove twice per interval
+    pub cleanup_interval: Duration,
+    /// How long to wait after a host starts before placing anything on it. This only applies to
+    /// scalers created after it changes
+    pub host_quiet_period: Duration,
+    /// How long newly started component instances are protected from scaling down. This only
+    /// applies to scalers created after it changes
+    pub component_start_cooldown: Duration,
+    /// How long models are considered to be tearing down after they are undeployed
+    pub teardown_period: Duration,
+}
+
+impl From<&WadmConfig> for RuntimeConfig {
+    fn from(config: &WadmConfig) -> RuntimeConfig {
+        RuntimeConfig {
+            cleanup_interval: Duration::from_secs(config.cleanup_interval),
+            host_quiet_period: Duration::from_secs(config.host_quiet_period),
+            component_start_cooldown: Duration::from_secs(config.component_start_cooldown),
+            teardown_period: Duration::from_secs(config.teardown_period),
+        }
+    }
+}
+
+/// A change to the [`RuntimeConfig`], with every value in seconds. Settings that aren't given are
+/// left as they are
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RuntimeConfigUpdate {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cleanup_interval: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub host_quiet_period: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub component_start_cooldown: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub teardown_period: Option<u64>,
+}
+
+impl RuntimeConfigUpdate {
+    /// Returns the given config with this update applied, or an error if the update is invalid
+    pub fn apply(&self, config: RuntimeConfig) -> anyhow::Result<RuntimeConfig> {
+        if self.cleanup_interval == Some(0) {
+            bail!("The cleanup interval must be greater than 0");
+        }
+        Ok(RuntimeConfig {
+            cleanup_interval: self
+                .cleanup_interval
+                .map_or(config.cleanup_interval, Duration::from_secs),
+            host_quiet_period: self
+                .host_quiet_period
+                .map_or(config.host_quiet_period, Duration::from_secs),
+            component_start_cooldown: self
+                .component_start_cooldown
+                .map_or(config.component_start_cooldown, Duration::from_secs),
+            teardown_period: self
+                .teardown_period
+                .map_or(config.teardown_period, Duration::from_secs),
+        })
+    }
+}
+
+/// Listens for [`RuntimeConfigUpdate`]s published as JSON on the given subject and applies them to
+/// the shared config. If the message has a reply subject, the reply is the resulting config (in
+/// seconds) or the error that stopped the update from being applied
+pub(crate) async fn listen_for_updates(
+    client: async_nats::Client,
+    subject: String,
+    config: watch::Sender<RuntimeConfig>,
+) -> anyhow::Result<()> {
+    let mut sub = client.subscribe(subject.clone()).await?;
+    loop {
+        let Some(msg) = sub.next().await else {
+            warn!("Config reload subscriber hang up. Attempting to restart");
+            sub = client.subscribe(subject.clone()).await?;
+            continue;
+        };
+        let result = serde_json::from_slice::<RuntimeConfigUpdate>(&msg.payload)
+            .map_err(anyhow::Error::from)
+            .and_then(|update| update.apply(*config.borrow()));
+        let reply = match result {
+            Ok(updated) => {
+                info!(?updated, "Applying runtime config update");
+                config.send_replace(updated);
+                serde_json::json!({
+                    "result": "success",
+                    "config": RuntimeConfigUpdate {
+                        cleanup_interval: Some(updated.cleanup_interval.as_secs()),
+                        host_quiet_period: Some(updated.host_quiet_period.as_secs()),
+                        component_start_cooldown: Some(
+                            updated.component_start_cooldown.as_secs(),
+                        ),
+                        teardown_period: Some(updated.teardown_period.as_secs()),
+                    },
+                })
+            }
+            Err(e) => {
+                warn!(error = %e, "Ignoring invalid runtime config update");
+                serde_json::json!({ "result": "error", "message": e.to_string() })
+            }
+        };
+        if let Some(reply_to) = msg.reply {
+            if let Err(e) = client.publish(reply_to, reply.to_string().into()).await {
+                warn!(error = %e, "Unable to reply to runtime config update");
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn applies_partial_updates() {
+        let config = RuntimeConfig {
+            cleanup_interval: Duration::from_secs(70),
+            host_quiet_period: Duration::ZERO,
+            component_start_cooldown: Duration::from_secs(60),
+            teardown_period: Duration::from_secs(30),
+        };
+
+        let update: RuntimeConfigUpdate =
+            serde_json::from_str(r#"{"cleanup_interval": 20, "teardown_period": 0}"#).unwrap();
+        assert_eq!(
+            update.apply(config).unwrap(),
+            RuntimeConfig {
+                cleanup_interval: Duration::from_secs(20),
+                host_quiet_period: Duration::ZERO,
+                component_start_cooldown: Duration::from_secs(60),
+                teardown_period: Duration::ZERO,
+            }
+        );
+
+        assert!(RuntimeConfigUpdate {
+            cleanup_interval: Some(0),
+            ..Default::default()
+        }
+        .apply(config)
+        .is_err());
+        assert!(
+            serde_json::from_str::<RuntimeConfigUpdate>(r#"{"max_jobs": 4}"#).is_err(),
+            "Settings that can't be changed at runtime should be rejected"
+        );
+    }
+}
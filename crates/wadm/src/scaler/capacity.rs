@@ -0,0 +1,177 @@
+//! Contains a shared allocator for dividing limited host capacity fairly between models
+
+use std::{
+    collections::BTreeMap,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, RwLock,
+    },
+};
+
+/// Divides a fixed number of component instances per host between all of the models in a lattice
+/// that want them. Every scaler that places instances claims the number of instances it wants, and
+/// each model gets a max-min fair share of every host: models that want less than an even split
+/// get everything they ask for and the rest is split evenly between the others. This keeps
+/// whichever model reconciles first from taking all of the capacity. Clones share the same claims
+#[derive(Debug, Clone)]
+pub struct HostCapacity {
+    per_host: usize,
+    claims: Arc<RwLock<BTreeMap<u64, ClaimEntry>>>,
+    next_id: Arc<AtomicU64>,
+}
+
+#[derive(Debug, Clone)]
+struct ClaimEntry {
+    model_name: String,
+    instances: usize,
+}
+
+/// The capacity a single claim can use on each host
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct CapacityLimit {
+    /// The total number of component instances that fit on a host
+    pub per_host: usize,
+    /// The number of instances the claim may run on a single host
+    pub share: usize,
+}
+
+impl HostCapacity {
+    /// Creates a new allocator for hosts that can each run `per_host` component instances
+    pub fn new(per_host: usize) -> HostCapacity {
+        HostCapacity {
+            per_host,
+            claims: Arc::default(),
+            next_id: Arc::default(),
+        }
+    }
+
+    /// Claims capacity for `instances` instances on behalf of a scaler in the given model. The
+    /// claim is released when it is dropped
+    pub fn claim(&self, model_name: &str, instances: usize) -> CapacityClaim {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        self.claims.write().unwrap().insert(
+            id,
+            ClaimEntry {
+                model_name: model_name.to_owned(),
+                instances,
+            },
+        );
+        CapacityClaim {
+            capacity: self.clone(),
+            id,
+        }
+    }
+
+    /// Returns a copy of this allocator with everything currently claimed by models other than
+    /// the given one. The copy doesn't share claims with this allocator, so claims made against it
+    /// don't change the share of any running scaler
+    pub fn detached_without(&self, model_name: &str) -> HostCapacity {
+        let claims = self
+            .claims
+            .read()
+            .unwrap()
+            .iter()
+            .filter(|(_, entry)| entry.model_name != model_name)
+            .map(|(id, entry)| (*id, entry.clone()))
+            .collect();
+        HostCapacity {
+            per_host: self.per_host,
+            claims: Arc::new(RwLock::new(claims)),
+            next_id: Arc::new(AtomicU64::new(self.next_id.load(Ordering::Relaxed))),
+        }
+    }
+
+    fn limit(&self, id: u64) -> CapacityLimit {
+        let claims = self.claims.read().unwrap();
+        let Some(model_name) = claims.get(&id).map(|entry| entry.model_name.as_str()) else {
+            return CapacityLimit {
+                per_host: self.per_host,
+                share: 0,
+            };
+        };
+
+        let mut model_demand: BTreeMap<&str, usize> = BTreeMap::new();
+        for entry in claims.values() {
+            *model_demand.entry(&entry.model_name).or_default() += entry.instances;
+        }
+        let model_share = fair_shares(self.per_host, model_demand)
+            .get(model_name)
+            .copied()
+            .unwrap_or_default();
+        // The model's share is then divided the same way between the scalers in the model
+        let share = fair_shares(
+            model_share,
+            claims
+                .iter()
+                .filter(|(_, entry)| entry.model_name == model_name)
+                .map(|(id, entry)| (*id, entry.instances))
+                .collect(),
+        )
+        .get(&id)
+        .copied()
+        .unwrap_or_default();
+
+        CapacityLimit {
+            per_host: self.per_host,
+            share,
+        }
+    }
+}
+
+/// A claim on a share of [`HostCapacity`], released when dropped
+#[derive(Debug)]
+pub struct CapacityClaim {
+    capacity: HostCapacity,
+    id: u64,
+}
+
+impl CapacityClaim {
+    /// Updates the number of instances this claim wants
+    pub fn set_instances(&self, instances: usize) {
+        if let Some(entry) = self.capacity.claims.write().unwrap().get_mut(&self.id) {
+            entry.instances = instances;
+        }
+    }
+
+    /// Returns the capacity this claim can currently use on each host, based on everything else
+    /// that is claimed
+    pub fn limit(&self) -> CapacityLimit {
+        self.capacity.limit(self.id)
+    }
+}
+
+impl Drop for CapacityClaim {
+    fn drop(&mut self) {
+        self.capacity.claims.write().unwrap().remove(&self.id);
+    }
+}
+
+/// Divides `available` between the given demands so that no demand can get more without taking
+/// from a smaller one. Demands that fit in an even split are satisfied and whatever they leave is
+/// split between the rest, with any remainder going to the keys that sort first
+fn fair_shares<K: Ord>(available: usize, demands: BTreeMap<K, usize>) -> BTreeMap<K, usize> {
+    let mut shares = BTreeMap::new();
+    let mut remaining = available;
+    let mut unsatisfied = demands.into_iter().collect::<Vec<_>>();
+    while !unsatisfied.is_empty() {
+        let even = remaining / unsatisfied.len();
+        let (satisfied, rest): (Vec<_>, Vec<_>) = unsatisfied
+            .into_iter()
+            .partition(|(_, demand)| *demand <= even);
+        if satisfied.is_empty() {
+            let extra = remaining % rest.len();
+            shares.extend(
+                rest.into_iter()
+                    .enumerate()
+                    .map(|(i, (key, _))| (key, even + usize::from(i < extra))),
+            );
+            break;
+        }
+        for (key, demand) in satisfied {
+            remaining -= demand;
+            shares.insert(key, demand);
+        }
+        unsatisfied = rest;
+    }
+    shares
+}
@@ -0,0 +1,112 @@
+//! Contains a bounded pool for running CPU heavy scaler computations off of the async runtime
+
+use std::sync::Arc;
+
+use anyhow::Result;
+use tokio::sync::Semaphore;
+
+/// A bounded pool for running CPU heavy work, like computing placements for large lattices, on
+/// tokio's blocking threads so that it doesn't hold up event processing. At most `max_concurrent`
+/// computations run at the same time and the rest wait their turn. Clones share the same bound
+#[derive(Debug, Clone)]
+pub struct ComputePool {
+    permits: Arc<Semaphore>,
+}
+
+impl ComputePool {
+    /// Creates a new pool that runs at most `max_concurrent` computations at the same time. A
+    /// value of 0 is treated as 1
+    pub fn new(max_concurrent: usize) -> ComputePool {
+        ComputePool {
+            permits: Arc::new(Semaphore::new(max_concurrent.max(1))),
+        }
+    }
+
+    /// Runs the given computation on the pool, waiting for a free slot first
+    pub async fn run<F, T>(&self, compute: F) -> Result<T>
+    where
+        F: FnOnce() -> T + Send + 'static,
+        T: Send + 'static,
+    {
+        let permit = self.permits.clone().acquire_owned().await?;
+        // The permit moves into the task so the slot is held until the computation is done, even if
+        // the caller stops waiting on it
+        tokio::task::spawn_blocking(move || {
+            let _permit = permit;
+            compute()
+        })
+        .await
+        .map_err(anyhow::Error::from)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{
+        sync::atomic::{AtomicUsize, Ordering},
+        time::{Duration, Instant},
+    };
+
+    use super::*;
+
+    /// Burns CPU for the given duration, like a large reconcile would
+    fn busy_compute(duration: Duration) -> u64 {
+        let start = Instant::now();
+        let mut iterations = 0u64;
+        while start.elapsed() < duration {
+            iterations = iterations.wrapping_add(1);
+        }
+        iterations
+    }
+
+    // A single worker thread means anything computed inline would block all event processing
+    #[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+    async fn event_processing_stays_responsive_during_large_reconciles() {
+        let pool = ComputePool::new(2);
+        let running = Arc::new(AtomicUsize::new(0));
+        let max_running = Arc::new(AtomicUsize::new(0));
+
+        let reconciles = (0..4)
+            .map(|_| {
+                let pool = pool.clone();
+                let running = running.clone();
+                let max_running = max_running.clone();
+                tokio::spawn(async move {
+                    pool.run(move || {
+                        let now = running.fetch_add(1, Ordering::SeqCst) + 1;
+                        max_running.fetch_max(now, Ordering::SeqCst);
+                        let result = busy_compute(Duration::from_millis(250));
+                        running.fetch_sub(1, Ordering::SeqCst);
+                        result
+                    })
+                    .await
+                })
+            })
+            .collect::<Vec<_>>();
+
+        // Handle "events" while the reconciles are running, each of which should only take as long
+        // as its own work
+        let mut worst = Duration::ZERO;
+        for _ in 0..20 {
+            let start = Instant::now();
+            tokio::time::sleep(Duration::from_millis(10)).await;
+            worst = worst.max(start.elapsed());
+        }
+        assert!(
+            worst < Duration::from_millis(100),
+            "Event processing should not be blocked by reconciles, worst latency was {worst:?}"
+        );
+
+        for reconcile in reconciles {
+            reconcile
+                .await
+                .unwrap()
+                .expect("Computation should complete");
+        }
+        assert_eq!(
+            max_running.load(Ordering::SeqCst),
+            2,
+            "No more than the configured number of computations should run at once"
+        );
+    }
+}
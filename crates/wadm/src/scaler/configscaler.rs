@@ -258,6 +258,7 @@ mod test {
                     version: semver::Version::new(0, 0, 0),
                     uptime_human: String::default(),
                     uptime_seconds: 0,
+                    utilization: None,
                 }))
                 .await
                 .expect("handle_event should succeed"),
@@ -327,6 +328,7 @@ mod test {
                     version: semver::Version::new(0, 0, 0),
                     uptime_human: String::default(),
                     uptime_seconds: 0,
+                    utilization: None,
                 }))
                 .await
                 .expect("handle_event should succeed"),
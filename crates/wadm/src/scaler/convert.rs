@@ -1,38 +1,48 @@
 //! Contains code for converting the list of [`Component`]s in an application into a list of [`Scaler`]s
 //! that are responsible for monitoring and enforcing the desired state of a lattice
 
-use std::{collections::HashMap, time::Duration};
+use std::{
+    collections::{BTreeMap, HashMap},
+    time::Duration,
+};
 
 use anyhow::Result;
 use tracing::{error, warn};
 use wadm_types::{
     api::StatusInfo, CapabilityProperties, Component, ComponentProperties, ConfigProperty,
-    LinkProperty, Policy, Properties, SecretProperty, SharedApplicationComponentProperties,
-    SpreadScalerProperty, Trait, TraitProperty, DAEMONSCALER_TRAIT, LINK_TRAIT, SPREADSCALER_TRAIT,
+    LinkProperty, Manifest, Policy, Properties, SecretProperty,
+    SharedApplicationComponentProperties, SpreadScalerProperty, Trait, TraitProperty,
+    DAEMONSCALER_TRAIT, LINK_TRAIT, METRICSCALER_TRAIT, SPREADSCALER_TRAIT,
 };
 use wasmcloud_secrets_types::SECRET_PREFIX;
 
 use crate::{
     publisher::Publisher,
     scaler::{
-        spreadscaler::{link::LINK_SCALER_KIND, ComponentSpreadScaler, SPREAD_SCALER_KIND},
+        spreadscaler::{
+            link::LINK_SCALER_KIND, ComponentSpreadScaler, ScaleDownPolicy, SPREAD_SCALER_KIND,
+        },
         statusscaler::StatusScaler,
-        Scaler,
+        ComponentScalerConfig, Scaler,
     },
     storage::{snapshot::SnapshotStore, ReadStore},
-    workers::{ConfigSource, LinkSource, SecretSource},
+    workers::{ConfigSource, InstanceAnnotations, LinkSource, SecretSource},
     DEFAULT_LINK_NAME,
 };
 
 use super::{
     configscaler::ConfigScaler,
     daemonscaler::{provider::ProviderDaemonScaler, ComponentDaemonScaler},
+    metricscaler::{MetricScaler, MetricScalerProperty, METRIC_SCALER_KIND},
+    normalize_reference,
+    registry::{ScalerContext, ScalerRegistry},
     secretscaler::SecretScaler,
+    shadowscaler::ShadowScaler,
     spreadscaler::{
         link::{LinkScaler, LinkScalerConfig},
         provider::{ProviderSpreadConfig, ProviderSpreadScaler},
     },
-    BackoffWrapper,
+    BackoffWrapper, ComputePool, HostCapacity,
 };
 
 pub(crate) type BoxedScaler = Box<dyn Scaler + Send + Sync + 'static>;
@@ -40,35 +50,227 @@ pub(crate) type ScalerList = Vec<BoxedScaler>;
 
 const EMPTY_TRAIT_VEC: Vec<Trait> = Vec::new();
 
+/// Manifest wide options that change how the built in scalers behave
+#[derive(Debug, Clone, Default)]
+pub(crate) struct ScalerOptions {
+    /// Whether spread scalers should fail when multiple versions are running
+    pub uniform_versions: bool,
+    /// How component spread scalers choose which instances to stop
+    pub scale_down_policy: ScaleDownPolicy,
+    /// Extra annotations added to every component and provider started for the manifest
+    pub instance_annotations: BTreeMap<String, String>,
+    /// The pool component spread scalers compute their commands on, if any
+    pub compute_pool: Option<ComputePool>,
+    /// How long after a host starts before anything can be placed on it
+    pub host_quiet_period: Duration,
+    /// How long component spread scalers avoid stopping instances they just started
+    pub start_cooldown: Duration,
+    /// The host capacity component spread scalers share with other models, if it is limited
+    pub host_capacity: Option<HostCapacity>,
+    /// Whether scalers notify other wadm instances with event fingerprints rather than full events
+    pub compact_notifications: bool,
+    /// Whether image references pinned to a digest have their tag removed, so that re-tagging the
+    /// same image doesn't change scaler IDs
+    pub stable_ids: bool,
+    /// How long provider spread scalers avoid a host after the provider failed to start on it
+    pub provider_retry_cooldown: Duration,
+    /// How far component spreads can be from their desired count before they are corrected
+    pub count_tolerance: usize,
+    /// The most commands a scaler can issue in a single pass, if it is limited
+    pub command_budget: Option<usize>,
+    /// Whether component scalers only place components on hosts with providers for the
+    /// capabilities they require
+    pub check_capabilities: bool,
+    /// Whether component spread scalers issue commands with absolute instance counts
+    pub declarative_commands: bool,
+}
+
+impl ScalerOptions {
+    /// Reads the options from the manifest's annotations, along with the configured instance
+    /// annotations. Invalid options are logged and their defaults are used instead
+    pub(crate) fn from_manifest(
+        manifest: &Manifest,
+        instance_annotations: &InstanceAnnotations,
+    ) -> ScalerOptions {
+        let scale_down_policy = manifest
+            .scale_down_policy()
+            .and_then(|raw| match raw.parse() {
+                Ok(policy) => Some(policy),
+                Err(e) => {
+                    let name = &manifest.metadata.name;
+                    warn!(error = %e, %name, "Ignoring invalid scale down policy");
+                    None
+                }
+            })
+            .unwrap_or_default();
+        let provider_retry_cooldown = manifest
+            .provider_retry_cooldown()
+            .and_then(|raw| match raw.trim().parse() {
+                Ok(secs) => Some(Duration::from_secs(secs)),
+                Err(e) => {
+                    let name = &manifest.metadata.name;
+                    warn!(error = %e, %name, "Ignoring invalid provider retry cooldown");
+                    None
+                }
+            })
+            .unwrap_or_default();
+        let count_tolerance = manifest
+            .count_tolerance()
+            .and_then(|raw| match raw.trim().parse() {
+                Ok(tolerance) => Some(tolerance),
+                Err(e) => {
+                    let name = &manifest.metadata.name;
+                    warn!(error = %e, %name, "Ignoring invalid count tolerance");
+                    None
+                }
+            })
+            .unwrap_or_default();
+        let command_budget = manifest
+            .command_budget()
+            .and_then(|raw| match raw.trim().parse() {
+                Ok(0) => {
+                    let name = &manifest.metadata.name;
+                    warn!(%name, "Ignoring command budget of zero");
+                    None
+                }
+                Ok(budget) => Some(budget),
+                Err(e) => {
+                    let name = &manifest.metadata.name;
+                    warn!(error = %e, %name, "Ignoring invalid command budget");
+                    None
+                }
+            });
+        ScalerOptions {
+            uniform_versions: manifest.requires_uniform_versions(),
+            scale_down_policy,
+            instance_annotations: instance_annotations.for_manifest(manifest),
+            compute_pool: None,
+            host_quiet_period: Duration::ZERO,
+            start_cooldown: Duration::ZERO,
+            host_capacity: None,
+            compact_notifications: false,
+            stable_ids: manifest.uses_stable_scaler_ids(),
+            provider_retry_cooldown,
+            count_tolerance,
+            command_budget,
+            check_capabilities: manifest.checks_capabilities(),
+            declarative_commands: manifest.uses_declarative_commands(),
+        }
+    }
+
+    /// Returns the image reference the built in scalers should use for the given image
+    fn image_reference(&self, image: &str) -> String {
+        if self.stable_ids {
+            normalize_reference(image).into_owned()
+        } else {
+            image.to_owned()
+        }
+    }
+}
+
+/// Everything shared by the scalers created for a single manifest
+pub(crate) struct ManifestContext<'a, S, P, L> {
+    /// The lattice id the scalers operate on
+    pub lattice_id: &'a str,
+    /// The name of the manifest that the scalers are being created for
+    pub manifest_name: &'a str,
+    /// The version of the manifest that the scalers are being created for
+    pub manifest_version: &'a str,
+    /// The subject the scalers use to report status
+    pub notifier_subject: &'a str,
+    /// The publisher the scalers use to report status
+    pub notifier: &'a P,
+    /// The store the scalers use to access lattice state
+    pub snapshot_data: &'a SnapshotStore<S, L>,
+    /// The policies the scalers use to access secrets
+    pub policies: &'a HashMap<&'a String, &'a Policy>,
+    /// Factories for custom trait types, which take precedence over the built in scalers
+    pub registry: &'a ScalerRegistry,
+    /// Manifest wide options applied to the built in scalers
+    pub options: ScalerOptions,
+}
+
+impl<S, P, L> ManifestContext<'_, S, P, L> {
+    /// Creates a [`StatusScaler`] reporting the given status in place of a scaler that couldn't be
+    /// created. The ID is derived from the manifest so it stays the same each time the scalers are
+    /// rebuilt
+    fn status_scaler(&self, kind: &str, name: &str, status: StatusInfo) -> BoxedScaler {
+        Box::new(StatusScaler::for_manifest(
+            self.manifest_name,
+            self.manifest_version,
+            kind,
+            name,
+            status,
+        ))
+    }
+}
+
 /// Converts a list of manifest [`Component`]s into a [`ScalerList`], resolving shared application
 /// references, links, configuration and secrets as necessary.
 ///
 /// # Arguments
 /// * `components` - The list of components to convert
-/// * `policies` - The policies to use when creating the scalers so they can access secrets
-/// * `lattice_id` - The lattice id the scalers operate on
-/// * `notifier` - The publisher to use when creating the scalers so they can report status
-/// * `name` - The name of the manifest that the scalers are being created for
-/// * `notifier_subject` - The subject to use when creating the scalers so they can report status
-/// * `snapshot_data` - The store to use when creating the scalers so they can access lattice state
+/// * `shadowed` - The names of components whose scalers should be wrapped in a [`ShadowScaler`]
+/// * `context` - Everything shared by the scalers created for the manifest
 pub(crate) fn manifest_components_to_scalers<S, P, L>(
     components: &[Component],
-    policies: &HashMap<&String, &Policy>,
-    lattice_id: &str,
-    manifest_name: &str,
-    notifier_subject: &str,
-    notifier: &P,
-    snapshot_data: &SnapshotStore<S, L>,
+    shadowed: &[&str],
+    context: &ManifestContext<S, P, L>,
 ) -> ScalerList
 where
     S: ReadStore + Send + Sync + Clone + 'static,
     P: Publisher + Clone + Send + Sync + 'static,
     L: LinkSource + ConfigSource + SecretSource + Clone + Send + Sync + 'static,
 {
+    manifest_components_to_scaler_groups(components, shadowed, context)
+        .into_iter()
+        .flatten()
+        .collect()
+}
+
+/// Same as [`manifest_components_to_scalers`], but returns the scalers of each component
+/// separately, in the same order as the given components
+pub(crate) fn manifest_components_to_scaler_groups<S, P, L>(
+    components: &[Component],
+    shadowed: &[&str],
+    context: &ManifestContext<S, P, L>,
+) -> Vec<ScalerList>
+where
+    S: ReadStore + Send + Sync + Clone + 'static,
+    P: Publisher + Clone + Send + Sync + 'static,
+    L: LinkSource + ConfigSource + SecretSource + Clone + Send + Sync + 'static,
+{
+    let &ManifestContext {
+        lattice_id,
+        manifest_name,
+        notifier,
+        registry,
+        ..
+    } = context;
     let mut scalers: ScalerList = Vec::new();
-    components
-        .iter()
-        .for_each(|component| match &component.properties {
+    // The index of the first scaler created for each component, used to split the scalers back up
+    // by component
+    let mut component_starts = Vec::with_capacity(components.len());
+    components.iter().for_each(|component| {
+        component_starts.push(scalers.len());
+        // Any trait with a registered factory is handled by that factory instead of the built in
+        // scalers
+        let builtin_traits;
+        let traits = if registry.is_empty() {
+            component.traits.as_ref()
+        } else {
+            custom_scalers(&mut scalers, component, context);
+            builtin_traits = component
+                .traits
+                .iter()
+                .flatten()
+                .filter(|trt| !registry.contains(&trt.trait_type))
+                .cloned()
+                .collect::<Vec<_>>();
+            Some(&builtin_traits)
+        };
+
+        match &component.properties {
             Properties::Component { properties } => {
                 // Determine if this component is contained in this manifest or a shared application
                 let (application_name, component_name) = match resolve_manifest_component(
@@ -80,12 +282,11 @@ where
                     Ok(names) => names,
                     Err(err) => {
                         error!(err);
-                        scalers.push(Box::new(StatusScaler::new(
-                            uuid::Uuid::new_v4().to_string(),
+                        scalers.push(context.status_scaler(
                             SPREAD_SCALER_KIND,
                             &component.name,
                             StatusInfo::failed(err),
-                        )) as BoxedScaler);
+                        ));
                         return;
                     }
                 };
@@ -94,15 +295,10 @@ where
                     &mut scalers,
                     components,
                     properties,
-                    component.traits.as_ref(),
-                    manifest_name,
+                    traits,
                     application_name,
                     component_name,
-                    lattice_id,
-                    policies,
-                    notifier_subject,
-                    notifier,
-                    snapshot_data,
+                    context,
                 )
             }
             Properties::Capability { properties } => {
@@ -116,12 +312,11 @@ where
                     Ok(names) => names,
                     Err(err) => {
                         error!(err);
-                        scalers.push(Box::new(StatusScaler::new(
-                            uuid::Uuid::new_v4().to_string(),
+                        scalers.push(context.status_scaler(
                             SPREAD_SCALER_KIND,
                             &component.name,
                             StatusInfo::failed(err),
-                        )) as BoxedScaler);
+                        ));
                         return;
                     }
                 };
@@ -129,19 +324,75 @@ where
                     &mut scalers,
                     components,
                     properties,
-                    component.traits.as_ref(),
-                    manifest_name,
+                    traits,
                     application_name,
                     component_name,
-                    lattice_id,
-                    policies,
-                    notifier_subject,
-                    notifier,
-                    snapshot_data,
+                    context,
                 )
             }
-        });
-    scalers
+        }
+    });
+
+    let mut groups = Vec::with_capacity(components.len());
+    for start in component_starts.into_iter().rev() {
+        groups.push(scalers.split_off(start));
+    }
+    groups.reverse();
+    groups
+        .into_iter()
+        .zip(components)
+        .map(|(group, component)| {
+            if !shadowed.contains(&component.name.as_str()) {
+                return group;
+            }
+            group
+                .into_iter()
+                .map(|scaler| {
+                    Box::new(ShadowScaler::new(
+                        scaler,
+                        notifier.clone(),
+                        lattice_id,
+                        manifest_name,
+                    )) as BoxedScaler
+                })
+                .collect()
+        })
+        .collect()
+}
+
+/// Helper function that extends a [`ScalerList`] with scalers created by the registered
+/// [`ScalerFactory`](super::registry::ScalerFactory) for each custom trait of a [`Component`]. If a
+/// factory fails, a [`StatusScaler`] is added in its place so the failure shows up in the status
+fn custom_scalers<S, P, L>(
+    scalers: &mut ScalerList,
+    component: &Component,
+    context: &ManifestContext<S, P, L>,
+) {
+    let &ManifestContext {
+        lattice_id,
+        manifest_name,
+        notifier_subject,
+        registry,
+        ..
+    } = context;
+    scalers.extend(component.traits.iter().flatten().filter_map(|trt| {
+        let factory = registry.get(&trt.trait_type)?;
+        let ctx = ScalerContext {
+            lattice_id,
+            manifest_name,
+            notifier_subject,
+            component,
+            scaler_trait: trt,
+        };
+        Some(factory.create(ctx).unwrap_or_else(|e| {
+            error!(error = %e, trait_type = %trt.trait_type, "Unable to create custom scaler");
+            context.status_scaler(
+                &trt.trait_type,
+                &component.name,
+                StatusInfo::failed(&e.to_string()),
+            )
+        }))
+    }));
 }
 
 /// Helper function, primarily to remove nesting, that extends a [`ScalerList`] with all scalers
@@ -152,34 +403,32 @@ where
 /// * `components` - The list of components to convert
 /// * `properties` - The properties of the component to convert
 /// * `traits` - The traits of the component to convert
-/// * `manifest_name` - The name of the manifest that the scalers are being created for
 /// * `application_name` - The name of the application that the scalers are being created for
 /// * `component_name` - The name of the component to convert
-/// * **The following arguments are required to create scalers, passed directly through to the scaler
-/// * `lattice_id` - The lattice id the scalers operate on
-/// * `policies` - The policies to use when creating the scalers so they can access secrets
-/// * `notifier_subject` - The subject to use when creating the scalers so they can report status
-/// * `notifier` - The publisher to use when creating the scalers so they can report status
-/// * `snapshot_data` - The store to use when creating the scalers so they can access lattice state
-#[allow(clippy::too_many_arguments)]
+/// * `context` - Everything shared by the scalers created for the manifest
 fn component_scalers<S, P, L>(
     scalers: &mut ScalerList,
     components: &[Component],
     properties: &ComponentProperties,
     traits: Option<&Vec<Trait>>,
-    manifest_name: &str,
     application_name: &str,
     component_name: &str,
-    lattice_id: &str,
-    policies: &HashMap<&String, &Policy>,
-    notifier_subject: &str,
-    notifier: &P,
-    snapshot_data: &SnapshotStore<S, L>,
+    context: &ManifestContext<S, P, L>,
 ) where
     S: ReadStore + Send + Sync + Clone + 'static,
     P: Publisher + Clone + Send + Sync + 'static,
     L: LinkSource + ConfigSource + SecretSource + Clone + Send + Sync + 'static,
 {
+    let &ManifestContext {
+        lattice_id,
+        manifest_name,
+        notifier_subject,
+        notifier,
+        snapshot_data,
+        policies,
+        ref options,
+        ..
+    } = context;
     scalers.extend(traits.unwrap_or(&EMPTY_TRAIT_VEC).iter().filter_map(|trt| {
         // If an image is specified, then it's a component in the same manifest. Otherwise, it's a shared component
         let component_id = if properties.image.is_some() {
@@ -197,8 +446,12 @@ fn component_scalers<S, P, L>(
         );
 
         config_names.append(&mut secret_names.clone());
+        let image = properties
+            .image
+            .as_deref()
+            .map(|image| options.image_reference(image));
         // TODO(#451): Consider a way to report on status of a shared component
-        match (trt.trait_type.as_str(), &trt.properties, &properties.image) {
+        match (trt.trait_type.as_str(), &trt.properties, &image) {
             // Shared application components already have their own spread/daemon scalers, you
             // cannot modify them from another manifest
             (SPREADSCALER_TRAIT, TraitProperty::SpreadScaler(_), None) => {
@@ -216,83 +469,121 @@ fn component_scalers<S, P, L>(
             (SPREADSCALER_TRAIT, TraitProperty::SpreadScaler(p), Some(image_ref)) => {
                 // If the image is not specified, then it's a reference to a shared provider
                 // in a different manifest
-                Some(Box::new(BackoffWrapper::new(
-                    ComponentSpreadScaler::new(
-                        snapshot_data.clone(),
-                        image_ref.clone(),
-                        component_id,
-                        lattice_id.to_owned(),
-                        application_name.to_owned(),
-                        p.to_owned(),
-                        component_name,
-                        config_names,
-                    ),
-                    notifier.clone(),
-                    config_scalers,
-                    secret_scalers,
-                    notifier_subject,
-                    application_name,
-                    Some(Duration::from_secs(5)),
-                )) as BoxedScaler)
+                Some(Box::new(
+                    BackoffWrapper::new(
+                        ComponentSpreadScaler::new(
+                            snapshot_data.clone(),
+                            ComponentScalerConfig {
+                                lattice_id: lattice_id.to_owned(),
+                                component_id,
+                                component_reference: image_ref.clone(),
+                                model_name: application_name.to_owned(),
+                                component_config: config_names,
+                            },
+                            p.to_owned(),
+                            component_name,
+                        )
+                        .with_uniform_versions(options.uniform_versions)
+                        .with_scale_down_policy(options.scale_down_policy)
+                        .with_count_tolerance(options.count_tolerance)
+                        .with_compute_pool(options.compute_pool.clone())
+                        .with_host_quiet_period(options.host_quiet_period)
+                        .with_start_cooldown(options.start_cooldown)
+                        .with_host_capacity(options.host_capacity.as_ref())
+                        .with_capability_check(options.check_capabilities)
+                        .with_declarative_commands(options.declarative_commands),
+                        notifier.clone(),
+                        config_scalers,
+                        secret_scalers,
+                        notifier_subject,
+                        application_name,
+                        Some(Duration::from_secs(5)),
+                        None,
+                        None,
+                    )
+                    .with_instance_annotations(options.instance_annotations.clone())
+                    .with_compact_notifications(options.compact_notifications)
+                    .with_command_budget(options.command_budget),
+                ) as BoxedScaler)
             }
             (DAEMONSCALER_TRAIT, TraitProperty::SpreadScaler(p), Some(image_ref)) => {
-                Some(Box::new(BackoffWrapper::new(
-                    ComponentDaemonScaler::new(
-                        snapshot_data.clone(),
-                        image_ref.to_owned(),
-                        component_id,
-                        lattice_id.to_owned(),
-                        application_name.to_owned(),
-                        p.to_owned(),
-                        component_name,
-                        config_names,
-                    ),
-                    notifier.clone(),
-                    config_scalers,
-                    secret_scalers,
-                    notifier_subject,
-                    application_name,
-                    Some(Duration::from_secs(5)),
-                )) as BoxedScaler)
+                Some(Box::new(
+                    BackoffWrapper::new(
+                        ComponentDaemonScaler::new(
+                            snapshot_data.clone(),
+                            ComponentScalerConfig {
+                                lattice_id: lattice_id.to_owned(),
+                                component_id,
+                                component_reference: image_ref.to_owned(),
+                                model_name: application_name.to_owned(),
+                                component_config: config_names,
+                            },
+                            p.to_owned(),
+                            component_name,
+                        )
+                        .with_host_quiet_period(options.host_quiet_period)
+                        .with_capability_check(options.check_capabilities),
+                        notifier.clone(),
+                        config_scalers,
+                        secret_scalers,
+                        notifier_subject,
+                        application_name,
+                        Some(Duration::from_secs(5)),
+                        None,
+                        None,
+                    )
+                    .with_instance_annotations(options.instance_annotations.clone())
+                    .with_compact_notifications(options.compact_notifications)
+                    .with_command_budget(options.command_budget),
+                ) as BoxedScaler)
+            }
+            (METRICSCALER_TRAIT, TraitProperty::Custom(_), Some(image_ref)) => {
+                let property = match MetricScalerProperty::try_from(trt.properties.clone()) {
+                    Ok(property) => property,
+                    Err(e) => {
+                        error!(error = %e, %component_name, "Invalid metricscaler properties");
+                        return Some(context.status_scaler(
+                            METRIC_SCALER_KIND,
+                            component_name,
+                            StatusInfo::failed(&format!("Invalid metricscaler properties: {e}")),
+                        ));
+                    }
+                };
+                Some(Box::new(
+                    BackoffWrapper::new(
+                        MetricScaler::new(
+                            snapshot_data.clone(),
+                            ComponentScalerConfig {
+                                lattice_id: lattice_id.to_owned(),
+                                component_id,
+                                component_reference: image_ref.to_owned(),
+                                model_name: application_name.to_owned(),
+                                component_config: config_names,
+                            },
+                            property,
+                            component_name,
+                        ),
+                        notifier.clone(),
+                        config_scalers,
+                        secret_scalers,
+                        notifier_subject,
+                        application_name,
+                        Some(Duration::from_secs(5)),
+                        None,
+                        None,
+                    )
+                    .with_instance_annotations(options.instance_annotations.clone())
+                    .with_compact_notifications(options.compact_notifications)
+                    .with_command_budget(options.command_budget),
+                ) as BoxedScaler)
             }
             (LINK_TRAIT, TraitProperty::Link(p), _) => {
                 // Find the target component of the link and create a scaler for it
                 components
                     .iter()
-                    .find_map(|component| match &component.properties {
-                        Properties::Capability {
-                            properties:
-                                CapabilityProperties {
-                                    id,
-                                    application,
-                                    image,
-                                    ..
-                                },
-                        }
-                        | Properties::Component {
-                            properties:
-                                ComponentProperties {
-                                    id,
-                                    application,
-                                    image,
-                                    ..
-                                },
-                        } if component.name == p.target.name => Some(link_scaler(
-                            p,
-                            lattice_id,
-                            manifest_name,
-                            application_name,
-                            &component.name,
-                            component_id.to_string(),
-                            id.as_ref(),
-                            image.as_ref(),
-                            application.as_ref(),
-                            policies,
-                            notifier_subject,
-                            notifier,
-                            snapshot_data,
-                        )),
-                        _ => None,
+                    .find(|component| component.name == p.target.name)
+                    .map(|target| {
+                        link_scaler(p, application_name, component_id.to_string(), target, context)
                     })
             }
             _ => None,
@@ -307,34 +598,32 @@ fn component_scalers<S, P, L>(
 /// * `components` - The list of components to convert
 /// * `properties` - The properties of the capability provider to convert
 /// * `traits` - The traits of the component to convert
-/// * `manifest_name` - The name of the manifest that the scalers are being created for
 /// * `application_name` - The name of the application that the scalers are being created for
 /// * `component_name` - The name of the component to convert
-/// * **The following arguments are required to create scalers, passed directly through to the scaler
-/// * `lattice_id` - The lattice id the scalers operate on
-/// * `policies` - The policies to use when creating the scalers so they can access secrets
-/// * `notifier_subject` - The subject to use when creating the scalers so they can report status
-/// * `notifier` - The publisher to use when creating the scalers so they can report status
-/// * `snapshot_data` - The store to use when creating the scalers so they can access lattice state
-#[allow(clippy::too_many_arguments)]
+/// * `context` - Everything shared by the scalers created for the manifest
 fn provider_scalers<S, P, L>(
     scalers: &mut ScalerList,
     components: &[Component],
     properties: &CapabilityProperties,
     traits: Option<&Vec<Trait>>,
-    manifest_name: &str,
     application_name: &str,
     component_name: &str,
-    lattice_id: &str,
-    policies: &HashMap<&String, &Policy>,
-    notifier_subject: &str,
-    notifier: &P,
-    snapshot_data: &SnapshotStore<S, L>,
+    context: &ManifestContext<S, P, L>,
 ) where
     S: ReadStore + Send + Sync + Clone + 'static,
     P: Publisher + Clone + Send + Sync + 'static,
     L: LinkSource + ConfigSource + SecretSource + Clone + Send + Sync + 'static,
 {
+    let &ManifestContext {
+        lattice_id,
+        manifest_name,
+        notifier_subject,
+        notifier,
+        snapshot_data,
+        policies,
+        ref options,
+        ..
+    } = context;
     // If an image is specified, then it's a provider in the same manifest. Otherwise, it's a shared component
     let provider_id = if properties.image.is_some() {
         compute_component_id(manifest_name, properties.id.as_ref(), component_name)
@@ -342,9 +631,14 @@ fn provider_scalers<S, P, L>(
         compute_component_id(application_name, properties.id.as_ref(), component_name)
     };
 
+    let image = properties
+        .image
+        .as_deref()
+        .map(|image| options.image_reference(image));
+
     let mut scaler_specified = false;
     scalers.extend(traits.unwrap_or(&EMPTY_TRAIT_VEC).iter().filter_map(|trt| {
-        match (trt.trait_type.as_str(), &trt.properties, &properties.image) {
+        match (trt.trait_type.as_str(), &trt.properties, &image) {
             // Shared application components already have their own spread/daemon scalers, you
             // cannot modify them from another manifest
             (SPREADSCALER_TRAIT, TraitProperty::SpreadScaler(_), None) => {
@@ -371,27 +665,37 @@ fn provider_scalers<S, P, L>(
                 );
                 config_names.append(&mut secret_names.clone());
 
-                Some(Box::new(BackoffWrapper::new(
-                    ProviderSpreadScaler::new(
-                        snapshot_data.clone(),
-                        ProviderSpreadConfig {
-                            lattice_id: lattice_id.to_owned(),
-                            provider_id: provider_id.to_owned(),
-                            provider_reference: image.to_owned(),
-                            spread_config: p.to_owned(),
-                            model_name: application_name.to_owned(),
-                            provider_config: config_names,
-                        },
-                        component_name,
-                    ),
-                    notifier.clone(),
-                    config_scalers,
-                    secret_scalers,
-                    notifier_subject,
-                    application_name,
-                    // Providers are a bit longer because it can take a bit to download
-                    Some(Duration::from_secs(60)),
-                )) as BoxedScaler)
+                Some(Box::new(
+                    BackoffWrapper::new(
+                        ProviderSpreadScaler::new(
+                            snapshot_data.clone(),
+                            ProviderSpreadConfig {
+                                lattice_id: lattice_id.to_owned(),
+                                provider_id: provider_id.to_owned(),
+                                provider_reference: image.to_owned(),
+                                spread_config: p.to_owned(),
+                                model_name: application_name.to_owned(),
+                                provider_config: config_names,
+                            },
+                            component_name,
+                        )
+                        .with_uniform_versions(options.uniform_versions)
+                        .with_host_quiet_period(options.host_quiet_period)
+                        .with_start_failure_cooldown(options.provider_retry_cooldown),
+                        notifier.clone(),
+                        config_scalers,
+                        secret_scalers,
+                        notifier_subject,
+                        application_name,
+                        // Providers are a bit longer because it can take a bit to download
+                        Some(Duration::from_secs(60)),
+                        None,
+                        None,
+                    )
+                    .with_instance_annotations(options.instance_annotations.clone())
+                    .with_compact_notifications(options.compact_notifications)
+                    .with_command_budget(options.command_budget),
+                ) as BoxedScaler)
             }
             (DAEMONSCALER_TRAIT, TraitProperty::SpreadScaler(p), Some(image)) => {
                 scaler_specified = true;
@@ -404,7 +708,8 @@ fn provider_scalers<S, P, L>(
                     policies,
                 );
                 config_names.append(&mut secret_names.clone());
-                Some(Box::new(BackoffWrapper::new(
+                Some(Box::new(
+                    BackoffWrapper::new(
                         ProviderDaemonScaler::new(
                             snapshot_data.clone(),
                             ProviderSpreadConfig {
@@ -416,7 +721,8 @@ fn provider_scalers<S, P, L>(
                                 provider_config: config_names,
                             },
                             component_name,
-                        ),
+                        )
+                        .with_host_quiet_period(options.host_quiet_period),
                         notifier.clone(),
                         config_scalers,
                         secret_scalers,
@@ -424,7 +730,13 @@ fn provider_scalers<S, P, L>(
                         application_name,
                         // Providers are a bit longer because it can take a bit to download
                         Some(Duration::from_secs(60)),
-                    )) as BoxedScaler)
+                        None,
+                        None,
+                    )
+                    .with_instance_annotations(options.instance_annotations.clone())
+                    .with_compact_notifications(options.compact_notifications)
+                    .with_command_budget(options.command_budget),
+                ) as BoxedScaler)
             }
             // Find the target component of the link and create a scaler for it.
             (LINK_TRAIT, TraitProperty::Link(p), _) => {
@@ -439,29 +751,15 @@ fn provider_scalers<S, P, L>(
                             );
                             None
                         }
-                        Properties::Component {
-                            properties:
-                                ComponentProperties {
-                                    image,
-                                    application,
-                                    id,
-                                    ..
-                                },
-                        } if component.name == p.target.name => Some(link_scaler(
-                            p,
-                            lattice_id,
-                            manifest_name,
-                            application_name,
-                            &component.name,
-                            provider_id.to_owned(),
-                            id.as_ref(),
-                            image.as_ref(),
-                            application.as_ref(),
-                            policies,
-                            notifier_subject,
-                            notifier,
-                            snapshot_data,
-                        )),
+                        Properties::Component { .. } if component.name == p.target.name => {
+                            Some(link_scaler(
+                                p,
+                                application_name,
+                                provider_id.to_owned(),
+                                component,
+                                context,
+                            ))
+                        }
                         _ => None,
                     })
             }
@@ -470,7 +768,7 @@ fn provider_scalers<S, P, L>(
     }));
     // Allow providers to omit the spreadscaler entirely for simplicity
     if !scaler_specified {
-        if let Some(image) = &properties.image {
+        if let Some(image) = &image {
             let (config_scalers, mut config_names) =
                 config_to_scalers(snapshot_data, application_name, &properties.config);
 
@@ -481,30 +779,47 @@ fn provider_scalers<S, P, L>(
                 policies,
             );
             config_names.append(&mut secret_names);
-            scalers.push(Box::new(BackoffWrapper::new(
-                ProviderSpreadScaler::new(
-                    snapshot_data.clone(),
-                    ProviderSpreadConfig {
-                        lattice_id: lattice_id.to_owned(),
-                        provider_id,
-                        provider_reference: image.to_owned(),
-                        spread_config: SpreadScalerProperty {
-                            instances: 1,
-                            spread: vec![],
+            scalers.push(Box::new(
+                BackoffWrapper::new(
+                    ProviderSpreadScaler::new(
+                        snapshot_data.clone(),
+                        ProviderSpreadConfig {
+                            lattice_id: lattice_id.to_owned(),
+                            provider_id,
+                            provider_reference: image.to_owned(),
+                            spread_config: SpreadScalerProperty {
+                                instances: 1,
+                                spread: vec![],
+                                topology_key: None,
+                                min_count: None,
+                                max_count: None,
+                                anti_affinity: Vec::new(),
+                                weight_key: None,
+                                selector: Vec::new(),
+                                reconcile_interval: None,
+                                instances_per_host: None,
+                            },
+                            model_name: application_name.to_owned(),
+                            provider_config: config_names,
                         },
-                        model_name: application_name.to_owned(),
-                        provider_config: config_names,
-                    },
-                    component_name,
-                ),
-                notifier.clone(),
-                config_scalers,
-                secret_scalers,
-                notifier_subject,
-                application_name,
-                // Providers are a bit longer because it can take a bit to download
-                Some(Duration::from_secs(60)),
-            )) as BoxedScaler)
+                        component_name,
+                    )
+                    .with_uniform_versions(options.uniform_versions)
+                    .with_host_quiet_period(options.host_quiet_period),
+                    notifier.clone(),
+                    config_scalers,
+                    secret_scalers,
+                    notifier_subject,
+                    application_name,
+                    // Providers are a bit longer because it can take a bit to download
+                    Some(Duration::from_secs(60)),
+                    None,
+                    None,
+                )
+                .with_instance_annotations(options.instance_annotations.clone())
+                .with_compact_notifications(options.compact_notifications)
+                .with_command_budget(options.command_budget),
+            ) as BoxedScaler)
         }
     }
 }
@@ -513,39 +828,52 @@ fn provider_scalers<S, P, L>(
 ///
 /// # Arguments
 /// * `link_property` - The properties of the link to convert
-/// * `lattice_id` - The lattice id the scalers operate on
-/// * `manifest_name` - The name of the manifest that the scalers are being created for
 /// * `application_name` - The name of the application that the scalers are being created for
-/// * `component_name` - The name of the component to convert
 /// * `source_id` - The ID of the source component
-/// * `target_id` - The optional ID of the target component
-/// * `image` - The optional image reference of the target component
-/// * `shared` - The optional shared application reference of the target component
-/// * `policies` - The policies to use when creating the scalers so they can access secrets
-/// * `notifier_subject` - The subject to use when creating the scalers so they can report status
-/// * `notifier` - The publisher to use when creating the scalers so they can report status
-/// * `snapshot_data` - The store to use when creating the scalers so they can access lattice state
-#[allow(clippy::too_many_arguments)]
+/// * `target` - The target component of the link
+/// * `context` - Everything shared by the scalers created for the manifest
 fn link_scaler<S, P, L>(
     link_property: &LinkProperty,
-    lattice_id: &str,
-    manifest_name: &str,
     application_name: &str,
-    component_name: &str,
     source_id: String,
-    target_id: Option<&String>,
-    image: Option<&String>,
-    shared: Option<&SharedApplicationComponentProperties>,
-    policies: &HashMap<&String, &Policy>,
-    notifier_subject: &str,
-    notifier: &P,
-    snapshot_data: &SnapshotStore<S, L>,
+    target: &Component,
+    context: &ManifestContext<S, P, L>,
 ) -> BoxedScaler
 where
     S: ReadStore + Send + Sync + Clone + 'static,
     P: Publisher + Clone + Send + Sync + 'static,
     L: LinkSource + ConfigSource + SecretSource + Clone + Send + Sync + 'static,
 {
+    let &ManifestContext {
+        lattice_id,
+        manifest_name,
+        notifier_subject,
+        notifier,
+        snapshot_data,
+        policies,
+        ..
+    } = context;
+    let component_name = target.name.as_str();
+    let (target_id, image, shared) = match &target.properties {
+        Properties::Capability {
+            properties:
+                CapabilityProperties {
+                    id,
+                    image,
+                    application,
+                    ..
+                },
+        }
+        | Properties::Component {
+            properties:
+                ComponentProperties {
+                    id,
+                    image,
+                    application,
+                    ..
+                },
+        } => (id.as_ref(), image.as_ref(), application.as_ref()),
+    };
     let (mut config_scalers, mut source_config) = config_to_scalers(
         snapshot_data,
         manifest_name,
@@ -583,10 +911,9 @@ where
             Ok(name) => name,
             Err(err) => {
                 error!(err);
-                return Box::new(StatusScaler::new(
-                    uuid::Uuid::new_v4().to_string(),
+                return context.status_scaler(
                     LINK_SCALER_KIND,
-                    format!(
+                    &format!(
                         "{} -({}:{})-> {}",
                         component_name,
                         link_property.namespace,
@@ -594,16 +921,16 @@ where
                         link_property.target.name
                     ),
                     StatusInfo::failed(err),
-                )) as BoxedScaler;
+                );
             }
         };
-    let target = compute_component_id(target_manifest_name, target_id, target_component_name);
+    let target_id = compute_component_id(target_manifest_name, target_id, target_component_name);
     Box::new(BackoffWrapper::new(
         LinkScaler::new(
             snapshot_data.clone(),
             LinkScalerConfig {
                 source_id,
-                target,
+                target: target_id,
                 wit_namespace: link_property.namespace.to_owned(),
                 wit_package: link_property.package.to_owned(),
                 wit_interfaces: link_property.interfaces.to_owned(),
@@ -624,6 +951,8 @@ where
         notifier_subject,
         application_name,
         Some(Duration::from_secs(5)),
+        None,
+        None,
     )) as BoxedScaler
 }
 
@@ -743,7 +1072,10 @@ fn resolve_manifest_component<'a>(
 
 #[cfg(test)]
 mod test {
-    use super::compute_component_id;
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::test_util::{NoopPublisher, TestLatticeSource, TestStore};
 
     #[test]
     fn compute_proper_component_id() {
@@ -777,4 +1109,148 @@ mod test {
             "my_thing-thing_wasm"
         );
     }
+
+    #[test]
+    fn stable_ids_ignore_tags_of_pinned_references() {
+        let digest = "sha256:4e8a2c6f0b1d3e5a7c9b2d4f6a8c0e1b3d5f7a9c2e4b6d8f0a1c3e5b7d9f2a4c";
+        let snapshot = SnapshotStore::new(
+            Arc::new(TestStore::default()),
+            TestLatticeSource::default(),
+            "stable_ids".to_string(),
+        );
+        let scaler_ids = |image: &str, stable_ids: bool| {
+            let manifest: Manifest = serde_yaml::from_str(&format!(
+                r#"
+apiVersion: core.oam.dev/v1beta1
+kind: Application
+metadata:
+  name: stable
+  annotations:
+    experimental.wasmcloud.dev/stable-scaler-ids: '{stable_ids}'
+spec:
+  components:
+    - name: echo
+      type: component
+      properties:
+        image: {image}
+      traits:
+        - type: spreadscaler
+          properties:
+            instances: 1
+    - name: httpserver
+      type: capability
+      properties:
+        image: {image}
+"#
+            ))
+            .unwrap();
+            manifest_components_to_scalers(
+                &manifest.spec.components,
+                &[],
+                &ManifestContext {
+                    lattice_id: "stable_ids",
+                    manifest_name: &manifest.metadata.name,
+                    manifest_version: manifest.version(),
+                    notifier_subject: "doesntmatter",
+                    notifier: &NoopPublisher,
+                    snapshot_data: &snapshot,
+                    policies: &manifest.policy_lookup(),
+                    registry: &ScalerRegistry::default(),
+                    options: ScalerOptions::from_manifest(
+                        &manifest,
+                        &InstanceAnnotations::default(),
+                    ),
+                },
+            )
+            .iter()
+            .map(|scaler| scaler.id().to_owned())
+            .collect::<Vec<_>>()
+        };
+
+        let before = scaler_ids(&format!("localhost:5000/echo:0.1.0@{digest}"), true);
+        assert_eq!(before.len(), 2);
+        assert_eq!(
+            before,
+            scaler_ids(&format!("localhost:5000/echo:latest@{digest}"), true),
+            "Re-tagging the same digest shouldn't change scaler IDs"
+        );
+        assert_eq!(
+            before,
+            scaler_ids(&format!("localhost:5000/echo@{digest}"), true),
+            "Dropping the tag shouldn't change scaler IDs"
+        );
+        assert_ne!(
+            scaler_ids(&format!("localhost:5000/echo:0.1.0@{digest}"), false),
+            scaler_ids(&format!("localhost:5000/echo:latest@{digest}"), false),
+            "Tags should still be part of the ID unless stable IDs are enabled"
+        );
+        assert_ne!(
+            scaler_ids("localhost:5000/echo:0.1.0", true),
+            scaler_ids("localhost:5000/echo:0.2.0", true),
+            "Tags that aren't pinned to a digest can point at different images"
+        );
+    }
+
+    #[test]
+    fn status_scaler_ids_are_derived_from_manifest() {
+        let snapshot = SnapshotStore::new(
+            Arc::new(TestStore::default()),
+            TestLatticeSource::default(),
+            "status_ids".to_string(),
+        );
+        let scaler_ids = |version: &str| {
+            // A component without an image or shared application can't be resolved, so it gets a
+            // status scaler instead
+            let manifest: Manifest = serde_yaml::from_str(&format!(
+                r#"
+apiVersion: core.oam.dev/v1beta1
+kind: Application
+metadata:
+  name: status
+  annotations:
+    version: {version}
+spec:
+  components:
+    - name: echo
+      type: component
+      properties: {{}}
+"#
+            ))
+            .unwrap();
+            manifest_components_to_scalers(
+                &manifest.spec.components,
+                &[],
+                &ManifestContext {
+                    lattice_id: "status_ids",
+                    manifest_name: &manifest.metadata.name,
+                    manifest_version: manifest.version(),
+                    notifier_subject: "doesntmatter",
+                    notifier: &NoopPublisher,
+                    snapshot_data: &snapshot,
+                    policies: &manifest.policy_lookup(),
+                    registry: &ScalerRegistry::default(),
+                    options: ScalerOptions::from_manifest(
+                        &manifest,
+                        &InstanceAnnotations::default(),
+                    ),
+                },
+            )
+            .iter()
+            .map(|scaler| scaler.id().to_owned())
+            .collect::<Vec<_>>()
+        };
+
+        let ids = scaler_ids("v0.1.0");
+        assert_eq!(ids.len(), 1, "Should have a single status scaler");
+        assert_eq!(
+            ids,
+            scaler_ids("v0.1.0"),
+            "Rebuilding the same manifest should keep the same status scaler ID"
+        );
+        assert_ne!(
+            ids,
+            scaler_ids("v0.2.0"),
+            "A new version of the manifest should get a new status scaler ID"
+        );
+    }
 }
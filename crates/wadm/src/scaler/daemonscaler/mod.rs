@@ -1,5 +1,6 @@
 use std::cmp::Ordering;
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, HashMap};
+use std::time::Duration;
 
 use anyhow::Result;
 use async_trait::async_trait;
@@ -8,18 +9,22 @@ use tracing::{instrument, trace};
 use wadm_types::{api::StatusInfo, Spread, SpreadScalerProperty, TraitProperty};
 
 use crate::scaler::spreadscaler::{
-    compute_ineligible_hosts, eligible_hosts, spreadscaler_annotations,
+    compute_ineligible_hosts, eligible_hosts, remove_incapable_hosts, remove_quiet_hosts,
+    spreadscaler_annotations,
 };
 use crate::{
     commands::{Command, ScaleComponent},
     events::{Event, HostHeartbeat, HostStarted, HostStopped},
-    scaler::Scaler,
-    storage::{Component, Host, ReadStore},
+    scaler::{ComponentScalerConfig, ManagedInstances, ReconcilePlan, Scaler},
+    storage::{Component, ConsistentRead, Host, Provider, ReadStore},
 };
 
 use super::compute_id_sha256;
 
 pub mod provider;
+pub mod selector;
+
+use selector::LabelSelector;
 
 // Annotation constants
 pub const DAEMON_SCALER_KIND: &str = "DaemonScaler";
@@ -50,10 +55,17 @@ pub struct ComponentDaemonScaler<S> {
     id: String,
     status: RwLock<StatusInfo>,
     config: Vec<String>,
+    /// How long after a host starts before the component can be placed on it
+    host_quiet_period: Duration,
+    /// Whether the component is only placed on hosts running providers for every capability it
+    /// requires
+    check_capabilities: bool,
+    /// The selector hosts must match to be eligible, or why the configured selector is invalid
+    selector: Result<LabelSelector, String>,
 }
 
 #[async_trait]
-impl<S: ReadStore + Send + Sync + Clone> Scaler for ComponentDaemonScaler<S> {
+impl<S: ReadStore + ConsistentRead + Send + Sync + Clone> Scaler for ComponentDaemonScaler<S> {
     fn id(&self) -> &str {
         &self.id
     }
@@ -62,6 +74,14 @@ impl<S: ReadStore + Send + Sync + Clone> Scaler for ComponentDaemonScaler<S> {
         DAEMON_SCALER_KIND
     }
 
+    fn reconcile_interval(&self) -> Option<Duration> {
+        self.spread_config
+            .spread_config
+            .reconcile_interval
+            .filter(|secs| *secs > 0)
+            .map(Duration::from_secs)
+    }
+
     fn name(&self) -> String {
         self.spread_config.component_id.to_string()
     }
@@ -76,17 +96,27 @@ impl<S: ReadStore + Send + Sync + Clone> Scaler for ComponentDaemonScaler<S> {
             TraitProperty::SpreadScaler(prop) => prop,
             _ => anyhow::bail!("Given config was not a daemon scaler config object"),
         };
+        let selector = LabelSelector::parse(&spread_config.selector)?;
         // If no spreads are specified, an empty spread is sufficient to match _every_ host
         // in a lattice
         let spread_config = if spread_config.spread.is_empty() {
             SpreadScalerProperty {
                 instances: spread_config.instances,
                 spread: vec![Spread::default()],
+                topology_key: None,
+                min_count: None,
+                max_count: None,
+                anti_affinity: Vec::new(),
+                weight_key: None,
+                selector: spread_config.selector,
+                reconcile_interval: spread_config.reconcile_interval,
+                instances_per_host: None,
             }
         } else {
             spread_config
         };
         self.spread_config.spread_config = spread_config;
+        self.selector = Ok(selector);
         self.reconcile().await
     }
 
@@ -125,22 +155,57 @@ impl<S: ReadStore + Send + Sync + Clone> Scaler for ComponentDaemonScaler<S> {
     }
 
     #[instrument(level = "trace", skip_all, fields(name = %self.spread_config.model_name, scaler_id = %self.id))]
-    async fn reconcile(&self) -> Result<Vec<Command>> {
+    async fn diff(&self) -> Result<ReconcilePlan> {
         let component_id = &self.spread_config.component_id;
-        let component = self
-            .store
+        let selector = match &self.selector {
+            Ok(selector) => selector,
+            Err(e) => {
+                *self.status.write().await =
+                    StatusInfo::failed(&format!("Invalid daemonscaler selector: {e}"));
+                return Ok(ReconcilePlan::default());
+            }
+        };
+        // Read everything from a single view so a concurrent update can't change the hosts out
+        // from under us partway through
+        let store = self.store.consistent_view().await;
+        let component = store
             .get::<Component>(&self.spread_config.lattice_id, component_id)
             .await?;
+        let managed = ManagedInstances::of_component(&self.id, component.as_ref());
 
-        let hosts = self
-            .store
-            .list::<Host>(&self.spread_config.lattice_id)
-            .await?;
+        let mut hosts = store.list::<Host>(&self.spread_config.lattice_id).await?;
+        remove_quiet_hosts(&mut hosts, self.host_quiet_period, |host| {
+            host.components.contains_key(component_id)
+        });
+        if self.check_capabilities {
+            let providers = store
+                .list::<Provider>(&self.spread_config.lattice_id)
+                .await?;
+            let required = component
+                .as_ref()
+                .map(|component| component.capabilities.as_slice())
+                .unwrap_or_default();
+            if let Some(message) =
+                remove_incapable_hosts(&mut hosts, required, &providers, |host| {
+                    host.components.contains_key(component_id)
+                })
+            {
+                trace!(%message, "No hosts satisfy the required capabilities");
+                *self.status.write().await = StatusInfo::failed(&message);
+                return Ok(managed.plan(Vec::new()));
+            }
+        }
+        // Hosts that don't match the selector are never placed on, so anything already running on
+        // them is removed along with everything on hosts that match no spread
+        let (hosts, unselected_hosts): (HashMap<_, _>, HashMap<_, _>) = hosts
+            .into_iter()
+            .partition(|(_, host)| selector.matches(&host.labels));
 
-        let ineligible_hosts = compute_ineligible_hosts(
+        let mut ineligible_hosts = compute_ineligible_hosts(
             &hosts,
             self.spread_config.spread_config.spread.iter().collect(),
         );
+        ineligible_hosts.extend(&unselected_hosts);
 
         // Remove any components that are managed by this scaler and running on ineligible hosts
         let remove_ineligible: Vec<Command> = ineligible_hosts
@@ -169,10 +234,12 @@ impl<S: ReadStore + Send + Sync + Clone> Scaler for ComponentDaemonScaler<S> {
             );
             trace!(?status, "Updating scaler status");
             *self.status.write().await = status;
-            return Ok(remove_ineligible);
+            return Ok(managed.plan(remove_ineligible));
         }
 
         let mut spread_status = vec![];
+        let instances = self.spread_config.spread_config.instances;
+        let (mut achieved, mut desired) = (0, 0);
 
         trace!(spread = ?self.spread_config.spread_config.spread, ?component_id, "Computing commands");
         let commands = self
@@ -212,6 +279,10 @@ impl<S: ReadStore + Send + Sync + Clone> Scaler for ComponentDaemonScaler<S> {
                             (id, count)
                         })
                         .collect::<Vec<(&String, usize)>>();
+                    for (_, current_count) in &components_per_host {
+                        achieved += (*current_count).min(instances) as u32;
+                        desired += instances as u32;
+                    }
 
                     Some(
                         components_per_host
@@ -273,11 +344,16 @@ impl<S: ReadStore + Send + Sync + Clone> Scaler for ComponentDaemonScaler<S> {
                     .collect::<Vec<String>>()
                     .join(" "),
             ),
-        };
+        }
+        .with_progress(achieved, desired);
         trace!(?status, "Updating scaler status");
         *self.status.write().await = status;
 
-        Ok(commands)
+        Ok(managed.plan(commands))
+    }
+
+    async fn reconcile(&self) -> Result<Vec<Command>> {
+        self.diff().await.map(ReconcilePlan::into_commands)
     }
 
     #[instrument(level = "trace", skip_all, fields(name = %self.spread_config.model_name))]
@@ -291,6 +367,10 @@ impl<S: ReadStore + Send + Sync + Clone> Scaler for ComponentDaemonScaler<S> {
             id: self.id.clone(),
             status: RwLock::new(StatusInfo::reconciling("")),
             config: self.config.clone(),
+            host_quiet_period: self.host_quiet_period,
+            // Everything is removed during cleanup, so which hosts could run it doesn't matter
+            check_capabilities: false,
+            selector: self.selector.clone(),
         };
 
         cleanerupper.reconcile().await
@@ -299,17 +379,19 @@ impl<S: ReadStore + Send + Sync + Clone> Scaler for ComponentDaemonScaler<S> {
 
 impl<S: ReadStore + Send + Sync> ComponentDaemonScaler<S> {
     /// Construct a new ComponentDaemonScaler with specified configuration values
-    #[allow(clippy::too_many_arguments)]
     pub fn new(
         store: S,
-        component_reference: String,
-        component_id: String,
-        lattice_id: String,
-        model_name: String,
+        config: ComponentScalerConfig,
         spread_config: SpreadScalerProperty,
         component_name: &str,
-        config: Vec<String>,
     ) -> Self {
+        let ComponentScalerConfig {
+            lattice_id,
+            component_id,
+            component_reference,
+            model_name,
+            component_config: config,
+        } = config;
         // Compute the id of this scaler based on all of the configuration values
         // that make it unique. This is used during upgrades to determine if a
         // scaler is the same as a previous one.
@@ -322,12 +404,21 @@ impl<S: ReadStore + Send + Sync> ComponentDaemonScaler<S> {
         ];
         id_parts.extend(config.iter().map(std::string::String::as_str));
         let id = compute_id_sha256(&id_parts);
+        let selector = LabelSelector::parse(&spread_config.selector).map_err(|e| e.to_string());
         // If no spreads are specified, an empty spread is sufficient to match _every_ host
         // in a lattice
         let spread_config = if spread_config.spread.is_empty() {
             SpreadScalerProperty {
                 instances: spread_config.instances,
                 spread: vec![Spread::default()],
+                topology_key: None,
+                min_count: None,
+                max_count: None,
+                anti_affinity: Vec::new(),
+                weight_key: None,
+                selector: spread_config.selector,
+                reconcile_interval: spread_config.reconcile_interval,
+                instances_per_host: None,
             }
         } else {
             spread_config
@@ -344,8 +435,26 @@ impl<S: ReadStore + Send + Sync> ComponentDaemonScaler<S> {
             id,
             status: RwLock::new(StatusInfo::reconciling("")),
             config,
+            host_quiet_period: Duration::ZERO,
+            check_capabilities: false,
+            selector,
         }
     }
+
+    /// Configures how long after a host starts before the component can be placed on it, giving
+    /// the host time to finish starting up. Disabled by default
+    pub fn with_host_quiet_period(mut self, quiet_period: Duration) -> Self {
+        self.host_quiet_period = quiet_period;
+        self
+    }
+
+    /// Configures whether the component is only placed on hosts running providers for every
+    /// capability its claims require. If no host provides them all, the scaler is failed and
+    /// nothing is placed. Disabled by default
+    pub fn with_capability_check(mut self, check: bool) -> Self {
+        self.check_capabilities = check;
+        self
+    }
 }
 
 #[cfg(test)]
@@ -397,6 +506,7 @@ mod test {
                     version: None,
                     id: host_id.to_string(),
                     last_seen: Utc::now(),
+                    ..Default::default()
                 },
             )
             .await?;
@@ -426,17 +536,27 @@ mod test {
                     weight: Some(384),
                 },
             ],
+            topology_key: None,
+            min_count: None,
+            max_count: None,
+            anti_affinity: Vec::new(),
+            weight_key: None,
+            selector: Vec::new(),
+            reconcile_interval: None,
+            instances_per_host: None,
         };
 
         let daemonscaler = ComponentDaemonScaler::new(
             store.clone(),
-            component_reference.to_string(),
-            component_id.to_string(),
-            lattice_id.to_string(),
-            MODEL_NAME.to_string(),
+            ComponentScalerConfig {
+                lattice_id: lattice_id.to_string(),
+                component_id: component_id.to_string(),
+                component_reference: component_reference.to_string(),
+                model_name: MODEL_NAME.to_string(),
+                component_config: vec![],
+            },
             complex_spread,
             "fake_component",
-            vec![],
         );
 
         let cmds = daemonscaler.reconcile().await?;
@@ -517,6 +637,14 @@ mod test {
                     weight: None,
                 },
             ],
+            topology_key: None,
+            min_count: None,
+            max_count: None,
+            anti_affinity: Vec::new(),
+            weight_key: None,
+            selector: Vec::new(),
+            reconcile_interval: None,
+            instances_per_host: None,
         };
 
         let blobby_spread_property = SpreadScalerProperty {
@@ -547,28 +675,40 @@ mod test {
                     weight: Some(33),
                 },
             ],
+            topology_key: None,
+            min_count: None,
+            max_count: None,
+            anti_affinity: Vec::new(),
+            weight_key: None,
+            selector: Vec::new(),
+            reconcile_interval: None,
+            instances_per_host: None,
         };
 
         let echo_daemonscaler = ComponentDaemonScaler::new(
             store.clone(),
-            echo_ref.to_string(),
-            echo_id.to_string(),
-            lattice_id.to_string(),
-            MODEL_NAME.to_string(),
+            ComponentScalerConfig {
+                lattice_id: lattice_id.to_string(),
+                component_id: echo_id.to_string(),
+                component_reference: echo_ref.to_string(),
+                model_name: MODEL_NAME.to_string(),
+                component_config: vec![],
+            },
             echo_spread_property,
             "fake_echo",
-            vec![],
         );
 
         let blobby_daemonscaler = ComponentDaemonScaler::new(
             store.clone(),
-            blobby_ref.to_string(),
-            blobby_id.to_string(),
-            lattice_id.to_string(),
-            MODEL_NAME.to_string(),
+            ComponentScalerConfig {
+                lattice_id: lattice_id.to_string(),
+                component_id: blobby_id.to_string(),
+                component_reference: blobby_ref.to_string(),
+                model_name: MODEL_NAME.to_string(),
+                component_config: vec![],
+            },
             blobby_spread_property,
             "fake_blobby",
-            vec![],
         );
 
         // STATE SETUP BEGIN
@@ -617,6 +757,7 @@ mod test {
                         ),
                     ]),
                     reference: echo_ref.to_string(),
+                    ..Default::default()
                 },
             )
             .await?;
@@ -654,6 +795,7 @@ mod test {
                         ),
                     ]),
                     reference: blobby_ref.to_string(),
+                    ..Default::default()
                 },
             )
             .await?;
@@ -678,6 +820,7 @@ mod test {
                     version: None,
                     id: host_id_one.to_string(),
                     last_seen: Utc::now(),
+                    ..Default::default()
                 },
             )
             .await?;
@@ -702,6 +845,7 @@ mod test {
                     version: None,
                     id: host_id_two.to_string(),
                     last_seen: Utc::now(),
+                    ..Default::default()
                 },
             )
             .await?;
@@ -722,6 +866,7 @@ mod test {
                     version: None,
                     id: host_id_three.to_string(),
                     last_seen: Utc::now(),
+                    ..Default::default()
                 },
             )
             .await?;
@@ -835,16 +980,26 @@ mod test {
                 )]),
                 weight: None,
             }],
+            topology_key: None,
+            min_count: None,
+            max_count: None,
+            anti_affinity: Vec::new(),
+            weight_key: None,
+            selector: Vec::new(),
+            reconcile_interval: None,
+            instances_per_host: None,
         };
         let blobby_daemonscaler = ComponentDaemonScaler::new(
             store.clone(),
-            blobby_ref.to_string(),
-            blobby_id.to_string(),
-            lattice_id.to_string(),
-            MODEL_NAME.to_string(),
+            ComponentScalerConfig {
+                lattice_id: lattice_id.to_string(),
+                component_id: blobby_id.to_string(),
+                component_reference: blobby_ref.to_string(),
+                model_name: MODEL_NAME.to_string(),
+                component_config: vec![],
+            },
             blobby_spread_property,
             "fake_blobby",
-            vec![],
         );
 
         // STATE SETUP BEGIN
@@ -886,6 +1041,7 @@ mod test {
                         ),
                     ]),
                     reference: blobby_ref.to_string(),
+                    ..Default::default()
                 },
             )
             .await?;
@@ -909,6 +1065,7 @@ mod test {
                     version: None,
                     id: host_id_one.to_string(),
                     last_seen: Utc::now(),
+                    ..Default::default()
                 },
             )
             .await?;
@@ -930,6 +1087,7 @@ mod test {
                     version: None,
                     id: host_id_two.to_string(),
                     last_seen: Utc::now(),
+                    ..Default::default()
                 },
             )
             .await?;
@@ -985,6 +1143,7 @@ mod test {
             version: semver::Version::new(0, 63, 1),
             host_id: host_id_three.to_string(),
             uptime_human: "time_is_a_human_construct".to_string(),
+            utilization: None,
         };
 
         worker
@@ -992,6 +1151,8 @@ mod test {
                 lattice_id: lattice_id.to_string(),
                 inner: Event::HostHeartbeat(modifying_event.clone()),
                 acker: None,
+                ack_batcher: None,
+                published: None,
             })
             .await
             .expect("should be able to handle an event");
@@ -1053,6 +1214,7 @@ mod test {
                         ),
                     ]),
                     reference: blobby_ref.to_string(),
+                    ..Default::default()
                 },
             )
             .await?;
@@ -1066,4 +1228,173 @@ mod test {
 
         Ok(())
     }
+
+    #[tokio::test]
+    async fn selector_limits_eligible_hosts() -> Result<()> {
+        let lattice_id = "daemon_selector";
+        let component_reference = "fakecloud.azurecr.io/echo:0.3.4".to_string();
+        let component_id = "fakecloud_azurecr_io_echo_0_3_4".to_string();
+
+        let store = Arc::new(TestStore::default());
+        let mut daemonscaler = ComponentDaemonScaler::new(
+            store.clone(),
+            ComponentScalerConfig {
+                lattice_id: lattice_id.to_string(),
+                component_id: component_id.to_string(),
+                component_reference: component_reference.to_string(),
+                model_name: MODEL_NAME.to_string(),
+                component_config: vec![],
+            },
+            SpreadScalerProperty {
+                instances: 2,
+                spread: vec![],
+                topology_key: None,
+                min_count: None,
+                max_count: None,
+                anti_affinity: Vec::new(),
+                weight_key: None,
+                selector: vec![
+                    "zone In [us-east-1, us-east-2]".to_string(),
+                    "tier NotIn [spare]".to_string(),
+                ],
+                reconcile_interval: None,
+                instances_per_host: None,
+            },
+            "fake_component",
+        );
+
+        for (host_id, labels) in [
+            ("east", vec![("zone", "us-east-1")]),
+            ("spare", vec![("zone", "us-east-2"), ("tier", "spare")]),
+            ("west", vec![("zone", "us-west-1")]),
+        ] {
+            store
+                .store(
+                    lattice_id,
+                    host_id.to_string(),
+                    Host {
+                        components: HashMap::from_iter([(component_id.to_string(), 2)]),
+                        labels: labels
+                            .into_iter()
+                            .map(|(key, value)| (key.to_string(), value.to_string()))
+                            .collect(),
+                        id: host_id.to_string(),
+                        last_seen: Utc::now(),
+                        ..Default::default()
+                    },
+                )
+                .await?;
+        }
+        store
+            .store(
+                lattice_id,
+                component_id.to_string(),
+                Component {
+                    id: component_id.to_string(),
+                    instances: HashMap::from_iter(["east", "spare", "west"].map(|host_id| {
+                        (
+                            host_id.to_string(),
+                            HashSet::from_iter([WadmComponentInfo {
+                                count: 2,
+                                annotations: spreadscaler_annotations("default", daemonscaler.id()),
+                            }]),
+                        )
+                    })),
+                    reference: component_reference.to_string(),
+                    ..Default::default()
+                },
+            )
+            .await?;
+
+        // Instances on hosts that don't match the selector are removed, even though they match
+        // the default spread
+        let mut cmds = daemonscaler.reconcile().await?;
+        cmds.sort_by_key(|cmd| match cmd {
+            Command::ScaleComponent(scale) => scale.host_id.clone(),
+            _ => String::new(),
+        });
+        assert_eq!(
+            cmds,
+            ["spare", "west"].map(|host_id| Command::ScaleComponent(ScaleComponent {
+                component_id: component_id.to_string(),
+                reference: component_reference.to_string(),
+                host_id: host_id.to_string(),
+                count: 0,
+                model_name: MODEL_NAME.to_string(),
+                annotations: BTreeMap::new(),
+                config: vec![],
+            }))
+        );
+
+        // Once they're gone, the hosts that don't match aren't counted towards the status
+        store
+            .store(
+                lattice_id,
+                component_id.to_string(),
+                Component {
+                    id: component_id.to_string(),
+                    instances: HashMap::from_iter([(
+                        "east".to_string(),
+                        HashSet::from_iter([WadmComponentInfo {
+                            count: 2,
+                            annotations: spreadscaler_annotations("default", daemonscaler.id()),
+                        }]),
+                    )]),
+                    reference: component_reference.to_string(),
+                    ..Default::default()
+                },
+            )
+            .await?;
+        for host_id in ["spare", "west"] {
+            let mut host = store
+                .get::<Host>(lattice_id, host_id)
+                .await?
+                .expect("host should exist");
+            host.components.clear();
+            store.store(lattice_id, host_id.to_string(), host).await?;
+        }
+        assert!(daemonscaler.reconcile().await?.is_empty());
+        assert_eq!(
+            daemonscaler.status().await.status_type,
+            StatusType::Deployed
+        );
+
+        // An empty In list matches nothing, so the remaining instances are removed too
+        daemonscaler
+            .update_config(TraitProperty::SpreadScaler(SpreadScalerProperty {
+                instances: 2,
+                spread: vec![],
+                topology_key: None,
+                min_count: None,
+                max_count: None,
+                anti_affinity: Vec::new(),
+                weight_key: None,
+                selector: vec!["zone In []".to_string()],
+                reconcile_interval: None,
+                instances_per_host: None,
+            }))
+            .await?;
+        assert_eq!(
+            daemonscaler.status().await.status_type,
+            StatusType::Reconciling
+        );
+
+        assert!(daemonscaler
+            .update_config(TraitProperty::SpreadScaler(SpreadScalerProperty {
+                instances: 2,
+                spread: vec![],
+                topology_key: None,
+                min_count: None,
+                max_count: None,
+                anti_affinity: Vec::new(),
+                weight_key: None,
+                selector: vec!["zone Is [us-east-1]".to_string()],
+                reconcile_interval: None,
+                instances_per_host: None,
+            }))
+            .await
+            .is_err());
+
+        Ok(())
+    }
 }
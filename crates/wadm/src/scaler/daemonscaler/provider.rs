@@ -1,4 +1,5 @@
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, HashMap};
+use std::time::Duration;
 
 use anyhow::Result;
 use async_trait::async_trait;
@@ -13,20 +14,22 @@ use crate::events::{
     ProviderInfo, ProviderStarted, ProviderStopped,
 };
 use crate::scaler::compute_id_sha256;
+use crate::scaler::shared::provider_resource;
 use crate::scaler::spreadscaler::{
-    compute_ineligible_hosts, eligible_hosts, provider::ProviderSpreadConfig,
-    spreadscaler_annotations,
+    compute_ineligible_hosts, eligible_hosts,
+    provider::{unhealthy_provider_message, ProviderSpreadConfig, UNHEALTHY_PROVIDER_PREFIX},
+    remove_quiet_hosts, spreadscaler_annotations,
 };
-use crate::storage::{Provider, ProviderStatus};
+use crate::storage::Provider;
 use crate::SCALER_KEY;
 use crate::{
     commands::{Command, StartProvider},
     events::{Event, HostStarted, HostStopped},
-    scaler::Scaler,
+    scaler::{ManagedInstances, ReconcilePlan, Scaler},
     storage::{Host, ReadStore},
 };
 
-use super::DAEMON_SCALER_KIND;
+use super::{selector::LabelSelector, DAEMON_SCALER_KIND};
 
 /// The ProviderDaemonScaler ensures that a provider is running on every host, according to a
 /// [SpreadScalerProperty](crate::model::SpreadScalerProperty)
@@ -38,6 +41,10 @@ pub struct ProviderDaemonScaler<S> {
     store: S,
     id: String,
     status: RwLock<StatusInfo>,
+    /// How long after a host starts before the provider can be placed on it
+    host_quiet_period: Duration,
+    /// The selector hosts must match to be eligible, or why the configured selector is invalid
+    selector: Result<LabelSelector, String>,
 }
 
 #[async_trait]
@@ -50,13 +57,42 @@ impl<S: ReadStore + Send + Sync + Clone> Scaler for ProviderDaemonScaler<S> {
         DAEMON_SCALER_KIND
     }
 
+    fn reconcile_interval(&self) -> Option<Duration> {
+        self.config
+            .spread_config
+            .reconcile_interval
+            .filter(|secs| *secs > 0)
+            .map(Duration::from_secs)
+    }
+
+    fn shared_resource(&self) -> Option<String> {
+        Some(provider_resource(
+            &self.config.provider_reference,
+            &self.config.provider_id,
+        ))
+    }
+
     fn name(&self) -> String {
         self.config.provider_id.to_string()
     }
 
     async fn status(&self) -> StatusInfo {
         let _ = self.reconcile().await;
-        self.status.read().await.to_owned()
+        let status = self.status.read().await.to_owned();
+        if status.status_type != StatusType::Deployed {
+            return status;
+        }
+        // Reconciling only checks where the provider is running, so a placement that has failed
+        // its health check still needs to be reported
+        match self
+            .store
+            .get::<Provider>(&self.config.lattice_id, &self.config.provider_id)
+            .await
+        {
+            Ok(Some(provider)) => unhealthy_provider_message(&provider)
+                .map_or(status, |message| StatusInfo::failed(&message)),
+            _ => status,
+        }
     }
 
     async fn update_config(&mut self, config: TraitProperty) -> Result<Vec<Command>> {
@@ -64,17 +100,27 @@ impl<S: ReadStore + Send + Sync + Clone> Scaler for ProviderDaemonScaler<S> {
             TraitProperty::SpreadScaler(prop) => prop,
             _ => anyhow::bail!("Given config was not a daemon scaler config object"),
         };
+        let selector = LabelSelector::parse(&spread_config.selector)?;
         // If no spreads are specified, an empty spread is sufficient to match _every_ host
         // in a lattice
         let spread_config = if spread_config.spread.is_empty() {
             SpreadScalerProperty {
                 instances: spread_config.instances,
                 spread: vec![Spread::default()],
+                topology_key: None,
+                min_count: None,
+                max_count: None,
+                anti_affinity: Vec::new(),
+                weight_key: None,
+                selector: spread_config.selector,
+                reconcile_interval: spread_config.reconcile_interval,
+                instances_per_host: None,
             }
         } else {
             spread_config
         };
         self.config.spread_config = spread_config;
+        self.selector = Ok(selector);
         self.reconcile().await
     }
 
@@ -114,26 +160,18 @@ impl<S: ReadStore + Send + Sync + Clone> Scaler for ProviderDaemonScaler<S> {
                     .get::<Provider>(&self.config.lattice_id, &self.config.provider_id)
                     .await?;
 
-                let unhealthy_providers = provider.map_or(0, |p| {
-                    p.hosts
-                        .values()
-                        .filter(|s| *s == &ProviderStatus::Failed)
-                        .count()
-                });
+                let unhealthy = provider.as_ref().and_then(unhealthy_provider_message);
                 let status = self.status.read().await.to_owned();
                 // update health status of scaler
-                if let Some(status) = match (status, unhealthy_providers > 0) {
+                if let Some(status) = match (status, unhealthy) {
                     // scaler is deployed but contains unhealthy providers
                     (
                         StatusInfo {
                             status_type: StatusType::Deployed,
                             ..
                         },
-                        true,
-                    ) => Some(StatusInfo::failed(&format!(
-                        "Unhealthy provider on {} host(s)",
-                        unhealthy_providers
-                    ))),
+                        Some(message),
+                    ) => Some(StatusInfo::failed(&message)),
                     // scaler can become unhealthy only if it was previously deployed
                     // once scaler becomes healthy again revert back to deployed state
                     // this is a workaround to detect unhealthy status until
@@ -142,9 +180,10 @@ impl<S: ReadStore + Send + Sync + Clone> Scaler for ProviderDaemonScaler<S> {
                         StatusInfo {
                             status_type: StatusType::Failed,
                             message,
+                            ..
                         },
-                        false,
-                    ) if message.starts_with("Unhealthy provider on") => {
+                        None,
+                    ) if message.starts_with(UNHEALTHY_PROVIDER_PREFIX) => {
                         Some(StatusInfo::deployed(""))
                     }
                     // don't update status if scaler is not deployed
@@ -162,12 +201,36 @@ impl<S: ReadStore + Send + Sync + Clone> Scaler for ProviderDaemonScaler<S> {
     }
 
     #[instrument(level = "trace", skip_all, fields(name = %self.config.model_name, scaler_id = %self.id))]
-    async fn reconcile(&self) -> Result<Vec<Command>> {
-        let hosts = self.store.list::<Host>(&self.config.lattice_id).await?;
+    async fn diff(&self) -> Result<ReconcilePlan> {
+        let selector = match &self.selector {
+            Ok(selector) => selector,
+            Err(e) => {
+                *self.status.write().await =
+                    StatusInfo::failed(&format!("Invalid daemonscaler selector: {e}"));
+                return Ok(ReconcilePlan::default());
+            }
+        };
+        let mut hosts = self.store.list::<Host>(&self.config.lattice_id).await?;
         let provider_id = &self.config.provider_id;
         let provider_ref = &self.config.provider_reference;
+        let provider = self
+            .store
+            .get::<Provider>(&self.config.lattice_id, provider_id)
+            .await?;
+        let managed =
+            ManagedInstances::of_provider(&self.id, provider_id, &hosts, provider.as_ref());
+        remove_quiet_hosts(&mut hosts, self.host_quiet_period, |host| {
+            host.providers
+                .iter()
+                .any(|provider| &provider.provider_id == provider_id)
+        });
+        // Hosts that don't match the selector are never placed on, so anything already running on
+        // them is removed along with everything on hosts that match no spread
+        let (hosts, unselected_hosts): (HashMap<_, _>, HashMap<_, _>) = hosts
+            .into_iter()
+            .partition(|(_, host)| selector.matches(&host.labels));
 
-        let ineligible_hosts = compute_ineligible_hosts(
+        let mut ineligible_hosts = compute_ineligible_hosts(
             &hosts,
             self.config
                 .spread_config
@@ -175,10 +238,11 @@ impl<S: ReadStore + Send + Sync + Clone> Scaler for ProviderDaemonScaler<S> {
                 .iter()
                 .collect::<Vec<&Spread>>(),
         );
+        ineligible_hosts.extend(&unselected_hosts);
         // Remove any providers that are managed by this scaler and running on ineligible hosts
         let remove_ineligible: Vec<Command> = ineligible_hosts
-            .iter()
-            .filter_map(|(_host_id, host)| {
+            .values()
+            .filter_map(|host| {
                 if host
                     .providers
                     .get(&ProviderInfo {
@@ -212,7 +276,7 @@ impl<S: ReadStore + Send + Sync + Clone> Scaler for ProviderDaemonScaler<S> {
             );
             trace!(?status, "Updating scaler status");
             *self.status.write().await = status;
-            return Ok(remove_ineligible);
+            return Ok(managed.plan(remove_ineligible));
         }
 
         let mut spread_status = vec![];
@@ -227,9 +291,9 @@ impl<S: ReadStore + Send + Sync + Clone> Scaler for ProviderDaemonScaler<S> {
                 let eligible_hosts = eligible_hosts(&hosts, spread);
                 if !eligible_hosts.is_empty() {
                     eligible_hosts
-                        .iter()
+                        .values()
                         // Filter out hosts that are already running this provider
-                        .filter_map(|(_host_id, host)| {
+                        .filter_map(|host| {
                             let provider_on_host = host.providers.get(&ProviderInfo {
                                 provider_id: provider_id.to_string(),
                                 provider_ref: provider_ref.to_string(),
@@ -290,7 +354,11 @@ impl<S: ReadStore + Send + Sync + Clone> Scaler for ProviderDaemonScaler<S> {
         trace!(?status, "Updating scaler status");
         *self.status.write().await = status;
 
-        Ok(commands)
+        Ok(managed.plan(commands))
+    }
+
+    async fn reconcile(&self) -> Result<Vec<Command>> {
+        self.diff().await.map(ReconcilePlan::into_commands)
     }
 
     #[instrument(level = "trace", skip_all, fields(name = %self.config.model_name))]
@@ -303,6 +371,8 @@ impl<S: ReadStore + Send + Sync + Clone> Scaler for ProviderDaemonScaler<S> {
             store: self.store.clone(),
             id: self.id.clone(),
             status: RwLock::new(StatusInfo::reconciling("")),
+            host_quiet_period: self.host_quiet_period,
+            selector: self.selector.clone(),
         };
 
         cleanerupper.reconcile().await
@@ -329,6 +399,8 @@ impl<S: ReadStore + Send + Sync> ProviderDaemonScaler<S> {
                 .map(std::string::String::as_str),
         );
         let id = compute_id_sha256(&id_parts);
+        let selector =
+            LabelSelector::parse(&config.spread_config.selector).map_err(|e| e.to_string());
 
         // If no spreads are specified, an empty spread is sufficient to match _every_ host
         // in a lattice
@@ -336,6 +408,14 @@ impl<S: ReadStore + Send + Sync> ProviderDaemonScaler<S> {
             SpreadScalerProperty {
                 instances: config.spread_config.instances,
                 spread: vec![Spread::default()],
+                topology_key: None,
+                min_count: None,
+                max_count: None,
+                anti_affinity: Vec::new(),
+                weight_key: None,
+                selector: config.spread_config.selector,
+                reconcile_interval: config.spread_config.reconcile_interval,
+                instances_per_host: None,
             }
         } else {
             config.spread_config
@@ -348,8 +428,17 @@ impl<S: ReadStore + Send + Sync> ProviderDaemonScaler<S> {
             },
             id,
             status: RwLock::new(StatusInfo::reconciling("")),
+            host_quiet_period: Duration::ZERO,
+            selector,
         }
     }
+
+    /// Configures how long after a host starts before the provider can be placed on it, giving the
+    /// host time to finish starting up. Disabled by default
+    pub fn with_host_quiet_period(mut self, quiet_period: Duration) -> Self {
+        self.host_quiet_period = quiet_period;
+        self
+    }
 }
 
 #[cfg(test)]
@@ -366,7 +455,7 @@ mod test {
     use crate::{
         commands::{Command, StartProvider},
         scaler::{spreadscaler::spreadscaler_annotations, Scaler},
-        storage::{Host, Provider, Store},
+        storage::{Host, Provider, ProviderStatus, Store},
         test_util::TestStore,
     };
 
@@ -384,6 +473,14 @@ mod test {
             spread_config: SpreadScalerProperty {
                 instances: 1,
                 spread: vec![],
+                topology_key: None,
+                min_count: None,
+                max_count: None,
+                anti_affinity: Vec::new(),
+                weight_key: None,
+                selector: Vec::new(),
+                reconcile_interval: None,
+                instances_per_host: None,
             },
             provider_config: vec![],
         };
@@ -399,6 +496,14 @@ mod test {
             spread_config: SpreadScalerProperty {
                 instances: 1,
                 spread: vec![],
+                topology_key: None,
+                min_count: None,
+                max_count: None,
+                anti_affinity: Vec::new(),
+                weight_key: None,
+                selector: Vec::new(),
+                reconcile_interval: None,
+                instances_per_host: None,
             },
             provider_config: vec!["foobar".to_string()],
         };
@@ -440,6 +545,7 @@ mod test {
                     version: None,
                     id: host_id_one.to_string(),
                     last_seen: Utc::now(),
+                    ..Default::default()
                 },
             )
             .await?;
@@ -461,6 +567,7 @@ mod test {
                     version: None,
                     id: host_id_two.to_string(),
                     last_seen: Utc::now(),
+                    ..Default::default()
                 },
             )
             .await?;
@@ -475,6 +582,7 @@ mod test {
                     issuer: "issuer".to_string(),
                     reference: provider_ref.to_string(),
                     hosts: HashMap::new(),
+                    capabilities: Vec::new(),
                 },
             )
             .await?;
@@ -488,6 +596,14 @@ mod test {
                 requirements: BTreeMap::from_iter([("inda".to_string(), "cloud".to_string())]),
                 weight: Some(100),
             }],
+            topology_key: None,
+            min_count: None,
+            max_count: None,
+            anti_affinity: Vec::new(),
+            weight_key: None,
+            selector: Vec::new(),
+            reconcile_interval: None,
+            instances_per_host: None,
         };
 
         let spreadscaler = ProviderDaemonScaler::new(
@@ -596,6 +712,7 @@ mod test {
                     version: None,
                     id: host_id_one.to_string(),
                     last_seen: Utc::now(),
+                    ..Default::default()
                 },
             )
             .await?;
@@ -621,6 +738,7 @@ mod test {
                     version: None,
                     id: host_id_two.to_string(),
                     last_seen: Utc::now(),
+                    ..Default::default()
                 },
             )
             .await?;
@@ -635,9 +753,10 @@ mod test {
                     issuer: "issuer".to_string(),
                     reference: provider_ref.to_string(),
                     hosts: HashMap::from([
-                        (host_id_one.to_string(), ProviderStatus::Failed),
-                        (host_id_two.to_string(), ProviderStatus::Running),
+                        (host_id_one.to_string(), ProviderStatus::Failed.into()),
+                        (host_id_two.to_string(), ProviderStatus::Running.into()),
                     ]),
+                    capabilities: Vec::new(),
                 },
             )
             .await?;
@@ -651,6 +770,14 @@ mod test {
                 requirements: BTreeMap::from_iter([("inda".to_string(), "cloud".to_string())]),
                 weight: Some(100),
             }],
+            topology_key: None,
+            min_count: None,
+            max_count: None,
+            anti_affinity: Vec::new(),
+            weight_key: None,
+            selector: Vec::new(),
+            reconcile_interval: None,
+            instances_per_host: None,
         };
 
         let spreadscaler = ProviderDaemonScaler::new(
@@ -688,9 +815,10 @@ mod test {
                     issuer: "issuer".to_string(),
                     reference: provider_ref.to_string(),
                     hosts: HashMap::from([
-                        (host_id_one.to_string(), ProviderStatus::Pending),
-                        (host_id_two.to_string(), ProviderStatus::Running),
+                        (host_id_one.to_string(), ProviderStatus::Pending.into()),
+                        (host_id_two.to_string(), ProviderStatus::Running.into()),
                     ]),
+                    capabilities: Vec::new(),
                 },
             )
             .await?;
@@ -745,6 +873,7 @@ mod test {
                     version: None,
                     id: host_id_one.to_string(),
                     last_seen: Utc::now(),
+                    ..Default::default()
                 },
             )
             .await?;
@@ -770,6 +899,7 @@ mod test {
                     version: None,
                     id: host_id_two.to_string(),
                     last_seen: Utc::now(),
+                    ..Default::default()
                 },
             )
             .await?;
@@ -784,9 +914,10 @@ mod test {
                     issuer: "issuer".to_string(),
                     reference: provider_ref.to_string(),
                     hosts: HashMap::from([
-                        (host_id_one.to_string(), ProviderStatus::Failed),
-                        (host_id_two.to_string(), ProviderStatus::Running),
+                        (host_id_one.to_string(), ProviderStatus::Failed.into()),
+                        (host_id_two.to_string(), ProviderStatus::Running.into()),
                     ]),
+                    capabilities: Vec::new(),
                 },
             )
             .await?;
@@ -800,6 +931,14 @@ mod test {
                 requirements: BTreeMap::from_iter([("inda".to_string(), "cloud".to_string())]),
                 weight: Some(100),
             }],
+            topology_key: None,
+            min_count: None,
+            max_count: None,
+            anti_affinity: Vec::new(),
+            weight_key: None,
+            selector: Vec::new(),
+            reconcile_interval: None,
+            instances_per_host: None,
         };
 
         let spreadscaler = ProviderDaemonScaler::new(
@@ -827,10 +966,10 @@ mod test {
             ))
             .await?;
 
-        assert_eq!(
-            spreadscaler.status.read().await.to_owned(),
-            StatusInfo::failed("Unhealthy provider on 1 host(s)")
-        );
+        let unhealthy = StatusInfo::failed(&format!(
+            "Unhealthy provider on 1 host(s): provider {provider_id} on host {host_id_one} failed"
+        ));
+        assert_eq!(spreadscaler.status.read().await.to_owned(), unhealthy);
         Ok(())
     }
 }
@@ -0,0 +1,142 @@
+//! Set-based host label selectors for daemonscalers, e.g. `zone In [us-east-1, us-east-2]`
+
+use std::collections::{BTreeSet, HashMap};
+use std::str::FromStr;
+
+use anyhow::{bail, Context, Result};
+
+/// How a [`SelectorClause`] compares a host label to its values
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SelectorOperator {
+    /// The label must be set to one of the values
+    In,
+    /// The label must not be set to any of the values. Hosts without the label pass
+    NotIn,
+}
+
+/// A single clause of a [`LabelSelector`], in the form `KEY In [VALUE, ...]` or
+/// `KEY NotIn [VALUE, ...]`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SelectorClause {
+    pub key: String,
+    pub operator: SelectorOperator,
+    pub values: BTreeSet<String>,
+}
+
+impl SelectorClause {
+    /// Returns true if the given host labels satisfy this clause. An empty `In` list matches no
+    /// hosts
+    pub fn matches(&self, labels: &HashMap<String, String>) -> bool {
+        let value = labels.get(&self.key);
+        match self.operator {
+            SelectorOperator::In => value.is_some_and(|value| self.values.contains(value)),
+            SelectorOperator::NotIn => !value.is_some_and(|value| self.values.contains(value)),
+        }
+    }
+}
+
+impl FromStr for SelectorClause {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid =
+            || format!("Invalid selector '{s}', expected KEY In [VALUES] or KEY NotIn [VALUES]");
+        let (key, rest) = s
+            .trim()
+            .split_once(char::is_whitespace)
+            .with_context(invalid)?;
+        let (operator, values) = rest.trim_start().split_once('[').with_context(invalid)?;
+        let operator = match operator.trim() {
+            "In" => SelectorOperator::In,
+            "NotIn" => SelectorOperator::NotIn,
+            _ => bail!(invalid()),
+        };
+        let values = values.trim_end().strip_suffix(']').with_context(invalid)?;
+        if values.contains(['[', ']']) {
+            bail!(invalid());
+        }
+        Ok(SelectorClause {
+            key: key.to_owned(),
+            operator,
+            values: values
+                .split(',')
+                .map(str::trim)
+                .filter(|value| !value.is_empty())
+                .map(ToOwned::to_owned)
+                .collect(),
+        })
+    }
+}
+
+/// A set of clauses that a host's labels must all satisfy for a daemonscaler to place anything on
+/// it. An empty selector matches every host
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LabelSelector {
+    clauses: Vec<SelectorClause>,
+}
+
+impl LabelSelector {
+    /// Parses each of the given clauses, failing if any of them is malformed
+    pub fn parse(clauses: &[String]) -> Result<LabelSelector> {
+        Ok(LabelSelector {
+            clauses: clauses
+                .iter()
+                .map(|clause| clause.parse())
+                .collect::<Result<_>>()?,
+        })
+    }
+
+    /// Returns true if the given host labels satisfy every clause of this selector
+    pub fn matches(&self, labels: &HashMap<String, String>) -> bool {
+        self.clauses.iter().all(|clause| clause.matches(labels))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn labels(pairs: &[(&str, &str)]) -> HashMap<String, String> {
+        pairs
+            .iter()
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn matches_set_based_clauses() {
+        let selector = LabelSelector::parse(&[
+            "zone In [us-east-1, us-east-2]".to_string(),
+            "tier NotIn [spare]".to_string(),
+        ])
+        .expect("selector should parse");
+
+        assert!(selector.matches(&labels(&[("zone", "us-east-1"), ("tier", "web")])));
+        // A NotIn clause passes for hosts without the label
+        assert!(selector.matches(&labels(&[("zone", "us-east-2")])));
+        assert!(!selector.matches(&labels(&[("zone", "us-east-1"), ("tier", "spare")])));
+        assert!(!selector.matches(&labels(&[("zone", "us-west-1")])));
+        assert!(!selector.matches(&labels(&[("tier", "web")])));
+
+        let empty_in = LabelSelector::parse(&["zone In []".to_string()]).unwrap();
+        assert!(!empty_in.matches(&labels(&[("zone", "us-east-1")])));
+        assert!(LabelSelector::default().matches(&labels(&[])));
+    }
+
+    #[test]
+    fn rejects_malformed_selectors() {
+        for selector in [
+            "zone",
+            "zone In us-east-1",
+            "zone Is [us-east-1]",
+            "zone In [us-east-1",
+            "zone In [[us-east-1]]",
+            "In [us-east-1]",
+        ] {
+            assert!(
+                LabelSelector::parse(&[selector.to_string()]).is_err(),
+                "{selector} should not parse"
+            );
+        }
+    }
+}
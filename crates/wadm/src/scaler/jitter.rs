@@ -0,0 +1,73 @@
+//! Contains helpers for spreading the periodic reconciles of different models across an interval
+
+use std::time::Duration;
+
+use sha2::{Digest, Sha256};
+use tokio::time::{Instant, Interval, MissedTickBehavior};
+
+/// Returns how long after the start of every interval the given model should be reconciled. The
+/// offset comes from a hash of the model name, so it is the same on every wadm instance and across
+/// restarts, and is always less than `jitter`. The jitter is capped at the interval, and a jitter
+/// of zero reconciles every model at the start of the interval
+pub fn reconcile_offset(model_name: &str, interval: Duration, jitter: Duration) -> Duration {
+    let jitter = jitter.min(interval).as_nanos();
+    if jitter == 0 {
+        return Duration::ZERO;
+    }
+    let hash = Sha256::digest(model_name.as_bytes());
+    // SAFETY: A sha256 hash is always 32 bytes long
+    let position = u64::from_be_bytes(hash[..8].try_into().unwrap());
+    let offset = (position as u128 * jitter) >> u64::BITS;
+    Duration::from_nanos(offset as u64)
+}
+
+/// Returns an [`Interval`] that ticks every `interval` for the given model, with the first tick
+/// delayed by the model's [`reconcile_offset`] so models don't all reconcile at the same time
+pub fn jittered_interval(model_name: &str, interval: Duration, jitter: Duration) -> Interval {
+    let start = Instant::now() + reconcile_offset(model_name, interval, jitter);
+    let mut ticker = tokio::time::interval_at(start, interval);
+    // Catching up on missed ticks all at once would line the reconciles back up
+    ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    ticker
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::BTreeSet;
+
+    use super::*;
+
+    #[test]
+    fn reconciles_are_spread_across_the_interval() {
+        let interval = Duration::from_secs(60);
+        let offsets = (0..100)
+            .map(|i| reconcile_offset(&format!("model-{i}"), interval, interval))
+            .collect::<Vec<_>>();
+
+        assert!(offsets.iter().all(|offset| *offset < interval));
+        // Every 10 second slice of the interval should have some of the models in it
+        let slices = offsets
+            .iter()
+            .map(|offset| offset.as_secs() / 10)
+            .collect::<BTreeSet<_>>();
+        assert_eq!(
+            slices.len(),
+            6,
+            "Reconciles should not be aligned: {offsets:?}"
+        );
+
+        assert_eq!(
+            reconcile_offset("model-1", interval, interval),
+            offsets[1],
+            "Offsets should be stable for a model"
+        );
+        assert!(
+            reconcile_offset("model-1", interval, Duration::from_secs(5)) < Duration::from_secs(5),
+            "Offsets should stay within the jitter range"
+        );
+        assert_eq!(
+            reconcile_offset("model-1", interval, Duration::ZERO),
+            Duration::ZERO
+        );
+    }
+}
@@ -0,0 +1,405 @@
+//! Contains a scaler wrapper that only allows non-urgent changes during configured maintenance
+//! windows
+
+use std::{
+    str::FromStr,
+    sync::atomic::{AtomicBool, Ordering},
+    time::Duration,
+};
+
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use chrono::{NaiveTime, Utc};
+use tracing::{trace, warn};
+use wadm_types::{
+    api::{StatusInfo, StatusType},
+    Manifest, TraitProperty,
+};
+
+use crate::{
+    commands::Command,
+    events::Event,
+    scaler::{ReconcilePlan, Scaler},
+};
+
+use super::manager::{BoxedScaler, ScalerList};
+
+/// A daily window of time (in UTC) during which changes are allowed. A window where the end is
+/// before the start wraps around midnight, and a window where both are the same is always open
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MaintenanceWindow {
+    pub start: NaiveTime,
+    pub end: NaiveTime,
+}
+
+impl MaintenanceWindow {
+    fn contains(&self, time: NaiveTime) -> bool {
+        match self.start.cmp(&self.end) {
+            std::cmp::Ordering::Less => self.start <= time && time < self.end,
+            std::cmp::Ordering::Greater => time >= self.start || time < self.end,
+            std::cmp::Ordering::Equal => true,
+        }
+    }
+}
+
+impl FromStr for MaintenanceWindow {
+    type Err = anyhow::Error;
+
+    /// Parses a window in the form of `HH:MM-HH:MM`
+    fn from_str(s: &str) -> Result<Self> {
+        let (start, end) = s
+            .split_once('-')
+            .with_context(|| format!("maintenance window '{s}' must be in the form HH:MM-HH:MM"))?;
+        let parse = |time: &str| {
+            NaiveTime::parse_from_str(time.trim(), "%H:%M")
+                .with_context(|| format!("invalid time '{time}' in maintenance window '{s}'"))
+        };
+        Ok(MaintenanceWindow {
+            start: parse(start)?,
+            end: parse(end)?,
+        })
+    }
+}
+
+/// A set of daily [`MaintenanceWindow`]s during which changes are allowed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MaintenanceSchedule {
+    windows: Vec<MaintenanceWindow>,
+}
+
+impl MaintenanceSchedule {
+    pub fn new(windows: Vec<MaintenanceWindow>) -> MaintenanceSchedule {
+        MaintenanceSchedule { windows }
+    }
+
+    /// Returns true if the given time is within any of the windows
+    pub fn is_open_at(&self, time: NaiveTime) -> bool {
+        self.windows.iter().any(|window| window.contains(time))
+    }
+
+    /// Returns the start of the next window to open after the given time
+    fn next_opening(&self, time: NaiveTime) -> Option<NaiveTime> {
+        self.windows
+            .iter()
+            .map(|window| window.start)
+            // Wrapping subtraction gives how long until the window starts, even across midnight
+            .min_by_key(|start| start.overflowing_sub_signed(time - NaiveTime::MIN).0)
+    }
+}
+
+impl FromStr for MaintenanceSchedule {
+    type Err = anyhow::Error;
+
+    /// Parses a comma separated list of windows, e.g. `22:00-02:00,12:00-13:00`
+    fn from_str(s: &str) -> Result<Self> {
+        let windows = s
+            .split(',')
+            .filter(|window| !window.trim().is_empty())
+            .map(MaintenanceWindow::from_str)
+            .collect::<Result<Vec<_>>>()?;
+        if windows.is_empty() {
+            anyhow::bail!("maintenance schedule must contain at least one window");
+        }
+        Ok(MaintenanceSchedule { windows })
+    }
+}
+
+/// Wraps every scaler in the list in a [`MaintenanceScaler`] if the manifest has a maintenance
+/// schedule. An invalid schedule is logged and ignored
+pub(crate) fn apply_maintenance_schedule(manifest: &Manifest, scalers: ScalerList) -> ScalerList {
+    let Some(raw) = manifest.maintenance_windows() else {
+        return scalers;
+    };
+    let schedule = match MaintenanceSchedule::from_str(raw) {
+        Ok(schedule) => schedule,
+        Err(e) => {
+            warn!(error = %e, name = %manifest.metadata.name, "Ignoring invalid maintenance schedule");
+            return scalers;
+        }
+    };
+    scalers
+        .into_iter()
+        .map(|scaler| Box::new(MaintenanceScaler::new(scaler, schedule.clone())) as BoxedScaler)
+        .collect()
+}
+
+/// How often a [`MaintenanceScaler`] checks whether its schedule has opened so it can apply any
+/// deferred changes. Windows are set to the minute, so this lands in every window at least once
+const WINDOW_CHECK_INTERVAL: Duration = Duration::from_secs(30);
+
+/// The MaintenanceScaler wraps another scaler and only lets it make non-urgent changes while its
+/// [`MaintenanceSchedule`] is open. Outside of the schedule, the wrapped scaler is only run in
+/// response to events that mean something has failed (like a host stopping or an instance failing
+/// to start), so failures are still recovered from.
+///
+/// Deferred commands aren't kept, as they would be stale by the time the schedule opens. Instead,
+/// the scaler records that changes are waiting and is reconciled on a timer, so the wrapped scaler
+/// recomputes them from the current state as soon as the schedule opens
+pub struct MaintenanceScaler {
+    inner: BoxedScaler,
+    schedule: MaintenanceSchedule,
+    /// Whether changes were held back since the last time the wrapped scaler was reconciled
+    deferred: AtomicBool,
+}
+
+impl MaintenanceScaler {
+    pub fn new(inner: BoxedScaler, schedule: MaintenanceSchedule) -> MaintenanceScaler {
+        MaintenanceScaler {
+            inner,
+            schedule,
+            deferred: AtomicBool::new(false),
+        }
+    }
+
+    fn is_open(&self) -> bool {
+        self.schedule.is_open_at(Utc::now().time())
+    }
+
+    fn defer(&self, reason: &str) -> Vec<Command> {
+        trace!(scaler_id = %self.id(), "Outside of maintenance window, deferring {reason}");
+        self.deferred.store(true, Ordering::Relaxed);
+        Vec::with_capacity(0)
+    }
+}
+
+/// Returns true if the event means something in the lattice failed and needs to be replaced
+fn is_urgent(event: &Event) -> bool {
+    matches!(
+        event,
+        Event::HostStopped(_)
+            | Event::ProviderStopped(_)
+            | Event::ProviderStartFailed(_)
+            | Event::ProviderHealthCheckFailed(_)
+            | Event::ComponentScaleFailed(_)
+    )
+}
+
+#[async_trait]
+impl Scaler for MaintenanceScaler {
+    fn id(&self) -> &str {
+        self.inner.id()
+    }
+
+    fn name(&self) -> String {
+        self.inner.name()
+    }
+
+    fn kind(&self) -> &str {
+        self.inner.kind()
+    }
+
+    async fn status(&self) -> StatusInfo {
+        let status = self.inner.status().await;
+        let now = Utc::now().time();
+        match (status.status_type, self.schedule.next_opening(now)) {
+            (StatusType::Reconciling, Some(opening)) if !self.schedule.is_open_at(now) => {
+                StatusInfo::reconciling(&format!(
+                    "Changes deferred until the maintenance window opens at {} UTC",
+                    opening.format("%H:%M")
+                ))
+            }
+            _ => status,
+        }
+    }
+
+    async fn update_config(&mut self, config: TraitProperty) -> Result<Vec<Command>> {
+        // The wrapped scaler keeps the new config, so the next reconcile in the window applies it
+        let commands = self.inner.update_config(config).await?;
+        if self.is_open() {
+            Ok(commands)
+        } else {
+            Ok(self.defer("config update"))
+        }
+    }
+
+    async fn handle_event(&self, event: &Event) -> Result<Vec<Command>> {
+        if is_urgent(event) || self.is_open() {
+            self.inner.handle_event(event).await
+        } else {
+            Ok(self.defer("changes"))
+        }
+    }
+
+    async fn reconcile(&self) -> Result<Vec<Command>> {
+        if self.is_open() {
+            if self.deferred.swap(false, Ordering::Relaxed) {
+                trace!(scaler_id = %self.id(), "Maintenance window open, applying deferred changes");
+            }
+            self.inner.reconcile().await
+        } else {
+            Ok(self.defer("changes"))
+        }
+    }
+
+    async fn diff(&self) -> Result<ReconcilePlan> {
+        // The plan shows what will change once the window opens
+        self.inner.diff().await
+    }
+
+    async fn cleanup(&self) -> Result<Vec<Command>> {
+        // Removing a manifest is an explicit request, so it isn't held back by the schedule
+        self.inner.cleanup().await
+    }
+
+    fn pause(&self) {
+        self.inner.pause()
+    }
+
+    fn resume(&self) {
+        self.inner.resume()
+    }
+
+    fn reconcile_interval(&self) -> Option<Duration> {
+        // Check for the window opening often enough to apply deferred changes right away
+        Some(
+            self.inner
+                .reconcile_interval()
+                .map_or(WINDOW_CHECK_INTERVAL, |interval| {
+                    interval.min(WINDOW_CHECK_INTERVAL)
+                }),
+        )
+    }
+
+    fn shared_resource(&self) -> Option<String> {
+        self.inner.shared_resource()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use chrono::TimeDelta;
+
+    use super::*;
+    use crate::{
+        commands::ScaleComponent,
+        events::{HostHeartbeat, HostStopped},
+    };
+
+    /// A scaler that always wants to start another instance
+    struct ScaleUpScaler;
+
+    #[async_trait]
+    impl Scaler for ScaleUpScaler {
+        fn id(&self) -> &str {
+            "scaleup"
+        }
+
+        async fn status(&self) -> StatusInfo {
+            StatusInfo::reconciling("Scaling component on 1 host(s)")
+        }
+
+        async fn update_config(&mut self, _config: TraitProperty) -> Result<Vec<Command>> {
+            self.reconcile().await
+        }
+
+        async fn handle_event(&self, _event: &Event) -> Result<Vec<Command>> {
+            self.reconcile().await
+        }
+
+        async fn reconcile(&self) -> Result<Vec<Command>> {
+            Ok(vec![Command::ScaleComponent(ScaleComponent {
+                component_id: "component".to_string(),
+                host_id: "host".to_string(),
+                count: 1,
+                ..Default::default()
+            })])
+        }
+
+        async fn cleanup(&self) -> Result<Vec<Command>> {
+            Ok(Vec::new())
+        }
+    }
+
+    #[test]
+    fn windows_wrap_around_midnight() {
+        let schedule = MaintenanceSchedule::from_str("22:00-02:00, 12:00-13:00").unwrap();
+        let at = |time: &str| NaiveTime::parse_from_str(time, "%H:%M").unwrap();
+        assert!(schedule.is_open_at(at("23:30")));
+        assert!(schedule.is_open_at(at("01:59")));
+        assert!(schedule.is_open_at(at("12:00")));
+        assert!(!schedule.is_open_at(at("02:00")));
+        assert!(!schedule.is_open_at(at("21:59")));
+        assert_eq!(schedule.next_opening(at("14:00")), Some(at("22:00")));
+        assert_eq!(schedule.next_opening(at("23:00")), Some(at("12:00")));
+        assert!(MaintenanceSchedule::from_str("tomorrow").is_err());
+    }
+
+    #[tokio::test]
+    async fn defers_non_urgent_changes_outside_of_window() {
+        // A window that opened an hour ago and closed a minute ago
+        let now = Utc::now().time();
+        let closed = MaintenanceSchedule::new(vec![MaintenanceWindow {
+            start: now - TimeDelta::hours(1),
+            end: now - TimeDelta::minutes(1),
+        }]);
+        let scaler = MaintenanceScaler::new(Box::new(ScaleUpScaler), closed);
+
+        assert!(
+            scaler.reconcile().await.unwrap().is_empty(),
+            "Reconcile should be deferred outside of the window"
+        );
+        let heartbeat = Event::HostHeartbeat(HostHeartbeat {
+            components: vec![],
+            providers: vec![],
+            labels: Default::default(),
+            issuer: String::new(),
+            friendly_name: "host".to_string(),
+            uptime_seconds: 60,
+            uptime_human: "60s".to_string(),
+            version: semver::Version::new(1, 0, 0),
+            host_id: "host".to_string(),
+            utilization: None,
+        });
+        assert!(
+            scaler.handle_event(&heartbeat).await.unwrap().is_empty(),
+            "Non-urgent events should be deferred outside of the window"
+        );
+
+        let host_stopped = Event::HostStopped(HostStopped {
+            labels: Default::default(),
+            id: "other".to_string(),
+        });
+        assert_eq!(
+            scaler.handle_event(&host_stopped).await.unwrap(),
+            ScaleUpScaler.reconcile().await.unwrap(),
+            "Failure recovery should still run outside of the window"
+        );
+
+        let status = scaler.status().await;
+        assert_eq!(status.status_type, StatusType::Reconciling);
+        assert!(
+            status.message.starts_with("Changes deferred until"),
+            "Status should say changes are deferred, got: {}",
+            status.message
+        );
+
+        let mut scaler = scaler;
+        assert!(
+            scaler
+                .update_config(TraitProperty::Custom(serde_json::Value::Null))
+                .await
+                .unwrap()
+                .is_empty(),
+            "Config updates should be deferred outside of the window"
+        );
+
+        // Changes are applied on the next timed reconcile once the window opens
+        assert_eq!(scaler.reconcile_interval(), Some(WINDOW_CHECK_INTERVAL));
+        assert!(scaler.deferred.load(Ordering::Relaxed));
+        scaler.schedule = MaintenanceSchedule::from_str("00:00-00:00").unwrap();
+        assert_eq!(
+            scaler.reconcile().await.unwrap(),
+            ScaleUpScaler.reconcile().await.unwrap(),
+            "Deferred changes should be applied once the window opens"
+        );
+        assert!(!scaler.deferred.load(Ordering::Relaxed));
+
+        // The same scaler is unrestricted when the window is open
+        let open = MaintenanceScaler::new(
+            Box::new(ScaleUpScaler),
+            MaintenanceSchedule::from_str("00:00-00:00").unwrap(),
+        );
+        assert_eq!(open.reconcile().await.unwrap().len(), 1);
+        assert_eq!(open.status().await, ScaleUpScaler.status().await);
+    }
+}
@@ -1,6 +1,11 @@
 //! A struct that manages creating and removing scalers for all manifests
 
-use std::{collections::HashMap, ops::Deref, sync::Arc};
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet},
+    ops::Deref,
+    sync::{Arc, Weak},
+    time::{Duration, Instant},
+};
 
 use anyhow::Result;
 use async_nats::jetstream::{
@@ -13,28 +18,86 @@ use cloudevents::Event as CloudEvent;
 use futures::StreamExt;
 use serde::{Deserialize, Serialize};
 use tokio::{
-    sync::{OwnedRwLockReadGuard, RwLock},
+    sync::{watch, OwnedRwLockReadGuard, RwLock},
     task::JoinHandle,
+    time::MissedTickBehavior,
 };
-use tracing::{debug, error, instrument, trace, warn};
+use tracing::{debug, error, info, instrument, trace, warn};
 use wadm_types::{
-    api::{Status, StatusInfo},
-    Manifest,
+    api::{Status, StatusInfo, StatusType},
+    validation::{ValidationFailure, ValidationFailureLevel, ValidationReport},
+    Manifest, Policy, Properties, TraitProperty, DEPLOYED_AT_ANNOTATION_KEY,
+    VERSION_ANNOTATION_KEY,
 };
 
 use crate::{
+    commands::DeleteLink,
     events::Event,
     publisher::Publisher,
+    reload::RuntimeConfig,
     scaler::{Command, Scaler},
-    storage::{snapshot::SnapshotStore, ReadStore},
-    workers::{CommandPublisher, ConfigSource, LinkSource, SecretSource, StatusPublisher},
+    storage::{snapshot::SnapshotStore, Link as LinkState, ReadStore},
+    workers::{
+        CommandPublisher, ConfigSource, InstanceAnnotations, LinkSource, SecretSource,
+        StatusPublisher,
+    },
 };
 
-use super::convert::manifest_components_to_scalers;
+use super::{
+    convert::{
+        manifest_components_to_scaler_groups, manifest_components_to_scalers, ManifestContext,
+        ScalerOptions,
+    },
+    maintenance::apply_maintenance_schedule,
+    registry::ScalerRegistry,
+    shared::SharedScalers,
+    ComputePool, EventFingerprint, HostCapacity,
+};
 
 pub type BoxedScaler = Box<dyn Scaler + Send + Sync + 'static>;
 pub type ScalerList = Vec<BoxedScaler>;
 
+/// Returns the names of all of the configs in the manifest that are managed outside of wadm, which
+/// must already exist for the manifest to deploy
+fn external_configs(manifest: &Manifest) -> BTreeSet<&str> {
+    let component_configs =
+        manifest
+            .components()
+            .flat_map(|component| match &component.properties {
+                Properties::Component { properties } => properties.config.iter(),
+                Properties::Capability { properties } => properties.config.iter(),
+            });
+    let link_configs = manifest
+        .links()
+        .filter_map(|link| match &link.properties {
+            TraitProperty::Link(link) => Some(link),
+            _ => None,
+        })
+        .flat_map(|link| {
+            link.source
+                .iter()
+                .flat_map(|source| source.config.iter())
+                .chain(link.target.config.iter())
+        });
+    component_configs
+        .chain(link_configs)
+        .filter(|config| config.properties.is_none())
+        .map(|config| config.name.as_str())
+        .collect()
+}
+
+/// Returns the annotations of the manifest without the ones that change with every version
+fn without_version_annotations(manifest: &Manifest) -> BTreeMap<&String, &String> {
+    manifest
+        .metadata
+        .annotations
+        .iter()
+        .filter(|(key, _)| {
+            key.as_str() != VERSION_ANNOTATION_KEY && key.as_str() != DEPLOYED_AT_ANNOTATION_KEY
+        })
+        .collect()
+}
+
 pub const WADM_NOTIFY_PREFIX: &str = "wadm.notify";
 
 /// All events sent for manifest notifications
@@ -56,6 +119,19 @@ pub enum Notifications {
         scaler_id: String,
         event: CloudEvent,
     },
+    /// Remove an event from the expected list for a manifest scaler, sending only the parts of the
+    /// event needed to match it
+    RemoveExpectedFingerprint {
+        name: String,
+        scaler_id: String,
+        fingerprint: EventFingerprint,
+    },
+    /// Pause or resume a scaler for a manifest
+    SetScalerPaused {
+        name: String,
+        scaler_id: String,
+        paused: bool,
+    },
 }
 
 /// A wrapper type returned when getting a list of scalers for a model
@@ -97,6 +173,25 @@ impl Deref for SingleScaler {
     }
 }
 
+/// Settings for how a [`ScalerManager`] builds the scalers for each model
+#[derive(Clone)]
+pub struct ScalerManagerOptions {
+    /// Factories for custom trait types, which take precedence over the built in scalers
+    pub registry: ScalerRegistry,
+    /// Annotations added to everything the built in scalers start
+    pub instance_annotations: InstanceAnnotations,
+    /// The pool component spread scalers compute their commands on, if any
+    pub compute_pool: Option<ComputePool>,
+    /// How many instances per host component spread scalers share fairly between all models in
+    /// the lattice, if it is limited
+    pub max_instances_per_host: Option<usize>,
+    /// Whether scalers send other wadm instances [`EventFingerprint`]s rather than full events
+    pub compact_notifications: bool,
+    /// Settings that can change while wadm is running, such as the host quiet period and teardown
+    /// period
+    pub runtime_config: watch::Receiver<RuntimeConfig>,
+}
+
 /// A manager that consumes notifications from a stream for a lattice and then either adds or removes the
 /// necessary scalers
 #[derive(Clone)]
@@ -109,6 +204,24 @@ pub struct ScalerManager<StateStore, P: Clone, L: Clone> {
     command_publisher: CommandPublisher<P>,
     status_publisher: StatusPublisher<P>,
     snapshot_data: SnapshotStore<StateStore, L>,
+    registry: ScalerRegistry,
+    instance_annotations: InstanceAnnotations,
+    compute_pool: Option<ComputePool>,
+    host_capacity: Option<HostCapacity>,
+    compact_notifications: bool,
+    /// Settings that can change while wadm is running, such as the host quiet period and teardown
+    /// period
+    runtime_config: watch::Receiver<RuntimeConfig>,
+    /// Models that were recently undeployed, along with when their scalers were removed
+    tearing_down: Arc<RwLock<HashMap<String, Instant>>>,
+    /// The IDs of paused scalers for each model, kept so scalers that are rebuilt stay paused
+    paused: Arc<RwLock<HashMap<String, HashSet<String>>>>,
+    /// The periodic reconcile tasks for each model's scalers that have a reconcile interval
+    reconcile_timers: Arc<RwLock<HashMap<String, Vec<JoinHandle<()>>>>>,
+    /// The scalers shared by every model requiring the same resource (see
+    /// [`Scaler::shared_resource`]), so a resource is only stopped once the last model requiring
+    /// it is removed
+    shared_scalers: SharedScalers,
 }
 
 impl<StateStore, P: Clone, L: Clone> Drop for ScalerManager<StateStore, P, L> {
@@ -127,7 +240,9 @@ where
 {
     /// Creates a new ScalerManager configured to notify messages to `wadm.notify.{lattice_id}`
     /// using the given jetstream client. Also creates an ephemeral consumer for notifications on
-    /// the given stream
+    /// the given stream. Scalers are built according to the given [`ScalerManagerOptions`]. The
+    /// host quiet period and teardown period are read from the runtime config whenever they are
+    /// used, so updates to them take effect without recreating the manager
     #[allow(clippy::too_many_arguments)]
     pub async fn new(
         client: P,
@@ -139,7 +254,16 @@ where
         command_publisher: CommandPublisher<P>,
         status_publisher: StatusPublisher<P>,
         link_getter: L,
+        options: ScalerManagerOptions,
     ) -> Result<ScalerManager<StateStore, P, L>> {
+        let ScalerManagerOptions {
+            registry,
+            instance_annotations,
+            compute_pool,
+            max_instances_per_host,
+            compact_notifications,
+            runtime_config,
+        } = options;
         // Create the consumer first so that we can make sure we don't miss anything during the
         // first reconcile pass
         let subject = format!("{WADM_NOTIFY_PREFIX}.{lattice_id}");
@@ -184,21 +308,46 @@ where
             link_getter.clone(),
             lattice_id.to_owned(),
         );
+        // Capacity is shared by every model in the lattice, so there is one allocator per lattice
+        let host_capacity = max_instances_per_host.map(HostCapacity::new);
         let scalers: HashMap<String, ScalerList> = all_manifests
             .into_iter()
             .filter_map(|manifest| {
-                let data = manifest.get_deployed()?;
                 let name = manifest.name().to_owned();
+                let data = match manifest.get_deployed_resolved()? {
+                    Ok(data) => data,
+                    Err(e) => {
+                        warn!(error = %e, %name, "Unable to resolve manifest variables, skipping manifest");
+                        return None;
+                    }
+                };
+                let data = &data;
                 let scalers = manifest_components_to_scalers(
                     &data.spec.components,
-                    &data.policy_lookup(),
-                    lattice_id,
-                    &name,
-                    &subject,
-                    &client,
-                    &snapshot_data,
+                    &data.shadowed_components(),
+                    &ManifestContext {
+                        lattice_id,
+                        manifest_name: &name,
+                        manifest_version: data.version(),
+                        notifier_subject: &subject,
+                        notifier: &client,
+                        snapshot_data: &snapshot_data,
+                        policies: &data.policy_lookup(),
+                        registry: &registry,
+                        options: ScalerOptions {
+                            compute_pool: compute_pool.clone(),
+                            host_quiet_period: runtime_config.borrow().host_quiet_period,
+                            start_cooldown: runtime_config.borrow().component_start_cooldown,
+                            host_capacity: host_capacity.clone(),
+                            compact_notifications,
+                            ..ScalerOptions::from_manifest(data, &instance_annotations)
+                        },
+                    },
                 );
-                Some((name, scalers))
+                Some((
+                    name,
+                    apply_maintenance_schedule(data, scalers),
+                ))
             })
             .collect();
 
@@ -213,8 +362,40 @@ where
             command_publisher,
             status_publisher,
             snapshot_data,
+            registry,
+            instance_annotations,
+            compute_pool,
+            host_capacity,
+            compact_notifications,
+            runtime_config,
+            tearing_down: Arc::default(),
+            paused: Arc::default(),
+            reconcile_timers: Arc::default(),
+            shared_scalers: SharedScalers::default(),
         };
+        {
+            let mut all_scalers = manager.scalers.write().await;
+            // Share scalers in a stable order so the same model's scaler manages a shared resource
+            // every time
+            let mut names = all_scalers.keys().cloned().collect::<Vec<_>>();
+            names.sort_unstable();
+            for name in names {
+                let Some(scalers) = all_scalers.remove(&name) else {
+                    continue;
+                };
+                let scalers = manager.shared_scalers.share(&name, scalers).await;
+                manager.start_reconcile_timers(&name, &scalers).await;
+                all_scalers.insert(name, scalers);
+            }
+        }
         let cloned = manager.clone();
+        // Clean up in the background so a slow lattice doesn't hold up starting the manager
+        let sweeper = manager.clone();
+        tokio::spawn(async move {
+            if let Err(e) = sweeper.remove_orphaned_links().await {
+                warn!(error = %e, lattice_id = %sweeper.lattice_id, "Unable to remove orphaned links");
+            }
+        });
         let handle = tokio::spawn(async move { cloned.notify(messages).await });
         manager.handle = Some(Arc::new(handle));
         Ok(manager)
@@ -245,15 +426,117 @@ where
             command_publisher,
             status_publisher,
             snapshot_data,
+            registry: ScalerRegistry::default(),
+            instance_annotations: InstanceAnnotations::default(),
+            compute_pool: None,
+            host_capacity: None,
+            compact_notifications: false,
+            runtime_config: watch::channel(RuntimeConfig::default()).1,
+            tearing_down: Arc::default(),
+            paused: Arc::default(),
+            reconcile_timers: Arc::default(),
+            shared_scalers: SharedScalers::default(),
         }
     }
 
+    /// Sets the registry of custom scaler factories to use when creating scalers. This only
+    /// affects scalers created after it is set
+    #[cfg(test)]
+    pub(crate) fn with_scaler_registry(mut self, registry: ScalerRegistry) -> Self {
+        self.registry = registry;
+        self
+    }
+
+    /// Sets the annotations added to everything the built in scalers start. This only affects
+    /// scalers created after it is set
+    #[cfg(test)]
+    pub(crate) fn with_instance_annotations(mut self, annotations: InstanceAnnotations) -> Self {
+        self.instance_annotations = annotations;
+        self
+    }
+
+    /// Sets how long models are considered to be tearing down after they are undeployed
+    #[cfg(test)]
+    pub(crate) fn with_teardown_period(mut self, teardown_period: Duration) -> Self {
+        let runtime_config = RuntimeConfig {
+            teardown_period,
+            ..*self.runtime_config.borrow()
+        };
+        self.runtime_config = watch::channel(runtime_config).1;
+        self
+    }
+
+    /// Sets where the manager reads the settings that can change while wadm is running
+    #[cfg(test)]
+    pub(crate) fn with_runtime_config(
+        mut self,
+        runtime_config: watch::Receiver<RuntimeConfig>,
+    ) -> Self {
+        self.runtime_config = runtime_config;
+        self
+    }
+
     /// Refreshes the snapshot data consumed by all scalers. This is a temporary workaround until we
     /// start caching data
     pub(crate) async fn refresh_data(&self) -> Result<()> {
         self.snapshot_data.refresh().await
     }
 
+    /// Removes any links in the lattice that were put by a model that is no longer deployed. This
+    /// cleans up links left behind when a model's delete link commands were lost, for example if
+    /// wadm crashed while the model was being undeployed
+    #[instrument(level = "debug", skip(self), fields(lattice_id = %self.lattice_id))]
+    pub(crate) async fn remove_orphaned_links(&self) -> Result<()> {
+        self.snapshot_data.refresh().await?;
+        let owners = self
+            .snapshot_data
+            .list::<LinkState>(&self.lattice_id)
+            .await?;
+        if owners.is_empty() {
+            return Ok(());
+        }
+        let deployed = self
+            .scalers
+            .read()
+            .await
+            .keys()
+            .cloned()
+            .collect::<HashSet<_>>();
+
+        let commands = self
+            .snapshot_data
+            .get_links()
+            .await?
+            .into_iter()
+            .filter_map(|link| {
+                let key = LinkState::key(
+                    link.source_id(),
+                    link.wit_namespace(),
+                    link.wit_package(),
+                    link.name(),
+                );
+                let owner = owners.get(&key)?;
+                (!deployed.contains(&owner.model_name)).then(|| {
+                    Command::DeleteLink(DeleteLink {
+                        source_id: link.source_id().to_owned(),
+                        wit_namespace: link.wit_namespace().to_owned(),
+                        wit_package: link.wit_package().to_owned(),
+                        link_name: link.name().to_owned(),
+                        model_name: owner.model_name.clone(),
+                    })
+                })
+            })
+            .collect::<Vec<_>>();
+        if commands.is_empty() {
+            return Ok(());
+        }
+        info!(
+            num_links = commands.len(),
+            "Removing links owned by models that are no longer deployed"
+        );
+        self.command_publisher.publish_commands(commands).await
+    }
+
     /// Adds scalers for the given manifest. Emitting an event to notify other wadm processes that
     /// they should create them as well. Only returns an error if it can't notify. Returns the
     /// scaler list for immediate use in reconciliation
@@ -279,18 +562,146 @@ where
             .ok_or_else(|| anyhow::anyhow!("Data error: scalers no longer exist after creation"))
     }
 
+    /// Returns the options for the built in scalers of the given manifest
+    fn scaler_options(&self, manifest: &Manifest) -> ScalerOptions {
+        ScalerOptions {
+            compute_pool: self.compute_pool.clone(),
+            host_quiet_period: self.runtime_config.borrow().host_quiet_period,
+            start_cooldown: self.runtime_config.borrow().component_start_cooldown,
+            host_capacity: self.host_capacity.clone(),
+            compact_notifications: self.compact_notifications,
+            ..ScalerOptions::from_manifest(manifest, &self.instance_annotations)
+        }
+    }
+
     pub fn scalers_for_manifest<'a>(&'a self, manifest: &'a Manifest) -> ScalerList {
+        let scalers = self.build_scalers(manifest, self.scaler_options(manifest));
+        apply_maintenance_schedule(manifest, scalers)
+    }
+
+    /// Returns everything shared by the scalers built for the given manifest
+    fn manifest_context<'a>(
+        &'a self,
+        manifest: &'a Manifest,
+        policies: &'a HashMap<&'a String, &'a Policy>,
+        options: ScalerOptions,
+    ) -> ManifestContext<'a, StateStore, P, L> {
+        ManifestContext {
+            lattice_id: &self.lattice_id,
+            manifest_name: &manifest.metadata.name,
+            manifest_version: manifest.version(),
+            notifier_subject: &self.subject,
+            notifier: &self.client,
+            snapshot_data: &self.snapshot_data,
+            policies,
+            registry: &self.registry,
+            options,
+        }
+    }
+
+    fn build_scalers(&self, manifest: &Manifest, options: ScalerOptions) -> ScalerList {
         manifest_components_to_scalers(
             &manifest.spec.components,
-            &manifest.policy_lookup(),
-            &self.lattice_id,
-            &manifest.metadata.name,
-            &self.subject,
-            &self.client,
-            &self.snapshot_data,
+            &manifest.shadowed_components(),
+            &self.manifest_context(manifest, &manifest.policy_lookup(), options),
         )
     }
 
+    /// Checks whether the given manifest could be deployed to this lattice as it is right now,
+    /// without deploying it. Along with the same validation done when a manifest is put, this
+    /// builds the manifest's scalers against the current state of the lattice and reports every
+    /// scaler that could not reach its desired state, such as a spread with no eligible hosts, and
+    /// any externally managed configuration that doesn't exist.
+    ///
+    /// Nothing is stored or published and no commands are sent. The scalers are never added to
+    /// this manager and any share of host capacity they claim is discarded with them
+    #[instrument(level = "debug", skip_all, fields(name = %manifest.metadata.name, lattice_id = %self.lattice_id))]
+    pub async fn validate_manifest(&self, manifest: &Manifest) -> Result<ValidationReport> {
+        let mut failures = wadm_types::validation::validate_manifest(manifest).await?;
+        self.snapshot_data.refresh().await?;
+
+        let options = ScalerOptions {
+            host_capacity: self
+                .host_capacity
+                .as_ref()
+                .map(|capacity| capacity.detached_without(&manifest.metadata.name)),
+            ..self.scaler_options(manifest)
+        };
+        for scaler in self.build_scalers(manifest, options) {
+            let status = scaler.status().await;
+            if status.status_type == StatusType::Failed {
+                failures.push(ValidationFailure::new(
+                    ValidationFailureLevel::Error,
+                    format!(
+                        "{} for {} cannot be satisfied: {}",
+                        scaler.kind(),
+                        scaler.name(),
+                        status.message
+                    ),
+                ));
+            }
+        }
+
+        for config_name in external_configs(manifest) {
+            if self.snapshot_data.get_config(config_name).await?.is_none() {
+                failures.push(ValidationFailure::new(
+                    ValidationFailureLevel::Error,
+                    format!("configuration [{config_name}] does not exist in the lattice"),
+                ));
+            }
+        }
+
+        Ok(failures.into_iter().collect())
+    }
+
+    /// Builds the scalers for a new version of a manifest that replaces `previous`, keeping the
+    /// `existing` scalers of every component that didn't change between the two versions so only
+    /// the scalers affected by the change are rebuilt. Returns the scalers for the new version
+    /// along with the existing scalers that weren't kept
+    pub(crate) fn update_scalers_for_manifest(
+        &self,
+        previous: &Manifest,
+        manifest: &Manifest,
+        mut existing: ScalerList,
+    ) -> (ScalerList, ScalerList) {
+        let groups = manifest_components_to_scaler_groups(
+            &manifest.spec.components,
+            &manifest.shadowed_components(),
+            &self.manifest_context(
+                manifest,
+                &manifest.policy_lookup(),
+                self.scaler_options(manifest),
+            ),
+        );
+        // Anything manifest wide can change how every scaler behaves, so nothing can be kept if it
+        // changed
+        let options_unchanged = without_version_annotations(previous)
+            == without_version_annotations(manifest)
+            && previous.spec.policies == manifest.spec.policies;
+
+        let mut scalers = ScalerList::with_capacity(existing.len());
+        for (component, group) in manifest.spec.components.iter().zip(groups) {
+            let unchanged = options_unchanged
+                && previous.spec.components.contains(component)
+                && group
+                    .iter()
+                    .all(|new| existing.iter().any(|old| old.id() == new.id()));
+            if !unchanged {
+                scalers.extend(apply_maintenance_schedule(manifest, group));
+                continue;
+            }
+            for new in group {
+                // SAFETY: We just checked that every scaler in the group has an existing match
+                let idx = existing
+                    .iter()
+                    .position(|old| old.id() == new.id())
+                    .unwrap();
+                scalers.push(existing.swap_remove(idx));
+            }
+        }
+        (scalers, existing)
+    }
+
     /// Gets the scalers for the given model name, returning None if they don't exist.
     #[instrument(level = "trace", skip(self), fields(lattice_id = %self.lattice_id))]
     pub async fn get_scalers<'a>(&'a self, name: &'a str) -> Option<Scalers> {
@@ -356,7 +767,7 @@ where
         {
             error!(error = %e, "Unable to publish notification");
             if let Some(scalers) = scalers {
-                self.scalers.write().await.insert(name.to_owned(), scalers);
+                self.add_raw_scalers(name, scalers).await;
             }
             Some(Err(e))
         } else {
@@ -364,23 +775,147 @@ where
         }
     }
 
+    /// Pauses the scaler with the given ID for the given model on every wadm instance, so it stops
+    /// issuing commands until it is resumed. The scaler stays paused if it is rebuilt because of a
+    /// new version of the model, but not once the model is undeployed or wadm restarts. Returns
+    /// false if the scaler doesn't exist
+    #[instrument(level = "debug", skip(self), fields(lattice_id = %self.lattice_id))]
+    pub async fn pause_scaler(&self, name: &str, scaler_id: &str) -> Result<bool> {
+        self.set_scaler_paused(name, scaler_id, true).await
+    }
+
+    /// Resumes a scaler paused with [`ScalerManager::pause_scaler`] on every wadm instance.
+    /// Returns false if the scaler doesn't exist
+    #[instrument(level = "debug", skip(self), fields(lattice_id = %self.lattice_id))]
+    pub async fn resume_scaler(&self, name: &str, scaler_id: &str) -> Result<bool> {
+        self.set_scaler_paused(name, scaler_id, false).await
+    }
+
+    async fn set_scaler_paused(&self, name: &str, scaler_id: &str, paused: bool) -> Result<bool> {
+        if !self.set_raw_scaler_paused(name, scaler_id, paused).await {
+            return Ok(false);
+        }
+        let notification = serde_json::to_vec(&Notifications::SetScalerPaused {
+            name: name.to_owned(),
+            scaler_id: scaler_id.to_owned(),
+            paused,
+        })?;
+        self.client
+            .publish(notification, Some(&self.subject))
+            .await?;
+        Ok(true)
+    }
+
+    /// Pauses or resumes a scaler without any of the publishing
+    async fn set_raw_scaler_paused(&self, name: &str, scaler_id: &str, paused: bool) -> bool {
+        let Some(scaler) = self.get_specific_scaler(name, scaler_id).await else {
+            return false;
+        };
+        let mut all_paused = self.paused.write().await;
+        if paused {
+            scaler.pause();
+            all_paused
+                .entry(name.to_owned())
+                .or_default()
+                .insert(scaler_id.to_owned());
+        } else {
+            scaler.resume();
+            if let Some(ids) = all_paused.get_mut(name) {
+                ids.remove(scaler_id);
+                if ids.is_empty() {
+                    all_paused.remove(name);
+                }
+            }
+        }
+        true
+    }
+
     /// An internal function to allow pushing the scalers without any of the publishing
     async fn add_raw_scalers(&self, name: &str, scalers: ScalerList) {
+        let scalers = self.shared_scalers.share(name, scalers).await;
+        if let Some(ids) = self.paused.read().await.get(name) {
+            scalers
+                .iter()
+                .filter(|scaler| ids.contains(scaler.id()))
+                .for_each(|scaler| scaler.pause());
+        }
+        self.start_reconcile_timers(name, &scalers).await;
         self.scalers.write().await.insert(name.to_owned(), scalers);
+        // A model that is deployed again is no longer being torn down
+        self.tearing_down.write().await.remove(name);
+    }
+
+    /// Returns whether the given model was undeployed less than the configured teardown period
+    /// ago. Events caused by the model's cleanup can keep arriving during this time, but there are
+    /// no scalers left that need to handle them
+    pub async fn is_tearing_down(&self, name: &str) -> bool {
+        self.tearing_down
+            .read()
+            .await
+            .get(name)
+            .is_some_and(|removed| removed.elapsed() < self.teardown_period())
+    }
+
+    fn teardown_period(&self) -> Duration {
+        self.runtime_config.borrow().teardown_period
+    }
+
+    async fn mark_tearing_down(&self, name: &str) {
+        let teardown_period = self.teardown_period();
+        if teardown_period.is_zero() {
+            return;
+        }
+        let mut tearing_down = self.tearing_down.write().await;
+        // Forget about models that are done tearing down so this doesn't grow forever
+        tearing_down.retain(|_, removed| removed.elapsed() < teardown_period);
+        tearing_down.insert(name.to_owned(), Instant::now());
     }
 
     /// A function that removes the scalers without any of the publishing
     /// CAUTION: This function does not do any cleanup, so it should only be used in scenarios
     /// where you are prepared to handle that yourself.
     pub(crate) async fn remove_raw_scalers(&self, name: &str) -> Option<ScalerList> {
+        if let Some(timers) = self.reconcile_timers.write().await.remove(name) {
+            timers.iter().for_each(JoinHandle::abort);
+        }
+        self.shared_scalers.release(name).await;
         self.scalers.write().await.remove(name)
     }
 
+    /// Starts a task for each of the given scalers that has a reconcile interval, replacing any
+    /// tasks already running for the model. Every wadm instance runs these tasks for the scalers
+    /// it has, so commands from them are only deduplicated across instances if command
+    /// deduplication is enabled
+    async fn start_reconcile_timers(&self, name: &str, scalers: &ScalerList) {
+        let timers = scalers
+            .iter()
+            .filter_map(|scaler| Some((scaler.id().to_owned(), scaler.reconcile_interval()?)))
+            .map(|(scaler_id, interval)| {
+                tokio::spawn(reconcile_periodically(
+                    Arc::downgrade(&self.scalers),
+                    self.snapshot_data.clone(),
+                    self.command_publisher.clone(),
+                    name.to_owned(),
+                    scaler_id,
+                    interval,
+                ))
+            })
+            .collect::<Vec<_>>();
+        let mut all_timers = self.reconcile_timers.write().await;
+        if let Some(previous) = all_timers.remove(name) {
+            previous.iter().for_each(JoinHandle::abort);
+        }
+        if !timers.is_empty() {
+            all_timers.insert(name.to_owned(), timers);
+        }
+    }
+
     /// Does everything except sending the notification
     #[instrument(level = "debug", skip(self), fields(lattice_id = %self.lattice_id))]
     async fn remove_scalers_internal(&self, name: &str) -> Option<Result<ScalerList>> {
         // Remove the scalers first to avoid them handling events while we're cleaning up
         let scalers = self.remove_raw_scalers(name).await?;
+        self.mark_tearing_down(name).await;
 
         // Always refresh data before cleaning up
         if let Err(e) = self.refresh_data().await {
@@ -398,16 +933,18 @@ where
             Err(e) => {
                 warn!(err = ?e, "Error when running cleanup steps for scalers. Operation will be retried");
                 // Put the scalers back into the map so we can run cleanup again on retry
-                self.scalers.write().await.insert(name.to_owned(), scalers);
+                self.add_raw_scalers(name, scalers).await;
                 return Some(Err(e));
             }
         };
         trace!(?commands, "Publishing cleanup commands");
         if let Err(e) = self.command_publisher.publish_commands(commands).await {
             error!(error = %e, "Unable to publish cleanup commands");
-            self.scalers.write().await.insert(name.to_owned(), scalers);
+            self.add_raw_scalers(name, scalers).await;
             Some(Err(e))
         } else {
+            // Pausing only lasts as long as the model is deployed
+            self.paused.write().await.remove(name);
             Some(Ok(scalers))
         }
     }
@@ -430,15 +967,11 @@ where
                             match notification {
                                 Notifications::CreateScalers(manifest) => {
                                     // We don't want to trigger the notification, so just create the scalers and then insert
-                                    let scalers = manifest_components_to_scalers(
-                                        &manifest.spec.components,
-                                        &manifest.policy_lookup(),
-                                        &self.lattice_id,
-                                        &manifest.metadata.name,
-                                        &self.subject,
-                                        &self.client,
-                                        &self.snapshot_data,
+                                    let scalers = self.build_scalers(
+                                        &manifest,
+                                        self.scaler_options(&manifest),
                                     );
+                                    let scalers = apply_maintenance_schedule(&manifest, scalers);
                                     let num_scalers = scalers.len();
                                     self.add_raw_scalers(&manifest.metadata.name, scalers).await;
                                     trace!(name = %manifest.metadata.name, %num_scalers, "Finished creating scalers for manifest");
@@ -531,6 +1064,22 @@ where
                                         debug!(%name, "Received request to remove event for non-existent scalers, ignoring");
                                     }
                                 }
+                                Notifications::RemoveExpectedFingerprint{ name, scaler_id, fingerprint } => {
+                                    trace!(%name, "Removing expected event for manifest");
+                                    if let Some(scaler) = self.get_specific_scaler(&name, &scaler_id).await {
+                                        if let Err(e) = scaler.handle_event(&Event::from(fingerprint)).await {
+                                            error!(error = %e, %name, %scaler_id, "Unable to register expected events for scaler");
+                                        }
+                                    } else {
+                                        debug!(%name, "Received request to remove event for non-existent scalers, ignoring");
+                                    }
+                                }
+                                Notifications::SetScalerPaused{ name, scaler_id, paused } => {
+                                    trace!(%name, %scaler_id, paused, "Setting whether scaler is paused");
+                                    if !self.set_raw_scaler_paused(&name, &scaler_id, paused).await {
+                                        debug!(%name, %scaler_id, "Received request to pause non-existent scaler, ignoring");
+                                    }
+                                }
                             }
                             // Always ack if we get here
                             if let Err(e) = msg.double_ack().await {
@@ -553,3 +1102,763 @@ where
         }
     }
 }
+
+/// Reconciles the given scaler every `interval` for as long as it exists, publishing any commands
+/// it returns. This goes through the same wrappers as every other reconcile, so a tick while the
+/// scaler is backing off or waiting on expected events does nothing
+#[instrument(level = "debug", skip(scalers, snapshot_data, command_publisher))]
+async fn reconcile_periodically<StateStore, P, L>(
+    scalers: Weak<RwLock<HashMap<String, ScalerList>>>,
+    snapshot_data: SnapshotStore<StateStore, L>,
+    command_publisher: CommandPublisher<P>,
+    name: String,
+    scaler_id: String,
+    interval: Duration,
+) where
+    StateStore: ReadStore + Send + Sync + Clone + 'static,
+    P: Publisher + Clone + Send + Sync + 'static,
+    L: LinkSource + ConfigSource + SecretSource + Clone + Send + Sync + 'static,
+{
+    let mut ticker = tokio::time::interval(interval);
+    ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    // The first tick completes right away, and new scalers are reconciled when they are added
+    ticker.tick().await;
+    loop {
+        ticker.tick().await;
+        let Some(scalers) = scalers.upgrade() else {
+            return;
+        };
+        if let Err(e) = snapshot_data.refresh().await {
+            warn!(error = %e, "Unable to refresh data for periodic reconcile");
+            continue;
+        }
+        let commands = {
+            let scalers = scalers.read().await;
+            let Some(scaler) = scalers
+                .get(&name)
+                .and_then(|scalers| scalers.iter().find(|scaler| scaler.id() == scaler_id))
+            else {
+                trace!("Scaler no longer exists, stopping periodic reconcile");
+                return;
+            };
+            scaler.reconcile().await
+        };
+        match commands {
+            Ok(commands) if !commands.is_empty() => {
+                trace!(?commands, "Publishing commands from periodic reconcile");
+                if let Err(e) = command_publisher.publish_commands(commands).await {
+                    error!(error = %e, "Unable to publish commands from periodic reconcile");
+                }
+            }
+            Ok(_) => {}
+            Err(e) => warn!(error = %e, "Periodic reconcile failed"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{collections::BTreeMap, sync::Arc};
+
+    use wadm_types::{validation::ValidationOutput, DEPLOYED_AT_ANNOTATION_KEY};
+    use wasmcloud_control_interface::Link;
+
+    use super::*;
+    use crate::{
+        commands::PutLink,
+        scaler::spreadscaler::{spreadscaler_annotations, SPREAD_SCALER_KIND},
+        storage::{Host, Store},
+        test_util::{RecorderPublisher, TestLatticeSource, TestStore},
+        workers::MANIFEST_VERSION_ANNOTATION,
+    };
+
+    #[tokio::test]
+    async fn removes_links_owned_by_missing_models() {
+        let lattice_id = "orphaned_links";
+        let store = Arc::new(TestStore::default());
+        let link = |source_id: &str, target: &str| {
+            Link::builder()
+                .source_id(source_id)
+                .target(target)
+                .name("default")
+                .wit_namespace("wasi")
+                .wit_package("http")
+                .interfaces(vec!["incoming-handler".to_string()])
+                .build()
+                .unwrap()
+        };
+        let owner = |source_id: &str, target: &str, model_name: &str| {
+            (
+                LinkState::key(source_id, "wasi", "http", "default"),
+                LinkState::from(&PutLink {
+                    source_id: source_id.to_string(),
+                    target: target.to_string(),
+                    name: "default".to_string(),
+                    wit_namespace: "wasi".to_string(),
+                    wit_package: "http".to_string(),
+                    interfaces: vec!["incoming-handler".to_string()],
+                    model_name: model_name.to_string(),
+                    ..Default::default()
+                }),
+            )
+        };
+        store
+            .store_many(
+                lattice_id,
+                [
+                    owner("deleted-httpserver", "deleted-component", "deleted"),
+                    owner("deployed-httpserver", "deployed-component", "deployed"),
+                ],
+            )
+            .await
+            .unwrap();
+        let lattice_source = TestLatticeSource {
+            links: vec![
+                link("deleted-httpserver", "deleted-component"),
+                link("deployed-httpserver", "deployed-component"),
+                // Links that weren't put by wadm are left alone
+                link("unmanaged-httpserver", "unmanaged-component"),
+            ],
+            ..Default::default()
+        };
+
+        let publisher = RecorderPublisher::<Command> {
+            received: Arc::new(RwLock::new(Vec::new())),
+        };
+        let manager = ScalerManager::test_new(
+            publisher.clone(),
+            lattice_id,
+            store,
+            CommandPublisher::new(publisher.clone(), "doesntmatter"),
+            StatusPublisher::new(publisher.clone(), None, "doesntmatter"),
+            lattice_source,
+        )
+        .await;
+        manager.add_raw_scalers("deployed", Vec::new()).await;
+
+        manager.remove_orphaned_links().await.unwrap();
+
+        assert_eq!(
+            *publisher.received.read().await,
+            vec![Command::DeleteLink(DeleteLink {
+                source_id: "deleted-httpserver".to_string(),
+                wit_namespace: "wasi".to_string(),
+                wit_package: "http".to_string(),
+                link_name: "default".to_string(),
+                model_name: "deleted".to_string(),
+            })],
+            "Only the link owned by the missing model should be deleted"
+        );
+    }
+
+    #[tokio::test]
+    async fn adds_instance_annotations_to_commands() {
+        let lattice_id = "instance_annotations";
+        let store = Arc::new(TestStore::default());
+        store
+            .store(
+                lattice_id,
+                "host1".to_string(),
+                Host {
+                    id: "host1".to_string(),
+                    last_seen: chrono::Utc::now(),
+                    ..Default::default()
+                },
+            )
+            .await
+            .unwrap();
+
+        let manifest: Manifest = serde_yaml::from_str(
+            r#"
+apiVersion: core.oam.dev/v1beta1
+kind: Application
+metadata:
+  name: annotated
+  annotations:
+    version: v0.1.0
+    wasmcloud.dev/deployed-at: "2024-01-01T00:00:00+00:00"
+spec:
+  components:
+    - name: http_component
+      type: component
+      properties:
+        image: fakecloud.io/http:0.1.0
+      traits:
+        - type: spreadscaler
+          properties:
+            instances: 2
+"#,
+        )
+        .unwrap();
+
+        // Backoff notifications go out on the same publisher, so record everything as raw JSON
+        let publisher = RecorderPublisher::<serde_json::Value> {
+            received: Arc::new(RwLock::new(Vec::new())),
+        };
+        let manager = ScalerManager::test_new(
+            publisher.clone(),
+            lattice_id,
+            store,
+            CommandPublisher::new(publisher.clone(), "doesntmatter"),
+            StatusPublisher::new(publisher.clone(), None, "doesntmatter"),
+            TestLatticeSource::default(),
+        )
+        .await
+        .with_instance_annotations(InstanceAnnotations {
+            static_annotations: BTreeMap::from_iter([("team".to_string(), "platform".to_string())]),
+            deploy_metadata: true,
+        });
+        manager.refresh_data().await.unwrap();
+
+        let mut commands = Vec::new();
+        for scaler in manager.scalers_for_manifest(&manifest) {
+            commands.extend(scaler.reconcile().await.unwrap());
+        }
+        let Some(Command::ScaleComponent(scale)) = commands
+            .iter()
+            .find(|cmd| matches!(cmd, Command::ScaleComponent(_)))
+        else {
+            panic!("Should have scaled the component, got: {commands:?}");
+        };
+
+        assert_eq!(scale.count, 2);
+        let annotation = |key: &str| scale.annotations.get(key).map(String::as_str);
+        assert_eq!(annotation("team"), Some("platform"));
+        assert_eq!(annotation(MANIFEST_VERSION_ANNOTATION), Some("v0.1.0"));
+        assert_eq!(
+            annotation(DEPLOYED_AT_ANNOTATION_KEY),
+            Some("2024-01-01T00:00:00+00:00")
+        );
+        assert!(
+            scale.annotations.contains_key(crate::SCALER_KEY),
+            "Scaler annotations should still be set"
+        );
+    }
+
+    #[tokio::test]
+    async fn paused_scalers_stay_paused_when_rebuilt() {
+        let lattice_id = "paused_scalers";
+        let store = Arc::new(TestStore::default());
+        store
+            .store(
+                lattice_id,
+                "host1".to_string(),
+                Host {
+                    id: "host1".to_string(),
+                    last_seen: chrono::Utc::now(),
+                    ..Default::default()
+                },
+            )
+            .await
+            .unwrap();
+
+        let manifest: Manifest = serde_yaml::from_str(
+            r#"
+apiVersion: core.oam.dev/v1beta1
+kind: Application
+metadata:
+  name: paused
+  annotations:
+    version: v0.1.0
+spec:
+  components:
+    - name: http_component
+      type: component
+      properties:
+        image: fakecloud.io/http:0.1.0
+      traits:
+        - type: spreadscaler
+          properties:
+            instances: 2
+"#,
+        )
+        .unwrap();
+
+        let publisher = RecorderPublisher::<serde_json::Value> {
+            received: Arc::new(RwLock::new(Vec::new())),
+        };
+        let manager = ScalerManager::test_new(
+            publisher.clone(),
+            lattice_id,
+            store,
+            CommandPublisher::new(publisher.clone(), "doesntmatter"),
+            StatusPublisher::new(publisher.clone(), None, "doesntmatter"),
+            TestLatticeSource::default(),
+        )
+        .await;
+        manager.refresh_data().await.unwrap();
+        manager
+            .add_scalers(&manifest, manager.scalers_for_manifest(&manifest))
+            .await
+            .unwrap();
+        let scaler_id = manager
+            .get_scalers("paused")
+            .await
+            .unwrap()
+            .iter()
+            .find(|scaler| scaler.kind() == SPREAD_SCALER_KIND)
+            .expect("Should have a spread scaler")
+            .id()
+            .to_owned();
+
+        assert!(!manager.pause_scaler("paused", "missing").await.unwrap());
+        assert!(manager.pause_scaler("paused", &scaler_id).await.unwrap());
+
+        let assert_paused = || async {
+            let scaler = manager
+                .get_specific_scaler("paused", &scaler_id)
+                .await
+                .unwrap();
+            assert_eq!(scaler.status().await.status_type, StatusType::Paused);
+            assert!(
+                scaler.reconcile().await.unwrap().is_empty(),
+                "Paused scalers should not issue commands"
+            );
+        };
+        assert_paused().await;
+
+        // Deploying a new version rebuilds the scalers, which shouldn't resume them
+        manager
+            .add_raw_scalers("paused", manager.scalers_for_manifest(&manifest))
+            .await;
+        assert_paused().await;
+
+        assert!(manager.resume_scaler("paused", &scaler_id).await.unwrap());
+        let scaler = manager
+            .get_specific_scaler("paused", &scaler_id)
+            .await
+            .unwrap();
+        assert!(
+            scaler
+                .reconcile()
+                .await
+                .unwrap()
+                .iter()
+                .any(|cmd| matches!(cmd, Command::ScaleComponent(_))),
+            "Resumed scalers should issue commands again"
+        );
+    }
+
+    #[tokio::test]
+    async fn shared_providers_are_stopped_with_the_last_model() {
+        let lattice_id = "shared_providers";
+        let store = Arc::new(TestStore::default());
+        let manifest = |name: &str| -> Manifest {
+            serde_yaml::from_str(&format!(
+                r#"
+apiVersion: core.oam.dev/v1beta1
+kind: Application
+metadata:
+  name: {name}
+  annotations:
+    version: v0.1.0
+spec:
+  components:
+    - name: httpserver
+      type: capability
+      properties:
+        image: fakecloud.io/httpserver:0.1.0
+        id: httpserver
+      traits:
+        - type: spreadscaler
+          properties:
+            instances: 1
+            spread:
+              - name: everywhere
+                requirements: {{}}
+"#
+            ))
+            .unwrap()
+        };
+
+        let publisher = RecorderPublisher::<serde_json::Value> {
+            received: Arc::new(RwLock::new(Vec::new())),
+        };
+        let commands = RecorderPublisher::<serde_json::Value> {
+            received: Arc::new(RwLock::new(Vec::new())),
+        };
+        let stopped_providers = || async {
+            commands
+                .received
+                .read()
+                .await
+                .iter()
+                .filter_map(|cmd| match serde_json::from_value(cmd.clone()) {
+                    Ok(Command::StopProvider(stop)) => Some(stop.provider_id),
+                    _ => None,
+                })
+                .collect::<Vec<_>>()
+        };
+        let manager = ScalerManager::test_new(
+            publisher.clone(),
+            lattice_id,
+            store.clone(),
+            CommandPublisher::new(commands.clone(), "doesntmatter"),
+            StatusPublisher::new(publisher.clone(), None, "doesntmatter"),
+            TestLatticeSource::default(),
+        )
+        .await;
+        let mut scaler_ids = Vec::new();
+        for name in ["first", "second"] {
+            let manifest = manifest(name);
+            manager
+                .add_scalers(&manifest, manager.scalers_for_manifest(&manifest))
+                .await
+                .unwrap();
+            let scalers = manager.get_scalers(name).await.unwrap();
+            let scaler = scalers
+                .iter()
+                .find(|scaler| scaler.shared_resource().is_some())
+                .expect("Provider scaler should share the provider");
+            scaler_ids.push(scaler.id().to_owned());
+        }
+        assert_eq!(
+            scaler_ids[0], scaler_ids[1],
+            "Both models should share the scaler of the first model"
+        );
+
+        // The provider was started by the shared scaler
+        store
+            .store(
+                lattice_id,
+                "host1".to_string(),
+                Host {
+                    id: "host1".to_string(),
+                    last_seen: chrono::Utc::now(),
+                    providers: HashSet::from([crate::events::ProviderInfo {
+                        provider_id: "httpserver".to_string(),
+                        provider_ref: "fakecloud.io/httpserver:0.1.0".to_string(),
+                        annotations: spreadscaler_annotations("everywhere", &scaler_ids[0]),
+                    }]),
+                    ..Default::default()
+                },
+            )
+            .await
+            .unwrap();
+
+        manager.remove_scalers("first").await.unwrap().unwrap();
+        assert!(
+            stopped_providers().await.is_empty(),
+            "The provider should keep running while another model requires it"
+        );
+
+        // The second model manages the provider it adopted, so removing it stops the provider
+        manager.remove_scalers("second").await.unwrap().unwrap();
+        assert_eq!(
+            stopped_providers().await,
+            vec!["httpserver".to_string()],
+            "The provider should be stopped once no model requires it"
+        );
+        assert!(manager.shared_scalers.is_empty().await);
+    }
+
+    #[tokio::test]
+    async fn reconciles_scalers_with_an_interval_periodically() {
+        let lattice_id = "periodic_reconcile";
+        let store = Arc::new(TestStore::default());
+        store
+            .store(
+                lattice_id,
+                "host1".to_string(),
+                Host {
+                    id: "host1".to_string(),
+                    last_seen: chrono::Utc::now(),
+                    ..Default::default()
+                },
+            )
+            .await
+            .unwrap();
+
+        let manifest: Manifest = serde_yaml::from_str(
+            r#"
+apiVersion: core.oam.dev/v1beta1
+kind: Application
+metadata:
+  name: periodic
+  annotations:
+    version: v0.1.0
+spec:
+  components:
+    - name: http_component
+      type: component
+      properties:
+        image: fakecloud.io/http:0.1.0
+      traits:
+        - type: spreadscaler
+          properties:
+            instances: 1
+            reconcile_interval: 1
+"#,
+        )
+        .unwrap();
+
+        let publisher = RecorderPublisher::<serde_json::Value> {
+            received: Arc::new(RwLock::new(Vec::new())),
+        };
+        let commands = RecorderPublisher::<serde_json::Value> {
+            received: Arc::new(RwLock::new(Vec::new())),
+        };
+        let manager = ScalerManager::test_new(
+            publisher.clone(),
+            lattice_id,
+            store,
+            CommandPublisher::new(commands.clone(), "doesntmatter"),
+            StatusPublisher::new(publisher.clone(), None, "doesntmatter"),
+            TestLatticeSource::default(),
+        )
+        .await;
+        // Adding the scalers doesn't reconcile them, so anything published comes from the timer
+        manager
+            .add_scalers(&manifest, manager.scalers_for_manifest(&manifest))
+            .await
+            .unwrap();
+        assert!(commands.received.read().await.is_empty());
+
+        tokio::time::sleep(Duration::from_millis(1300)).await;
+        assert_eq!(
+            commands.received.read().await.len(),
+            1,
+            "The timer should have reconciled the scaler and published its command"
+        );
+
+        // The scaler is now waiting for the event its command causes, so the next tick does nothing
+        tokio::time::sleep(Duration::from_secs(1)).await;
+        assert_eq!(
+            commands.received.read().await.len(),
+            1,
+            "A tick while the scaler is backing off should not publish commands"
+        );
+
+        manager.remove_raw_scalers("periodic").await;
+        assert!(
+            manager.reconcile_timers.read().await.is_empty(),
+            "Removing the scalers should stop their timers"
+        );
+    }
+
+    #[tokio::test]
+    async fn compact_notifications_remove_expected_events_on_other_instances() {
+        let lattice_id = "compact_notifications";
+        let store = Arc::new(TestStore::default());
+        store
+            .store(
+                lattice_id,
+                "host1".to_string(),
+                Host {
+                    id: "host1".to_string(),
+                    last_seen: chrono::Utc::now(),
+                    ..Default::default()
+                },
+            )
+            .await
+            .unwrap();
+
+        let manifest: Manifest = serde_yaml::from_str(
+            r#"
+apiVersion: core.oam.dev/v1beta1
+kind: Application
+metadata:
+  name: compact
+  annotations:
+    version: v0.1.0
+spec:
+  components:
+    - name: http_component
+      type: component
+      properties:
+        image: fakecloud.io/http:0.1.0
+      traits:
+        - type: spreadscaler
+          properties:
+            instances: 2
+"#,
+        )
+        .unwrap();
+
+        let publisher = RecorderPublisher::<serde_json::Value> {
+            received: Arc::new(RwLock::new(Vec::new())),
+        };
+        let mut manager = ScalerManager::test_new(
+            publisher.clone(),
+            lattice_id,
+            store,
+            CommandPublisher::new(publisher.clone(), "doesntmatter"),
+            StatusPublisher::new(publisher.clone(), None, "doesntmatter"),
+            TestLatticeSource::default(),
+        )
+        .await;
+        manager.compact_notifications = true;
+        manager.refresh_data().await.unwrap();
+
+        // Each list of scalers stands in for the same model on a different wadm instance
+        let spread_scaler = |scalers: ScalerList| {
+            scalers
+                .into_iter()
+                .find(|scaler| scaler.kind() == SPREAD_SCALER_KIND)
+                .expect("Manifest should have a spread scaler")
+        };
+        let handling = spread_scaler(manager.scalers_for_manifest(&manifest));
+        let other = spread_scaler(manager.scalers_for_manifest(&manifest));
+
+        let commands = handling.reconcile().await.unwrap();
+        let (expected, _) = commands
+            .iter()
+            .find_map(Command::corresponding_event)
+            .expect("Reconcile should expect an event");
+        // Registering expected events on the other instance reconciles the same way
+        assert_eq!(other.reconcile().await.unwrap(), commands);
+        assert!(
+            other.reconcile().await.unwrap().is_empty(),
+            "Other instance should be waiting on the expected event"
+        );
+
+        handling.handle_event(&expected).await.unwrap();
+        let notification = publisher
+            .received
+            .read()
+            .await
+            .iter()
+            .filter_map(|data| serde_json::from_value::<Notifications>(data.clone()).ok())
+            .find_map(|notification| match notification {
+                Notifications::RemoveExpectedFingerprint { fingerprint, .. } => Some(fingerprint),
+                _ => None,
+            })
+            .expect("Handling the expected event should send a fingerprint");
+
+        other
+            .handle_event(&Event::from(notification))
+            .await
+            .unwrap();
+        assert_eq!(
+            other.reconcile().await.unwrap(),
+            commands,
+            "Other instance should no longer be waiting on the expected event"
+        );
+    }
+
+    #[tokio::test]
+    async fn validates_manifest_against_lattice() {
+        let lattice_id = "validate_manifest";
+        let store = Arc::new(TestStore::default());
+        store
+            .store(
+                lattice_id,
+                "host1".to_string(),
+                Host {
+                    id: "host1".to_string(),
+                    labels: HashMap::from([("zone".to_string(), "east".to_string())]),
+                    last_seen: chrono::Utc::now(),
+                    ..Default::default()
+                },
+            )
+            .await
+            .unwrap();
+
+        let manifest = |zone: &str, config: &str| -> Manifest {
+            serde_yaml::from_str(&format!(
+                r#"
+apiVersion: core.oam.dev/v1beta1
+kind: Application
+metadata:
+  name: validated
+  annotations:
+    version: v0.1.0
+spec:
+  components:
+    - name: http_component
+      type: component
+      properties:
+        image: fakecloud.io/http:0.1.0
+        config:
+          - name: {config}
+      traits:
+        - type: spreadscaler
+          properties:
+            instances: 2
+            spread:
+              - name: {zone}
+                requirements:
+                  zone: {zone}
+"#
+            ))
+            .unwrap()
+        };
+
+        let publisher = RecorderPublisher::<serde_json::Value> {
+            received: Arc::new(RwLock::new(Vec::new())),
+        };
+        let manager = ScalerManager::test_new(
+            publisher.clone(),
+            lattice_id,
+            store,
+            CommandPublisher::new(publisher.clone(), "doesntmatter"),
+            StatusPublisher::new(publisher.clone(), None, "doesntmatter"),
+            TestLatticeSource {
+                config: HashMap::from([("shared".to_string(), HashMap::new())]),
+                ..Default::default()
+            },
+        )
+        .await;
+
+        let report = manager
+            .validate_manifest(&manifest("east", "shared"))
+            .await
+            .unwrap();
+        assert!(report.valid(), "Feasible manifest had errors: {report:?}");
+
+        let report = manager
+            .validate_manifest(&manifest("west", "missing"))
+            .await
+            .unwrap();
+        assert!(!report.valid());
+        assert_eq!(report.errors.len(), 2, "Unexpected errors: {report:?}");
+        assert!(report.errors.iter().any(|failure| failure
+            .msg
+            .starts_with("SpreadScaler for validated-http_component")));
+        assert!(report
+            .errors
+            .iter()
+            .any(|failure| failure.msg.contains("[missing] does not exist")));
+
+        assert!(
+            manager.get_scalers("validated").await.is_none(),
+            "Validation should not add scalers"
+        );
+        assert!(
+            publisher.received.read().await.is_empty(),
+            "Validation should not publish anything"
+        );
+    }
+
+    #[tokio::test]
+    async fn applies_runtime_config_changes() {
+        let lattice_id = "runtime_config";
+        let publisher = RecorderPublisher::<Command> {
+            received: Arc::new(RwLock::new(Vec::new())),
+        };
+        let (runtime_config, runtime_config_rx) = watch::channel(RuntimeConfig::default());
+        let manager = ScalerManager::test_new(
+            publisher.clone(),
+            lattice_id,
+            Arc::new(TestStore::default()),
+            CommandPublisher::new(publisher.clone(), "doesntmatter"),
+            StatusPublisher::new(publisher.clone(), None, "doesntmatter"),
+            TestLatticeSource::default(),
+        )
+        .await
+        .with_runtime_config(runtime_config_rx);
+
+        manager.mark_tearing_down("before").await;
+        assert!(
+            !manager.is_tearing_down("before").await,
+            "Models shouldn't tear down without a teardown period"
+        );
+
+        runtime_config.send_modify(|config| config.teardown_period = Duration::from_secs(60));
+        manager.mark_tearing_down("after").await;
+        assert!(
+            manager.is_tearing_down("after").await,
+            "The new teardown period should apply without recreating the manager"
+        );
+    }
+}
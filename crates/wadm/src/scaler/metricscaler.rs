@@ -0,0 +1,476 @@
+//! Contains a scaler that sizes a component to the resource utilization reported by the hosts it
+//! runs on
+
+use std::collections::{BTreeMap, HashMap};
+
+use anyhow::Result;
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use tokio::sync::RwLock;
+use tracing::{instrument, trace};
+use wadm_types::{api::StatusInfo, TraitProperty};
+
+use crate::{
+    commands::{Command, ScaleComponent},
+    events::{Event, HostUtilization},
+    scaler::{ComponentScalerConfig, Scaler},
+    storage::{Component, ConsistentRead, Host, ReadStore},
+    SCALER_KEY,
+};
+
+use super::compute_id_sha256;
+
+pub const METRIC_SCALER_KIND: &str = "MetricScaler";
+
+/// The host resource a [`MetricScaler`] scales on
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LoadMetric {
+    #[default]
+    Cpu,
+    Memory,
+}
+
+impl LoadMetric {
+    fn load(&self, utilization: &HostUtilization) -> u64 {
+        match self {
+            LoadMetric::Cpu => utilization.cpu_percent as u64,
+            LoadMetric::Memory => utilization.memory_percent as u64,
+        }
+    }
+}
+
+/// Properties for the metricscaler trait. Loads are percentages of a single host's capacity
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct MetricScalerProperty {
+    /// The resource to scale on
+    #[serde(default)]
+    pub metric: LoadMetric,
+    /// The average load each instance should carry
+    pub target_load: u32,
+    /// The fewest instances to run, no matter how little load there is
+    #[serde(default = "default_min_instances")]
+    pub min_instances: usize,
+    /// The most instances to run, no matter how much load there is
+    pub max_instances: usize,
+    /// How far below the target (as a percentage of it) the load per instance has to be before
+    /// scaling down, so small changes in load don't scale the component up and down repeatedly
+    #[serde(default = "default_hysteresis_percent")]
+    pub hysteresis_percent: u32,
+}
+
+fn default_min_instances() -> usize {
+    1
+}
+
+fn default_hysteresis_percent() -> u32 {
+    10
+}
+
+impl TryFrom<TraitProperty> for MetricScalerProperty {
+    type Error = anyhow::Error;
+
+    fn try_from(value: TraitProperty) -> Result<Self> {
+        match value {
+            TraitProperty::Custom(value) => Ok(serde_json::from_value(value)?),
+            _ => anyhow::bail!("Given config was not a metric scaler config object"),
+        }
+    }
+}
+
+/// The MetricScaler runs as many instances of a component as it takes to keep the average load
+/// per instance at a target, using the utilization that hosts report in their heartbeats. The
+/// load is the total utilization of the hosts running the component, or of every host before the
+/// component is running anywhere, and instances are spread evenly across all hosts.
+///
+/// Hosts don't have to report utilization. If none of the hosts it looks at do, this scaler leaves
+/// the component as it is and reports that it has no data
+pub struct MetricScaler<S> {
+    store: S,
+    id: String,
+    lattice_id: String,
+    model_name: String,
+    component_id: String,
+    component_reference: String,
+    property: MetricScalerProperty,
+    config: Vec<String>,
+    status: RwLock<StatusInfo>,
+}
+
+#[async_trait]
+impl<S: ReadStore + ConsistentRead + Send + Sync + Clone> Scaler for MetricScaler<S> {
+    fn id(&self) -> &str {
+        &self.id
+    }
+
+    fn kind(&self) -> &str {
+        METRIC_SCALER_KIND
+    }
+
+    fn name(&self) -> String {
+        self.component_id.to_string()
+    }
+
+    async fn status(&self) -> StatusInfo {
+        let _ = self.reconcile().await;
+        self.status.read().await.to_owned()
+    }
+
+    async fn update_config(&mut self, config: TraitProperty) -> Result<Vec<Command>> {
+        self.property = config.try_into()?;
+        self.reconcile().await
+    }
+
+    #[instrument(level = "trace", skip_all, fields(scaler_id = %self.id))]
+    async fn handle_event(&self, event: &Event) -> Result<Vec<Command>> {
+        match event {
+            Event::ComponentScaled(evt) if evt.component_id == self.component_id => {
+                self.reconcile().await
+            }
+            Event::HostHeartbeat(_) | Event::HostStarted(_) | Event::HostStopped(_) => {
+                self.reconcile().await
+            }
+            _ => Ok(Vec::new()),
+        }
+    }
+
+    #[instrument(level = "trace", skip_all, fields(name = %self.model_name, scaler_id = %self.id))]
+    async fn reconcile(&self) -> Result<Vec<Command>> {
+        let store = self.store.consistent_view().await;
+        let hosts = store.list::<Host>(&self.lattice_id).await?;
+        let component = store
+            .get::<Component>(&self.lattice_id, &self.component_id)
+            .await?;
+        let running = self.running_instances(component.as_ref());
+        let current: usize = running.values().sum();
+
+        // Before the component is running anywhere, every host's load is what it would share
+        let loaded_hosts = hosts
+            .values()
+            .filter(|host| current == 0 || running.contains_key(&host.id))
+            .filter_map(|host| host.utilization.as_ref())
+            .map(|utilization| self.property.metric.load(utilization))
+            .collect::<Vec<_>>();
+        if loaded_hosts.is_empty() {
+            let status = StatusInfo::waiting(&format!(
+                "No utilization data reported by hosts, keeping {current} instance(s) of {}",
+                self.component_reference
+            ));
+            trace!(?status, "Updating scaler status");
+            *self.status.write().await = status;
+            return Ok(Vec::new());
+        }
+
+        let load: u64 = loaded_hosts.iter().sum();
+        let desired = self.desired_instances(load, current);
+        let commands = self.placement_commands(&hosts, &running, desired);
+        trace!(
+            load,
+            current,
+            desired,
+            ?commands,
+            "Calculated commands for metric scaler"
+        );
+
+        let load_message = if current == 0 {
+            format!("load {load}% with no instances running")
+        } else {
+            format!("load {}% per instance", load / current as u64)
+        };
+        let load_message = format!("{load_message}, target {}%", self.property.target_load);
+        let status = if commands.is_empty() {
+            StatusInfo::deployed(&format!("Running {current} instance(s), {load_message}"))
+        } else {
+            StatusInfo::reconciling(&format!(
+                "Scaling from {current} to {desired} instance(s), {load_message}"
+            ))
+        };
+        trace!(?status, "Updating scaler status");
+        *self.status.write().await = status;
+
+        Ok(commands)
+    }
+
+    #[instrument(level = "trace", skip_all, fields(name = %self.model_name))]
+    async fn cleanup(&self) -> Result<Vec<Command>> {
+        let component = self
+            .store
+            .get::<Component>(&self.lattice_id, &self.component_id)
+            .await?;
+        Ok(self
+            .running_instances(component.as_ref())
+            .into_keys()
+            .map(|host_id| self.scale_command(host_id, 0))
+            .collect())
+    }
+}
+
+impl<S: ReadStore + Send + Sync> MetricScaler<S> {
+    /// Construct a new MetricScaler with specified configuration values
+    pub fn new(
+        store: S,
+        config: ComponentScalerConfig,
+        property: MetricScalerProperty,
+        component_name: &str,
+    ) -> Self {
+        let ComponentScalerConfig {
+            lattice_id,
+            component_id,
+            component_reference,
+            model_name,
+            component_config: config,
+        } = config;
+        let mut id_parts = vec![
+            METRIC_SCALER_KIND,
+            &model_name,
+            component_name,
+            &component_id,
+            &component_reference,
+        ];
+        id_parts.extend(config.iter().map(String::as_str));
+        let id = compute_id_sha256(&id_parts);
+        Self {
+            store,
+            id,
+            lattice_id,
+            model_name,
+            component_id,
+            component_reference,
+            property,
+            config,
+            status: RwLock::new(StatusInfo::reconciling("")),
+        }
+    }
+
+    /// Returns the number of instances this scaler is running on each host
+    fn running_instances(&self, component: Option<&Component>) -> HashMap<String, usize> {
+        component
+            .into_iter()
+            .flat_map(|component| component.instances.iter())
+            .filter_map(|(host_id, instances)| {
+                let count: usize = instances
+                    .iter()
+                    .filter(|info| info.annotations.get(SCALER_KEY) == Some(&self.id))
+                    .map(|info| info.count)
+                    .sum();
+                (count > 0).then(|| (host_id.to_owned(), count))
+            })
+            .collect()
+    }
+
+    /// Returns how many instances should be running to carry the given load. This scales up as
+    /// soon as the load per instance is above the target, but only scales down once the load per
+    /// instance would stay below the target by the hysteresis margin afterwards
+    fn desired_instances(&self, load: u64, current: usize) -> usize {
+        let target = self.property.target_load.max(1) as u64;
+        let scale_up = load.div_ceil(target) as usize;
+        let margin = 100u64
+            .saturating_sub(self.property.hysteresis_percent as u64)
+            .max(1);
+        let scale_down = (load * 100).div_ceil(target * margin) as usize;
+        let desired = if scale_up > current {
+            scale_up
+        } else if scale_down < current {
+            scale_down
+        } else {
+            current
+        };
+        desired.clamp(
+            self.property.min_instances,
+            self.property.max_instances.max(self.property.min_instances),
+        )
+    }
+
+    /// Spreads the desired number of instances evenly across all hosts, returning the commands to
+    /// get there. Hosts already running the most instances keep any that don't divide evenly
+    fn placement_commands(
+        &self,
+        hosts: &HashMap<String, Host>,
+        running: &HashMap<String, usize>,
+        desired: usize,
+    ) -> Vec<Command> {
+        let mut host_ids = hosts.keys().collect::<Vec<_>>();
+        host_ids.sort_by(|a, b| {
+            let count = |id: &String| running.get(id).copied().unwrap_or_default();
+            count(b).cmp(&count(a)).then_with(|| a.cmp(b))
+        });
+        let per_host = desired / host_ids.len().max(1);
+        let remainder = desired % host_ids.len().max(1);
+        let mut commands = host_ids
+            .iter()
+            .enumerate()
+            .filter_map(|(index, host_id)| {
+                let count = per_host + usize::from(index < remainder);
+                (running.get(*host_id).copied().unwrap_or_default() != count)
+                    .then(|| self.scale_command(host_id.to_string(), count))
+            })
+            .collect::<Vec<_>>();
+        // Instances on hosts that have since gone away are removed as well
+        commands.extend(
+            running
+                .keys()
+                .filter(|host_id| !hosts.contains_key(*host_id))
+                .map(|host_id| self.scale_command(host_id.to_owned(), 0)),
+        );
+        commands
+    }
+
+    fn scale_command(&self, host_id: String, count: usize) -> Command {
+        Command::ScaleComponent(ScaleComponent {
+            component_id: self.component_id.to_owned(),
+            reference: self.component_reference.to_owned(),
+            host_id,
+            count: count as u32,
+            model_name: self.model_name.to_owned(),
+            annotations: BTreeMap::from_iter([(SCALER_KEY.to_string(), self.id.to_owned())]),
+            config: self.config.clone(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{collections::HashSet, sync::Arc};
+
+    use wadm_types::api::StatusType;
+
+    use super::*;
+    use crate::{
+        storage::{Store, WadmComponentInfo},
+        test_util::TestStore,
+    };
+
+    const LATTICE_ID: &str = "metricscaler";
+
+    async fn store_host(store: &TestStore, id: &str, utilization: Option<HostUtilization>) {
+        store
+            .store(
+                LATTICE_ID,
+                id.to_string(),
+                Host {
+                    id: id.to_string(),
+                    last_seen: chrono::Utc::now(),
+                    utilization,
+                    ..Default::default()
+                },
+            )
+            .await
+            .unwrap();
+    }
+
+    fn cpu(cpu_percent: u32) -> Option<HostUtilization> {
+        Some(HostUtilization {
+            cpu_percent,
+            memory_percent: 0,
+        })
+    }
+
+    fn scaler(store: Arc<TestStore>) -> MetricScaler<Arc<TestStore>> {
+        MetricScaler::new(
+            store,
+            ComponentScalerConfig {
+                lattice_id: LATTICE_ID.to_string(),
+                component_id: "metric-http".to_string(),
+                component_reference: "fakecloud.io/http:0.1.0".to_string(),
+                model_name: "metric".to_string(),
+                component_config: Vec::new(),
+            },
+            MetricScalerProperty {
+                metric: LoadMetric::Cpu,
+                target_load: 40,
+                min_instances: 1,
+                max_instances: 10,
+                hysteresis_percent: 10,
+            },
+            "http",
+        )
+    }
+
+    fn scaled_counts(commands: &[Command]) -> BTreeMap<String, u32> {
+        commands
+            .iter()
+            .map(|cmd| match cmd {
+                Command::ScaleComponent(scale) => (scale.host_id.clone(), scale.count),
+                cmd => panic!("Unexpected command {cmd:?}"),
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn scales_to_target_load_with_hysteresis() {
+        let store = Arc::new(TestStore::default());
+        store_host(&store, "host1", cpu(60)).await;
+        store_host(&store, "host2", cpu(60)).await;
+        let scaler = scaler(store.clone());
+
+        // 120% of load at 40% per instance needs 3 instances
+        let commands = scaler.reconcile().await.unwrap();
+        assert_eq!(
+            scaled_counts(&commands),
+            BTreeMap::from_iter([("host1".to_string(), 2), ("host2".to_string(), 1)])
+        );
+        assert_eq!(
+            scaler.status.read().await.message,
+            "Scaling from 0 to 3 instance(s), load 120% with no instances running, target 40%"
+        );
+
+        let running = |count: usize| {
+            HashSet::from_iter([WadmComponentInfo {
+                annotations: BTreeMap::from_iter([(SCALER_KEY.to_string(), scaler.id.clone())]),
+                count,
+            }])
+        };
+        store
+            .store(
+                LATTICE_ID,
+                "metric-http".to_string(),
+                Component {
+                    id: "metric-http".to_string(),
+                    reference: "fakecloud.io/http:0.1.0".to_string(),
+                    instances: HashMap::from_iter([
+                        ("host1".to_string(), running(2)),
+                        ("host2".to_string(), running(1)),
+                    ]),
+                    ..Default::default()
+                },
+            )
+            .await
+            .unwrap();
+
+        // Load just under what 3 instances can carry isn't far enough below the target to scale
+        // down, even though 110% would fit on 3 instances with room to spare
+        store_host(&store, "host1", cpu(55)).await;
+        store_host(&store, "host2", cpu(55)).await;
+        assert!(scaler.reconcile().await.unwrap().is_empty());
+        let status = scaler.status().await;
+        assert_eq!(status.status_type, StatusType::Deployed);
+        assert_eq!(
+            status.message,
+            "Running 3 instance(s), load 36% per instance, target 40%"
+        );
+
+        // Once the load drops enough, the extra instance is removed
+        store_host(&store, "host1", cpu(35)).await;
+        store_host(&store, "host2", cpu(35)).await;
+        assert_eq!(
+            scaled_counts(&scaler.reconcile().await.unwrap()),
+            BTreeMap::from_iter([("host1".to_string(), 1)])
+        );
+    }
+
+    #[tokio::test]
+    async fn reports_no_data_without_utilization() {
+        let store = Arc::new(TestStore::default());
+        store_host(&store, "host1", None).await;
+        let scaler = scaler(store);
+
+        assert!(scaler.reconcile().await.unwrap().is_empty());
+        let status = scaler.status().await;
+        assert_eq!(status.status_type, StatusType::Waiting);
+        assert!(
+            status.message.starts_with("No utilization data"),
+            "Unexpected status: {status:?}"
+        );
+    }
+}
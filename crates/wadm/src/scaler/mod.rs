@@ -1,36 +1,67 @@
-use std::{sync::Arc, time::Duration};
+use std::{
+    borrow::Cow,
+    collections::BTreeMap,
+    sync::{
+        atomic::{AtomicBool, AtomicU32, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
 
 use anyhow::Result;
 use async_trait::async_trait;
+use metrics::{counter, histogram};
+use serde::{Deserialize, Serialize};
 use sha2::{Digest, Sha256};
 use tokio::{
     sync::{Mutex, RwLock},
     task::JoinHandle,
 };
-use tracing::{error, instrument, trace, Instrument};
+use tracing::{error, instrument, trace, warn, Instrument};
 use wadm_types::{api::StatusInfo, TraitProperty};
 
 use crate::{
     commands::Command,
-    events::{ComponentScaleFailed, ComponentScaled, Event, ProviderStartFailed, ProviderStarted},
+    events::{
+        CommandExecuted, ComponentScaleFailed, ComponentScaled, ConfigDeleted, ConfigSet, Event,
+        LinkdefDeleted, ProviderStartFailed, ProviderStarted,
+    },
     publisher::Publisher,
     workers::{get_commands_and_result, ConfigSource, SecretSource},
 };
 
+mod capacity;
+mod compute;
 pub mod configscaler;
 mod convert;
 pub mod daemonscaler;
+mod jitter;
+pub mod maintenance;
 pub mod manager;
+pub mod metricscaler;
+mod plan;
+pub mod registry;
 pub mod secretscaler;
+pub mod shadowscaler;
+mod shared;
 pub mod spreadscaler;
 pub mod statusscaler;
 
 use manager::Notifications;
 
+pub use capacity::{CapacityClaim, CapacityLimit, HostCapacity};
+pub use compute::ComputePool;
+pub(crate) use convert::compute_component_id;
+pub use jitter::{jittered_interval, reconcile_offset};
+pub(crate) use plan::ManagedInstances;
+pub use plan::{HostPlan, ReconcilePlan};
+
 use self::configscaler::ConfigScaler;
 use self::secretscaler::SecretScaler;
 
 const DEFAULT_WAIT_TIMEOUT: Duration = Duration::from_secs(30);
+const DEFAULT_BACKOFF_TIMEOUT: Duration = Duration::from_secs(5);
+const DEFAULT_MAX_BACKOFF_TIMEOUT: Duration = Duration::from_secs(300);
 const DEFAULT_SCALER_KIND: &str = "Scaler";
 
 /// A trait describing a struct that can be configured to compute the difference between
@@ -81,11 +112,56 @@ pub trait Scaler {
     /// Compute commands that must be taken to achieve desired state as specified in config
     async fn reconcile(&self) -> Result<Vec<Command>>;
 
+    /// Compute a plan describing how the actual state differs from the desired state, along with
+    /// the commands that [`Scaler::reconcile`] would return to fix it. Scalers that place
+    /// instances on hosts should implement this and build `reconcile` on top of it, so tooling
+    /// can explain what a scaler wants to do. By default the plan only contains the commands from
+    /// `reconcile`
+    async fn diff(&self) -> Result<ReconcilePlan> {
+        self.reconcile().await.map(ReconcilePlan::new)
+    }
+
     /// Returns the list of commands needed to cleanup for a scaler
     ///
     /// This purposefully does not consume the scaler so that if there is a failure it can be kept
     /// around
     async fn cleanup(&self) -> Result<Vec<Command>>;
+
+    /// Stops the scaler from issuing any commands until [`Scaler::resume`] is called. By default
+    /// this does nothing, so scalers that can't be paused keep running as normal
+    fn pause(&self) {}
+
+    /// Lets a paused scaler issue commands again
+    fn resume(&self) {}
+
+    /// How often the scaler should be reconciled on a timer, on top of the reconciles triggered
+    /// by events. By default scalers are only reconciled when something happens
+    fn reconcile_interval(&self) -> Option<Duration> {
+        None
+    }
+
+    /// An identifier for a lattice resource managed by this scaler that scalers of other models
+    /// can require as well, such as a provider. Every model requiring the same resource shares
+    /// a single scaler for it, which only cleans up once no other model requires it. By default
+    /// scalers don't manage any shared resources
+    fn shared_resource(&self) -> Option<String> {
+        None
+    }
+}
+
+/// Config for the component a component scaler manages
+#[derive(Clone, Debug)]
+pub struct ComponentScalerConfig {
+    /// Lattice ID that the scaler monitors
+    pub lattice_id: String,
+    /// Unique component identifier for the component
+    pub component_id: String,
+    /// OCI, Bindle, or File reference for the component
+    pub component_reference: String,
+    /// The name of the wadm model the scaler is under
+    pub model_name: String,
+    /// Named configuration passed to the component when it starts
+    pub component_config: Vec<String>,
 }
 
 /// The BackoffWrapper is a wrapper around a scaler that is responsible for
@@ -102,7 +178,9 @@ pub trait Scaler {
 ///    to download larger images from an OCI repository without being bombarded with repeat requests.
 /// 3. `backoff_status`: If a scaler receives an event that it was expecting, but it was a failure
 ///    event, the scaler should back off exponentially while reporting that failure status. This both
-///    allows for diagnosing issues with reconciliation and prevents thrashing.
+///    allows for diagnosing issues with reconciliation and prevents thrashing. The backoff starts
+///    at `base_timeout` and doubles with every consecutive cycle that ends in failure, up to
+///    `max_timeout`, and is reset once an expected success event clears the list.
 ///
 /// All of the above effectively allows the inner Scaler to only worry about the logic around
 /// reconciling and handling events, rather than be concerned about whether or not
@@ -128,9 +206,26 @@ pub(crate) struct BackoffWrapper<T, P, C> {
     /// The status of the scaler, set when the scaler is backing off due to a
     /// failure event.
     backoff_status: Arc<RwLock<Option<StatusInfo>>>,
-    // TODO(#253): Figure out where/when/how to store the backoff and exponentially repeat it
+    /// The amount of time to back off for after the first failure event
+    base_timeout: Duration,
+    /// The longest amount of time to back off for, no matter how many failures there have been
+    max_timeout: Duration,
+    /// The number of consecutive reconcile cycles that ended in a failure event
+    failure_streak: AtomicU32,
     /// Responsible for cleaning up the backoff status after a specified duration
     status_cleaner: Mutex<Option<JoinHandle<()>>>,
+    /// Extra annotations added to everything the scaler starts
+    instance_annotations: BTreeMap<String, String>,
+    /// Whether to notify other instances of received events with an [`EventFingerprint`] rather
+    /// than the full event
+    compact_notifications: bool,
+    /// Set when an operator has paused the scaler, which stops it from issuing any commands
+    paused: AtomicBool,
+    /// The most commands the scaler can issue in a single pass, with the rest left for later passes
+    command_budget: Option<usize>,
+    /// The status of the scaler, set while it is converging in batches because a pass wanted to
+    /// issue more commands than its budget allows
+    budget_status: RwLock<Option<StatusInfo>>,
 }
 
 impl<T, P, C> BackoffWrapper<T, P, C>
@@ -140,7 +235,9 @@ where
     C: ConfigSource + SecretSource + Send + Sync + Clone + 'static,
 {
     /// Wraps the given scaler in a new BackoffWrapper. `cleanup_timeout` can be set to a
-    /// desired waiting time, otherwise it will default to 30s
+    /// desired waiting time, otherwise it will default to 30s. `base_timeout` and `max_timeout`
+    /// bound the exponential backoff after failures and default to 5s and 5m
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         scaler: T,
         notifier: P,
@@ -149,7 +246,10 @@ where
         notify_subject: &str,
         model_name: &str,
         cleanup_timeout: Option<Duration>,
+        base_timeout: Option<Duration>,
+        max_timeout: Option<Duration>,
     ) -> Self {
+        let base_timeout = base_timeout.unwrap_or(DEFAULT_BACKOFF_TIMEOUT);
         Self {
             scaler,
             notifier,
@@ -161,14 +261,102 @@ where
             event_cleaner: Mutex::new(None),
             cleanup_timeout: cleanup_timeout.unwrap_or(DEFAULT_WAIT_TIMEOUT),
             backoff_status: Arc::new(RwLock::new(None)),
+            base_timeout,
+            max_timeout: max_timeout
+                .unwrap_or(DEFAULT_MAX_BACKOFF_TIMEOUT)
+                .max(base_timeout),
+            failure_streak: AtomicU32::new(0),
             status_cleaner: Mutex::new(None),
+            instance_annotations: BTreeMap::new(),
+            compact_notifications: false,
+            paused: AtomicBool::new(false),
+            command_budget: None,
+            budget_status: RwLock::new(None),
+        }
+    }
+
+    /// Adds the given annotations to every component and provider the wrapped scaler starts. This
+    /// is done before expected events are computed so they match the events for the started
+    /// instances
+    pub fn with_instance_annotations(mut self, annotations: BTreeMap<String, String>) -> Self {
+        self.instance_annotations = annotations;
+        self
+    }
+
+    /// Sends only an [`EventFingerprint`] of received events to other wadm instances, rather than
+    /// the full event. All wadm instances in the lattice must be able to read fingerprints
+    pub fn with_compact_notifications(mut self, compact: bool) -> Self {
+        self.compact_notifications = compact;
+        self
+    }
+
+    /// Caps the number of commands the wrapped scaler issues in a single reconcile or event. Any
+    /// commands over the budget are dropped, which is safe because the scaler computes them again
+    /// the next time it reconciles. This paces large scale changes rather than sending thousands
+    /// of commands at once
+    pub fn with_command_budget(mut self, budget: Option<usize>) -> Self {
+        self.command_budget = budget;
+        self
+    }
+
+    /// Returns the labels every metric recorded for this scaler is tagged with
+    fn metric_labels(&self) -> [(&'static str, String); 2] {
+        [
+            ("scaler_id", self.scaler.id().to_owned()),
+            ("model_name", self.model_name.clone()),
+        ]
+    }
+
+    /// Records the commands generated by a single event or reconcile
+    fn record_commands(&self, commands: &[Command]) {
+        counter!("wadm_scaler_commands_total", &self.metric_labels())
+            .increment(commands.len() as u64);
+    }
+
+    /// Drops any commands over the command budget, reporting that the scaler is converging in
+    /// batches for as long as it has more commands than fit in a single pass
+    async fn limit_commands(&self, mut commands: Vec<Command>) -> Vec<Command> {
+        let Some(budget) = self.command_budget else {
+            return commands;
+        };
+        let deferred = commands.len().saturating_sub(budget);
+        let status = (deferred > 0).then(|| {
+            trace!(
+                budget,
+                deferred,
+                "Scaler is over its command budget, deferring commands"
+            );
+            commands.truncate(budget);
+            StatusInfo::reconciling(&format!(
+                "Converging in batches of {budget} commands, {deferred} deferred to later passes"
+            ))
+        });
+        *self.budget_status.write().await = status;
+        commands
+    }
+
+    fn annotate(&self, mut commands: Vec<Command>) -> Vec<Command> {
+        if !self.instance_annotations.is_empty() {
+            commands
+                .iter_mut()
+                .for_each(|cmd| cmd.add_annotations(&self.instance_annotations));
         }
+        commands
     }
 
     pub async fn event_count(&self) -> usize {
         self.expected_events.read().await.len()
     }
 
+    /// Returns how long to back off for given the current failure streak
+    fn backoff_timeout(&self) -> Duration {
+        backoff_timeout(
+            self.base_timeout,
+            self.max_timeout,
+            self.failure_streak.load(Ordering::Relaxed),
+        )
+    }
+
     /// Adds events to the expected events list
     ///
     /// # Arguments
@@ -199,10 +387,12 @@ where
             let matches_success = evt_matches_expected(success, event);
             let matches_failure = fail
                 .as_ref()
-                .map_or(false, |f| evt_matches_expected(f, event));
+                .is_some_and(|f| evt_matches_expected(f, event));
 
-            // Update failed_event if the event matches the failure event
-            failed_event |= matches_failure;
+            // Update failed_event if the event matches the failure event. A command execution
+            // result only ever matches when the command failed
+            failed_event |=
+                matches_failure || (matches_success && matches!(event, Event::CommandExecuted(_)));
 
             // Retain the event if it doesn't match either the success or failure event
             !(matches_success || matches_failure)
@@ -229,6 +419,11 @@ where
     ///   or an error of type `anyhow::Error` if any error occurs while processing the event.
     #[instrument(level = "trace", skip_all, fields(scaler_id = %self.id()))]
     async fn handle_event_internal(&self, event: &Event) -> anyhow::Result<Vec<Command>> {
+        if self.paused.load(Ordering::Relaxed) {
+            trace!("Scaler is paused, ignoring event");
+            return Ok(Vec::with_capacity(0));
+        }
+        counter!("wadm_scaler_events_handled_total", &self.metric_labels()).increment(1);
         let model_name = &self.model_name;
         let (expected_event, failed_event) = self.remove_event(event).await?;
         let commands: Vec<Command> = if expected_event {
@@ -241,18 +436,44 @@ where
                 let failed_message = match event {
                     Event::ProviderStartFailed(evt) => evt.error.clone(),
                     Event::ComponentScaleFailed(evt) => evt.error.clone(),
+                    Event::CommandExecuted(evt) => evt.message.clone(),
                     _ => format!("Received a failed event of type '{}'", event.raw_type()),
                 };
-                *self.backoff_status.write().await = Some(StatusInfo::failed(&failed_message));
-                // TODO(#253): Here we could refer to a stored previous duration and increase it
-                self.set_timed_status_cleanup(std::time::Duration::from_secs(5))
-                    .await;
+                let mut backoff_status = self.backoff_status.write().await;
+                // Failures that arrive while already backing off are part of the same cycle
+                if backoff_status.is_none() {
+                    self.failure_streak.fetch_add(1, Ordering::Relaxed);
+                }
+                *backoff_status = Some(StatusInfo::failed(&failed_message));
+                drop(backoff_status);
+                self.set_timed_status_cleanup(self.backoff_timeout()).await;
+                // Let the scaler know about the failure so it can take it into account once the
+                // backoff ends. Nothing it computes now is sent since the scaler is backing off
+                if let Err(e) = self.scaler.handle_event(event).await {
+                    warn!(error = ?e, "Scaler failed to handle failure event");
+                }
+            } else if self.event_count().await == 0 && self.backoff_status.read().await.is_none() {
+                trace!("Scaler received all expected events, resetting backoff");
+                self.failure_streak.store(0, Ordering::Relaxed);
             }
-            let data = serde_json::to_vec(&Notifications::RemoveExpectedEvent {
-                name: model_name.to_owned(),
-                scaler_id: self.scaler.id().to_owned(),
-                event: event.to_owned().try_into()?,
-            })?;
+            let fingerprint = self
+                .compact_notifications
+                .then(|| EventFingerprint::from_event(event))
+                .flatten();
+            let data = match fingerprint {
+                Some(fingerprint) => {
+                    serde_json::to_vec(&Notifications::RemoveExpectedFingerprint {
+                        name: model_name.to_owned(),
+                        scaler_id: self.scaler.id().to_owned(),
+                        fingerprint,
+                    })?
+                }
+                None => serde_json::to_vec(&Notifications::RemoveExpectedEvent {
+                    name: model_name.to_owned(),
+                    scaler_id: self.scaler.id().to_owned(),
+                    event: event.to_owned().try_into()?,
+                })?,
+            };
             self.notifier
                 .publish(data, Some(&self.notify_subject))
                 .await?;
@@ -263,9 +484,11 @@ where
             trace!("Scaler received event but is still expecting events, ignoring");
             // If a scaler is expecting events still, don't have it handle events. This is effectively
             // the backoff mechanism within wadm
+            counter!("wadm_scaler_events_ignored_total", &self.metric_labels()).increment(1);
             Vec::with_capacity(0)
         } else if self.backoff_status.read().await.is_some() {
             trace!("Scaler received event but is in backoff, ignoring");
+            counter!("wadm_scaler_events_ignored_total", &self.metric_labels()).increment(1);
             Vec::with_capacity(0)
         } else {
             trace!("Scaler is not backing off, checking configuration");
@@ -302,11 +525,14 @@ where
             // If the config scalers or secret scalers have commands to send, return them
             if !config_commands.is_empty() || !secret_commands.is_empty() {
                 config_commands.append(&mut secret_commands);
+                self.record_commands(&config_commands);
                 return Ok(config_commands);
             }
 
             trace!("Scaler required configuration is present, handling event");
-            let commands = self.scaler.handle_event(event).await?;
+            let commands = self
+                .limit_commands(self.annotate(self.scaler.handle_event(event).await?))
+                .await;
 
             // Based on the commands, compute the events that we expect to see for this scaler. The scaler
             // will then ignore incoming events until all of the expected events have been received.
@@ -330,11 +556,16 @@ where
             commands
         };
 
+        self.record_commands(&commands);
         Ok(commands)
     }
 
     #[instrument(level = "trace", skip_all, fields(scaler_id = %self.id()))]
     async fn reconcile_internal(&self) -> Result<Vec<Command>> {
+        if self.paused.load(Ordering::Relaxed) {
+            trace!("Scaler is paused, not reconciling");
+            return Ok(Vec::with_capacity(0));
+        }
         // If we're already in backoff, return an empty list
         let current_event_count = self.event_count().await;
         if current_event_count > 0 {
@@ -362,10 +593,24 @@ where
         commands.append(secret_commands.as_mut());
 
         if !commands.is_empty() {
+            self.record_commands(&commands);
             return Ok(commands);
         }
 
-        match self.scaler.reconcile().await {
+        let start = Instant::now();
+        let commands = match self.scaler.reconcile().await {
+            Ok(commands) => Ok(self.limit_commands(self.annotate(commands)).await),
+            Err(e) => Err(e),
+        };
+        histogram!(
+            "wadm_scaler_reconcile_duration_seconds",
+            &self.metric_labels()
+        )
+        .record(start.elapsed());
+        if let Ok(commands) = &commands {
+            self.record_commands(commands);
+        }
+        match commands {
             // "Back off" scaler with expected corresponding events if the scaler generated commands
             Ok(commands) if !commands.is_empty() => {
                 // Generate expected events
@@ -401,7 +646,7 @@ where
     }
 
     async fn cleanup_internal(&self) -> Result<Vec<Command>> {
-        let mut commands = self.scaler.cleanup().await.unwrap_or_default();
+        let mut commands = self.annotate(self.scaler.cleanup().await.unwrap_or_default());
         for config in self.required_config.iter() {
             match config.cleanup().await {
                 Ok(cmds) => commands.extend(cmds),
@@ -435,7 +680,12 @@ where
             handle.abort();
         }
         let expected_events = self.expected_events.clone();
-        let timeout = self.cleanup_timeout;
+        // Expected events are always given at least the cleanup timeout to show up, but are
+        // waited on for longer when the scaler keeps failing
+        let timeout = match self.failure_streak.load(Ordering::Relaxed) {
+            0 => self.cleanup_timeout,
+            _ => self.backoff_timeout().max(self.cleanup_timeout),
+        };
 
         *event_cleaner = Some(tokio::spawn(
             async move {
@@ -467,6 +717,26 @@ where
     }
 }
 
+/// Returns `base` doubled for every failure after the first in the streak, capped at `max`
+fn backoff_timeout(base: Duration, max: Duration, failure_streak: u32) -> Duration {
+    let factor = 1u32
+        .checked_shl(failure_streak.saturating_sub(1))
+        .unwrap_or(u32::MAX);
+    base.checked_mul(factor).unwrap_or(max).min(max)
+}
+
+/// Stops any pending cleanup tasks so a replaced scaler doesn't keep backing off in the background
+impl<T, P, C> Drop for BackoffWrapper<T, P, C> {
+    fn drop(&mut self) {
+        if let Some(handle) = self.event_cleaner.get_mut().take() {
+            handle.abort();
+        }
+        if let Some(handle) = self.status_cleaner.get_mut().take() {
+            handle.abort();
+        }
+    }
+}
+
 #[async_trait]
 /// The [`Scaler`] trait implementation for the [`BackoffWrapper`] is mostly a simple wrapper,
 /// with three exceptions, which allow scalers to sync state between different wadm instances.
@@ -500,29 +770,74 @@ where
     }
 
     async fn status(&self) -> StatusInfo {
+        if self.paused.load(Ordering::Relaxed) {
+            return StatusInfo::paused("Reconciliation has been paused by an operator");
+        }
         // If the scaler has a backoff status, return that, otherwise return the status of the scaler
         if let Some(status) = self.backoff_status.read().await.clone() {
             status
+        } else if let Some(status) = self.budget_status.read().await.clone() {
+            status
         } else {
-            self.scaler.status().await
+            #[cfg(feature = "scaler_metrics")]
+            let start = Instant::now();
+            let status = self.scaler.status().await;
+            #[cfg(feature = "scaler_metrics")]
+            histogram!("wadm_scaler_status_duration_seconds", &self.metric_labels())
+                .record(start.elapsed());
+            status
         }
     }
 
     async fn update_config(&mut self, config: TraitProperty) -> Result<Vec<Command>> {
-        self.scaler.update_config(config).await
+        let commands = self.scaler.update_config(config).await?;
+        Ok(self.annotate(commands))
     }
 
     async fn handle_event(&self, event: &Event) -> Result<Vec<Command>> {
-        self.handle_event_internal(event).await
+        #[cfg(feature = "scaler_metrics")]
+        let start = Instant::now();
+        let res = self.handle_event_internal(event).await;
+        #[cfg(feature = "scaler_metrics")]
+        histogram!(
+            "wadm_scaler_handle_event_duration_seconds",
+            &self.metric_labels()
+        )
+        .record(start.elapsed());
+        res
     }
 
     async fn reconcile(&self) -> Result<Vec<Command>> {
         self.reconcile_internal().await
     }
 
+    async fn diff(&self) -> Result<ReconcilePlan> {
+        let plan = self.scaler.diff().await?;
+        Ok(ReconcilePlan {
+            commands: self.annotate(plan.commands),
+            ..plan
+        })
+    }
+
     async fn cleanup(&self) -> Result<Vec<Command>> {
         self.cleanup_internal().await
     }
+
+    fn pause(&self) {
+        self.paused.store(true, Ordering::Relaxed);
+    }
+
+    fn resume(&self) {
+        self.paused.store(false, Ordering::Relaxed);
+    }
+
+    fn reconcile_interval(&self) -> Option<Duration> {
+        self.scaler.reconcile_interval()
+    }
+
+    fn shared_resource(&self) -> Option<String> {
+        self.scaler.shared_resource()
+    }
 }
 
 /// A specialized function that compares an incoming lattice event to an "expected" event
@@ -596,10 +911,212 @@ fn evt_matches_expected(incoming: &Event, expected: &Event) -> bool {
                 ..
             }),
         ) => a1 == a2 && i1 == i2 && c1 == c2 && h1 == h2,
+        (
+            Event::ConfigSet(ConfigSet { config_name: n1 }),
+            Event::ConfigSet(ConfigSet { config_name: n2 }),
+        )
+        | (
+            Event::ConfigDeleted(ConfigDeleted { config_name: n1 }),
+            Event::ConfigDeleted(ConfigDeleted { config_name: n2 }),
+        ) => n1 == n2,
+        (
+            Event::LinkdefDeleted(LinkdefDeleted {
+                source_id: s1,
+                name: n1,
+                wit_namespace: ns1,
+                wit_package: p1,
+            }),
+            Event::LinkdefDeleted(LinkdefDeleted {
+                source_id: s2,
+                name: n2,
+                wit_namespace: ns2,
+                wit_package: p2,
+            }),
+        ) => s1 == s2 && n1 == n2 && ns1 == ns2 && p1 == p2,
+        // A command that failed to execute will never produce its expected event, so it matches
+        // the events that the command would have produced. Expected events are always lattice
+        // events, so only the received event can be a command result
+        (
+            other,
+            Event::CommandExecuted(CommandExecuted {
+                success: false,
+                command,
+                ..
+            }),
+        ) => command
+            .corresponding_event()
+            .is_some_and(|(success, _)| evt_matches_expected(&success, other)),
         _ => false,
     }
 }
 
+/// The parts of an event that [`evt_matches_expected`] compares, which is all another wadm
+/// instance needs to remove the event from its expected events. Failure messages are kept so the
+/// backoff status is the same everywhere
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum EventFingerprint {
+    ProviderStarted {
+        annotations: BTreeMap<String, String>,
+        image_ref: String,
+        provider_id: String,
+        host_id: String,
+    },
+    ProviderStartFailed {
+        provider_id: String,
+        provider_ref: String,
+        host_id: String,
+        error: String,
+    },
+    ComponentScaled {
+        annotations: BTreeMap<String, String>,
+        image_ref: String,
+        component_id: String,
+        host_id: String,
+    },
+    ComponentScaleFailed {
+        annotations: BTreeMap<String, String>,
+        image_ref: String,
+        component_id: String,
+        host_id: String,
+        error: String,
+    },
+    ConfigSet {
+        config_name: String,
+    },
+    ConfigDeleted {
+        config_name: String,
+    },
+    LinkdefDeleted(LinkdefDeleted),
+    CommandExecuted(CommandExecuted),
+}
+
+impl EventFingerprint {
+    /// Returns the fingerprint of the given event, or `None` if it is not an event that can be
+    /// expected
+    pub fn from_event(event: &Event) -> Option<EventFingerprint> {
+        let fingerprint = match event {
+            Event::ProviderStarted(evt) => EventFingerprint::ProviderStarted {
+                annotations: evt.annotations.clone(),
+                image_ref: evt.image_ref.clone(),
+                provider_id: evt.provider_id.clone(),
+                host_id: evt.host_id.clone(),
+            },
+            Event::ProviderStartFailed(evt) => EventFingerprint::ProviderStartFailed {
+                provider_id: evt.provider_id.clone(),
+                provider_ref: evt.provider_ref.clone(),
+                host_id: evt.host_id.clone(),
+                error: evt.error.clone(),
+            },
+            Event::ComponentScaled(evt) => EventFingerprint::ComponentScaled {
+                annotations: evt.annotations.clone(),
+                image_ref: evt.image_ref.clone(),
+                component_id: evt.component_id.clone(),
+                host_id: evt.host_id.clone(),
+            },
+            Event::ComponentScaleFailed(evt) => EventFingerprint::ComponentScaleFailed {
+                annotations: evt.annotations.clone(),
+                image_ref: evt.image_ref.clone(),
+                component_id: evt.component_id.clone(),
+                host_id: evt.host_id.clone(),
+                error: evt.error.clone(),
+            },
+            Event::ConfigSet(evt) => EventFingerprint::ConfigSet {
+                config_name: evt.config_name.clone(),
+            },
+            Event::ConfigDeleted(evt) => EventFingerprint::ConfigDeleted {
+                config_name: evt.config_name.clone(),
+            },
+            Event::LinkdefDeleted(evt) => EventFingerprint::LinkdefDeleted(evt.clone()),
+            Event::CommandExecuted(evt) => EventFingerprint::CommandExecuted(evt.clone()),
+            _ => return None,
+        };
+        Some(fingerprint)
+    }
+}
+
+/// Rebuilds an event that matches the same expected events as the one the fingerprint was taken
+/// from. Anything not in the fingerprint is left empty
+impl From<EventFingerprint> for Event {
+    fn from(fingerprint: EventFingerprint) -> Event {
+        match fingerprint {
+            EventFingerprint::ProviderStarted {
+                annotations,
+                image_ref,
+                provider_id,
+                host_id,
+            } => Event::ProviderStarted(ProviderStarted {
+                annotations,
+                claims: None,
+                image_ref,
+                provider_id,
+                host_id,
+            }),
+            EventFingerprint::ProviderStartFailed {
+                provider_id,
+                provider_ref,
+                host_id,
+                error,
+            } => Event::ProviderStartFailed(ProviderStartFailed {
+                error,
+                provider_id,
+                provider_ref,
+                host_id,
+            }),
+            EventFingerprint::ComponentScaled {
+                annotations,
+                image_ref,
+                component_id,
+                host_id,
+            } => Event::ComponentScaled(ComponentScaled {
+                annotations,
+                claims: None,
+                image_ref,
+                max_instances: 0,
+                component_id,
+                host_id,
+            }),
+            EventFingerprint::ComponentScaleFailed {
+                annotations,
+                image_ref,
+                component_id,
+                host_id,
+                error,
+            } => Event::ComponentScaleFailed(ComponentScaleFailed {
+                annotations,
+                claims: None,
+                image_ref,
+                max_instances: 0,
+                component_id,
+                host_id,
+                error,
+            }),
+            EventFingerprint::ConfigSet { config_name } => {
+                Event::ConfigSet(ConfigSet { config_name })
+            }
+            EventFingerprint::ConfigDeleted { config_name } => {
+                Event::ConfigDeleted(ConfigDeleted { config_name })
+            }
+            EventFingerprint::LinkdefDeleted(evt) => Event::LinkdefDeleted(evt),
+            EventFingerprint::CommandExecuted(evt) => Event::CommandExecuted(evt),
+        }
+    }
+}
+
+/// Removes the tag from an image reference that is pinned to a digest (e.g.
+/// `ghcr.io/wasmcloud/echo:0.1.0@sha256:...` becomes `ghcr.io/wasmcloud/echo@sha256:...`), since
+/// the digest alone decides which image is used. Any other reference is returned as is
+pub(crate) fn normalize_reference(reference: &str) -> Cow<'_, str> {
+    let Some((name, digest)) = reference.split_once('@') else {
+        return Cow::Borrowed(reference);
+    };
+    // A colon before the last slash is a registry port rather than a tag
+    let repository_start = name.rfind('/').map_or(0, |idx| idx + 1);
+    match name[repository_start..].rfind(':') {
+        Some(idx) => Cow::Owned(format!("{}@{digest}", &name[..repository_start + idx])),
+        None => Cow::Borrowed(reference),
+    }
+}
+
 /// Computes the sha256 digest of the given parameters to form a unique ID for a scaler
 pub(crate) fn compute_id_sha256(params: &[&str]) -> String {
     let mut hasher = Sha256::new();
@@ -609,3 +1126,349 @@ pub(crate) fn compute_id_sha256(params: &[&str]) -> String {
     let hash = hasher.finalize();
     format!("{hash:x}")
 }
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashMap;
+
+    use super::*;
+    use crate::{
+        commands::{PutConfig, ScaleComponent},
+        test_util::{NoopPublisher, TestLatticeSource},
+        SCALER_KEY,
+    };
+
+    #[test]
+    fn backoff_grows_exponentially_up_to_max() {
+        let base = Duration::from_secs(5);
+        let max = Duration::from_secs(60);
+        let timeouts = (0..=6)
+            .map(|streak| backoff_timeout(base, max, streak).as_secs())
+            .collect::<Vec<_>>();
+        assert_eq!(timeouts, vec![5, 5, 10, 20, 40, 60, 60]);
+        assert_eq!(
+            backoff_timeout(base, max, u32::MAX),
+            max,
+            "Long failure streaks should not overflow"
+        );
+    }
+
+    #[test]
+    fn partial_scale_matches_expected_event() {
+        let expected = Event::ComponentScaled(ComponentScaled {
+            annotations: BTreeMap::from([(SCALER_KEY.to_string(), "scaler".to_string())]),
+            claims: None,
+            image_ref: "echo:0.1.0".to_string(),
+            max_instances: 5,
+            component_id: "echo".to_string(),
+            host_id: "host".to_string(),
+        });
+        let Event::ComponentScaled(scaled) = &expected else {
+            unreachable!()
+        };
+        // Hosts report the absolute number of instances they ended up with, which can fall short
+        // of what was asked for
+        let partial = Event::ComponentScaled(ComponentScaled {
+            max_instances: 3,
+            ..scaled.clone()
+        });
+        assert!(
+            evt_matches_expected(&partial, &expected),
+            "A scale that fell short should still clear the expected event"
+        );
+        let other_host = Event::ComponentScaled(ComponentScaled {
+            host_id: "other".to_string(),
+            ..scaled.clone()
+        });
+        assert!(!evt_matches_expected(&other_host, &expected));
+    }
+
+    #[test]
+    fn deleted_link_matches_expected_event() {
+        let delete = Command::DeleteLink(crate::commands::DeleteLink {
+            source_id: "source".to_string(),
+            wit_namespace: "wasi".to_string(),
+            wit_package: "keyvalue".to_string(),
+            link_name: "default".to_string(),
+            model_name: "model".to_string(),
+        });
+        let (expected, _) = delete
+            .corresponding_event()
+            .expect("Deleting a link should expect an event");
+        let deleted = LinkdefDeleted {
+            source_id: "source".to_string(),
+            name: "default".to_string(),
+            wit_namespace: "wasi".to_string(),
+            wit_package: "keyvalue".to_string(),
+        };
+        assert!(
+            evt_matches_expected(&Event::LinkdefDeleted(deleted.clone()), &expected),
+            "A link deleted by the scaler should clear the expected event instead of being put back"
+        );
+        let other_link = Event::LinkdefDeleted(LinkdefDeleted {
+            name: "other".to_string(),
+            ..deleted
+        });
+        assert!(!evt_matches_expected(&other_link, &expected));
+    }
+
+    /// A scaler that always wants to put the same config
+    struct PutConfigScaler;
+
+    #[async_trait]
+    impl Scaler for PutConfigScaler {
+        fn id(&self) -> &str {
+            "putconfig"
+        }
+
+        async fn status(&self) -> StatusInfo {
+            StatusInfo::reconciling("")
+        }
+
+        async fn update_config(&mut self, _config: TraitProperty) -> Result<Vec<Command>> {
+            self.reconcile().await
+        }
+
+        async fn handle_event(&self, _event: &Event) -> Result<Vec<Command>> {
+            self.reconcile().await
+        }
+
+        async fn reconcile(&self) -> Result<Vec<Command>> {
+            Ok(vec![Command::PutConfig(PutConfig {
+                config_name: "app-config".to_string(),
+                config: HashMap::from([("key".to_string(), "value".to_string())]),
+            })])
+        }
+
+        async fn cleanup(&self) -> Result<Vec<Command>> {
+            Ok(Vec::new())
+        }
+    }
+
+    #[tokio::test]
+    async fn config_set_clears_expected_put_config() {
+        let scaler = BackoffWrapper::new(
+            PutConfigScaler,
+            NoopPublisher,
+            Vec::<ConfigScaler<TestLatticeSource>>::new(),
+            Vec::new(),
+            "doesntmatter",
+            "config_model",
+            None,
+            None,
+            None,
+        );
+
+        assert_eq!(scaler.reconcile().await.unwrap().len(), 1);
+        assert_eq!(scaler.event_count().await, 1);
+        assert!(
+            scaler.reconcile().await.unwrap().is_empty(),
+            "The put should not be sent again while its event is expected"
+        );
+
+        let unrelated = Event::ConfigSet(ConfigSet {
+            config_name: "other-config".to_string(),
+        });
+        scaler.handle_event(&unrelated).await.unwrap();
+        assert_eq!(scaler.event_count().await, 1);
+
+        let expected = Event::ConfigSet(ConfigSet {
+            config_name: "app-config".to_string(),
+        });
+        scaler.handle_event(&expected).await.unwrap();
+        assert_eq!(
+            scaler.event_count().await,
+            0,
+            "The config set event should clear the expected event"
+        );
+    }
+
+    #[test]
+    fn records_metrics_tagged_by_scaler_and_model() {
+        use metrics::{Key, Label};
+        use metrics_util::{
+            debugging::{DebugValue, DebuggingRecorder},
+            CompositeKey, MetricKind,
+        };
+
+        let recorder = DebuggingRecorder::new();
+        let snapshotter = recorder.snapshotter();
+        metrics::with_local_recorder(&recorder, || {
+            tokio::runtime::Builder::new_current_thread()
+                .enable_all()
+                .build()
+                .unwrap()
+                .block_on(async {
+                    let scaler = BackoffWrapper::new(
+                        PutConfigScaler,
+                        NoopPublisher,
+                        Vec::<ConfigScaler<TestLatticeSource>>::new(),
+                        Vec::new(),
+                        "doesntmatter",
+                        "metrics_model",
+                        None,
+                        None,
+                        None,
+                    );
+                    scaler.reconcile().await.unwrap();
+                    // The put's event is still expected, so this one is ignored
+                    scaler
+                        .handle_event(&Event::ConfigSet(ConfigSet {
+                            config_name: "other-config".to_string(),
+                        }))
+                        .await
+                        .unwrap();
+                })
+        });
+
+        let snapshot = snapshotter.snapshot().into_hashmap();
+        let labels = vec![
+            Label::new("scaler_id", "putconfig"),
+            Label::new("model_name", "metrics_model"),
+        ];
+        let value = |kind, name| {
+            snapshot
+                .get(&CompositeKey::new(
+                    kind,
+                    Key::from_parts(name, labels.clone()),
+                ))
+                .map(|(_, _, value)| value)
+        };
+        assert_eq!(
+            value(MetricKind::Counter, "wadm_scaler_commands_total"),
+            Some(&DebugValue::Counter(1))
+        );
+        assert_eq!(
+            value(MetricKind::Counter, "wadm_scaler_events_handled_total"),
+            Some(&DebugValue::Counter(1))
+        );
+        assert_eq!(
+            value(MetricKind::Counter, "wadm_scaler_events_ignored_total"),
+            Some(&DebugValue::Counter(1)),
+            "Events received while expecting events should be counted as ignored"
+        );
+        assert!(
+            matches!(
+                value(MetricKind::Histogram, "wadm_scaler_reconcile_duration_seconds"),
+                Some(DebugValue::Histogram(timings)) if timings.len() == 1
+            ),
+            "Reconcile should be timed"
+        );
+        let handle_event_timings = value(
+            MetricKind::Histogram,
+            "wadm_scaler_handle_event_duration_seconds",
+        );
+        if cfg!(feature = "scaler_metrics") {
+            assert!(
+                matches!(
+                    handle_event_timings,
+                    Some(DebugValue::Histogram(timings)) if timings.len() == 1
+                ),
+                "Event handling should be timed"
+            );
+        } else {
+            assert!(
+                handle_event_timings.is_none(),
+                "Scaler calls should only be timed with the scaler_metrics feature"
+            );
+        }
+    }
+
+    /// A scaler that wants one instance of a component on each of `desired` hosts, and sees the
+    /// instances in `running` as already placed
+    struct ScaleOutScaler {
+        desired: usize,
+        running: Arc<std::sync::RwLock<usize>>,
+    }
+
+    #[async_trait]
+    impl Scaler for ScaleOutScaler {
+        fn id(&self) -> &str {
+            "scaleout"
+        }
+
+        async fn status(&self) -> StatusInfo {
+            StatusInfo::deployed("")
+        }
+
+        async fn update_config(&mut self, _config: TraitProperty) -> Result<Vec<Command>> {
+            self.reconcile().await
+        }
+
+        async fn handle_event(&self, _event: &Event) -> Result<Vec<Command>> {
+            Ok(Vec::new())
+        }
+
+        async fn reconcile(&self) -> Result<Vec<Command>> {
+            let running = *self.running.read().unwrap();
+            Ok((running..self.desired)
+                .map(|host| {
+                    Command::ScaleComponent(ScaleComponent {
+                        component_id: "echo".to_string(),
+                        reference: "echo:0.1.0".to_string(),
+                        host_id: format!("host-{host}"),
+                        count: 1,
+                        model_name: "budget_model".to_string(),
+                        ..Default::default()
+                    })
+                })
+                .collect())
+        }
+
+        async fn cleanup(&self) -> Result<Vec<Command>> {
+            Ok(Vec::new())
+        }
+    }
+
+    #[tokio::test]
+    async fn command_budget_converges_in_batches() {
+        let running = Arc::new(std::sync::RwLock::new(0));
+        let scaler = BackoffWrapper::new(
+            ScaleOutScaler {
+                desired: 25,
+                running: running.clone(),
+            },
+            NoopPublisher,
+            Vec::<ConfigScaler<TestLatticeSource>>::new(),
+            Vec::new(),
+            "doesntmatter",
+            "budget_model",
+            None,
+            None,
+            None,
+        )
+        .with_command_budget(Some(10));
+
+        let mut batches = Vec::new();
+        loop {
+            let commands = scaler.reconcile().await.unwrap();
+            if commands.is_empty() {
+                break;
+            }
+            batches.push(commands.len());
+            if *running.read().unwrap() == 0 {
+                assert_eq!(
+                    scaler.status().await.message,
+                    "Converging in batches of 10 commands, 15 deferred to later passes"
+                );
+            }
+            // The hosts report the instances they started, which lets the next pass go ahead
+            for command in commands {
+                let (event, _) = command
+                    .corresponding_event()
+                    .expect("Scale commands should have an expected event");
+                scaler.handle_event(&event).await.unwrap();
+                *running.write().unwrap() += 1;
+            }
+            assert!(batches.len() <= 3, "Scaler should have converged by now");
+        }
+
+        assert_eq!(batches, vec![10, 10, 5]);
+        assert_eq!(*running.read().unwrap(), 25);
+        assert_eq!(
+            scaler.status().await.status_type,
+            wadm_types::api::StatusType::Deployed,
+            "The batch status should be cleared once everything fits in a pass"
+        );
+    }
+}
@@ -0,0 +1,327 @@
+//! Structured plans describing what a scaler wants to change and why, which tooling can use to
+//! explain a reconcile instead of only showing the commands it results in
+
+use std::collections::{BTreeMap, HashMap};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    commands::Command,
+    storage::{Component, Host, Provider},
+    SCALER_KEY,
+};
+
+/// The instances a scaler manages on a single host
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HostPlan {
+    /// The number of instances currently running on the host
+    pub actual: usize,
+    /// The number of instances that will be running on the host once the commands have run
+    pub desired: usize,
+}
+
+/// What a scaler wants to change in the lattice, returned by [`Scaler::diff`](super::Scaler::diff)
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ReconcilePlan {
+    /// The instances managed by the scaler on each host, before and after the commands have run,
+    /// keyed by host ID. Scalers that don't place instances on hosts leave this empty
+    pub hosts: BTreeMap<String, HostPlan>,
+    /// The commands that would be sent to reach the desired state
+    pub commands: Vec<Command>,
+}
+
+impl ReconcilePlan {
+    /// Returns a plan that only contains the given commands, for scalers that can't describe
+    /// their changes per host
+    pub fn new(commands: Vec<Command>) -> ReconcilePlan {
+        ReconcilePlan {
+            hosts: BTreeMap::new(),
+            commands,
+        }
+    }
+
+    /// Returns the IDs of the hosts the scaler will be running instances on once the commands
+    /// have run
+    pub fn selected_hosts(&self) -> impl Iterator<Item = &str> {
+        self.hosts
+            .iter()
+            .filter(|(_, host)| host.desired > 0)
+            .map(|(host_id, _)| host_id.as_str())
+    }
+
+    /// Returns whether the plan changes anything in the lattice
+    pub fn is_empty(&self) -> bool {
+        self.commands.is_empty()
+    }
+
+    /// Consumes the plan, returning the commands it would send
+    pub fn into_commands(self) -> Vec<Command> {
+        self.commands
+    }
+}
+
+/// The instances a scaler manages on each host, keyed by host ID and then by the annotations the
+/// instances were started with. This is captured before a reconcile so the commands it returns
+/// can be turned into a [`ReconcilePlan`]
+#[derive(Debug, Clone, Default)]
+pub(crate) struct ManagedInstances(BTreeMap<String, BTreeMap<BTreeMap<String, String>, usize>>);
+
+impl ManagedInstances {
+    /// Returns the instances of the given component that were started by the given scaler
+    pub(crate) fn of_component(scaler_id: &str, component: Option<&Component>) -> ManagedInstances {
+        ManagedInstances(
+            component
+                .into_iter()
+                .flat_map(|component| component.instances.iter())
+                .map(|(host_id, instances)| {
+                    let managed = instances
+                        .iter()
+                        .filter(|info| managed_by(&info.annotations, scaler_id))
+                        .map(|info| (info.annotations.clone(), info.count))
+                        .collect();
+                    (host_id.clone(), managed)
+                })
+                .collect(),
+        )
+    }
+
+    /// Returns the instances of the given provider that were started by the given scaler. Hosts
+    /// only report whether they run a provider, so the number of instances comes from the stored
+    /// provider, if there is one
+    pub(crate) fn of_provider(
+        scaler_id: &str,
+        provider_id: &str,
+        hosts: &HashMap<String, Host>,
+        provider: Option<&Provider>,
+    ) -> ManagedInstances {
+        ManagedInstances(
+            hosts
+                .iter()
+                .map(|(host_id, host)| {
+                    let managed = host
+                        .providers
+                        .iter()
+                        .filter(|info| {
+                            info.provider_id == provider_id
+                                && managed_by(&info.annotations, scaler_id)
+                        })
+                        .map(|info| {
+                            let instances = provider
+                                .and_then(|provider| provider.hosts.get(host_id))
+                                .map(|status| status.instances)
+                                .unwrap_or(1);
+                            (info.annotations.clone(), instances)
+                        })
+                        .collect();
+                    (host_id.clone(), managed)
+                })
+                .collect(),
+        )
+    }
+
+    /// Builds the plan that results from running the given commands against these instances.
+    /// Scaling a component sets the number of instances with its annotations on the host, or
+    /// removes every instance if it scales to 0 without any annotations. Starting and stopping a
+    /// provider adds or removes a single instance
+    pub(crate) fn plan(self, commands: Vec<Command>) -> ReconcilePlan {
+        let mut hosts: BTreeMap<String, HostPlan> = self
+            .0
+            .iter()
+            .map(|(host_id, instances)| {
+                let actual = instances.values().sum();
+                (
+                    host_id.clone(),
+                    HostPlan {
+                        actual,
+                        desired: actual,
+                    },
+                )
+            })
+            .collect();
+        let mut desired = self.0;
+        for command in &commands {
+            match command {
+                Command::ScaleComponent(scale) => {
+                    let instances = desired.entry(scale.host_id.clone()).or_default();
+                    if scale.count == 0 && scale.annotations.is_empty() {
+                        instances.clear();
+                    } else {
+                        *instances
+                            .entry(matching_key(instances, &scale.annotations))
+                            .or_default() = scale.count as usize;
+                    }
+                }
+                Command::StartProvider(start) => {
+                    let instances = desired.entry(start.host_id.clone()).or_default();
+                    *instances
+                        .entry(matching_key(instances, &start.annotations))
+                        .or_default() += 1;
+                }
+                Command::StopProvider(stop) => {
+                    // A host runs a single set of instances of a provider, so stopping one always
+                    // removes from it
+                    if let Some(count) = desired
+                        .entry(stop.host_id.clone())
+                        .or_default()
+                        .values_mut()
+                        .find(|count| **count > 0)
+                    {
+                        *count -= 1;
+                    }
+                }
+                _ => {}
+            }
+        }
+        for (host_id, instances) in desired {
+            hosts.entry(host_id).or_default().desired = instances.values().sum();
+        }
+        // Hosts the scaler has nothing to do with aren't part of the plan
+        hosts.retain(|_, host| host.actual > 0 || host.desired > 0);
+        ReconcilePlan { hosts, commands }
+    }
+}
+
+/// Returns the annotations of the instances that were started with the given annotations. The
+/// stored annotations can include more than the ones in the command, such as annotations added to
+/// every instance of a model
+fn matching_key(
+    instances: &BTreeMap<BTreeMap<String, String>, usize>,
+    annotations: &BTreeMap<String, String>,
+) -> BTreeMap<String, String> {
+    instances
+        .keys()
+        .find(|existing| {
+            annotations
+                .iter()
+                .all(|(key, value)| existing.get(key) == Some(value))
+        })
+        .unwrap_or(annotations)
+        .clone()
+}
+
+fn managed_by(annotations: &BTreeMap<String, String>, scaler_id: &str) -> bool {
+    annotations.get(SCALER_KEY).map(String::as_str) == Some(scaler_id)
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashSet;
+
+    use super::*;
+    use crate::{
+        commands::{ScaleComponent, StartProvider, StopProvider},
+        events::ProviderInfo,
+        storage::WadmComponentInfo,
+    };
+
+    fn annotations(scaler_id: &str) -> BTreeMap<String, String> {
+        BTreeMap::from([(SCALER_KEY.to_string(), scaler_id.to_string())])
+    }
+
+    #[test]
+    fn plan_applies_commands_to_managed_instances() {
+        let component = Component {
+            id: "component".to_string(),
+            instances: HashMap::from([
+                (
+                    "host-one".to_string(),
+                    HashSet::from([
+                        WadmComponentInfo {
+                            annotations: annotations("scaler"),
+                            count: 2,
+                        },
+                        WadmComponentInfo {
+                            annotations: annotations("someone-else"),
+                            count: 5,
+                        },
+                    ]),
+                ),
+                (
+                    "host-two".to_string(),
+                    HashSet::from([WadmComponentInfo {
+                        annotations: annotations("someone-else"),
+                        count: 1,
+                    }]),
+                ),
+            ]),
+            ..Default::default()
+        };
+        let plan = ManagedInstances::of_component("scaler", Some(&component)).plan(vec![
+            Command::ScaleComponent(ScaleComponent {
+                host_id: "host-one".to_string(),
+                count: 0,
+                ..Default::default()
+            }),
+            Command::ScaleComponent(ScaleComponent {
+                host_id: "host-three".to_string(),
+                count: 3,
+                annotations: annotations("scaler"),
+                ..Default::default()
+            }),
+        ]);
+        assert_eq!(
+            plan.hosts,
+            BTreeMap::from([
+                (
+                    "host-one".to_string(),
+                    HostPlan {
+                        actual: 2,
+                        desired: 0
+                    }
+                ),
+                (
+                    "host-three".to_string(),
+                    HostPlan {
+                        actual: 0,
+                        desired: 3
+                    }
+                ),
+            ]),
+            "Only hosts with instances managed by the scaler should be in the plan"
+        );
+        assert_eq!(
+            plan.selected_hosts().collect::<Vec<_>>(),
+            vec!["host-three"]
+        );
+        assert_eq!(plan.into_commands().len(), 2);
+
+        let hosts = HashMap::from([(
+            "host-one".to_string(),
+            Host {
+                providers: HashSet::from([ProviderInfo {
+                    provider_id: "provider".to_string(),
+                    provider_ref: String::new(),
+                    annotations: annotations("scaler"),
+                }]),
+                ..Default::default()
+            },
+        )]);
+        let plan = ManagedInstances::of_provider("scaler", "provider", &hosts, None).plan(vec![
+            Command::StopProvider(StopProvider {
+                provider_id: "provider".to_string(),
+                host_id: "host-one".to_string(),
+                annotations: annotations("scaler"),
+                ..Default::default()
+            }),
+            Command::StartProvider(StartProvider {
+                provider_id: "provider".to_string(),
+                host_id: "host-two".to_string(),
+                annotations: annotations("scaler"),
+                ..Default::default()
+            }),
+        ]);
+        assert_eq!(
+            plan.hosts.values().copied().collect::<Vec<_>>(),
+            vec![
+                HostPlan {
+                    actual: 1,
+                    desired: 0
+                },
+                HostPlan {
+                    actual: 0,
+                    desired: 1
+                }
+            ]
+        );
+    }
+}
@@ -0,0 +1,87 @@
+//! A registry of user provided scaler constructors, used to support custom trait types in
+//! manifests without modifying the built in scaler conversion
+
+use std::{collections::HashMap, sync::Arc};
+
+use anyhow::Result;
+use wadm_types::{Component, Trait};
+
+use super::manager::BoxedScaler;
+
+/// All of the information about a manifest trait that a [`ScalerFactory`] can use to construct a
+/// scaler
+#[derive(Debug, Clone, Copy)]
+pub struct ScalerContext<'a> {
+    /// The lattice the scaler will operate on
+    pub lattice_id: &'a str,
+    /// The name of the manifest the trait is defined in
+    pub manifest_name: &'a str,
+    /// The notification subject for the lattice, used by scalers that need to report on expected
+    /// events
+    pub notifier_subject: &'a str,
+    /// The component the trait is attached to
+    pub component: &'a Component,
+    /// The trait that should be turned into a scaler
+    pub scaler_trait: &'a Trait,
+}
+
+/// A constructor for a custom scaler type
+pub trait ScalerFactory: Send + Sync {
+    /// Creates a new scaler for the given trait. Returning an error will cause the scaler to be
+    /// reported as failed in the status of the manifest
+    fn create(&self, ctx: ScalerContext<'_>) -> Result<BoxedScaler>;
+}
+
+impl<F> ScalerFactory for F
+where
+    F: Fn(ScalerContext<'_>) -> Result<BoxedScaler> + Send + Sync,
+{
+    fn create(&self, ctx: ScalerContext<'_>) -> Result<BoxedScaler> {
+        self(ctx)
+    }
+}
+
+/// A registry of [`ScalerFactory`]s keyed by the trait type they handle. Any trait type registered
+/// here takes precedence over the built in scalers.
+///
+/// This type is cheap to clone as the factories are reference counted
+#[derive(Clone, Default)]
+pub struct ScalerRegistry {
+    factories: HashMap<String, Arc<dyn ScalerFactory>>,
+}
+
+impl ScalerRegistry {
+    /// Registers a factory for the given trait type, replacing any factory already registered for
+    /// that type
+    pub fn register(
+        &mut self,
+        trait_type: impl Into<String>,
+        factory: impl ScalerFactory + 'static,
+    ) -> &mut Self {
+        self.factories.insert(trait_type.into(), Arc::new(factory));
+        self
+    }
+
+    /// Returns the factory registered for the given trait type, if any
+    pub fn get(&self, trait_type: &str) -> Option<&dyn ScalerFactory> {
+        self.factories.get(trait_type).map(|f| f.as_ref())
+    }
+
+    /// Returns true if a factory is registered for the given trait type
+    pub fn contains(&self, trait_type: &str) -> bool {
+        self.factories.contains_key(trait_type)
+    }
+
+    /// Returns true if no factories have been registered
+    pub fn is_empty(&self) -> bool {
+        self.factories.is_empty()
+    }
+}
+
+impl std::fmt::Debug for ScalerRegistry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ScalerRegistry")
+            .field("trait_types", &self.factories.keys().collect::<Vec<_>>())
+            .finish()
+    }
+}
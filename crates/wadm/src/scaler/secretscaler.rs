@@ -300,6 +300,7 @@ mod test {
                     version: semver::Version::new(0, 0, 0),
                     uptime_human: String::default(),
                     uptime_seconds: 0,
+                    utilization: None,
                 }))
                 .await
                 .expect("handle_event should succeed"),
@@ -0,0 +1,527 @@
+//! A scaler wrapper that runs a scaler without ever letting it act on the lattice
+
+use std::collections::HashSet;
+
+use anyhow::Result;
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use tokio::sync::Mutex;
+use tracing::{info, warn};
+use wadm_types::{api::StatusInfo, TraitProperty};
+
+use crate::{
+    commands::{
+        Command, DeleteConfig, DeleteLink, PutConfig, PutLink, ScaleComponent, StartProvider,
+        StopProvider, UpdateComponent,
+    },
+    events::{CommandExecuted, Event},
+    publisher::Publisher,
+    scaler::{ReconcilePlan, Scaler},
+};
+
+use super::manager::BoxedScaler;
+
+/// The subject prefix that shadow scalers publish their intended commands to. The full subject is
+/// `wadm.shadow.{lattice_id}.{manifest_name}`
+pub const WADM_SHADOW_PREFIX: &str = "wadm.shadow";
+/// The suffix added to a shadow subject for the results of comparing shadow commands against the
+/// commands that were actually executed, i.e. `wadm.shadow.{lattice_id}.{manifest_name}.comparison`
+pub const WADM_SHADOW_COMPARISON_SUFFIX: &str = "comparison";
+
+/// How a command compared between a shadow scaler and the lattice
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ShadowOutcome {
+    /// The shadow scaler wanted to send the command and the same command was executed
+    Matched,
+    /// The shadow scaler wanted to send the command, but it was replaced by different commands
+    /// before the same command was executed
+    ShadowOnly,
+    /// A command was executed for something the shadow scaler manages, but the shadow scaler
+    /// didn't want to send it
+    RealOnly,
+}
+
+/// The result of comparing a single command, published to the comparison subject
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ShadowComparison {
+    pub outcome: ShadowOutcome,
+    pub command: Command,
+}
+
+/// Running totals of how the commands of a shadow scaler compared to the executed commands
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ShadowStats {
+    pub matched: usize,
+    pub shadow_only: usize,
+    pub real_only: usize,
+}
+
+#[derive(Default)]
+struct Comparison {
+    /// Commands the shadow scaler wanted to send that haven't been executed yet, without
+    /// annotations
+    pending: Vec<Command>,
+    /// Everything the shadow scaler has ever issued a command for, used to ignore executed
+    /// commands for parts of the manifest that aren't shadowed
+    targets: HashSet<String>,
+    stats: ShadowStats,
+}
+
+/// The ShadowScaler wraps another scaler and runs it as normal, but instead of returning the
+/// commands it computes, it logs them and publishes them (as a JSON list of [`Command`]s) to a
+/// shadow subject. This allows a new scaler to be evaluated against the real state of the lattice
+/// without it ever changing anything.
+///
+/// Shadow commands are compared against the [`CommandExecuted`] events for the same manifest. Each
+/// comparison is published as a [`ShadowComparison`] to the comparison subject and the totals are
+/// available from [`ShadowScaler::stats`]. Successful commands other than links only produce these
+/// events when wadm is configured to publish command successes.
+///
+/// NOTE: Because the commands are never executed, a wrapped [`BackoffWrapper`](super::BackoffWrapper)
+/// will wait on expected events that never arrive, which keeps a shadow scaler from repeatedly
+/// publishing the same commands until the expected events time out
+pub struct ShadowScaler<P> {
+    inner: BoxedScaler,
+    publisher: P,
+    subject: String,
+    model_name: String,
+    comparison: Mutex<Comparison>,
+}
+
+impl<P: Publisher> ShadowScaler<P> {
+    /// Wraps the given scaler, publishing any commands it would issue to the shadow subject for the
+    /// given lattice and manifest
+    pub fn new(inner: BoxedScaler, publisher: P, lattice_id: &str, manifest_name: &str) -> Self {
+        ShadowScaler {
+            inner,
+            publisher,
+            subject: format!("{WADM_SHADOW_PREFIX}.{lattice_id}.{manifest_name}"),
+            model_name: manifest_name.to_owned(),
+            comparison: Mutex::default(),
+        }
+    }
+
+    /// Returns how the commands of this scaler have compared to the executed commands so far
+    pub async fn stats(&self) -> ShadowStats {
+        self.comparison.lock().await.stats
+    }
+
+    async fn shadow(&self, commands: Vec<Command>) -> Result<Vec<Command>> {
+        if commands.is_empty() {
+            return Ok(commands);
+        }
+        info!(
+            scaler_id = %self.inner.id(),
+            num_commands = commands.len(),
+            subject = %self.subject,
+            "Shadow scaler would have issued commands"
+        );
+        let published = match serde_json::to_vec(&commands) {
+            Ok(data) => self.publisher.publish(data, Some(&self.subject)).await,
+            Err(e) => Err(e.into()),
+        };
+        if let Err(e) = published {
+            warn!(error = %e, scaler_id = %self.inner.id(), "Unable to publish shadow commands");
+        }
+
+        // The latest commands replace whatever was still pending, anything they dropped was never
+        // going to be executed by the shadow scaler
+        let commands: Vec<Command> = commands.iter().map(without_annotations).collect();
+        let superseded = {
+            let mut comparison = self.comparison.lock().await;
+            let (kept, superseded): (Vec<_>, Vec<_>) = std::mem::take(&mut comparison.pending)
+                .into_iter()
+                .partition(|pending| commands.contains(pending));
+            comparison.stats.shadow_only += superseded.len();
+            comparison.pending = kept;
+            for command in commands {
+                comparison.targets.insert(target(&command));
+                if !comparison.pending.contains(&command) {
+                    comparison.pending.push(command);
+                }
+            }
+            superseded
+        };
+        for command in superseded {
+            self.report(ShadowOutcome::ShadowOnly, command).await;
+        }
+        Ok(Vec::with_capacity(0))
+    }
+
+    /// Compares an executed command for this manifest against the pending shadow commands
+    async fn compare(&self, executed: &CommandExecuted) {
+        if !executed.success || executed.command.model_name() != Some(self.model_name.as_str()) {
+            return;
+        }
+        let command = without_annotations(&executed.command);
+        let outcome = {
+            let mut comparison = self.comparison.lock().await;
+            if let Some(idx) = comparison.pending.iter().position(|c| c == &command) {
+                comparison.pending.remove(idx);
+                comparison.stats.matched += 1;
+                ShadowOutcome::Matched
+            } else if comparison.targets.contains(&target(&command)) {
+                comparison.stats.real_only += 1;
+                ShadowOutcome::RealOnly
+            } else {
+                return;
+            }
+        };
+        self.report(outcome, command).await;
+    }
+
+    async fn report(&self, outcome: ShadowOutcome, command: Command) {
+        if outcome != ShadowOutcome::Matched {
+            info!(scaler_id = %self.inner.id(), ?outcome, ?command, "Shadow command diverged");
+        }
+        let subject = format!("{}.{WADM_SHADOW_COMPARISON_SUFFIX}", self.subject);
+        let published = match serde_json::to_vec(&ShadowComparison { outcome, command }) {
+            Ok(data) => self.publisher.publish(data, Some(&subject)).await,
+            Err(e) => Err(e.into()),
+        };
+        if let Err(e) = published {
+            warn!(error = %e, scaler_id = %self.inner.id(), "Unable to publish shadow comparison");
+        }
+    }
+}
+
+/// Returns a copy of the command without annotations, which differ between scalers that would
+/// otherwise send the same command
+fn without_annotations(command: &Command) -> Command {
+    let mut command = command.clone();
+    if let Command::ScaleComponent(ScaleComponent { annotations, .. })
+    | Command::UpdateComponent(UpdateComponent { annotations, .. })
+    | Command::StartProvider(StartProvider { annotations, .. })
+    | Command::StopProvider(StopProvider { annotations, .. }) = &mut command
+    {
+        annotations.clear();
+    }
+    command
+}
+
+/// Returns the thing in the lattice that a command acts on
+fn target(command: &Command) -> String {
+    match command {
+        Command::ScaleComponent(ScaleComponent { component_id, .. })
+        | Command::UpdateComponent(UpdateComponent { component_id, .. }) => {
+            format!("component/{component_id}")
+        }
+        Command::StartProvider(StartProvider { provider_id, .. })
+        | Command::StopProvider(StopProvider { provider_id, .. }) => {
+            format!("provider/{provider_id}")
+        }
+        Command::PutLink(PutLink { source_id, .. })
+        | Command::DeleteLink(DeleteLink { source_id, .. }) => format!("link/{source_id}"),
+        Command::PutConfig(PutConfig { config_name, .. })
+        | Command::DeleteConfig(DeleteConfig { config_name }) => format!("config/{config_name}"),
+    }
+}
+
+#[async_trait]
+impl<P: Publisher + Send + Sync> Scaler for ShadowScaler<P> {
+    fn id(&self) -> &str {
+        self.inner.id()
+    }
+
+    fn name(&self) -> String {
+        self.inner.name()
+    }
+
+    fn kind(&self) -> &str {
+        self.inner.kind()
+    }
+
+    async fn status(&self) -> StatusInfo {
+        self.inner.status().await
+    }
+
+    async fn update_config(&mut self, config: TraitProperty) -> Result<Vec<Command>> {
+        let commands = self.inner.update_config(config).await?;
+        self.shadow(commands).await
+    }
+
+    async fn handle_event(&self, event: &Event) -> Result<Vec<Command>> {
+        if let Event::CommandExecuted(executed) = event {
+            self.compare(executed).await;
+        }
+        let commands = self.inner.handle_event(event).await?;
+        self.shadow(commands).await
+    }
+
+    async fn reconcile(&self) -> Result<Vec<Command>> {
+        let commands = self.inner.reconcile().await?;
+        self.shadow(commands).await
+    }
+
+    async fn diff(&self) -> Result<ReconcilePlan> {
+        // Explaining what the scaler would do doesn't send anything, so there is nothing to shadow
+        self.inner.diff().await
+    }
+
+    async fn cleanup(&self) -> Result<Vec<Command>> {
+        // A shadow scaler never created anything, so it must not be allowed to remove anything the
+        // real scalers are managing
+        let commands = self.inner.cleanup().await?;
+        self.shadow(commands).await
+    }
+    fn pause(&self) {
+        self.inner.pause()
+    }
+
+    fn resume(&self) {
+        self.inner.resume()
+    }
+
+    fn reconcile_interval(&self) -> Option<std::time::Duration> {
+        self.inner.reconcile_interval()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+
+    use tokio::sync::RwLock;
+
+    use super::*;
+
+    type Published = (Option<String>, serde_json::Value);
+
+    /// A publisher that records the destination of everything it publishes
+    #[derive(Clone, Default)]
+    struct SubjectRecorder {
+        received: Arc<RwLock<Vec<Published>>>,
+    }
+
+    #[async_trait]
+    impl Publisher for SubjectRecorder {
+        async fn publish(&self, data: Vec<u8>, destination: Option<&str>) -> Result<()> {
+            self.received.write().await.push((
+                destination.map(ToOwned::to_owned),
+                serde_json::from_slice(&data)?,
+            ));
+            Ok(())
+        }
+    }
+
+    /// A scaler that always wants to stop the same provider
+    struct StopScaler;
+
+    #[async_trait]
+    impl Scaler for StopScaler {
+        fn id(&self) -> &str {
+            "stopper"
+        }
+
+        async fn status(&self) -> StatusInfo {
+            StatusInfo::deployed("")
+        }
+
+        async fn update_config(&mut self, _config: TraitProperty) -> Result<Vec<Command>> {
+            self.reconcile().await
+        }
+
+        async fn handle_event(&self, _event: &Event) -> Result<Vec<Command>> {
+            self.reconcile().await
+        }
+
+        async fn reconcile(&self) -> Result<Vec<Command>> {
+            Ok(vec![Command::StopProvider(StopProvider {
+                provider_id: "provider".to_string(),
+                host_id: "host".to_string(),
+                model_name: "shadowed".to_string(),
+                annotations: Default::default(),
+            })])
+        }
+
+        async fn cleanup(&self) -> Result<Vec<Command>> {
+            self.reconcile().await
+        }
+    }
+
+    #[tokio::test]
+    async fn commands_only_go_to_shadow_subject() {
+        let publisher = SubjectRecorder::default();
+        let scaler = ShadowScaler::new(
+            Box::new(StopScaler),
+            publisher.clone(),
+            "default",
+            "shadowed",
+        );
+
+        assert_eq!(
+            scaler.id(),
+            "stopper",
+            "Shadow scaler should keep the inner ID"
+        );
+        assert!(
+            scaler
+                .reconcile()
+                .await
+                .expect("Reconcile should succeed")
+                .is_empty(),
+            "No real commands should be returned from reconcile"
+        );
+        assert!(
+            scaler
+                .cleanup()
+                .await
+                .expect("Cleanup should succeed")
+                .is_empty(),
+            "No real commands should be returned from cleanup"
+        );
+
+        let received = publisher.received.read().await;
+        assert_eq!(received.len(), 2);
+        for (subject, commands) in received.iter() {
+            assert_eq!(subject.as_deref(), Some("wadm.shadow.default.shadowed"));
+            assert_eq!(
+                commands,
+                &serde_json::to_value(StopScaler.reconcile().await.unwrap()).unwrap()
+            );
+        }
+    }
+
+    /// A scaler that returns the next planned list of commands each time it reconciles
+    struct PlannedScaler {
+        plans: std::sync::Mutex<Vec<Vec<Command>>>,
+    }
+
+    #[async_trait]
+    impl Scaler for PlannedScaler {
+        fn id(&self) -> &str {
+            "planned"
+        }
+
+        async fn status(&self) -> StatusInfo {
+            StatusInfo::deployed("")
+        }
+
+        async fn update_config(&mut self, _config: TraitProperty) -> Result<Vec<Command>> {
+            Ok(Vec::new())
+        }
+
+        async fn handle_event(&self, _event: &Event) -> Result<Vec<Command>> {
+            Ok(Vec::new())
+        }
+
+        async fn reconcile(&self) -> Result<Vec<Command>> {
+            Ok(self.plans.lock().unwrap().pop().unwrap_or_default())
+        }
+
+        async fn cleanup(&self) -> Result<Vec<Command>> {
+            Ok(Vec::new())
+        }
+    }
+
+    fn stop(host_id: &str) -> Command {
+        Command::StopProvider(StopProvider {
+            provider_id: "provider".to_string(),
+            host_id: host_id.to_string(),
+            model_name: "shadowed".to_string(),
+            annotations: Default::default(),
+        })
+    }
+
+    fn executed(command: Command) -> Event {
+        Event::CommandExecuted(CommandExecuted {
+            command_id: command.id(),
+            command,
+            success: true,
+            message: String::new(),
+        })
+    }
+
+    #[tokio::test]
+    async fn shadow_commands_are_compared_to_executed_commands() {
+        let publisher = SubjectRecorder::default();
+        // Plans are popped from the end
+        let scaler = ShadowScaler::new(
+            Box::new(PlannedScaler {
+                plans: std::sync::Mutex::new(vec![
+                    vec![stop("host-3")],
+                    vec![stop("host-2")],
+                    vec![stop("host-1")],
+                ]),
+            }),
+            publisher.clone(),
+            "default",
+            "shadowed",
+        );
+
+        scaler.reconcile().await.unwrap();
+        // The real command carries different annotations, which shouldn't matter
+        let mut real = stop("host-1");
+        real.add_annotations(&[("scaler".to_string(), "real".to_string())].into());
+        scaler.handle_event(&executed(real)).await.unwrap();
+        // Something the shadow scaler didn't want to do to the provider it manages
+        scaler
+            .handle_event(&executed(stop("other-host")))
+            .await
+            .unwrap();
+        // Commands for other parts of the manifest, other manifests and failed commands are ignored
+        scaler
+            .handle_event(&executed(Command::ScaleComponent(ScaleComponent {
+                component_id: "unrelated".to_string(),
+                model_name: "shadowed".to_string(),
+                ..Default::default()
+            })))
+            .await
+            .unwrap();
+        let mut other_model = stop("other-host");
+        if let Command::StopProvider(stop) = &mut other_model {
+            stop.model_name = "other".to_string();
+        }
+        scaler.handle_event(&executed(other_model)).await.unwrap();
+        scaler
+            .handle_event(&Event::CommandExecuted(CommandExecuted {
+                command_id: String::new(),
+                command: stop("other-host"),
+                success: false,
+                message: String::new(),
+            }))
+            .await
+            .unwrap();
+        // The shadow scaler changes its mind before the command is executed
+        scaler.reconcile().await.unwrap();
+        scaler.reconcile().await.unwrap();
+
+        assert_eq!(
+            scaler.stats().await,
+            ShadowStats {
+                matched: 1,
+                shadow_only: 1,
+                real_only: 1,
+            }
+        );
+
+        let comparisons = publisher
+            .received
+            .read()
+            .await
+            .iter()
+            .filter(|(subject, _)| {
+                subject.as_deref() == Some("wadm.shadow.default.shadowed.comparison")
+            })
+            .map(|(_, data)| serde_json::from_value::<ShadowComparison>(data.clone()).unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            comparisons,
+            vec![
+                ShadowComparison {
+                    outcome: ShadowOutcome::Matched,
+                    command: stop("host-1"),
+                },
+                ShadowComparison {
+                    outcome: ShadowOutcome::RealOnly,
+                    command: stop("other-host"),
+                },
+                ShadowComparison {
+                    outcome: ShadowOutcome::ShadowOnly,
+                    command: stop("host-2"),
+                },
+            ]
+        );
+    }
+}
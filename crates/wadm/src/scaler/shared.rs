@@ -0,0 +1,189 @@
+//! Contains a registry of scalers that manage a lattice resource more than one model can require,
+//! such as a provider. Every model requiring the same resource shares a single scaler, so the
+//! resource is managed (and owned) by one scaler no matter which of the models is removed first
+
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Arc,
+    time::Duration,
+};
+
+use anyhow::Result;
+use async_trait::async_trait;
+use tokio::sync::RwLock;
+use tracing::debug;
+use wadm_types::{api::StatusInfo, TraitProperty};
+
+use crate::{
+    commands::Command,
+    events::Event,
+    scaler::{ReconcilePlan, Scaler},
+};
+
+use super::manager::{BoxedScaler, ScalerList};
+
+/// Returns the identity of a provider for sharing it between models. Models only share a provider
+/// if they use the same reference under the same provider ID
+pub(crate) fn provider_resource(provider_reference: &str, provider_id: &str) -> String {
+    format!("provider/{provider_reference}/{provider_id}")
+}
+
+struct SharedEntry {
+    scaler: Arc<BoxedScaler>,
+    /// The models currently requiring the resource
+    models: HashSet<String>,
+}
+
+/// The shared scalers of a lattice, keyed by the resource they manage (see
+/// [`Scaler::shared_resource`])
+#[derive(Clone, Default)]
+pub(crate) struct SharedScalers {
+    entries: Arc<RwLock<HashMap<String, SharedEntry>>>,
+}
+
+impl SharedScalers {
+    /// Registers the given scalers as the scalers of the given model, returning them with every
+    /// scaler of a shared resource replaced by the scaler shared between all models requiring
+    /// it. The first model to require a resource provides the scaler for it, which is kept until
+    /// no model requires the resource anymore. Any resources the model no longer requires are
+    /// released
+    pub(crate) async fn share(&self, model: &str, scalers: ScalerList) -> ScalerList {
+        let mut entries = self.entries.write().await;
+        let mut required = HashSet::new();
+        let scalers = scalers
+            .into_iter()
+            .map(|scaler| {
+                let Some(resource) = scaler.shared_resource() else {
+                    return scaler;
+                };
+                required.insert(resource.clone());
+                let keep_existing = entries.get(&resource).is_some_and(|entry| {
+                    entry.scaler.id() == scaler.id()
+                        || entry.models.iter().any(|other| other != model)
+                });
+                if !keep_existing {
+                    entries.insert(
+                        resource.clone(),
+                        SharedEntry {
+                            scaler: Arc::new(scaler),
+                            models: HashSet::new(),
+                        },
+                    );
+                }
+                let entry = entries
+                    .get_mut(&resource)
+                    .expect("entry should exist after being inserted");
+                entry.models.insert(model.to_owned());
+                Box::new(SharedScaler {
+                    inner: entry.scaler.clone(),
+                    resource,
+                    model: model.to_owned(),
+                    shared: self.clone(),
+                }) as BoxedScaler
+            })
+            .collect();
+        release(&mut entries, model, |resource| !required.contains(resource));
+        scalers
+    }
+
+    /// Records that the given model no longer requires any shared resources
+    pub(crate) async fn release(&self, model: &str) {
+        release(&mut *self.entries.write().await, model, |_| true);
+    }
+
+    /// Returns whether a model other than the given one requires the resource
+    async fn required_elsewhere(&self, resource: &str, model: &str) -> bool {
+        self.entries
+            .read()
+            .await
+            .get(resource)
+            .is_some_and(|entry| entry.models.iter().any(|other| other != model))
+    }
+
+    #[cfg(test)]
+    pub(crate) async fn is_empty(&self) -> bool {
+        self.entries.read().await.is_empty()
+    }
+}
+
+fn release(entries: &mut HashMap<String, SharedEntry>, model: &str, filter: impl Fn(&str) -> bool) {
+    entries
+        .iter_mut()
+        .filter(|(resource, _)| filter(resource))
+        .for_each(|(_, entry)| {
+            entry.models.remove(model);
+        });
+    entries.retain(|_, entry| !entry.models.is_empty());
+}
+
+/// A handle to a shared scaler for one of the models requiring its resource. Cleaning up does
+/// nothing while another model still requires the resource
+struct SharedScaler {
+    inner: Arc<BoxedScaler>,
+    resource: String,
+    model: String,
+    shared: SharedScalers,
+}
+
+#[async_trait]
+impl Scaler for SharedScaler {
+    fn id(&self) -> &str {
+        self.inner.id()
+    }
+
+    fn name(&self) -> String {
+        self.inner.name()
+    }
+
+    fn kind(&self) -> &str {
+        self.inner.kind()
+    }
+
+    async fn status(&self) -> StatusInfo {
+        self.inner.status().await
+    }
+
+    async fn update_config(&mut self, _config: TraitProperty) -> Result<Vec<Command>> {
+        anyhow::bail!("Scalers shared between models can't be reconfigured")
+    }
+
+    async fn handle_event(&self, event: &Event) -> Result<Vec<Command>> {
+        self.inner.handle_event(event).await
+    }
+
+    async fn reconcile(&self) -> Result<Vec<Command>> {
+        self.inner.reconcile().await
+    }
+
+    async fn diff(&self) -> Result<ReconcilePlan> {
+        self.inner.diff().await
+    }
+
+    async fn cleanup(&self) -> Result<Vec<Command>> {
+        if self
+            .shared
+            .required_elsewhere(&self.resource, &self.model)
+            .await
+        {
+            debug!(resource = %self.resource, model = %self.model, "Not cleaning up resource that another model still requires");
+            return Ok(Vec::new());
+        }
+        self.inner.cleanup().await
+    }
+
+    fn pause(&self) {
+        self.inner.pause()
+    }
+
+    fn resume(&self) {
+        self.inner.resume()
+    }
+
+    fn reconcile_interval(&self) -> Option<Duration> {
+        self.inner.reconcile_interval()
+    }
+
+    fn shared_resource(&self) -> Option<String> {
+        Some(self.resource.clone())
+    }
+}
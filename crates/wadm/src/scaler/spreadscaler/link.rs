@@ -1,7 +1,7 @@
 use anyhow::Result;
 use async_trait::async_trait;
 use tokio::sync::RwLock;
-use tracing::instrument;
+use tracing::{instrument, trace};
 use wadm_types::{api::StatusInfo, TraitProperty};
 
 use crate::{
@@ -11,7 +11,7 @@ use crate::{
         ProviderHealthCheckStatus,
     },
     scaler::{compute_id_sha256, Scaler},
-    storage::ReadStore,
+    storage::{Link as LinkState, ReadStore},
     workers::LinkSource,
 };
 
@@ -44,8 +44,6 @@ pub struct LinkScalerConfig {
 /// The LinkSpreadScaler ensures that link configuration exists on a specified lattice.
 pub struct LinkScaler<S, L> {
     pub config: LinkScalerConfig,
-    // TODO(#253): Reenable once we figure out https://github.com/wasmCloud/wadm/issues/123
-    #[allow(unused)]
     store: S,
     ctl_client: L,
     id: String,
@@ -137,6 +135,16 @@ where
     async fn reconcile(&self) -> Result<Vec<Command>> {
         let source_id = &self.config.source_id;
         let target = &self.config.target;
+
+        // A host only keeps one link per source, interface and name, so if another model owns a
+        // different version of this link, putting ours would just be overwritten again
+        if let Some(owner) = self.conflicting_owner().await? {
+            trace!(%owner, "Link is owned by another model, not putting link");
+            *self.status.write().await =
+                StatusInfo::failed(&format!("Link conflicts with model {owner}"));
+            return Ok(Vec::with_capacity(0));
+        }
+
         let linkdefs = self.ctl_client.get_links().await?;
         let (exists, _config_different) = linkdefs
             .into_iter()
@@ -226,6 +234,33 @@ where
 }
 
 impl<S: ReadStore + Send + Sync, L: LinkSource> LinkScaler<S, L> {
+    /// Returns the name of the model that owns this link if it conflicts with this scaler's config
+    async fn conflicting_owner(&self) -> Result<Option<String>> {
+        let key = LinkState::key(
+            &self.config.source_id,
+            &self.config.wit_namespace,
+            &self.config.wit_package,
+            &self.config.name,
+        );
+        let desired = LinkState {
+            model_name: self.config.model_name.to_owned(),
+            source_id: self.config.source_id.to_owned(),
+            target: self.config.target.to_owned(),
+            name: self.config.name.to_owned(),
+            wit_namespace: self.config.wit_namespace.to_owned(),
+            wit_package: self.config.wit_package.to_owned(),
+            interfaces: self.config.wit_interfaces.to_owned(),
+            source_config: self.config.source_config.clone(),
+            target_config: self.config.target_config.clone(),
+        };
+        Ok(self
+            .store
+            .get::<LinkState>(&self.config.lattice_id, &key)
+            .await?
+            .filter(|owned| owned.conflicts_with(&desired))
+            .map(|owned| owned.model_name))
+    }
+
     /// Construct a new LinkScaler with specified configuration values
     pub fn new(store: S, link_config: LinkScalerConfig, ctl_client: L) -> Self {
         // Compute the id of this scaler based on all of the configuration values
@@ -283,6 +318,7 @@ mod test {
     use chrono::Utc;
 
     use super::*;
+    use wadm_types::api::StatusType;
 
     use crate::{
         events::{ComponentScaled, ProviderHealthCheckInfo, ProviderInfo},
@@ -468,6 +504,77 @@ mod test {
         );
     }
 
+    #[tokio::test]
+    async fn conflicting_link_from_another_model_fails() {
+        let lattice_id = "conflicting-linkdef".to_string();
+        let store = Arc::new(create_store(&lattice_id, "component_ref", "provider_ref").await);
+
+        let config = |model_name: &str, target: &str| LinkScalerConfig {
+            source_id: "component".to_string(),
+            target: target.to_string(),
+            wit_namespace: "namespace".to_string(),
+            wit_package: "package".to_string(),
+            wit_interfaces: vec!["interface".to_string()],
+            name: "default".to_string(),
+            lattice_id: lattice_id.clone(),
+            model_name: model_name.to_string(),
+            source_config: vec![],
+            target_config: vec![],
+        };
+        let owner = PutLink {
+            source_id: "component".to_string(),
+            target: "provider".to_string(),
+            name: "default".to_string(),
+            wit_namespace: "namespace".to_string(),
+            wit_package: "package".to_string(),
+            interfaces: vec!["interface".to_string()],
+            model_name: "model_a".to_string(),
+            ..Default::default()
+        };
+        store
+            .store(
+                &lattice_id,
+                LinkState::key("component", "namespace", "package", "default"),
+                LinkState::from(&owner),
+            )
+            .await
+            .unwrap();
+        let lattice_source = TestLatticeSource {
+            links: vec![Link::builder()
+                .source_id("component")
+                .target("provider")
+                .wit_namespace("namespace")
+                .wit_package("package")
+                .interfaces(vec!["interface".to_string()])
+                .name("default")
+                .build()
+                .unwrap()],
+            ..Default::default()
+        };
+
+        let model_a = LinkScaler::new(
+            store.clone(),
+            config("model_a", "provider"),
+            lattice_source.clone(),
+        );
+        let model_b = LinkScaler::new(
+            store.clone(),
+            config("model_b", "other_provider"),
+            lattice_source,
+        );
+
+        assert!(
+            model_b.reconcile().await.unwrap().is_empty(),
+            "Conflicting link should not be put"
+        );
+        let status = model_b.status().await;
+        assert_eq!(status.status_type, StatusType::Failed);
+        assert_eq!(status.message, "Link conflicts with model model_a");
+
+        assert!(model_a.reconcile().await.unwrap().is_empty());
+        assert_eq!(model_a.status().await.status_type, StatusType::Deployed);
+    }
+
     #[tokio::test]
     async fn can_put_linkdef_from_triggering_events() {
         let lattice_id = "can_put_linkdef_from_triggering_events";
@@ -505,6 +612,7 @@ mod test {
                     version: None,
                     id: host_id_one.to_string(),
                     last_seen: Utc::now(),
+                    ..Default::default()
                 },
             )
             .await
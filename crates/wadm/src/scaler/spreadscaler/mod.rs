@@ -1,5 +1,13 @@
 use std::{
-    cmp::Ordering, cmp::Reverse, collections::BTreeMap, collections::HashMap, collections::HashSet,
+    cmp::Ordering,
+    cmp::Reverse,
+    collections::BTreeMap,
+    collections::BTreeSet,
+    collections::HashMap,
+    collections::HashSet,
+    hash::{DefaultHasher, Hash, Hasher},
+    str::FromStr,
+    time::{Duration, Instant},
 };
 
 use anyhow::Result;
@@ -7,19 +15,20 @@ use async_trait::async_trait;
 use tokio::sync::RwLock;
 use tracing::{instrument, trace, warn};
 use wadm_types::{
-    api::StatusInfo, Spread, SpreadScalerProperty, TraitProperty, DEFAULT_SPREAD_WEIGHT,
+    api::{StatusInfo, StatusType},
+    Spread, SpreadScalerProperty, TraitProperty, DEFAULT_SPREAD_WEIGHT,
 };
 
 use crate::events::HostHeartbeat;
 use crate::{
-    commands::{Command, ScaleComponent},
+    commands::{Command, ScaleComponent, UpdateComponent},
     events::{Event, HostStarted, HostStopped},
-    scaler::Scaler,
-    storage::{Component, Host, ReadStore},
+    scaler::{ComponentScalerConfig, ManagedInstances, ReconcilePlan, Scaler},
+    storage::{Component, ConsistentRead, Host, Provider, ReadStore, WadmComponentInfo},
     SCALER_KEY,
 };
 
-use super::compute_id_sha256;
+use super::{compute_id_sha256, CapacityClaim, CapacityLimit, ComputePool, HostCapacity};
 
 pub mod link;
 pub mod provider;
@@ -36,12 +45,42 @@ struct ComponentSpreadConfig {
     component_reference: String,
     /// Unique component identifier for a component
     component_id: String,
+    /// Name of the component in the manifest, needed to compute the IDs of previous versions of
+    /// this scaler
+    component_name: String,
     /// Lattice ID that this SpreadScaler monitors
     lattice_id: String,
     /// The name of the wadm model this SpreadScaler is under
     model_name: String,
     /// Configuration for this SpreadScaler
     spread_config: SpreadScalerProperty,
+    /// Whether to emit the absolute desired count for each host rather than the count needed to
+    /// correct the observed state
+    declarative: bool,
+    /// How long newly started instances are protected from being stopped when scaling down
+    start_cooldown: Duration,
+    /// Whether running more than one version of the component at a time is a failure
+    uniform_versions: bool,
+    /// How instances are chosen to be stopped when scaling down
+    scale_down_policy: ScaleDownPolicy,
+    /// How long after a host starts before new instances can be placed on it
+    host_quiet_period: Duration,
+    /// How far the number of running instances of a spread can be from the desired count before
+    /// the scaler corrects it
+    count_tolerance: usize,
+    /// Whether new instances are only placed on hosts running providers for every capability the
+    /// component requires
+    check_capabilities: bool,
+}
+
+/// The most recent time this scaler saw instances start on a host for a spread
+#[derive(Debug, Clone, Copy)]
+struct InstanceStart {
+    /// The number of instances running on the host before they were started
+    previous_count: usize,
+    /// The number of instances currently running on the host
+    count: usize,
+    started_at: Instant,
 }
 
 /// The ComponentSpreadScaler ensures that a certain number of instances are running,
@@ -55,12 +94,21 @@ pub struct ComponentSpreadScaler<S> {
     store: S,
     id: String,
     status: RwLock<StatusInfo>,
+    /// Recent instance starts keyed by host ID and spread name, used to enforce the start cooldown
+    instance_starts: RwLock<HashMap<(String, String), InstanceStart>>,
+    /// A fingerprint of the observed state from the last reconcile, if that reconcile had nothing
+    /// to do. Reconciling against the same state again is skipped
+    reconciled_state: RwLock<Option<u64>>,
     /// Named configuration to pass to the component.
     pub config: Vec<String>,
+    /// The pool to compute commands on, if they shouldn't be computed inline
+    compute_pool: Option<ComputePool>,
+    /// This scaler's claim on host capacity, if capacity is shared between models
+    capacity: Option<CapacityClaim>,
 }
 
 #[async_trait]
-impl<S: ReadStore + Send + Sync + Clone> Scaler for ComponentSpreadScaler<S> {
+impl<S: ReadStore + ConsistentRead + Send + Sync + Clone> Scaler for ComponentSpreadScaler<S> {
     fn id(&self) -> &str {
         &self.id
     }
@@ -69,6 +117,14 @@ impl<S: ReadStore + Send + Sync + Clone> Scaler for ComponentSpreadScaler<S> {
         SPREAD_SCALER_KIND
     }
 
+    fn reconcile_interval(&self) -> Option<Duration> {
+        self.spread_config
+            .spread_config
+            .reconcile_interval
+            .filter(|secs| *secs > 0)
+            .map(Duration::from_secs)
+    }
+
     fn name(&self) -> String {
         self.spread_config.component_id.to_string()
     }
@@ -83,8 +139,21 @@ impl<S: ReadStore + Send + Sync + Clone> Scaler for ComponentSpreadScaler<S> {
             TraitProperty::SpreadScaler(prop) => prop,
             _ => anyhow::bail!("Given config was not a spread scaler config object"),
         };
+        if let (Some(min), Some(max)) = (
+            spread_config.min_count,
+            spread_config.max_count.filter(|max| *max > 0),
+        ) {
+            anyhow::ensure!(
+                min <= max,
+                "min_count ({min}) cannot be greater than max_count ({max})"
+            );
+        }
+        if let Some(claim) = &self.capacity {
+            claim.set_instances(spread_config.instances);
+        }
         self.spread_config.spread_config = spread_config;
-        self.spread_requirements = compute_spread(&self.spread_config.spread_config);
+        self.spread_requirements = compute_bounded_spread(&self.spread_config.spread_config);
+        self.invalidate_reconciled_state().await;
         self.reconcile().await
     }
 
@@ -97,6 +166,15 @@ impl<S: ReadStore + Send + Sync + Clone> Scaler for ComponentSpreadScaler<S> {
             // TODO: React to ComponentScaleFailed with an exponential backoff, can't just immediately retry since that
             // would cause a very tight loop of failures
             Event::ComponentScaled(evt) if evt.component_id == self.spread_config.component_id => {
+                if let (Some(scaler_id), Some(spread_name)) = (
+                    evt.annotations.get(SCALER_KEY),
+                    evt.annotations.get(SPREAD_KEY),
+                ) {
+                    if scaler_id == self.id() {
+                        self.record_instance_count(&evt.host_id, spread_name, evt.max_instances)
+                            .await;
+                    }
+                }
                 self.reconcile().await
             }
             Event::HostStopped(HostStopped { labels, .. })
@@ -120,17 +198,151 @@ impl<S: ReadStore + Send + Sync + Clone> Scaler for ComponentSpreadScaler<S> {
     }
 
     #[instrument(level = "trace", skip_all, fields(name = %self.spread_config.model_name, scaler_id = %self.id))]
-    async fn reconcile(&self) -> Result<Vec<Command>> {
+    async fn diff(&self) -> Result<ReconcilePlan> {
         let component_id = &self.spread_config.component_id;
-        let component = self
-            .store
+        // Read everything from a single view so a concurrent update can't change the hosts out
+        // from under us partway through
+        let store = self.store.consistent_view().await;
+        let component = store
             .get::<Component>(&self.spread_config.lattice_id, component_id)
             .await?;
+        let managed = ManagedInstances::of_component(&self.id, component.as_ref());
+        let mut hosts = store.list::<Host>(&self.spread_config.lattice_id).await?;
+        let protected_instances = self.protected_instances().await;
+        let start_times = self.start_times().await;
+        let capacity = self.capacity.as_ref().map(CapacityClaim::limit);
+        let (achieved, desired) = self.progress(component.as_ref());
+
+        if self.spread_config.check_capabilities {
+            let providers = store
+                .list::<Provider>(&self.spread_config.lattice_id)
+                .await?;
+            let required = component
+                .as_ref()
+                .map(|component| component.capabilities.as_slice())
+                .unwrap_or_default();
+            if let Some(message) =
+                remove_incapable_hosts(&mut hosts, required, &providers, |host| {
+                    host.components.contains_key(component_id)
+                })
+            {
+                trace!(%message, "No hosts satisfy the required capabilities");
+                *self.status.write().await =
+                    StatusInfo::failed(&message).with_progress(achieved, desired);
+                *self.reconciled_state.write().await = None;
+                return Ok(managed.plan(Vec::new()));
+            }
+        }
 
-        let hosts = self
-            .store
-            .list::<Host>(&self.spread_config.lattice_id)
-            .await?;
+        // Hosts become eligible as their quiet period passes without anything else changing, so
+        // they are removed before fingerprinting
+        remove_quiet_hosts(&mut hosts, self.spread_config.host_quiet_period, |host| {
+            host.components.contains_key(component_id)
+        });
+        let fingerprint = state_fingerprint(
+            component_id,
+            component.as_ref(),
+            &hosts,
+            &protected_instances,
+            &start_times,
+            capacity,
+        );
+        if *self.reconciled_state.read().await == Some(fingerprint) {
+            trace!("Nothing has changed since the last reconcile, skipping");
+            return Ok(managed.plan(Vec::new()));
+        }
+
+        let (commands, status) = match &self.compute_pool {
+            Some(pool) => {
+                let planner = self.planner();
+                pool.run(move || {
+                    planner.compute_commands(
+                        component,
+                        hosts,
+                        protected_instances,
+                        start_times,
+                        capacity,
+                    )
+                })
+                .await?
+            }
+            None => {
+                self.compute_commands(component, hosts, protected_instances, start_times, capacity)
+            }
+        };
+        let status = status.with_progress(achieved, desired);
+        trace!(?status, "Updating scaler status");
+        *self.status.write().await = status;
+        // Only a reconcile that had nothing left to do is cached, so commands are always recomputed
+        // until the observed state catches up with them
+        *self.reconciled_state.write().await = commands.is_empty().then_some(fingerprint);
+        Ok(managed.plan(commands))
+    }
+
+    async fn reconcile(&self) -> Result<Vec<Command>> {
+        self.diff().await.map(ReconcilePlan::into_commands)
+    }
+
+    #[instrument(level = "trace", skip_all, fields(name = %self.spread_config.model_name))]
+    async fn cleanup(&self) -> Result<Vec<Command>> {
+        let mut config_clone = self.spread_config.clone();
+        config_clone.spread_config.instances = 0;
+        // Everything is removed during cleanup, so which hosts could run it doesn't matter
+        config_clone.check_capabilities = false;
+        let spread_requirements = compute_spread(&config_clone.spread_config);
+
+        let cleanerupper = ComponentSpreadScaler {
+            spread_config: config_clone,
+            store: self.store.clone(),
+            spread_requirements,
+            id: self.id.clone(),
+            status: RwLock::new(StatusInfo::reconciling("")),
+            // Cleanup removes everything, so nothing is protected by the start cooldown
+            instance_starts: RwLock::default(),
+            reconciled_state: RwLock::default(),
+            config: self.config.clone(),
+            compute_pool: self.compute_pool.clone(),
+            // Scaling to 0 never needs capacity
+            capacity: None,
+        };
+
+        cleanerupper.reconcile().await
+    }
+}
+
+impl<S> ComponentSpreadScaler<S> {
+    /// Returns a copy of this scaler's configuration without its store or any runtime state, so
+    /// commands can be computed on the compute pool
+    fn planner(&self) -> ComponentSpreadScaler<()> {
+        ComponentSpreadScaler {
+            spread_config: self.spread_config.clone(),
+            spread_requirements: self.spread_requirements.clone(),
+            store: (),
+            id: self.id.clone(),
+            status: RwLock::new(StatusInfo::reconciling("")),
+            instance_starts: RwLock::default(),
+            reconciled_state: RwLock::default(),
+            config: self.config.clone(),
+            compute_pool: None,
+            capacity: None,
+        }
+    }
+
+    /// Computes the commands needed to reach the desired state from the given observed state,
+    /// along with the resulting status of the scaler. This only does computation, so it is safe to
+    /// run off of the async runtime
+    fn compute_commands(
+        &self,
+        component: Option<Component>,
+        hosts: HashMap<String, Host>,
+        protected_instances: HashMap<(String, String), usize>,
+        start_times: HashMap<(String, String), Instant>,
+        capacity: Option<CapacityLimit>,
+    ) -> (Vec<Command>, StatusInfo) {
+        let component_id = &self.spread_config.component_id;
+        let skew = component
+            .as_ref()
+            .and_then(|component| version_skew(component.running_references()));
 
         let ineligible_hosts = compute_ineligible_hosts(
             &hosts,
@@ -165,14 +377,14 @@ impl<S: ReadStore + Send + Sync + Clone> Scaler for ComponentSpreadScaler<S> {
             let status = StatusInfo::reconciling(
                 "Found components running on ineligible hosts, removing them.",
             );
-            trace!(?status, "Updating scaler status");
-            *self.status.write().await = status;
-            return Ok(remove_ineligible);
+            return (remove_ineligible, status);
         }
 
         let mut spread_status = vec![];
+        let mut spread_readiness = vec![];
         trace!(spread_requirements = ?self.spread_requirements, ?component_id, "Computing commands");
         let mut component_instances_per_eligible_host: HashMap<&String, usize> = HashMap::new();
+        let mut placement_limited = false;
         let commands = self
             .spread_requirements
             .iter()
@@ -187,22 +399,15 @@ impl<S: ReadStore + Send + Sync + Clone> Scaler for ComponentSpreadScaler<S> {
                     // by this scaler. Ignoring ones where we aren't running anything
                     let running_components_per_host: HashMap<&String, usize> = component
                         .as_ref()
-                        .map(|component| &component.instances)
-                        .map(|instances| {
-                            instances
+                        .map(|component| {
+                            component.instances
                                 .iter()
                                 .filter_map(|(host_id, instances)| {
                                     let count = instances
                                         .iter()
                                         .filter_map(|info| {
-                                            spreadscaler_annotations(&spread.name, self.id()).iter().all(
-                                                |(key, value)| {
-                                                    info.annotations
-                                                        .get(key)
-                                                        .map(|v| v == value)
-                                                        .unwrap_or(false)
-                                                },
-                                            ).then_some(info.count)
+                                            self.manages_instance(component, host_id, &spread.name, info)
+                                                .then_some(info.count)
                                         })
                                         .sum();
                                     (count > 0).then_some((host_id, count))
@@ -219,6 +424,66 @@ impl<S: ReadStore + Send + Sync + Clone> Scaler for ComponentSpreadScaler<S> {
 
                     let current_count: usize = running_components_per_host.values().sum();
                     trace!(current = %current_count, expected = %count, "Calculated running components, reconciling with expected count");
+                    let within_tolerance = self.within_tolerance(current_count, *count);
+                    spread_readiness.push(format!(
+                        "spread '{}' {} {current_count}/{count}",
+                        spread.name,
+                        if within_tolerance { "ready" } else { "compensating" }
+                    ));
+                    if current_count != *count && within_tolerance {
+                        trace!(tolerance = %self.spread_config.count_tolerance, "Running components are within tolerance, skipping");
+                        return None;
+                    }
+
+                    let anti_affinity = &self.spread_config.spread_config.anti_affinity;
+                    if !anti_affinity.is_empty() {
+                        let (desired, placed) = anti_affinity_placement(anti_affinity, *count, &eligible_hosts, &running_components_per_host);
+                        if placed < *count {
+                            placement_limited = true;
+                            spread_status.push(StatusInfo::failed(&format!("Could not place all instances of spread {} for {} with anti-affinity on {}, only {placed}/{count} distinct label groups found.", spread.name, self.spread_config.component_reference, anti_affinity.join(", "))));
+                        }
+                        let commands = self.absolute_commands(spread, desired, &running_components_per_host);
+                        return (!commands.is_empty()).then_some(commands);
+                    }
+
+                    if let Some(topology_key) = &self.spread_config.spread_config.topology_key {
+                        let (desired, domains) = topology_placement(topology_key, *count, &eligible_hosts, &running_components_per_host);
+                        if domains == 0 {
+                            spread_status.push(StatusInfo::failed(&format!("Could not satisfy spread {} for {}, no eligible hosts have the topology label '{topology_key}'.", spread.name, self.spread_config.component_reference)));
+                            return None;
+                        }
+                        // Instances can still run, but losing the single domain takes all of them
+                        // down with it
+                        if domains < 2 && *count > 1 {
+                            spread_status.push(StatusInfo::failed(&format!("Could not spread {} across failure domains, only 1 distinct value found for topology label '{topology_key}'.", spread.name)));
+                        }
+                        let commands = self.absolute_commands(spread, desired, &running_components_per_host);
+                        return (!commands.is_empty()).then_some(commands);
+                    }
+
+                    if let Some(weight_key) = &self.spread_config.spread_config.weight_key {
+                        let desired = weighted_placement(weight_key, *count, &eligible_hosts, &running_components_per_host);
+                        let commands = self.absolute_commands(spread, desired, &running_components_per_host);
+                        return (!commands.is_empty()).then_some(commands);
+                    }
+
+                    // When capacity is shared between models, placement is limited to this model's
+                    // share of each host. Scaling down works the same either way
+                    if let Some(capacity) = capacity.filter(|_| current_count <= *count) {
+                        let (desired, placed) = capacity_placement(capacity, *count, &eligible_hosts, &running_components_per_host);
+                        if placed < *count {
+                            placement_limited = true;
+                            spread_status.push(StatusInfo::failed(&format!("Could not place all instances of spread {} for {}, only {placed}/{count} fit in this model's share of host capacity.", spread.name, self.spread_config.component_reference)));
+                        }
+                        let commands = self.absolute_commands(spread, desired, &running_components_per_host);
+                        return (!commands.is_empty()).then_some(commands);
+                    }
+
+                    if self.spread_config.declarative {
+                        let commands = self.declarative_commands(spread, *count, &eligible_hosts, &running_components_per_host);
+                        return (!commands.is_empty()).then_some(commands);
+                    }
+
                     // Here we'll generate commands for the proper host depending on where they are running
                     match current_count.cmp(count) {
                         Ordering::Equal => None,
@@ -232,7 +497,7 @@ impl<S: ReadStore + Send + Sync + Clone> Scaler for ComponentSpreadScaler<S> {
                                 host_id: eligible_hosts.keys().next().unwrap().to_string(),
                                 count: *count as u32,
                                 model_name: self.spread_config.model_name.to_owned(),
-                                annotations: spreadscaler_annotations(&spread.name, self.id()),
+                                annotations: spreadscaler_annotations(&spread.name, &self.id),
                                         config: self.config.clone(),
                             })])
                         }
@@ -240,28 +505,44 @@ impl<S: ReadStore + Send + Sync + Clone> Scaler for ComponentSpreadScaler<S> {
                         Ordering::Greater => {
                             // Components across all available hosts that exceed our desired number
                             let count_to_stop = current_count - count;
-                            let (_, commands) = running_components_per_host.into_iter().fold((0usize, Vec::new()), |(mut current_stopped, mut commands), (host_id, instance_count)| {
-                                let remaining_to_stop = count_to_stop - current_stopped;
-                                // Desired count on the host, subtracting the number we need to stop
-                                // from the total number of instances on the host, down to 0.
-                                let count = instance_count.saturating_sub(remaining_to_stop);
-                                // If there aren't any on here then we don't need a command to stop
-                                if instance_count > 0 {
-                                    // Keep track of how many we've stopped, which will be the smaller of the current
-                                    // instance count or the number we need to stop
-                                    current_stopped += std::cmp::min(instance_count, remaining_to_stop);
-                                    commands.push(Command::ScaleComponent(ScaleComponent {
+                            let running = running_components_per_host
+                                .into_iter()
+                                .map(|(host_id, instances)| {
+                                    let key = (host_id.to_owned(), spread.name.to_owned());
+                                    RunningOnHost {
+                                        host_id,
+                                        instances,
+                                        // Instances started within the cooldown can't be stopped
+                                        protected: protected_instances
+                                            .get(&key)
+                                            .copied()
+                                            .unwrap_or_default(),
+                                        started_at: start_times.get(&key).copied(),
+                                    }
+                                })
+                                .collect();
+                            let policy = self.spread_config.scale_down_policy;
+                            let commands = plan_scale_down(policy, running, count_to_stop)
+                                .into_iter()
+                                // If there aren't any on here (or they're all protected) then we
+                                // don't need a command to stop
+                                .filter(|(host, stopping)| {
+                                    host.instances > 0 && (*stopping > 0 || host.protected == 0)
+                                })
+                                .map(|(host, stopping)| {
+                                    Command::ScaleComponent(ScaleComponent {
                                         component_id: component_id.to_owned(),
                                         reference: self.spread_config.component_reference.to_owned(),
-                                        host_id: host_id.to_owned(),
-                                        count: count as u32,
+                                        host_id: host.host_id.to_owned(),
+                                        // Desired count on the host, subtracting the number we need
+                                        // to stop from the total number of instances on the host
+                                        count: (host.instances - stopping) as u32,
                                         model_name: self.spread_config.model_name.to_owned(),
-                                        annotations: spreadscaler_annotations(&spread.name, self.id()),
+                                        annotations: spreadscaler_annotations(&spread.name, &self.id),
                                         config: self.config.clone(),
-                                    }));
-                                }
-                                (current_stopped, commands)
-                            });
+                                    })
+                                })
+                                .collect();
                             Some(commands)
                         }
                     }
@@ -276,228 +557,1125 @@ impl<S: ReadStore + Send + Sync + Clone> Scaler for ComponentSpreadScaler<S> {
             .collect::<Vec<Command>>();
         trace!(?commands, "Calculated commands for component scaler");
 
-        // Detect spread requirement conflicts
+        // Detect spread requirement conflicts. Falling short on capacity or anti-affinity is
+        // reported separately
         if let Some(message) = detect_spread_requirement_conflicts(
             &self.spread_requirements,
             &hosts,
             &component_instances_per_eligible_host,
             &commands,
-        ) {
-            let status = StatusInfo::failed(&message);
-            trace!(?status, "Updating scaler status");
-            *self.status.write().await = status;
-            return Ok(vec![]);
+            |projected, target| self.within_tolerance(projected, target),
+        )
+        .filter(|_| !placement_limited)
+        {
+            return (vec![], StatusInfo::failed(&message));
+        }
+
+        // Once every spread has the right number of instances, any of them still running an old
+        // reference are updated in place rather than being stopped and started again
+        let updates = component
+            .as_ref()
+            .filter(|_| commands.is_empty() && spread_status.is_empty())
+            .map(|component| self.update_commands(component))
+            .unwrap_or_default();
+        if !updates.is_empty() {
+            let status = StatusInfo::reconciling(&format!(
+                "Updating component to {} on {} host(s)",
+                self.spread_config.component_reference,
+                updates.len()
+            ));
+            return (updates, status);
         }
 
+        // Only report per spread readiness when there are multiple spreads, otherwise the overall
+        // status already says everything there is to know
+        let readiness = if self.spread_requirements.len() > 1 {
+            spread_readiness.join(", ")
+        } else {
+            String::new()
+        };
         let status = match (spread_status.is_empty(), commands.is_empty()) {
             // No failures, no commands, scaler satisfied
-            (true, true) => StatusInfo::deployed(""),
+            (true, true) => StatusInfo::deployed(&readiness),
             // No failures, commands generated, scaler is reconciling
-            (true, false) => {
-                StatusInfo::reconciling(&format!("Scaling component on {} host(s)", commands.len()))
-            }
+            (true, false) => StatusInfo::reconciling(&join_status_message(
+                format!("Scaling component on {} host(s)", commands.len()),
+                &readiness,
+            )),
             // Failures occurred, scaler is in a failed state
-            (false, _) => StatusInfo::failed(
-                &spread_status
+            (false, _) => StatusInfo::failed(&join_status_message(
+                spread_status
                     .into_iter()
                     .map(|s| s.message)
                     .collect::<Vec<String>>()
                     .join(" "),
-            ),
+                &readiness,
+            )),
         };
+        let status = apply_version_skew(status, skew, self.spread_config.uniform_versions);
+        let status = apply_max_count(status, &self.spread_config.spread_config);
 
-        trace!(?status, "Updating scaler status");
-        *self.status.write().await = status;
+        (commands, status)
+    }
 
-        Ok(commands)
+    /// Returns whether the given number of running instances is close enough to the desired count
+    /// that nothing needs to be done
+    fn within_tolerance(&self, current: usize, desired: usize) -> bool {
+        current == desired
+            || (current > 0
+                && desired > 0
+                && current.abs_diff(desired) <= self.spread_config.count_tolerance)
     }
 
-    #[instrument(level = "trace", skip_all, fields(name = %self.spread_config.model_name))]
-    async fn cleanup(&self) -> Result<Vec<Command>> {
-        let mut config_clone = self.spread_config.clone();
-        config_clone.spread_config.instances = 0;
-        let spread_requirements = compute_spread(&config_clone.spread_config);
+    /// Returns the ID this scaler had before its reference was changed to the one it is configured
+    /// with, if the given host is still running a different reference of the component
+    fn previous_id(&self, component: &Component, host_id: &str) -> Option<String> {
+        let reference = component
+            .host_references
+            .get(host_id)
+            .unwrap_or(&component.reference);
+        (*reference != self.spread_config.component_reference).then(|| {
+            spreadscaler_id(
+                &self.spread_config.model_name,
+                &self.spread_config.component_name,
+                &self.spread_config.component_id,
+                reference,
+                &self.config,
+            )
+        })
+    }
 
-        let cleanerupper = ComponentSpreadScaler {
-            spread_config: config_clone,
-            store: self.store.clone(),
-            spread_requirements,
-            id: self.id.clone(),
-            status: RwLock::new(StatusInfo::reconciling("")),
-            config: self.config.clone(),
-        };
+    /// Returns whether the given instance of the component on the given host is managed by this
+    /// scaler for the given spread. Instances started by a previous version of this scaler that
+    /// only had a different reference are included while the host still runs that reference, so
+    /// they can be updated in place
+    fn manages_instance(
+        &self,
+        component: &Component,
+        host_id: &str,
+        spread_name: &str,
+        info: &WadmComponentInfo,
+    ) -> bool {
+        annotated_by(info, spread_name, &self.id)
+            || self
+                .previous_id(component, host_id)
+                .is_some_and(|previous| annotated_by(info, spread_name, &previous))
+    }
 
-        cleanerupper.reconcile().await
+    /// Returns how many of the desired instances of each spread are running, out of the total
+    /// number desired. Instances over a spread's count don't make up for another spread falling
+    /// short
+    fn progress(&self, component: Option<&Component>) -> (u32, u32) {
+        self.spread_requirements
+            .iter()
+            .fold((0, 0), |(achieved, desired), (spread, count)| {
+                let running: usize = component
+                    .map(|component| {
+                        component
+                            .instances
+                            .iter()
+                            .flat_map(|(host_id, instances)| {
+                                instances
+                                    .iter()
+                                    .filter(|info| {
+                                        self.manages_instance(
+                                            component,
+                                            host_id,
+                                            &spread.name,
+                                            info,
+                                        )
+                                    })
+                                    .map(|info| info.count)
+                            })
+                            .sum()
+                    })
+                    .unwrap_or_default();
+                (
+                    achieved + running.min(*count) as u32,
+                    desired + *count as u32,
+                )
+            })
+    }
+
+    /// Computes `UpdateComponent` commands for every host running instances started by a previous
+    /// version of this scaler that only had a different reference. The updated instances are
+    /// annotated as belonging to this scaler
+    fn update_commands(&self, component: &Component) -> Vec<Command> {
+        // Sorted by host so the same state always results in the same commands
+        component
+            .instances
+            .iter()
+            .collect::<BTreeMap<_, _>>()
+            .into_iter()
+            .filter_map(|(host_id, instances)| {
+                let previous = self.previous_id(component, host_id)?;
+                let spread = self.spread_requirements.iter().find_map(|(spread, _)| {
+                    instances
+                        .iter()
+                        .any(|info| annotated_by(info, &spread.name, &previous))
+                        .then_some(spread)
+                })?;
+                Some(Command::UpdateComponent(UpdateComponent {
+                    component_id: component.id.to_owned(),
+                    host_id: host_id.to_owned(),
+                    new_reference: self.spread_config.component_reference.to_owned(),
+                    model_name: self.spread_config.model_name.to_owned(),
+                    annotations: spreadscaler_annotations(&spread.name, &self.id),
+                }))
+            })
+            .collect()
+    }
+
+    /// Computes the desired state for a spread as a list of `ScaleComponent` commands with absolute
+    /// counts. Instances are spread evenly across the eligible hosts (see [`even_placement`]) and
+    /// every other host running instances for the spread is scaled to 0. Hosts that already run
+    /// their desired count are skipped.
+    fn declarative_commands(
+        &self,
+        spread: &Spread,
+        count: usize,
+        eligible_hosts: &HashMap<&String, &Host>,
+        running_components_per_host: &HashMap<&String, usize>,
+    ) -> Vec<Command> {
+        let desired = even_placement(count, eligible_hosts, running_components_per_host);
+        self.absolute_commands(spread, desired, running_components_per_host)
+    }
+
+    /// Computes a list of `ScaleComponent` commands with absolute counts that brings every host in
+    /// the desired map to its desired count, skipping hosts that already run it
+    fn absolute_commands(
+        &self,
+        spread: &Spread,
+        desired: BTreeMap<&String, usize>,
+        running_components_per_host: &HashMap<&String, usize>,
+    ) -> Vec<Command> {
+        desired
+            .into_iter()
+            .filter(|(host_id, desired_count)| {
+                running_components_per_host
+                    .get(host_id)
+                    .copied()
+                    .unwrap_or_default()
+                    != *desired_count
+            })
+            .map(|(host_id, desired_count)| {
+                Command::ScaleComponent(ScaleComponent {
+                    component_id: self.spread_config.component_id.to_owned(),
+                    reference: self.spread_config.component_reference.to_owned(),
+                    host_id: host_id.to_owned(),
+                    count: desired_count as u32,
+                    model_name: self.spread_config.model_name.to_owned(),
+                    annotations: spreadscaler_annotations(&spread.name, &self.id),
+                    config: self.config.clone(),
+                })
+            })
+            .collect()
     }
 }
 
 impl<S: ReadStore + Send + Sync> ComponentSpreadScaler<S> {
     /// Construct a new ComponentSpreadScaler with specified configuration values
-    #[allow(clippy::too_many_arguments)]
     pub fn new(
         store: S,
-        component_reference: String,
-        component_id: String,
-        lattice_id: String,
-        model_name: String,
+        config: ComponentScalerConfig,
         spread_config: SpreadScalerProperty,
         component_name: &str,
-        config: Vec<String>,
     ) -> Self {
-        // Compute the id of this scaler based on all of the configuration values
-        // that make it unique. This is used during upgrades to determine if a
-        // scaler is the same as a previous one.
-        let mut id_parts = vec![
-            SPREAD_SCALER_KIND,
+        let ComponentScalerConfig {
+            lattice_id,
+            component_id,
+            component_reference,
+            model_name,
+            component_config: config,
+        } = config;
+        let id = spreadscaler_id(
             &model_name,
             component_name,
             &component_id,
             &component_reference,
-        ];
-        id_parts.extend(config.iter().map(std::string::String::as_str));
-        let id = compute_id_sha256(&id_parts);
+            &config,
+        );
 
         Self {
             store,
-            spread_requirements: compute_spread(&spread_config),
+            spread_requirements: compute_bounded_spread(&spread_config),
             spread_config: ComponentSpreadConfig {
                 component_reference,
                 component_id,
+                component_name: component_name.to_owned(),
                 lattice_id,
                 spread_config,
                 model_name,
+                declarative: false,
+                start_cooldown: Duration::ZERO,
+                uniform_versions: false,
+                scale_down_policy: ScaleDownPolicy::default(),
+                host_quiet_period: Duration::ZERO,
+                count_tolerance: 0,
+                check_capabilities: false,
             },
             id,
             config,
             status: RwLock::new(StatusInfo::reconciling("")),
+            instance_starts: RwLock::default(),
+            reconciled_state: RwLock::default(),
+            compute_pool: None,
+            capacity: None,
         }
     }
-}
 
-/// Helper function to create a predictable annotations map for a spread
-pub(crate) fn spreadscaler_annotations(
-    spread_name: &str,
-    scaler_id: &str,
-) -> BTreeMap<String, String> {
-    BTreeMap::from_iter([
-        (SCALER_KEY.to_string(), scaler_id.to_string()),
-        (SPREAD_KEY.to_string(), spread_name.to_string()),
-    ])
-}
+    /// Configures this scaler to compute its commands on the given pool rather than inline on the
+    /// async runtime, which keeps reconciles for large lattices from holding up event processing
+    pub fn with_compute_pool(mut self, pool: Option<ComputePool>) -> Self {
+        self.compute_pool = pool;
+        self
+    }
 
-/// Helper function that computes a list of eligible hosts to match with a spread
-pub(crate) fn eligible_hosts<'a>(
-    all_hosts: &'a HashMap<String, Host>,
-    spread: &Spread,
-) -> HashMap<&'a String, &'a Host> {
-    all_hosts
-        .iter()
-        .filter(|(_id, host)| {
-            spread
-                .requirements
-                .iter()
-                .all(|(key, value)| host.labels.get(key).map(|v| v.eq(value)).unwrap_or(false))
-        })
-        .collect()
-}
+    /// Configures this scaler to claim its instances from the given host capacity, which is shared
+    /// fairly with the scalers of other models. Without it, placement isn't limited by capacity
+    pub fn with_host_capacity(mut self, capacity: Option<&HostCapacity>) -> Self {
+        self.capacity = capacity.map(|capacity| {
+            capacity.claim(
+                &self.spread_config.model_name,
+                self.spread_config.spread_config.instances,
+            )
+        });
+        self
+    }
 
-/// Helper function that computes a list of ineligible hosts that match none of the spread requirements
-pub(crate) fn compute_ineligible_hosts<'a>(
-    all_hosts: &'a HashMap<String, Host>,
-    spreads: Vec<&Spread>,
-) -> HashMap<&'a String, &'a Host> {
-    // Find all host IDs that are eligible for any spread
-    let eligible_ids = spreads
-        .iter()
-        .flat_map(|spread| eligible_hosts(all_hosts, spread).into_keys())
-        .collect::<HashSet<_>>();
+    /// Configures a cooldown after instances are started on a host. Instances started within the
+    /// cooldown are never chosen when scaling down, which gives the lattice state time to settle
+    /// and keeps a transient miscount from immediately stopping what was just started. The
+    /// cooldown is disabled by default and does not apply to declarative commands.
+    pub fn with_start_cooldown(mut self, cooldown: Duration) -> Self {
+        self.spread_config.start_cooldown = cooldown;
+        self
+    }
 
-    // Filter out all hosts that are eligible for any spread, leaving only ineligible hosts
-    all_hosts
-        .iter()
-        .filter(|(id, _)| !eligible_ids.contains(id))
-        .collect::<HashMap<_, _>>()
-}
+    /// Configures whether running more than one version of the component at the same time (e.g.
+    /// partway through a rollout) marks the scaler as failed. Version skew is always reported in
+    /// the status message, this only changes whether it is treated as a failure
+    pub fn with_uniform_versions(mut self, required: bool) -> Self {
+        self.spread_config.uniform_versions = required;
+        self
+    }
 
-/// Given a spread config, return a vector of tuples that represents the spread
-/// and the actual number of components to start for a specific spread requirement
-fn compute_spread(spread_config: &SpreadScalerProperty) -> Vec<(Spread, usize)> {
-    let requested_instances = spread_config.instances;
-    let mut requested_spreads = spread_config.spread.clone();
-    requested_spreads.sort_by_key(|s| Reverse(s.weight.unwrap_or(DEFAULT_SPREAD_WEIGHT)));
+    /// Configures how instances are chosen to be stopped when scaling down. Defaults to
+    /// [`ScaleDownPolicy::Any`]
+    pub fn with_scale_down_policy(mut self, policy: ScaleDownPolicy) -> Self {
+        self.spread_config.scale_down_policy = policy;
+        self
+    }
 
-    let total_weight = requested_spreads
-        .iter()
-        .map(|s| s.weight.unwrap_or(DEFAULT_SPREAD_WEIGHT))
-        .sum::<usize>();
-    let computed_spreads: Vec<(Spread, usize)> = requested_spreads
-        .iter()
-        .map(|s| {
-            (
-                s.to_owned(),
-                // Order is important here since usizes chop off remaining decimals
-                (requested_instances * s.weight.unwrap_or(DEFAULT_SPREAD_WEIGHT)) / total_weight,
-            )
-        })
-        .collect();
+    /// Configures how long after a host starts before new instances can be placed on it, giving
+    /// the host time to finish starting up. Disabled by default
+    pub fn with_host_quiet_period(mut self, quiet_period: Duration) -> Self {
+        self.spread_config.host_quiet_period = quiet_period;
+        self
+    }
 
-    let computed_spreads = if computed_spreads.is_empty() {
-        vec![(Spread::default(), requested_instances)]
-    } else {
-        computed_spreads
-    };
+    /// Configures how many instances a spread can be over or under its desired count before this
+    /// scaler issues commands to correct it, so momentary miscounts while instances come and go
+    /// don't cause extra commands. A spread inside the band is reported as deployed. The tolerance
+    /// never keeps a spread from starting its first instance or from scaling to zero, and is 0
+    /// (disabled) by default
+    pub fn with_count_tolerance(mut self, tolerance: usize) -> Self {
+        self.spread_config.count_tolerance = tolerance;
+        self
+    }
 
-    // Because of math, we may end up rounding a few instances away. Evenly distribute them
-    // among the remaining hosts
-    let computed_instances = computed_spreads
-        .iter()
-        .map(|(_, count)| count)
-        .sum::<usize>();
-    let computed_spreads = match computed_instances.cmp(&requested_instances) {
-        // To meet the specified/requested number of instances, evenly distribute the remaining unassigned instances among the computed spreads
-        Ordering::Less => {
-            let mut diff = requested_instances - computed_instances;
-            computed_spreads
-                .into_iter()
-                .map(|(spread, count)| {
-                    let additional = if diff > 0 {
-                        diff -= 1;
-                        1
-                    } else {
-                        0
-                    };
-                    (spread, count + additional)
-                })
-                .collect()
+    /// Configures whether new instances are only placed on hosts running providers for every
+    /// capability the component's claims require. If no host provides them all, the scaler is
+    /// failed and nothing is placed. Capabilities are only known once the component has run in the
+    /// lattice, so this has no effect before then. Disabled by default
+    pub fn with_capability_check(mut self, check: bool) -> Self {
+        self.spread_config.check_capabilities = check;
+        self
+    }
+
+    /// Records the number of instances running on a host for a spread as reported by an instance
+    /// event, tracking when the count last went up
+    async fn record_instance_count(&self, host_id: &str, spread_name: &str, count: usize) {
+        let key = (host_id.to_owned(), spread_name.to_owned());
+        let mut starts = self.instance_starts.write().await;
+        match starts.get_mut(&key) {
+            _ if count == 0 => {
+                starts.remove(&key);
+            }
+            Some(start) if count > start.count => {
+                *start = InstanceStart {
+                    previous_count: start.count,
+                    count,
+                    started_at: Instant::now(),
+                };
+            }
+            Some(start) => start.count = count,
+            None => {
+                starts.insert(
+                    key,
+                    InstanceStart {
+                        previous_count: 0,
+                        count,
+                        started_at: Instant::now(),
+                    },
+                );
+            }
         }
-        // This isn't possible (usizes round down) but I added an arm _just in case_
-        // there was a case that I didn't imagine
-        Ordering::Greater => {
-            warn!("Requesting more component instances than were specified");
-            computed_spreads
+    }
+
+    /// Returns the number of instances on each host and spread that are still within the start
+    /// cooldown
+    async fn protected_instances(&self) -> HashMap<(String, String), usize> {
+        let cooldown = self.spread_config.start_cooldown;
+        if cooldown.is_zero() {
+            return HashMap::new();
         }
-        Ordering::Equal => computed_spreads,
-    };
+        self.instance_starts
+            .read()
+            .await
+            .iter()
+            .filter(|(_, start)| start.started_at.elapsed() < cooldown)
+            .map(|(key, start)| {
+                (
+                    key.clone(),
+                    start.count.saturating_sub(start.previous_count),
+                )
+            })
+            .collect()
+    }
 
-    // If there are no spreads specified, we should only have one (default) spread with the total number of instances
-    if requested_spreads.is_empty() && computed_spreads.len() == 1 {
-        return computed_spreads;
+    /// Forces the next reconcile to recompute its commands, even if the observed state is the same
+    async fn invalidate_reconciled_state(&self) {
+        self.reconciled_state.write().await.take();
     }
 
-    // this is an invariant check; the predicate should not be true.
-    if computed_spreads.len() != requested_spreads.len() {
-        eprintln!("Computed spreads and requested spreads are not the same length. This should not happen.");
+    /// Returns when instances were last started on each host and spread, as seen by this scaler
+    async fn start_times(&self) -> HashMap<(String, String), Instant> {
+        self.instance_starts
+            .read()
+            .await
+            .iter()
+            .map(|(key, start)| (key.clone(), start.started_at))
+            .collect()
     }
 
-    // The output has spreads ordered by weight; we may want to return them per the order in the config file at some point. For now, this will do.
-    computed_spreads
+    /// Configures this scaler to emit declarative commands. In this mode, every `ScaleComponent`
+    /// command carries the absolute number of instances that should be running on a host for a
+    /// spread, computed from the scaler configuration, instead of being derived from the number
+    /// of instances that need to be started or stopped. This makes each reconcile robust to
+    /// missed events, as the host is always told exactly what should be running.
+    pub fn with_declarative_commands(mut self, declarative: bool) -> Self {
+        self.spread_config.declarative = declarative;
+        self
+    }
 }
 
-fn detect_spread_requirement_conflicts(
-    spread_requirements: &[(Spread, usize)],
+/// Helper function that appends per spread readiness to a status message, if there is any
+fn join_status_message(message: String, readiness: &str) -> String {
+    if readiness.is_empty() {
+        message
+    } else {
+        format!("{message}. {readiness}")
+    }
+}
+
+/// Hashes everything a [`ComponentSpreadScaler`] reconciles against for the given component. The
+/// hosts should only be the ones that can currently be placed on, so a host becoming eligible,
+/// like at the end of its quiet period, counts as a change. Maps are sorted first so the same
+/// state always gives the same fingerprint. Host heartbeat data that doesn't affect scaling, like
+/// the last seen time, is left out so that heartbeats alone don't count as a change
+fn state_fingerprint(
+    component_id: &str,
+    component: Option<&Component>,
     hosts: &HashMap<String, Host>,
-    running_instances_per_host: &HashMap<&String, usize>,
+    protected_instances: &HashMap<(String, String), usize>,
+    start_times: &HashMap<(String, String), Instant>,
+    capacity: Option<CapacityLimit>,
+) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    hosts
+        .iter()
+        .map(|(id, host)| {
+            (
+                id,
+                (
+                    host.labels.iter().collect::<BTreeMap<_, _>>(),
+                    host.components.get(component_id),
+                ),
+            )
+        })
+        .collect::<BTreeMap<_, _>>()
+        .hash(&mut hasher);
+    if let Some(component) = component {
+        component
+            .instances
+            .iter()
+            .map(|(host_id, instances)| {
+                let mut instances = instances
+                    .iter()
+                    .map(|info| (&info.annotations, info.count))
+                    .collect::<Vec<_>>();
+                instances.sort();
+                (host_id, instances)
+            })
+            .collect::<BTreeMap<_, _>>()
+            .hash(&mut hasher);
+        component
+            .host_references
+            .iter()
+            .collect::<BTreeMap<_, _>>()
+            .hash(&mut hasher);
+    }
+    protected_instances
+        .iter()
+        .collect::<BTreeMap<_, _>>()
+        .hash(&mut hasher);
+    start_times
+        .iter()
+        .collect::<BTreeMap<_, _>>()
+        .hash(&mut hasher);
+    // Free capacity depends on everything running on a host, not just this component
+    if let Some(capacity) = capacity {
+        capacity.hash(&mut hasher);
+        hosts
+            .iter()
+            .map(|(id, host)| (id, host.components.values().sum::<usize>()))
+            .collect::<BTreeMap<_, _>>()
+            .hash(&mut hasher);
+    }
+    hasher.finish()
+}
+
+/// Computes how many instances of a spread should run on each eligible host when host capacity is
+/// shared between models, returning the desired count for each host along with the total placed.
+/// Running instances stay where they are, unless they are over this model's share of a full host,
+/// in which case they are scaled back to the share so other models can use the space. Anything
+/// left is placed on hosts with room, in host ID order
+fn capacity_placement<'a>(
+    capacity: CapacityLimit,
+    count: usize,
+    eligible_hosts: &HashMap<&'a String, &'a Host>,
+    running_components_per_host: &HashMap<&String, usize>,
+) -> (BTreeMap<&'a String, usize>, usize) {
+    let mut desired = BTreeMap::new();
+    let mut room = BTreeMap::new();
+    for (host_id, host) in eligible_hosts {
+        let running = running_components_per_host
+            .get(host_id)
+            .copied()
+            .unwrap_or_default();
+        let free = capacity
+            .per_host
+            .saturating_sub(host.components.values().sum());
+        let keep = if free == 0 {
+            running.min(capacity.share)
+        } else {
+            running
+        };
+        desired.insert(*host_id, keep);
+        room.insert(
+            *host_id,
+            capacity.share.min(running + free).saturating_sub(keep),
+        );
+    }
+
+    let mut remaining = count.saturating_sub(desired.values().sum());
+    for (host_id, room) in room {
+        let placing = remaining.min(room);
+        *desired.entry(host_id).or_default() += placing;
+        remaining -= placing;
+    }
+    (desired, count - remaining)
+}
+
+/// Computes how many instances of a spread should run on each eligible host so that no two
+/// instances run on hosts with the same value for any of the given label keys, returning the
+/// desired count for each host along with the total placed. Every instance gets its own host. Hosts
+/// already running instances are picked first, then the rest in host ID order. Hosts without one of
+/// the labels never conflict on it. Any other hosts running instances are scaled to 0
+fn anti_affinity_placement<'a>(
+    keys: &[String],
+    count: usize,
+    eligible_hosts: &HashMap<&'a String, &'a Host>,
+    running_components_per_host: &HashMap<&'a String, usize>,
+) -> (BTreeMap<&'a String, usize>, usize) {
+    let mut desired = running_components_per_host
+        .keys()
+        .map(|host_id| (*host_id, 0))
+        .collect::<BTreeMap<&String, usize>>();
+    let mut candidates = eligible_hosts.iter().collect::<Vec<_>>();
+    candidates.sort_by_key(|(host_id, _)| {
+        (
+            Reverse(
+                running_components_per_host
+                    .get(*host_id)
+                    .copied()
+                    .unwrap_or_default(),
+            ),
+            **host_id,
+        )
+    });
+
+    let mut used: HashSet<(&String, &String)> = HashSet::new();
+    let mut placed = 0;
+    for (host_id, host) in candidates {
+        if placed == count {
+            break;
+        }
+        let values = keys
+            .iter()
+            .filter_map(|key| host.labels.get(key).map(|value| (key, value)))
+            .collect::<Vec<_>>();
+        if values.iter().any(|value| used.contains(value)) {
+            continue;
+        }
+        used.extend(values);
+        desired.insert(*host_id, 1);
+        placed += 1;
+    }
+    (desired, placed)
+}
+
+/// Computes how many instances of a spread should run on each eligible host so that each host's
+/// share is proportional to the integer weight in its `weight_key` label. Hosts without the label,
+/// or with a value that isn't an integer, have a weight of 1. If every weight is 0, all hosts are
+/// weighted equally. Shares are rounded down and the instances left over go to the hosts with the
+/// largest remainders, breaking ties by host ID, so the counts always add up to `count`. Any other
+/// hosts running instances are scaled to 0
+fn weighted_placement<'a>(
+    weight_key: &str,
+    count: usize,
+    eligible_hosts: &HashMap<&'a String, &'a Host>,
+    running_components_per_host: &HashMap<&'a String, usize>,
+) -> BTreeMap<&'a String, usize> {
+    let mut weights = eligible_hosts
+        .iter()
+        .map(|(host_id, host)| {
+            let weight = host
+                .labels
+                .get(weight_key)
+                .and_then(|value| value.trim().parse::<u64>().ok())
+                .unwrap_or(1);
+            (*host_id, weight)
+        })
+        .collect::<BTreeMap<&String, u64>>();
+    if weights.values().all(|weight| *weight == 0) {
+        weights.values_mut().for_each(|weight| *weight = 1);
+    }
+    // Widened so large weights and counts can't overflow
+    let total = weights
+        .values()
+        .map(|weight| u128::from(*weight))
+        .sum::<u128>();
+
+    let mut desired = running_components_per_host
+        .keys()
+        .map(|host_id| (*host_id, 0))
+        .collect::<BTreeMap<&String, usize>>();
+    let mut remainders = Vec::with_capacity(weights.len());
+    let mut assigned = 0;
+    for (host_id, weight) in weights {
+        let quota = count as u128 * u128::from(weight);
+        let share = (quota / total) as usize;
+        desired.insert(host_id, share);
+        remainders.push((quota % total, host_id));
+        assigned += share;
+    }
+    remainders.sort_by_key(|(remainder, host_id)| (Reverse(*remainder), *host_id));
+    for (_, host_id) in remainders.into_iter().take(count - assigned) {
+        *desired.entry(host_id).or_default() += 1;
+    }
+    desired
+}
+
+/// Computes how many instances of a spread should run on each host to distribute them evenly across
+/// the eligible hosts. Any remainder goes to the hosts already running the most instances for the
+/// spread, then to the hosts that sort first, so instances aren't moved around needlessly. Any other
+/// hosts running instances are scaled to 0
+fn even_placement<'a>(
+    count: usize,
+    eligible_hosts: &HashMap<&'a String, &'a Host>,
+    running_components_per_host: &HashMap<&'a String, usize>,
+) -> BTreeMap<&'a String, usize> {
+    let mut hosts = eligible_hosts.keys().copied().collect::<Vec<_>>();
+    hosts.sort_by_key(|host_id| {
+        (
+            Reverse(
+                running_components_per_host
+                    .get(host_id)
+                    .copied()
+                    .unwrap_or_default(),
+            ),
+            *host_id,
+        )
+    });
+    let mut desired = running_components_per_host
+        .keys()
+        .map(|host_id| (*host_id, 0))
+        .collect::<BTreeMap<&String, usize>>();
+    // An empty list of eligible hosts is handled by the caller
+    let share = count / hosts.len().max(1);
+    let remainder = count % hosts.len().max(1);
+    for (idx, host_id) in hosts.into_iter().enumerate() {
+        desired.insert(host_id, share + usize::from(idx < remainder));
+    }
+    desired
+}
+
+/// Computes how many instances of a spread should run on each host to distribute them evenly across
+/// the distinct values of the given topology label, returning the desired count for each host along
+/// with the number of distinct values found. Each value gets an even share of the instances, with
+/// any remainder going to the values that sort first, so every value runs an instance before any
+/// value runs two. Within a value, the share is placed on a single host, preferring the host that
+/// already runs the most instances for the spread. Hosts without the label, and any other hosts
+/// running instances, are scaled to 0
+fn topology_placement<'a>(
+    topology_key: &str,
+    count: usize,
+    eligible_hosts: &HashMap<&'a String, &'a Host>,
+    running_components_per_host: &HashMap<&'a String, usize>,
+) -> (BTreeMap<&'a String, usize>, usize) {
+    let mut domains: BTreeMap<&String, Vec<&String>> = BTreeMap::new();
+    for (host_id, host) in eligible_hosts {
+        if let Some(value) = host.labels.get(topology_key) {
+            domains.entry(value).or_default().push(host_id);
+        }
+    }
+
+    let mut desired = running_components_per_host
+        .keys()
+        .map(|host_id| (*host_id, 0))
+        .collect::<BTreeMap<&String, usize>>();
+    let num_domains = domains.len();
+    for (idx, hosts) in domains.into_values().enumerate() {
+        let share = count / num_domains + usize::from(idx < count % num_domains);
+        if share == 0 {
+            continue;
+        }
+        let target_host = hosts
+            .into_iter()
+            .max_by_key(|host_id| {
+                (
+                    running_components_per_host
+                        .get(host_id)
+                        .copied()
+                        .unwrap_or_default(),
+                    Reverse(*host_id),
+                )
+            })
+            // Every domain has at least one host
+            .unwrap();
+        desired.insert(target_host, share);
+    }
+    (desired, num_domains)
+}
+
+/// How a [`ComponentSpreadScaler`] chooses which instances to stop when more are running than
+/// required. Start times are only known for instances this scaler has seen start, any others are
+/// treated as older than all of them
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ScaleDownPolicy {
+    /// Stop instances on whichever hosts are found first
+    #[default]
+    Any,
+    /// Stop instances on the hosts where instances were most recently started first
+    Newest,
+    /// Stop instances on the hosts where instances have been running the longest first
+    Oldest,
+    /// Stop instances on the hosts running the most instances first, evening out the number of
+    /// instances running on each host
+    EvenHosts,
+}
+
+impl FromStr for ScaleDownPolicy {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "any" => Ok(ScaleDownPolicy::Any),
+            "newest" => Ok(ScaleDownPolicy::Newest),
+            "oldest" => Ok(ScaleDownPolicy::Oldest),
+            "even" | "even-hosts" => Ok(ScaleDownPolicy::EvenHosts),
+            _ => anyhow::bail!(
+                "unknown scale down policy '{s}', expected one of any, newest, oldest or even-hosts"
+            ),
+        }
+    }
+}
+
+/// The instances of a spread running on a host, used to plan a scale down
+#[derive(Debug)]
+struct RunningOnHost<'a> {
+    host_id: &'a String,
+    instances: usize,
+    /// The number of instances that can't be stopped
+    protected: usize,
+    /// When instances were last started on the host, if known
+    started_at: Option<Instant>,
+}
+
+impl RunningOnHost<'_> {
+    fn stoppable(&self) -> usize {
+        self.instances.saturating_sub(self.protected)
+    }
+}
+
+/// Chooses how many instances to stop on each host according to the given policy, returning every
+/// host along with the number of instances to stop on it
+fn plan_scale_down(
+    policy: ScaleDownPolicy,
+    mut hosts: Vec<RunningOnHost<'_>>,
+    count_to_stop: usize,
+) -> Vec<(RunningOnHost<'_>, usize)> {
+    match policy {
+        ScaleDownPolicy::Any => (),
+        ScaleDownPolicy::Newest => hosts.sort_by(|a, b| {
+            b.started_at
+                .cmp(&a.started_at)
+                .then_with(|| a.host_id.cmp(b.host_id))
+        }),
+        ScaleDownPolicy::Oldest => hosts.sort_by(|a, b| {
+            a.started_at
+                .cmp(&b.started_at)
+                .then_with(|| a.host_id.cmp(b.host_id))
+        }),
+        ScaleDownPolicy::EvenHosts => {
+            hosts.sort_by(|a, b| a.host_id.cmp(b.host_id));
+            let mut stops = vec![0; hosts.len()];
+            for _ in 0..count_to_stop {
+                // Stop one at a time from whichever host has the most left running
+                let Some(idx) = (0..hosts.len())
+                    .filter(|idx| stops[*idx] < hosts[*idx].stoppable())
+                    .min_by_key(|idx| Reverse(hosts[*idx].instances - stops[*idx]))
+                else {
+                    break;
+                };
+                stops[idx] += 1;
+            }
+            return hosts.into_iter().zip(stops).collect();
+        }
+    }
+
+    let mut remaining = count_to_stop;
+    hosts
+        .into_iter()
+        .map(|host| {
+            let stopping = std::cmp::min(host.stoppable(), remaining);
+            remaining -= stopping;
+            (host, stopping)
+        })
+        .collect()
+}
+
+/// Returns a status message describing the version skew if more than one distinct reference is
+/// running, e.g. `Version skew: running 0.1.0 and 0.2.0`. Versions are taken from the tag or digest
+/// of each reference, falling back to the full references if that isn't enough to tell them apart
+pub(crate) fn version_skew<'a>(references: impl IntoIterator<Item = &'a str>) -> Option<String> {
+    let references = references
+        .into_iter()
+        .filter(|reference| !reference.is_empty())
+        .collect::<BTreeSet<_>>();
+    if references.len() < 2 {
+        return None;
+    }
+    let versions = references
+        .iter()
+        .map(|reference| reference_version(reference))
+        .collect::<BTreeSet<_>>();
+    let running = if versions.len() == references.len() {
+        versions
+    } else {
+        references
+    }
+    .into_iter()
+    .collect::<Vec<_>>();
+    let (last, rest) = running.split_last()?;
+    Some(format!(
+        "Version skew: running {} and {last}",
+        rest.join(", ")
+    ))
+}
+
+/// Returns the tag or digest of an image reference, or the whole reference if it has neither
+fn reference_version(reference: &str) -> &str {
+    let name = reference.rsplit('/').next().unwrap_or(reference);
+    name.split_once('@')
+        .or_else(|| name.rsplit_once(':'))
+        .map(|(_, version)| version)
+        .unwrap_or(reference)
+}
+
+/// Adds any version skew to the given status, failing the status if uniform versions are required
+pub(crate) fn apply_version_skew(
+    mut status: StatusInfo,
+    skew: Option<String>,
+    uniform_versions: bool,
+) -> StatusInfo {
+    match skew {
+        Some(skew) if uniform_versions => StatusInfo::failed(&skew),
+        Some(skew) => {
+            status.message = if status.message.is_empty() {
+                skew
+            } else {
+                format!("{}. {skew}", status.message)
+            };
+            status
+        }
+        None => status,
+    }
+}
+
+/// Notes in the status when the requested number of instances is over `max_count`. A scaler that
+/// is otherwise deployed is reported as unhealthy, since it isn't running what was requested
+fn apply_max_count(status: StatusInfo, spread_config: &SpreadScalerProperty) -> StatusInfo {
+    let bounded = spread_config.bounded_instances();
+    if spread_config.instances <= bounded {
+        return status;
+    }
+    let message = join_status_message(
+        format!(
+            "Requested {} instances but max_count limits it to {bounded}",
+            spread_config.instances
+        ),
+        &status.message,
+    );
+    match status.status_type {
+        StatusType::Deployed => StatusInfo::unhealthy(&message),
+        status_type => StatusInfo {
+            status_type,
+            message,
+            progress: status.progress,
+        },
+    }
+}
+
+/// Helper function to create a predictable annotations map for a spread
+/// Computes the ID of a spread scaler from all of the configuration values that make it unique.
+/// This is used during upgrades to determine if a scaler is the same as a previous one.
+fn spreadscaler_id(
+    model_name: &str,
+    component_name: &str,
+    component_id: &str,
+    component_reference: &str,
+    config: &[String],
+) -> String {
+    let mut id_parts = vec![
+        SPREAD_SCALER_KIND,
+        model_name,
+        component_name,
+        component_id,
+        component_reference,
+    ];
+    id_parts.extend(config.iter().map(String::as_str));
+    compute_id_sha256(&id_parts)
+}
+
+pub(crate) fn spreadscaler_annotations(
+    spread_name: &str,
+    scaler_id: &str,
+) -> BTreeMap<String, String> {
+    BTreeMap::from_iter([
+        (SCALER_KEY.to_string(), scaler_id.to_string()),
+        (SPREAD_KEY.to_string(), spread_name.to_string()),
+    ])
+}
+
+/// Returns whether the given instance carries the annotations of the given scaler and spread
+fn annotated_by(info: &WadmComponentInfo, spread_name: &str, scaler_id: &str) -> bool {
+    spreadscaler_annotations(spread_name, scaler_id)
+        .iter()
+        .all(|(key, value)| info.annotations.get(key) == Some(value))
+}
+
+/// Helper function that computes a list of eligible hosts to match with a spread
+pub(crate) fn eligible_hosts<'a>(
+    all_hosts: &'a HashMap<String, Host>,
+    spread: &Spread,
+) -> HashMap<&'a String, &'a Host> {
+    all_hosts
+        .iter()
+        .filter(|(_id, host)| {
+            spread
+                .requirements
+                .iter()
+                .all(|(key, value)| host.labels.get(key).map(|v| v.eq(value)).unwrap_or(false))
+        })
+        .collect()
+}
+
+/// Helper function that removes hosts that started less than the quiet period ago, giving them time
+/// to finish starting up before anything is placed on them. Hosts that `is_running` returns true
+/// for are kept so what is already running on them is still accounted for
+pub(crate) fn remove_quiet_hosts(
+    hosts: &mut HashMap<String, Host>,
+    quiet_period: Duration,
+    is_running: impl Fn(&Host) -> bool,
+) {
+    if quiet_period.is_zero() {
+        return;
+    }
+    let quiet_period = chrono::Duration::from_std(quiet_period).unwrap_or(chrono::Duration::MAX);
+    let now = chrono::Utc::now();
+    hosts.retain(|_, host| is_running(host) || now - host.started_at() >= quiet_period);
+}
+
+/// Helper function that removes hosts that aren't running providers for every one of the
+/// `required` capabilities. Hosts that `is_running` returns true for are kept so what is already
+/// running on them is still accounted for. Returns a message explaining which capabilities can't
+/// be satisfied if no host provides all of them
+pub(crate) fn remove_incapable_hosts(
+    hosts: &mut HashMap<String, Host>,
+    required: &[String],
+    providers: &HashMap<String, Provider>,
+    is_running: impl Fn(&Host) -> bool,
+) -> Option<String> {
+    if required.is_empty() {
+        return None;
+    }
+    let host_capabilities = |host: &Host| -> HashSet<&str> {
+        host.providers
+            .iter()
+            .filter_map(|info| providers.get(&info.provider_id))
+            .flat_map(|provider| provider.capabilities.iter().map(String::as_str))
+            .collect()
+    };
+    let capable = |host: &Host| {
+        let capabilities = host_capabilities(host);
+        required
+            .iter()
+            .all(|capability| capabilities.contains(capability.as_str()))
+    };
+    if hosts.values().any(capable) {
+        hosts.retain(|_, host| is_running(host) || capable(host));
+        return None;
+    }
+
+    let provided = hosts
+        .values()
+        .flat_map(host_capabilities)
+        .collect::<HashSet<_>>();
+    let unmet = required
+        .iter()
+        .filter(|capability| !provided.contains(capability.as_str()))
+        .map(String::as_str)
+        .collect::<Vec<_>>();
+    Some(if unmet.is_empty() {
+        format!(
+            "No host satisfies all required capabilities {}",
+            required.join(", ")
+        )
+    } else {
+        format!("No host satisfies required capability {}", unmet.join(", "))
+    })
+}
+
+/// Helper function that computes a list of ineligible hosts that match none of the spread requirements
+pub(crate) fn compute_ineligible_hosts<'a>(
+    all_hosts: &'a HashMap<String, Host>,
+    spreads: Vec<&Spread>,
+) -> HashMap<&'a String, &'a Host> {
+    // Find all host IDs that are eligible for any spread
+    let eligible_ids = spreads
+        .iter()
+        .flat_map(|spread| eligible_hosts(all_hosts, spread).into_keys())
+        .collect::<HashSet<_>>();
+
+    // Filter out all hosts that are eligible for any spread, leaving only ineligible hosts
+    all_hosts
+        .iter()
+        .filter(|(id, _)| !eligible_ids.contains(id))
+        .collect::<HashMap<_, _>>()
+}
+
+/// Computes the spread requirements for the given config after clamping its instances between its
+/// `min_count` and `max_count`
+fn compute_bounded_spread(spread_config: &SpreadScalerProperty) -> Vec<(Spread, usize)> {
+    compute_spread(&SpreadScalerProperty {
+        instances: spread_config.bounded_instances(),
+        ..spread_config.clone()
+    })
+}
+
+/// Given a spread config, return a vector of tuples that represents the spread
+/// and the actual number of components to start for a specific spread requirement
+fn compute_spread(spread_config: &SpreadScalerProperty) -> Vec<(Spread, usize)> {
+    let requested_instances = spread_config.instances;
+    let mut requested_spreads = spread_config.spread.clone();
+    requested_spreads.sort_by_key(|s| Reverse(s.weight.unwrap_or(DEFAULT_SPREAD_WEIGHT)));
+
+    let total_weight = requested_spreads
+        .iter()
+        .map(|s| s.weight.unwrap_or(DEFAULT_SPREAD_WEIGHT))
+        .sum::<usize>();
+    let computed_spreads: Vec<(Spread, usize)> = requested_spreads
+        .iter()
+        .map(|s| {
+            (
+                s.to_owned(),
+                // Order is important here since usizes chop off remaining decimals
+                (requested_instances * s.weight.unwrap_or(DEFAULT_SPREAD_WEIGHT)) / total_weight,
+            )
+        })
+        .collect();
+
+    let computed_spreads = if computed_spreads.is_empty() {
+        vec![(Spread::default(), requested_instances)]
+    } else {
+        computed_spreads
+    };
+
+    // Because of math, we may end up rounding a few instances away. Evenly distribute them
+    // among the remaining hosts
+    let computed_instances = computed_spreads
+        .iter()
+        .map(|(_, count)| count)
+        .sum::<usize>();
+    let computed_spreads = match computed_instances.cmp(&requested_instances) {
+        // To meet the specified/requested number of instances, evenly distribute the remaining unassigned instances among the computed spreads
+        Ordering::Less => {
+            let mut diff = requested_instances - computed_instances;
+            computed_spreads
+                .into_iter()
+                .map(|(spread, count)| {
+                    let additional = if diff > 0 {
+                        diff -= 1;
+                        1
+                    } else {
+                        0
+                    };
+                    (spread, count + additional)
+                })
+                .collect()
+        }
+        // This isn't possible (usizes round down) but I added an arm _just in case_
+        // there was a case that I didn't imagine
+        Ordering::Greater => {
+            warn!("Requesting more component instances than were specified");
+            computed_spreads
+        }
+        Ordering::Equal => computed_spreads,
+    };
+
+    // If there are no spreads specified, we should only have one (default) spread with the total number of instances
+    if requested_spreads.is_empty() && computed_spreads.len() == 1 {
+        return computed_spreads;
+    }
+
+    // this is an invariant check; the predicate should not be true.
+    if computed_spreads.len() != requested_spreads.len() {
+        eprintln!("Computed spreads and requested spreads are not the same length. This should not happen.");
+    }
+
+    // The output has spreads ordered by weight; we may want to return them per the order in the config file at some point. For now, this will do.
+    computed_spreads
+}
+
+fn detect_spread_requirement_conflicts(
+    spread_requirements: &[(Spread, usize)],
+    hosts: &HashMap<String, Host>,
+    running_instances_per_host: &HashMap<&String, usize>,
     commands: &[Command],
+    satisfies: impl Fn(usize, usize) -> bool,
 ) -> Option<String> {
     // Step 1: Determine the union of all eligible hosts for the configured spreads
     // and collect the current instance count for each eligible host
@@ -566,7 +1744,7 @@ fn detect_spread_requirement_conflicts(
     // Step 4: Compare the tuples' values to detect conflicts
     let mut conflicts = Vec::new();
     for (spread_name, (projected_count, target_count)) in spread_instances {
-        if projected_count != target_count {
+        if !satisfies(projected_count, target_count) {
             conflicts.push(format!(
                 "Spread requirement conflict: {} spread requires {} instances vs {} computed from reconciliation commands",
                 spread_name, target_count, projected_count
@@ -599,14 +1777,15 @@ mod test {
         commands::Command,
         consumers::{manager::Worker, ScopedMessage},
         events::{
-            ComponentScaled, Event, LinkdefDeleted, LinkdefSet, ProviderStarted, ProviderStopped,
+            ComponentScaled, Event, HostHeartbeat, LinkdefDeleted, LinkdefSet, ProviderInfo,
+            ProviderStarted, ProviderStopped,
         },
         scaler::{
             manager::ScalerManager,
             spreadscaler::{spreadscaler_annotations, ComponentSpreadScaler},
-            Scaler,
+            HostPlan, Scaler,
         },
-        storage::{Component, Host, Store, WadmComponentInfo},
+        storage::{Component, Host, Provider, Store, StoreError, WadmComponentInfo},
         test_util::{NoopPublisher, TestLatticeSource, TestStore},
         workers::{CommandPublisher, EventWorker, StatusPublisher},
     };
@@ -625,6 +1804,14 @@ mod test {
                 requirements: BTreeMap::new(),
                 weight: Some(100),
             }],
+            topology_key: None,
+            min_count: None,
+            max_count: None,
+            anti_affinity: Vec::new(),
+            weight_key: None,
+            selector: Vec::new(),
+            reconcile_interval: None,
+            instances_per_host: None,
         };
 
         let simple_spread_res = compute_spread(&simple_spread);
@@ -645,6 +1832,14 @@ mod test {
                     weight: Some(100),
                 },
             ],
+            topology_key: None,
+            min_count: None,
+            max_count: None,
+            anti_affinity: Vec::new(),
+            weight_key: None,
+            selector: Vec::new(),
+            reconcile_interval: None,
+            instances_per_host: None,
         };
 
         let multi_spread_even_res = compute_spread(&multi_spread_even);
@@ -666,6 +1861,14 @@ mod test {
                     weight: Some(40),
                 },
             ],
+            topology_key: None,
+            min_count: None,
+            max_count: None,
+            anti_affinity: Vec::new(),
+            weight_key: None,
+            selector: Vec::new(),
+            reconcile_interval: None,
+            instances_per_host: None,
         };
 
         let multi_spread_even_res = compute_spread(&multi_spread_odd);
@@ -687,6 +1890,14 @@ mod test {
                     weight: Some(100),
                 },
             ],
+            topology_key: None,
+            min_count: None,
+            max_count: None,
+            anti_affinity: Vec::new(),
+            weight_key: None,
+            selector: Vec::new(),
+            reconcile_interval: None,
+            instances_per_host: None,
         };
 
         let multi_spread_even_res = compute_spread(&multi_spread_odd);
@@ -708,6 +1919,14 @@ mod test {
                     weight: None,
                 },
             ],
+            topology_key: None,
+            min_count: None,
+            max_count: None,
+            anti_affinity: Vec::new(),
+            weight_key: None,
+            selector: Vec::new(),
+            reconcile_interval: None,
+            instances_per_host: None,
         };
 
         let multi_spread_even_no_weight = compute_spread(&multi_spread_even_no_weight);
@@ -718,176 +1937,1895 @@ mod test {
         let simple_spread_replica_only = SpreadScalerProperty {
             instances: 12,
             spread: vec![],
+            topology_key: None,
+            min_count: None,
+            max_count: None,
+            anti_affinity: Vec::new(),
+            weight_key: None,
+            selector: Vec::new(),
+            reconcile_interval: None,
+            instances_per_host: None,
         };
 
-        let simple_replica_only = compute_spread(&simple_spread_replica_only);
-        assert_eq!(simple_replica_only.len(), 1);
-        assert_eq!(simple_replica_only[0].1, 12);
+        let simple_replica_only = compute_spread(&simple_spread_replica_only);
+        assert_eq!(simple_replica_only.len(), 1);
+        assert_eq!(simple_replica_only[0].1, 12);
+
+        // Ensure we handle an all around complex case
+        let complex_spread = SpreadScalerProperty {
+            instances: 103,
+            spread: vec![
+                Spread {
+                    // 9 + 1 (remainder trip)
+                    name: "ComplexOne".to_string(),
+                    requirements: BTreeMap::new(),
+                    weight: Some(42),
+                },
+                Spread {
+                    // 0
+                    name: "ComplexTwo".to_string(),
+                    requirements: BTreeMap::new(),
+                    weight: Some(3),
+                },
+                Spread {
+                    // 8
+                    name: "ComplexThree".to_string(),
+                    requirements: BTreeMap::new(),
+                    weight: Some(37),
+                },
+                Spread {
+                    // 84 + 1 (remainder trip)
+                    name: "ComplexFour".to_string(),
+                    requirements: BTreeMap::new(),
+                    weight: Some(384),
+                },
+            ],
+            topology_key: None,
+            min_count: None,
+            max_count: None,
+            anti_affinity: Vec::new(),
+            weight_key: None,
+            selector: Vec::new(),
+            reconcile_interval: None,
+            instances_per_host: None,
+        };
+        let complex_spread_res = compute_spread(&complex_spread);
+        assert_eq!(complex_spread_res[0].1, 85);
+        assert_eq!(complex_spread_res[1].1, 10);
+        assert_eq!(complex_spread_res[2].1, 8);
+        assert_eq!(complex_spread_res[3].1, 0);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn can_compute_spread_commands() -> Result<()> {
+        let lattice_id = "can_compute_spread_commands";
+        let component_reference = "fakecloud.azurecr.io/echo:0.3.4".to_string();
+        let component_id = "fakecloud_azurecr_io_echo_0_3_4".to_string();
+
+        let host_id1 = "HOST_ONE";
+        let host_id2 = "HOST_TWO";
+        let host_id3 = "HOST_THREE";
+
+        let store = Arc::new(TestStore::default());
+
+        // Create three hosts with different labels
+        let mut host1_labels = HashMap::new();
+        host1_labels.insert("zone".to_string(), "east".to_string());
+
+        let mut host2_labels = HashMap::new();
+        host2_labels.insert("zone".to_string(), "west".to_string());
+
+        let mut host3_labels = HashMap::new();
+        host3_labels.insert("zone".to_string(), "central".to_string());
+
+        // Store the hosts
+        for (host_id, labels) in [
+            (host_id1, host1_labels),
+            (host_id2, host2_labels),
+            (host_id3, host3_labels),
+        ] {
+            store
+                .store(
+                    lattice_id,
+                    host_id.to_string(),
+                    Host {
+                        components: HashMap::new(),
+                        friendly_name: format!("host_{}", host_id),
+                        labels,
+                        providers: HashSet::new(),
+                        uptime_seconds: 123,
+                        version: None,
+                        id: host_id.to_string(),
+                        last_seen: Utc::now(),
+                        ..Default::default()
+                    },
+                )
+                .await?;
+        }
+
+        // Create spread requirements that map to specific hosts
+        let mut east_requirement = BTreeMap::new();
+        east_requirement.insert("zone".to_string(), "east".to_string());
+
+        let mut west_requirement = BTreeMap::new();
+        west_requirement.insert("zone".to_string(), "west".to_string());
+
+        let mut central_requirement = BTreeMap::new();
+        central_requirement.insert("zone".to_string(), "central".to_string());
+
+        let spread_config = SpreadScalerProperty {
+            instances: 103,
+            spread: vec![
+                Spread {
+                    name: "EastZone".to_string(),
+                    requirements: east_requirement, // Maps to host1
+                    weight: Some(42),
+                },
+                Spread {
+                    name: "WestZone".to_string(),
+                    requirements: west_requirement, // Maps to host2
+                    weight: Some(3),
+                },
+                Spread {
+                    name: "CentralZone".to_string(),
+                    requirements: central_requirement, // Maps to host3
+                    weight: Some(37),
+                },
+            ],
+            topology_key: None,
+            min_count: None,
+            max_count: None,
+            anti_affinity: Vec::new(),
+            weight_key: None,
+            selector: Vec::new(),
+            reconcile_interval: None,
+            instances_per_host: None,
+        };
+
+        let spreadscaler = ComponentSpreadScaler::new(
+            store.clone(),
+            ComponentScalerConfig {
+                lattice_id: lattice_id.to_string(),
+                component_id: component_id.to_string(),
+                component_reference: component_reference.to_string(),
+                model_name: MODEL_NAME.to_string(),
+                component_config: vec![],
+            },
+            spread_config,
+            "fake_component",
+        );
+        let pooled = ComponentSpreadScaler::new(
+            store.clone(),
+            ComponentScalerConfig {
+                lattice_id: lattice_id.to_string(),
+                component_id: component_id.to_string(),
+                component_reference: component_reference.to_string(),
+                model_name: MODEL_NAME.to_string(),
+                component_config: vec![],
+            },
+            spreadscaler.spread_config.spread_config.clone(),
+            "fake_component",
+        )
+        .with_compute_pool(Some(ComputePool::new(1)));
+
+        let cmds = spreadscaler.reconcile().await?;
+        assert_eq!(cmds.len(), 3);
+        assert_eq!(
+            pooled.reconcile().await?,
+            cmds,
+            "Computing on the pool should give the same commands as computing inline"
+        );
+        assert_eq!(pooled.status().await, spreadscaler.status().await);
+
+        // With weights 42:3:37 and total instances of 103
+        // EastZone (east) should get (52 + 1) instances
+        // WestZone (west) should get 3 instances
+        // CentralZone (central) should get (46 + 1) instances
+
+        assert!(cmds.contains(&Command::ScaleComponent(ScaleComponent {
+            component_id: component_id.to_string(),
+            reference: component_reference.to_string(),
+            host_id: host_id1.to_string(), // east zone
+            count: 53,
+            model_name: MODEL_NAME.to_string(),
+            annotations: spreadscaler_annotations("EastZone", spreadscaler.id()),
+            config: vec![]
+        })));
+
+        assert!(cmds.contains(&Command::ScaleComponent(ScaleComponent {
+            component_id: component_id.to_string(),
+            reference: component_reference.to_string(),
+            host_id: host_id2.to_string(), // west zone
+            count: 3,
+            model_name: MODEL_NAME.to_string(),
+            annotations: spreadscaler_annotations("WestZone", spreadscaler.id()),
+            config: vec![]
+        })));
+
+        assert!(cmds.contains(&Command::ScaleComponent(ScaleComponent {
+            component_id: component_id.to_string(),
+            reference: component_reference.to_string(),
+            host_id: host_id3.to_string(), // central zone
+            count: 47,
+            model_name: MODEL_NAME.to_string(),
+            annotations: spreadscaler_annotations("CentralZone", spreadscaler.id()),
+            config: vec![]
+        })));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn start_cooldown_protects_new_instances() -> Result<()> {
+        let lattice_id = "start_cooldown";
+        let component_reference = "fakecloud.azurecr.io/echo:0.3.4".to_string();
+        let component_id = "fakecloud_azurecr_io_echo_0_3_4".to_string();
+        let old_host = "NASDASDIMAREALHOSTONE";
+        let new_host = "NASDASDIMAREALHOSTTWO";
+
+        let store = Arc::new(TestStore::default());
+        for host_id in [old_host, new_host] {
+            store
+                .store(
+                    lattice_id,
+                    host_id.to_string(),
+                    Host {
+                        id: host_id.to_string(),
+                        last_seen: Utc::now(),
+                        ..Default::default()
+                    },
+                )
+                .await?;
+        }
+
+        let spreadscaler = ComponentSpreadScaler::new(
+            store.clone(),
+            ComponentScalerConfig {
+                lattice_id: lattice_id.to_string(),
+                component_id: component_id.to_string(),
+                component_reference: component_reference.to_string(),
+                model_name: MODEL_NAME.to_string(),
+                component_config: vec![],
+            },
+            SpreadScalerProperty {
+                instances: 1,
+                spread: vec![],
+                topology_key: None,
+                min_count: None,
+                max_count: None,
+                anti_affinity: Vec::new(),
+                weight_key: None,
+                selector: Vec::new(),
+                reconcile_interval: None,
+                instances_per_host: None,
+            },
+            "fake_component",
+        )
+        .with_start_cooldown(Duration::from_secs(60));
+        let annotations = spreadscaler_annotations("default", spreadscaler.id());
+
+        // One instance too many is running, and the one on the new host was just started
+        store
+            .store(
+                lattice_id,
+                component_id.to_string(),
+                Component {
+                    id: component_id.to_string(),
+                    name: "Echo".to_string(),
+                    issuer: "AASDASDASDASD".to_string(),
+                    instances: HashMap::from_iter([old_host, new_host].map(|host_id| {
+                        (
+                            host_id.to_string(),
+                            HashSet::from_iter([WadmComponentInfo {
+                                annotations: annotations.clone(),
+                                count: 1,
+                            }]),
+                        )
+                    })),
+                    reference: component_reference.to_string(),
+                    ..Default::default()
+                },
+            )
+            .await?;
+
+        let cmds = spreadscaler
+            .handle_event(&Event::ComponentScaled(ComponentScaled {
+                annotations: annotations.clone(),
+                claims: None,
+                image_ref: component_reference.to_string(),
+                max_instances: 1,
+                component_id: component_id.to_string(),
+                host_id: new_host.to_string(),
+            }))
+            .await?;
+        assert_eq!(
+            cmds,
+            vec![Command::ScaleComponent(ScaleComponent {
+                component_id: component_id.to_string(),
+                reference: component_reference.to_string(),
+                host_id: old_host.to_string(),
+                count: 0,
+                model_name: MODEL_NAME.to_string(),
+                annotations: annotations.clone(),
+                config: vec![]
+            })],
+            "Only the instance outside of the cooldown should be chosen for scale down"
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn scale_down_policy_chooses_instances_to_stop() -> Result<()> {
+        let lattice_id = "scale_down_policy";
+        let component_reference = "fakecloud.azurecr.io/echo:0.3.4".to_string();
+        let component_id = "fakecloud_azurecr_io_echo_0_3_4".to_string();
+        let busy_host = "NASDASDIMAREALHOSTONE";
+        let older_host = "NASDASDIMAREALHOSTTWO";
+        let newer_host = "NASDASDIMAREALHOSTTHREE";
+
+        let store = Arc::new(TestStore::default());
+        for host_id in [busy_host, older_host, newer_host] {
+            store
+                .store(
+                    lattice_id,
+                    host_id.to_string(),
+                    Host {
+                        id: host_id.to_string(),
+                        last_seen: Utc::now(),
+                        ..Default::default()
+                    },
+                )
+                .await?;
+        }
+
+        let spreadscaler = |policy| {
+            ComponentSpreadScaler::new(
+                store.clone(),
+                ComponentScalerConfig {
+                    lattice_id: lattice_id.to_string(),
+                    component_id: component_id.to_string(),
+                    component_reference: component_reference.to_string(),
+                    model_name: MODEL_NAME.to_string(),
+                    component_config: vec![],
+                },
+                SpreadScalerProperty {
+                    instances: 3,
+                    spread: vec![],
+                    topology_key: None,
+                    min_count: None,
+                    max_count: None,
+                    anti_affinity: Vec::new(),
+                    weight_key: None,
+                    selector: Vec::new(),
+                    reconcile_interval: None,
+                    instances_per_host: None,
+                },
+                "fake_component",
+            )
+            .with_scale_down_policy(policy)
+        };
+        let annotations =
+            spreadscaler_annotations("default", spreadscaler(ScaleDownPolicy::Any).id());
+
+        // Two more instances are running than required
+        store
+            .store(
+                lattice_id,
+                component_id.to_string(),
+                Component {
+                    id: component_id.to_string(),
+                    instances: HashMap::from_iter(
+                        [(busy_host, 3), (older_host, 1), (newer_host, 1)].map(
+                            |(host_id, count)| {
+                                (
+                                    host_id.to_string(),
+                                    HashSet::from_iter([WadmComponentInfo {
+                                        annotations: annotations.clone(),
+                                        count,
+                                    }]),
+                                )
+                            },
+                        ),
+                    ),
+                    reference: component_reference.to_string(),
+                    ..Default::default()
+                },
+            )
+            .await?;
+
+        let desired_counts = |cmds: Vec<Command>| {
+            cmds.into_iter()
+                .filter_map(|cmd| match cmd {
+                    Command::ScaleComponent(scale) => Some((scale.host_id, scale.count)),
+                    _ => None,
+                })
+                .collect::<BTreeMap<_, _>>()
+        };
+
+        let newest = spreadscaler(ScaleDownPolicy::Newest);
+        // Start times are only known for the instances the scaler saw start
+        newest.record_instance_count(older_host, "default", 1).await;
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        newest.record_instance_count(newer_host, "default", 1).await;
+        assert_eq!(
+            desired_counts(newest.reconcile().await?),
+            BTreeMap::from_iter([
+                (busy_host.to_string(), 3),
+                (older_host.to_string(), 0),
+                (newer_host.to_string(), 0),
+            ]),
+            "The most recently started instances should be stopped"
+        );
+
+        let even = spreadscaler(ScaleDownPolicy::EvenHosts);
+        assert_eq!(
+            desired_counts(even.reconcile().await?),
+            BTreeMap::from_iter([
+                (busy_host.to_string(), 1),
+                (older_host.to_string(), 1),
+                (newer_host.to_string(), 1),
+            ]),
+            "Instances should be stopped on the busiest host until all hosts are even"
+        );
+
+        assert!("sideways".parse::<ScaleDownPolicy>().is_err());
+        assert_eq!(
+            "even-hosts".parse::<ScaleDownPolicy>()?,
+            ScaleDownPolicy::EvenHosts
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn clamps_instances_to_bounds() -> Result<()> {
+        let lattice_id = "instance_bounds";
+        let component_reference = "fakecloud.azurecr.io/echo:0.3.4".to_string();
+        let component_id = "fakecloud_azurecr_io_echo_0_3_4".to_string();
+        let host_id = "NASDASDIMAREALHOSTONE";
+
+        let store = Arc::new(TestStore::default());
+        store
+            .store(
+                lattice_id,
+                host_id.to_string(),
+                Host {
+                    id: host_id.to_string(),
+                    last_seen: Utc::now(),
+                    ..Default::default()
+                },
+            )
+            .await?;
+
+        let property = |instances, min_count, max_count| SpreadScalerProperty {
+            instances,
+            spread: vec![],
+            topology_key: None,
+            min_count,
+            max_count,
+            anti_affinity: Vec::new(),
+            weight_key: None,
+            selector: Vec::new(),
+            reconcile_interval: None,
+            instances_per_host: None,
+        };
+        let mut spreadscaler = ComponentSpreadScaler::new(
+            store.clone(),
+            ComponentScalerConfig {
+                lattice_id: lattice_id.to_string(),
+                component_id: component_id.to_string(),
+                component_reference: component_reference.to_string(),
+                model_name: MODEL_NAME.to_string(),
+                component_config: vec![],
+            },
+            property(1000, None, Some(3)),
+            "fake_component",
+        );
+        let counts = |cmds: Vec<Command>| {
+            cmds.into_iter()
+                .map(|cmd| match cmd {
+                    Command::ScaleComponent(scale) => scale.count,
+                    cmd => panic!("Unexpected command {cmd:?}"),
+                })
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            counts(spreadscaler.reconcile().await?),
+            vec![3],
+            "Requested instances should be capped at max_count"
+        );
+        store
+            .store(
+                lattice_id,
+                component_id.to_string(),
+                Component {
+                    id: component_id.to_string(),
+                    instances: HashMap::from_iter([(
+                        host_id.to_string(),
+                        HashSet::from_iter([WadmComponentInfo {
+                            annotations: spreadscaler_annotations("default", spreadscaler.id()),
+                            count: 3,
+                        }]),
+                    )]),
+                    reference: component_reference.to_string(),
+                    ..Default::default()
+                },
+            )
+            .await?;
+        assert!(spreadscaler.reconcile().await?.is_empty());
+        let status = spreadscaler.status().await;
+        assert_eq!(
+            status.status_type,
+            StatusType::Unhealthy,
+            "Hitting max_count should not be reported as fully deployed"
+        );
+        assert!(
+            status.message.contains("max_count"),
+            "Status should explain the bound was hit, got: {}",
+            status.message
+        );
+
+        assert!(
+            spreadscaler
+                .update_config(TraitProperty::SpreadScaler(property(4, Some(5), Some(3))))
+                .await
+                .is_err(),
+            "min_count greater than max_count should be rejected"
+        );
+        assert_eq!(
+            counts(
+                spreadscaler
+                    .update_config(TraitProperty::SpreadScaler(property(1, Some(4), None)))
+                    .await?
+            ),
+            vec![4],
+            "Requested instances should be raised to min_count"
+        );
+        assert_eq!(
+            counts(
+                spreadscaler
+                    .update_config(TraitProperty::SpreadScaler(property(10, None, Some(0))))
+                    .await?
+            ),
+            vec![10],
+            "A max_count of 0 should not limit instances"
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn skips_reconcile_when_nothing_changed() -> Result<()> {
+        let lattice_id = "reconcile_cache";
+        let component_reference = "fakecloud.azurecr.io/echo:0.3.4".to_string();
+        let component_id = "fakecloud_azurecr_io_echo_0_3_4".to_string();
+        let host_id = "NASDASDIMAREALHOSTONE";
+
+        let store = Arc::new(TestStore::default());
+        store
+            .store(
+                lattice_id,
+                host_id.to_string(),
+                Host {
+                    id: host_id.to_string(),
+                    components: HashMap::from_iter([(component_id.clone(), 1)]),
+                    last_seen: Utc::now(),
+                    ..Default::default()
+                },
+            )
+            .await?;
+
+        let spreadscaler = ComponentSpreadScaler::new(
+            store.clone(),
+            ComponentScalerConfig {
+                lattice_id: lattice_id.to_string(),
+                component_id: component_id.to_string(),
+                component_reference: component_reference.to_string(),
+                model_name: MODEL_NAME.to_string(),
+                component_config: vec![],
+            },
+            SpreadScalerProperty {
+                instances: 1,
+                spread: vec![],
+                topology_key: None,
+                min_count: None,
+                max_count: None,
+                anti_affinity: Vec::new(),
+                weight_key: None,
+                selector: Vec::new(),
+                reconcile_interval: None,
+                instances_per_host: None,
+            },
+            "fake_component",
+        );
+        let component = |count| Component {
+            id: component_id.to_string(),
+            instances: HashMap::from_iter([(
+                host_id.to_string(),
+                HashSet::from_iter([WadmComponentInfo {
+                    annotations: spreadscaler_annotations("default", spreadscaler.id()),
+                    count,
+                }]),
+            )]),
+            reference: component_reference.to_string(),
+            ..Default::default()
+        };
+        store
+            .store(lattice_id, component_id.to_string(), component(1))
+            .await?;
+
+        assert!(spreadscaler.reconcile().await?.is_empty());
+        assert_eq!(
+            spreadscaler.status.read().await.status_type,
+            StatusType::Deployed
+        );
+
+        // A recomputation would overwrite this status
+        *spreadscaler.status.write().await = StatusInfo::failed("not recomputed");
+        assert!(spreadscaler.reconcile().await?.is_empty());
+        assert_eq!(
+            spreadscaler.status.read().await.message,
+            "not recomputed",
+            "Reconciling unchanged state should not recompute commands"
+        );
+
+        // A heartbeat that doesn't change anything the scaler reconciles against is skipped too
+        let heartbeat = Event::HostHeartbeat(HostHeartbeat {
+            components: vec![],
+            providers: vec![],
+            labels: HashMap::new(),
+            issuer: String::new(),
+            friendly_name: "host".to_string(),
+            uptime_seconds: 60,
+            uptime_human: "60s".to_string(),
+            version: semver::Version::new(1, 0, 0),
+            host_id: host_id.to_string(),
+            utilization: None,
+        });
+        assert!(spreadscaler.handle_event(&heartbeat).await?.is_empty());
+        assert_eq!(
+            spreadscaler.status.read().await.message,
+            "not recomputed",
+            "Heartbeats alone should not recompute commands"
+        );
+
+        // Any change to the observed state means the commands are computed again
+        store
+            .store(lattice_id, component_id.to_string(), component(0))
+            .await?;
+        assert_eq!(spreadscaler.reconcile().await?.len(), 1);
+        assert_eq!(
+            spreadscaler.status.read().await.status_type,
+            StatusType::Reconciling
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn diff_explains_commands_per_host() -> Result<()> {
+        let lattice_id = "reconcile_diff";
+        let component_reference = "fakecloud.azurecr.io/echo:0.3.4".to_string();
+        let component_id = "fakecloud_azurecr_io_echo_0_3_4".to_string();
+        let host_id = "NASDASDIMAREALHOSTONE";
+
+        let store = Arc::new(TestStore::default());
+        store
+            .store(
+                lattice_id,
+                host_id.to_string(),
+                Host {
+                    id: host_id.to_string(),
+                    components: HashMap::from_iter([(component_id.clone(), 1)]),
+                    last_seen: Utc::now(),
+                    ..Default::default()
+                },
+            )
+            .await?;
+
+        let spreadscaler = ComponentSpreadScaler::new(
+            store.clone(),
+            ComponentScalerConfig {
+                lattice_id: lattice_id.to_string(),
+                component_id: component_id.to_string(),
+                component_reference: component_reference.to_string(),
+                model_name: MODEL_NAME.to_string(),
+                component_config: vec![],
+            },
+            SpreadScalerProperty {
+                instances: 4,
+                spread: vec![],
+                topology_key: None,
+                min_count: None,
+                max_count: None,
+                anti_affinity: Vec::new(),
+                weight_key: None,
+                selector: Vec::new(),
+                reconcile_interval: None,
+                instances_per_host: None,
+            },
+            "fake_component",
+        );
+        store
+            .store(
+                lattice_id,
+                component_id.to_string(),
+                Component {
+                    id: component_id.to_string(),
+                    instances: HashMap::from_iter([(
+                        host_id.to_string(),
+                        HashSet::from_iter([WadmComponentInfo {
+                            annotations: spreadscaler_annotations("default", spreadscaler.id()),
+                            count: 1,
+                        }]),
+                    )]),
+                    reference: component_reference.to_string(),
+                    ..Default::default()
+                },
+            )
+            .await?;
+
+        let plan = spreadscaler.diff().await?;
+        assert_eq!(
+            plan.hosts,
+            BTreeMap::from_iter([(
+                host_id.to_string(),
+                HostPlan {
+                    actual: 1,
+                    desired: 4
+                }
+            )]),
+            "The plan should show the running and requested instances on the host"
+        );
+        assert_eq!(plan.selected_hosts().collect::<Vec<_>>(), vec![host_id]);
+        assert_eq!(
+            plan.commands,
+            spreadscaler.reconcile().await?,
+            "Reconciling should return the commands from the plan"
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn reports_version_skew_during_rollout() -> Result<()> {
+        let lattice_id = "version_skew";
+        let component_id = "fakecloud_azurecr_io_echo".to_string();
+        let old_host = "NASDASDIMAREALHOSTONE";
+        let new_host = "NASDASDIMAREALHOSTTWO";
+
+        let store = Arc::new(TestStore::default());
+        for host_id in [old_host, new_host] {
+            store
+                .store(
+                    lattice_id,
+                    host_id.to_string(),
+                    Host {
+                        id: host_id.to_string(),
+                        components: HashMap::from_iter([(component_id.clone(), 1)]),
+                        last_seen: Utc::now(),
+                        ..Default::default()
+                    },
+                )
+                .await?;
+        }
+
+        let spreadscaler = |uniform_versions| {
+            ComponentSpreadScaler::new(
+                store.clone(),
+                ComponentScalerConfig {
+                    lattice_id: lattice_id.to_string(),
+                    component_id: component_id.clone(),
+                    component_reference: "fakecloud.azurecr.io/echo:0.2.0".to_string(),
+                    model_name: MODEL_NAME.to_string(),
+                    component_config: vec![],
+                },
+                SpreadScalerProperty {
+                    instances: 2,
+                    spread: vec![],
+                    topology_key: None,
+                    min_count: None,
+                    max_count: None,
+                    anti_affinity: Vec::new(),
+                    weight_key: None,
+                    selector: Vec::new(),
+                    reconcile_interval: None,
+                    instances_per_host: None,
+                },
+                "fake_component",
+            )
+            .with_uniform_versions(uniform_versions)
+        };
+        let annotations = spreadscaler_annotations("default", spreadscaler(false).id());
+
+        // Partway through a rollout, one host is still running the old version
+        store
+            .store(
+                lattice_id,
+                component_id.clone(),
+                Component {
+                    id: component_id.clone(),
+                    instances: HashMap::from_iter([old_host, new_host].map(|host_id| {
+                        (
+                            host_id.to_string(),
+                            HashSet::from_iter([WadmComponentInfo {
+                                annotations: annotations.clone(),
+                                count: 1,
+                            }]),
+                        )
+                    })),
+                    reference: "fakecloud.azurecr.io/echo:0.2.0".to_string(),
+                    host_references: HashMap::from_iter([
+                        (
+                            old_host.to_string(),
+                            "fakecloud.azurecr.io/echo:0.1.0".to_string(),
+                        ),
+                        (
+                            new_host.to_string(),
+                            "fakecloud.azurecr.io/echo:0.2.0".to_string(),
+                        ),
+                    ]),
+                    ..Default::default()
+                },
+            )
+            .await?;
+
+        let reporting = spreadscaler(false);
+        assert!(reporting.reconcile().await?.is_empty());
+        let status = reporting.status().await;
+        assert_eq!(
+            status.status_type,
+            StatusType::Deployed,
+            "Skew should only be reported unless uniform versions are required"
+        );
+        assert_eq!(status.message, "Version skew: running 0.1.0 and 0.2.0");
+
+        let gated = spreadscaler(true);
+        gated.reconcile().await?;
+        let status = gated.status().await;
+        assert_eq!(status.status_type, StatusType::Failed);
+        assert_eq!(status.message, "Version skew: running 0.1.0 and 0.2.0");
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn updates_changed_reference_in_place() -> Result<()> {
+        let lattice_id = "update_in_place";
+        let component_id = "fakecloud_azurecr_io_echo".to_string();
+        let old_reference = "fakecloud.azurecr.io/echo:0.1.0";
+        let new_reference = "fakecloud.azurecr.io/echo:0.2.0";
+        let host_ids = ["NASDASDIMAREALHOSTONE", "NASDASDIMAREALHOSTTWO"];
+
+        let store = Arc::new(TestStore::default());
+        for host_id in host_ids {
+            store
+                .store(
+                    lattice_id,
+                    host_id.to_string(),
+                    Host {
+                        id: host_id.to_string(),
+                        components: HashMap::from_iter([(component_id.clone(), 1)]),
+                        last_seen: Utc::now(),
+                        ..Default::default()
+                    },
+                )
+                .await?;
+        }
+
+        let spreadscaler = |reference: &str, instances| {
+            ComponentSpreadScaler::new(
+                store.clone(),
+                ComponentScalerConfig {
+                    lattice_id: lattice_id.to_string(),
+                    component_id: component_id.clone(),
+                    component_reference: reference.to_string(),
+                    model_name: MODEL_NAME.to_string(),
+                    component_config: vec![],
+                },
+                SpreadScalerProperty {
+                    instances,
+                    spread: vec![],
+                    topology_key: None,
+                    min_count: None,
+                    max_count: None,
+                    anti_affinity: Vec::new(),
+                    weight_key: None,
+                    selector: Vec::new(),
+                    reconcile_interval: None,
+                    instances_per_host: None,
+                },
+                "fake_component",
+            )
+        };
+        let old_annotations =
+            spreadscaler_annotations("default", spreadscaler(old_reference, 2).id());
+
+        // Both instances were started by the scaler for the previous version of the manifest
+        store
+            .store(
+                lattice_id,
+                component_id.clone(),
+                Component {
+                    id: component_id.clone(),
+                    instances: HashMap::from_iter(host_ids.map(|host_id| {
+                        (
+                            host_id.to_string(),
+                            HashSet::from_iter([WadmComponentInfo {
+                                annotations: old_annotations.clone(),
+                                count: 1,
+                            }]),
+                        )
+                    })),
+                    reference: old_reference.to_string(),
+                    host_references: HashMap::from_iter(
+                        host_ids.map(|host_id| (host_id.to_string(), old_reference.to_string())),
+                    ),
+                    ..Default::default()
+                },
+            )
+            .await?;
+
+        let updater = spreadscaler(new_reference, 2);
+        let new_annotations = spreadscaler_annotations("default", updater.id());
+        let commands = updater.reconcile().await?;
+        assert_eq!(
+            commands,
+            host_ids
+                .map(|host_id| Command::UpdateComponent(UpdateComponent {
+                    component_id: component_id.clone(),
+                    host_id: host_id.to_string(),
+                    new_reference: new_reference.to_string(),
+                    model_name: MODEL_NAME.to_string(),
+                    annotations: new_annotations.clone(),
+                }))
+                .to_vec(),
+            "Only the reference changed, so instances should be updated in place"
+        );
+        assert_eq!(updater.status().await.status_type, StatusType::Reconciling);
+        let Some((Event::ComponentScaled(expected), None)) = commands[0].corresponding_event()
+        else {
+            panic!("Update should expect the component to be scaled");
+        };
+        assert_eq!(expected.image_ref, new_reference);
+        assert_eq!(
+            expected.annotations.get(SCALER_KEY),
+            new_annotations.get(SCALER_KEY)
+        );
+
+        // When the count changed too, the instances are scaled first and updated afterwards
+        let commands = spreadscaler(new_reference, 1).reconcile().await?;
+        assert!(!commands.is_empty());
+        assert!(commands
+            .iter()
+            .all(|command| matches!(command, Command::ScaleComponent(_))));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn declarative_commands_carry_absolute_counts() -> Result<()> {
+        let lattice_id = "declarative_commands";
+        let component_reference = "fakecloud.azurecr.io/echo:0.3.4".to_string();
+        let component_id = "fakecloud_azurecr_io_echo_0_3_4".to_string();
+        let host_id_one = "NASDASDIMAREALHOSTONE";
+        let host_id_two = "NASDASDIMAREALHOSTTWO";
+
+        let store = Arc::new(TestStore::default());
+
+        for host_id in [host_id_one, host_id_two] {
+            store
+                .store(
+                    lattice_id,
+                    host_id.to_string(),
+                    Host {
+                        components: HashMap::new(),
+                        friendly_name: "hey".to_string(),
+                        labels: HashMap::new(),
+                        providers: HashSet::new(),
+                        uptime_seconds: 123,
+                        version: None,
+                        id: host_id.to_string(),
+                        last_seen: Utc::now(),
+                        ..Default::default()
+                    },
+                )
+                .await?;
+        }
+
+        let spreadscaler = ComponentSpreadScaler::new(
+            store.clone(),
+            ComponentScalerConfig {
+                lattice_id: lattice_id.to_string(),
+                component_id: component_id.to_string(),
+                component_reference: component_reference.to_string(),
+                model_name: MODEL_NAME.to_string(),
+                component_config: vec![],
+            },
+            SpreadScalerProperty {
+                instances: 5,
+                spread: vec![],
+                topology_key: None,
+                min_count: None,
+                max_count: None,
+                anti_affinity: Vec::new(),
+                weight_key: None,
+                selector: Vec::new(),
+                reconcile_interval: None,
+                instances_per_host: None,
+            },
+            "fake_component",
+        )
+        .with_declarative_commands(true);
+
+        let scale = |host_id: &str, count| {
+            Command::ScaleComponent(ScaleComponent {
+                component_id: component_id.to_string(),
+                reference: component_reference.to_string(),
+                host_id: host_id.to_string(),
+                count,
+                model_name: MODEL_NAME.to_string(),
+                annotations: spreadscaler_annotations("default", spreadscaler.id()),
+                config: vec![],
+            })
+        };
+
+        // Nothing is running, so the count is spread across both hosts with the remainder going to
+        // the first one
+        let cmds = spreadscaler.reconcile().await?;
+        assert_eq!(cmds, vec![scale(host_id_one, 3), scale(host_id_two, 2)]);
+
+        // Drifted state across both hosts: the remainder stays on the host with the most instances
+        // and every command carries the absolute count for its host
+        store
+            .store(
+                lattice_id,
+                component_id.to_string(),
+                Component {
+                    id: component_id.to_string(),
+                    name: "Echo".to_string(),
+                    issuer: "AASDASDASDASD".to_string(),
+                    instances: HashMap::from_iter([
+                        (
+                            host_id_one.to_string(),
+                            HashSet::from_iter([WadmComponentInfo {
+                                annotations: spreadscaler_annotations("default", spreadscaler.id()),
+                                count: 1,
+                            }]),
+                        ),
+                        (
+                            host_id_two.to_string(),
+                            HashSet::from_iter([WadmComponentInfo {
+                                annotations: spreadscaler_annotations("default", spreadscaler.id()),
+                                count: 7,
+                            }]),
+                        ),
+                    ]),
+                    reference: component_reference.to_string(),
+                    ..Default::default()
+                },
+            )
+            .await?;
+
+        let cmds = spreadscaler.reconcile().await?;
+        assert_eq!(cmds, vec![scale(host_id_one, 2), scale(host_id_two, 3)]);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn topology_key_spreads_across_failure_domains() -> Result<()> {
+        let lattice_id = "topology_key";
+        let component_reference = "fakecloud.azurecr.io/echo:0.3.4".to_string();
+        let component_id = "fakecloud_azurecr_io_echo_0_3_4".to_string();
+
+        let store = Arc::new(TestStore::default());
+        for (host_id, zone) in [("zone-a-1", "a"), ("zone-a-2", "a"), ("zone-b-1", "b")] {
+            store
+                .store(
+                    lattice_id,
+                    host_id.to_string(),
+                    Host {
+                        labels: HashMap::from_iter([("zone".to_string(), zone.to_string())]),
+                        id: host_id.to_string(),
+                        last_seen: Utc::now(),
+                        ..Default::default()
+                    },
+                )
+                .await?;
+        }
+
+        let scaler = |instances, topology_key: &str| {
+            ComponentSpreadScaler::new(
+                store.clone(),
+                ComponentScalerConfig {
+                    lattice_id: lattice_id.to_string(),
+                    component_id: component_id.to_string(),
+                    component_reference: component_reference.to_string(),
+                    model_name: MODEL_NAME.to_string(),
+                    component_config: vec![],
+                },
+                SpreadScalerProperty {
+                    instances,
+                    spread: vec![],
+                    topology_key: Some(topology_key.to_string()),
+                    min_count: None,
+                    max_count: None,
+                    anti_affinity: Vec::new(),
+                    weight_key: None,
+                    selector: Vec::new(),
+                    reconcile_interval: None,
+                    instances_per_host: None,
+                },
+                "fake_component",
+            )
+        };
+        let placement = |cmds: Vec<Command>| {
+            cmds.into_iter()
+                .map(|cmd| match cmd {
+                    Command::ScaleComponent(scale) => (scale.host_id, scale.count),
+                    cmd => panic!("Unexpected command {cmd:?}"),
+                })
+                .collect::<Vec<_>>()
+        };
+
+        let spreadscaler = scaler(2, "zone");
+        assert_eq!(
+            placement(spreadscaler.reconcile().await?),
+            vec![("zone-a-1".to_string(), 1), ("zone-b-1".to_string(), 1)],
+            "Instances should be spread across both zones"
+        );
+        assert_eq!(
+            spreadscaler.status().await.status_type,
+            StatusType::Reconciling
+        );
+
+        let spreadscaler = scaler(3, "zone");
+        assert_eq!(
+            placement(spreadscaler.reconcile().await?),
+            vec![("zone-a-1".to_string(), 2), ("zone-b-1".to_string(), 1)],
+            "Both zones should have an instance before one of them doubles up"
+        );
+
+        // No host has the label, so there is nowhere to put the instances
+        let spreadscaler = scaler(2, "rack");
+        assert!(spreadscaler.reconcile().await?.is_empty());
+        assert_eq!(spreadscaler.status().await.status_type, StatusType::Failed);
+
+        // Instances can run in a single zone, but it is reported as a failure to spread
+        store.delete::<Host>(lattice_id, "zone-b-1").await?;
+        let spreadscaler = scaler(2, "zone");
+        assert_eq!(
+            placement(spreadscaler.reconcile().await?),
+            vec![("zone-a-1".to_string(), 2)]
+        );
+        let status = spreadscaler.status().await;
+        assert_eq!(status.status_type, StatusType::Failed);
+        assert!(
+            status.message.contains("only 1 distinct value"),
+            "Status should explain why spreading failed, got: {}",
+            status.message
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn anti_affinity_avoids_shared_label_values() -> Result<()> {
+        let lattice_id = "anti_affinity";
+        let component_reference = "fakecloud.azurecr.io/echo:0.3.4".to_string();
+        let component_id = "fakecloud_azurecr_io_echo_0_3_4".to_string();
+
+        let store = Arc::new(TestStore::default());
+        for (host_id, rack, zone) in [
+            ("rack-a-1", "a", "east"),
+            ("rack-a-2", "a", "west"),
+            ("rack-b-1", "b", "east"),
+        ] {
+            store
+                .store(
+                    lattice_id,
+                    host_id.to_string(),
+                    Host {
+                        labels: HashMap::from_iter([
+                            ("rack".to_string(), rack.to_string()),
+                            ("zone".to_string(), zone.to_string()),
+                        ]),
+                        id: host_id.to_string(),
+                        last_seen: Utc::now(),
+                        ..Default::default()
+                    },
+                )
+                .await?;
+        }
+
+        let scaler = |instances, anti_affinity: &[&str]| {
+            ComponentSpreadScaler::new(
+                store.clone(),
+                ComponentScalerConfig {
+                    lattice_id: lattice_id.to_string(),
+                    component_id: component_id.to_string(),
+                    component_reference: component_reference.to_string(),
+                    model_name: MODEL_NAME.to_string(),
+                    component_config: vec![],
+                },
+                SpreadScalerProperty {
+                    instances,
+                    spread: vec![],
+                    topology_key: None,
+                    min_count: None,
+                    max_count: None,
+                    anti_affinity: anti_affinity.iter().map(|key| key.to_string()).collect(),
+                    weight_key: None,
+                    selector: Vec::new(),
+                    reconcile_interval: None,
+                    instances_per_host: None,
+                },
+                "fake_component",
+            )
+        };
+        let placement = |cmds: Vec<Command>| {
+            cmds.into_iter()
+                .map(|cmd| match cmd {
+                    Command::ScaleComponent(scale) => (scale.host_id, scale.count),
+                    cmd => panic!("Unexpected command {cmd:?}"),
+                })
+                .collect::<Vec<_>>()
+        };
+
+        let spreadscaler = scaler(2, &["rack"]);
+        assert_eq!(
+            placement(spreadscaler.reconcile().await?),
+            vec![("rack-a-1".to_string(), 1), ("rack-b-1".to_string(), 1)],
+            "Instances should land on hosts in different racks"
+        );
+        assert_eq!(
+            spreadscaler.status().await.status_type,
+            StatusType::Reconciling
+        );
+
+        // There are only two racks, so only two instances can be placed
+        let spreadscaler = scaler(3, &["rack"]);
+        assert_eq!(
+            placement(spreadscaler.reconcile().await?),
+            vec![("rack-a-1".to_string(), 1), ("rack-b-1".to_string(), 1)],
+            "Whatever fits should still be placed"
+        );
+        let status = spreadscaler.status().await;
+        assert_eq!(status.status_type, StatusType::Failed);
+        assert!(
+            status.message.contains("only 2/3"),
+            "Status should report the shortfall, got: {}",
+            status.message
+        );
+
+        // Every other host shares either a rack or a zone with the first one
+        let spreadscaler = scaler(2, &["rack", "zone"]);
+        assert_eq!(
+            placement(spreadscaler.reconcile().await?),
+            vec![("rack-a-1".to_string(), 1)],
+            "Hosts sharing a value for any of the keys should be avoided"
+        );
+        assert_eq!(spreadscaler.status().await.status_type, StatusType::Failed);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn weight_key_spreads_proportionally() -> Result<()> {
+        let lattice_id = "weight_key";
+        let component_reference = "fakecloud.azurecr.io/echo:0.3.4".to_string();
+        let component_id = "fakecloud_azurecr_io_echo_0_3_4".to_string();
+
+        let store = Arc::new(TestStore::default());
+        for (host_id, weight) in [
+            ("host-big", Some("3")),
+            ("host-broken", Some("lots")),
+            ("host-small", None),
+        ] {
+            store
+                .store(
+                    lattice_id,
+                    host_id.to_string(),
+                    Host {
+                        labels: weight
+                            .map(|weight| {
+                                HashMap::from_iter([("weight".to_string(), weight.to_string())])
+                            })
+                            .unwrap_or_default(),
+                        id: host_id.to_string(),
+                        last_seen: Utc::now(),
+                        ..Default::default()
+                    },
+                )
+                .await?;
+        }
+
+        let scaler = |instances| {
+            ComponentSpreadScaler::new(
+                store.clone(),
+                ComponentScalerConfig {
+                    lattice_id: lattice_id.to_string(),
+                    component_id: component_id.to_string(),
+                    component_reference: component_reference.to_string(),
+                    model_name: MODEL_NAME.to_string(),
+                    component_config: vec![],
+                },
+                SpreadScalerProperty {
+                    instances,
+                    spread: vec![],
+                    topology_key: None,
+                    min_count: None,
+                    max_count: None,
+                    anti_affinity: Vec::new(),
+                    weight_key: Some("weight".to_string()),
+                    selector: Vec::new(),
+                    reconcile_interval: None,
+                    instances_per_host: None,
+                },
+                "fake_component",
+            )
+        };
+        let placement = |cmds: Vec<Command>| {
+            cmds.into_iter()
+                .map(|cmd| match cmd {
+                    Command::ScaleComponent(scale) => (scale.host_id, scale.count),
+                    cmd => panic!("Unexpected command {cmd:?}"),
+                })
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            placement(scaler(5).reconcile().await?),
+            vec![
+                ("host-big".to_string(), 3),
+                ("host-broken".to_string(), 1),
+                ("host-small".to_string(), 1),
+            ],
+            "Hosts should get instances in proportion to their weight"
+        );
+        // 4 instances give shares of 2.4, 0.8 and 0.8, so the two largest remainders round up
+        assert_eq!(
+            placement(scaler(4).reconcile().await?),
+            vec![
+                ("host-big".to_string(), 2),
+                ("host-broken".to_string(), 1),
+                ("host-small".to_string(), 1),
+            ],
+            "Leftover instances should go to the largest remainders"
+        );
+        assert_eq!(
+            placement(scaler(1).reconcile().await?),
+            vec![("host-big".to_string(), 1)],
+            "Counts should always add up to the desired total"
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn skips_hosts_within_quiet_period() -> Result<()> {
+        let lattice_id = "host_quiet_period";
+        let component_reference = "fakecloud.azurecr.io/echo:0.3.4".to_string();
+        let component_id = "fakecloud_azurecr_io_echo_0_3_4".to_string();
+
+        let store = Arc::new(TestStore::default());
+        for (host_id, uptime_seconds) in [("new-host", 0), ("old-host", 3600)] {
+            store
+                .store(
+                    lattice_id,
+                    host_id.to_string(),
+                    Host {
+                        id: host_id.to_string(),
+                        last_seen: Utc::now(),
+                        uptime_seconds,
+                        ..Default::default()
+                    },
+                )
+                .await?;
+        }
+
+        let scaler = || {
+            ComponentSpreadScaler::new(
+                store.clone(),
+                ComponentScalerConfig {
+                    lattice_id: lattice_id.to_string(),
+                    component_id: component_id.to_string(),
+                    component_reference: component_reference.to_string(),
+                    model_name: MODEL_NAME.to_string(),
+                    component_config: vec![],
+                },
+                SpreadScalerProperty {
+                    instances: 4,
+                    spread: vec![],
+                    topology_key: None,
+                    min_count: None,
+                    max_count: None,
+                    anti_affinity: Vec::new(),
+                    weight_key: None,
+                    selector: Vec::new(),
+                    reconcile_interval: None,
+                    instances_per_host: None,
+                },
+                "fake_component",
+            )
+            .with_host_quiet_period(Duration::from_secs(60))
+        };
+        let placement = |cmds: Vec<Command>| {
+            let mut placement = cmds
+                .into_iter()
+                .map(|cmd| match cmd {
+                    Command::ScaleComponent(scale) => (scale.host_id, scale.count),
+                    cmd => panic!("Unexpected command {cmd:?}"),
+                })
+                .collect::<Vec<_>>();
+            placement.sort();
+            placement
+        };
+
+        assert_eq!(
+            placement(scaler().reconcile().await?),
+            vec![("old-host".to_string(), 4)],
+            "Nothing should be placed on a host that just started"
+        );
+
+        // With only the new host left there is nowhere to place anything yet
+        store.delete::<Host>(lattice_id, "old-host").await?;
+        let waiting = scaler();
+        assert!(waiting.reconcile().await?.is_empty());
+
+        // Once the quiet period has passed the host is eligible
+        store
+            .store(
+                lattice_id,
+                "new-host".to_string(),
+                Host {
+                    id: "new-host".to_string(),
+                    last_seen: Utc::now(),
+                    uptime_seconds: 60,
+                    ..Default::default()
+                },
+            )
+            .await?;
+        assert_eq!(
+            placement(waiting.reconcile().await?),
+            vec![("new-host".to_string(), 4)],
+            "Hosts should be used once their quiet period has passed"
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn only_places_on_hosts_with_required_capabilities() -> Result<()> {
+        let lattice_id = "check_capabilities";
+        let component_id = "echo";
+        let store = Arc::new(TestStore::default());
+        for host_id in ["host-one", "host-two"] {
+            store
+                .store(
+                    lattice_id,
+                    host_id.to_string(),
+                    Host {
+                        id: host_id.to_string(),
+                        last_seen: Utc::now(),
+                        ..Default::default()
+                    },
+                )
+                .await?;
+        }
+        store
+            .store(
+                lattice_id,
+                component_id.to_string(),
+                Component {
+                    id: component_id.to_string(),
+                    reference: "fakecloud.azurecr.io/echo:0.3.4".to_string(),
+                    capabilities: vec!["wasmcloud:httpserver".to_string()],
+                    ..Default::default()
+                },
+            )
+            .await?;
+
+        let spreadscaler = ComponentSpreadScaler::new(
+            store.clone(),
+            ComponentScalerConfig {
+                lattice_id: lattice_id.to_string(),
+                component_id: component_id.to_string(),
+                component_reference: "fakecloud.azurecr.io/echo:0.3.4".to_string(),
+                model_name: MODEL_NAME.to_string(),
+                component_config: vec![],
+            },
+            SpreadScalerProperty {
+                instances: 2,
+                spread: vec![],
+                topology_key: None,
+                min_count: None,
+                max_count: None,
+                anti_affinity: Vec::new(),
+                weight_key: None,
+                selector: Vec::new(),
+                reconcile_interval: None,
+                instances_per_host: None,
+            },
+            component_id,
+        )
+        .with_capability_check(true);
+
+        assert!(
+            spreadscaler.reconcile().await?.is_empty(),
+            "Nothing should be placed when no host has the required provider"
+        );
+        let status = spreadscaler.status.read().await.to_owned();
+        assert_eq!(status.status_type, StatusType::Failed);
+        assert!(
+            status
+                .message
+                .contains("No host satisfies required capability wasmcloud:httpserver"),
+            "Status should name the missing capability, got: {}",
+            status.message
+        );
+
+        // Once a host runs a provider for the capability, only that host is used
+        store
+            .store(
+                lattice_id,
+                "httpserver".to_string(),
+                Provider {
+                    id: "httpserver".to_string(),
+                    capabilities: vec!["wasmcloud:httpserver".to_string()],
+                    ..Default::default()
+                },
+            )
+            .await?;
+        store
+            .store(
+                lattice_id,
+                "host-two".to_string(),
+                Host {
+                    id: "host-two".to_string(),
+                    last_seen: Utc::now(),
+                    providers: HashSet::from_iter([ProviderInfo {
+                        provider_id: "httpserver".to_string(),
+                        provider_ref: String::new(),
+                        annotations: BTreeMap::new(),
+                    }]),
+                    ..Default::default()
+                },
+            )
+            .await?;
+        let commands = spreadscaler.reconcile().await?;
+        assert_eq!(commands.len(), 1);
+        assert!(commands.iter().all(|cmd| matches!(
+            cmd,
+            Command::ScaleComponent(scale) if scale.host_id == "host-two" && scale.count == 2
+        )));
+
+        Ok(())
+    }
 
-        // Ensure we handle an all around complex case
-        let complex_spread = SpreadScalerProperty {
-            instances: 103,
-            spread: vec![
-                Spread {
-                    // 9 + 1 (remainder trip)
-                    name: "ComplexOne".to_string(),
-                    requirements: BTreeMap::new(),
-                    weight: Some(42),
-                },
-                Spread {
-                    // 0
-                    name: "ComplexTwo".to_string(),
-                    requirements: BTreeMap::new(),
-                    weight: Some(3),
+    #[tokio::test]
+    async fn shares_host_capacity_fairly_between_models() -> Result<()> {
+        let lattice_id = "host_capacity";
+        let store = Arc::new(TestStore::default());
+        store
+            .store(
+                lattice_id,
+                "host".to_string(),
+                Host {
+                    id: "host".to_string(),
+                    last_seen: Utc::now(),
+                    ..Default::default()
                 },
-                Spread {
-                    // 8
-                    name: "ComplexThree".to_string(),
-                    requirements: BTreeMap::new(),
-                    weight: Some(37),
+            )
+            .await?;
+
+        let capacity = HostCapacity::new(4);
+        let scaler = |model_name: &str| {
+            ComponentSpreadScaler::new(
+                store.clone(),
+                ComponentScalerConfig {
+                    lattice_id: lattice_id.to_string(),
+                    component_id: format!("{model_name}-echo"),
+                    component_reference: format!("fakecloud.azurecr.io/{model_name}:0.1.0"),
+                    model_name: model_name.to_string(),
+                    component_config: vec![],
                 },
-                Spread {
-                    // 84 + 1 (remainder trip)
-                    name: "ComplexFour".to_string(),
-                    requirements: BTreeMap::new(),
-                    weight: Some(384),
+                SpreadScalerProperty {
+                    instances: 4,
+                    spread: vec![],
+                    topology_key: None,
+                    min_count: None,
+                    max_count: None,
+                    anti_affinity: Vec::new(),
+                    weight_key: None,
+                    selector: Vec::new(),
+                    reconcile_interval: None,
+                    instances_per_host: None,
                 },
-            ],
+                "echo",
+            )
+            .with_host_capacity(Some(&capacity))
         };
-        let complex_spread_res = compute_spread(&complex_spread);
-        assert_eq!(complex_spread_res[0].1, 85);
-        assert_eq!(complex_spread_res[1].1, 10);
-        assert_eq!(complex_spread_res[2].1, 8);
-        assert_eq!(complex_spread_res[3].1, 0);
+        let placement = |cmds: Vec<Command>| {
+            cmds.into_iter()
+                .map(|cmd| match cmd {
+                    Command::ScaleComponent(scale) => (scale.host_id, scale.count),
+                    cmd => panic!("Unexpected command {cmd:?}"),
+                })
+                .collect::<Vec<_>>()
+        };
+
+        let first = scaler("first");
+        let second = scaler("second");
+        assert_eq!(
+            placement(first.reconcile().await?),
+            vec![("host".to_string(), 2)],
+            "The model that reconciles first should only get its share of the host"
+        );
+        assert_eq!(
+            placement(second.reconcile().await?),
+            vec![("host".to_string(), 2)],
+            "The other model should get the rest of the host"
+        );
+        let status = first.status().await;
+        assert_eq!(status.status_type, StatusType::Failed);
+        assert!(
+            status.message.contains("only 2/4"),
+            "Status should explain that the model is limited by capacity, got: {}",
+            status.message
+        );
+
+        // Once the other model is gone, its share is free to use
+        drop(second);
+        assert_eq!(
+            placement(first.reconcile().await?),
+            vec![("host".to_string(), 4)]
+        );
 
         Ok(())
     }
 
+    /// A store that swaps out the only host for a new one the first time a component is read,
+    /// emulating a heartbeat landing partway through a reconcile
+    #[derive(Clone)]
+    struct RacingStore {
+        reads: Arc<TestStore>,
+        live: Arc<TestStore>,
+        lattice_id: &'static str,
+        raced: Arc<std::sync::atomic::AtomicBool>,
+        replacement: Host,
+    }
+
+    #[async_trait::async_trait]
+    impl ReadStore for RacingStore {
+        async fn get<T>(&self, lattice_id: &str, id: &str) -> Result<Option<T>, StoreError>
+        where
+            T: serde::de::DeserializeOwned + crate::storage::StateKind,
+        {
+            if !self.raced.swap(true, std::sync::atomic::Ordering::SeqCst) {
+                let current = self.live.list::<Host>(self.lattice_id).await?;
+                self.live
+                    .delete_many::<Host, _, _>(self.lattice_id, current.keys())
+                    .await?;
+                self.live
+                    .store(
+                        self.lattice_id,
+                        self.replacement.id.clone(),
+                        self.replacement.clone(),
+                    )
+                    .await?;
+            }
+            self.reads.get(lattice_id, id).await
+        }
+
+        async fn list<T>(&self, lattice_id: &str) -> Result<HashMap<String, T>, StoreError>
+        where
+            T: serde::de::DeserializeOwned + crate::storage::StateKind,
+        {
+            self.reads.list(lattice_id).await
+        }
+    }
+
+    #[async_trait::async_trait]
+    impl ConsistentRead for RacingStore {
+        async fn consistent_view(&self) -> Self {
+            RacingStore {
+                reads: self.reads.consistent_view().await,
+                ..self.clone()
+            }
+        }
+    }
+
     #[tokio::test]
-    async fn can_compute_spread_commands() -> Result<()> {
-        let lattice_id = "can_compute_spread_commands";
+    async fn reconciles_against_a_single_snapshot() -> Result<()> {
+        let lattice_id = "single_snapshot";
         let component_reference = "fakecloud.azurecr.io/echo:0.3.4".to_string();
         let component_id = "fakecloud_azurecr_io_echo_0_3_4".to_string();
-
-        let host_id1 = "HOST_ONE";
-        let host_id2 = "HOST_TWO";
-        let host_id3 = "HOST_THREE";
+        let host = |id: &str| Host {
+            components: HashMap::new(),
+            friendly_name: "hey".to_string(),
+            labels: HashMap::new(),
+            providers: HashSet::new(),
+            uptime_seconds: 123,
+            version: None,
+            id: id.to_string(),
+            last_seen: Utc::now(),
+            ..Default::default()
+        };
 
         let store = Arc::new(TestStore::default());
+        store
+            .store(lattice_id, "HOSTONE".to_string(), host("HOSTONE"))
+            .await?;
+        let racing_store = RacingStore {
+            reads: store.clone(),
+            live: store.clone(),
+            lattice_id,
+            raced: Arc::default(),
+            replacement: host("HOSTTWO"),
+        };
 
-        // Create three hosts with different labels
-        let mut host1_labels = HashMap::new();
-        host1_labels.insert("zone".to_string(), "east".to_string());
+        let spreadscaler = ComponentSpreadScaler::new(
+            racing_store,
+            ComponentScalerConfig {
+                lattice_id: lattice_id.to_string(),
+                component_id: component_id.to_string(),
+                component_reference: component_reference.to_string(),
+                model_name: MODEL_NAME.to_string(),
+                component_config: vec![],
+            },
+            SpreadScalerProperty {
+                instances: 2,
+                spread: vec![],
+                topology_key: None,
+                min_count: None,
+                max_count: None,
+                anti_affinity: Vec::new(),
+                weight_key: None,
+                selector: Vec::new(),
+                reconcile_interval: None,
+                instances_per_host: None,
+            },
+            "fake_component",
+        );
 
-        let mut host2_labels = HashMap::new();
-        host2_labels.insert("zone".to_string(), "west".to_string());
+        let cmds = spreadscaler.reconcile().await?;
+        assert_eq!(
+            cmds,
+            vec![Command::ScaleComponent(ScaleComponent {
+                component_id: component_id.to_string(),
+                reference: component_reference.to_string(),
+                host_id: "HOSTONE".to_string(),
+                count: 2,
+                model_name: MODEL_NAME.to_string(),
+                annotations: spreadscaler_annotations("default", spreadscaler.id()),
+                config: vec![]
+            })],
+            "Reconcile should only use the hosts from when it started"
+        );
+        assert!(
+            store.get::<Host>(lattice_id, "HOSTTWO").await?.is_some(),
+            "The store should have changed during the reconcile"
+        );
 
-        let mut host3_labels = HashMap::new();
-        host3_labels.insert("zone".to_string(), "central".to_string());
+        Ok(())
+    }
 
-        // Store the hosts
-        for (host_id, labels) in [
-            (host_id1, host1_labels),
-            (host_id2, host2_labels),
-            (host_id3, host3_labels),
-        ] {
+    #[tokio::test]
+    async fn reports_per_spread_readiness() -> Result<()> {
+        let lattice_id = "per_spread_readiness";
+        let component_reference = "fakecloud.azurecr.io/echo:0.3.4".to_string();
+        let component_id = "fakecloud_azurecr_io_echo_0_3_4".to_string();
+        let east_host = "NASDASDIMAREALHOSTEAST";
+        let west_host = "NASDASDIMAREALHOSTWEST";
+
+        let store = Arc::new(TestStore::default());
+
+        for (host_id, zone) in [(east_host, "east"), (west_host, "west")] {
             store
                 .store(
                     lattice_id,
                     host_id.to_string(),
                     Host {
                         components: HashMap::new(),
-                        friendly_name: format!("host_{}", host_id),
-                        labels,
+                        friendly_name: "hey".to_string(),
+                        labels: HashMap::from_iter([("zone".to_string(), zone.to_string())]),
                         providers: HashSet::new(),
                         uptime_seconds: 123,
                         version: None,
                         id: host_id.to_string(),
                         last_seen: Utc::now(),
+                        ..Default::default()
                     },
                 )
                 .await?;
         }
 
-        // Create spread requirements that map to specific hosts
-        let mut east_requirement = BTreeMap::new();
-        east_requirement.insert("zone".to_string(), "east".to_string());
-
-        let mut west_requirement = BTreeMap::new();
-        west_requirement.insert("zone".to_string(), "west".to_string());
-
-        let mut central_requirement = BTreeMap::new();
-        central_requirement.insert("zone".to_string(), "central".to_string());
-
-        let spread_config = SpreadScalerProperty {
-            instances: 103,
-            spread: vec![
-                Spread {
-                    name: "EastZone".to_string(),
-                    requirements: east_requirement, // Maps to host1
-                    weight: Some(42),
-                },
-                Spread {
-                    name: "WestZone".to_string(),
-                    requirements: west_requirement, // Maps to host2
-                    weight: Some(3),
-                },
-                Spread {
-                    name: "CentralZone".to_string(),
-                    requirements: central_requirement, // Maps to host3
-                    weight: Some(37),
-                },
-            ],
-        };
-
         let spreadscaler = ComponentSpreadScaler::new(
             store.clone(),
-            component_reference.to_string(),
-            component_id.to_string(),
-            lattice_id.to_string(),
-            MODEL_NAME.to_string(),
-            spread_config,
+            ComponentScalerConfig {
+                lattice_id: lattice_id.to_string(),
+                component_id: component_id.to_string(),
+                component_reference: component_reference.to_string(),
+                model_name: MODEL_NAME.to_string(),
+                component_config: vec![],
+            },
+            SpreadScalerProperty {
+                instances: 6,
+                spread: vec![
+                    Spread {
+                        name: "east".to_string(),
+                        requirements: BTreeMap::from_iter([(
+                            "zone".to_string(),
+                            "east".to_string(),
+                        )]),
+                        weight: Some(50),
+                    },
+                    Spread {
+                        name: "west".to_string(),
+                        requirements: BTreeMap::from_iter([(
+                            "zone".to_string(),
+                            "west".to_string(),
+                        )]),
+                        weight: Some(50),
+                    },
+                ],
+                topology_key: None,
+                min_count: None,
+                max_count: None,
+                anti_affinity: Vec::new(),
+                weight_key: None,
+                selector: Vec::new(),
+                reconcile_interval: None,
+                instances_per_host: None,
+            },
             "fake_component",
-            vec![],
         );
 
-        let cmds = spreadscaler.reconcile().await?;
-        assert_eq!(cmds.len(), 3);
-
-        // With weights 42:3:37 and total instances of 103
-        // EastZone (east) should get (52 + 1) instances
-        // WestZone (west) should get 3 instances
-        // CentralZone (central) should get (46 + 1) instances
-
-        assert!(cmds.contains(&Command::ScaleComponent(ScaleComponent {
-            component_id: component_id.to_string(),
-            reference: component_reference.to_string(),
-            host_id: host_id1.to_string(), // east zone
-            count: 53,
-            model_name: MODEL_NAME.to_string(),
-            annotations: spreadscaler_annotations("EastZone", spreadscaler.id()),
-            config: vec![]
-        })));
-
-        assert!(cmds.contains(&Command::ScaleComponent(ScaleComponent {
-            component_id: component_id.to_string(),
-            reference: component_reference.to_string(),
-            host_id: host_id2.to_string(), // west zone
-            count: 3,
-            model_name: MODEL_NAME.to_string(),
-            annotations: spreadscaler_annotations("WestZone", spreadscaler.id()),
-            config: vec![]
-        })));
+        // The east spread is fully running, the west spread has nothing running
+        store
+            .store(
+                lattice_id,
+                component_id.to_string(),
+                Component {
+                    id: component_id.to_string(),
+                    name: "Echo".to_string(),
+                    issuer: "AASDASDASDASD".to_string(),
+                    instances: HashMap::from_iter([(
+                        east_host.to_string(),
+                        HashSet::from_iter([WadmComponentInfo {
+                            annotations: spreadscaler_annotations("east", spreadscaler.id()),
+                            count: 3,
+                        }]),
+                    )]),
+                    reference: component_reference.to_string(),
+                    ..Default::default()
+                },
+            )
+            .await?;
 
-        assert!(cmds.contains(&Command::ScaleComponent(ScaleComponent {
-            component_id: component_id.to_string(),
-            reference: component_reference.to_string(),
-            host_id: host_id3.to_string(), // central zone
-            count: 47,
-            model_name: MODEL_NAME.to_string(),
-            annotations: spreadscaler_annotations("CentralZone", spreadscaler.id()),
-            config: vec![]
-        })));
+        let status = spreadscaler.status().await;
+        assert_eq!(status.status_type, StatusType::Reconciling);
+        assert!(
+            status.message.contains("spread 'east' ready 3/3"),
+            "Status should report the satisfied spread, got: {}",
+            status.message
+        );
+        assert!(
+            status.message.contains("spread 'west' compensating 0/3"),
+            "Status should report the unsatisfied spread, got: {}",
+            status.message
+        );
+        assert_eq!(
+            status.progress,
+            Some((3, 6)),
+            "Progress should count the running instances against the total desired"
+        );
 
         Ok(())
     }
@@ -928,6 +3866,14 @@ mod test {
                     weight: Some(25), // 103
                 },
             ],
+            topology_key: None,
+            min_count: None,
+            max_count: None,
+            anti_affinity: Vec::new(),
+            weight_key: None,
+            selector: Vec::new(),
+            reconcile_interval: None,
+            instances_per_host: None,
         };
 
         let blobby_spread_property = SpreadScalerProperty {
@@ -958,28 +3904,40 @@ mod test {
                     weight: Some(33), // 3
                 },
             ],
+            topology_key: None,
+            min_count: None,
+            max_count: None,
+            anti_affinity: Vec::new(),
+            weight_key: None,
+            selector: Vec::new(),
+            reconcile_interval: None,
+            instances_per_host: None,
         };
 
         let echo_spreadscaler = ComponentSpreadScaler::new(
             store.clone(),
-            echo_ref.to_string(),
-            echo_id.to_string(),
-            lattice_id.to_string(),
-            MODEL_NAME.to_string(),
+            ComponentScalerConfig {
+                lattice_id: lattice_id.to_string(),
+                component_id: echo_id.to_string(),
+                component_reference: echo_ref.to_string(),
+                model_name: MODEL_NAME.to_string(),
+                component_config: vec![],
+            },
             echo_spread_property,
             "fake_echo",
-            vec![],
         );
 
         let blobby_spreadscaler = ComponentSpreadScaler::new(
             store.clone(),
-            blobby_ref.to_string(),
-            blobby_id.to_string(),
-            lattice_id.to_string(),
-            MODEL_NAME.to_string(),
+            ComponentScalerConfig {
+                lattice_id: lattice_id.to_string(),
+                component_id: blobby_id.to_string(),
+                component_reference: blobby_ref.to_string(),
+                model_name: MODEL_NAME.to_string(),
+                component_config: vec![],
+            },
             blobby_spread_property,
             "fake_blobby",
-            vec![],
         );
 
         // STATE SETUP BEGIN
@@ -1028,6 +3986,7 @@ mod test {
                         ),
                     ]),
                     reference: echo_ref.to_string(),
+                    ..Default::default()
                 },
             )
             .await?;
@@ -1065,6 +4024,7 @@ mod test {
                         ),
                     ]),
                     reference: blobby_ref.to_string(),
+                    ..Default::default()
                 },
             )
             .await?;
@@ -1089,6 +4049,7 @@ mod test {
                     version: None,
                     id: host_id_one.to_string(),
                     last_seen: Utc::now(),
+                    ..Default::default()
                 },
             )
             .await?;
@@ -1113,6 +4074,7 @@ mod test {
                     version: None,
                     id: host_id_two.to_string(),
                     last_seen: Utc::now(),
+                    ..Default::default()
                 },
             )
             .await?;
@@ -1133,6 +4095,7 @@ mod test {
                     version: None,
                     id: host_id_three.to_string(),
                     last_seen: Utc::now(),
+                    ..Default::default()
                 },
             )
             .await?;
@@ -1204,17 +4167,27 @@ mod test {
             // Makes it so we always get at least 2 commands
             instances: 9,
             spread: Vec::new(),
+            topology_key: None,
+            min_count: None,
+            max_count: None,
+            anti_affinity: Vec::new(),
+            weight_key: None,
+            selector: Vec::new(),
+            reconcile_interval: None,
+            instances_per_host: None,
         };
 
         let spreadscaler = ComponentSpreadScaler::new(
             store.clone(),
-            component_reference.to_string(),
-            component_id.to_string(),
-            lattice_id.to_string(),
-            MODEL_NAME.to_string(),
+            ComponentScalerConfig {
+                lattice_id: lattice_id.to_string(),
+                component_id: component_id.to_string(),
+                component_reference: component_reference.to_string(),
+                model_name: MODEL_NAME.to_string(),
+                component_config: vec![],
+            },
             real_spread,
             "fake_component",
-            vec![],
         );
 
         // STATE SETUP BEGIN, ONE HOST
@@ -1234,6 +4207,7 @@ mod test {
                             version: None,
                             id: host_id.to_string(),
                             last_seen: Utc::now(),
+                            ..Default::default()
                         },
                     ),
                     (
@@ -1248,6 +4222,7 @@ mod test {
                             version: None,
                             id: host_id2.to_string(),
                             last_seen: Utc::now(),
+                            ..Default::default()
                         },
                     ),
                 ],
@@ -1279,6 +4254,7 @@ mod test {
                         ),
                     ]),
                     reference: component_reference.to_string(),
+                    ..Default::default()
                 },
             )
             .await?;
@@ -1391,17 +4367,27 @@ mod test {
                     weight: Some(33), // 3
                 },
             ],
+            topology_key: None,
+            min_count: None,
+            max_count: None,
+            anti_affinity: Vec::new(),
+            weight_key: None,
+            selector: Vec::new(),
+            reconcile_interval: None,
+            instances_per_host: None,
         };
 
         let blobby_spreadscaler = ComponentSpreadScaler::new(
             store.clone(),
-            blobby_ref.to_string(),
-            blobby_id.to_string(),
-            lattice_id.to_string(),
-            MODEL_NAME.to_string(),
+            ComponentScalerConfig {
+                lattice_id: lattice_id.to_string(),
+                component_id: blobby_id.to_string(),
+                component_reference: blobby_ref.to_string(),
+                model_name: MODEL_NAME.to_string(),
+                component_config: vec![],
+            },
             blobby_spread_property,
             "fake_blobby",
-            vec![],
         );
 
         // STATE SETUP BEGIN
@@ -1438,6 +4424,7 @@ mod test {
                         ),
                     ]),
                     reference: blobby_ref.to_string(),
+                    ..Default::default()
                 },
             )
             .await?;
@@ -1461,6 +4448,7 @@ mod test {
                     version: None,
                     id: host_id_one.to_string(),
                     last_seen: Utc::now(),
+                    ..Default::default()
                 },
             )
             .await?;
@@ -1482,6 +4470,7 @@ mod test {
                     version: None,
                     id: host_id_two.to_string(),
                     last_seen: Utc::now(),
+                    ..Default::default()
                 },
             )
             .await?;
@@ -1502,6 +4491,7 @@ mod test {
                     version: None,
                     id: host_id_three.to_string(),
                     last_seen: Utc::now(),
+                    ..Default::default()
                 },
             )
             .await?;
@@ -1581,6 +4571,8 @@ mod test {
                 lattice_id: lattice_id.to_string(),
                 inner: Event::ComponentScaled(modifying_event.clone()),
                 acker: None,
+                ack_batcher: None,
+                published: None,
             })
             .await
             .expect("should be able to handle an event");
@@ -1644,6 +4636,7 @@ mod test {
                     version: None,
                     id: "NASDASDIMAREALHOST".to_string(),
                     last_seen: Utc::now(),
+                    ..Default::default()
                 },
             ),
             (
@@ -1660,6 +4653,7 @@ mod test {
                     version: None,
                     id: "NASDASDIMAREALHOST2".to_string(),
                     last_seen: Utc::now(),
+                    ..Default::default()
                 },
             ),
             (
@@ -1676,6 +4670,7 @@ mod test {
                     version: None,
                     id: "NASDASDIMAREALHOST3".to_string(),
                     last_seen: Utc::now(),
+                    ..Default::default()
                 },
             ),
             (
@@ -1693,6 +4688,7 @@ mod test {
                     version: None,
                     id: "NASDASDIMAREALHOST4".to_string(),
                     last_seen: Utc::now(),
+                    ..Default::default()
                 },
             ),
         ]);
@@ -1738,6 +4734,7 @@ mod test {
                     version: None,
                     id: host_id_1.to_string(),
                     last_seen: Utc::now(),
+                    ..Default::default()
                 },
             )
             .await?;
@@ -1758,6 +4755,7 @@ mod test {
                     version: None,
                     id: host_id_2.to_string(),
                     last_seen: Utc::now(),
+                    ..Default::default()
                 },
             )
             .await?;
@@ -1778,6 +4776,7 @@ mod test {
                     version: None,
                     id: host_id_3.to_string(),
                     last_seen: Utc::now(),
+                    ..Default::default()
                 },
             )
             .await?;
@@ -1798,6 +4797,7 @@ mod test {
                     version: None,
                     id: host_id_4.to_string(),
                     last_seen: Utc::now(),
+                    ..Default::default()
                 },
             )
             .await?;
@@ -1821,17 +4821,27 @@ mod test {
                     weight: Some(50),
                 },
             ],
+            topology_key: None,
+            min_count: None,
+            max_count: None,
+            anti_affinity: Vec::new(),
+            weight_key: None,
+            selector: Vec::new(),
+            reconcile_interval: None,
+            instances_per_host: None,
         };
 
         let spreadscaler = ComponentSpreadScaler::new(
             store.clone(),
-            component_reference.to_string(),
-            component_id.to_string(),
-            lattice_id.to_string(),
-            MODEL_NAME.to_string(),
+            ComponentScalerConfig {
+                lattice_id: lattice_id.to_string(),
+                component_id: component_id.to_string(),
+                component_reference: component_reference.to_string(),
+                model_name: MODEL_NAME.to_string(),
+                component_config: vec![],
+            },
             spread_property,
             &component_name,
-            vec![],
         );
 
         spreadscaler.reconcile().await?;
@@ -1881,17 +4891,27 @@ mod test {
                     weight: Some(50),
                 },
             ],
+            topology_key: None,
+            min_count: None,
+            max_count: None,
+            anti_affinity: Vec::new(),
+            weight_key: None,
+            selector: Vec::new(),
+            reconcile_interval: None,
+            instances_per_host: None,
         };
 
         let spreadscaler = ComponentSpreadScaler::new(
             store.clone(),
-            component_reference.to_string(),
-            component_id.to_string(),
-            lattice_id.to_string(),
-            MODEL_NAME.to_string(),
+            ComponentScalerConfig {
+                lattice_id: lattice_id.to_string(),
+                component_id: component_id.to_string(),
+                component_reference: component_reference.to_string(),
+                model_name: MODEL_NAME.to_string(),
+                component_config: vec![],
+            },
             spread_property,
             component_name,
-            vec![],
         );
 
         // Create components with the specified labels and add them to the store
@@ -1962,6 +4982,7 @@ mod test {
                         ),
                     ]),
                     reference: component_reference.to_string(),
+                    ..Default::default()
                 },
             )
             .await?;
@@ -1983,6 +5004,7 @@ mod test {
                     version: None,
                     id: host_id_1.to_string(),
                     last_seen: Utc::now(),
+                    ..Default::default()
                 },
             )
             .await?;
@@ -2003,6 +5025,7 @@ mod test {
                     version: None,
                     id: host_id_2.to_string(),
                     last_seen: Utc::now(),
+                    ..Default::default()
                 },
             )
             .await?;
@@ -2023,6 +5046,7 @@ mod test {
                     version: None,
                     id: host_id_3.to_string(),
                     last_seen: Utc::now(),
+                    ..Default::default()
                 },
             )
             .await?;
@@ -2043,6 +5067,7 @@ mod test {
                     version: None,
                     id: host_id_4.to_string(),
                     last_seen: Utc::now(),
+                    ..Default::default()
                 },
             )
             .await?;
@@ -2060,4 +5085,95 @@ mod test {
 
         Ok(())
     }
+
+    #[tokio::test]
+    async fn count_tolerance_ignores_small_miscounts() -> Result<()> {
+        let lattice_id = "count_tolerance";
+        let component_reference = "fakecloud.azurecr.io/echo:0.3.4".to_string();
+        let component_id = "fakecloud_azurecr_io_echo_0_3_4".to_string();
+        let host_id = "NASDASDIMAREALHOST";
+
+        let store = Arc::new(TestStore::default());
+        store
+            .store(
+                lattice_id,
+                host_id.to_string(),
+                Host {
+                    id: host_id.to_string(),
+                    last_seen: Utc::now(),
+                    ..Default::default()
+                },
+            )
+            .await?;
+
+        let spreadscaler = ComponentSpreadScaler::new(
+            store.clone(),
+            ComponentScalerConfig {
+                lattice_id: lattice_id.to_string(),
+                component_id: component_id.to_string(),
+                component_reference: component_reference.to_string(),
+                model_name: MODEL_NAME.to_string(),
+                component_config: vec![],
+            },
+            SpreadScalerProperty {
+                instances: 3,
+                spread: vec![],
+                topology_key: None,
+                min_count: None,
+                max_count: None,
+                anti_affinity: Vec::new(),
+                weight_key: None,
+                selector: Vec::new(),
+                reconcile_interval: None,
+                instances_per_host: None,
+            },
+            "fake_component",
+        )
+        .with_count_tolerance(1);
+        let annotations = spreadscaler_annotations("default", spreadscaler.id());
+        let running = |count| Component {
+            id: component_id.to_string(),
+            instances: HashMap::from_iter([(
+                host_id.to_string(),
+                HashSet::from_iter([WadmComponentInfo {
+                    annotations: annotations.clone(),
+                    count,
+                }]),
+            )]),
+            reference: component_reference.to_string(),
+            ..Default::default()
+        };
+
+        // One instance short of the desired count is within the tolerance
+        store
+            .store(lattice_id, component_id.to_string(), running(2))
+            .await?;
+        assert!(
+            spreadscaler.reconcile().await?.is_empty(),
+            "No commands should be issued within the tolerance"
+        );
+        assert_eq!(
+            spreadscaler.status().await.status_type,
+            StatusType::Deployed
+        );
+
+        // Two short is outside of it
+        store
+            .store(lattice_id, component_id.to_string(), running(1))
+            .await?;
+        assert_eq!(
+            spreadscaler.reconcile().await?,
+            vec![Command::ScaleComponent(ScaleComponent {
+                component_id: component_id.to_string(),
+                reference: component_reference.to_string(),
+                host_id: host_id.to_string(),
+                count: 3,
+                model_name: MODEL_NAME.to_string(),
+                annotations: annotations.clone(),
+                config: vec![]
+            })]
+        );
+
+        Ok(())
+    }
 }
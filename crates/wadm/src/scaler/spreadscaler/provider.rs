@@ -1,6 +1,7 @@
 use std::{
     cmp::Ordering,
     collections::{BTreeMap, HashMap},
+    time::{Duration, Instant},
 };
 
 use anyhow::Result;
@@ -16,15 +17,17 @@ use crate::{
     commands::{Command, StartProvider, StopProvider},
     events::{
         Event, HostHeartbeat, HostStarted, HostStopped, ProviderHealthCheckFailed,
-        ProviderHealthCheckInfo, ProviderHealthCheckPassed, ProviderInfo, ProviderStarted,
-        ProviderStopped,
+        ProviderHealthCheckInfo, ProviderHealthCheckPassed, ProviderInfo, ProviderStartFailed,
+        ProviderStarted, ProviderStopped,
     },
     scaler::{
         compute_id_sha256,
+        shared::provider_resource,
         spreadscaler::{
-            compute_ineligible_hosts, compute_spread, eligible_hosts, spreadscaler_annotations,
+            apply_version_skew, compute_ineligible_hosts, compute_spread, eligible_hosts,
+            remove_quiet_hosts, spreadscaler_annotations, version_skew,
         },
-        Scaler,
+        ManagedInstances, ReconcilePlan, Scaler,
     },
     storage::{Host, Provider, ProviderStatus, ReadStore},
     SCALER_KEY,
@@ -32,6 +35,32 @@ use crate::{
 
 use super::SPREAD_SCALER_KIND;
 
+pub(crate) const UNHEALTHY_PROVIDER_PREFIX: &str = "Unhealthy provider on";
+
+/// Returns a status message naming every host the provider has failed on, or `None` if it is
+/// healthy everywhere
+pub(crate) fn unhealthy_provider_message(provider: &Provider) -> Option<String> {
+    let mut failed = provider
+        .hosts
+        .iter()
+        .filter(|(_, host)| host.status == ProviderStatus::Failed)
+        .map(|(host_id, _)| host_id.as_str())
+        .collect::<Vec<_>>();
+    if failed.is_empty() {
+        return None;
+    }
+    failed.sort_unstable();
+    let placements = failed
+        .iter()
+        .map(|host_id| format!("provider {} on host {host_id} failed", provider.id))
+        .collect::<Vec<_>>();
+    Some(format!(
+        "{UNHEALTHY_PROVIDER_PREFIX} {} host(s): {}",
+        failed.len(),
+        placements.join(", ")
+    ))
+}
+
 /// Config for a ProviderSpreadConfig
 #[derive(Clone)]
 pub struct ProviderSpreadConfig {
@@ -63,6 +92,15 @@ pub struct ProviderSpreadScaler<S: ReadStore + Send + Sync> {
     provider_id: OnceCell<String>,
     id: String,
     status: RwLock<StatusInfo>,
+    /// Whether running more than one version of the provider at a time is a failure
+    uniform_versions: bool,
+    /// How long after a host starts before the provider can be placed on it
+    host_quiet_period: Duration,
+    /// How long to avoid a host after the provider failed to start on it
+    start_failure_cooldown: Duration,
+    /// When the provider last failed to start on each host, used to enforce the start failure
+    /// cooldown
+    failed_starts: RwLock<HashMap<String, Instant>>,
 }
 
 #[async_trait]
@@ -75,13 +113,42 @@ impl<S: ReadStore + Send + Sync + Clone> Scaler for ProviderSpreadScaler<S> {
         SPREAD_SCALER_KIND
     }
 
+    fn reconcile_interval(&self) -> Option<Duration> {
+        self.config
+            .spread_config
+            .reconcile_interval
+            .filter(|secs| *secs > 0)
+            .map(Duration::from_secs)
+    }
+
+    fn shared_resource(&self) -> Option<String> {
+        Some(provider_resource(
+            &self.config.provider_reference,
+            &self.config.provider_id,
+        ))
+    }
+
     fn name(&self) -> String {
         self.config.provider_id.to_string()
     }
 
     async fn status(&self) -> StatusInfo {
         let _ = self.reconcile().await;
-        self.status.read().await.to_owned()
+        let status = self.status.read().await.to_owned();
+        if status.status_type != StatusType::Deployed {
+            return status;
+        }
+        // Reconciling only checks where the provider is running, so a placement that has failed
+        // its health check still needs to be reported
+        match self
+            .store
+            .get::<Provider>(&self.config.lattice_id, &self.config.provider_id)
+            .await
+        {
+            Ok(Some(provider)) => unhealthy_provider_message(&provider)
+                .map_or(status, |message| StatusInfo::failed(&message)),
+            _ => status,
+        }
     }
 
     #[instrument(level = "debug", skip_all, fields(scaler_id = %self.id))]
@@ -119,6 +186,22 @@ impl<S: ReadStore + Send + Sync + Clone> Scaler for ProviderSpreadScaler<S> {
             {
                 self.reconcile().await
             }
+            // Remember where the provider failed to start so the next reconcile tries another host.
+            // A retry is already scheduled by the backoff, so there is nothing to do right away
+            Event::ProviderStartFailed(ProviderStartFailed {
+                provider_id,
+                host_id,
+                ..
+            }) if provider_id == &self.config.provider_id
+                && !self.start_failure_cooldown.is_zero() =>
+            {
+                trace!(%host_id, "Provider failed to start, avoiding host for the cooldown");
+                self.failed_starts
+                    .write()
+                    .await
+                    .insert(host_id.to_owned(), Instant::now());
+                Ok(Vec::new())
+            }
             // perform status updates for health check events
             Event::ProviderHealthCheckFailed(ProviderHealthCheckFailed {
                 data: ProviderHealthCheckInfo { provider_id, .. },
@@ -131,26 +214,18 @@ impl<S: ReadStore + Send + Sync + Clone> Scaler for ProviderSpreadScaler<S> {
                     .get::<Provider>(&self.config.lattice_id, &self.config.provider_id)
                     .await?;
 
-                let unhealthy_providers = provider.map_or(0, |p| {
-                    p.hosts
-                        .values()
-                        .filter(|s| *s == &ProviderStatus::Failed)
-                        .count()
-                });
+                let unhealthy = provider.as_ref().and_then(unhealthy_provider_message);
                 let status = self.status.read().await.to_owned();
                 // update health status of scaler
-                if let Some(status) = match (status, unhealthy_providers > 0) {
+                if let Some(status) = match (status, unhealthy) {
                     // scaler is deployed but contains unhealthy providers
                     (
                         StatusInfo {
                             status_type: StatusType::Deployed,
                             ..
                         },
-                        true,
-                    ) => Some(StatusInfo::failed(&format!(
-                        "Unhealthy provider on {} host(s)",
-                        unhealthy_providers
-                    ))),
+                        Some(message),
+                    ) => Some(StatusInfo::failed(&message)),
                     // scaler can become unhealthy only if it was previously deployed
                     // once scaler becomes healthy again revert back to deployed state
                     // this is a workaround to detect unhealthy status until
@@ -159,9 +234,10 @@ impl<S: ReadStore + Send + Sync + Clone> Scaler for ProviderSpreadScaler<S> {
                         StatusInfo {
                             status_type: StatusType::Failed,
                             message,
+                            ..
                         },
-                        false,
-                    ) if message.starts_with("Unhealthy provider on") => {
+                        None,
+                    ) if message.starts_with(UNHEALTHY_PROVIDER_PREFIX) => {
                         Some(StatusInfo::deployed(""))
                     }
                     // don't update status if scaler is not deployed
@@ -179,10 +255,28 @@ impl<S: ReadStore + Send + Sync + Clone> Scaler for ProviderSpreadScaler<S> {
     }
 
     #[instrument(level = "debug", skip_all, fields(provider_ref = %self.config.provider_reference, provider_id = %self.config.provider_id, scaler_id = %self.id))]
-    async fn reconcile(&self) -> Result<Vec<Command>> {
-        let hosts = self.store.list::<Host>(&self.config.lattice_id).await?;
+    async fn diff(&self) -> Result<ReconcilePlan> {
+        let mut hosts = self.store.list::<Host>(&self.config.lattice_id).await?;
         let provider_id = &self.config.provider_id;
         let provider_ref = &self.config.provider_reference;
+        let provider = self
+            .store
+            .get::<Provider>(&self.config.lattice_id, provider_id)
+            .await?;
+        let managed =
+            ManagedInstances::of_provider(&self.id, provider_id, &hosts, provider.as_ref());
+        remove_quiet_hosts(&mut hosts, self.host_quiet_period, |host| {
+            host.providers
+                .iter()
+                .any(|provider| &provider.provider_id == provider_id)
+        });
+        let skew = version_skew(
+            hosts
+                .values()
+                .flat_map(|host| host.providers.iter())
+                .filter(|provider| &provider.provider_id == provider_id)
+                .map(|provider| provider.provider_ref.as_str()),
+        );
 
         let ineligible_hosts = compute_ineligible_hosts(
             &hosts,
@@ -193,8 +287,8 @@ impl<S: ReadStore + Send + Sync + Clone> Scaler for ProviderSpreadScaler<S> {
         );
         // Remove any providers that are managed by this scaler and running on ineligible hosts
         let remove_ineligible: Vec<Command> = ineligible_hosts
-            .iter()
-            .filter_map(|(_host_id, host)| {
+            .values()
+            .filter_map(|host| {
                 if host
                     .providers
                     .get(&ProviderInfo {
@@ -228,15 +322,41 @@ impl<S: ReadStore + Send + Sync + Clone> Scaler for ProviderSpreadScaler<S> {
             );
             trace!(?status, "Updating scaler status");
             *self.status.write().await = status;
-            return Ok(remove_ineligible);
+            return Ok(managed.plan(remove_ineligible));
         }
 
+        let avoided_hosts = self.avoided_hosts().await;
+
+        // Hosts only report whether they run the provider, so the number of instances on each host
+        // comes from the stored provider
+        let per_host = self.instances_per_host();
+        let instance_counts: HashMap<String, usize> = provider
+            .map(|provider| {
+                provider
+                    .hosts
+                    .into_iter()
+                    .map(|(host_id, host)| (host_id, host.instances))
+                    .collect()
+            })
+            .unwrap_or_default();
+
         let mut spread_status = vec![];
 
         let commands = self
             .spread_requirements
             .iter()
             .flat_map(|(spread, count)| {
+                if per_host > 1 {
+                    let (commands, failure) = self.multi_instance_commands(
+                        spread,
+                        *count,
+                        &hosts,
+                        &instance_counts,
+                        &avoided_hosts,
+                    );
+                    spread_status.extend(failure);
+                    return commands;
+                }
                 let eligible_hosts = eligible_hosts(&hosts, spread);
                 let eligible_count = eligible_hosts.len();
                 // Partition hosts into ones running this provider (no matter what is running it), and others
@@ -304,12 +424,12 @@ impl<S: ReadStore + Send + Sync + Clone> Scaler for ProviderSpreadScaler<S> {
                         // Take `num_to_start` commands from this iterator
                         let commands = other
                             .into_iter()
-                            .filter(|(_host_id, host)| {
+                            .filter(|(host_id, host)| {
                                 !host.providers.contains(&ProviderInfo {
                                     provider_id: provider_id.to_string(),
                                     provider_ref: provider_ref.to_string(),
                                     annotations: BTreeMap::default(),
-                                })
+                                }) && !avoided_hosts.contains(*host_id)
                             })
                             .map(|(_host_id, host)| {
                                 Command::StartProvider(StartProvider {
@@ -342,13 +462,23 @@ impl<S: ReadStore + Send + Sync + Clone> Scaler for ProviderSpreadScaler<S> {
 
         trace!(?commands, "Calculated commands for provider scaler");
 
+        // Starts are only ever moved away from failed hosts, so they are only worth mentioning
+        // while something still needs to be started
+        let retrying = (!avoided_hosts.is_empty() && !commands.is_empty()).then(|| {
+            format!(
+                ", retrying away from host(s) where it failed to start: {}",
+                avoided_hosts.join(", ")
+            )
+        });
         let status = match (spread_status.is_empty(), commands.is_empty()) {
             // No failures, no commands, scaler satisfied
             (true, true) => StatusInfo::deployed(""),
             // No failures, commands generated, scaler is reconciling
-            (true, false) => {
-                StatusInfo::reconciling(&format!("Scaling provider on {} host(s)", commands.len()))
-            }
+            (true, false) => StatusInfo::reconciling(&format!(
+                "Scaling provider on {} host(s){}",
+                commands.len(),
+                retrying.unwrap_or_default()
+            )),
             // Failures occurred, scaler is in a failed state
             (false, _) => StatusInfo::failed(
                 &spread_status
@@ -358,10 +488,15 @@ impl<S: ReadStore + Send + Sync + Clone> Scaler for ProviderSpreadScaler<S> {
                     .join(" "),
             ),
         };
+        let status = apply_version_skew(status, skew, self.uniform_versions);
         trace!(?status, "Updating scaler status");
         *self.status.write().await = status;
 
-        Ok(commands)
+        Ok(managed.plan(commands))
+    }
+
+    async fn reconcile(&self) -> Result<Vec<Command>> {
+        self.diff().await.map(ReconcilePlan::into_commands)
     }
 
     #[instrument(level = "trace", skip_all, fields(name = %self.config.model_name))]
@@ -377,6 +512,11 @@ impl<S: ReadStore + Send + Sync + Clone> Scaler for ProviderSpreadScaler<S> {
             provider_id: self.provider_id.clone(),
             id: self.id.clone(),
             status: RwLock::new(StatusInfo::reconciling("")),
+            uniform_versions: self.uniform_versions,
+            host_quiet_period: self.host_quiet_period,
+            // Cleanup never starts anything, so there is nothing to avoid
+            start_failure_cooldown: Duration::ZERO,
+            failed_starts: RwLock::default(),
         };
 
         cleanerupper.reconcile().await
@@ -411,8 +551,158 @@ impl<S: ReadStore + Send + Sync> ProviderSpreadScaler<S> {
             config,
             id,
             status: RwLock::new(StatusInfo::reconciling("")),
+            uniform_versions: false,
+            host_quiet_period: Duration::ZERO,
+            start_failure_cooldown: Duration::ZERO,
+            failed_starts: RwLock::default(),
         }
     }
+
+    /// Configures how long to avoid a host after the provider failed to start on it. While a host
+    /// is avoided, the provider is started on a different eligible host instead, if there is one.
+    /// Disabled by default
+    pub fn with_start_failure_cooldown(mut self, cooldown: Duration) -> Self {
+        self.start_failure_cooldown = cooldown;
+        self
+    }
+
+    /// The most instances of the provider that can run on each host
+    fn instances_per_host(&self) -> usize {
+        self.config
+            .spread_config
+            .instances_per_host
+            .unwrap_or(1)
+            .max(1)
+    }
+
+    /// Computes the commands to run `count` instances of the provider for the given spread when a
+    /// host can run more than one instance. Instances are started on the eligible hosts running the
+    /// fewest instances first, so they are spread across hosts before any host runs more than one,
+    /// and are stopped from the hosts running the most. Like with a single instance per host,
+    /// instances started by anything else count towards the spread but are never stopped. Returns
+    /// a failed status if there isn't room for every instance on the eligible hosts
+    fn multi_instance_commands(
+        &self,
+        spread: &Spread,
+        count: usize,
+        hosts: &HashMap<String, Host>,
+        instance_counts: &HashMap<String, usize>,
+        avoided_hosts: &[String],
+    ) -> (Vec<Command>, Option<StatusInfo>) {
+        let provider_id = &self.config.provider_id;
+        let annotations = spreadscaler_annotations(&spread.name, &self.id);
+        let per_host = self.instances_per_host();
+
+        // The number of instances on each eligible host, and whether they belong to this spread.
+        // Sorted by host ID so the same hosts are picked every time
+        let placements = eligible_hosts(hosts, spread)
+            .into_iter()
+            .map(|(host_id, host)| {
+                let running = host
+                    .providers
+                    .iter()
+                    .find(|provider| &provider.provider_id == provider_id);
+                let instances = running
+                    .map(|_| instance_counts.get(host_id).copied().unwrap_or(1))
+                    .unwrap_or_default();
+                let owned = running.is_some_and(|provider| {
+                    annotations
+                        .iter()
+                        .all(|(k, v)| provider.annotations.get(k) == Some(v))
+                });
+                (host_id.as_str(), (instances, owned))
+            })
+            .collect::<BTreeMap<_, _>>();
+        let current = placements
+            .values()
+            .map(|(instances, _)| instances)
+            .sum::<usize>();
+        trace!(%current, expected = %count, %per_host, %provider_id, "Calculated running provider instances, reconciling with expected count");
+
+        let mut commands = Vec::new();
+        let mut placements = placements;
+        match current.cmp(&count) {
+            Ordering::Greater => {
+                for _ in count..current {
+                    let Some((host_id, (instances, _))) = placements
+                        .iter_mut()
+                        .filter(|(_, (instances, owned))| *owned && *instances > 0)
+                        .max_by_key(|(_, (instances, _))| *instances)
+                    else {
+                        break;
+                    };
+                    *instances -= 1;
+                    commands.push(Command::StopProvider(StopProvider {
+                        provider_id: provider_id.to_owned(),
+                        host_id: host_id.to_string(),
+                        model_name: self.config.model_name.to_owned(),
+                        annotations: annotations.clone(),
+                    }));
+                }
+                (commands, None)
+            }
+            Ordering::Less => {
+                for _ in current..count {
+                    let Some((host_id, (instances, owned))) = placements
+                        .iter_mut()
+                        .filter(|(host_id, (instances, owned))| {
+                            (*owned || *instances == 0)
+                                && *instances < per_host
+                                && !avoided_hosts.iter().any(|avoided| avoided == *host_id)
+                        })
+                        .min_by_key(|(_, (instances, _))| *instances)
+                    else {
+                        break;
+                    };
+                    *instances += 1;
+                    *owned = true;
+                    commands.push(Command::StartProvider(StartProvider {
+                        reference: self.config.provider_reference.to_owned(),
+                        provider_id: provider_id.to_owned(),
+                        host_id: host_id.to_string(),
+                        model_name: self.config.model_name.to_owned(),
+                        annotations: annotations.clone(),
+                        config: self.config.provider_config.clone(),
+                    }));
+                }
+                let failure = (current + commands.len() < count).then(|| {
+                    StatusInfo::failed(&format!(
+                        "Could not satisfy spread {} for {}, room for {}/{} instances on eligible hosts.",
+                        spread.name,
+                        self.config.provider_reference,
+                        current + commands.len(),
+                        count
+                    ))
+                });
+                (commands, failure)
+            }
+            Ordering::Equal => (commands, None),
+        }
+    }
+
+    /// Returns the sorted IDs of hosts the provider recently failed to start on, forgetting any
+    /// failures older than the cooldown
+    async fn avoided_hosts(&self) -> Vec<String> {
+        let mut failed_starts = self.failed_starts.write().await;
+        failed_starts.retain(|_, failed_at| failed_at.elapsed() < self.start_failure_cooldown);
+        let mut hosts = failed_starts.keys().cloned().collect::<Vec<_>>();
+        hosts.sort_unstable();
+        hosts
+    }
+
+    /// Configures how long after a host starts before the provider can be placed on it, giving the
+    /// host time to finish starting up. Disabled by default
+    pub fn with_host_quiet_period(mut self, quiet_period: Duration) -> Self {
+        self.host_quiet_period = quiet_period;
+        self
+    }
+
+    /// Configures whether running more than one version of the provider at the same time marks
+    /// the scaler as failed. Version skew is always reported in the status message
+    pub fn with_uniform_versions(mut self, required: bool) -> Self {
+        self.uniform_versions = required;
+        self
+    }
 }
 
 #[cfg(test)]
@@ -429,12 +719,13 @@ mod test {
     use crate::{
         commands::{Command, StartProvider, StopProvider},
         events::ProviderInfo,
+        scaler::{configscaler::ConfigScaler, BackoffWrapper},
         scaler::{
             spreadscaler::{provider::ProviderSpreadScaler, spreadscaler_annotations},
             Scaler,
         },
-        storage::{Host, Provider, ProviderStatus, Store},
-        test_util::TestStore,
+        storage::{Host, Provider, ProviderHostStatus, ProviderStatus, Store},
+        test_util::{NoopPublisher, TestLatticeSource, TestStore},
     };
 
     use super::*;
@@ -451,6 +742,14 @@ mod test {
             spread_config: SpreadScalerProperty {
                 instances: 1,
                 spread: vec![],
+                topology_key: None,
+                min_count: None,
+                max_count: None,
+                anti_affinity: Vec::new(),
+                weight_key: None,
+                selector: Vec::new(),
+                reconcile_interval: None,
+                instances_per_host: None,
             },
             provider_config: vec![],
         };
@@ -466,6 +765,14 @@ mod test {
             spread_config: SpreadScalerProperty {
                 instances: 1,
                 spread: vec![],
+                topology_key: None,
+                min_count: None,
+                max_count: None,
+                anti_affinity: Vec::new(),
+                weight_key: None,
+                selector: Vec::new(),
+                reconcile_interval: None,
+                instances_per_host: None,
             },
             provider_config: vec!["foobar".to_string()],
         };
@@ -506,6 +813,7 @@ mod test {
                     version: None,
                     id: host_id_one.to_string(),
                     last_seen: Utc::now(),
+                    ..Default::default()
                 },
             )
             .await?;
@@ -526,6 +834,7 @@ mod test {
                     version: None,
                     id: host_id_two.to_string(),
                     last_seen: Utc::now(),
+                    ..Default::default()
                 },
             )
             .await?;
@@ -540,6 +849,7 @@ mod test {
                     issuer: "issuer".to_string(),
                     reference: provider_ref.to_string(),
                     hosts: HashMap::new(),
+                    capabilities: Vec::new(),
                 },
             )
             .await?;
@@ -559,6 +869,14 @@ mod test {
                     weight: Some(100),
                 },
             ],
+            topology_key: None,
+            min_count: None,
+            max_count: None,
+            anti_affinity: Vec::new(),
+            weight_key: None,
+            selector: Vec::new(),
+            reconcile_interval: None,
+            instances_per_host: None,
         };
 
         let spreadscaler = ProviderSpreadScaler::new(
@@ -670,6 +988,14 @@ mod test {
                     weight: Some(2),
                 },
             ],
+            topology_key: None,
+            min_count: None,
+            max_count: None,
+            anti_affinity: Vec::new(),
+            weight_key: None,
+            selector: Vec::new(),
+            reconcile_interval: None,
+            instances_per_host: None,
         };
 
         let spreadscaler = ProviderSpreadScaler::new(
@@ -706,6 +1032,7 @@ mod test {
                     version: None,
                     id: host_id_one.to_string(),
                     last_seen: Utc::now(),
+                    ..Default::default()
                 },
             )
             .await?;
@@ -726,6 +1053,7 @@ mod test {
                     version: None,
                     id: host_id_two.to_string(),
                     last_seen: Utc::now(),
+                    ..Default::default()
                 },
             )
             .await?;
@@ -745,6 +1073,7 @@ mod test {
                     version: None,
                     id: host_id_three.to_string(),
                     last_seen: Utc::now(),
+                    ..Default::default()
                 },
             )
             .await?;
@@ -769,6 +1098,7 @@ mod test {
                     version: None,
                     id: host_id_four.to_string(),
                     last_seen: Utc::now(),
+                    ..Default::default()
                 },
             )
             .await?;
@@ -783,9 +1113,10 @@ mod test {
                     issuer: "issuer".to_string(),
                     reference: provider_ref.to_string(),
                     hosts: HashMap::from_iter([
-                        (host_id_one.to_string(), ProviderStatus::Running),
-                        (host_id_four.to_string(), ProviderStatus::Running),
+                        (host_id_one.to_string(), ProviderStatus::Running.into()),
+                        (host_id_four.to_string(), ProviderStatus::Running.into()),
                     ]),
+                    capabilities: Vec::new(),
                 },
             )
             .await?;
@@ -920,6 +1251,7 @@ mod test {
                     version: None,
                     id: host_id_one.to_string(),
                     last_seen: Utc::now(),
+                    ..Default::default()
                 },
             )
             .await?;
@@ -946,6 +1278,7 @@ mod test {
                     version: None,
                     id: host_id_two.to_string(),
                     last_seen: Utc::now(),
+                    ..Default::default()
                 },
             )
             .await?;
@@ -960,6 +1293,7 @@ mod test {
                     issuer: "issuer".to_string(),
                     reference: provider_ref.to_string(),
                     hosts: HashMap::new(),
+                    capabilities: Vec::new(),
                 },
             )
             .await?;
@@ -979,6 +1313,14 @@ mod test {
                     weight: Some(100),
                 },
             ],
+            topology_key: None,
+            min_count: None,
+            max_count: None,
+            anti_affinity: Vec::new(),
+            weight_key: None,
+            selector: Vec::new(),
+            reconcile_interval: None,
+            instances_per_host: None,
         };
 
         let spreadscaler = ProviderSpreadScaler::new(
@@ -1057,6 +1399,7 @@ mod test {
                     version: None,
                     id: host_id_one.to_string(),
                     last_seen: Utc::now(),
+                    ..Default::default()
                 },
             )
             .await?;
@@ -1083,6 +1426,7 @@ mod test {
                     version: None,
                     id: host_id_two.to_string(),
                     last_seen: Utc::now(),
+                    ..Default::default()
                 },
             )
             .await?;
@@ -1097,6 +1441,7 @@ mod test {
                     issuer: "issuer".to_string(),
                     reference: provider_ref.to_string(),
                     hosts: HashMap::new(),
+                    capabilities: Vec::new(),
                 },
             )
             .await?;
@@ -1116,6 +1461,14 @@ mod test {
                     weight: Some(100),
                 },
             ],
+            topology_key: None,
+            min_count: None,
+            max_count: None,
+            anti_affinity: Vec::new(),
+            weight_key: None,
+            selector: Vec::new(),
+            reconcile_interval: None,
+            instances_per_host: None,
         };
 
         let spreadscaler = ProviderSpreadScaler::new(
@@ -1169,6 +1522,7 @@ mod test {
                     version: None,
                     id: host_id_one.to_string(),
                     last_seen: Utc::now(),
+                    ..Default::default()
                 },
             )
             .await?;
@@ -1181,6 +1535,14 @@ mod test {
                 requirements: BTreeMap::from_iter([("cloud".to_string(), "fake".to_string())]),
                 weight: Some(100),
             }],
+            topology_key: None,
+            min_count: None,
+            max_count: None,
+            anti_affinity: Vec::new(),
+            weight_key: None,
+            selector: Vec::new(),
+            reconcile_interval: None,
+            instances_per_host: None,
         };
 
         let spreadscaler = ProviderSpreadScaler::new(
@@ -1218,6 +1580,7 @@ mod test {
                     version: None,
                     id: host_id_two.to_string(),
                     last_seen: Utc::now(),
+                    ..Default::default()
                 },
             )
             .await?;
@@ -1232,6 +1595,7 @@ mod test {
                     issuer: "issuer".to_string(),
                     reference: provider_ref.to_string(),
                     hosts: HashMap::new(),
+                    capabilities: Vec::new(),
                 },
             )
             .await?;
@@ -1297,6 +1661,7 @@ mod test {
                     version: None,
                     id: host_id_one.to_string(),
                     last_seen: Utc::now(),
+                    ..Default::default()
                 },
             )
             .await?;
@@ -1309,6 +1674,14 @@ mod test {
                 requirements: BTreeMap::from_iter([("cloud".to_string(), "fake".to_string())]),
                 weight: Some(100),
             }],
+            topology_key: None,
+            min_count: None,
+            max_count: None,
+            anti_affinity: Vec::new(),
+            weight_key: None,
+            selector: Vec::new(),
+            reconcile_interval: None,
+            instances_per_host: None,
         };
 
         let spreadscaler = ProviderSpreadScaler::new(
@@ -1346,6 +1719,7 @@ mod test {
                     version: None,
                     id: host_id_two.to_string(),
                     last_seen: Utc::now(),
+                    ..Default::default()
                 },
             )
             .await?;
@@ -1360,9 +1734,10 @@ mod test {
                     issuer: "issuer".to_string(),
                     reference: provider_ref.to_string(),
                     hosts: HashMap::from([
-                        (host_id_one.to_string(), ProviderStatus::Failed),
-                        (host_id_two.to_string(), ProviderStatus::Running),
+                        (host_id_one.to_string(), ProviderStatus::Failed.into()),
+                        (host_id_two.to_string(), ProviderStatus::Running.into()),
                     ]),
+                    capabilities: Vec::new(),
                 },
             )
             .await?;
@@ -1389,9 +1764,10 @@ mod test {
                     issuer: "issuer".to_string(),
                     reference: provider_ref.to_string(),
                     hosts: HashMap::from([
-                        (host_id_one.to_string(), ProviderStatus::Pending),
-                        (host_id_two.to_string(), ProviderStatus::Running),
+                        (host_id_one.to_string(), ProviderStatus::Pending.into()),
+                        (host_id_two.to_string(), ProviderStatus::Running.into()),
                     ]),
+                    capabilities: Vec::new(),
                 },
             )
             .await?;
@@ -1446,6 +1822,7 @@ mod test {
                     version: None,
                     id: host_id_one.to_string(),
                     last_seen: Utc::now(),
+                    ..Default::default()
                 },
             )
             .await?;
@@ -1458,6 +1835,14 @@ mod test {
                 requirements: BTreeMap::from_iter([("cloud".to_string(), "fake".to_string())]),
                 weight: Some(100),
             }],
+            topology_key: None,
+            min_count: None,
+            max_count: None,
+            anti_affinity: Vec::new(),
+            weight_key: None,
+            selector: Vec::new(),
+            reconcile_interval: None,
+            instances_per_host: None,
         };
 
         let spreadscaler = ProviderSpreadScaler::new(
@@ -1495,6 +1880,7 @@ mod test {
                     version: None,
                     id: host_id_two.to_string(),
                     last_seen: Utc::now(),
+                    ..Default::default()
                 },
             )
             .await?;
@@ -1509,9 +1895,10 @@ mod test {
                     issuer: "issuer".to_string(),
                     reference: provider_ref.to_string(),
                     hosts: HashMap::from([
-                        (host_id_one.to_string(), ProviderStatus::Failed),
-                        (host_id_two.to_string(), ProviderStatus::Running),
+                        (host_id_one.to_string(), ProviderStatus::Failed.into()),
+                        (host_id_two.to_string(), ProviderStatus::Running.into()),
                     ]),
+                    capabilities: Vec::new(),
                 },
             )
             .await?;
@@ -1528,10 +1915,247 @@ mod test {
             ))
             .await?;
 
+        let unhealthy = StatusInfo::failed(&format!(
+            "Unhealthy provider on 1 host(s): provider {provider_id} on host {host_id_one} failed"
+        ));
+        assert_eq!(spreadscaler.status.read().await.to_owned(), unhealthy);
         assert_eq!(
-            spreadscaler.status.read().await.to_owned(),
-            StatusInfo::failed("Unhealthy provider on 1 host(s)")
+            spreadscaler.status().await,
+            unhealthy,
+            "The failed placement should be named even after reconciling"
         );
         Ok(())
     }
+
+    #[tokio::test]
+    async fn retries_failed_start_on_another_host() -> Result<()> {
+        let lattice_id = "provider_start_retry";
+        let provider_ref = "fakecloud.azurecr.io/provider:3.2.1".to_string();
+        let provider_id = "fakecloud_azurecr_io_provider_3_2_1".to_string();
+
+        let store = Arc::new(TestStore::default());
+        for host_id in ["NASDASDIMAREALHOSTONE", "NASDASDIMAREALHOSTTWO"] {
+            store
+                .store(
+                    lattice_id,
+                    host_id.to_string(),
+                    Host {
+                        id: host_id.to_string(),
+                        last_seen: Utc::now(),
+                        ..Default::default()
+                    },
+                )
+                .await?;
+        }
+
+        let scaler = BackoffWrapper::new(
+            ProviderSpreadScaler::new(
+                store.clone(),
+                ProviderSpreadConfig {
+                    lattice_id: lattice_id.to_string(),
+                    provider_reference: provider_ref.clone(),
+                    provider_id: provider_id.clone(),
+                    model_name: MODEL_NAME.to_string(),
+                    spread_config: SpreadScalerProperty {
+                        instances: 1,
+                        spread: vec![],
+                        topology_key: None,
+                        min_count: None,
+                        max_count: None,
+                        anti_affinity: Vec::new(),
+                        weight_key: None,
+                        selector: Vec::new(),
+                        reconcile_interval: None,
+                        instances_per_host: None,
+                    },
+                    provider_config: vec![],
+                },
+                "fake_provider",
+            )
+            .with_start_failure_cooldown(Duration::from_secs(60)),
+            NoopPublisher,
+            Vec::<ConfigScaler<TestLatticeSource>>::new(),
+            Vec::new(),
+            "doesntmatter",
+            MODEL_NAME,
+            None,
+            Some(Duration::from_millis(10)),
+            None,
+        );
+        let started_on = |commands: Vec<Command>| match commands.as_slice() {
+            [Command::StartProvider(start)] => start.host_id.clone(),
+            _ => panic!("Expected a single start provider command, got {commands:?}"),
+        };
+
+        let failed_host = started_on(scaler.reconcile().await?);
+        scaler
+            .handle_event(&Event::ProviderStartFailed(ProviderStartFailed {
+                error: "failed to pull image".to_string(),
+                provider_id: provider_id.clone(),
+                provider_ref: provider_ref.clone(),
+                host_id: failed_host.clone(),
+            }))
+            .await?;
+
+        // Wait out the backoff from the failure
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        let retried_host = started_on(scaler.reconcile().await?);
+        assert_ne!(
+            retried_host, failed_host,
+            "The retry should go to a host the provider didn't fail on"
+        );
+        let status = scaler.status().await;
+        assert_eq!(status.status_type, StatusType::Reconciling);
+        assert!(
+            status.message.ends_with(&format!(
+                "retrying away from host(s) where it failed to start: {failed_host}"
+            )),
+            "The retry should be reported in the status, got: {}",
+            status.message
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn can_run_multiple_instances_per_host() -> Result<()> {
+        let lattice_id = "provider_multi_instance";
+        let provider_ref = "fakecloud.azurecr.io/provider:3.2.1".to_string();
+        let provider_id = "fakecloud_azurecr_io_provider_3_2_1".to_string();
+        let host_ids = ["NASDASDIMAREALHOSTONE", "NASDASDIMAREALHOSTTWO"];
+
+        let store = Arc::new(TestStore::default());
+        for host_id in host_ids {
+            store
+                .store(
+                    lattice_id,
+                    host_id.to_string(),
+                    Host {
+                        id: host_id.to_string(),
+                        last_seen: Utc::now(),
+                        ..Default::default()
+                    },
+                )
+                .await?;
+        }
+
+        let spreadscaler = |instances| {
+            ProviderSpreadScaler::new(
+                store.clone(),
+                ProviderSpreadConfig {
+                    lattice_id: lattice_id.to_string(),
+                    provider_reference: provider_ref.clone(),
+                    provider_id: provider_id.clone(),
+                    model_name: MODEL_NAME.to_string(),
+                    spread_config: SpreadScalerProperty {
+                        instances,
+                        spread: vec![],
+                        topology_key: None,
+                        min_count: None,
+                        max_count: None,
+                        anti_affinity: Vec::new(),
+                        weight_key: None,
+                        selector: Vec::new(),
+                        reconcile_interval: None,
+                        instances_per_host: Some(2),
+                    },
+                    provider_config: vec![],
+                },
+                "fake_provider",
+            )
+        };
+        let count_per_host = |commands: &[Command]| {
+            commands
+                .iter()
+                .fold(BTreeMap::<String, usize>::new(), |mut counts, command| {
+                    let host_id = match command {
+                        Command::StartProvider(start) => &start.host_id,
+                        Command::StopProvider(stop) => &stop.host_id,
+                        other => panic!("Unexpected command {other:?}"),
+                    };
+                    *counts.entry(host_id.clone()).or_default() += 1;
+                    counts
+                })
+        };
+
+        let commands = spreadscaler(3).reconcile().await?;
+        assert!(commands
+            .iter()
+            .all(|command| matches!(command, Command::StartProvider(_))));
+        assert_eq!(
+            count_per_host(&commands),
+            BTreeMap::from([(host_ids[0].to_string(), 2), (host_ids[1].to_string(), 1)]),
+            "Instances should be spread across hosts before doubling up"
+        );
+
+        let scaler = spreadscaler(5);
+        let commands = scaler.reconcile().await?;
+        assert_eq!(
+            commands.len(),
+            4,
+            "No host should be given more than the instances per host"
+        );
+        assert_eq!(scaler.status().await.status_type, StatusType::Failed);
+
+        // Run two instances on each host, then scale down to one
+        let scaler = spreadscaler(1);
+        for host_id in host_ids {
+            store
+                .store(
+                    lattice_id,
+                    host_id.to_string(),
+                    Host {
+                        id: host_id.to_string(),
+                        last_seen: Utc::now(),
+                        providers: HashSet::from([ProviderInfo {
+                            provider_id: provider_id.clone(),
+                            provider_ref: provider_ref.clone(),
+                            annotations: spreadscaler_annotations("default", scaler.id()),
+                        }]),
+                        ..Default::default()
+                    },
+                )
+                .await?;
+        }
+        store
+            .store(
+                lattice_id,
+                provider_id.clone(),
+                Provider {
+                    id: provider_id.clone(),
+                    reference: provider_ref.clone(),
+                    hosts: host_ids
+                        .iter()
+                        .map(|host_id| {
+                            (
+                                host_id.to_string(),
+                                ProviderHostStatus {
+                                    instances: 2,
+                                    ..Default::default()
+                                },
+                            )
+                        })
+                        .collect(),
+                    ..Default::default()
+                },
+            )
+            .await?;
+
+        let commands = scaler.reconcile().await?;
+        assert!(commands
+            .iter()
+            .all(|command| matches!(command, Command::StopProvider(_))));
+        assert_eq!(
+            count_per_host(&commands).values().sum::<usize>(),
+            3,
+            "Every instance but one should be stopped"
+        );
+        assert_eq!(
+            scaler.cleanup().await?.len(),
+            4,
+            "Cleaning up should stop every instance on every host"
+        );
+
+        Ok(())
+    }
 }
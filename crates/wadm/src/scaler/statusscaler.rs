@@ -2,7 +2,11 @@ use anyhow::Result;
 use async_trait::async_trait;
 use wadm_types::{api::StatusInfo, TraitProperty};
 
-use crate::{commands::Command, events::Event, scaler::Scaler};
+use crate::{
+    commands::Command,
+    events::Event,
+    scaler::{compute_id_sha256, Scaler},
+};
 
 /// The StatusScaler is a scaler that only reports a predefined status and does not perform any actions.
 /// It's primarily used as a placeholder for a scaler that wadm failed to initialize for reasons that
@@ -63,4 +67,18 @@ impl StatusScaler {
             status,
         }
     }
+
+    /// Creates a StatusScaler for the given scaler kind and name in a manifest. The ID is derived
+    /// from the manifest name and version, the kind and the name, so the same manifest always gets
+    /// the same ID
+    pub fn for_manifest(
+        manifest_name: &str,
+        manifest_version: &str,
+        kind: &str,
+        name: &str,
+        status: StatusInfo,
+    ) -> Self {
+        let id = compute_id_sha256(&[manifest_name, manifest_version, kind, name]);
+        StatusScaler::new(id, kind, name, status)
+    }
 }
@@ -0,0 +1,328 @@
+//! A self-test that exercises the whole control loop when wadm starts, so misconfiguration (like a
+//! wrong subject or an unreachable store) is reported right away instead of as silent failures once
+//! wadm is running
+
+use std::time::Duration;
+
+use async_nats::jetstream::{
+    consumer::{pull::Config as PullConfig, AckPolicy},
+    stream::Stream,
+    Context,
+};
+use futures::StreamExt;
+use serde::{Deserialize, Serialize};
+use tracing::{debug, info, instrument};
+use wadm_types::api::{Status, StatusInfo};
+
+use crate::{
+    commands::{Command, ScaleComponent},
+    publisher::Publisher,
+    storage::{StateKind, Store, StoreError},
+    workers::StatusPublisher,
+};
+
+/// The name used for the model, component and state written by the self-test
+pub const SELF_TEST_NAME: &str = "wadm-self-test";
+
+/// How long the self-test waits for each step before failing it
+const STEP_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// The step of the control loop that failed during a self-test
+#[derive(Debug, thiserror::Error)]
+pub enum SelfTestError {
+    /// Writing to, reading from or deleting from the state store failed
+    #[error("Self-test failed: unable to use the state store: {0}")]
+    Store(#[source] StoreError),
+    /// A command couldn't be published and then consumed from the command stream
+    #[error("Self-test failed: unable to publish and consume a command: {0:#}")]
+    Command(#[source] anyhow::Error),
+    /// A status couldn't be published
+    #[error("Self-test failed: unable to publish a status: {0:#}")]
+    Status(#[source] anyhow::Error),
+}
+
+/// Something that can send a command through the same path as real commands and consume it back
+#[async_trait::async_trait]
+pub trait CommandRoundTrip {
+    /// Publishes the given command and returns it once it has been consumed again
+    async fn round_trip(&self, command: &Command) -> anyhow::Result<Command>;
+}
+
+/// Sends commands through a JetStream command stream. The command is published on the given
+/// subject and consumed with a temporary consumer filtered to it, so it is never handled by a
+/// command worker
+pub struct JetStreamRoundTrip {
+    context: Context,
+    stream: Stream,
+    subject: String,
+}
+
+impl JetStreamRoundTrip {
+    /// Creates a round trip through the given command stream on the given subject. The subject
+    /// shouldn't be one that any command consumer is reading from
+    pub fn new(context: Context, stream: Stream, subject: String) -> JetStreamRoundTrip {
+        JetStreamRoundTrip {
+            context,
+            stream,
+            subject,
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl CommandRoundTrip for JetStreamRoundTrip {
+    async fn round_trip(&self, command: &Command) -> anyhow::Result<Command> {
+        let consumer = self
+            .stream
+            .create_consumer(PullConfig {
+                filter_subject: self.subject.clone(),
+                ack_policy: AckPolicy::Explicit,
+                inactive_threshold: STEP_TIMEOUT * 2,
+                ..Default::default()
+            })
+            .await
+            .map_err(|e| anyhow::anyhow!("Unable to create consumer: {e}"))?;
+        Publisher::publish(
+            &self.context,
+            serde_json::to_vec(command)?,
+            Some(&self.subject),
+        )
+        .await?;
+        let msg = consumer
+            .fetch()
+            .max_messages(1)
+            .expires(STEP_TIMEOUT)
+            .messages()
+            .await
+            .map_err(|e| anyhow::anyhow!("Unable to fetch command: {e}"))?
+            .next()
+            .await
+            .ok_or_else(|| anyhow::anyhow!("Command was not received on {}", self.subject))?
+            .map_err(|e| anyhow::anyhow!("Unable to receive command: {e}"))?;
+        // Acking removes the command from the work queue
+        msg.ack()
+            .await
+            .map_err(|e| anyhow::anyhow!("Unable to ack command: {e}"))?;
+        serde_json::from_slice(&msg.payload).map_err(anyhow::Error::from)
+    }
+}
+
+/// The state written to the store during a self-test
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct SelfTestProbe {
+    run_id: String,
+}
+
+impl StateKind for SelfTestProbe {
+    const KIND: &'static str = "selftest";
+}
+
+/// Runs each step of the self-test in order, stopping at the first step that fails. Everything is
+/// done in the given lattice, which should be one that no hosts are running in
+#[instrument(level = "info", skip(store, commands, status_publisher))]
+pub async fn run_self_test<S, R, P>(
+    store: &S,
+    commands: &R,
+    status_publisher: &StatusPublisher<P>,
+    lattice_id: &str,
+) -> Result<(), SelfTestError>
+where
+    S: Store + Sync,
+    R: CommandRoundTrip + Sync,
+    P: Publisher + Sync,
+{
+    let run_id = uuid::Uuid::new_v4().to_string();
+
+    debug!("Checking state store");
+    let probe = SelfTestProbe {
+        run_id: run_id.clone(),
+    };
+    store
+        .store(lattice_id, SELF_TEST_NAME.to_string(), probe.clone())
+        .await
+        .map_err(SelfTestError::Store)?;
+    match store
+        .get::<SelfTestProbe>(lattice_id, SELF_TEST_NAME)
+        .await
+        .map_err(SelfTestError::Store)?
+    {
+        Some(stored) if stored == probe => {}
+        _ => {
+            return Err(SelfTestError::Store(StoreError::Other(
+                "Data read back from the store didn't match what was written".to_string(),
+            )))
+        }
+    }
+    store
+        .delete::<SelfTestProbe>(lattice_id, SELF_TEST_NAME)
+        .await
+        .map_err(SelfTestError::Store)?;
+
+    debug!("Checking command round trip");
+    // Scaling to 0 instances of a component that doesn't exist does nothing, even if it somehow
+    // ended up being handled
+    let command = Command::from(ScaleComponent {
+        component_id: SELF_TEST_NAME.to_string(),
+        host_id: SELF_TEST_NAME.to_string(),
+        count: 0,
+        model_name: SELF_TEST_NAME.to_string(),
+        annotations: [("run_id".to_string(), run_id.clone())].into(),
+        ..Default::default()
+    });
+    let received = tokio::time::timeout(STEP_TIMEOUT, commands.round_trip(&command))
+        .await
+        .map_err(|_| SelfTestError::Command(anyhow::anyhow!("Timed out")))?
+        .map_err(SelfTestError::Command)?;
+    if received.id() != command.id() {
+        return Err(SelfTestError::Command(anyhow::anyhow!(
+            "Received a different command than the one that was published"
+        )));
+    }
+
+    debug!("Checking status publishing");
+    // Statuses that didn't change aren't published again, so make each one unique
+    let status = Status::new(
+        StatusInfo::deployed(&format!("Self-test {run_id} passed")),
+        Vec::new(),
+    );
+    tokio::time::timeout(
+        STEP_TIMEOUT,
+        status_publisher.publish_status(SELF_TEST_NAME, status),
+    )
+    .await
+    .map_err(|_| SelfTestError::Status(anyhow::anyhow!("Timed out")))?
+    .map_err(SelfTestError::Status)?;
+
+    info!("Self-test passed");
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashMap;
+
+    use serde::de::DeserializeOwned;
+
+    use super::*;
+    use crate::{
+        storage::ReadStore,
+        test_util::{NoopPublisher, TestStore},
+    };
+
+    /// Hands back every command it is given, as a working command stream would
+    struct Echo;
+
+    #[async_trait::async_trait]
+    impl CommandRoundTrip for Echo {
+        async fn round_trip(&self, command: &Command) -> anyhow::Result<Command> {
+            Ok(command.clone())
+        }
+    }
+
+    /// Never receives the published command, like a stream that doesn't capture the subject
+    struct WrongSubject;
+
+    #[async_trait::async_trait]
+    impl CommandRoundTrip for WrongSubject {
+        async fn round_trip(&self, _: &Command) -> anyhow::Result<Command> {
+            anyhow::bail!("Command was not received on wadm.cmd.wrong")
+        }
+    }
+
+    /// A store that can't be reached
+    struct UnreachableStore;
+
+    #[async_trait::async_trait]
+    impl ReadStore for UnreachableStore {
+        async fn get<T>(&self, _: &str, _: &str) -> Result<Option<T>, StoreError>
+        where
+            T: DeserializeOwned + StateKind,
+        {
+            Err(StoreError::Connection("connection refused".into()))
+        }
+
+        async fn list<T>(&self, _: &str) -> Result<HashMap<String, T>, StoreError>
+        where
+            T: DeserializeOwned + StateKind,
+        {
+            Err(StoreError::Connection("connection refused".into()))
+        }
+    }
+
+    #[async_trait::async_trait]
+    impl Store for UnreachableStore {
+        async fn store_many<T, D>(&self, _: &str, _: D) -> Result<(), StoreError>
+        where
+            T: Serialize + DeserializeOwned + StateKind + Send + Sync + Clone,
+            D: IntoIterator<Item = (String, T)> + Send,
+        {
+            Err(StoreError::Connection("connection refused".into()))
+        }
+
+        async fn delete_many<T, D, K>(&self, _: &str, _: D) -> Result<(), StoreError>
+        where
+            T: Serialize + DeserializeOwned + StateKind + Send + Sync,
+            D: IntoIterator<Item = K> + Send,
+            K: AsRef<str>,
+        {
+            Err(StoreError::Connection("connection refused".into()))
+        }
+    }
+
+    /// A publisher that always fails, like one without permission to publish statuses
+    struct FailingPublisher;
+
+    #[async_trait::async_trait]
+    impl Publisher for FailingPublisher {
+        async fn publish(&self, _: Vec<u8>, _: Option<&str>) -> anyhow::Result<()> {
+            anyhow::bail!("permissions violation")
+        }
+    }
+
+    #[tokio::test]
+    async fn self_test_reports_broken_dependency() {
+        let store = TestStore::default();
+        let status_publisher = StatusPublisher::new(NoopPublisher, None, "wadm.status");
+        let lattice_id = "selftest";
+
+        run_self_test(&store, &Echo, &status_publisher, lattice_id)
+            .await
+            .expect("Self-test should pass with working dependencies");
+        assert!(
+            store
+                .get::<SelfTestProbe>(lattice_id, SELF_TEST_NAME)
+                .await
+                .unwrap()
+                .is_none(),
+            "Self-test should clean up the data it stored"
+        );
+
+        let err = run_self_test(&UnreachableStore, &Echo, &status_publisher, lattice_id)
+            .await
+            .expect_err("Self-test should fail with an unreachable store");
+        assert!(
+            matches!(err, SelfTestError::Store(_)),
+            "Store should be reported as broken, got {err}"
+        );
+        assert!(err.to_string().contains("connection refused"));
+
+        let err = run_self_test(&store, &WrongSubject, &status_publisher, lattice_id)
+            .await
+            .expect_err("Self-test should fail if commands aren't received");
+        assert!(
+            matches!(err, SelfTestError::Command(_)),
+            "Command round trip should be reported as broken, got {err}"
+        );
+        assert!(err.to_string().contains("wadm.cmd.wrong"));
+
+        let failing_status = StatusPublisher::new(FailingPublisher, None, "wadm.status");
+        let err = run_self_test(&store, &Echo, &failing_status, lattice_id)
+            .await
+            .expect_err("Self-test should fail if statuses can't be published");
+        assert!(
+            matches!(err, SelfTestError::Status(_)),
+            "Status publishing should be reported as broken, got {err}"
+        );
+        assert!(err.to_string().contains("permissions violation"));
+    }
+}
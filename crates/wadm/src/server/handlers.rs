@@ -10,14 +10,19 @@ use wadm_types::{
     api::{
         DeleteModelRequest, DeleteModelResponse, DeleteResult, DeployModelRequest,
         DeployModelResponse, DeployResult, GetModelRequest, GetModelResponse, GetResult,
-        ListModelsResponse, PutModelResponse, PutResult, Status, StatusResponse, StatusResult,
-        UndeployModelRequest, VersionInfo, VersionResponse,
+        ListModelsResponse, PatchModelRequest, PatchModelResponse, PatchResult, PauseResponse,
+        PauseResult, PauseScalerRequest, PutModelResponse, PutResult, Status, StatusResponse,
+        StatusResult, UndeployModelRequest, VersionInfo, VersionResponse,
     },
     CapabilityProperties, Manifest, Properties,
 };
-use wadm_types::{ComponentProperties, LATEST_VERSION};
+use wadm_types::{ComponentProperties, LATEST_VERSION, VERSION_ANNOTATION_KEY};
 
-use crate::{model::StoredManifest, publisher::Publisher};
+use crate::{
+    model::StoredManifest,
+    publisher::Publisher,
+    workers::{template_manifest, SubjectTemplate},
+};
 
 use super::{parser::parse_manifest, storage::ModelStorage, ManifestNotifier};
 
@@ -26,6 +31,7 @@ pub(crate) struct Handler<P> {
     pub(crate) client: Client,
     pub(crate) notifier: ManifestNotifier<P>,
     pub(crate) status_stream: Stream,
+    pub(crate) status_subject: SubjectTemplate,
 }
 
 impl<P: Publisher> Handler<P> {
@@ -564,7 +570,7 @@ impl<P: Publisher> Handler<P> {
         name: &str,
     ) {
         let req: DeployModelRequest = if msg.payload.is_empty() {
-            DeployModelRequest { version: None }
+            DeployModelRequest::default()
         } else {
             match serde_json::from_reader(std::io::Cursor::new(msg.payload)) {
                 Ok(r) => r,
@@ -608,17 +614,6 @@ impl<P: Publisher> Handler<P> {
                 }
             };
 
-        // Retrieve all stored models in the lattice
-        let stored_models = match self.store.list(account_id, lattice_id).await {
-            Ok(d) => d,
-            Err(e) => {
-                error!(error = %e, "Unable to fetch data");
-                self.send_error(msg.reply, "Internal storage error".to_string())
-                    .await;
-                return;
-            }
-        };
-
         // Fetch the model that's being staged for deployment for validation
         let staged_model = match req.version.clone() {
             Some(v) if v == LATEST_VERSION => manifests.get_current(),
@@ -646,6 +641,130 @@ impl<P: Publisher> Handler<P> {
             // Get the current version if payload version is None, since deploy() does the same
             None => manifests.get_current(),
         };
+        // A deploy without any variables keeps the ones the application was last deployed with,
+        // so redeploying doesn't drop them
+        let variables = if req.variables.is_empty() {
+            manifests.variables().clone()
+        } else {
+            req.variables.clone()
+        };
+        // Everything is validated against the manifest that will actually be deployed
+        let staged_model = match template_manifest(staged_model, &variables) {
+            Ok(model) => model,
+            Err(e) => {
+                self.send_error(msg.reply, format!("{e:#}")).await;
+                return;
+            }
+        };
+
+        if let Err(message) = self
+            .check_deployable(account_id, lattice_id, name, &staged_model)
+            .await
+        {
+            self.send_error(msg.reply, message).await;
+            return;
+        }
+
+        if !manifests.deploy(req.version.clone()) {
+            trace!("Requested version does not exist");
+            self.send_reply(
+                msg.reply,
+                // NOTE: We are constructing all data here, so this shouldn't fail, but just in
+                // case we unwrap to nothing
+                serde_json::to_vec(&DeployModelResponse {
+                    result: DeployResult::Error,
+                    message: format!(
+                        "Application with the name {name} does not have the specified version to deploy"
+                    ),
+                    name: name.to_string(),
+                    version: req.version,
+                })
+                .unwrap_or_default(),
+            )
+            .await;
+            return;
+        }
+        manifests.set_variables(variables.clone());
+        // SAFETY: We can unwrap here because we know we _just_ successfully deployed the manifest so they should all exist
+        let manifest = manifests
+            .get_version(manifests.deployed_version().unwrap())
+            .unwrap()
+            .to_owned();
+
+        let manifest_version = manifest.version().to_string();
+        let reply = self
+            .store
+            .set(account_id, lattice_id, manifests, Some(current_revision))
+            .await
+            .map(|_| DeployModelResponse {
+                result: DeployResult::Acknowledged,
+                message: format!(
+                    "Successfully deployed application {name} {}",
+                    manifest.version()
+                ),
+                name: name.to_string(),
+                version: Some(manifest_version.clone()),
+            })
+            .unwrap_or_else(|e| {
+                error!(error = %e, "Unable to store updated data");
+                DeployModelResponse {
+                    result: DeployResult::Error,
+                    message: "Internal storage error".to_string(),
+                    name: name.to_string(),
+                    version: Some(manifest_version.clone()),
+                }
+            });
+        trace!("Manifest saved in store, sending notification");
+        if let Err(e) = self
+            .notifier
+            .deployed(lattice_id, manifest, variables)
+            .await
+        {
+            error!(error = ?e, "Error when attempting to send deployed notification");
+            self.send_reply(
+                msg.reply,
+                // NOTE: We are constructing all data here, so this shouldn't fail, but just in
+                // case we unwrap to nothing
+                serde_json::to_vec(&DeployModelResponse {
+                    result: DeployResult::Error,
+                    message: "Error notifying processors of newly deployed manifest. This is likely a transient error, so please retry the request".to_string(),
+                    name: name.to_string(),
+                    version: Some(manifest_version),
+                })
+                .unwrap_or_default(),
+            )
+            .await;
+            return;
+        }
+        trace!(resp = ?reply, "Sending response");
+        self.send_reply(
+            msg.reply,
+            // NOTE: We are constructing all data here, so this shouldn't fail, but just in
+            // case we unwrap to nothing
+            serde_json::to_vec(&reply).unwrap_or_default(),
+        )
+        .await;
+    }
+
+    /// Checks that the given manifest can be deployed as the given model alongside the other models
+    /// deployed in the lattice: none of its component IDs may already be used by another deployed
+    /// model, and every shared component it uses must be deployed. Returns the message to reply
+    /// with if it can't be deployed
+    async fn check_deployable(
+        &self,
+        account_id: Option<&str>,
+        lattice_id: &str,
+        name: &str,
+        staged_model: &Manifest,
+    ) -> Result<(), String> {
+        // Retrieve all stored models in the lattice
+        let stored_models = match self.store.list(account_id, lattice_id).await {
+            Ok(d) => d,
+            Err(e) => {
+                error!(error = %e, "Unable to fetch data");
+                return Err("Internal storage error".to_string());
+            }
+        };
 
         // Retrieve all the existing identifiers of deployed components and providers, and check if the staged model has any duplicates
         let mut existing_ids: HashMap<String, String> = HashMap::new();
@@ -661,16 +780,14 @@ impl<P: Publisher> Handler<P> {
                     Ok(None) => (StoredManifest::default(), 0),
                     Err(e) => {
                         error!(error = %e, "Unable to fetch data");
-                        self.send_error(msg.reply, "Internal storage error".to_string())
-                            .await;
-                        return;
+                        return Err("Internal storage error".to_string());
                     }
                 };
 
                 // Performing checks against all other manifests except previous versions of the current manifest
                 // Because upgrading versions is a valid case for carrying over the same identifiers
                 if stored_manifest.name() != name {
-                    if let Some(deployed_manifest) = stored_manifest.get_deployed() {
+                    if let Some(Ok(deployed_manifest)) = stored_manifest.get_deployed_resolved() {
                         for component in deployed_manifest.spec.components.iter() {
                             let (Properties::Capability {
                                 properties: CapabilityProperties { id, .. },
@@ -705,14 +822,9 @@ impl<P: Publisher> Handler<P> {
                         conflicting_manifest_name,
                         "Component identifier is already deployed in a different application.",
                     );
-                    self.send_error(
-                                msg.reply,
-                                format!(
-                                    "Component identifier '{id}' is already deployed in a different application '{conflicting_manifest_name}'."
-                            ),
-                            )
-                            .await;
-                    return;
+                    return Err(format!(
+                        "Component identifier '{id}' is already deployed in a different application '{conflicting_manifest_name}'."
+                    ));
                 }
             }
         }
@@ -729,85 +841,182 @@ impl<P: Publisher> Handler<P> {
 
         // Ensure all shared components point to a valid component that is deployed in another application
         if !missing_shared_components.is_empty() {
+            return Err(format!("Application contains shared components that are not deployed in other applications: {:?}", missing_shared_components.iter().map(|c| &c.name).collect::<Vec<_>>()));
+        }
+        Ok(())
+    }
+
+    #[instrument(level = "debug", skip(self, msg))]
+    pub async fn patch_model(
+        &self,
+        msg: Message,
+        account_id: Option<&str>,
+        lattice_id: &str,
+        name: &str,
+    ) {
+        let req: PatchModelRequest =
+            match serde_json::from_reader(std::io::Cursor::new(msg.payload)) {
+                Ok(r) => r,
+                Err(e) => {
+                    self.send_error(
+                        msg.reply,
+                        format!("Unable to parse patch application request: {e:?}"),
+                    )
+                    .await;
+                    return;
+                }
+            };
+        trace!(?req, "Got request");
+
+        trace!("Fetching current data from store");
+        let (mut manifests, current_revision) =
+            match self.store.get(account_id, lattice_id, name).await {
+                Ok(Some(m)) => m,
+                Ok(None) => {
+                    self.send_reply(
+                        msg.reply,
+                        // NOTE: We are constructing all data here, so this shouldn't fail, but just in
+                        // case we unwrap to nothing
+                        serde_json::to_vec(&PatchModelResponse {
+                            result: PatchResult::NotFound,
+                            message: format!("Application with the name {name} not found"),
+                            name: name.to_string(),
+                            version: None,
+                        })
+                        .unwrap_or_default(),
+                    )
+                    .await;
+                    return;
+                }
+                Err(e) => {
+                    error!(error = %e, "Unable to fetch data");
+                    self.send_error(msg.reply, "Internal storage error".to_string())
+                        .await;
+                    return;
+                }
+            };
+
+        let current = manifests.get_current();
+        let mut manifest = match current
+            .patched(&req.components)
+            .and_then(|patched| patched.json_patched(&req.operations))
+        {
+            Ok(m) => m,
+            Err(e) => {
+                self.send_error(msg.reply, format!("Unable to apply patch: {e}"))
+                    .await;
+                return;
+            }
+        };
+        if let Some(version) = req.version {
+            manifest
+                .metadata
+                .annotations
+                .insert(VERSION_ANNOTATION_KEY.to_string(), version);
+        }
+        // Patching the deployed version also deploys the patched version in its place
+        let redeploy = manifests.is_deployed(current.version());
+
+        let manifest_validation_output = validate_manifest_version(manifest.version());
+        let manifest_validation_errors = manifest_validation_output.errors();
+        if !manifest_validation_errors.is_empty() {
             self.send_error(
                 msg.reply,
-                format!("Application contains shared components that are not deployed in other applications: {:?}", missing_shared_components.iter().map(|c| &c.name).collect::<Vec<_>>())
-            ).await;
+                format!(
+                    "invalid manifest version, errors: {:#?}",
+                    manifest_validation_errors
+                        .iter()
+                        .map(|e| e.msg.clone())
+                        .collect::<Vec<String>>()
+                        .join("\n")
+                ),
+            )
+            .await;
+            return;
+        }
+        if let Some(error_message) = validate_manifest(&manifest).await.err() {
+            self.send_error(msg.reply, error_message.to_string()).await;
             return;
         }
 
-        if !manifests.deploy(req.version.clone()) {
-            trace!("Requested version does not exist");
-            self.send_reply(
+        let incoming_version = manifest.version().to_owned();
+        if !manifests.add_version(manifest) {
+            self.send_error(
                 msg.reply,
-                // NOTE: We are constructing all data here, so this shouldn't fail, but just in
-                // case we unwrap to nothing
-                serde_json::to_vec(&DeployModelResponse {
-                    result: DeployResult::Error,
-                    message: format!(
-                        "Application with the name {name} does not have the specified version to deploy"
-                    ),
-                    name: name.to_string(),
-                    version: req.version,
-                })
-                .unwrap_or_default(),
+                format!("Manifest version {incoming_version} already exists"),
             )
             .await;
             return;
         }
-        // SAFETY: We can unwrap here because we know we _just_ successfully deployed the manifest so they should all exist
-        let manifest = manifests
-            .get_version(manifests.deployed_version().unwrap())
-            .unwrap()
-            .to_owned();
+        let version = manifests.current_version().to_owned();
+        if redeploy {
+            manifests.deploy(Some(version.clone()));
+            // The patched version is deployed with the variables the previous version was
+            let deployed = match manifests.get_deployed_resolved() {
+                Some(Ok(deployed)) => deployed,
+                Some(Err(e)) => {
+                    self.send_error(msg.reply, format!("{e:#}")).await;
+                    return;
+                }
+                None => manifests.get_current().to_owned(),
+            };
+            if let Err(message) = self
+                .check_deployable(account_id, lattice_id, name, &deployed)
+                .await
+            {
+                self.send_error(msg.reply, message).await;
+                return;
+            }
+        }
+        // SAFETY: We just added this version
+        let manifest = manifests.get_version(&version).unwrap().to_owned();
+        let variables = manifests.variables().clone();
 
-        let manifest_version = manifest.version().to_string();
-        let reply = self
+        trace!(%version, redeploy, "Storing patched manifest");
+        if let Err(e) = self
             .store
             .set(account_id, lattice_id, manifests, Some(current_revision))
             .await
-            .map(|_| DeployModelResponse {
-                result: DeployResult::Acknowledged,
-                message: format!(
-                    "Successfully deployed application {name} {}",
-                    manifest.version()
-                ),
-                name: name.to_string(),
-                version: Some(manifest_version.clone()),
-            })
-            .unwrap_or_else(|e| {
-                error!(error = %e, "Unable to store updated data");
-                DeployModelResponse {
-                    result: DeployResult::Error,
-                    message: "Internal storage error".to_string(),
-                    name: name.to_string(),
-                    version: Some(manifest_version.clone()),
-                }
-            });
-        trace!("Manifest saved in store, sending notification");
-        if let Err(e) = self.notifier.deployed(lattice_id, manifest).await {
-            error!(error = ?e, "Error when attempting to send deployed notification");
-            self.send_reply(
-                msg.reply,
-                // NOTE: We are constructing all data here, so this shouldn't fail, but just in
-                // case we unwrap to nothing
-                serde_json::to_vec(&DeployModelResponse {
-                    result: DeployResult::Error,
-                    message: "Error notifying processors of newly deployed manifest. This is likely a transient error, so please retry the request".to_string(),
-                    name: name.to_string(),
-                    version: Some(manifest_version),
-                })
-                .unwrap_or_default(),
-            )
-            .await;
+        {
+            error!(error = %e, "Unable to store updated data");
+            self.send_error(msg.reply, "Internal storage error".to_string())
+                .await;
             return;
         }
-        trace!(resp = ?reply, "Sending response");
+
+        let resp = if !redeploy {
+            PatchModelResponse {
+                result: PatchResult::NewVersion,
+                message: format!("Successfully patched application {name} {version}"),
+                name: name.to_string(),
+                version: Some(version),
+            }
+        } else if let Err(e) = self
+            .notifier
+            .deployed(lattice_id, manifest, variables)
+            .await
+        {
+            error!(error = ?e, "Error when attempting to send deployed notification");
+            PatchModelResponse {
+                result: PatchResult::Error,
+                message: "Error notifying processors of newly deployed manifest. This is likely a transient error, so please retry the request".to_string(),
+                name: name.to_string(),
+                version: Some(version),
+            }
+        } else {
+            PatchModelResponse {
+                result: PatchResult::Deployed,
+                message: format!("Successfully patched and deployed application {name} {version}"),
+                name: name.to_string(),
+                version: Some(version),
+            }
+        };
+        trace!(resp = ?resp, "Sending response");
         self.send_reply(
             msg.reply,
             // NOTE: We are constructing all data here, so this shouldn't fail, but just in
             // case we unwrap to nothing
-            serde_json::to_vec(&reply).unwrap_or_default(),
+            serde_json::to_vec(&resp).unwrap_or_default(),
         )
         .await;
     }
@@ -955,6 +1164,104 @@ impl<P: Publisher> Handler<P> {
         .await;
     }
 
+    /// Pauses or resumes one of the scalers of a deployed model on every wadm instance. The scaler
+    /// must be reported in the current status of the model
+    #[instrument(level = "debug", skip(self, msg))]
+    pub async fn set_scaler_paused(
+        &self,
+        msg: Message,
+        account_id: Option<&str>,
+        lattice_id: &str,
+        name: &str,
+        paused: bool,
+    ) {
+        let req: PauseScalerRequest = match serde_json::from_slice(&msg.payload) {
+            Ok(r) => r,
+            Err(e) => {
+                self.send_error(
+                    msg.reply,
+                    format!("Unable to parse pause scaler request: {e:?}"),
+                )
+                .await;
+                return;
+            }
+        };
+        trace!(?req, "Got request");
+
+        let deployed = match self.store.get(account_id, lattice_id, name).await {
+            Ok(Some((manifests, _))) => manifests.deployed_version().is_some(),
+            Ok(None) => false,
+            Err(e) => {
+                error!(error = %e, "Unable to fetch data");
+                self.send_error(msg.reply, "Internal storage error".to_string())
+                    .await;
+                return;
+            }
+        };
+        let scaler_exists = deployed
+            && self
+                .get_manifest_status(lattice_id, name)
+                .await
+                .is_some_and(|status| status.scalers.iter().any(|s| s.id == req.scaler_id));
+        let response = if !scaler_exists {
+            PauseResponse {
+                result: PauseResult::NotFound,
+                message: format!(
+                    "Scaler {} not found for deployed application {name}",
+                    req.scaler_id
+                ),
+            }
+        } else if let Err(e) = self
+            .notifier
+            .scaler_paused(lattice_id, name, &req.scaler_id, paused)
+            .await
+        {
+            error!(error = %e, "Unable to publish pause notification");
+            PauseResponse {
+                result: PauseResult::Error,
+                message: "Unable to send request to wadm instances".to_string(),
+            }
+        } else {
+            PauseResponse {
+                result: PauseResult::Acknowledged,
+                message: format!(
+                    "Scaler {} of application {name} {}",
+                    req.scaler_id,
+                    if paused { "paused" } else { "resumed" }
+                ),
+            }
+        };
+        self.send_reply(
+            msg.reply,
+            // NOTE: We are constructing all data here, so this shouldn't fail, but just in
+            // case we unwrap to nothing
+            serde_json::to_vec(&response).unwrap_or_default(),
+        )
+        .await;
+    }
+
+    /// Acknowledges a request to pause or resume reaping for the lattice. Every wadm instance
+    /// receives the request itself (see [`Reaper::handle_control`](crate::storage::reaper::Reaper::handle_control)),
+    /// so only the reply is sent from here. This doesn't persist, so instances started afterwards
+    /// (or restarted) reap the lattice as usual
+    #[instrument(level = "debug", skip(self, msg))]
+    pub async fn set_reaping_paused(&self, msg: Message, lattice_id: &str, paused: bool) {
+        let response = PauseResponse {
+            result: PauseResult::Acknowledged,
+            message: format!(
+                "Reaping for lattice {lattice_id} {}",
+                if paused { "paused" } else { "resumed" }
+            ),
+        };
+        self.send_reply(
+            msg.reply,
+            // NOTE: We are constructing all data here, so this shouldn't fail, but just in
+            // case we unwrap to nothing
+            serde_json::to_vec(&response).unwrap_or_default(),
+        )
+        .await;
+    }
+
     /// Sends a reply to the topic with the given data, logging an error if one occurs when
     /// sending the reply
     #[instrument(level = "debug", skip(self, data))]
@@ -994,7 +1301,7 @@ impl<P: Publisher> Handler<P> {
         // to ensure we fetch the latest message from the cluster leader.
         match self
             .status_stream
-            .get_last_raw_message_by_subject(&format!("wadm.status.{lattice_id}.{name}",))
+            .get_last_raw_message_by_subject(&self.status_subject.render(lattice_id, Some(name)))
             .await
             .map(|status_msg| serde_json::from_slice::<Status>(&status_msg.payload))
         {
@@ -6,7 +6,7 @@ use futures::StreamExt;
 use tracing::{info, instrument, warn};
 use wadm_types::api::DEFAULT_WADM_TOPIC_PREFIX;
 
-use crate::publisher::Publisher;
+use crate::{publisher::Publisher, workers::SubjectTemplate, DEFAULT_STATUS_SUBJECT_TEMPLATE};
 
 mod handlers;
 mod notifier;
@@ -24,10 +24,101 @@ const QUEUE_GROUP: &str = "wadm_server";
 pub struct Server<P> {
     handler: Handler<P>,
     subscriber: Subscriber,
+    api: ApiSubjects,
+}
+
+/// The subjects the wadm API is served on, which are all under the API prefix, preceded by the
+/// account ID in multitenant mode. This is shared by everything that listens on API subjects
+#[derive(Debug, Clone)]
+pub(crate) struct ApiSubjects {
     prefix: String,
     multitenant: bool,
 }
 
+impl ApiSubjects {
+    /// Returns the API subjects under the given prefix, or the default prefix if none is given.
+    /// Returns an error if the prefix is empty
+    pub(crate) fn new(topic_prefix: Option<&str>, multitenant: bool) -> anyhow::Result<Self> {
+        // Trim off any spaces or trailing/preceding dots
+        let prefix = topic_prefix
+            .unwrap_or(DEFAULT_WADM_TOPIC_PREFIX)
+            .trim()
+            .trim_matches('.')
+            .to_owned();
+        if prefix.is_empty() {
+            anyhow::bail!("Given prefix was empty")
+        }
+        Ok(ApiSubjects {
+            prefix,
+            multitenant,
+        })
+    }
+
+    /// Returns the subject filter matching every API request
+    fn filter(&self) -> String {
+        self.with_prefix(">")
+    }
+
+    /// Returns the subject filter matching every request in the given category (e.g. `reaper`)
+    /// for all lattices, with any operation and no object name
+    pub(crate) fn category_filter(&self, category: &str) -> String {
+        self.with_prefix(&format!("*.{category}.*"))
+    }
+
+    fn with_prefix(&self, tokens: &str) -> String {
+        if self.multitenant {
+            format!("*.{}.{tokens}", self.prefix)
+        } else {
+            format!("{}.{tokens}", self.prefix)
+        }
+    }
+
+    /// Splits the given API subject into its parts, returning an error if it is malformed
+    pub(crate) fn parse<'a>(&self, subject: &'a str) -> anyhow::Result<ParsedSubject<'a>> {
+        // Topic structure: wadm.api.{lattice-id}.{category}.{operation}.{object}
+        // Multitenant topic structure: {account-id}.wadm.api.{lattice-id}.{category}.{operation}.{object}
+        // First, clean off the account if multitenant, then prefix and then split and iterate
+        let (account_id, subject) = if self.multitenant {
+            if let Some((account_id, rest)) = subject.split_once('.') {
+                (Some(account_id), rest)
+            } else {
+                anyhow::bail!("Expected to find account ID in multitenant subject")
+            }
+        } else {
+            (None, subject)
+        };
+
+        let mut trimmed = subject
+            .trim_start_matches(&self.prefix)
+            .trim_start_matches('.')
+            .split('.')
+            .fuse();
+
+        let lattice_id = trimmed
+            .next()
+            .ok_or_else(|| anyhow::anyhow!("Expected to find lattice ID"))?;
+        let category = trimmed
+            .next()
+            .ok_or_else(|| anyhow::anyhow!("Expected to find API category"))?;
+        let operation = trimmed
+            .next()
+            .ok_or_else(|| anyhow::anyhow!("Expected to find operation"))?;
+        // Some commands don't have names, so this is optional
+        let object_name = trimmed.next();
+        // Catch malformed long subjects
+        if trimmed.next().is_some() {
+            anyhow::bail!("Found extra components of subject, ensure your manifest name consists of only alphanumeric characters, dashes, and underscores.")
+        }
+        Ok(ParsedSubject {
+            account_id,
+            lattice_id,
+            category,
+            operation,
+            object_name,
+        })
+    }
+}
+
 impl<P: Publisher> Server<P> {
     /// Returns a new server configured with the given store, NATS client, and optional topic
     /// prefix. Returns an error if it can't subscribe on the right topics
@@ -43,23 +134,8 @@ impl<P: Publisher> Server<P> {
         status_stream: Stream,
         notifier: ManifestNotifier<P>,
     ) -> anyhow::Result<Server<P>> {
-        // Trim off any spaces or trailing/preceding dots
-        let prefix = topic_prefix
-            .unwrap_or(DEFAULT_WADM_TOPIC_PREFIX)
-            .trim()
-            .trim_matches('.')
-            .to_owned();
-        if prefix.is_empty() {
-            anyhow::bail!("Given prefix was empty")
-        }
-
-        let topic_prefix = if multitenant {
-            format!("*.{prefix}")
-        } else {
-            prefix.clone()
-        };
-
-        let topic = format!("{topic_prefix}.>");
+        let api = ApiSubjects::new(topic_prefix, multitenant)?;
+        let topic = api.filter();
         info!(%topic, "Creating API subscriber");
         // NOTE(thomastaylor312): Technically there is a condition where two people try to send an
         // update to the same manifest. We are protected against this overwriting each other (we
@@ -77,13 +153,20 @@ impl<P: Publisher> Server<P> {
                 client,
                 notifier,
                 status_stream,
+                status_subject: DEFAULT_STATUS_SUBJECT_TEMPLATE.parse()?,
             },
             subscriber,
-            prefix,
-            multitenant,
+            api,
         })
     }
 
+    /// Configures the server to read model statuses from the subjects rendered from the given
+    /// template. This must match the template used to publish statuses
+    pub fn with_status_subject(mut self, template: SubjectTemplate) -> Server<P> {
+        self.handler.status_subject = template;
+        self
+    }
+
     /// Starts the server, consuming it.
     ///
     /// This function will run until it either returns an error (which should always be fatal) or
@@ -91,7 +174,7 @@ impl<P: Publisher> Server<P> {
     #[instrument(level = "info", skip_all)]
     pub async fn serve(mut self) -> anyhow::Result<()> {
         while let Some(msg) = self.subscriber.next().await {
-            if !msg.subject.starts_with(&self.prefix) && !self.multitenant {
+            if !msg.subject.starts_with(&self.api.prefix) && !self.api.multitenant {
                 warn!(subject = %msg.subject, "Received message on an invalid subject");
                 continue;
             }
@@ -101,7 +184,7 @@ impl<P: Publisher> Server<P> {
             // strings. But we need to pass the message to consume the data off of it in the
             // handlers
             let subject = msg.subject.clone();
-            let parsed = match self.parse_subject(&subject) {
+            let parsed = match self.api.parse(&subject) {
                 Ok(p) => p,
                 Err(e) => {
                     self.handler
@@ -182,6 +265,17 @@ impl<P: Publisher> Server<P> {
                         .deploy_model(msg, account_id, lattice_id, name)
                         .await
                 }
+                ParsedSubject {
+                    account_id,
+                    lattice_id,
+                    category: "model",
+                    operation: "patch",
+                    object_name: Some(name),
+                } => {
+                    self.handler
+                        .patch_model(msg, account_id, lattice_id, name)
+                        .await
+                }
                 ParsedSubject {
                     account_id,
                     lattice_id,
@@ -204,6 +298,28 @@ impl<P: Publisher> Server<P> {
                         .model_status(msg, account_id, lattice_id, name)
                         .await
                 }
+                ParsedSubject {
+                    account_id,
+                    lattice_id,
+                    category: "model",
+                    operation: operation @ ("pause" | "resume"),
+                    object_name: Some(name),
+                } => {
+                    self.handler
+                        .set_scaler_paused(msg, account_id, lattice_id, name, operation == "pause")
+                        .await
+                }
+                ParsedSubject {
+                    account_id: _,
+                    lattice_id,
+                    category: "reaper",
+                    operation: operation @ ("pause" | "resume"),
+                    object_name: None,
+                } => {
+                    self.handler
+                        .set_reaping_paused(msg, lattice_id, operation == "pause")
+                        .await
+                }
                 ParsedSubject {
                     account_id: _,
                     lattice_id: _,
@@ -226,56 +342,39 @@ impl<P: Publisher> Server<P> {
         }
         Err(anyhow::anyhow!("Subscriber terminated"))
     }
+}
 
-    fn parse_subject<'a>(&self, subject: &'a str) -> anyhow::Result<ParsedSubject<'a>> {
-        // Topic structure: wadm.api.{lattice-id}.{category}.{operation}.{object}
-        // Multitenant topic structure: {account-id}.wadm.api.{lattice-id}.{category}.{operation}.{object}
-        // First, clean off the account if multitenant, then prefix and then split and iterate
-        let (account_id, subject) = if self.multitenant {
-            if let Some((account_id, rest)) = subject.split_once('.') {
-                (Some(account_id), rest)
-            } else {
-                anyhow::bail!("Expected to find account ID in multitenant subject")
-            }
-        } else {
-            (None, subject)
-        };
+pub(crate) struct ParsedSubject<'a> {
+    pub(crate) account_id: Option<&'a str>,
+    pub(crate) lattice_id: &'a str,
+    pub(crate) category: &'a str,
+    pub(crate) operation: &'a str,
+    pub(crate) object_name: Option<&'a str>,
+}
 
-        let mut trimmed = subject
-            .trim_start_matches(&self.prefix)
-            .trim_start_matches('.')
-            .split('.')
-            .fuse();
+#[cfg(test)]
+mod test {
+    use super::ApiSubjects;
 
-        let lattice_id = trimmed
-            .next()
-            .ok_or_else(|| anyhow::anyhow!("Expected to find lattice ID"))?;
-        let category = trimmed
-            .next()
-            .ok_or_else(|| anyhow::anyhow!("Expected to find API category"))?;
-        let operation = trimmed
-            .next()
-            .ok_or_else(|| anyhow::anyhow!("Expected to find operation"))?;
-        // Some commands don't have names, so this is optional
-        let object_name = trimmed.next();
-        // Catch malformed long subjects
-        if trimmed.next().is_some() {
-            anyhow::bail!("Found extra components of subject, ensure your manifest name consists of only alphanumeric characters, dashes, and underscores.")
-        }
-        Ok(ParsedSubject {
-            account_id,
-            lattice_id,
-            category,
-            operation,
-            object_name,
-        })
-    }
-}
+    #[test]
+    fn category_filter_uses_prefix_and_account() {
+        let api = ApiSubjects::new(Some(".custom.api."), false).unwrap();
+        assert_eq!(api.category_filter("reaper"), "custom.api.*.reaper.*");
+        let parsed = api.parse("custom.api.default.reaper.pause").unwrap();
+        assert_eq!(
+            (parsed.lattice_id, parsed.category, parsed.operation),
+            ("default", "reaper", "pause")
+        );
 
-struct ParsedSubject<'a> {
-    account_id: Option<&'a str>,
-    lattice_id: &'a str,
-    category: &'a str,
-    operation: &'a str,
-    object_name: Option<&'a str>,
+        let api = ApiSubjects::new(None, true).unwrap();
+        assert_eq!(api.category_filter("reaper"), "*.wadm.api.*.reaper.*");
+        let parsed = api
+            .parse("AACCOUNT.wadm.api.default.reaper.resume")
+            .unwrap();
+        assert_eq!(parsed.account_id, Some("AACCOUNT"));
+        assert_eq!(parsed.lattice_id, "default");
+        assert_eq!(parsed.operation, "resume");
+
+        assert!(ApiSubjects::new(Some(" . "), false).is_err());
+    }
 }
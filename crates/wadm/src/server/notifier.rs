@@ -1,3 +1,5 @@
+use std::collections::BTreeMap;
+
 use cloudevents::Event as CloudEvent;
 use tracing::{instrument, trace};
 use wadm_types::Manifest;
@@ -5,6 +7,7 @@ use wadm_types::Manifest;
 use crate::{
     events::{Event, ManifestPublished, ManifestUnpublished},
     publisher::Publisher,
+    scaler::manager::{Notifications, WADM_NOTIFY_PREFIX},
 };
 
 /// A notifier that publishes changes about manifests with the given publisher
@@ -42,15 +45,45 @@ impl<P: Publisher> ManifestNotifier<P> {
             .await
     }
 
-    pub async fn deployed(&self, lattice_id: &str, manifest: Manifest) -> anyhow::Result<()> {
+    /// Notifies that the given manifest was deployed with the given values for its placeholders
+    pub async fn deployed(
+        &self,
+        lattice_id: &str,
+        manifest: Manifest,
+        variables: BTreeMap<String, String>,
+    ) -> anyhow::Result<()> {
         self.send_event(
             lattice_id,
             "manifest_published",
-            Event::ManifestPublished(ManifestPublished { manifest }),
+            Event::ManifestPublished(ManifestPublished {
+                manifest,
+                variables,
+            }),
         )
         .await
     }
 
+    /// Notifies every wadm instance managing the lattice to pause or resume the given scaler
+    pub async fn scaler_paused(
+        &self,
+        lattice_id: &str,
+        name: &str,
+        scaler_id: &str,
+        paused: bool,
+    ) -> anyhow::Result<()> {
+        let notification = serde_json::to_vec(&Notifications::SetScalerPaused {
+            name: name.to_owned(),
+            scaler_id: scaler_id.to_owned(),
+            paused,
+        })?;
+        self.publisher
+            .publish(
+                notification,
+                Some(&format!("{WADM_NOTIFY_PREFIX}.{lattice_id}")),
+            )
+            .await
+    }
+
     pub async fn undeployed(&self, lattice_id: &str, name: &str) -> anyhow::Result<()> {
         self.send_event(
             lattice_id,
@@ -0,0 +1,543 @@
+//! A module that defines a generic sink for the state changes wadm makes, so they can be sent to
+//! systems other than NATS, along with the sinks that can be configured when starting wadm
+
+use std::{str::FromStr, sync::Arc, time::Duration};
+
+use anyhow::Context as _;
+use serde::{Deserialize, Serialize};
+use tokio::sync::mpsc;
+use tracing::warn;
+use wadm_types::api::Status;
+
+use crate::{
+    commands::Command,
+    publisher::Publisher,
+    storage::{record_command, Store},
+};
+
+/// A change wadm made to the state of a lattice
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum StateChange {
+    /// A command was sent to the lattice to bring it closer to the desired state
+    Command {
+        lattice_id: String,
+        command: Command,
+    },
+    /// The status of a model changed
+    Status {
+        lattice_id: String,
+        model_name: String,
+        status: Status,
+    },
+}
+
+impl StateChange {
+    /// Returns the ID of the lattice the change was made in
+    pub fn lattice_id(&self) -> &str {
+        match self {
+            StateChange::Command { lattice_id, .. } | StateChange::Status { lattice_id, .. } => {
+                lattice_id
+            }
+        }
+    }
+}
+
+#[async_trait::async_trait]
+pub trait StateChangeSink {
+    /// Sends the given change to the sink. Implementors are responsible for documenting guarantees
+    /// of delivery for sent changes
+    async fn send(&self, change: &StateChange) -> anyhow::Result<()>;
+}
+
+/// A sink that can be shared between all of the publishers that send to it
+pub type SharedSink = Arc<dyn StateChangeSink + Send + Sync>;
+
+/// Sends every change to all of the sinks in the list. Every sink is sent to even if an earlier one
+/// fails, and the first failure is returned
+#[async_trait::async_trait]
+impl StateChangeSink for Vec<SharedSink> {
+    async fn send(&self, change: &StateChange) -> anyhow::Result<()> {
+        futures::future::join_all(self.iter().map(|sink| sink.send(change)))
+            .await
+            .into_iter()
+            .collect()
+    }
+}
+
+/// A sink that publishes each change as JSON on `{subject_prefix}.{lattice_id}` with the given
+/// publisher, which has the same delivery guarantees as the publisher
+pub struct NatsSink<P> {
+    publisher: P,
+    subject_prefix: String,
+}
+
+impl<P> NatsSink<P> {
+    pub fn new(publisher: P, subject_prefix: &str) -> NatsSink<P> {
+        NatsSink {
+            publisher,
+            subject_prefix: subject_prefix.to_owned(),
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl<P: Publisher + Send + Sync> StateChangeSink for NatsSink<P> {
+    async fn send(&self, change: &StateChange) -> anyhow::Result<()> {
+        let subject = format!("{}.{}", self.subject_prefix, change.lattice_id());
+        self.publisher
+            .publish(serde_json::to_vec(change)?, Some(&subject))
+            .await
+    }
+}
+
+/// A sink that records every command in the [`CommandHistory`](crate::storage::CommandHistory) of
+/// the model that sent it, keeping the commands sent within the retention window so they can be
+/// reviewed with [`command_history`](crate::storage::command_history) and replayed. Status changes
+/// are ignored
+pub struct CommandHistorySink<S> {
+    store: S,
+    retention: chrono::Duration,
+}
+
+impl<S> CommandHistorySink<S> {
+    pub fn new(store: S, retention: Duration) -> CommandHistorySink<S> {
+        CommandHistorySink {
+            store,
+            retention: chrono::Duration::from_std(retention).unwrap_or(chrono::Duration::MAX),
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl<S: Store + Send + Sync> StateChangeSink for CommandHistorySink<S> {
+    async fn send(&self, change: &StateChange) -> anyhow::Result<()> {
+        let StateChange::Command {
+            lattice_id,
+            command,
+        } = change
+        else {
+            return Ok(());
+        };
+        record_command(&self.store, lattice_id, command, self.retention)
+            .await
+            .map_err(anyhow::Error::from)
+    }
+}
+
+/// How long a request to a webhook or Kafka REST proxy can take before it is abandoned
+#[cfg(feature = "external_sinks")]
+const EXTERNAL_SINK_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How many changes can be waiting to be sent to a webhook or Kafka REST proxy before new changes
+/// are dropped
+#[cfg(feature = "external_sinks")]
+const EXTERNAL_SINK_QUEUE_SIZE: usize = 1024;
+
+/// A sink that sends changes to another sink from a background task, so slow sinks don't hold up
+/// publishing commands and statuses. Changes are sent in order. Sending only fails if the queue of
+/// changes waiting to be sent is full, in which case the change is dropped. Failures when the
+/// change is actually sent are logged, as they happen after sending has returned
+pub struct QueuedSink {
+    queue: mpsc::Sender<StateChange>,
+}
+
+impl QueuedSink {
+    /// Starts sending changes to the given sink in the background, queueing up to `capacity`
+    /// changes while the sink is busy
+    pub fn new<S: StateChangeSink + Send + Sync + 'static>(sink: S, capacity: usize) -> QueuedSink {
+        let (queue, mut changes) = mpsc::channel::<StateChange>(capacity.max(1));
+        tokio::spawn(async move {
+            while let Some(change) = changes.recv().await {
+                if let Err(e) = sink.send(&change).await {
+                    warn!(error = %e, lattice_id = %change.lattice_id(), "Failed to send change to sink");
+                }
+            }
+        });
+        QueuedSink { queue }
+    }
+}
+
+#[async_trait::async_trait]
+impl StateChangeSink for QueuedSink {
+    async fn send(&self, change: &StateChange) -> anyhow::Result<()> {
+        self.queue
+            .try_send(change.clone())
+            .map_err(|e| anyhow::anyhow!("Unable to queue change for sink: {e}"))
+    }
+}
+
+/// Builds the HTTP client used by external sinks, which gives up on requests that take too long
+#[cfg(feature = "external_sinks")]
+fn external_client() -> reqwest::Client {
+    reqwest::Client::builder()
+        .timeout(EXTERNAL_SINK_TIMEOUT)
+        .build()
+        .unwrap_or_default()
+}
+
+/// A sink that POSTs each change as JSON to a webhook. A change is only considered sent once the
+/// webhook responds with a success status
+#[cfg(feature = "external_sinks")]
+pub struct WebhookSink {
+    client: reqwest::Client,
+    url: String,
+}
+
+#[cfg(feature = "external_sinks")]
+impl WebhookSink {
+    pub fn new(url: &str) -> WebhookSink {
+        WebhookSink {
+            client: external_client(),
+            url: url.to_owned(),
+        }
+    }
+}
+
+#[cfg(feature = "external_sinks")]
+#[async_trait::async_trait]
+impl StateChangeSink for WebhookSink {
+    async fn send(&self, change: &StateChange) -> anyhow::Result<()> {
+        self.client
+            .post(&self.url)
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .body(serde_json::to_vec(change)?)
+            .send()
+            .await?
+            .error_for_status()?;
+        Ok(())
+    }
+}
+
+/// A sink that produces each change to a Kafka topic through a Kafka REST proxy, given the URL of
+/// the topic on the proxy (e.g. `http://localhost:8082/topics/wadm`). Changes are keyed by lattice
+/// ID so the changes for a lattice stay in order. A change is only considered sent once the proxy
+/// has accepted it
+#[cfg(feature = "external_sinks")]
+pub struct KafkaRestSink {
+    client: reqwest::Client,
+    topic_url: String,
+}
+
+#[cfg(feature = "external_sinks")]
+impl KafkaRestSink {
+    pub fn new(topic_url: &str) -> KafkaRestSink {
+        KafkaRestSink {
+            client: external_client(),
+            topic_url: topic_url.to_owned(),
+        }
+    }
+}
+
+#[cfg(feature = "external_sinks")]
+#[async_trait::async_trait]
+impl StateChangeSink for KafkaRestSink {
+    async fn send(&self, change: &StateChange) -> anyhow::Result<()> {
+        let body = serde_json::json!({
+            "records": [{ "key": change.lattice_id(), "value": change }]
+        });
+        self.client
+            .post(&self.topic_url)
+            .header(
+                reqwest::header::CONTENT_TYPE,
+                "application/vnd.kafka.json.v2+json",
+            )
+            .body(serde_json::to_vec(&body)?)
+            .send()
+            .await?
+            .error_for_status()?;
+        Ok(())
+    }
+}
+
+/// A sink configured when starting wadm, in the form `nats:SUBJECT_PREFIX`, `webhook:URL` or
+/// `kafka:TOPIC_URL`. Webhook and Kafka sinks require the `external_sinks` feature
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SinkConfig {
+    Nats { subject_prefix: String },
+    Webhook { url: String },
+    Kafka { topic_url: String },
+}
+
+impl FromStr for SinkConfig {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (kind, target) = s
+            .split_once(':')
+            .filter(|(_, target)| !target.trim().is_empty())
+            .with_context(|| {
+                format!("Invalid sink '{s}', expected nats:PREFIX, webhook:URL or kafka:TOPIC_URL")
+            })?;
+        let target = target.trim().to_owned();
+        match kind.trim() {
+            "nats" => Ok(SinkConfig::Nats {
+                subject_prefix: target,
+            }),
+            "webhook" => Ok(SinkConfig::Webhook { url: target }),
+            "kafka" => Ok(SinkConfig::Kafka { topic_url: target }),
+            kind => anyhow::bail!("Unknown sink type '{kind}', expected nats, webhook or kafka"),
+        }
+    }
+}
+
+impl SinkConfig {
+    /// Builds the configured sink, using the given publisher for NATS sinks. Webhook and Kafka
+    /// sinks are sent to from a [`QueuedSink`]
+    pub fn build<P: Publisher + Send + Sync + 'static>(
+        &self,
+        publisher: P,
+    ) -> anyhow::Result<SharedSink> {
+        match self {
+            SinkConfig::Nats { subject_prefix } => {
+                Ok(Arc::new(NatsSink::new(publisher, subject_prefix)))
+            }
+            #[cfg(feature = "external_sinks")]
+            SinkConfig::Webhook { url } => Ok(Arc::new(QueuedSink::new(
+                WebhookSink::new(url),
+                EXTERNAL_SINK_QUEUE_SIZE,
+            ))),
+            #[cfg(feature = "external_sinks")]
+            SinkConfig::Kafka { topic_url } => Ok(Arc::new(QueuedSink::new(
+                KafkaRestSink::new(topic_url),
+                EXTERNAL_SINK_QUEUE_SIZE,
+            ))),
+            #[cfg(not(feature = "external_sinks"))]
+            SinkConfig::Webhook { .. } | SinkConfig::Kafka { .. } => {
+                anyhow::bail!("Webhook and Kafka sinks require the external_sinks feature")
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use tokio::sync::RwLock;
+
+    use super::*;
+    use crate::{
+        commands::ScaleComponent,
+        storage::command_history,
+        test_util::{RecorderPublisher, TestStore},
+        workers::{CommandPublisher, StatusPublisher},
+    };
+
+    /// A sink that keeps every change sent to it
+    #[derive(Default)]
+    struct CapturingSink {
+        changes: RwLock<Vec<StateChange>>,
+    }
+
+    #[async_trait::async_trait]
+    impl StateChangeSink for CapturingSink {
+        async fn send(&self, change: &StateChange) -> anyhow::Result<()> {
+            self.changes.write().await.push(change.clone());
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn delivers_state_changes_to_sink() {
+        let lattice_id = "sink";
+        let sink = Arc::new(CapturingSink::default());
+        let command_publisher = CommandPublisher::new(
+            RecorderPublisher::<Command> {
+                received: Arc::default(),
+            },
+            "doesntmatter",
+        )
+        .with_sink(lattice_id, sink.clone());
+        let status_publisher = StatusPublisher::new(
+            RecorderPublisher::<Status> {
+                received: Arc::default(),
+            },
+            None,
+            "doesntmatter",
+        )
+        .with_sink(lattice_id, sink.clone());
+
+        let command = Command::ScaleComponent(ScaleComponent {
+            component_id: "component".to_string(),
+            reference: "fakecloud.io/http:0.1.0".to_string(),
+            host_id: "host".to_string(),
+            count: 1,
+            model_name: "model".to_string(),
+            ..Default::default()
+        });
+        command_publisher
+            .publish_commands(vec![command.clone()])
+            .await
+            .unwrap();
+        let status = Status::new(wadm_types::api::StatusInfo::deployed(""), Vec::new());
+        status_publisher
+            .publish_status("model", status.clone())
+            .await
+            .unwrap();
+
+        assert_eq!(
+            *sink.changes.read().await,
+            vec![
+                StateChange::Command {
+                    lattice_id: lattice_id.to_string(),
+                    command,
+                },
+                StateChange::Status {
+                    lattice_id: lattice_id.to_string(),
+                    model_name: "model".to_string(),
+                    status,
+                },
+            ]
+        );
+    }
+
+    /// A sink that waits to be let through before it accepts each change
+    struct GatedSink {
+        gate: Arc<tokio::sync::Semaphore>,
+        inner: Arc<CapturingSink>,
+    }
+
+    #[async_trait::async_trait]
+    impl StateChangeSink for GatedSink {
+        async fn send(&self, change: &StateChange) -> anyhow::Result<()> {
+            self.gate.acquire().await?.forget();
+            self.inner.send(change).await
+        }
+    }
+
+    #[tokio::test]
+    async fn queued_sink_sends_in_the_background() {
+        let gate = Arc::new(tokio::sync::Semaphore::new(0));
+        let captured = Arc::new(CapturingSink::default());
+        let sink = QueuedSink::new(
+            GatedSink {
+                gate: gate.clone(),
+                inner: captured.clone(),
+            },
+            2,
+        );
+        let status = |model_name: &str| StateChange::Status {
+            lattice_id: "queued".to_string(),
+            model_name: model_name.to_string(),
+            status: Status::new(wadm_types::api::StatusInfo::deployed(""), Vec::new()),
+        };
+
+        // The first change is taken off the queue by the background task, the next two fill it
+        for name in ["one", "two", "three"] {
+            tokio::time::timeout(Duration::from_secs(1), sink.send(&status(name)))
+                .await
+                .expect("Sending shouldn't wait on the sink")
+                .unwrap();
+            tokio::task::yield_now().await;
+        }
+        assert!(
+            sink.send(&status("four")).await.is_err(),
+            "Changes should be dropped once the queue is full"
+        );
+
+        gate.add_permits(3);
+        tokio::time::timeout(Duration::from_secs(1), async {
+            while captured.changes.read().await.len() < 3 {
+                tokio::task::yield_now().await;
+            }
+        })
+        .await
+        .expect("Queued changes should be sent");
+        assert_eq!(
+            *captured.changes.read().await,
+            vec![status("one"), status("two"), status("three")],
+            "Changes should be sent in order"
+        );
+    }
+
+    #[tokio::test]
+    async fn records_and_replays_command_history() {
+        let lattice_id = "history";
+        let store = Arc::new(TestStore::default());
+        let command_publisher = CommandPublisher::new(
+            RecorderPublisher::<Command> {
+                received: Arc::default(),
+            },
+            "doesntmatter",
+        )
+        .with_sink(
+            lattice_id,
+            Arc::new(CommandHistorySink::new(
+                store.clone(),
+                Duration::from_secs(60 * 60 * 24),
+            )),
+        );
+
+        let scale = |model_name: &str, count| {
+            Command::ScaleComponent(ScaleComponent {
+                component_id: "component".to_string(),
+                reference: "fakecloud.io/http:0.1.0".to_string(),
+                host_id: "host".to_string(),
+                count,
+                model_name: model_name.to_string(),
+                ..Default::default()
+            })
+        };
+        let before = chrono::Utc::now();
+        command_publisher
+            .publish_commands(vec![scale("x", 1), scale("y", 1), scale("x", 2)])
+            .await
+            .unwrap();
+
+        let history = command_history(&*store, lattice_id, "x", before - chrono::Duration::days(1))
+            .await
+            .unwrap();
+        assert_eq!(
+            history
+                .iter()
+                .map(|recorded| recorded.command.clone())
+                .collect::<Vec<_>>(),
+            vec![scale("x", 1), scale("x", 2)],
+            "Only the commands for the model should be returned, in order"
+        );
+        assert!(
+            command_history(
+                &*store,
+                lattice_id,
+                "x",
+                chrono::Utc::now() + chrono::Duration::days(1)
+            )
+            .await
+            .unwrap()
+            .is_empty(),
+            "Commands sent before the start of the query should be filtered out"
+        );
+
+        // Replaying sends the commands to the command topic, where the command worker picks them up
+        let command_topic = RecorderPublisher::<Command> {
+            received: Arc::default(),
+        };
+        CommandPublisher::new(command_topic.clone(), "wadm.cmd.history")
+            .replay_commands(history)
+            .await
+            .unwrap();
+        assert_eq!(
+            *command_topic.received.read().await,
+            vec![scale("x", 1), scale("x", 2)]
+        );
+    }
+
+    #[test]
+    fn parses_sink_config() {
+        assert_eq!(
+            "nats:wadm.changes".parse::<SinkConfig>().unwrap(),
+            SinkConfig::Nats {
+                subject_prefix: "wadm.changes".to_string()
+            }
+        );
+        assert_eq!(
+            "kafka:http://localhost:8082/topics/wadm"
+                .parse::<SinkConfig>()
+                .unwrap(),
+            SinkConfig::Kafka {
+                topic_url: "http://localhost:8082/topics/wadm".to_string()
+            }
+        );
+        assert!("smtp:someone@example.com".parse::<SinkConfig>().is_err());
+        assert!("webhook:".parse::<SinkConfig>().is_err());
+    }
+}
@@ -0,0 +1,310 @@
+//! Contains a store wrapper that holds writes in memory until they are flushed in bulk
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::Arc,
+};
+
+use async_trait::async_trait;
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::Value;
+use tokio::sync::RwLock;
+
+use super::{
+    CasError, CommandClaim, Component, Host, InstanceLease, Link, Provider, ReadStore, StateKind,
+    Store, StoreError,
+};
+
+/// Buffered entries keyed by lattice ID and state kind. A `None` entry is a pending delete
+type Pending = BTreeMap<(String, &'static str), BTreeMap<String, Option<Value>>>;
+
+/// A [`Store`] that holds all writes in memory until [`BufferedStore::flush`] is called, which
+/// writes them to the underlying store with a single `store_many` and `delete_many` per lattice and
+/// kind of state. Reads see buffered writes, so code written against a normal store behaves the
+/// same. Multiple writes to the same entry are collapsed into the last one.
+///
+/// This is meant for rebuilding state from a large number of events, where writing every change
+/// as it happens would be slow. Only the kinds of state wadm tracks are buffered, anything else is
+/// written straight through. Clones share the same buffer
+pub struct BufferedStore<S> {
+    store: S,
+    pending: Arc<RwLock<Pending>>,
+}
+
+impl<S: Clone> Clone for BufferedStore<S> {
+    fn clone(&self) -> Self {
+        BufferedStore {
+            store: self.store.clone(),
+            pending: self.pending.clone(),
+        }
+    }
+}
+
+impl<S: Store + Send + Sync> BufferedStore<S> {
+    /// Returns a new store that buffers writes to the given store
+    pub fn new(store: S) -> BufferedStore<S> {
+        BufferedStore {
+            store,
+            pending: Arc::default(),
+        }
+    }
+
+    /// Writes everything that has been buffered to the underlying store. If a write fails, the
+    /// entries that haven't been written yet stay buffered so flushing can be retried
+    pub async fn flush(&self) -> Result<(), StoreError> {
+        let mut pending = self.pending.write().await;
+        while let Some(((lattice_id, kind), entries)) = pending.pop_first() {
+            let res = match kind {
+                Host::KIND => self.flush_kind::<Host>(&lattice_id, &entries).await,
+                Component::KIND => self.flush_kind::<Component>(&lattice_id, &entries).await,
+                Provider::KIND => self.flush_kind::<Provider>(&lattice_id, &entries).await,
+                Link::KIND => self.flush_kind::<Link>(&lattice_id, &entries).await,
+                CommandClaim::KIND => self.flush_kind::<CommandClaim>(&lattice_id, &entries).await,
+                InstanceLease::KIND => {
+                    self.flush_kind::<InstanceLease>(&lattice_id, &entries)
+                        .await
+                }
+                // Only buffered kinds are ever added
+                _ => Ok(()),
+            };
+            if let Err(e) = res {
+                pending.insert((lattice_id, kind), entries);
+                return Err(e);
+            }
+        }
+        Ok(())
+    }
+
+    async fn flush_kind<T>(
+        &self,
+        lattice_id: &str,
+        entries: &BTreeMap<String, Option<Value>>,
+    ) -> Result<(), StoreError>
+    where
+        T: Serialize + DeserializeOwned + StateKind + Send + Sync + Clone,
+    {
+        let mut stored = Vec::new();
+        let mut deleted = Vec::new();
+        for (id, value) in entries {
+            match value {
+                Some(value) => stored.push((id.to_owned(), T::deserialize(value)?)),
+                None => deleted.push(id.as_str()),
+            }
+        }
+        if !stored.is_empty() {
+            self.store.store_many(lattice_id, stored).await?;
+        }
+        if !deleted.is_empty() {
+            self.store
+                .delete_many::<T, _, _>(lattice_id, deleted)
+                .await?;
+        }
+        Ok(())
+    }
+}
+
+/// Returns whether the given kind of state is buffered rather than written straight through
+fn is_buffered(kind: &str) -> bool {
+    [
+        Host::KIND,
+        Component::KIND,
+        Provider::KIND,
+        Link::KIND,
+        CommandClaim::KIND,
+        InstanceLease::KIND,
+    ]
+    .contains(&kind)
+}
+
+#[async_trait]
+impl<S: Store + Send + Sync> ReadStore for BufferedStore<S> {
+    async fn get<T>(&self, lattice_id: &str, id: &str) -> Result<Option<T>, StoreError>
+    where
+        T: DeserializeOwned + StateKind,
+    {
+        let buffered = self
+            .pending
+            .read()
+            .await
+            .get(&(lattice_id.to_owned(), T::KIND))
+            .and_then(|entries| entries.get(id).cloned());
+        match buffered {
+            Some(value) => value
+                .map(serde_json::from_value)
+                .transpose()
+                .map_err(StoreError::from),
+            None => self.store.get(lattice_id, id).await,
+        }
+    }
+
+    async fn list<T>(&self, lattice_id: &str) -> Result<HashMap<String, T>, StoreError>
+    where
+        T: DeserializeOwned + StateKind,
+    {
+        // Buffered entries are copied out first since the listed data can't be held across an await
+        let buffered = self
+            .pending
+            .read()
+            .await
+            .get(&(lattice_id.to_owned(), T::KIND))
+            .cloned()
+            .unwrap_or_default();
+        let mut all = self.store.list::<T>(lattice_id).await?;
+        for (id, value) in buffered {
+            match value {
+                Some(value) => {
+                    all.insert(id, serde_json::from_value(value)?);
+                }
+                None => {
+                    all.remove(&id);
+                }
+            }
+        }
+        Ok(all)
+    }
+
+    async fn get_many<T, K>(
+        &self,
+        lattice_id: &str,
+        ids: K,
+    ) -> Result<HashMap<String, T>, StoreError>
+    where
+        T: DeserializeOwned + StateKind + Send,
+        K: IntoIterator<Item = String> + Send,
+        K::IntoIter: Send,
+    {
+        let ids = ids.into_iter().collect::<Vec<_>>();
+        let buffered = self
+            .pending
+            .read()
+            .await
+            .get(&(lattice_id.to_owned(), T::KIND))
+            .map(|entries| {
+                ids.iter()
+                    .filter_map(|id| entries.get(id).map(|value| (id.clone(), value.clone())))
+                    .collect::<HashMap<_, _>>()
+            })
+            .unwrap_or_default();
+        // Only the IDs without a buffered write need to be fetched from the underlying store
+        let mut found = self
+            .store
+            .get_many::<T, _>(
+                lattice_id,
+                ids.into_iter()
+                    .filter(|id| !buffered.contains_key(id))
+                    .collect::<Vec<_>>(),
+            )
+            .await?;
+        for (id, value) in buffered {
+            if let Some(value) = value {
+                found.insert(id, serde_json::from_value(value)?);
+            }
+        }
+        Ok(found)
+    }
+}
+
+#[async_trait]
+impl<S: Store + Send + Sync> Store for BufferedStore<S> {
+    async fn store_many<T, D>(&self, lattice_id: &str, data: D) -> Result<(), StoreError>
+    where
+        T: Serialize + DeserializeOwned + StateKind + Send + Sync + Clone,
+        D: IntoIterator<Item = (String, T)> + Send,
+    {
+        if !is_buffered(T::KIND) {
+            return self.store.store_many(lattice_id, data).await;
+        }
+        // Serialize everything first so a failure doesn't leave a partial write behind
+        let data = data
+            .into_iter()
+            .map(|(id, item)| Ok((id, Some(serde_json::to_value(item)?))))
+            .collect::<Result<Vec<_>, StoreError>>()?;
+        self.pending
+            .write()
+            .await
+            .entry((lattice_id.to_owned(), T::KIND))
+            .or_default()
+            .extend(data);
+        Ok(())
+    }
+
+    async fn delete_many<T, D, K>(&self, lattice_id: &str, data: D) -> Result<(), StoreError>
+    where
+        T: Serialize + DeserializeOwned + StateKind + Send + Sync,
+        D: IntoIterator<Item = K> + Send,
+        K: AsRef<str>,
+    {
+        if !is_buffered(T::KIND) {
+            return self.store.delete_many::<T, _, _>(lattice_id, data).await;
+        }
+        self.pending
+            .write()
+            .await
+            .entry((lattice_id.to_owned(), T::KIND))
+            .or_default()
+            .extend(data.into_iter().map(|id| (id.as_ref().to_owned(), None)));
+        Ok(())
+    }
+
+    /// Buffered kinds have no revision, since the buffer is only meant to have a single writer
+    async fn get_with_revision<T>(
+        &self,
+        lattice_id: &str,
+        id: &str,
+    ) -> Result<(Option<T>, u64), StoreError>
+    where
+        T: DeserializeOwned + StateKind + Send,
+    {
+        if !is_buffered(T::KIND) {
+            return self.store.get_with_revision(lattice_id, id).await;
+        }
+        Ok((self.get(lattice_id, id).await?, 0))
+    }
+
+    /// Buffered kinds are always written to the buffer, since the buffer is only meant to have a
+    /// single writer
+    async fn store_cas<T>(
+        &self,
+        lattice_id: &str,
+        id: String,
+        data: T,
+        expected_revision: u64,
+    ) -> Result<u64, CasError<StoreError>>
+    where
+        T: Serialize + DeserializeOwned + StateKind + Send + Sync + Clone,
+    {
+        if !is_buffered(T::KIND) {
+            return self
+                .store
+                .store_cas(lattice_id, id, data, expected_revision)
+                .await;
+        }
+        self.store(lattice_id, id, data)
+            .await
+            .map_err(CasError::Store)?;
+        Ok(0)
+    }
+
+    /// Buffered kinds are always deleted in the buffer, since the buffer is only meant to have a
+    /// single writer
+    async fn delete_cas<T>(
+        &self,
+        lattice_id: &str,
+        id: &str,
+        expected_revision: u64,
+    ) -> Result<u64, CasError<StoreError>>
+    where
+        T: Serialize + DeserializeOwned + StateKind + Send + Sync,
+    {
+        if !is_buffered(T::KIND) {
+            return self
+                .store
+                .delete_cas::<T>(lattice_id, id, expected_revision)
+                .await;
+        }
+        self.delete::<T>(lattice_id, id)
+            .await
+            .map_err(CasError::Store)?;
+        Ok(0)
+    }
+}
@@ -0,0 +1,356 @@
+//! Contains helpers for reconstructing a manifest from the managed state of a lattice
+
+use std::collections::{BTreeMap, HashMap};
+
+use anyhow::Result;
+use wadm_types::{
+    CapabilityProperties, Component as ManifestComponent, ComponentProperties, LinkProperty,
+    Manifest, Metadata, Properties, Specification, SpreadScalerProperty, TargetConfig, Trait,
+    APPLICATION_KIND, DESCRIPTION_ANNOTATION_KEY,
+};
+
+use crate::{
+    scaler::compute_component_id, storage::ReadStore, workers::LinkSource, APP_SPEC_ANNOTATION,
+};
+
+use super::{Component, Host};
+
+/// The OAM API version used for generated manifests
+const GENERATED_API_VERSION: &str = "core.oam.dev/v1beta1";
+
+/// Generates a best effort manifest for the given model from what is currently running in the
+/// lattice. Components and providers are found by the [`APP_SPEC_ANNOTATION`] wadm puts on
+/// everything it starts, and each one gets a spreadscaler with the number of instances currently
+/// running. Any links between the found components are added as link traits.
+///
+/// Only what can be observed from the lattice is recovered, so spread requirements, configuration
+/// and secrets will need to be added back to the generated manifest by hand. No version is set, so
+/// one will be generated if the manifest is put back into wadm
+pub async fn generate_manifest<S, L>(
+    store: &S,
+    link_source: &L,
+    lattice_id: &str,
+    model_name: &str,
+) -> Result<Manifest>
+where
+    S: ReadStore + Send + Sync,
+    L: LinkSource + Send + Sync,
+{
+    let is_managed = |annotations: &BTreeMap<String, String>| {
+        annotations.get(APP_SPEC_ANNOTATION).map(String::as_str) == Some(model_name)
+    };
+
+    // Keyed by ID so links can be resolved to the names used in the manifest
+    let mut found: BTreeMap<String, (String, Properties, usize)> = BTreeMap::new();
+
+    let components = store.list::<Component>(lattice_id).await?;
+    for (id, component) in components {
+        let count = component
+            .instances
+            .values()
+            .flatten()
+            .filter(|info| is_managed(&info.annotations))
+            .map(|info| info.count)
+            .sum::<usize>();
+        if count == 0 {
+            continue;
+        }
+        let (name, explicit_id) = component_name(model_name, &id);
+        let properties = Properties::Component {
+            properties: ComponentProperties {
+                image: (!component.reference.is_empty()).then_some(component.reference),
+                application: None,
+                id: explicit_id,
+                config: Vec::new(),
+                secrets: Vec::new(),
+            },
+        };
+        found.insert(id, (name, properties, count));
+    }
+
+    // Provider annotations are only tracked on the hosts they are running on
+    let hosts = store.list::<Host>(lattice_id).await?;
+    let mut providers: HashMap<&str, (&str, usize)> = HashMap::new();
+    for provider in hosts
+        .values()
+        .flat_map(|host| host.providers.iter())
+        .filter(|provider| is_managed(&provider.annotations))
+    {
+        providers
+            .entry(&provider.provider_id)
+            .or_insert((&provider.provider_ref, 0))
+            .1 += 1;
+    }
+    for (id, (reference, count)) in providers {
+        let (name, explicit_id) = component_name(model_name, id);
+        let properties = Properties::Capability {
+            properties: CapabilityProperties {
+                image: (!reference.is_empty()).then(|| reference.to_owned()),
+                application: None,
+                id: explicit_id,
+                config: Vec::new(),
+                secrets: Vec::new(),
+            },
+        };
+        found.insert(id.to_owned(), (name, properties, count));
+    }
+
+    let mut links: HashMap<String, Vec<Trait>> = HashMap::new();
+    for link in link_source.get_links().await? {
+        let Some((target_name, _, _)) = found
+            .get(link.target())
+            .filter(|_| found.contains_key(link.source_id()))
+        else {
+            continue;
+        };
+        links
+            .entry(link.source_id().to_owned())
+            .or_default()
+            .push(Trait::new_link(LinkProperty {
+                namespace: link.wit_namespace().to_owned(),
+                package: link.wit_package().to_owned(),
+                interfaces: link.interfaces().to_owned(),
+                target: TargetConfig {
+                    name: target_name.to_owned(),
+                    ..Default::default()
+                },
+                name: (link.name() != crate::DEFAULT_LINK_NAME).then(|| link.name().to_owned()),
+                ..Default::default()
+            }));
+    }
+
+    let mut components = found
+        .into_iter()
+        .map(|(id, (name, properties, count))| {
+            let mut traits = vec![Trait::new_spreadscaler(SpreadScalerProperty {
+                instances: count,
+                spread: Vec::new(),
+                topology_key: None,
+                min_count: None,
+                max_count: None,
+                anti_affinity: Vec::new(),
+                weight_key: None,
+                selector: Vec::new(),
+                reconcile_interval: None,
+                instances_per_host: None,
+            })];
+            traits.extend(links.remove(&id).unwrap_or_default());
+            ManifestComponent {
+                name,
+                properties,
+                traits: Some(traits),
+            }
+        })
+        .collect::<Vec<_>>();
+    components.sort_by(|a, b| a.name.cmp(&b.name));
+
+    Ok(Manifest {
+        api_version: GENERATED_API_VERSION.to_owned(),
+        kind: APPLICATION_KIND.to_owned(),
+        metadata: Metadata {
+            name: model_name.to_owned(),
+            annotations: BTreeMap::from_iter([(
+                DESCRIPTION_ANNOTATION_KEY.to_owned(),
+                format!("Generated from the running state of lattice {lattice_id}"),
+            )]),
+            labels: BTreeMap::new(),
+        },
+        spec: Specification {
+            components,
+            policies: Vec::new(),
+        },
+    })
+}
+
+/// Returns the manifest name for the given ID, along with the ID if it has to be set explicitly
+/// because it wasn't generated from the model and component name
+fn component_name(model_name: &str, id: &str) -> (String, Option<String>) {
+    // An empty component name gives us just the prefix of generated IDs
+    let prefix = compute_component_id(model_name, None, "");
+    match id.strip_prefix(&prefix) {
+        Some(name) if !name.is_empty() => (name.to_owned(), None),
+        _ => (id.to_owned(), Some(id.to_owned())),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashSet;
+    use std::sync::Arc;
+
+    use chrono::Utc;
+    use wadm_types::TraitProperty;
+    use wasmcloud_control_interface::Link;
+
+    use super::*;
+    use crate::{
+        events::ProviderInfo,
+        storage::{Store, WadmComponentInfo},
+        test_util::{TestLatticeSource, TestStore},
+        workers::insert_managed_annotations,
+    };
+
+    #[tokio::test]
+    async fn generates_manifest_from_managed_state() {
+        let lattice_id = "generate_manifest";
+        let store = Arc::new(TestStore::default());
+        let mut managed = BTreeMap::new();
+        insert_managed_annotations(&mut managed, "recovered");
+        let mut other = BTreeMap::new();
+        insert_managed_annotations(&mut other, "other");
+
+        let instances = |host_id: &str, annotations: &BTreeMap<String, String>, count| {
+            (
+                host_id.to_owned(),
+                HashSet::from_iter([WadmComponentInfo {
+                    annotations: annotations.clone(),
+                    count,
+                }]),
+            )
+        };
+        store
+            .store_many(
+                lattice_id,
+                [
+                    (
+                        "recovered-http_component".to_string(),
+                        Component {
+                            id: "recovered-http_component".to_string(),
+                            reference: "fakecloud.io/http:0.1.0".to_string(),
+                            instances: HashMap::from_iter([
+                                instances("host1", &managed, 3),
+                                instances("host2", &managed, 2),
+                            ]),
+                            ..Default::default()
+                        },
+                    ),
+                    (
+                        "custom_id".to_string(),
+                        Component {
+                            id: "custom_id".to_string(),
+                            reference: "fakecloud.io/custom:0.1.0".to_string(),
+                            instances: HashMap::from_iter([instances("host1", &managed, 1)]),
+                            ..Default::default()
+                        },
+                    ),
+                    (
+                        "other-component".to_string(),
+                        Component {
+                            id: "other-component".to_string(),
+                            reference: "fakecloud.io/other:0.1.0".to_string(),
+                            instances: HashMap::from_iter([instances("host1", &other, 4)]),
+                            ..Default::default()
+                        },
+                    ),
+                ],
+            )
+            .await
+            .unwrap();
+
+        let host = |id: &str| Host {
+            id: id.to_owned(),
+            providers: HashSet::from_iter([ProviderInfo {
+                provider_id: "recovered-httpserver".to_string(),
+                provider_ref: "fakecloud.io/httpserver:0.1.0".to_string(),
+                annotations: managed.clone(),
+            }]),
+            last_seen: Utc::now(),
+            ..Default::default()
+        };
+        store
+            .store_many(
+                lattice_id,
+                [
+                    ("host1".to_string(), host("host1")),
+                    ("host2".to_string(), host("host2")),
+                ],
+            )
+            .await
+            .unwrap();
+
+        let lattice_source = TestLatticeSource {
+            links: vec![
+                Link::builder()
+                    .source_id("recovered-httpserver")
+                    .target("recovered-http_component")
+                    .name("default")
+                    .wit_namespace("wasi")
+                    .wit_package("http")
+                    .interfaces(vec!["incoming-handler".to_string()])
+                    .build()
+                    .unwrap(),
+                // Links to things outside of the model aren't included
+                Link::builder()
+                    .source_id("recovered-http_component")
+                    .target("other-component")
+                    .name("default")
+                    .wit_namespace("wasi")
+                    .wit_package("keyvalue")
+                    .interfaces(vec!["store".to_string()])
+                    .build()
+                    .unwrap(),
+            ],
+            ..Default::default()
+        };
+
+        let manifest = generate_manifest(&store, &lattice_source, lattice_id, "recovered")
+            .await
+            .expect("Should be able to generate a manifest");
+
+        assert_eq!(manifest.metadata.name, "recovered");
+        let summary = manifest
+            .components()
+            .map(|component| {
+                let instances = component
+                    .traits
+                    .iter()
+                    .flatten()
+                    .find_map(|trt| match &trt.properties {
+                        TraitProperty::SpreadScaler(spread) => Some(spread.instances),
+                        _ => None,
+                    })
+                    .expect("Every component should have a spreadscaler");
+                (component.name.as_str(), instances)
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            summary,
+            vec![("custom_id", 1), ("http_component", 5), ("httpserver", 2)],
+            "Generated manifest should contain all managed components with their running counts"
+        );
+
+        let custom = manifest
+            .components()
+            .find(|c| c.name == "custom_id")
+            .unwrap();
+        assert!(
+            matches!(&custom.properties, Properties::Component { properties } if properties.id.as_deref() == Some("custom_id")),
+            "IDs that weren't generated from the model should be set explicitly"
+        );
+
+        let provider_links = manifest
+            .components()
+            .find(|c| c.name == "httpserver")
+            .unwrap()
+            .traits
+            .iter()
+            .flatten()
+            .filter_map(|trt| match &trt.properties {
+                TraitProperty::Link(link) => Some(link.target.name.as_str()),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(provider_links, vec!["http_component"]);
+        assert!(
+            manifest
+                .components()
+                .find(|c| c.name == "http_component")
+                .unwrap()
+                .traits
+                .iter()
+                .flatten()
+                .all(|trt| !trt.is_link()),
+            "Links to components outside of the model should not be included"
+        );
+    }
+}
@@ -0,0 +1,262 @@
+//! Contains helpers for keeping a bounded history of the statuses of each model, which helps when
+//! debugging deployments that keep flapping between statuses, and of the commands sent for each
+//! model, which can be reviewed and replayed
+
+use std::collections::VecDeque;
+
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use wadm_types::api::StatusInfo;
+
+use super::{update_cas, CasError, ReadStore, StateKind, Store, StoreError};
+use crate::commands::Command;
+
+/// The ID that the history of commands that don't belong to a model is stored under
+pub const NO_MODEL_HISTORY_ID: &str = "_";
+
+/// A status a model was in, along with when it was in it. Identical statuses reported one after
+/// another are coalesced into a single transition
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct StatusTransition {
+    /// The status the model transitioned to
+    pub status: StatusInfo,
+    /// When the model first reported this status
+    pub first_seen: DateTime<Utc>,
+    /// When the model last reported this status before transitioning to another one
+    pub last_seen: DateTime<Utc>,
+    /// The number of times in a row this status was reported
+    pub count: u64,
+}
+
+/// The most recent status transitions of a model, oldest first. The ID of the history is the name
+/// of the model
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+pub struct StatusHistory {
+    pub transitions: VecDeque<StatusTransition>,
+}
+
+impl StateKind for StatusHistory {
+    const KIND: &'static str = "statushistory";
+}
+
+impl StatusHistory {
+    /// Records that the given status was reported at the given time. If it is the same as the last
+    /// status, the last transition is updated instead of adding a new one. The oldest transitions
+    /// are dropped so no more than `max_transitions` are kept
+    pub fn record(&mut self, status: &StatusInfo, at: DateTime<Utc>, max_transitions: usize) {
+        match self.transitions.back_mut() {
+            Some(last) if last.status == *status => {
+                last.last_seen = at;
+                last.count += 1;
+            }
+            _ => self.transitions.push_back(StatusTransition {
+                status: status.clone(),
+                first_seen: at,
+                last_seen: at,
+                count: 1,
+            }),
+        }
+        while self.transitions.len() > max_transitions {
+            self.transitions.pop_front();
+        }
+    }
+}
+
+/// Records the given status in the history of the given model, keeping at most `max_transitions`
+/// transitions
+pub async fn record_status<S>(
+    store: &S,
+    lattice_id: &str,
+    model_name: &str,
+    status: &StatusInfo,
+    max_transitions: usize,
+) -> Result<(), CasError<StoreError>>
+where
+    S: Store + Sync + ?Sized,
+{
+    let now = Utc::now();
+    update_cas::<_, StatusHistory, _>(store, lattice_id, model_name, |current| {
+        let mut history = current.unwrap_or_default();
+        history.record(status, now, max_transitions);
+        Some(history)
+    })
+    .await
+    .map(|_| ())
+}
+
+/// Returns the recorded status transitions of the given model, oldest first. Models without any
+/// recorded statuses return an empty list
+pub async fn status_history<S>(
+    store: &S,
+    lattice_id: &str,
+    model_name: &str,
+) -> Result<Vec<StatusTransition>, StoreError>
+where
+    S: ReadStore + Send + Sync,
+{
+    Ok(store
+        .get::<StatusHistory>(lattice_id, model_name)
+        .await?
+        .map(|history| history.transitions.into())
+        .unwrap_or_default())
+}
+
+/// A command that was sent to a lattice, along with when it was sent
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct RecordedCommand {
+    /// The command that was sent
+    pub command: Command,
+    /// When the command was sent
+    pub sent_at: DateTime<Utc>,
+}
+
+/// The commands sent for a model within the retention window, oldest first. The ID of the history
+/// is the name of the model, or [`NO_MODEL_HISTORY_ID`] for commands that don't belong to a model
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+pub struct CommandHistory {
+    pub commands: VecDeque<RecordedCommand>,
+}
+
+impl StateKind for CommandHistory {
+    const KIND: &'static str = "commandhistory";
+}
+
+impl CommandHistory {
+    /// Records that the given command was sent at the given time, dropping any commands that were
+    /// sent longer than `retention` before it
+    pub fn record(&mut self, command: &Command, at: DateTime<Utc>, retention: Duration) {
+        self.commands.push_back(RecordedCommand {
+            command: command.clone(),
+            sent_at: at,
+        });
+        while self
+            .commands
+            .front()
+            .is_some_and(|recorded| at - recorded.sent_at > retention)
+        {
+            self.commands.pop_front();
+        }
+    }
+}
+
+/// Records the given command in the history of the model that sent it, dropping any commands in
+/// that history that are older than `retention`
+pub async fn record_command<S>(
+    store: &S,
+    lattice_id: &str,
+    command: &Command,
+    retention: Duration,
+) -> Result<(), CasError<StoreError>>
+where
+    S: Store + Sync + ?Sized,
+{
+    let now = Utc::now();
+    let id = command.model_name().unwrap_or(NO_MODEL_HISTORY_ID);
+    update_cas::<_, CommandHistory, _>(store, lattice_id, id, |current| {
+        let mut history = current.unwrap_or_default();
+        history.record(command, now, retention);
+        Some(history)
+    })
+    .await
+    .map(|_| ())
+}
+
+/// Returns the recorded commands of the given model that were sent at or after `since`, oldest
+/// first. Use [`NO_MODEL_HISTORY_ID`] to fetch the commands that don't belong to a model. The
+/// commands can be sent again with
+/// [`CommandPublisher::replay_commands`](crate::workers::CommandPublisher::replay_commands)
+pub async fn command_history<S>(
+    store: &S,
+    lattice_id: &str,
+    model_name: &str,
+    since: DateTime<Utc>,
+) -> Result<Vec<RecordedCommand>, StoreError>
+where
+    S: ReadStore + Send + Sync,
+{
+    Ok(store
+        .get::<CommandHistory>(lattice_id, model_name)
+        .await?
+        .map(|history| {
+            history
+                .commands
+                .into_iter()
+                .filter(|recorded| recorded.sent_at >= since)
+                .collect()
+        })
+        .unwrap_or_default())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_util::TestStore;
+
+    #[tokio::test]
+    async fn history_coalesces_and_is_bounded() {
+        let store = TestStore::default();
+        let lattice_id = "history";
+
+        let reconciling = StatusInfo::reconciling("");
+        let deployed = StatusInfo::deployed("");
+        let failed = StatusInfo::failed("no hosts");
+        for status in [&reconciling, &reconciling, &deployed, &failed, &deployed] {
+            record_status(&store, lattice_id, "flappy", status, 3)
+                .await
+                .expect("Should be able to record status");
+        }
+
+        let history = status_history(&store, lattice_id, "flappy").await.unwrap();
+        assert_eq!(
+            history
+                .iter()
+                .map(|transition| &transition.status)
+                .collect::<Vec<_>>(),
+            vec![&deployed, &failed, &deployed],
+            "Only the most recent transitions should be kept"
+        );
+        assert!(history.iter().all(|transition| transition.count == 1));
+
+        record_status(&store, lattice_id, "flappy", &deployed, 3)
+            .await
+            .unwrap();
+        let history = status_history(&store, lattice_id, "flappy").await.unwrap();
+        assert_eq!(history.len(), 3, "Identical status should be coalesced");
+        let last = history.last().unwrap();
+        assert_eq!(last.count, 2);
+        assert!(last.last_seen >= last.first_seen);
+
+        assert!(
+            status_history(&store, lattice_id, "unknown")
+                .await
+                .unwrap()
+                .is_empty(),
+            "Models without statuses should have an empty history"
+        );
+    }
+
+    #[test]
+    fn command_history_drops_commands_past_retention() {
+        let now = Utc::now();
+        let command = |count| {
+            Command::from(crate::commands::ScaleComponent {
+                model_name: "model".to_string(),
+                count,
+                ..Default::default()
+            })
+        };
+        let mut history = CommandHistory::default();
+        history.record(&command(1), now - Duration::hours(25), Duration::hours(24));
+        history.record(&command(2), now - Duration::hours(1), Duration::hours(24));
+        history.record(&command(3), now, Duration::hours(24));
+        assert_eq!(
+            history
+                .commands
+                .iter()
+                .map(|recorded| recorded.command.clone())
+                .collect::<Vec<_>>(),
+            vec![command(2), command(3)],
+            "Commands older than the retention window should be dropped"
+        );
+    }
+}
@@ -0,0 +1,306 @@
+//! Storage engine that keeps all state in memory
+//!
+//! This is meant for embedding wadm's scalers and workers without any external infrastructure, such
+//! as in integration tests. Nothing is persisted and state is not shared between processes, so it
+//! should not be used to run wadm itself. Data is serialized as JSON like the NATS KV store, so
+//! types behave the same way with either store
+
+use std::{collections::HashMap, sync::Arc};
+
+use async_trait::async_trait;
+use serde::{de::DeserializeOwned, Serialize};
+use tokio::sync::RwLock;
+
+use super::{CasError, ConsistentRead, ReadStore, StateKind, Store, StoreError};
+
+type Entries = HashMap<(String, &'static str), KindEntries>;
+
+/// The serialized state of a single kind in a lattice, with a revision that is bumped on every
+/// write to it
+#[derive(Debug, Clone, Default)]
+struct KindEntries {
+    revision: u64,
+    items: HashMap<String, Vec<u8>>,
+}
+
+/// A [`Store`] implementation that keeps all state in memory. Clones share the same state
+#[derive(Debug, Clone, Default)]
+pub struct MemoryStore {
+    inner: Arc<RwLock<Entries>>,
+}
+
+impl MemoryStore {
+    /// Returns a new, empty store
+    pub fn new() -> MemoryStore {
+        MemoryStore::default()
+    }
+}
+
+fn entry_key<T: StateKind>(lattice_id: &str) -> (String, &'static str) {
+    (lattice_id.to_owned(), T::KIND)
+}
+
+#[async_trait]
+impl ReadStore for MemoryStore {
+    async fn get<T>(&self, lattice_id: &str, id: &str) -> Result<Option<T>, StoreError>
+    where
+        T: DeserializeOwned + StateKind,
+    {
+        self.inner
+            .read()
+            .await
+            .get(&entry_key::<T>(lattice_id))
+            .and_then(|entries| entries.items.get(id))
+            .map(|raw| serde_json::from_slice(raw))
+            .transpose()
+            .map_err(StoreError::from)
+    }
+
+    async fn list<T>(&self, lattice_id: &str) -> Result<HashMap<String, T>, StoreError>
+    where
+        T: DeserializeOwned + StateKind,
+    {
+        self.inner
+            .read()
+            .await
+            .get(&entry_key::<T>(lattice_id))
+            .map(|entries| {
+                entries
+                    .items
+                    .iter()
+                    .map(|(id, raw)| Ok((id.to_owned(), serde_json::from_slice(raw)?)))
+                    .collect()
+            })
+            .unwrap_or_else(|| Ok(HashMap::new()))
+    }
+}
+
+#[async_trait]
+impl Store for MemoryStore {
+    async fn store_many<T, D>(&self, lattice_id: &str, data: D) -> Result<(), StoreError>
+    where
+        T: Serialize + DeserializeOwned + StateKind + Send + Sync + Clone,
+        D: IntoIterator<Item = (String, T)> + Send,
+    {
+        // Serialize everything first so a failure doesn't leave a partial write behind
+        let data = data
+            .into_iter()
+            .map(|(id, item)| Ok((id, serde_json::to_vec(&item)?)))
+            .collect::<Result<Vec<_>, StoreError>>()?;
+        let mut inner = self.inner.write().await;
+        let entries = inner.entry(entry_key::<T>(lattice_id)).or_default();
+        entries.items.extend(data);
+        entries.revision += 1;
+        Ok(())
+    }
+
+    async fn delete_many<T, D, K>(&self, lattice_id: &str, data: D) -> Result<(), StoreError>
+    where
+        T: Serialize + DeserializeOwned + StateKind + Send + Sync,
+        D: IntoIterator<Item = K> + Send,
+        K: AsRef<str>,
+    {
+        if let Some(entries) = self
+            .inner
+            .write()
+            .await
+            .get_mut(&entry_key::<T>(lattice_id))
+        {
+            for id in data {
+                entries.items.remove(id.as_ref());
+            }
+            entries.revision += 1;
+        }
+        Ok(())
+    }
+
+    /// The revision is tracked per kind of state in a lattice, so it changes whenever any item of
+    /// the same kind does
+    async fn get_with_revision<T>(
+        &self,
+        lattice_id: &str,
+        id: &str,
+    ) -> Result<(Option<T>, u64), StoreError>
+    where
+        T: DeserializeOwned + StateKind + Send,
+    {
+        let inner = self.inner.read().await;
+        let Some(entries) = inner.get(&entry_key::<T>(lattice_id)) else {
+            return Ok((None, 0));
+        };
+        let data = entries
+            .items
+            .get(id)
+            .map(|raw| serde_json::from_slice(raw))
+            .transpose()?;
+        Ok((data, entries.revision))
+    }
+
+    async fn store_cas<T>(
+        &self,
+        lattice_id: &str,
+        id: String,
+        data: T,
+        expected_revision: u64,
+    ) -> Result<u64, CasError<StoreError>>
+    where
+        T: Serialize + DeserializeOwned + StateKind + Send + Sync + Clone,
+    {
+        let raw = serde_json::to_vec(&data).map_err(|e| CasError::Store(StoreError::from(e)))?;
+        let mut inner = self.inner.write().await;
+        let entries = inner.entry(entry_key::<T>(lattice_id)).or_default();
+        if entries.revision != expected_revision {
+            return Err(CasError::Conflict {
+                expected: expected_revision,
+            });
+        }
+        entries.items.insert(id, raw);
+        entries.revision += 1;
+        Ok(entries.revision)
+    }
+
+    async fn delete_cas<T>(
+        &self,
+        lattice_id: &str,
+        id: &str,
+        expected_revision: u64,
+    ) -> Result<u64, CasError<StoreError>>
+    where
+        T: Serialize + DeserializeOwned + StateKind + Send + Sync,
+    {
+        let mut inner = self.inner.write().await;
+        let entries = inner.entry(entry_key::<T>(lattice_id)).or_default();
+        if entries.revision != expected_revision {
+            return Err(CasError::Conflict {
+                expected: expected_revision,
+            });
+        }
+        if entries.items.remove(id).is_some() {
+            entries.revision += 1;
+        }
+        Ok(entries.revision)
+    }
+}
+
+#[async_trait]
+impl ConsistentRead for MemoryStore {
+    async fn consistent_view(&self) -> Self {
+        MemoryStore {
+            inner: Arc::new(RwLock::new(self.inner.read().await.clone())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use chrono::Utc;
+
+    use super::*;
+    use crate::storage::{Component, Host};
+
+    #[tokio::test]
+    async fn stores_state_per_lattice_and_kind() {
+        let store = MemoryStore::new();
+        let host = Host {
+            id: "host1".to_string(),
+            last_seen: Utc::now(),
+            ..Default::default()
+        };
+        store
+            .store("lattice1", "host1".to_string(), host.clone())
+            .await
+            .unwrap();
+        store
+            .store(
+                "lattice1",
+                "component1".to_string(),
+                Component {
+                    id: "component1".to_string(),
+                    ..Default::default()
+                },
+            )
+            .await
+            .unwrap();
+
+        let stored = store
+            .get::<Host>("lattice1", "host1")
+            .await
+            .unwrap()
+            .expect("Host should be stored");
+        assert_eq!(stored.last_seen, host.last_seen);
+        let hosts = store.list::<Host>("lattice1").await.unwrap();
+        assert_eq!(
+            hosts.keys().collect::<Vec<_>>(),
+            vec!["host1"],
+            "Listing should only return state of the requested kind"
+        );
+        assert!(store.list::<Host>("lattice2").await.unwrap().is_empty());
+
+        let view = store.consistent_view().await;
+        store.delete::<Host>("lattice1", "host1").await.unwrap();
+        assert!(store
+            .get::<Host>("lattice1", "host1")
+            .await
+            .unwrap()
+            .is_none());
+        assert!(
+            view.get::<Host>("lattice1", "host1")
+                .await
+                .unwrap()
+                .is_some(),
+            "Views should not see changes made after they were taken"
+        );
+    }
+
+    #[tokio::test]
+    async fn rejects_stale_compare_and_swap() {
+        let store = MemoryStore::new();
+        let host = |labels: &[(&str, &str)]| Host {
+            id: "host1".to_string(),
+            labels: labels
+                .iter()
+                .map(|(key, value)| (key.to_string(), value.to_string()))
+                .collect(),
+            last_seen: Utc::now(),
+            ..Default::default()
+        };
+        let (current, revision) = store
+            .get_with_revision::<Host>("lattice1", "host1")
+            .await
+            .unwrap();
+        assert!(current.is_none());
+        let revision = store
+            .store_cas("lattice1", "host1".to_string(), host(&[]), revision)
+            .await
+            .unwrap();
+
+        // A write that happens after the read makes the stale swap fail
+        store
+            .store("lattice1", "host1".to_string(), host(&[("zone", "a")]))
+            .await
+            .unwrap();
+        let err = store
+            .store_cas("lattice1", "host1".to_string(), host(&[]), revision)
+            .await
+            .unwrap_err();
+        assert!(matches!(err, CasError::Conflict { expected } if expected == revision));
+
+        let (current, revision) = store
+            .get_with_revision::<Host>("lattice1", "host1")
+            .await
+            .unwrap();
+        let mut updated = current.expect("Host should be stored");
+        assert_eq!(updated.labels.get("zone").map(String::as_str), Some("a"));
+        updated.labels.insert("tier".to_string(), "web".to_string());
+        store
+            .store_cas("lattice1", "host1".to_string(), updated, revision)
+            .await
+            .unwrap();
+        let stored = store
+            .get::<Host>("lattice1", "host1")
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(stored.labels.len(), 2, "Both writes should be kept");
+    }
+}
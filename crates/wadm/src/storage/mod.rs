@@ -2,12 +2,77 @@ use async_trait::async_trait;
 use serde::{de::DeserializeOwned, Serialize};
 use std::{collections::HashMap, ops::Deref};
 
+pub mod buffered;
+pub mod export;
+pub mod history;
+#[cfg(feature = "memory_store")]
+pub mod memory;
 pub mod nats_kv;
+pub mod owner;
+pub mod read_through;
 pub mod reaper;
 pub(crate) mod snapshot;
 mod state;
 
-pub use state::{Component, Host, Provider, ProviderStatus, WadmComponentInfo};
+pub use buffered::BufferedStore;
+pub use export::generate_manifest;
+pub use history::{
+    command_history, record_command, record_status, status_history, CommandHistory,
+    RecordedCommand, StatusHistory, StatusTransition, NO_MODEL_HISTORY_ID,
+};
+#[cfg(feature = "memory_store")]
+pub use memory::MemoryStore;
+pub use owner::find_owning_model;
+pub use read_through::ReadThroughStore;
+pub use state::{
+    CommandClaim, Component, Host, InstanceLease, Link, Provider, ProviderHostStatus,
+    ProviderStatus, WadmComponentInfo,
+};
+
+/// The number of times [`update_cas`] retries an update that conflicted with another write
+pub(crate) const CAS_RETRIES: usize = 10;
+
+/// Errors returned by [`ReadStore`] and [`Store`] implementations. Callers can match on the
+/// variant to decide whether an operation is worth retrying. Every variant converts into an
+/// [`anyhow::Error`] with `?` for callers that don't care
+#[derive(Debug, thiserror::Error)]
+pub enum StoreError {
+    /// The requested key doesn't exist in a store that can't treat it as empty
+    #[error("Key not found: {0}")]
+    NotFound(String),
+
+    /// Errors that result from serializing or deserializing the data in the store
+    #[error("Error when encoding or decoding data in store: {0}")]
+    Serialization(#[from] serde_json::Error),
+
+    /// The store couldn't be reached or didn't respond in time. These are generally transient and
+    /// can be retried
+    #[error("Unable to reach store: {0}")]
+    Connection(Box<dyn std::error::Error + Send + Sync>),
+
+    /// A catch all error for anything else
+    #[error("{0}")]
+    Other(String),
+}
+
+impl StoreError {
+    /// Returns true if the error is transient and the operation can be retried
+    pub fn is_retryable(&self) -> bool {
+        matches!(self, StoreError::Connection(_))
+    }
+}
+
+/// Errors returned by [`Store::store_cas`]
+#[derive(Debug, thiserror::Error)]
+pub enum CasError<E> {
+    /// The state was written by someone else after it was read at the expected revision
+    #[error("State has changed since it was read at revision {expected}")]
+    Conflict { expected: u64 },
+
+    /// An error from the underlying store
+    #[error(transparent)]
+    Store(E),
+}
 
 /// A trait that must be implemented with a unique identifier for the given type. This is used in
 /// the construction of keys for a store
@@ -18,22 +83,53 @@ pub trait StateKind {
 
 #[async_trait]
 pub trait ReadStore {
-    type Error: std::error::Error + Send + Sync + 'static;
-
     /// Get the state for the specified kind with the given ID. Returns None if it doesn't exist
     ///
     /// The ID can vary depending on the type, but should be the unique ID for the object (e.g. a
     /// host key)
-    async fn get<T>(&self, lattice_id: &str, id: &str) -> Result<Option<T>, Self::Error>
+    async fn get<T>(&self, lattice_id: &str, id: &str) -> Result<Option<T>, StoreError>
     where
         T: DeserializeOwned + StateKind;
 
     /// Returns a map of all items of the given type.
     ///
     /// The map key is the value as given by [`StateId::id`]
-    async fn list<T>(&self, lattice_id: &str) -> Result<HashMap<String, T>, Self::Error>
+    async fn list<T>(&self, lattice_id: &str) -> Result<HashMap<String, T>, StoreError>
     where
         T: DeserializeOwned + StateKind;
+
+    /// Returns a map of the items of the given type with the given IDs. IDs that don't exist are
+    /// left out of the map
+    ///
+    /// The default implementation calls [`ReadStore::get`] for each ID, so stores that can fetch
+    /// multiple items at once should override it
+    async fn get_many<T, K>(
+        &self,
+        lattice_id: &str,
+        ids: K,
+    ) -> Result<HashMap<String, T>, StoreError>
+    where
+        T: DeserializeOwned + StateKind + Send,
+        K: IntoIterator<Item = String> + Send,
+        K::IntoIter: Send,
+    {
+        let mut found = HashMap::new();
+        for id in ids {
+            if let Some(item) = self.get(lattice_id, &id).await? {
+                found.insert(id, item);
+            }
+        }
+        Ok(found)
+    }
+}
+
+/// A [`ReadStore`] that can hand out a point in time view of its data. Reads from the returned view
+/// are not affected by any changes made to the original store after the view was taken, so
+/// multiple reads from the same view are always consistent with each other
+#[async_trait]
+pub trait ConsistentRead: ReadStore + Sized {
+    /// Returns a view of the current state of this store
+    async fn consistent_view(&self) -> Self;
 }
 
 /// A trait that indicates the ability of a struct to store state
@@ -50,7 +146,7 @@ pub trait Store: ReadStore {
     /// Store a piece of state with the given ID. This should overwrite existing state entries
     ///
     /// By default this will just call [`Store::store_many`] with a single item in the list of data
-    async fn store<T>(&self, lattice_id: &str, id: String, data: T) -> Result<(), Self::Error>
+    async fn store<T>(&self, lattice_id: &str, id: String, data: T) -> Result<(), StoreError>
     where
         T: Serialize + DeserializeOwned + StateKind + Send + Sync + Clone, // Needs to be clone in order to retry updates
     {
@@ -69,15 +165,47 @@ pub trait Store: ReadStore {
     /// [`StateKind`] is needed in order to store and access the data correctly,
     /// and, lastly, [`Send`] is needed because this is an async function and the data needs to be
     /// sendable between threads
-    async fn store_many<T, D>(&self, lattice_id: &str, data: D) -> Result<(), Self::Error>
+    async fn store_many<T, D>(&self, lattice_id: &str, data: D) -> Result<(), StoreError>
     where
         T: Serialize + DeserializeOwned + StateKind + Send + Sync + Clone, // Needs to be clone in order to retry updates
         D: IntoIterator<Item = (String, T)> + Send;
 
+    /// Store multiple items of the same type all or nothing. If this returns an error, none of the
+    /// given items have changed. Use this rather than [`Store::store_many`] when a partially
+    /// applied write would leave state the caller can't recover from
+    ///
+    /// By default this reads the current value of every item first and, if
+    /// [`Store::store_many`] fails, writes them back and deletes any items it created. Writes
+    /// made to the same items by someone else while rolling back can be overwritten, so stores
+    /// that can write a batch in a single operation should override this
+    async fn store_many_atomic<T, D>(&self, lattice_id: &str, data: D) -> Result<(), StoreError>
+    where
+        T: Serialize + DeserializeOwned + StateKind + Send + Sync + Clone,
+        D: IntoIterator<Item = (String, T)> + Send,
+    {
+        let data: Vec<(String, T)> = data.into_iter().collect();
+        let ids = data.iter().map(|(id, _)| id.to_owned()).collect::<Vec<_>>();
+        let previous: HashMap<String, T> = self.get_many(lattice_id, ids.clone()).await?;
+        let Err(e) = self.store_many(lattice_id, data).await else {
+            return Ok(());
+        };
+        let created = ids
+            .into_iter()
+            .filter(|id| !previous.contains_key(id))
+            .collect::<Vec<_>>();
+        if let Err(rollback) = self.store_many(lattice_id, previous).await {
+            tracing::error!(error = %rollback, kind = T::KIND, "Unable to restore state after a failed write");
+        }
+        if let Err(rollback) = self.delete_many::<T, _, _>(lattice_id, created).await {
+            tracing::error!(error = %rollback, kind = T::KIND, "Unable to remove state after a failed write");
+        }
+        Err(e)
+    }
+
     /// Delete a state entry
     ///
     /// By default this will just call [`Store::delete_many`] with a single item in the list of data
-    async fn delete<T>(&self, lattice_id: &str, id: &str) -> Result<(), Self::Error>
+    async fn delete<T>(&self, lattice_id: &str, id: &str) -> Result<(), StoreError>
     where
         T: Serialize + DeserializeOwned + StateKind + Send + Sync,
     {
@@ -95,17 +223,115 @@ pub trait Store: ReadStore {
     /// [`StateKind`] is needed in order to store and access the data correctly, and, lastly,
     /// [`Send`] is needed because this is an async function and the modified data needs to be
     /// sendable between threads
-    async fn delete_many<T, D, K>(&self, lattice_id: &str, data: D) -> Result<(), Self::Error>
+    async fn delete_many<T, D, K>(&self, lattice_id: &str, data: D) -> Result<(), StoreError>
     where
         T: Serialize + DeserializeOwned + StateKind + Send + Sync,
         D: IntoIterator<Item = K> + Send,
         K: AsRef<str>;
+
+    /// Get the state for the specified kind with the given ID, along with the revision it was read
+    /// at so it can be written back with [`Store::store_cas`]. The revision changes whenever the
+    /// state does, and may also change when other state of the same kind does
+    ///
+    /// By default this always returns revision 0, for stores that don't track revisions
+    async fn get_with_revision<T>(
+        &self,
+        lattice_id: &str,
+        id: &str,
+    ) -> Result<(Option<T>, u64), StoreError>
+    where
+        T: DeserializeOwned + StateKind + Send,
+    {
+        Ok((self.get(lattice_id, id).await?, 0))
+    }
+
+    /// Store a piece of state with the given ID, but only if nothing has been written since it was
+    /// read at `expected_revision` by [`Store::get_with_revision`]. Returns the new revision, or
+    /// [`CasError::Conflict`] if something else wrote to the store in the meantime
+    ///
+    /// By default this stores the data unconditionally and returns revision 0, which is only safe
+    /// for stores with a single writer
+    async fn store_cas<T>(
+        &self,
+        lattice_id: &str,
+        id: String,
+        data: T,
+        expected_revision: u64,
+    ) -> Result<u64, CasError<StoreError>>
+    where
+        T: Serialize + DeserializeOwned + StateKind + Send + Sync + Clone,
+    {
+        let _ = expected_revision;
+        self.store(lattice_id, id, data)
+            .await
+            .map_err(CasError::Store)?;
+        Ok(0)
+    }
+
+    /// Delete the state with the given ID, but only if nothing has been written since it was read
+    /// at `expected_revision` by [`Store::get_with_revision`]. Returns the new revision, or
+    /// [`CasError::Conflict`] if something else wrote to the store in the meantime
+    ///
+    /// By default this deletes the data unconditionally and returns revision 0, which is only safe
+    /// for stores with a single writer
+    async fn delete_cas<T>(
+        &self,
+        lattice_id: &str,
+        id: &str,
+        expected_revision: u64,
+    ) -> Result<u64, CasError<StoreError>>
+    where
+        T: Serialize + DeserializeOwned + StateKind + Send + Sync,
+    {
+        let _ = expected_revision;
+        self.delete::<T>(lattice_id, id)
+            .await
+            .map_err(CasError::Store)?;
+        Ok(0)
+    }
+}
+
+/// Reads the state with the given ID, passes it to `update` and writes back whatever it returns
+/// with [`Store::store_cas`], starting over from the read if something else wrote to the store in
+/// the meantime. Nothing is written if `update` returns `None`. Returns the state that was written
+pub async fn update_cas<S, T, F>(
+    store: &S,
+    lattice_id: &str,
+    id: &str,
+    mut update: F,
+) -> Result<Option<T>, CasError<StoreError>>
+where
+    S: Store + Sync + ?Sized,
+    T: Serialize + DeserializeOwned + StateKind + Send + Sync + Clone,
+    F: FnMut(Option<T>) -> Option<T> + Send,
+{
+    let mut attempt = 0;
+    loop {
+        let (current, revision) = store
+            .get_with_revision::<T>(lattice_id, id)
+            .await
+            .map_err(CasError::Store)?;
+        let Some(updated) = update(current) else {
+            return Ok(None);
+        };
+        match store
+            .store_cas(lattice_id, id.to_owned(), updated.clone(), revision)
+            .await
+        {
+            Ok(_) => return Ok(Some(updated)),
+            Err(CasError::Conflict { .. }) if attempt < CAS_RETRIES => {
+                tracing::debug!(%id, kind = T::KIND, "State changed while updating it, retrying");
+                attempt += 1;
+            }
+            Err(e) => return Err(e),
+        }
+    }
 }
 
 // Helper for making sure you can wrap any non-clonable store in an Arc
 #[async_trait]
 impl<S: Store + Send + Sync> Store for std::sync::Arc<S> {
-    async fn store_many<T, D>(&self, lattice_id: &str, data: D) -> Result<(), Self::Error>
+    async fn store_many<T, D>(&self, lattice_id: &str, data: D) -> Result<(), StoreError>
     where
         T: Serialize + DeserializeOwned + StateKind + Send + Sync + Clone,
         D: IntoIterator<Item = (String, T)> + Send,
@@ -113,7 +339,15 @@ impl<S: Store + Send + Sync> Store for std::sync::Arc<S> {
         self.as_ref().store_many(lattice_id, data).await
     }
 
-    async fn delete_many<T, D, K>(&self, lattice_id: &str, data: D) -> Result<(), Self::Error>
+    async fn store_many_atomic<T, D>(&self, lattice_id: &str, data: D) -> Result<(), StoreError>
+    where
+        T: Serialize + DeserializeOwned + StateKind + Send + Sync + Clone,
+        D: IntoIterator<Item = (String, T)> + Send,
+    {
+        self.as_ref().store_many_atomic(lattice_id, data).await
+    }
+
+    async fn delete_many<T, D, K>(&self, lattice_id: &str, data: D) -> Result<(), StoreError>
     where
         T: Serialize + DeserializeOwned + StateKind + Send + Sync,
         D: IntoIterator<Item = K> + Send,
@@ -121,25 +355,76 @@ impl<S: Store + Send + Sync> Store for std::sync::Arc<S> {
     {
         self.as_ref().delete_many::<T, _, _>(lattice_id, data).await
     }
+
+    async fn get_with_revision<T>(
+        &self,
+        lattice_id: &str,
+        id: &str,
+    ) -> Result<(Option<T>, u64), StoreError>
+    where
+        T: DeserializeOwned + StateKind + Send,
+    {
+        self.as_ref().get_with_revision(lattice_id, id).await
+    }
+
+    async fn store_cas<T>(
+        &self,
+        lattice_id: &str,
+        id: String,
+        data: T,
+        expected_revision: u64,
+    ) -> Result<u64, CasError<StoreError>>
+    where
+        T: Serialize + DeserializeOwned + StateKind + Send + Sync + Clone,
+    {
+        self.as_ref()
+            .store_cas(lattice_id, id, data, expected_revision)
+            .await
+    }
+
+    async fn delete_cas<T>(
+        &self,
+        lattice_id: &str,
+        id: &str,
+        expected_revision: u64,
+    ) -> Result<u64, CasError<StoreError>>
+    where
+        T: Serialize + DeserializeOwned + StateKind + Send + Sync,
+    {
+        self.as_ref()
+            .delete_cas::<T>(lattice_id, id, expected_revision)
+            .await
+    }
 }
 
 #[async_trait]
 impl<S: Store + Send + Sync> ReadStore for std::sync::Arc<S> {
-    type Error = S::Error;
-
-    async fn get<T>(&self, lattice_id: &str, id: &str) -> Result<Option<T>, Self::Error>
+    async fn get<T>(&self, lattice_id: &str, id: &str) -> Result<Option<T>, StoreError>
     where
         T: DeserializeOwned + StateKind,
     {
         self.as_ref().get(lattice_id, id).await
     }
 
-    async fn list<T>(&self, lattice_id: &str) -> Result<HashMap<String, T>, Self::Error>
+    async fn list<T>(&self, lattice_id: &str) -> Result<HashMap<String, T>, StoreError>
     where
         T: DeserializeOwned + StateKind,
     {
         self.as_ref().list(lattice_id).await
     }
+
+    async fn get_many<T, K>(
+        &self,
+        lattice_id: &str,
+        ids: K,
+    ) -> Result<HashMap<String, T>, StoreError>
+    where
+        T: DeserializeOwned + StateKind + Send,
+        K: IntoIterator<Item = String> + Send,
+        K::IntoIter: Send,
+    {
+        self.as_ref().get_many(lattice_id, ids).await
+    }
 }
 
 /// Scoped store is a convenience wrapper around a store that automatically passes the given
@@ -190,7 +475,7 @@ impl<S: Store + Sync> ScopedStore<S> {
     ///
     /// The ID can vary depending on the type, but should be the unique ID for the object (e.g. a
     /// host key)
-    pub async fn get<T>(&self, id: &str) -> Result<Option<T>, S::Error>
+    pub async fn get<T>(&self, id: &str) -> Result<Option<T>, StoreError>
     where
         T: DeserializeOwned + StateKind,
     {
@@ -200,7 +485,7 @@ impl<S: Store + Sync> ScopedStore<S> {
     /// Returns a map of all items of the given type.
     ///
     /// The map key is the value as given by [`StateId::id`]
-    pub async fn list<T>(&self) -> Result<HashMap<String, T>, S::Error>
+    pub async fn list<T>(&self) -> Result<HashMap<String, T>, StoreError>
     where
         T: DeserializeOwned + StateKind,
     {
@@ -208,7 +493,7 @@ impl<S: Store + Sync> ScopedStore<S> {
     }
 
     /// Store a piece of state. This should overwrite existing state entries
-    pub async fn store<T>(&self, id: String, data: T) -> Result<(), S::Error>
+    pub async fn store<T>(&self, id: String, data: T) -> Result<(), StoreError>
     where
         T: Serialize + DeserializeOwned + StateKind + Send + Sync + Clone,
     {
@@ -217,7 +502,7 @@ impl<S: Store + Sync> ScopedStore<S> {
 
     /// Store multiple items of the same type. This should overwrite existing state entries. This
     /// allows for stores to perform multiple writes simultaneously or to leverage transactions
-    pub async fn store_many<T, D>(&self, data: D) -> Result<(), S::Error>
+    pub async fn store_many<T, D>(&self, data: D) -> Result<(), StoreError>
     where
         T: Serialize + DeserializeOwned + StateKind + Send + Sync + Clone,
         D: IntoIterator<Item = (String, T)> + Send,
@@ -226,7 +511,7 @@ impl<S: Store + Sync> ScopedStore<S> {
     }
 
     /// Delete a state entry
-    pub async fn delete<T>(&self, id: &str) -> Result<(), S::Error>
+    pub async fn delete<T>(&self, id: &str) -> Result<(), StoreError>
     where
         T: Serialize + DeserializeOwned + StateKind + Send + Sync,
     {
@@ -235,7 +520,7 @@ impl<S: Store + Sync> ScopedStore<S> {
 
     /// Delete multiple state entries. This allows for stores to perform multiple delete
     /// simultaneously or to leverage transactions
-    pub async fn delete_many<T, D, K>(&self, data: D) -> Result<(), S::Error>
+    pub async fn delete_many<T, D, K>(&self, data: D) -> Result<(), StoreError>
     where
         T: Serialize + DeserializeOwned + StateKind + Send + Sync,
         D: IntoIterator<Item = K> + Send,
@@ -246,3 +531,126 @@ impl<S: Store + Sync> ScopedStore<S> {
             .await
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_util::TestStore;
+
+    /// A store that writes each item of a batch separately, and can fail a single write after a
+    /// set number of successful ones
+    #[derive(Default)]
+    struct FailingStore {
+        inner: TestStore,
+        writes_before_failure: std::sync::Mutex<Option<usize>>,
+    }
+
+    impl FailingStore {
+        fn count_write(&self) -> Result<(), StoreError> {
+            let mut writes_before_failure = self.writes_before_failure.lock().unwrap();
+            match writes_before_failure.as_mut() {
+                Some(0) => {
+                    *writes_before_failure = None;
+                    Err(StoreError::Other("injected write failure".to_string()))
+                }
+                Some(left) => {
+                    *left -= 1;
+                    Ok(())
+                }
+                None => Ok(()),
+            }
+        }
+    }
+
+    #[async_trait]
+    impl ReadStore for FailingStore {
+        async fn get<T>(&self, lattice_id: &str, id: &str) -> Result<Option<T>, StoreError>
+        where
+            T: DeserializeOwned + StateKind,
+        {
+            Ok(self.inner.get(lattice_id, id).await.unwrap())
+        }
+
+        async fn list<T>(&self, lattice_id: &str) -> Result<HashMap<String, T>, StoreError>
+        where
+            T: DeserializeOwned + StateKind,
+        {
+            Ok(self.inner.list(lattice_id).await.unwrap())
+        }
+    }
+
+    #[async_trait]
+    impl Store for FailingStore {
+        async fn store_many<T, D>(&self, lattice_id: &str, data: D) -> Result<(), StoreError>
+        where
+            T: Serialize + DeserializeOwned + StateKind + Send + Sync + Clone,
+            D: IntoIterator<Item = (String, T)> + Send,
+        {
+            for (id, item) in data.into_iter().collect::<Vec<_>>() {
+                self.count_write()?;
+                self.inner.store(lattice_id, id, item).await.unwrap();
+            }
+            Ok(())
+        }
+
+        async fn delete_many<T, D, K>(&self, lattice_id: &str, data: D) -> Result<(), StoreError>
+        where
+            T: Serialize + DeserializeOwned + StateKind + Send + Sync,
+            D: IntoIterator<Item = K> + Send,
+            K: AsRef<str>,
+        {
+            self.inner
+                .delete_many::<T, _, _>(lattice_id, data)
+                .await
+                .unwrap();
+            Ok(())
+        }
+    }
+
+    fn host(id: &str, friendly_name: &str) -> (String, Host) {
+        (
+            id.to_string(),
+            Host {
+                id: id.to_string(),
+                friendly_name: friendly_name.to_string(),
+                ..Default::default()
+            },
+        )
+    }
+
+    #[tokio::test]
+    async fn failed_atomic_write_changes_nothing() {
+        let lattice_id = "atomic";
+        let store = FailingStore::default();
+        let before = HashMap::from([host("host1", "before"), host("host2", "before")]);
+        store.store_many(lattice_id, before.clone()).await.unwrap();
+
+        // Fail partway through a batch that updates both existing hosts and adds a new one
+        *store.writes_before_failure.lock().unwrap() = Some(2);
+        let batch = [
+            host("host1", "after"),
+            host("host3", "after"),
+            host("host2", "after"),
+        ];
+        store
+            .store_many_atomic(lattice_id, batch.clone())
+            .await
+            .expect_err("The injected failure should be returned");
+        let names = |hosts: HashMap<String, Host>| {
+            hosts
+                .into_iter()
+                .map(|(id, host)| (id, host.friendly_name))
+                .collect::<HashMap<_, _>>()
+        };
+        assert_eq!(
+            names(store.list::<Host>(lattice_id).await.unwrap()),
+            names(before),
+            "A failed atomic write should leave every item as it was"
+        );
+
+        store.store_many_atomic(lattice_id, batch).await.unwrap();
+        let after = store.list::<Host>(lattice_id).await.unwrap();
+        assert_eq!(after.len(), 3);
+        assert!(after.values().all(|host| host.friendly_name == "after"));
+    }
+}
@@ -12,59 +12,59 @@
 //! All data is currently stored in a single encoded map per type (host, component, provider), where
 //! the keys are the ID as given by [`StateId::id`]. Once again, we reserve the right to change this
 //! structure in the future
+//!
+//! With the `kv_compression` feature, maps larger than a configured size are compressed with zstd
+//! and prefixed with a header byte so they can be told apart from plain JSON when read back. Plain
+//! values are always readable, so compression can be turned on for an existing bucket
+use std::borrow::Cow;
 use std::collections::HashMap;
-use std::io::Error as IoError;
 use std::time::Duration;
 
-use async_nats::{
-    jetstream::kv::{Operation, Store as KvStore},
-    Error as NatsError,
+use async_nats::jetstream::kv::{
+    EntryError, EntryErrorKind, Operation, Store as KvStore, UpdateError, UpdateErrorKind,
 };
 use async_trait::async_trait;
 use futures::Future;
 use serde::{de::DeserializeOwned, Serialize};
-use tracing::{debug, error, field::Empty, instrument, trace};
+use tracing::{debug, field::Empty, instrument, trace};
 use tracing_futures::Instrument;
 
-use super::{ReadStore, StateKind, Store};
-
-/// Errors that can be encountered by NATS KV Store implemenation
-#[derive(Debug, thiserror::Error)]
-pub enum NatsStoreError {
-    /// An I/O error occured
-    #[error("I/O Error: {0}")]
-    Io(#[from] IoError),
-
-    /// An error occured when performing a NATS operation
-    #[error("NATS error: {0:?}")]
-    Nats(#[from] NatsError),
+use super::{CasError, ReadStore, StateKind, Store, StoreError};
 
-    /// Errors that result from serializing or deserializing the data from the store
-    #[error("Error when encoding or decoding data to store: {0}")]
-    SerDe(#[from] serde_json::Error),
-
-    /// A catch all error for uenxpected non-fatal errors
-    #[error("{0}")]
-    Other(String),
-}
+/// The byte every compressed value starts with. Plain values are JSON objects, which always start
+/// with `{`
+const COMPRESSED_HEADER: u8 = 0x01;
 
 /// A [`Store`] implementation backed by NATS KV.
 #[derive(Debug, Clone)]
 pub struct NatsKvStore {
     store: KvStore,
+    compression_threshold: Option<usize>,
 }
 
 impl NatsKvStore {
     /// Returns a new [`Store`] implementation backed by the given KV NATS bucket
     pub fn new(store: KvStore) -> NatsKvStore {
-        NatsKvStore { store }
+        NatsKvStore {
+            store,
+            compression_threshold: None,
+        }
+    }
+
+    /// Compresses values larger than the given number of bytes before writing them. NATS KV limits
+    /// the size of each value, so this keeps lattices with many hosts or long histories from
+    /// failing to store. Values are read back the same whether or not they were compressed
+    #[cfg(feature = "kv_compression")]
+    pub fn with_compression(mut self, threshold: usize) -> NatsKvStore {
+        self.compression_threshold = Some(threshold);
+        self
     }
 
     /// Returns the map of the data with the revision of the current data
     async fn internal_list<T>(
         &self,
         lattice_id: &str,
-    ) -> Result<(HashMap<String, T>, u64), NatsStoreError>
+    ) -> Result<(HashMap<String, T>, u64), StoreError>
     where
         T: DeserializeOwned + StateKind,
     {
@@ -74,9 +74,9 @@ impl NatsKvStore {
         match self.store.entry(key).await {
             Ok(Some(entry)) if !matches!(entry.operation, Operation::Delete | Operation::Purge) => {
                 trace!(len = %entry.value.len(), "Fetched bytes from store...deserializing");
-                serde_json::from_slice::<'_, HashMap<String, T>>(&entry.value)
+                serde_json::from_slice::<'_, HashMap<String, T>>(&decode(&entry.value)?)
                     .map(|d| (d, entry.revision))
-                    .map_err(NatsStoreError::from)
+                    .map_err(StoreError::from)
             }
             // If it was a delete entry, we still need to return the revision
             Ok(Some(entry)) => {
@@ -88,7 +88,34 @@ impl NatsKvStore {
                 debug!("No data found for key, returning empty");
                 Ok((HashMap::with_capacity(0), 0))
             }
-            Err(e) => Err(NatsStoreError::Nats(e.into())),
+            Err(e) => Err(entry_error(e)),
+        }
+    }
+
+    /// Writes all of the data for a kind, as long as the key it is stored in is still at the given
+    /// revision. Returns the new revision
+    async fn update_at_revision<T>(
+        &self,
+        lattice_id: &str,
+        data: &HashMap<String, T>,
+        revision: u64,
+    ) -> Result<u64, CasError<StoreError>>
+    where
+        T: Serialize + StateKind,
+    {
+        let key = generate_key::<T>(lattice_id);
+        let updated_data =
+            serde_json::to_vec(data).map_err(|e| CasError::Store(StoreError::Serialization(e)))?;
+        let updated_data =
+            encode(updated_data, self.compression_threshold).map_err(CasError::Store)?;
+        match self.store.update(&key, updated_data.into(), revision).await {
+            Ok(revision) => Ok(revision),
+            // Someone else wrote to the key between our read and the update
+            Err(e) if e.to_string().contains("wrong last sequence") => {
+                debug!(%key, %lattice_id, "Got wrong last sequence when trying to update state");
+                Err(CasError::Conflict { expected: revision })
+            }
+            Err(e) => Err(CasError::Store(update_error(e))),
         }
     }
 
@@ -102,11 +129,11 @@ impl NatsKvStore {
         key: &str,
         timeout: Duration,
         updater: F,
-    ) -> Result<(), NatsStoreError>
+    ) -> Result<(), StoreError>
     where
         T: Serialize + DeserializeOwned + StateKind + Send,
         F: Fn(HashMap<String, T>) -> Fut,
-        Fut: Future<Output = Result<Vec<u8>, NatsStoreError>>,
+        Fut: Future<Output = Result<Vec<u8>, StoreError>>,
     {
         let res = tokio::time::timeout(timeout, async {
             loop {
@@ -123,6 +150,7 @@ impl NatsKvStore {
                 if updated_data.is_empty() {
                     return Ok(())
                 }
+                let updated_data = encode(updated_data, self.compression_threshold)?;
                 match self.store.update(key, updated_data.into(), revision).await {
                     Ok(_) => return Ok(()),
                     Err(e) => {
@@ -130,21 +158,21 @@ impl NatsKvStore {
                             debug!(%key, %lattice_id, "Got wrong last sequence when trying to update state. Retrying update operation");
                             continue;
                         }
-                        return Err(NatsStoreError::Nats(e.into()));
+                        return Err(update_error(e));
                     }
                     // TODO(#316): Uncomment this code once we can update to the latest
                     // async-nats, which actually allows us to access the inner source of the error
                     // Err(e) => {
                     //     let source = match e.source() {
                     //         Some(s) => s,
-                    //         None => return Err(NatsStoreError::Nats(e.into())),
+                    //         None => return Err(update_error(e)),
                     //     };
                     //     match source.downcast_ref::<PublishError>() {
                     //         Some(e) if matches!(e.kind(), PublishErrorKind::WrongLastSequence) => {
                     //             debug!(%key, %lattice_id, "Got wrong last sequence when trying to update state. Retrying update operation");
                     //             continue;
                     //         },
-                    //         _ => return Err(NatsStoreError::Nats(e.into())),
+                    //         _ => return Err(update_error(e)),
                     //     }
                     // }
                 }
@@ -152,9 +180,7 @@ impl NatsKvStore {
         })
         .await;
         match res {
-            Err(_e) => Err(NatsStoreError::Other(
-                "Timed out while retrying updates to key".to_string(),
-            )),
+            Err(e) => Err(StoreError::Connection(Box::new(e))),
             Ok(res2) => res2,
         }
     }
@@ -168,14 +194,12 @@ impl NatsKvStore {
 // to something like bincode or cbor and focus on things like avoiding allocations
 #[async_trait]
 impl ReadStore for NatsKvStore {
-    type Error = NatsStoreError;
-
     /// Get the state for the specified kind with the given ID.
     ///
     /// The ID can vary depending on the type, but should be the unique ID for the object (e.g. a
     /// host key)
     #[instrument(level = "debug", skip(self))]
-    async fn get<T>(&self, lattice_id: &str, id: &str) -> Result<Option<T>, Self::Error>
+    async fn get<T>(&self, lattice_id: &str, id: &str) -> Result<Option<T>, StoreError>
     where
         T: DeserializeOwned + StateKind,
     {
@@ -189,7 +213,7 @@ impl ReadStore for NatsKvStore {
     ///
     /// The map key is the value as given by [`StateId::id`]
     #[instrument(level = "debug", skip(self), fields(key = Empty))]
-    async fn list<T>(&self, lattice_id: &str) -> Result<HashMap<String, T>, Self::Error>
+    async fn list<T>(&self, lattice_id: &str) -> Result<HashMap<String, T>, StoreError>
     where
         T: DeserializeOwned + StateKind,
     {
@@ -201,6 +225,28 @@ impl ReadStore for NatsKvStore {
             .await
             .map(|(data, _)| data)
     }
+
+    /// Get the state for the specified kind with each of the given IDs.
+    ///
+    /// All data for a kind is stored under a single key, so this only fetches from the store once
+    /// no matter how many IDs are given
+    #[instrument(level = "debug", skip(self, ids))]
+    async fn get_many<T, K>(
+        &self,
+        lattice_id: &str,
+        ids: K,
+    ) -> Result<HashMap<String, T>, StoreError>
+    where
+        T: DeserializeOwned + StateKind + Send,
+        K: IntoIterator<Item = String> + Send,
+        K::IntoIter: Send,
+    {
+        let mut all_data = self.list::<T>(lattice_id).await?;
+        Ok(ids
+            .into_iter()
+            .filter_map(|id| all_data.remove_entry(&id))
+            .collect())
+    }
 }
 
 #[async_trait]
@@ -218,7 +264,7 @@ impl Store for NatsKvStore {
     /// and, lastly, [`Send`] is needed because this is an async function and the data needs to be
     /// sendable between threads
     #[instrument(level = "debug", skip(self, data), fields(key = Empty))]
-    async fn store_many<T, D>(&self, lattice_id: &str, data: D) -> Result<(), Self::Error>
+    async fn store_many<T, D>(&self, lattice_id: &str, data: D) -> Result<(), StoreError>
     where
         T: Serialize + DeserializeOwned + StateKind + Send + Sync + Clone,
         D: IntoIterator<Item = (String, T)> + Send,
@@ -241,7 +287,7 @@ impl Store for NatsKvStore {
                             trace!("Inserted new entry");
                         };
                     }
-                    serde_json::to_vec(&current_data).map_err(NatsStoreError::SerDe)
+                    serde_json::to_vec(&current_data).map_err(StoreError::Serialization)
                 }
                 .await
             },
@@ -250,8 +296,19 @@ impl Store for NatsKvStore {
         .await
     }
 
+    /// All of the items of a kind are kept in a single key, so [`NatsKvStore::store_many`] already
+    /// writes the whole batch with one revision checked update that either applies fully or not
+    /// at all. This skips the rollback done by the default implementation
+    async fn store_many_atomic<T, D>(&self, lattice_id: &str, data: D) -> Result<(), StoreError>
+    where
+        T: Serialize + DeserializeOwned + StateKind + Send + Sync + Clone,
+        D: IntoIterator<Item = (String, T)> + Send,
+    {
+        self.store_many(lattice_id, data).await
+    }
+
     #[instrument(level = "debug", skip(self, data), fields(key = Empty))]
-    async fn delete_many<T, D, K>(&self, lattice_id: &str, data: D) -> Result<(), Self::Error>
+    async fn delete_many<T, D, K>(&self, lattice_id: &str, data: D) -> Result<(), StoreError>
     where
         T: Serialize + DeserializeOwned + StateKind + Send + Sync,
         D: IntoIterator<Item = K> + Send,
@@ -283,7 +340,7 @@ impl Store for NatsKvStore {
                         return Ok(Vec::with_capacity(0));
                     }
 
-                    serde_json::to_vec(&current_data).map_err(NatsStoreError::SerDe)
+                    serde_json::to_vec(&current_data).map_err(StoreError::Serialization)
                 }
                 .await
             },
@@ -291,8 +348,189 @@ impl Store for NatsKvStore {
         .in_current_span()
         .await
     }
+
+    /// Get the state for the specified kind with the given ID, along with the revision of the key
+    /// it is stored in.
+    ///
+    /// All data for a kind is stored under a single key, so the revision changes whenever any item
+    /// of this kind in the lattice does, not just the one that was fetched
+    #[instrument(level = "debug", skip(self), fields(key = Empty))]
+    async fn get_with_revision<T>(
+        &self,
+        lattice_id: &str,
+        id: &str,
+    ) -> Result<(Option<T>, u64), StoreError>
+    where
+        T: DeserializeOwned + StateKind + Send,
+    {
+        let (mut all_data, revision) = self
+            .internal_list::<T>(lattice_id)
+            .in_current_span()
+            .await?;
+        Ok((all_data.remove(id), revision))
+    }
+
+    /// Store a piece of state using the revision of the key it is stored in.
+    ///
+    /// Because all data for a kind is stored under a single key, this conflicts if any item of this
+    /// kind in the lattice was written since the given revision, even if it wasn't the one being
+    /// stored. Callers should read the state again and retry when that happens
+    #[instrument(level = "debug", skip(self, data), fields(key = Empty))]
+    async fn store_cas<T>(
+        &self,
+        lattice_id: &str,
+        id: String,
+        data: T,
+        expected_revision: u64,
+    ) -> Result<u64, CasError<StoreError>>
+    where
+        T: Serialize + DeserializeOwned + StateKind + Send + Sync + Clone,
+    {
+        let (mut current_data, revision) = self
+            .internal_list::<T>(lattice_id)
+            .in_current_span()
+            .await
+            .map_err(CasError::Store)?;
+        check_revision(revision, expected_revision)?;
+        current_data.insert(id, data);
+        self.update_at_revision(lattice_id, &current_data, revision)
+            .await
+    }
+
+    /// Delete a piece of state using the revision of the key it is stored in.
+    ///
+    /// Like [`NatsKvStore::store_cas`], this conflicts if any item of this kind in the lattice was
+    /// written since the given revision
+    #[instrument(level = "debug", skip(self))]
+    async fn delete_cas<T>(
+        &self,
+        lattice_id: &str,
+        id: &str,
+        expected_revision: u64,
+    ) -> Result<u64, CasError<StoreError>>
+    where
+        T: Serialize + DeserializeOwned + StateKind + Send + Sync,
+    {
+        let (mut current_data, revision) = self
+            .internal_list::<T>(lattice_id)
+            .in_current_span()
+            .await
+            .map_err(CasError::Store)?;
+        check_revision(revision, expected_revision)?;
+        if current_data.remove(id).is_none() {
+            trace!(%id, "ID doesn't exist in store, ignoring");
+            return Ok(revision);
+        }
+        self.update_at_revision(lattice_id, &current_data, revision)
+            .await
+    }
+}
+
+/// Returns a conflict if the current revision isn't the one the caller read the data at
+fn check_revision(revision: u64, expected_revision: u64) -> Result<(), CasError<StoreError>> {
+    if revision == expected_revision {
+        return Ok(());
+    }
+    debug!(
+        revision,
+        expected_revision, "State has changed since it was read"
+    );
+    Err(CasError::Conflict {
+        expected: expected_revision,
+    })
+}
+
+/// Maps an error from fetching a key into a [`StoreError`]. Anything other than an invalid key means
+/// the request to NATS failed
+fn entry_error(e: EntryError) -> StoreError {
+    match e.kind() {
+        EntryErrorKind::InvalidKey => StoreError::Other(e.to_string()),
+        EntryErrorKind::TimedOut | EntryErrorKind::Other => StoreError::Connection(Box::new(e)),
+    }
+}
+
+/// Maps an error from updating a key into a [`StoreError`]
+fn update_error(e: UpdateError) -> StoreError {
+    match e.kind() {
+        UpdateErrorKind::InvalidKey | UpdateErrorKind::WrongLastRevision => {
+            StoreError::Other(e.to_string())
+        }
+        UpdateErrorKind::TimedOut | UpdateErrorKind::Other => StoreError::Connection(Box::new(e)),
+    }
 }
 
 fn generate_key<T: StateKind>(lattice_id: &str) -> String {
     format!("{}_{lattice_id}", T::KIND)
 }
+
+/// Compresses the given serialized data if it is larger than the compression threshold
+fn encode(data: Vec<u8>, compression_threshold: Option<usize>) -> Result<Vec<u8>, StoreError> {
+    match compression_threshold {
+        #[cfg(feature = "kv_compression")]
+        Some(threshold) if data.len() > threshold => {
+            let mut encoded = vec![COMPRESSED_HEADER];
+            zstd::stream::copy_encode(data.as_slice(), &mut encoded, 0)
+                .map_err(|e| StoreError::Other(format!("Unable to compress data: {e}")))?;
+            trace!(
+                len = data.len(),
+                compressed_len = encoded.len(),
+                "Compressed data"
+            );
+            Ok(encoded)
+        }
+        _ => Ok(data),
+    }
+}
+
+/// Returns the serialized data in a value from the store, decompressing it if needed
+fn decode(value: &[u8]) -> Result<Cow<'_, [u8]>, StoreError> {
+    match value.split_first() {
+        #[cfg(feature = "kv_compression")]
+        Some((&COMPRESSED_HEADER, compressed)) => zstd::stream::decode_all(compressed)
+            .map(Cow::Owned)
+            .map_err(|e| StoreError::Other(format!("Unable to decompress data: {e}"))),
+        #[cfg(not(feature = "kv_compression"))]
+        Some((&COMPRESSED_HEADER, _)) => Err(StoreError::Other(
+            "Data is compressed, but wadm was built without the kv_compression feature".to_string(),
+        )),
+        _ => Ok(Cow::Borrowed(value)),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn values_round_trip_with_and_without_compression() {
+        let data =
+            serde_json::to_vec(&HashMap::from([("host".to_string(), "a".repeat(1024))])).unwrap();
+
+        let plain = encode(data.clone(), None).unwrap();
+        assert_eq!(plain, data, "Data shouldn't change without compression");
+        assert_eq!(
+            decode(&plain).unwrap(),
+            data.as_slice(),
+            "Plain data should be read as is"
+        );
+
+        #[cfg(feature = "kv_compression")]
+        {
+            assert_eq!(
+                encode(data.clone(), Some(data.len())).unwrap(),
+                data,
+                "Data at the threshold shouldn't be compressed"
+            );
+            let compressed = encode(data.clone(), Some(100)).unwrap();
+            assert_eq!(compressed[0], COMPRESSED_HEADER);
+            assert!(compressed.len() < data.len());
+            assert_eq!(decode(&compressed).unwrap(), data.as_slice());
+        }
+
+        #[cfg(not(feature = "kv_compression"))]
+        assert!(
+            decode(&[COMPRESSED_HEADER, 0]).is_err(),
+            "Compressed data can't be read without the kv_compression feature"
+        );
+    }
+}
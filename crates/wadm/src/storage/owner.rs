@@ -0,0 +1,138 @@
+//! Contains helpers for finding which model manages something running in a lattice
+
+use std::collections::BTreeSet;
+
+use crate::APP_SPEC_ANNOTATION;
+
+use super::{Component, Host, ReadStore, StoreError};
+
+/// Returns the name of the model that manages the component or provider with the given ID, found
+/// from the [`APP_SPEC_ANNOTATION`] wadm puts on everything it starts. If a host ID is given, only
+/// instances running on that host are considered.
+///
+/// Returns `None` if nothing with that ID is running or if it isn't managed by wadm. If more than
+/// one model runs instances with the same ID, the name that sorts first is returned
+pub async fn find_owning_model<S>(
+    store: &S,
+    lattice_id: &str,
+    id: &str,
+    host_id: Option<&str>,
+) -> Result<Option<String>, StoreError>
+where
+    S: ReadStore + Send + Sync,
+{
+    let on_host = |host: &str| host_id.is_none_or(|wanted| wanted == host);
+    let mut owners = BTreeSet::new();
+
+    if let Some(component) = store.get::<Component>(lattice_id, id).await? {
+        owners.extend(
+            component
+                .instances
+                .iter()
+                .filter(|(host, _)| on_host(host))
+                .flat_map(|(_, infos)| infos)
+                .filter_map(|info| info.annotations.get(APP_SPEC_ANNOTATION).cloned()),
+        );
+    }
+
+    // Provider annotations are only tracked on the hosts they are running on
+    let hosts = store.list::<Host>(lattice_id).await?;
+    owners.extend(
+        hosts
+            .iter()
+            .filter(|(host, _)| on_host(host))
+            .flat_map(|(_, host)| host.providers.iter())
+            .filter(|provider| provider.provider_id == id)
+            .filter_map(|provider| provider.annotations.get(APP_SPEC_ANNOTATION).cloned()),
+    );
+
+    Ok(owners.pop_first())
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::{BTreeMap, HashMap, HashSet};
+
+    use chrono::Utc;
+
+    use super::*;
+    use crate::{
+        events::ProviderInfo,
+        storage::{Store, WadmComponentInfo},
+        test_util::TestStore,
+        workers::insert_managed_annotations,
+    };
+
+    #[tokio::test]
+    async fn finds_model_owning_an_instance() {
+        let lattice_id = "find_owner";
+        let store = TestStore::default();
+        let mut managed = BTreeMap::new();
+        insert_managed_annotations(&mut managed, "owner");
+        let mut other = BTreeMap::new();
+        insert_managed_annotations(&mut other, "other");
+
+        store
+            .store(
+                lattice_id,
+                "echo".to_string(),
+                Component {
+                    id: "echo".to_string(),
+                    instances: HashMap::from_iter([
+                        (
+                            "host1".to_string(),
+                            HashSet::from_iter([WadmComponentInfo {
+                                annotations: managed,
+                                count: 1,
+                            }]),
+                        ),
+                        (
+                            "host2".to_string(),
+                            HashSet::from_iter([WadmComponentInfo {
+                                annotations: BTreeMap::new(),
+                                count: 1,
+                            }]),
+                        ),
+                    ]),
+                    ..Default::default()
+                },
+            )
+            .await
+            .unwrap();
+        store
+            .store(
+                lattice_id,
+                "host1".to_string(),
+                Host {
+                    id: "host1".to_string(),
+                    providers: HashSet::from_iter([ProviderInfo {
+                        provider_id: "httpserver".to_string(),
+                        provider_ref: "httpserver:0.1.0".to_string(),
+                        annotations: other,
+                    }]),
+                    last_seen: Utc::now(),
+                    ..Default::default()
+                },
+            )
+            .await
+            .unwrap();
+
+        let owner = |id, host_id| find_owning_model(&store, lattice_id, id, host_id);
+        assert_eq!(owner("echo", None).await.unwrap().as_deref(), Some("owner"));
+        assert_eq!(
+            owner("echo", Some("host1")).await.unwrap().as_deref(),
+            Some("owner")
+        );
+        assert_eq!(
+            owner("echo", Some("host2")).await.unwrap(),
+            None,
+            "Unmanaged instances should not have an owner"
+        );
+        assert_eq!(
+            owner("httpserver", None).await.unwrap().as_deref(),
+            Some("other")
+        );
+        assert_eq!(owner("httpserver", Some("host2")).await.unwrap(), None);
+        assert_eq!(owner("missing", None).await.unwrap(), None);
+    }
+}
@@ -0,0 +1,344 @@
+//! Contains a store wrapper that falls back to the control interface while state is still being
+//! rebuilt
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+use async_trait::async_trait;
+use serde::{de::DeserializeOwned, Serialize};
+use tracing::{debug, warn};
+use wasmcloud_control_interface::HostInventory;
+
+use crate::{
+    events::HostHeartbeat,
+    workers::{Claims, ClaimsSource, InventorySource},
+};
+
+use super::{
+    Component, Host, Provider, ProviderStatus, ReadStore, StateKind, StoreError, WadmComponentInfo,
+};
+
+/// A [`ReadStore`] that answers reads from the control interface while the underlying store is
+/// still cold. On a fresh start, scalers can reconcile before any state has been rebuilt and would
+/// otherwise see an empty lattice. Until the store has any hosts for the lattice, a missing entry
+/// (or an empty list) of hosts, components or providers is filled in from the inventory and claims
+/// of every host in the lattice. Once the store has a host, all reads go straight to it.
+///
+/// Fetched state is never written back, so the event worker stays the only writer. Every fallback
+/// queries all hosts, so this should only be used for the lattice the given source talks to
+pub struct ReadThroughStore<S, L> {
+    store: S,
+    source: L,
+    lattice_id: String,
+    warm: AtomicBool,
+}
+
+impl<S, L> ReadThroughStore<S, L>
+where
+    S: ReadStore + Send + Sync,
+    L: InventorySource + ClaimsSource + Send + Sync,
+{
+    /// Returns a new store that reads from the given store, falling back to the given source for
+    /// the given lattice until the store has been populated
+    pub fn new(store: S, source: L, lattice_id: &str) -> ReadThroughStore<S, L> {
+        ReadThroughStore {
+            store,
+            source,
+            lattice_id: lattice_id.to_owned(),
+            warm: AtomicBool::new(false),
+        }
+    }
+
+    /// Returns whether reads for the given lattice and kind of state should fall back to the
+    /// lattice. Once the store has any hosts, it is considered warm and never checked again
+    async fn should_fall_back(&self, lattice_id: &str, kind: &str) -> Result<bool, StoreError> {
+        if lattice_id != self.lattice_id
+            || ![Host::KIND, Component::KIND, Provider::KIND].contains(&kind)
+            || self.warm.load(Ordering::Relaxed)
+        {
+            return Ok(false);
+        }
+        let hosts = self.store.list::<Host>(lattice_id).await?;
+        if hosts.is_empty() {
+            return Ok(true);
+        }
+        debug!(%lattice_id, "Store has been populated, no longer reading through to the lattice");
+        self.warm.store(true, Ordering::Relaxed);
+        Ok(false)
+    }
+
+    /// Fetches all state of the given kind from the lattice. Failing to reach the lattice is
+    /// treated like an empty lattice since the store didn't have anything either
+    async fn fetch<T>(&self) -> Result<HashMap<String, T>, StoreError>
+    where
+        T: DeserializeOwned + StateKind,
+    {
+        let inventories = match self.inventories().await {
+            Ok(inventories) => inventories,
+            Err(e) => {
+                warn!(error = %e, "Unable to fetch state from the lattice");
+                return Ok(HashMap::new());
+            }
+        };
+        match T::KIND {
+            Host::KIND => Ok(convert(hosts_from_inventory(&inventories))?),
+            Component::KIND => {
+                let claims = self.claims().await;
+                Ok(convert(components_from_inventory(&inventories, &claims))?)
+            }
+            Provider::KIND => {
+                let claims = self.claims().await;
+                Ok(convert(providers_from_inventory(&inventories, &claims))?)
+            }
+            _ => Ok(HashMap::new()),
+        }
+    }
+
+    async fn claims(&self) -> HashMap<String, Claims> {
+        self.source.get_claims().await.unwrap_or_else(|e| {
+            warn!(error = %e, "Unable to fetch claims from the lattice");
+            HashMap::new()
+        })
+    }
+
+    async fn inventories(&self) -> anyhow::Result<Vec<HostInventory>> {
+        let host_ids = self.source.get_host_ids().await?;
+        futures::future::try_join_all(host_ids.iter().map(|id| self.source.get_inventory(id))).await
+    }
+}
+
+/// Converts state built from the lattice into the requested type. The caller has already checked
+/// that the kinds match, so this only goes through serde to satisfy the type system
+fn convert<T, U>(state: HashMap<String, U>) -> Result<HashMap<String, T>, serde_json::Error>
+where
+    T: DeserializeOwned,
+    U: Serialize,
+{
+    serde_json::from_value(serde_json::to_value(state)?)
+}
+
+fn hosts_from_inventory(inventories: &[HostInventory]) -> HashMap<String, Host> {
+    inventories
+        .iter()
+        .map(|inventory| {
+            (
+                inventory.host_id().to_owned(),
+                Host::from(&HostHeartbeat::from(inventory)),
+            )
+        })
+        .collect()
+}
+
+fn components_from_inventory(
+    inventories: &[HostInventory],
+    claims: &HashMap<String, Claims>,
+) -> HashMap<String, Component> {
+    let mut components: HashMap<String, Component> = HashMap::new();
+    for inventory in inventories {
+        for description in inventory.components() {
+            let claims = claims.get(description.id());
+            let component = components
+                .entry(description.id().to_owned())
+                .or_insert_with(|| Component {
+                    id: description.id().to_owned(),
+                    name: claims
+                        .map(|c| c.name.clone())
+                        .or_else(|| description.name().map(String::from))
+                        .unwrap_or_default(),
+                    issuer: claims.map(|c| c.issuer.clone()).unwrap_or_default(),
+                    reference: description.image_ref().to_owned(),
+                    capabilities: claims.map(|c| c.capabilities.clone()).unwrap_or_default(),
+                    ..Default::default()
+                });
+            component
+                .instances
+                .entry(inventory.host_id().to_owned())
+                .or_default()
+                .insert(WadmComponentInfo {
+                    annotations: description
+                        .annotations()
+                        .cloned()
+                        .map(BTreeMap::from_iter)
+                        .unwrap_or_default(),
+                    count: description.max_instances() as usize,
+                });
+            component.host_references.insert(
+                inventory.host_id().to_owned(),
+                description.image_ref().to_owned(),
+            );
+        }
+    }
+    components
+}
+
+fn providers_from_inventory(
+    inventories: &[HostInventory],
+    claims: &HashMap<String, Claims>,
+) -> HashMap<String, Provider> {
+    let mut providers: HashMap<String, Provider> = HashMap::new();
+    for inventory in inventories {
+        for description in inventory.providers() {
+            let claims = claims.get(description.id());
+            providers
+                .entry(description.id().to_owned())
+                .or_insert_with(|| Provider {
+                    id: description.id().to_owned(),
+                    name: description.name().map(String::from).unwrap_or_default(),
+                    reference: description
+                        .image_ref()
+                        .map(String::from)
+                        .unwrap_or_default(),
+                    capabilities: claims.map(|c| c.capabilities.clone()).unwrap_or_default(),
+                    ..Default::default()
+                })
+                .hosts
+                .insert(
+                    inventory.host_id().to_owned(),
+                    ProviderStatus::default().into(),
+                );
+        }
+    }
+    providers
+}
+
+#[async_trait]
+impl<S, L> ReadStore for ReadThroughStore<S, L>
+where
+    S: ReadStore + Send + Sync,
+    L: InventorySource + ClaimsSource + Send + Sync,
+{
+    async fn get<T>(&self, lattice_id: &str, id: &str) -> Result<Option<T>, StoreError>
+    where
+        T: DeserializeOwned + StateKind,
+    {
+        if let Some(found) = self.store.get(lattice_id, id).await? {
+            return Ok(Some(found));
+        }
+        if !self.should_fall_back(lattice_id, T::KIND).await? {
+            return Ok(None);
+        }
+        Ok(self.fetch::<T>().await?.remove(id))
+    }
+
+    async fn list<T>(&self, lattice_id: &str) -> Result<HashMap<String, T>, StoreError>
+    where
+        T: DeserializeOwned + StateKind,
+    {
+        // Scoped so the listed data isn't held across the awaits below
+        {
+            let all = self.store.list(lattice_id).await?;
+            if !all.is_empty() {
+                return Ok(all);
+            }
+        }
+        if !self.should_fall_back(lattice_id, T::KIND).await? {
+            return Ok(HashMap::new());
+        }
+        self.fetch().await
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+
+    use tokio::sync::RwLock;
+    use wasmcloud_control_interface::{ComponentDescription, ProviderDescription};
+
+    use super::*;
+    use crate::{
+        storage::Store,
+        test_util::{TestLatticeSource, TestStore},
+    };
+
+    #[tokio::test]
+    async fn reads_through_to_lattice_until_populated() {
+        let lattice_id = "read_through";
+        let host_id = "host1";
+        let inventory = HostInventory::builder()
+            .friendly_name("cold-start".into())
+            .components(vec![ComponentDescription::builder()
+                .id("echo".into())
+                .image_ref("echo:0.1.0".into())
+                .revision(0)
+                .max_instances(3)
+                .build()
+                .expect("failed to build description")])
+            .providers(vec![ProviderDescription::builder()
+                .id("httpserver")
+                .image_ref("httpserver:0.1.0")
+                .revision(0)
+                .build()
+                .expect("failed to build provider description")])
+            .host_id(host_id.into())
+            .version("1.0.0".into())
+            .uptime_human("60s".into())
+            .uptime_seconds(60)
+            .build()
+            .expect("failed to build host inventory");
+        let source = TestLatticeSource {
+            claims: HashMap::from([(
+                "echo".to_string(),
+                Claims {
+                    name: "Echo".to_string(),
+                    capabilities: Vec::new(),
+                    issuer: "issuer".to_string(),
+                },
+            )]),
+            inventory: Arc::new(RwLock::new(HashMap::from([(
+                host_id.to_string(),
+                inventory,
+            )]))),
+            ..Default::default()
+        };
+        let inner = Arc::new(TestStore::default());
+        let store = ReadThroughStore::new(inner.clone(), source, lattice_id);
+
+        let hosts = store.list::<Host>(lattice_id).await.unwrap();
+        let host = hosts
+            .get(host_id)
+            .expect("Host should be read from the lattice");
+        assert_eq!(host.friendly_name, "cold-start");
+        assert_eq!(host.components.get("echo"), Some(&3));
+
+        let component = store
+            .get::<Component>(lattice_id, "echo")
+            .await
+            .unwrap()
+            .expect("Component should be read from the lattice");
+        assert_eq!(component.count(), 3);
+        assert_eq!(component.name, "Echo", "Name should come from claims");
+        assert_eq!(component.issuer, "issuer");
+
+        let providers = store.list::<Provider>(lattice_id).await.unwrap();
+        let provider = providers
+            .get("httpserver")
+            .expect("Provider should be read from the lattice");
+        assert!(provider.hosts.contains_key(host_id));
+        assert!(
+            store
+                .get::<Component>("other", "echo")
+                .await
+                .unwrap()
+                .is_none(),
+            "Other lattices should not be read through"
+        );
+
+        // Once state has been rebuilt, the store is the only source of truth
+        inner
+            .store(lattice_id, host_id.to_string(), host.clone())
+            .await
+            .unwrap();
+        assert!(
+            store
+                .get::<Component>(lattice_id, "echo")
+                .await
+                .unwrap()
+                .is_none(),
+            "Populated store should not be read through"
+        );
+        assert!(store.list::<Provider>(lattice_id).await.unwrap().is_empty());
+    }
+}
@@ -1,109 +1,423 @@
 //! Contains helpers for reaping Hosts that haven't received a heartbeat within a configured amount
-//! of time and components and providers on hosts that no longer exist
+//! of time, components and providers on hosts that no longer exist, and providers that have
+//! stopped reporting health checks
 
-use std::collections::HashMap;
+use std::{
+    collections::{HashMap, HashSet},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, RwLock,
+    },
+};
 
 use chrono::{Duration, Utc};
-use tokio::{task::JoinHandle, time};
+use cloudevents::Event as CloudEvent;
+use tokio::{sync::watch, task::JoinHandle, time};
 use tracing::{debug, error, info, instrument, trace, warn};
 
-use super::{Component, Host, Provider, Store};
+use crate::{
+    events::{Event, HostReaped},
+    publisher::Publisher,
+    DEFAULT_WADM_EVENTS_TOPIC,
+};
+
+use super::{
+    update_cas, CasError, Component, Host, Provider, ProviderStatus, StateKind, Store, StoreError,
+    CAS_RETRIES,
+};
+
+/// A callback invoked with the lattice ID and host when a host first enters the reaper's warning
+/// state
+pub type HostWarningCallback = Arc<dyn Fn(&str, &Host) + Send + Sync>;
 
 /// A struct that can reap various pieces of data from the given store
-pub struct Reaper<S> {
+pub struct Reaper<S, P> {
     store: S,
-    interval: Duration,
-    handles: HashMap<String, JoinHandle<()>>,
+    publisher: P,
+    /// The check interval, shared with all of the spawned tasks so it can be changed while they run
+    interval: Arc<watch::Sender<Duration>>,
+    handles: HashMap<String, ReapTask>,
+    paused: Arc<PauseState>,
+    on_warning: Arc<RwLock<Option<HostWarningCallback>>>,
+}
+
+/// A spawned reap loop for a single lattice along with the means to stop it
+struct ReapTask {
+    handle: JoinHandle<()>,
+    stop: watch::Sender<bool>,
+}
+
+impl ReapTask {
+    /// Tells the reap loop to exit once any reap in progress has finished
+    fn signal_stop(&self) {
+        // An error means the loop has already exited
+        let _ = self.stop.send(true);
+    }
+
+    /// Tells the reap loop to exit and waits for it to do so
+    async fn stop(self) {
+        self.signal_stop();
+        if let Err(e) = self.handle.await {
+            warn!(error = %e, "Reaper task did not exit cleanly");
+        }
+    }
+}
+
+/// Tracks whether reaping is paused for all lattices or for specific lattices. This is shared
+/// between the [`Reaper`] and all of its spawned tasks
+#[derive(Debug, Default)]
+struct PauseState {
+    all: AtomicBool,
+    lattices: RwLock<HashSet<String>>,
+}
+
+impl PauseState {
+    fn is_paused(&self, lattice_id: &str) -> bool {
+        self.all.load(Ordering::Relaxed)
+            || self
+                .lattices
+                .read()
+                .map(|lattices| lattices.contains(lattice_id))
+                // If the lock is poisoned, default to not reaping to be safe
+                .unwrap_or(true)
+    }
 }
 
-impl<S: Store + Clone + Send + Sync + 'static> Reaper<S> {
+impl<S, P> Reaper<S, P>
+where
+    S: Store + Clone + Send + Sync + 'static,
+    P: Publisher + Clone + Send + Sync + 'static,
+{
     /// Creates a new reaper using the given store configured to check for reaping every
     /// `check_interval` for all passed lattice IDs. This reaper will immediately begin executing
-    /// spawned tasks. When the reaper is dropped, it will stop polling all tasks. This function
+    /// spawned tasks. When the reaper is dropped, all tasks exit once any reap in progress has
+    /// finished. This function
     /// will panic if you pass it a duration that is larger than the maximum value accepted by the
     /// `chrono` library. As this is a rare case, we don't actually return an error and panic
     /// instead
     ///
     /// The reaper will wait for 2 * `check_interval` before removing anything. For example, if
     /// `check_interval` is set to 30s, then after 30s, the item is considered to be in a "warning"
-    /// state. This isn't actually reflected in state right now, but it will be logged. When the
-    /// next tick fires (around 60s total), then the item will be removed from the store
+    /// state. Hosts in this state have [`Host::reaping_warning`] set in the store until they are
+    /// seen again. When the next tick fires (around 60s total), then the item will be removed from
+    /// the store and a [`HostReaped`] event is published with the given publisher
     pub fn new(
         store: S,
+        publisher: P,
         check_interval: std::time::Duration,
         lattices_to_observe: impl IntoIterator<Item = String>,
-    ) -> Reaper<S> {
-        let interval = Duration::from_std(check_interval)
-            .expect("The given duration is out of bounds for a max duration value");
+    ) -> Reaper<S, P> {
+        let interval = Arc::new(watch::Sender::new(
+            Duration::from_std(check_interval)
+                .expect("The given duration is out of bounds for a max duration value"),
+        ));
+        let cloned_interval = interval.clone();
         let cloned_store = store.clone();
+        let cloned_publisher = publisher.clone();
+        let paused = Arc::new(PauseState::default());
+        let cloned_paused = paused.clone();
+        let on_warning: Arc<RwLock<Option<HostWarningCallback>>> = Arc::default();
+        let cloned_on_warning = on_warning.clone();
         let handles = lattices_to_observe.into_iter().map(move |id| {
+            let (stop, stop_rx) = watch::channel(false);
             (
                 id.clone(),
-                tokio::spawn(
-                    Undertaker {
-                        store: cloned_store.clone(),
-                        lattice_id: id,
-                        interval,
-                    }
-                    .reap(),
-                ),
+                ReapTask {
+                    handle: tokio::spawn(
+                        Undertaker {
+                            store: cloned_store.clone(),
+                            publisher: cloned_publisher.clone(),
+                            lattice_id: id,
+                            interval: *cloned_interval.borrow(),
+                            interval_updates: cloned_interval.subscribe(),
+                            paused: cloned_paused.clone(),
+                            on_warning: cloned_on_warning.clone(),
+                            stop: stop_rx,
+                        }
+                        .reap(),
+                    ),
+                    stop,
+                },
             )
         });
         Reaper {
             store,
+            publisher,
             interval,
             handles: handles.collect(),
+            paused,
+            on_warning,
+        }
+    }
+
+    /// Sets a callback to invoke when a host first enters the warning state, one interval before
+    /// it is removed. This gives external systems a head start on failing over from a host that
+    /// is about to be reaped. It is called again if the host recovers and later goes missing again
+    pub fn with_host_warning_callback(
+        self,
+        callback: impl Fn(&str, &Host) + Send + Sync + 'static,
+    ) -> Reaper<S, P> {
+        if let Ok(mut on_warning) = self.on_warning.write() {
+            *on_warning = Some(Arc::new(callback));
         }
+        self
     }
 
     /// Adds a new lattice to be reaped
     pub fn observe(&mut self, lattice_id: &str) {
         // If the handle exists and is still running, just leave it
-        if let Some(handle) = self.handles.get(lattice_id) {
-            if !handle.is_finished() {
+        if let Some(task) = self.handles.get(lattice_id) {
+            if !task.handle.is_finished() {
                 return;
             }
         }
+        let (stop, stop_rx) = watch::channel(false);
         self.handles.insert(
             lattice_id.to_owned(),
-            tokio::spawn(
-                Undertaker {
-                    store: self.store.clone(),
-                    lattice_id: lattice_id.to_owned(),
-                    interval: self.interval,
-                }
-                .reap(),
-            ),
+            ReapTask {
+                handle: tokio::spawn(
+                    Undertaker {
+                        store: self.store.clone(),
+                        publisher: self.publisher.clone(),
+                        lattice_id: lattice_id.to_owned(),
+                        interval: *self.interval.borrow(),
+                        interval_updates: self.interval.subscribe(),
+                        paused: self.paused.clone(),
+                        on_warning: self.on_warning.clone(),
+                        stop: stop_rx,
+                    }
+                    .reap(),
+                ),
+                stop,
+            },
         );
     }
 
-    /// Stops observing the given lattice
+    /// Stops observing the given lattice. Any reap in progress is left to finish in the
+    /// background, use [`Reaper::stop`] to wait for it instead
     pub fn remove(&mut self, lattice_id: &str) {
-        if let Some(handle) = self.handles.remove(lattice_id) {
-            handle.abort();
+        if let Some(task) = self.handles.remove(lattice_id) {
+            task.signal_stop();
+        }
+    }
+
+    /// Stops observing the given lattice, waiting for any reap in progress to finish. Reaps are
+    /// never interrupted partway through writing to the store, so once this returns the store is
+    /// no longer being modified by the reaper for this lattice
+    pub async fn stop(&mut self, lattice_id: &str) {
+        if let Some(task) = self.handles.remove(lattice_id) {
+            task.stop().await;
+        }
+    }
+
+    /// Changes how often every lattice is checked for things to reap, including the lattices that
+    /// are already being reaped. The next check happens one new interval after the change. Like
+    /// [`Reaper::new`], this panics if the duration is too large for the `chrono` library
+    pub fn set_interval(&self, check_interval: std::time::Duration) {
+        let interval = Duration::from_std(check_interval)
+            .expect("The given duration is out of bounds for a max duration value");
+        self.interval.send_replace(interval);
+    }
+
+    /// Pauses reaping for all lattices. This is useful during maintenance windows where many hosts
+    /// may go offline at the same time and we don't want to wipe out their state. The reaper tasks
+    /// keep ticking while paused, but nothing is removed from the store until [`Reaper::resume`]
+    /// is called
+    pub fn pause(&self) {
+        self.paused.all.store(true, Ordering::Relaxed);
+    }
+
+    /// Resumes reaping for all lattices, including any lattices that were individually paused with
+    /// [`Reaper::pause_lattice`]
+    pub fn resume(&self) {
+        self.paused.all.store(false, Ordering::Relaxed);
+        if let Ok(mut lattices) = self.paused.lattices.write() {
+            lattices.clear();
+        }
+    }
+
+    /// Pauses reaping for only the given lattice
+    pub fn pause_lattice(&self, lattice_id: &str) {
+        if let Ok(mut lattices) = self.paused.lattices.write() {
+            lattices.insert(lattice_id.to_owned());
+        }
+    }
+
+    /// Resumes reaping for the given lattice. If all lattices are paused with [`Reaper::pause`],
+    /// this lattice will remain paused until [`Reaper::resume`] is called
+    pub fn resume_lattice(&self, lattice_id: &str) {
+        if let Ok(mut lattices) = self.paused.lattices.write() {
+            lattices.remove(lattice_id);
+        }
+    }
+
+    /// Pauses or resumes reaping for a lattice as requested by the operation of a `reaper` API
+    /// request (e.g. `wadm.api.{lattice_id}.reaper.pause`). All instances reap the lattices they
+    /// manage, so every instance handles these requests rather than just one. Returns false if the
+    /// operation isn't valid
+    pub fn handle_control(&self, lattice_id: &str, operation: &str) -> bool {
+        match operation {
+            "pause" => {
+                info!(%lattice_id, "Pausing reaping for lattice");
+                self.pause_lattice(lattice_id);
+            }
+            "resume" => {
+                info!(%lattice_id, "Resuming reaping for lattice");
+                self.resume_lattice(lattice_id);
+            }
+            _ => return false,
+        }
+        true
+    }
+
+    /// Returns whether reaping is currently paused for the given lattice
+    pub fn is_paused(&self, lattice_id: &str) -> bool {
+        self.paused.is_paused(lattice_id)
+    }
+
+    /// Runs [`compact`] for the given lattice using this reaper's store. Returns the number of
+    /// entries removed
+    pub async fn compact(&self, lattice_id: &str) -> Result<usize, StoreError> {
+        compact(&self.store, lattice_id).await
+    }
+}
+
+impl<S, P> Drop for Reaper<S, P> {
+    fn drop(&mut self) {
+        // Dropping can't wait on the tasks, but they will still finish any reap in progress
+        // before exiting
+        for task in self.handles.values() {
+            task.signal_stop();
+        }
+    }
+}
+
+/// Removes logically empty entries from the store for the given lattice, returning the number of
+/// entries removed. This means components without any running instances and providers that aren't
+/// running on any hosts. The reaper only removes entries when it observes that the hosts they were
+/// running on are gone, so this cleans up anything that slipped through (e.g. entries left over
+/// from older versions of wadm). It runs after every reap, but can also be run on its own
+///
+/// Each entry is checked again right before it is deleted, and the delete only goes through if
+/// nothing has written to the store since. An entry the event worker writes to in the meantime is
+/// left for the next compaction
+#[instrument(level = "debug", skip(store))]
+pub async fn compact<S: Store + Sync>(store: &S, lattice_id: &str) -> Result<usize, StoreError> {
+    let empty_components = store
+        .list::<Component>(lattice_id)
+        .await?
+        .into_iter()
+        .filter_map(|(id, component)| is_empty_component(&component).then_some(id))
+        .collect::<Vec<_>>();
+    let empty_providers = store
+        .list::<Provider>(lattice_id)
+        .await?
+        .into_iter()
+        .filter_map(|(id, provider)| provider.hosts.is_empty().then_some(id))
+        .collect::<Vec<_>>();
+
+    debug!(components = %empty_components.len(), providers = %empty_providers.len(), "Compacting empty entries");
+
+    let mut removed = 0;
+    for id in empty_components {
+        removed += usize::from(delete_if_empty(store, lattice_id, &id, is_empty_component).await?);
+    }
+    for id in empty_providers {
+        removed += usize::from(
+            delete_if_empty(store, lattice_id, &id, |provider: &Provider| {
+                provider.hosts.is_empty()
+            })
+            .await?,
+        );
+    }
+
+    Ok(removed)
+}
+
+fn is_empty_component(component: &Component) -> bool {
+    component
+        .instances
+        .values()
+        .flatten()
+        .all(|info| info.count == 0)
+}
+
+/// Deletes the given entry if it is still empty at the revision it is read at, returning whether
+/// it was deleted
+async fn delete_if_empty<S, T>(
+    store: &S,
+    lattice_id: &str,
+    id: &str,
+    is_empty: impl Fn(&T) -> bool,
+) -> Result<bool, StoreError>
+where
+    S: Store + Sync,
+    T: serde::Serialize + serde::de::DeserializeOwned + StateKind + Send + Sync,
+{
+    let (current, revision) = store.get_with_revision::<T>(lattice_id, id).await?;
+    if !current.as_ref().is_some_and(is_empty) {
+        return Ok(false);
+    }
+    match store.delete_cas::<T>(lattice_id, id, revision).await {
+        Ok(_) => Ok(true),
+        Err(CasError::Conflict { .. }) => {
+            trace!(%id, kind = T::KIND, "Entry changed while compacting, leaving it for the next compaction");
+            Ok(false)
         }
+        Err(CasError::Store(e)) => Err(e),
     }
 }
 
-struct Undertaker<S> {
+struct Undertaker<S, P> {
     store: S,
+    publisher: P,
     lattice_id: String,
     interval: Duration,
+    interval_updates: watch::Receiver<Duration>,
+    paused: Arc<PauseState>,
+    on_warning: Arc<RwLock<Option<HostWarningCallback>>>,
+    stop: watch::Receiver<bool>,
 }
 
-impl<S: Store + Clone + Send + Sync + 'static> Undertaker<S> {
+impl<S, P> Undertaker<S, P>
+where
+    S: Store + Clone + Send + Sync + 'static,
+    P: Publisher + Send + Sync,
+{
     #[instrument(level = "debug", skip(self), fields(lattice_id = %self.lattice_id, check_interval = %self.interval))]
-    async fn reap(self) {
+    async fn reap(mut self) {
         debug!("Starting reaper");
         // SAFETY: We created this Duration from a std Duration, so it should unwrap back just fine
         let mut ticker = time::interval(self.interval.to_std().unwrap());
         ticker.set_missed_tick_behavior(time::MissedTickBehavior::Delay);
 
         loop {
-            ticker.tick().await;
+            tokio::select! {
+                _ = ticker.tick() => {}
+                // Either a stop was requested or the reaper was dropped
+                _ = self.stop.changed() => break,
+                Ok(()) = self.interval_updates.changed() => {
+                    self.interval = *self.interval_updates.borrow_and_update();
+                    debug!(check_interval = %self.interval, "Reaper interval changed");
+                    // SAFETY: The reaper only sends durations created from a std Duration
+                    let period = self.interval.to_std().unwrap();
+                    ticker = time::interval_at(time::Instant::now() + period, period);
+                    ticker.set_missed_tick_behavior(time::MissedTickBehavior::Delay);
+                    continue;
+                }
+            }
+            if self.paused.is_paused(&self.lattice_id) {
+                info!("Reaper is paused, skipping reap tasks");
+                continue;
+            }
             trace!("Tick fired, running reap tasks");
             // We want to reap hosts first so that the state is up to date for reaping components and providers
             self.reap_hosts().await;
+            // Stopping here keeps the store consistent, since components and providers are only
+            // reaped based on hosts that are already gone
+            if self.stop_requested() {
+                break;
+            }
             // Now get the current list of hosts
             let hosts = match self.store.list::<Host>(&self.lattice_id).await {
                 Ok(n) => n,
@@ -115,8 +429,24 @@ impl<S: Store + Clone + Send + Sync + 'static> Undertaker<S> {
             // Reap components and providers
             self.reap_components(&hosts).await;
             self.reap_providers(&hosts).await;
+            // Anything the reaps above couldn't attribute to a missing host is cleaned up here
+            match compact(&self.store, &self.lattice_id).await {
+                Ok(removed) if removed > 0 => debug!(%removed, "Compacted empty entries"),
+                Ok(_) => (),
+                Err(e) => {
+                    warn!(error = %e, "Error when compacting empty entries. Will retry on next tick")
+                }
+            }
             trace!("Completed reap tasks");
         }
+        debug!("Stopping reaper");
+    }
+
+    /// Returns whether the reap loop has been asked to stop, either explicitly or by dropping the
+    /// reaper
+    fn stop_requested(&self) -> bool {
+        // Only a stop is ever sent, so any change means we should stop
+        self.stop.has_changed().unwrap_or(true)
     }
 
     #[instrument(level = "debug", skip(self), fields(lattice_id = %self.lattice_id))]
@@ -129,25 +459,117 @@ impl<S: Store + Clone + Send + Sync + 'static> Undertaker<S> {
             }
         };
 
-        let hosts_to_remove = hosts.into_iter().filter_map(|(id, host)| {
+        let mut hosts_to_remove = Vec::new();
+        let mut hosts_to_update = Vec::new();
+        for (id, host) in hosts {
             let elapsed = Utc::now() - host.last_seen;
             if elapsed > (self.interval * 2) {
                 info!(%id, friendly_name = %host.friendly_name, "Host has not been seen for 2 intervals. Will reap node");
-                Some(id)
-            } else if elapsed > self.interval {
+                hosts_to_remove.push(id);
+                continue;
+            }
+            let warning = elapsed > self.interval;
+            if warning {
                 info!(%id, friendly_name = %host.friendly_name, "Host has not been seen for 1 interval. Next check will reap node from store");
-                None
-            } else {
-                None
             }
-        });
+            if warning != host.reaping_warning {
+                hosts_to_update.push(id);
+            }
+        }
 
-        if let Err(e) = self
-            .store
-            .delete_many::<Host, _, _>(&self.lattice_id, hosts_to_remove)
-            .await
+        // A heartbeat could come in at any point, so the warning is worked out again from the
+        // current host and only written if nothing else wrote to the store in the meantime
+        let callback = self.on_warning.read().ok().and_then(|cb| cb.clone());
+        for id in hosts_to_update {
+            let updated = update_cas(&self.store, &self.lattice_id, &id, |host: Option<Host>| {
+                let mut host = host?;
+                let warning = Utc::now() - host.last_seen > self.interval;
+                (warning != host.reaping_warning).then(|| {
+                    host.reaping_warning = warning;
+                    host
+                })
+            })
+            .await;
+            match updated {
+                // Only hosts that just entered the warning state are stored with it set
+                Ok(Some(host)) if host.reaping_warning => {
+                    if let Some(callback) = &callback {
+                        callback(&self.lattice_id, &host);
+                    }
+                }
+                Ok(_) => (),
+                Err(e) => {
+                    warn!(error = %e, %id, "Error when storing host warning. Will retry on next tick")
+                }
+            }
+        }
+
+        for id in hosts_to_remove {
+            match self.reap_host(&id).await {
+                Ok(Some(host)) => self.publish_reaped(id, host).await,
+                Ok(None) => debug!(%id, "Host was seen again before it could be reaped"),
+                Err(e) => {
+                    error!(error = %e, %id, "Error when deleting host from store. Will retry on next tick")
+                }
+            }
+        }
+    }
+
+    /// Deletes the given host if it still hasn't been seen for 2 intervals, returning the host that
+    /// was deleted. The host is checked again right before it is deleted, and the delete only goes
+    /// through if nothing has written to the store since, so a heartbeat that arrives while the
+    /// host is being reaped always saves it
+    async fn reap_host(&self, id: &str) -> Result<Option<Host>, CasError<StoreError>> {
+        let mut attempt = 0;
+        loop {
+            let (host, revision) = self
+                .store
+                .get_with_revision::<Host>(&self.lattice_id, id)
+                .await
+                .map_err(CasError::Store)?;
+            let Some(host) = host.filter(|host| Utc::now() - host.last_seen > self.interval * 2)
+            else {
+                return Ok(None);
+            };
+            match self
+                .store
+                .delete_cas::<Host>(&self.lattice_id, id, revision)
+                .await
+            {
+                Ok(_) => return Ok(Some(host)),
+                Err(CasError::Conflict { .. }) if attempt < CAS_RETRIES => {
+                    trace!(%id, "Hosts changed while reaping, checking the host again");
+                    attempt += 1;
+                }
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    /// Publishes a [`HostReaped`] event for a removed host. This is best effort, so failures are
+    /// only logged and never stop the reaper
+    async fn publish_reaped(&self, host_id: String, host: Host) {
+        let trimmer: &[_] = &['.', '>', '*'];
+        let subject = format!(
+            "{}.{}.host_reaped",
+            DEFAULT_WADM_EVENTS_TOPIC.trim_matches(trimmer),
+            self.lattice_id
+        );
+        let event = Event::HostReaped(HostReaped {
+            components_removed: host.components.len(),
+            providers_removed: host.providers.len(),
+            host_id,
+            friendly_name: host.friendly_name,
+            labels: host.labels,
+        });
+        let published = match CloudEvent::try_from(event)
+            .and_then(|event| serde_json::to_vec(&event).map_err(anyhow::Error::from))
         {
-            error!(error = %e, "Error when deleting hosts from store. Will retry on next tick")
+            Ok(data) => self.publisher.publish(data, Some(&subject)).await,
+            Err(e) => Err(e),
+        };
+        if let Err(e) = published {
+            warn!(error = %e, %subject, "Unable to publish host reaped event");
         }
     }
 
@@ -219,8 +641,29 @@ impl<S: Store + Clone + Send + Sync + 'static> Undertaker<S> {
                 provider
                     .hosts
                     .retain(|host_id, _| hosts.contains_key(host_id));
-                // If we got rid of something, that means this needs to update
-                (current_num_hosts != provider.hosts.len()).then_some((id, provider))
+                let mut changed = current_num_hosts != provider.hosts.len();
+                // Providers on living hosts that haven't been health checked for 2 intervals are
+                // marked as stale first and then dropped if they still haven't been checked by the
+                // next tick
+                provider.hosts.retain(|host_id, host| {
+                    let Some(last_health_check) = host.last_health_check else {
+                        return true;
+                    };
+                    if Utc::now() - last_health_check <= self.interval * 2 {
+                        return true;
+                    }
+                    changed = true;
+                    if host.status == ProviderStatus::Stale {
+                        info!(provider_id = %id, %host_id, "Provider is still stale. Removing it from host");
+                        false
+                    } else {
+                        info!(provider_id = %id, %host_id, "Provider has not been health checked for 2 intervals. Marking as stale");
+                        host.status = ProviderStatus::Stale;
+                        true
+                    }
+                });
+                // If we changed something, that means this needs to update
+                changed.then_some((id, provider))
             })
             .partition(|(_, provider)| provider.hosts.is_empty());
 
@@ -254,8 +697,9 @@ mod test {
     };
 
     use crate::{
-        storage::{ProviderStatus, ReadStore, WadmComponentInfo},
-        test_util::TestStore,
+        events::HostHeartbeat,
+        storage::{ProviderHostStatus, ReadStore, StateKind, WadmComponentInfo},
+        test_util::{NoopPublisher, RecorderPublisher, TestStore},
     };
 
     #[tokio::test]
@@ -320,7 +764,7 @@ mod test {
                 "fakeprovider".to_string(),
                 Provider {
                     id: "fakeprovider".to_string(),
-                    hosts: HashMap::from([(host1_id.to_string(), ProviderStatus::Running)]),
+                    hosts: HashMap::from([(host1_id.to_string(), ProviderStatus::Running.into())]),
                     ..Default::default()
                 },
             )
@@ -360,11 +804,40 @@ mod test {
         let reap_interval = std::time::Duration::from_millis(500);
         // Interval + wiggle
         let wait = reap_interval + std::time::Duration::from_millis(100);
-        let _reaper = Reaper::new(store.clone(), reap_interval, [lattice_id.to_owned()]);
+        let publisher = RecorderPublisher::<CloudEvent> {
+            received: Arc::default(),
+        };
+        let _reaper = Reaper::new(
+            store.clone(),
+            publisher.clone(),
+            reap_interval,
+            [lattice_id.to_owned()],
+        );
 
         // Wait for first node to be reaped (two ticks)
         tokio::time::sleep(wait * 2).await;
 
+        let reaped = publisher
+            .received
+            .read()
+            .await
+            .iter()
+            .cloned()
+            .map(Event::try_from)
+            .collect::<Result<Vec<_>, _>>()
+            .expect("Should have published valid events");
+        assert_eq!(
+            reaped,
+            vec![Event::HostReaped(HostReaped {
+                host_id: host1_id.to_string(),
+                friendly_name: String::new(),
+                labels: HashMap::new(),
+                components_removed: 1,
+                providers_removed: 0,
+            })],
+            "A reaped event should be published for the removed host"
+        );
+
         // Now check that the providers, components, and hosts were reaped
         let hosts = store.list::<Host>(lattice_id).await.unwrap();
         assert_eq!(hosts.len(), 1, "Only one host should be left");
@@ -454,7 +927,12 @@ mod test {
         let reap_interval = std::time::Duration::from_millis(50);
         // Interval + wiggle
         let wait = std::time::Duration::from_millis(70);
-        let _reaper = Reaper::new(store.clone(), reap_interval, [lattice_id.to_owned()]);
+        let _reaper = Reaper::new(
+            store.clone(),
+            NoopPublisher,
+            reap_interval,
+            [lattice_id.to_owned()],
+        );
 
         // Wait for first tick
         tokio::time::sleep(wait).await;
@@ -479,4 +957,758 @@ mod test {
             "Only one instance should remain on host"
         );
     }
+
+    #[tokio::test]
+    async fn test_stale_provider() {
+        let store = Arc::new(TestStore::default());
+
+        let lattice_id = "reaper";
+        let provider_id = "testprovider";
+        let host1_id = "host1";
+        let host2_id = "host2";
+
+        // Prepopulate the store with a provider that hasn't been checked on host1 in a long time
+        // and was just checked on host2
+        store
+            .store(
+                lattice_id,
+                provider_id.to_string(),
+                Provider {
+                    id: provider_id.to_string(),
+                    hosts: HashMap::from([
+                        (
+                            host1_id.to_string(),
+                            ProviderHostStatus {
+                                status: ProviderStatus::Running,
+                                last_health_check: Some(Utc::now() - Duration::hours(1)),
+                                instances: 1,
+                            },
+                        ),
+                        (
+                            host2_id.to_string(),
+                            ProviderHostStatus {
+                                status: ProviderStatus::Running,
+                                last_health_check: Some(Utc::now() + Duration::milliseconds(600)),
+                                instances: 1,
+                            },
+                        ),
+                    ]),
+                    ..Default::default()
+                },
+            )
+            .await
+            .unwrap();
+
+        store
+            .store_many(
+                lattice_id,
+                [host1_id, host2_id].map(|id| {
+                    (
+                        id.to_string(),
+                        Host {
+                            providers: HashSet::default(),
+                            id: id.to_string(),
+                            last_seen: Utc::now() + Duration::milliseconds(600),
+                            ..Default::default()
+                        },
+                    )
+                }),
+            )
+            .await
+            .unwrap();
+
+        let reap_interval = std::time::Duration::from_millis(50);
+        // Interval + wiggle
+        let wait = std::time::Duration::from_millis(70);
+        let _reaper = Reaper::new(
+            store.clone(),
+            NoopPublisher,
+            reap_interval,
+            [lattice_id.to_owned()],
+        );
+
+        // The first tick marks the provider as stale and the second one removes it
+        tokio::time::sleep(wait).await;
+
+        let provider = store
+            .get::<Provider>(lattice_id, provider_id)
+            .await
+            .unwrap()
+            .expect("Provider should still exist");
+        assert!(
+            !provider.hosts.contains_key(host1_id),
+            "Stale provider should have been removed from the host"
+        );
+        assert_eq!(
+            provider.hosts.get(host2_id).map(|host| &host.status),
+            Some(&ProviderStatus::Running),
+            "Freshly checked provider should be left alone"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_reaping_warning() {
+        let store = Arc::new(TestStore::default());
+
+        let lattice_id = "reaper_warning";
+        let host_id = "host1";
+
+        store
+            .store(
+                lattice_id,
+                host_id.to_string(),
+                Host {
+                    id: host_id.to_string(),
+                    // Already missed one interval, but not two
+                    last_seen: Utc::now() - Duration::milliseconds(250),
+                    ..Default::default()
+                },
+            )
+            .await
+            .unwrap();
+
+        let reap_interval = std::time::Duration::from_millis(200);
+        let _reaper = Reaper::new(
+            store.clone(),
+            NoopPublisher,
+            reap_interval,
+            [lattice_id.to_owned()],
+        );
+
+        // The first tick fires immediately
+        tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+        let host = store
+            .get::<Host>(lattice_id, host_id)
+            .await
+            .unwrap()
+            .expect("Host should not be reaped yet");
+        assert!(
+            host.reaping_warning,
+            "Host should be marked as at risk of reaping"
+        );
+
+        // A heartbeat comes in before the next tick
+        store
+            .store(
+                lattice_id,
+                host_id.to_string(),
+                Host::from(&HostHeartbeat {
+                    components: vec![],
+                    providers: vec![],
+                    labels: HashMap::default(),
+                    issuer: String::new(),
+                    friendly_name: "host".to_string(),
+                    uptime_seconds: 60,
+                    uptime_human: "60s".to_string(),
+                    version: semver::Version::new(1, 0, 0),
+                    host_id: host_id.to_string(),
+                    utilization: None,
+                }),
+            )
+            .await
+            .unwrap();
+
+        // Wait past the next tick
+        tokio::time::sleep(reap_interval).await;
+        let host = store
+            .get::<Host>(lattice_id, host_id)
+            .await
+            .unwrap()
+            .expect("Host should not be reaped after a heartbeat");
+        assert!(
+            !host.reaping_warning,
+            "Warning should be cleared by the heartbeat"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_host_warning_callback() {
+        let store = Arc::new(TestStore::default());
+
+        let lattice_id = "reaper_warning_callback";
+        let host_id = "host1";
+
+        store
+            .store(
+                lattice_id,
+                host_id.to_string(),
+                Host {
+                    id: host_id.to_string(),
+                    last_seen: Utc::now() - Duration::milliseconds(250),
+                    ..Default::default()
+                },
+            )
+            .await
+            .unwrap();
+
+        let warned = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let cloned_warned = warned.clone();
+        let reap_interval = std::time::Duration::from_millis(200);
+        let _reaper = Reaper::new(
+            store.clone(),
+            NoopPublisher,
+            reap_interval,
+            [lattice_id.to_owned()],
+        )
+        .with_host_warning_callback(move |lattice_id, host| {
+            cloned_warned
+                .lock()
+                .unwrap()
+                .push((lattice_id.to_owned(), host.id.clone()));
+        });
+
+        // The first tick fires immediately
+        tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+        assert_eq!(
+            *warned.lock().unwrap(),
+            vec![(lattice_id.to_string(), host_id.to_string())],
+            "Callback should fire when the host enters the warning state"
+        );
+        assert!(
+            store
+                .get::<Host>(lattice_id, host_id)
+                .await
+                .unwrap()
+                .is_some(),
+            "Host should not be reaped before the callback fires"
+        );
+
+        // The next tick removes the host without firing the callback again
+        tokio::time::sleep(reap_interval).await;
+        assert!(
+            store
+                .get::<Host>(lattice_id, host_id)
+                .await
+                .unwrap()
+                .is_none(),
+            "Host should be reaped after the second interval"
+        );
+        assert_eq!(warned.lock().unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_paused_reaper() {
+        let store = Arc::new(TestStore::default());
+
+        let lattice_id = "reaper_paused";
+        let host_id = "host1";
+
+        store
+            .store(
+                lattice_id,
+                host_id.to_string(),
+                Host {
+                    id: host_id.to_string(),
+                    // This host is already well past the reap threshold
+                    last_seen: Utc::now() - Duration::seconds(60),
+                    ..Default::default()
+                },
+            )
+            .await
+            .unwrap();
+
+        let reap_interval = std::time::Duration::from_millis(50);
+        let reaper = Reaper::new(
+            store.clone(),
+            NoopPublisher,
+            reap_interval,
+            [lattice_id.to_owned()],
+        );
+        reaper.pause();
+        assert!(reaper.is_paused(lattice_id));
+
+        // Wait for multiple ticks
+        tokio::time::sleep(reap_interval * 4).await;
+        assert_eq!(
+            store.list::<Host>(lattice_id).await.unwrap().len(),
+            1,
+            "Host should not be reaped while the reaper is paused"
+        );
+
+        // Pausing a single lattice should also prevent reaping once the global pause is lifted
+        assert!(reaper.handle_control(lattice_id, "pause"));
+        assert!(reaper.handle_control("someotherlattice", "resume"));
+        assert!(!reaper.handle_control(lattice_id, "stop"));
+        reaper.paused.all.store(false, Ordering::Relaxed);
+        tokio::time::sleep(reap_interval * 4).await;
+        assert_eq!(
+            store.list::<Host>(lattice_id).await.unwrap().len(),
+            1,
+            "Host should not be reaped while the lattice is paused"
+        );
+
+        reaper.resume();
+        assert!(!reaper.is_paused(lattice_id));
+        tokio::time::sleep(reap_interval * 2).await;
+        assert!(
+            store.list::<Host>(lattice_id).await.unwrap().is_empty(),
+            "Host should be reaped once the reaper is resumed"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_stopped_reaper() {
+        let store = Arc::new(TestStore::default());
+
+        let lattice_id = "reaper_stopped";
+        let other_lattice_id = "reaper_dropped";
+        let stale_host = |id: &str| Host {
+            id: id.to_string(),
+            // This host is already well past the reap threshold
+            last_seen: Utc::now() - Duration::seconds(60),
+            ..Default::default()
+        };
+
+        let reap_interval = std::time::Duration::from_millis(50);
+        let mut reaper = Reaper::new(
+            store.clone(),
+            NoopPublisher,
+            reap_interval,
+            [lattice_id.to_owned(), other_lattice_id.to_owned()],
+        );
+        reaper.stop(lattice_id).await;
+
+        store
+            .store(lattice_id, "host1".to_string(), stale_host("host1"))
+            .await
+            .unwrap();
+        tokio::time::sleep(reap_interval * 4).await;
+        assert_eq!(
+            store.list::<Host>(lattice_id).await.unwrap().len(),
+            1,
+            "Host should not be reaped once the lattice is stopped"
+        );
+
+        drop(reaper);
+        // Give the task a chance to see the stop before storing anything
+        tokio::time::sleep(reap_interval).await;
+        store
+            .store(other_lattice_id, "host2".to_string(), stale_host("host2"))
+            .await
+            .unwrap();
+        tokio::time::sleep(reap_interval * 4).await;
+        assert_eq!(
+            store.list::<Host>(other_lattice_id).await.unwrap().len(),
+            1,
+            "Host should not be reaped once the reaper is dropped"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_reaper_interval_change() {
+        let store = Arc::new(TestStore::default());
+
+        let lattice_id = "reaper_interval_change";
+        let host_id = "host1";
+
+        store
+            .store(
+                lattice_id,
+                host_id.to_string(),
+                Host {
+                    id: host_id.to_string(),
+                    last_seen: Utc::now() - Duration::seconds(3),
+                    ..Default::default()
+                },
+            )
+            .await
+            .unwrap();
+
+        let reaper = Reaper::new(
+            store.clone(),
+            NoopPublisher,
+            std::time::Duration::from_secs(10),
+            [lattice_id.to_owned()],
+        );
+        // Let the first tick run, which shouldn't reap a host seen well within the interval
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+        assert_eq!(
+            store.list::<Host>(lattice_id).await.unwrap().len(),
+            1,
+            "Host should not be reaped with the original interval"
+        );
+
+        // With a shorter interval the host is now stale, and the next check is one interval away
+        // rather than waiting out the original one
+        reaper.set_interval(std::time::Duration::from_millis(500));
+        tokio::time::sleep(std::time::Duration::from_secs(1)).await;
+        assert!(
+            store.list::<Host>(lattice_id).await.unwrap().is_empty(),
+            "Host should be reaped after the interval is shortened"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_compaction() {
+        let store = Arc::new(TestStore::default());
+        let lattice_id = "compaction";
+
+        store
+            .store_many(
+                lattice_id,
+                [
+                    (
+                        "running".to_string(),
+                        Component {
+                            id: "running".to_string(),
+                            instances: HashMap::from([(
+                                "host1".to_string(),
+                                HashSet::from_iter([WadmComponentInfo {
+                                    annotations: BTreeMap::default(),
+                                    count: 1,
+                                }]),
+                            )]),
+                            ..Default::default()
+                        },
+                    ),
+                    (
+                        "noinstances".to_string(),
+                        Component {
+                            id: "noinstances".to_string(),
+                            ..Default::default()
+                        },
+                    ),
+                    (
+                        "emptyinstances".to_string(),
+                        Component {
+                            id: "emptyinstances".to_string(),
+                            instances: HashMap::from([("host1".to_string(), HashSet::new())]),
+                            ..Default::default()
+                        },
+                    ),
+                ],
+            )
+            .await
+            .unwrap();
+        store
+            .store_many(
+                lattice_id,
+                [
+                    (
+                        "runningprovider".to_string(),
+                        Provider {
+                            id: "runningprovider".to_string(),
+                            hosts: HashMap::from([(
+                                "host1".to_string(),
+                                ProviderStatus::Running.into(),
+                            )]),
+                            ..Default::default()
+                        },
+                    ),
+                    (
+                        "orphanedprovider".to_string(),
+                        Provider {
+                            id: "orphanedprovider".to_string(),
+                            ..Default::default()
+                        },
+                    ),
+                ],
+            )
+            .await
+            .unwrap();
+
+        let removed = compact(&store, lattice_id).await.unwrap();
+        assert_eq!(removed, 3, "Should have removed all empty entries");
+
+        let components = store.list::<Component>(lattice_id).await.unwrap();
+        assert_eq!(components.len(), 1, "Only one component should remain");
+        assert!(components.contains_key("running"));
+        let providers = store.list::<Provider>(lattice_id).await.unwrap();
+        assert_eq!(providers.len(), 1, "Only one provider should remain");
+        assert!(providers.contains_key("runningprovider"));
+
+        // Compacting again should be a no-op
+        assert_eq!(compact(&store, lattice_id).await.unwrap(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_reaper_compacts_on_tick() {
+        let store = Arc::new(TestStore::default());
+        let lattice_id = "compaction_tick";
+
+        store
+            .store(
+                lattice_id,
+                "host1".to_string(),
+                Host {
+                    id: "host1".to_string(),
+                    components: HashMap::from([("emptyinstances".to_string(), 0)]),
+                    last_seen: Utc::now() + Duration::days(1),
+                    ..Default::default()
+                },
+            )
+            .await
+            .unwrap();
+        // Neither of these can be attributed to a missing host, so only compaction removes them
+        store
+            .store(
+                lattice_id,
+                "emptyinstances".to_string(),
+                Component {
+                    id: "emptyinstances".to_string(),
+                    instances: HashMap::from([("host1".to_string(), HashSet::new())]),
+                    ..Default::default()
+                },
+            )
+            .await
+            .unwrap();
+        store
+            .store(
+                lattice_id,
+                "orphanedprovider".to_string(),
+                Provider {
+                    id: "orphanedprovider".to_string(),
+                    ..Default::default()
+                },
+            )
+            .await
+            .unwrap();
+
+        let _reaper = Reaper::new(
+            store.clone(),
+            NoopPublisher,
+            std::time::Duration::from_millis(100),
+            [lattice_id.to_owned()],
+        );
+        tokio::time::sleep(std::time::Duration::from_millis(300)).await;
+
+        assert!(
+            store
+                .list::<Component>(lattice_id)
+                .await
+                .unwrap()
+                .is_empty(),
+            "Empty component should be compacted by the reaper"
+        );
+        assert!(
+            store.list::<Provider>(lattice_id).await.unwrap().is_empty(),
+            "Orphaned provider should be compacted by the reaper"
+        );
+        assert_eq!(
+            store.list::<Host>(lattice_id).await.unwrap().len(),
+            1,
+            "Live host should not be touched"
+        );
+    }
+
+    /// A store with a single revision for all of its data, where the given heartbeat lands for a
+    /// host in between the reaper checking the host and deleting it or storing its warning
+    #[derive(Clone, Default)]
+    struct HeartbeatMidReapStore {
+        inner: Arc<TestStore>,
+        revision: Arc<std::sync::atomic::AtomicU64>,
+        heartbeat: Arc<std::sync::Mutex<Option<Host>>>,
+    }
+
+    #[async_trait::async_trait]
+    impl ReadStore for HeartbeatMidReapStore {
+        async fn get<T>(&self, lattice_id: &str, id: &str) -> Result<Option<T>, StoreError>
+        where
+            T: serde::de::DeserializeOwned + StateKind,
+        {
+            self.inner.get(lattice_id, id).await
+        }
+
+        async fn list<T>(&self, lattice_id: &str) -> Result<HashMap<String, T>, StoreError>
+        where
+            T: serde::de::DeserializeOwned + StateKind,
+        {
+            self.inner.list(lattice_id).await
+        }
+    }
+
+    #[async_trait::async_trait]
+    impl Store for HeartbeatMidReapStore {
+        async fn store_many<T, D>(&self, lattice_id: &str, data: D) -> Result<(), StoreError>
+        where
+            T: serde::Serialize + serde::de::DeserializeOwned + StateKind + Send + Sync + Clone,
+            D: IntoIterator<Item = (String, T)> + Send,
+        {
+            self.revision
+                .fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+            self.inner.store_many(lattice_id, data).await
+        }
+
+        async fn delete_many<T, D, K>(&self, lattice_id: &str, data: D) -> Result<(), StoreError>
+        where
+            T: serde::Serialize + serde::de::DeserializeOwned + StateKind + Send + Sync,
+            D: IntoIterator<Item = K> + Send,
+            K: AsRef<str>,
+        {
+            self.revision
+                .fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+            self.inner.delete_many::<T, _, _>(lattice_id, data).await
+        }
+
+        async fn get_with_revision<T>(
+            &self,
+            lattice_id: &str,
+            id: &str,
+        ) -> Result<(Option<T>, u64), StoreError>
+        where
+            T: serde::de::DeserializeOwned + StateKind + Send,
+        {
+            let revision = self.revision.load(std::sync::atomic::Ordering::SeqCst);
+            Ok((self.inner.get(lattice_id, id).await?, revision))
+        }
+
+        async fn store_cas<T>(
+            &self,
+            lattice_id: &str,
+            id: String,
+            data: T,
+            expected_revision: u64,
+        ) -> Result<u64, CasError<StoreError>>
+        where
+            T: serde::Serialize + serde::de::DeserializeOwned + StateKind + Send + Sync + Clone,
+        {
+            self.heartbeat_lands(lattice_id, expected_revision).await?;
+            self.store(lattice_id, id, data)
+                .await
+                .map_err(CasError::Store)?;
+            Ok(self.revision.load(std::sync::atomic::Ordering::SeqCst))
+        }
+
+        async fn delete_cas<T>(
+            &self,
+            lattice_id: &str,
+            id: &str,
+            expected_revision: u64,
+        ) -> Result<u64, CasError<StoreError>>
+        where
+            T: serde::Serialize + serde::de::DeserializeOwned + StateKind + Send + Sync,
+        {
+            self.heartbeat_lands(lattice_id, expected_revision).await?;
+            self.delete::<T>(lattice_id, id)
+                .await
+                .map_err(CasError::Store)?;
+            Ok(self.revision.load(std::sync::atomic::Ordering::SeqCst))
+        }
+    }
+
+    impl HeartbeatMidReapStore {
+        /// Stores the heartbeat if it hasn't landed yet, then checks the expected revision
+        async fn heartbeat_lands(
+            &self,
+            lattice_id: &str,
+            expected_revision: u64,
+        ) -> Result<(), CasError<StoreError>> {
+            let heartbeat = self.heartbeat.lock().unwrap().take();
+            if let Some(host) = heartbeat {
+                self.store(lattice_id, host.id.clone(), host)
+                    .await
+                    .map_err(CasError::Store)?;
+            }
+            if self.revision.load(std::sync::atomic::Ordering::SeqCst) != expected_revision {
+                return Err(CasError::Conflict {
+                    expected: expected_revision,
+                });
+            }
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_heartbeat_during_reap_saves_host() {
+        let store = HeartbeatMidReapStore::default();
+        let lattice_id = "reaper_race";
+        let host_id = "host1";
+
+        let host = Host {
+            id: host_id.to_string(),
+            last_seen: Utc::now() - Duration::seconds(10),
+            ..Default::default()
+        };
+        store
+            .store(lattice_id, host_id.to_string(), host.clone())
+            .await
+            .unwrap();
+        let heartbeat_seen = Utc::now();
+        *store.heartbeat.lock().unwrap() = Some(Host {
+            last_seen: heartbeat_seen,
+            ..host
+        });
+
+        let publisher = RecorderPublisher::<CloudEvent> {
+            received: Arc::default(),
+        };
+        let _reaper = Reaper::new(
+            store.clone(),
+            publisher.clone(),
+            std::time::Duration::from_millis(200),
+            [lattice_id.to_owned()],
+        );
+
+        // The first tick fires immediately
+        tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+        assert!(
+            store.heartbeat.lock().unwrap().is_none(),
+            "The reaper should have tried to delete the host"
+        );
+        let host = store
+            .get::<Host>(lattice_id, host_id)
+            .await
+            .unwrap()
+            .expect("A host that heartbeated while being reaped should not be reaped");
+        assert_eq!(host.last_seen, heartbeat_seen);
+        assert!(
+            publisher.received.read().await.is_empty(),
+            "No reaped event should be published for a host that was saved"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_heartbeat_during_warning_keeps_host() {
+        let store = HeartbeatMidReapStore::default();
+        let lattice_id = "reaper_warning_race";
+        let host_id = "host1";
+
+        // Missed one interval but not two, so the reaper wants to warn about it
+        let host = Host {
+            id: host_id.to_string(),
+            last_seen: Utc::now() - Duration::milliseconds(300),
+            ..Default::default()
+        };
+        store
+            .store(lattice_id, host_id.to_string(), host.clone())
+            .await
+            .unwrap();
+        let heartbeat_seen = Utc::now();
+        *store.heartbeat.lock().unwrap() = Some(Host {
+            last_seen: heartbeat_seen,
+            ..host
+        });
+
+        let warned = Arc::new(std::sync::atomic::AtomicBool::new(false));
+        let was_warned = warned.clone();
+        let _reaper = Reaper::new(
+            store.clone(),
+            RecorderPublisher::<CloudEvent> {
+                received: Arc::default(),
+            },
+            std::time::Duration::from_millis(200),
+            [lattice_id.to_owned()],
+        )
+        .with_host_warning_callback(move |_, _| {
+            was_warned.store(true, std::sync::atomic::Ordering::SeqCst)
+        });
+
+        // The first tick fires immediately
+        tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+        assert!(
+            store.heartbeat.lock().unwrap().is_none(),
+            "The reaper should have tried to store the warning"
+        );
+        let host = store
+            .get::<Host>(lattice_id, host_id)
+            .await
+            .unwrap()
+            .expect("Host should still exist");
+        assert_eq!(
+            host.last_seen, heartbeat_seen,
+            "The warning should not overwrite the heartbeat"
+        );
+        assert!(!host.reaping_warning);
+        assert!(!warned.load(std::sync::atomic::Ordering::SeqCst));
+    }
 }
@@ -6,7 +6,9 @@ use tracing::debug;
 use wasmcloud_control_interface::Link;
 use wasmcloud_secrets_types::SecretConfig;
 
-use crate::storage::{Component, Host, Provider, ReadStore, StateKind};
+use crate::storage::{
+    Component, ConsistentRead, Host, Link as LinkState, Provider, ReadStore, StateKind, StoreError,
+};
 use crate::workers::{ConfigSource, LinkSource, SecretSource};
 
 // NOTE(thomastaylor312): This type is real ugly and we should probably find a better way to
@@ -27,7 +29,9 @@ pub struct SnapshotStore<S, L> {
     store: S,
     lattice_source: L,
     lattice_id: String,
-    stored_state: Arc<RwLock<InMemoryData>>,
+    // The data is behind its own Arc so a refresh can swap it out without affecting any views that
+    // are still reading the previous data
+    stored_state: Arc<RwLock<Arc<InMemoryData>>>,
     links: Arc<RwLock<Vec<Link>>>,
 }
 
@@ -87,6 +91,13 @@ where
             .into_iter()
             .map(|(key, val)| (key, serde_json::to_value(val).unwrap()))
             .collect::<HashMap<_, _>>();
+        let link_owners = self
+            .store
+            .list::<LinkState>(&self.lattice_id)
+            .await?
+            .into_iter()
+            .map(|(key, val)| (key, serde_json::to_value(val).unwrap()))
+            .collect::<HashMap<_, _>>();
 
         // If we fail to get the links, that likely just means the lattice source is down, so we
         // just fall back on what we have cached
@@ -96,12 +107,12 @@ where
             debug!("Failed to get links from lattice source, using cached links");
         };
 
-        {
-            let mut stored_state = self.stored_state.write().await;
-            stored_state.insert(Provider::KIND.to_owned(), providers);
-            stored_state.insert(Component::KIND.to_owned(), components);
-            stored_state.insert(Host::KIND.to_owned(), hosts);
-        }
+        *self.stored_state.write().await = Arc::new(HashMap::from([
+            (Provider::KIND.to_owned(), providers),
+            (Component::KIND.to_owned(), components),
+            (Host::KIND.to_owned(), hosts),
+            (LinkState::KIND.to_owned(), link_owners),
+        ]));
 
         Ok(())
     }
@@ -114,10 +125,8 @@ where
     S: ReadStore + Send + Sync,
     L: Send + Sync,
 {
-    type Error = S::Error;
-
     // NOTE(thomastaylor312): See other note about the generic T above, but this is hardcore lolsob
-    async fn get<T>(&self, _lattice_id: &str, id: &str) -> Result<Option<T>, Self::Error>
+    async fn get<T>(&self, _lattice_id: &str, id: &str) -> Result<Option<T>, StoreError>
     where
         T: serde::de::DeserializeOwned + StateKind,
     {
@@ -135,7 +144,7 @@ where
             }))
     }
 
-    async fn list<T>(&self, _lattice_id: &str) -> Result<HashMap<String, T>, Self::Error>
+    async fn list<T>(&self, _lattice_id: &str) -> Result<HashMap<String, T>, StoreError>
     where
         T: serde::de::DeserializeOwned + StateKind,
     {
@@ -159,6 +168,25 @@ where
     }
 }
 
+#[async_trait::async_trait]
+impl<S, L> ConsistentRead for SnapshotStore<S, L>
+where
+    S: ReadStore + Clone + Send + Sync,
+    L: Clone + Send + Sync,
+{
+    /// Returns a copy of this snapshot that is detached from any future refreshes. This is cheap as
+    /// the underlying data is shared until the original is refreshed
+    async fn consistent_view(&self) -> Self {
+        Self {
+            store: self.store.clone(),
+            lattice_source: self.lattice_source.clone(),
+            lattice_id: self.lattice_id.clone(),
+            stored_state: Arc::new(RwLock::new(self.stored_state.read().await.clone())),
+            links: Arc::new(RwLock::new(self.links.read().await.clone())),
+        }
+    }
+}
+
 #[async_trait::async_trait]
 impl<S, L> LinkSource for SnapshotStore<S, L>
 where
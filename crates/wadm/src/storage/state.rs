@@ -1,5 +1,5 @@
 use std::borrow::{Borrow, ToOwned};
-use std::collections::{BTreeMap, HashMap, HashSet};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
 use std::hash::{Hash, Hasher};
 
 use chrono::{DateTime, Utc};
@@ -7,7 +7,10 @@ use semver::Version;
 use serde::{Deserialize, Serialize};
 
 use super::StateKind;
-use crate::events::{ComponentScaled, HostHeartbeat, HostStarted, ProviderInfo, ProviderStarted};
+use crate::commands::PutLink;
+use crate::events::{
+    ComponentScaled, HostHeartbeat, HostStarted, HostUtilization, ProviderInfo, ProviderStarted,
+};
 
 /// A wasmCloud Capability provider
 // NOTE: We probably aren't going to use this _right now_ so we've kept it pretty minimal. But it is
@@ -27,27 +30,26 @@ pub struct Provider {
     /// The reference used to start the provider. Can be empty if it was started from a file
     pub reference: String,
 
-    /// The hosts this provider is running on
-    pub hosts: HashMap<String, ProviderStatus>,
+    /// The hosts this provider is running on, along with its status on each of them
+    pub hosts: HashMap<String, ProviderHostStatus>,
+
+    /// The capabilities the provider's claims say it provides, if it has any
+    #[serde(default)]
+    pub capabilities: Vec<String>,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[derive(Debug, Default, Serialize, Deserialize, Clone, PartialEq)]
 pub enum ProviderStatus {
     /// The provider is starting and hasn't returned a heartbeat yet
+    #[default]
     Pending,
     /// The provider is running
     Running,
     /// The provider failed to start
-    // TODO(thomastaylor312): In the future, we'll probably want to decay out a provider from state
-    // if it hasn't had a heartbeat
-    // if it fails a recent health check
     Failed,
-}
-
-impl Default for ProviderStatus {
-    fn default() -> Self {
-        Self::Pending
-    }
+    /// The provider hasn't reported a health check in a while. The host entry is removed by the
+    /// reaper if it still hasn't reported one by the next check
+    Stale,
 }
 
 impl std::fmt::Display for ProviderStatus {
@@ -59,11 +61,79 @@ impl std::fmt::Display for ProviderStatus {
                 Self::Pending => "pending".to_string(),
                 Self::Running => "running".to_string(),
                 Self::Failed => "failed".to_string(),
+                Self::Stale => "stale".to_string(),
             }
         )
     }
 }
 
+/// The status of a provider on a single host
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(from = "ProviderHostStatusRepr")]
+pub struct ProviderHostStatus {
+    /// The status of the provider on the host
+    pub status: ProviderStatus,
+
+    /// When the provider last reported a health check on the host. If this is `None`, the provider
+    /// hasn't reported one yet and is never considered stale
+    pub last_health_check: Option<DateTime<Utc>>,
+
+    /// The number of instances of the provider running on the host. Hosts that can run more than
+    /// one instance of the same provider report a start and stop for each one
+    pub instances: usize,
+}
+
+impl Default for ProviderHostStatus {
+    fn default() -> Self {
+        ProviderStatus::default().into()
+    }
+}
+
+impl From<ProviderStatus> for ProviderHostStatus {
+    fn from(status: ProviderStatus) -> Self {
+        ProviderHostStatus {
+            status,
+            last_health_check: None,
+            instances: 1,
+        }
+    }
+}
+
+/// Host entries used to only be the status, so both forms are accepted when reading state
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum ProviderHostStatusRepr {
+    Status(ProviderStatus),
+    Full {
+        status: ProviderStatus,
+        #[serde(default)]
+        last_health_check: Option<DateTime<Utc>>,
+        #[serde(default = "default_provider_instances")]
+        instances: usize,
+    },
+}
+
+fn default_provider_instances() -> usize {
+    1
+}
+
+impl From<ProviderHostStatusRepr> for ProviderHostStatus {
+    fn from(value: ProviderHostStatusRepr) -> Self {
+        match value {
+            ProviderHostStatusRepr::Status(status) => status.into(),
+            ProviderHostStatusRepr::Full {
+                status,
+                last_health_check,
+                instances,
+            } => ProviderHostStatus {
+                status,
+                last_health_check,
+                instances,
+            },
+        }
+    }
+}
+
 impl StateKind for Provider {
     const KIND: &'static str = "provider";
 }
@@ -147,6 +217,15 @@ pub struct Component {
 
     /// The reference used to start the component. Can be empty if it was started from a file
     pub reference: String,
+
+    /// The reference each host last reported running the component from, keyed by host ID. This
+    /// can differ between hosts while a new version of the component is being rolled out
+    #[serde(default)]
+    pub host_references: HashMap<String, String>,
+
+    /// The capabilities the component's claims say it requires, if it has any
+    #[serde(default)]
+    pub capabilities: Vec<String>,
 }
 
 impl Component {
@@ -167,6 +246,21 @@ impl Component {
             .map(|instances| instances.iter().map(|info| info.count).sum::<usize>())
             .unwrap_or_default()
     }
+
+    /// Returns every distinct reference this component is currently running from. Hosts without a
+    /// recorded reference are assumed to be running [`Component::reference`]
+    pub fn running_references(&self) -> BTreeSet<&str> {
+        self.instances
+            .keys()
+            .map(|host_id| {
+                self.host_references
+                    .get(host_id)
+                    .unwrap_or(&self.reference)
+                    .as_str()
+            })
+            .filter(|reference| !reference.is_empty())
+            .collect()
+    }
 }
 
 impl StateKind for Component {
@@ -180,6 +274,7 @@ impl From<ComponentScaled> for Component {
             id: value.component_id,
             name,
             issuer,
+            host_references: HashMap::from_iter([(value.host_id.clone(), value.image_ref.clone())]),
             reference: value.image_ref,
             instances: HashMap::from_iter([(
                 value.host_id,
@@ -188,6 +283,7 @@ impl From<ComponentScaled> for Component {
                     count: value.max_instances,
                 }]),
             )]),
+            capabilities: Vec::new(),
         }
     }
 }
@@ -214,6 +310,8 @@ impl From<&ComponentScaled> for Component {
                     count: value.max_instances,
                 }]),
             )]),
+            host_references: HashMap::from_iter([(value.host_id.clone(), value.image_ref.clone())]),
+            capabilities: Vec::new(),
         }
     }
 }
@@ -248,12 +346,28 @@ pub struct Host {
 
     /// The time when this host was last seen, as a RFC3339 timestamp
     pub last_seen: DateTime<Utc>,
+
+    /// Whether the host has missed a heartbeat interval and will be reaped if it isn't seen again
+    /// before the next reaper check. This is cleared by the next heartbeat from the host
+    #[serde(default)]
+    pub reaping_warning: bool,
+
+    /// The resource utilization from the host's last heartbeat, if the host reports it
+    #[serde(default)]
+    pub utilization: Option<HostUtilization>,
 }
 
 impl StateKind for Host {
     const KIND: &'static str = "host";
 }
 
+impl Host {
+    /// Returns roughly when the host started, based on its uptime as of when it was last seen
+    pub fn started_at(&self) -> DateTime<Utc> {
+        self.last_seen - chrono::Duration::seconds(self.uptime_seconds as i64)
+    }
+}
+
 impl From<HostStarted> for Host {
     fn from(value: HostStarted) -> Self {
         Host {
@@ -315,6 +429,8 @@ impl From<HostHeartbeat> for Host {
             version: Some(value.version),
             id: value.host_id,
             last_seen: Utc::now(),
+            reaping_warning: false,
+            utilization: value.utilization,
         }
     }
 }
@@ -356,6 +472,88 @@ impl From<&HostHeartbeat> for Host {
             version: Some(value.version.clone()),
             id: value.host_id.clone(),
             last_seen: Utc::now(),
+            reaping_warning: false,
+            utilization: value.utilization,
+        }
+    }
+}
+
+/// A link that was put into the lattice by wadm, used to track which model owns the link. Links
+/// are keyed by [`Link::key`], which matches how a host identifies a link, so only one model can
+/// own a given link at a time
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq, Eq)]
+pub struct Link {
+    /// The name of the model that put the link
+    pub model_name: String,
+    pub source_id: String,
+    pub target: String,
+    pub name: String,
+    pub wit_namespace: String,
+    pub wit_package: String,
+    pub interfaces: Vec<String>,
+    pub source_config: Vec<String>,
+    pub target_config: Vec<String>,
+}
+
+impl StateKind for Link {
+    const KIND: &'static str = "link";
+}
+
+impl Link {
+    /// Returns the ID a link is stored under
+    pub fn key(source_id: &str, wit_namespace: &str, wit_package: &str, name: &str) -> String {
+        format!("{source_id}/{wit_namespace}:{wit_package}/{name}")
+    }
+
+    /// Returns true if the given link is owned by a different model and has different values than
+    /// this one, meaning the two models would keep overwriting each other's link
+    pub fn conflicts_with(&self, other: &Link) -> bool {
+        self.model_name != other.model_name
+            && (self.target != other.target
+                || self.interfaces != other.interfaces
+                || self.source_config != other.source_config
+                || self.target_config != other.target_config)
+    }
+}
+
+impl From<&PutLink> for Link {
+    fn from(value: &PutLink) -> Self {
+        Link {
+            model_name: value.model_name.clone(),
+            source_id: value.source_id.clone(),
+            target: value.target.clone(),
+            name: value.name.clone(),
+            wit_namespace: value.wit_namespace.clone(),
+            wit_package: value.wit_package.clone(),
+            interfaces: value.interfaces.clone(),
+            source_config: value.source_config.clone(),
+            target_config: value.target_config.clone(),
         }
     }
 }
+
+/// A short-lived claim on a command, used to make sure only one wadm instance publishes a given
+/// command. The ID of the claim is the [`Command::id`](crate::commands::Command::id)
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct CommandClaim {
+    /// An identifier of the wadm instance that owns this claim
+    pub owner: String,
+    /// The time after which this claim is no longer valid
+    pub expires_at: DateTime<Utc>,
+}
+
+impl StateKind for CommandClaim {
+    const KIND: &'static str = "commandclaim";
+}
+
+/// A lease renewed by each running wadm instance to show other instances that it is still alive
+/// and in contact with the store. The ID of the lease is the ID of the wadm instance
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct InstanceLease {
+    /// The time after which the instance should be considered unreachable if it hasn't renewed
+    pub expires_at: DateTime<Utc>,
+}
+
+impl StateKind for InstanceLease {
+    const KIND: &'static str = "instancelease";
+}
@@ -1,4 +1,3 @@
-use std::convert::Infallible;
 use std::{collections::HashMap, sync::Arc};
 
 use serde::{de::DeserializeOwned, Serialize};
@@ -7,7 +6,7 @@ use wasmcloud_control_interface::{HostInventory, Link};
 use wasmcloud_secrets_types::SecretConfig;
 
 use crate::publisher::Publisher;
-use crate::storage::StateKind;
+use crate::storage::{CasError, StateKind, StoreError};
 use crate::workers::{
     secret_config_from_map, Claims, ClaimsSource, ConfigSource, InventorySource, LinkSource,
     SecretSource,
@@ -21,13 +20,31 @@ fn generate_key<T: StateKind>(lattice_id: &str) -> String {
 #[derive(Default)]
 pub struct TestStore {
     pub inner: tokio::sync::RwLock<HashMap<String, Vec<u8>>>,
+    // The revision of each key in `inner`, bumped on every write to it
+    revisions: std::sync::Mutex<HashMap<String, u64>>,
+}
+
+impl TestStore {
+    fn bump_revision(&self, key: &str) -> u64 {
+        let mut revisions = self.revisions.lock().unwrap();
+        let revision = revisions.entry(key.to_owned()).or_default();
+        *revision += 1;
+        *revision
+    }
+
+    fn revision(&self, key: &str) -> u64 {
+        self.revisions
+            .lock()
+            .unwrap()
+            .get(key)
+            .copied()
+            .unwrap_or_default()
+    }
 }
 
 #[async_trait::async_trait]
 impl crate::storage::ReadStore for TestStore {
-    type Error = Infallible;
-
-    async fn get<T>(&self, lattice_id: &str, id: &str) -> Result<Option<T>, Self::Error>
+    async fn get<T>(&self, lattice_id: &str, id: &str) -> Result<Option<T>, StoreError>
     where
         T: DeserializeOwned + StateKind,
     {
@@ -43,7 +60,7 @@ impl crate::storage::ReadStore for TestStore {
         Ok(all.remove(id))
     }
 
-    async fn list<T>(&self, lattice_id: &str) -> Result<HashMap<String, T>, Self::Error>
+    async fn list<T>(&self, lattice_id: &str) -> Result<HashMap<String, T>, StoreError>
     where
         T: DeserializeOwned + StateKind,
     {
@@ -58,9 +75,19 @@ impl crate::storage::ReadStore for TestStore {
     }
 }
 
+#[async_trait::async_trait]
+impl crate::storage::ConsistentRead for Arc<TestStore> {
+    async fn consistent_view(&self) -> Self {
+        Arc::new(TestStore {
+            inner: RwLock::new(self.inner.read().await.clone()),
+            revisions: std::sync::Mutex::new(self.revisions.lock().unwrap().clone()),
+        })
+    }
+}
+
 #[async_trait::async_trait]
 impl crate::storage::Store for TestStore {
-    async fn store_many<T, D>(&self, lattice_id: &str, data: D) -> Result<(), Self::Error>
+    async fn store_many<T, D>(&self, lattice_id: &str, data: D) -> Result<(), StoreError>
     where
         T: Serialize + DeserializeOwned + StateKind + Send + Sync + Clone,
         D: IntoIterator<Item = (String, T)> + Send,
@@ -77,11 +104,12 @@ impl crate::storage::Store for TestStore {
         self.inner
             .write()
             .await
-            .insert(key, serde_json::to_vec(&all).unwrap());
+            .insert(key.clone(), serde_json::to_vec(&all).unwrap());
+        self.bump_revision(&key);
         Ok(())
     }
 
-    async fn delete_many<T, D, K>(&self, lattice_id: &str, data: D) -> Result<(), Self::Error>
+    async fn delete_many<T, D, K>(&self, lattice_id: &str, data: D) -> Result<(), StoreError>
     where
         T: Serialize + DeserializeOwned + StateKind + Send + Sync,
         D: IntoIterator<Item = K> + Send,
@@ -101,9 +129,79 @@ impl crate::storage::Store for TestStore {
         self.inner
             .write()
             .await
-            .insert(key, serde_json::to_vec(&all).unwrap());
+            .insert(key.clone(), serde_json::to_vec(&all).unwrap());
+        self.bump_revision(&key);
         Ok(())
     }
+
+    async fn get_with_revision<T>(
+        &self,
+        lattice_id: &str,
+        id: &str,
+    ) -> Result<(Option<T>, u64), StoreError>
+    where
+        T: DeserializeOwned + StateKind + Send,
+    {
+        let key = generate_key::<T>(lattice_id);
+        let inner = self.inner.read().await;
+        let mut all: HashMap<String, T> = inner
+            .get(&key)
+            .map(|raw| serde_json::from_slice(raw).unwrap())
+            .unwrap_or_default();
+        Ok((all.remove(id), self.revision(&key)))
+    }
+
+    async fn store_cas<T>(
+        &self,
+        lattice_id: &str,
+        id: String,
+        data: T,
+        expected_revision: u64,
+    ) -> Result<u64, CasError<StoreError>>
+    where
+        T: Serialize + DeserializeOwned + StateKind + Send + Sync + Clone,
+    {
+        let key = generate_key::<T>(lattice_id);
+        // Holding the write lock makes the check and the write atomic
+        let mut inner = self.inner.write().await;
+        if self.revision(&key) != expected_revision {
+            return Err(CasError::Conflict {
+                expected: expected_revision,
+            });
+        }
+        let mut all: HashMap<String, T> = inner
+            .get(&key)
+            .map(|raw| serde_json::from_slice(raw).unwrap())
+            .unwrap_or_default();
+        all.insert(id, data);
+        inner.insert(key.clone(), serde_json::to_vec(&all).unwrap());
+        Ok(self.bump_revision(&key))
+    }
+
+    async fn delete_cas<T>(
+        &self,
+        lattice_id: &str,
+        id: &str,
+        expected_revision: u64,
+    ) -> Result<u64, CasError<StoreError>>
+    where
+        T: Serialize + DeserializeOwned + StateKind + Send + Sync,
+    {
+        let key = generate_key::<T>(lattice_id);
+        let mut inner = self.inner.write().await;
+        if self.revision(&key) != expected_revision {
+            return Err(CasError::Conflict {
+                expected: expected_revision,
+            });
+        }
+        let mut all: HashMap<String, T> = inner
+            .get(&key)
+            .map(|raw| serde_json::from_slice(raw).unwrap())
+            .unwrap_or_default();
+        all.remove(id);
+        inner.insert(key.clone(), serde_json::to_vec(&all).unwrap());
+        Ok(self.bump_revision(&key))
+    }
 }
 
 #[derive(Clone, Default, Debug)]
@@ -127,6 +225,10 @@ impl InventorySource for TestLatticeSource {
     async fn get_inventory(&self, host_id: &str) -> anyhow::Result<HostInventory> {
         Ok(self.inventory.read().await.get(host_id).cloned().unwrap())
     }
+
+    async fn get_host_ids(&self) -> anyhow::Result<Vec<String>> {
+        Ok(self.inventory.read().await.keys().cloned().collect())
+    }
 }
 
 #[async_trait::async_trait]
@@ -167,6 +269,7 @@ impl Publisher for NoopPublisher {
 }
 
 /// A publisher that records all data sent to it (as the given type deserialized from JSON)
+#[derive(Clone)]
 pub struct RecorderPublisher<T> {
     pub received: Arc<RwLock<Vec<T>>>,
 }
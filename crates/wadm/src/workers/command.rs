@@ -1,4 +1,17 @@
-use tracing::{instrument, trace};
+use std::{
+    collections::HashMap,
+    future::Future,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    time::{Duration, SystemTime},
+};
+
+use cloudevents::Event as CloudEvent;
+use tokio::sync::{Mutex, OwnedSemaphorePermit, Semaphore};
+use tracing::{debug, info, instrument, trace, warn};
+use wasmcloud_control_interface::CtlResponse;
 
 use crate::{
     commands::*,
@@ -6,30 +19,375 @@ use crate::{
         manager::{WorkError, WorkResult, Worker},
         ScopedMessage,
     },
+    events::{CommandExecuted, Event},
+    publisher::Publisher,
 };
 
 use super::insert_managed_annotations;
 
+/// Limits the number of operations that can run against any single host at the same time, while
+/// still allowing operations against different hosts to run in parallel.
+///
+/// This type is cheap to clone and all clones share the same limits
+#[derive(Debug, Clone)]
+pub struct HostConcurrencyLimit {
+    max_per_host: usize,
+    hosts: Arc<Mutex<HashMap<String, Arc<Semaphore>>>>,
+}
+
+impl HostConcurrencyLimit {
+    /// Creates a new limit allowing at most `max_per_host` operations per host. A value of 0 is
+    /// treated as 1
+    pub fn new(max_per_host: usize) -> HostConcurrencyLimit {
+        HostConcurrencyLimit {
+            max_per_host: max_per_host.max(1),
+            hosts: Arc::default(),
+        }
+    }
+
+    /// Waits until an operation can be run against the given host. The operation is counted
+    /// against the limit until the returned permit is dropped
+    pub async fn acquire(&self, host_id: &str) -> OwnedSemaphorePermit {
+        let semaphore = {
+            let mut hosts = self.hosts.lock().await;
+            // Drop any hosts that don't have anything running so we don't hold onto hosts that
+            // have gone away
+            hosts.retain(|_, semaphore| Arc::strong_count(semaphore) > 1);
+            hosts
+                .entry(host_id.to_owned())
+                .or_insert_with(|| Arc::new(Semaphore::new(self.max_per_host)))
+                .clone()
+        };
+        semaphore
+            .acquire_owned()
+            .await
+            .expect("host semaphores are never closed")
+    }
+}
+
+/// The default number of consecutive control interface failures before a [`CommandWorker`] pauses
+const DEFAULT_CTL_FAILURE_THRESHOLD: usize = 5;
+/// The default amount of time to wait before the first check of a paused control interface
+const DEFAULT_CTL_INITIAL_BACKOFF: Duration = Duration::from_secs(1);
+/// The default maximum amount of time to wait between checks of a paused control interface
+const DEFAULT_CTL_MAX_BACKOFF: Duration = Duration::from_secs(30);
+
+/// Tracks consecutive failures to reach the control interface. Once enough requests fail in a row,
+/// the breaker opens and [`wait_until_healthy`](Self::wait_until_healthy) holds the caller until a
+/// health check succeeds, backing off exponentially between checks. This keeps a worker from
+/// hot looping on commands that can't succeed while the control interface is down.
+///
+/// Only failures to get a response count against the breaker. A host responding with an error
+/// means the control interface is up.
+///
+/// This type is cheap to clone and all clones share the same state
+#[derive(Debug, Clone)]
+pub struct ControlInterfaceBreaker {
+    threshold: usize,
+    initial_backoff: Duration,
+    max_backoff: Duration,
+    consecutive_failures: Arc<AtomicUsize>,
+}
+
+impl Default for ControlInterfaceBreaker {
+    fn default() -> Self {
+        ControlInterfaceBreaker::new(
+            DEFAULT_CTL_FAILURE_THRESHOLD,
+            DEFAULT_CTL_INITIAL_BACKOFF,
+            DEFAULT_CTL_MAX_BACKOFF,
+        )
+    }
+}
+
+impl ControlInterfaceBreaker {
+    /// Creates a new breaker that opens after `threshold` consecutive failures (a value of 0 is
+    /// treated as 1) and checks the control interface with a backoff starting at
+    /// `initial_backoff`, doubling up to `max_backoff`
+    pub fn new(
+        threshold: usize,
+        initial_backoff: Duration,
+        max_backoff: Duration,
+    ) -> ControlInterfaceBreaker {
+        ControlInterfaceBreaker {
+            threshold: threshold.max(1),
+            initial_backoff,
+            max_backoff: max_backoff.max(initial_backoff),
+            consecutive_failures: Arc::default(),
+        }
+    }
+
+    /// Returns true if enough consecutive failures have happened that work should be paused
+    pub fn is_open(&self) -> bool {
+        self.consecutive_failures.load(Ordering::SeqCst) >= self.threshold
+    }
+
+    pub fn record_success(&self) {
+        self.consecutive_failures.store(0, Ordering::SeqCst);
+    }
+
+    pub fn record_failure(&self) {
+        self.consecutive_failures.fetch_add(1, Ordering::SeqCst);
+    }
+
+    /// Waits until the breaker is closed, running the given health check with an exponential
+    /// backoff until it returns true. Returns immediately if the breaker isn't open
+    pub async fn wait_until_healthy<F, Fut>(&self, mut health_check: F)
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = bool>,
+    {
+        if !self.is_open() {
+            return;
+        }
+        warn!(
+            failures = self.consecutive_failures.load(Ordering::SeqCst),
+            "Control interface appears to be unavailable, pausing until it recovers"
+        );
+        let mut backoff = self.initial_backoff;
+        loop {
+            tokio::time::sleep(backoff).await;
+            if health_check().await {
+                info!("Control interface is available again, resuming");
+                self.record_success();
+                return;
+            }
+            debug!(?backoff, "Control interface is still unavailable");
+            backoff = (backoff * 2).min(self.max_backoff);
+        }
+    }
+}
+
+/// The error returned by the control interface client when a request can't be completed
+type CtlError = Box<dyn std::error::Error + Send + Sync>;
+
+/// The default number of times a [`CommandWorker`] tries to send a command
+const DEFAULT_RETRY_MAX_ATTEMPTS: usize = 3;
+/// The default amount of time to wait before the first retry of a command
+const DEFAULT_RETRY_BASE_DELAY: Duration = Duration::from_millis(250);
+
+/// How a [`CommandWorker`] retries commands that fail to reach the control interface before
+/// giving up and nacking them. Retrying in-process with a backoff smooths over transient failures
+/// without every failed command being redelivered at once.
+///
+/// Only failures to get a response are retried. A host responding with an error is a permanent
+/// failure for that command and isn't retried
+#[derive(Debug, Clone, Copy)]
+pub struct RetryPolicy {
+    max_attempts: usize,
+    base_delay: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy::new(DEFAULT_RETRY_MAX_ATTEMPTS, DEFAULT_RETRY_BASE_DELAY)
+    }
+}
+
+impl RetryPolicy {
+    /// Creates a new policy that tries a command at most `max_attempts` times (a value of 0 is
+    /// treated as 1), waiting `base_delay` before the first retry and doubling the wait for each
+    /// retry after that
+    pub fn new(max_attempts: usize, base_delay: Duration) -> RetryPolicy {
+        RetryPolicy {
+            max_attempts: max_attempts.max(1),
+            base_delay,
+        }
+    }
+
+    /// Runs the given operation until it succeeds or the maximum number of attempts is reached,
+    /// returning the result of the last attempt
+    pub async fn retry<F, Fut, T, E>(&self, mut operation: F) -> Result<T, E>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<T, E>>,
+        E: std::fmt::Debug,
+    {
+        let mut delay = self.base_delay;
+        let mut attempt = 1;
+        loop {
+            match operation().await {
+                Err(e) if attempt < self.max_attempts => {
+                    debug!(error = ?e, %attempt, ?delay, "Command failed, retrying");
+                    tokio::time::sleep(delay).await;
+                    delay = delay.saturating_mul(2);
+                    attempt += 1;
+                }
+                res => return res,
+            }
+        }
+    }
+}
+
+/// The most commands a [`CommandWorker`] handles together when scale concurrency is enabled
+const MAX_COMMAND_BATCH_SIZE: usize = 64;
+
+/// Returns the host and component a `ScaleComponent` command targets, or the host and provider a
+/// `StartProvider` command targets. Returns `None` for any other command
+fn concurrent_target(command: &Command) -> Option<(&str, &str)> {
+    match command {
+        Command::ScaleComponent(scale) => Some((&scale.host_id, &scale.component_id)),
+        Command::StartProvider(start) => Some((&start.host_id, &start.provider_id)),
+        _ => None,
+    }
+}
+
+/// Runs a batch of commands with the given function, returning the results in the same order as
+/// the commands. Consecutive `ScaleComponent` and `StartProvider` commands run concurrently, at
+/// most `concurrency` at a time, but commands for the same component or provider on the same host
+/// run in order so a stop followed by a start can never be swapped. Provider starts are further
+/// limited per host by the [`HostConcurrencyLimit`] of the worker, if any. Any other command
+/// waits for everything before it and runs alone
+async fn run_batch<M, F, Fut, R>(messages: Vec<M>, concurrency: usize, run: F) -> Vec<R>
+where
+    M: AsRef<Command>,
+    F: Fn(M) -> Fut,
+    Fut: Future<Output = R>,
+{
+    let semaphore = Semaphore::new(concurrency.max(1));
+    let mut results = Vec::with_capacity(messages.len());
+    let mut messages = messages.into_iter().enumerate().peekable();
+    while let Some((index, message)) = messages.next() {
+        if concurrent_target(message.as_ref()).is_none() {
+            results.push((index, run(message).await));
+            continue;
+        }
+        let mut chains: HashMap<(String, String), Vec<(usize, M)>> = HashMap::new();
+        let mut next = Some((index, message));
+        while let Some((index, message)) = next {
+            if let Some((host_id, component_id)) = concurrent_target(message.as_ref()) {
+                chains
+                    .entry((host_id.to_owned(), component_id.to_owned()))
+                    .or_default()
+                    .push((index, message));
+            }
+            next = messages.next_if(|(_, message)| concurrent_target(message.as_ref()).is_some());
+        }
+        let ran = futures::future::join_all(chains.into_values().map(|chain| async {
+            let mut ran = Vec::with_capacity(chain.len());
+            for (index, message) in chain {
+                let _permit = semaphore
+                    .acquire()
+                    .await
+                    .expect("batch semaphore is never closed");
+                ran.push((index, run(message).await));
+            }
+            ran
+        }))
+        .await;
+        results.extend(ran.into_iter().flatten());
+    }
+    results.sort_by_key(|(index, _)| *index);
+    results.into_iter().map(|(_, result)| result).collect()
+}
+
 /// A worker implementation for handling incoming commands
 #[derive(Clone)]
-pub struct CommandWorker {
+pub struct CommandWorker<P> {
     client: wasmcloud_control_interface::Client,
+    publisher: P,
+    result_topic: String,
+    provider_start_limit: Option<HostConcurrencyLimit>,
+    breaker: ControlInterfaceBreaker,
+    command_ttl: Option<Duration>,
+    retry_policy: RetryPolicy,
+    scale_concurrency: Option<usize>,
+    publish_successes: bool,
 }
 
-impl CommandWorker {
-    /// Creates a new command worker with the given connection pool.
-    pub fn new(ctl_client: wasmcloud_control_interface::Client) -> CommandWorker {
-        CommandWorker { client: ctl_client }
+impl<P> CommandWorker<P> {
+    /// Creates a new command worker with the given connection pool. The result of every failed
+    /// command and every link command is published as a [`CommandExecuted`] event to the given
+    /// topic using the given publisher
+    pub fn new(
+        ctl_client: wasmcloud_control_interface::Client,
+        publisher: P,
+        result_topic: &str,
+    ) -> CommandWorker<P> {
+        CommandWorker {
+            client: ctl_client,
+            publisher,
+            result_topic: result_topic.to_owned(),
+            provider_start_limit: None,
+            breaker: ControlInterfaceBreaker::default(),
+            command_ttl: None,
+            retry_policy: RetryPolicy::default(),
+            scale_concurrency: None,
+            publish_successes: false,
+        }
     }
-}
 
-#[async_trait::async_trait]
-impl Worker for CommandWorker {
-    type Message = Command;
+    /// Limits the number of `StartProvider` commands that can be sent to a single host at the same
+    /// time. Provider starts only run at the same time when scale concurrency is enabled (see
+    /// [`CommandWorker::with_scale_concurrency`]). The limit can be shared with other workers so it
+    /// applies across all of them
+    pub fn with_provider_start_limit(mut self, limit: HostConcurrencyLimit) -> CommandWorker<P> {
+        self.provider_start_limit = Some(limit);
+        self
+    }
 
-    #[instrument(level = "trace", skip_all)]
-    async fn do_work(&self, mut message: ScopedMessage<Self::Message>) -> WorkResult<()> {
-        let res = match message.as_ref() {
+    /// Sets the breaker used to pause this worker when the control interface is unavailable.
+    /// Defaults to [`ControlInterfaceBreaker::default`]
+    pub fn with_breaker(mut self, breaker: ControlInterfaceBreaker) -> CommandWorker<P> {
+        self.breaker = breaker;
+        self
+    }
+
+    /// Sets how commands that fail to reach the control interface are retried before they are
+    /// nacked. Defaults to [`RetryPolicy::default`]
+    pub fn with_retry_policy(mut self, policy: RetryPolicy) -> CommandWorker<P> {
+        self.retry_policy = policy;
+        self
+    }
+
+    /// Handles commands that are waiting at the same time together, running `ScaleComponent` and
+    /// `StartProvider` commands for different components and providers at the same time instead
+    /// of one after another, at most `max_concurrent` at once. Commands for the same component or
+    /// provider on the same host still run in the order they were published. Disabled by default
+    pub fn with_scale_concurrency(mut self, max_concurrent: usize) -> CommandWorker<P> {
+        self.scale_concurrency = Some(max_concurrent.max(1));
+        self
+    }
+
+    /// Publishes a [`CommandExecuted`] event for every command that succeeds, not just for link
+    /// commands. Shadow scalers need these to compare their commands against the executed ones
+    pub fn with_success_results(mut self, enabled: bool) -> CommandWorker<P> {
+        self.publish_successes = enabled;
+        self
+    }
+
+    /// Discards commands that were published more than `ttl` ago instead of executing them. When
+    /// commands back up, the state that produced old commands has likely changed, and a newer
+    /// reconcile will have issued whatever is still needed. Discarded commands are acked so they
+    /// aren't redelivered
+    pub fn with_command_ttl(mut self, ttl: Duration) -> CommandWorker<P> {
+        self.command_ttl = Some(ttl);
+        self
+    }
+
+    /// Returns how long ago a command published at the given time was published, if it is older
+    /// than the configured TTL
+    fn expired_age(&self, published: Option<SystemTime>) -> Option<Duration> {
+        let ttl = self.command_ttl?;
+        let age = published?.elapsed().ok()?;
+        (age > ttl).then_some(age)
+    }
+
+    /// Returns whether the given result should be published. Failures let scalers update their
+    /// status right away and successful link commands record which model owns the link, but other
+    /// successes are only published when enabled since scalers already observe them through
+    /// lattice events
+    fn should_publish(&self, executed: &CommandExecuted) -> bool {
+        !executed.success
+            || self.publish_successes
+            || matches!(
+                executed.command,
+                Command::PutLink(_) | Command::DeleteLink(_)
+            )
+    }
+
+    /// Sends the given command to the lattice
+    async fn execute(&self, command: &Command) -> Result<CtlResponse<()>, CtlError> {
+        match command {
             Command::ScaleComponent(component) => {
                 trace!(command = ?component, "Handling scale component command");
                 // Order here is intentional to prevent scalers from overwriting managed annotations
@@ -46,8 +404,26 @@ impl Worker for CommandWorker {
                     )
                     .await
             }
+            Command::UpdateComponent(update) => {
+                trace!(command = ?update, "Handling update component command");
+                // Order here is intentional to prevent scalers from overwriting managed annotations
+                let mut annotations = update.annotations.clone();
+                insert_managed_annotations(&mut annotations, &update.model_name);
+                self.client
+                    .update_component(
+                        &update.host_id,
+                        &update.component_id,
+                        &update.new_reference,
+                        Some(annotations),
+                    )
+                    .await
+            }
             Command::StartProvider(prov) => {
                 trace!(command = ?prov, "Handling start provider command");
+                let _permit = match &self.provider_start_limit {
+                    Some(limit) => Some(limit.acquire(&prov.host_id).await),
+                    None => None,
+                };
                 // Order here is intentional to prevent scalers from overwriting managed annotations
                 let mut annotations = prov.annotations.clone();
                 insert_managed_annotations(&mut annotations, &prov.model_name);
@@ -98,7 +474,45 @@ impl Worker for CommandWorker {
                 self.client.delete_config(&delete_config.config_name).await
             }
         }
-        .map_err(|e| anyhow::anyhow!("{e:?}"));
+    }
+}
+
+#[async_trait::async_trait]
+impl<P: Publisher + Send + Sync> Worker for CommandWorker<P> {
+    type Message = Command;
+
+    #[instrument(level = "trace", skip_all)]
+    async fn do_work(&self, mut message: ScopedMessage<Self::Message>) -> WorkResult<()> {
+        if let Some(age) = self.expired_age(message.published()) {
+            info!(
+                ?age,
+                command = ?message.as_ref(),
+                "Discarding command that is older than the command TTL"
+            );
+            return message.ack().await.map_err(WorkError::from);
+        }
+
+        let res = self
+            .retry_policy
+            .retry(|| self.execute(message.as_ref()))
+            .await
+            .map_err(|e| anyhow::anyhow!("{e:?}"));
+
+        match res {
+            Ok(_) => self.breaker.record_success(),
+            Err(_) => self.breaker.record_failure(),
+        }
+
+        // NOTE: Failing to publish the result shouldn't cause the command to be retried, the
+        // scalers will still eventually observe the result through lattice events
+        let executed = command_executed(message.as_ref(), &res);
+        if self.should_publish(&executed) {
+            if let Err(e) =
+                publish_command_result(&self.publisher, &self.result_topic, executed).await
+            {
+                warn!(error = %e, "Unable to publish command result");
+            }
+        }
 
         match res {
             Ok(ack) if !ack.succeeded() => {
@@ -110,8 +524,457 @@ impl Worker for CommandWorker {
             Ok(_) => message.ack().await.map_err(WorkError::from),
             Err(e) => {
                 message.nack().await;
+                // Hold off on pulling the next command until the control interface is back, rather
+                // than failing every command in the meantime
+                self.breaker
+                    .wait_until_healthy(|| async { self.client.get_hosts().await.is_ok() })
+                    .await;
                 Err(WorkError::Other(e.into()))
             }
         }
     }
+
+    fn max_batch_size(&self) -> usize {
+        if self.scale_concurrency.is_some() {
+            MAX_COMMAND_BATCH_SIZE
+        } else {
+            1
+        }
+    }
+
+    async fn do_batch(&self, messages: Vec<ScopedMessage<Self::Message>>) -> Vec<WorkResult<()>> {
+        let concurrency = self.scale_concurrency.unwrap_or(1);
+        // Each message is still handled, and acked or nacked, on its own
+        run_batch(messages, concurrency, |message| self.do_work(message)).await
+    }
+}
+
+/// Builds a [`CommandExecuted`] event from the result of sending a command to the lattice
+fn command_executed(
+    command: &Command,
+    result: &anyhow::Result<CtlResponse<()>>,
+) -> CommandExecuted {
+    let (success, message) = match result {
+        Ok(ack) => (ack.succeeded(), ack.message().to_owned()),
+        Err(e) => (false, e.to_string()),
+    };
+    CommandExecuted {
+        command_id: command.id(),
+        command: command.to_owned(),
+        success,
+        message,
+    }
+}
+
+async fn publish_command_result<P: Publisher>(
+    publisher: &P,
+    topic: &str,
+    executed: CommandExecuted,
+) -> anyhow::Result<()> {
+    let event: CloudEvent = Event::CommandExecuted(executed).try_into()?;
+    publisher
+        .publish(serde_json::to_vec(&event)?, Some(topic))
+        .await
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::Duration;
+
+    use tokio::sync::RwLock;
+
+    use super::*;
+    use crate::test_util::RecorderPublisher;
+
+    #[tokio::test]
+    async fn provider_starts_are_limited_per_host() {
+        let limit = HostConcurrencyLimit::new(1);
+        // Currently running and max seen starts for each host, plus the max seen across all hosts
+        let running: Arc<[AtomicUsize; 2]> = Arc::default();
+        let max_running: Arc<[AtomicUsize; 2]> = Arc::default();
+        let total_running = Arc::new(AtomicUsize::new(0));
+        let max_total_running = Arc::new(AtomicUsize::new(0));
+
+        // Many different providers waiting to start on the same two hosts of a lattice
+        let messages = (0..10)
+            .map(|i| ScopedMessage {
+                lattice_id: "default".to_string(),
+                inner: Command::StartProvider(StartProvider {
+                    reference: format!("fakecloud.azurecr.io/provider{i}:0.1.0"),
+                    provider_id: format!("provider{i}"),
+                    host_id: format!("host{}", i % 2),
+                    model_name: "model".to_string(),
+                    ..Default::default()
+                }),
+                acker: None,
+                ack_batcher: None,
+                published: None,
+            })
+            .collect::<Vec<_>>();
+        run_batch(messages, MAX_COMMAND_BATCH_SIZE, |message| {
+            let limit = limit.clone();
+            let running = running.clone();
+            let max_running = max_running.clone();
+            let total_running = total_running.clone();
+            let max_total_running = max_total_running.clone();
+            async move {
+                let Command::StartProvider(start) = message.as_ref() else {
+                    panic!("Only provider starts are in the batch");
+                };
+                let _permit = limit.acquire(&start.host_id).await;
+                let host = if start.host_id == "host0" { 0 } else { 1 };
+                let now = running[host].fetch_add(1, Ordering::SeqCst) + 1;
+                max_running[host].fetch_max(now, Ordering::SeqCst);
+                let total = total_running.fetch_add(1, Ordering::SeqCst) + 1;
+                max_total_running.fetch_max(total, Ordering::SeqCst);
+                tokio::time::sleep(Duration::from_millis(20)).await;
+                running[host].fetch_sub(1, Ordering::SeqCst);
+                total_running.fetch_sub(1, Ordering::SeqCst);
+            }
+        })
+        .await;
+
+        for (host, max) in max_running.iter().enumerate() {
+            assert_eq!(
+                max.load(Ordering::SeqCst),
+                1,
+                "Starts on host{host} should be serialized"
+            );
+        }
+        assert_eq!(
+            max_total_running.load(Ordering::SeqCst),
+            2,
+            "Starts on different hosts should run in parallel"
+        );
+    }
+
+    #[tokio::test]
+    async fn breaker_pauses_during_control_interface_outage() {
+        let breaker =
+            ControlInterfaceBreaker::new(3, Duration::from_millis(10), Duration::from_millis(40));
+        let health_checks = Arc::new(AtomicUsize::new(0));
+        let available = Arc::new(std::sync::atomic::AtomicBool::new(false));
+        let health_check = {
+            let health_checks = health_checks.clone();
+            let available = available.clone();
+            move || {
+                let health_checks = health_checks.clone();
+                let available = available.clone();
+                async move {
+                    health_checks.fetch_add(1, Ordering::SeqCst);
+                    available.load(Ordering::SeqCst)
+                }
+            }
+        };
+
+        // A few failures in a row aren't enough to pause
+        breaker.record_failure();
+        breaker.record_failure();
+        assert!(!breaker.is_open());
+        breaker.wait_until_healthy(health_check.clone()).await;
+        assert_eq!(health_checks.load(Ordering::SeqCst), 0);
+
+        // Every command failing to reach the control interface opens the breaker
+        breaker.record_failure();
+        assert!(breaker.is_open(), "Breaker should open after the threshold");
+
+        let waiting = tokio::spawn({
+            let breaker = breaker.clone();
+            async move { breaker.wait_until_healthy(health_check).await }
+        });
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        assert!(
+            !waiting.is_finished(),
+            "Worker should stay paused while the control interface is down"
+        );
+        let checks = health_checks.load(Ordering::SeqCst);
+        assert!(
+            (2..=8).contains(&checks),
+            "Health checks should back off instead of looping, got {checks} checks"
+        );
+
+        available.store(true, Ordering::SeqCst);
+        tokio::time::timeout(Duration::from_secs(1), waiting)
+            .await
+            .expect("Worker should resume once the control interface is back")
+            .unwrap();
+        assert!(!breaker.is_open(), "Breaker should close after recovering");
+    }
+
+    #[tokio::test]
+    async fn failed_command_publishes_failure_event() {
+        let publisher = RecorderPublisher::<CloudEvent> {
+            received: Arc::new(RwLock::new(Vec::new())),
+        };
+        let command = Command::ScaleComponent(ScaleComponent {
+            component_id: "component".to_string(),
+            host_id: "host".to_string(),
+            count: 1,
+            reference: "fakecloud.azurecr.io/echo:0.3.4".to_string(),
+            model_name: "model".to_string(),
+            ..Default::default()
+        });
+
+        let res = Ok(CtlResponse::error("failed to fetch component"));
+        publish_command_result(
+            &publisher,
+            "wadm.evt.default.command_executed",
+            command_executed(&command, &res),
+        )
+        .await
+        .expect("Should be able to publish result");
+
+        let received = publisher.received.read().await;
+        assert_eq!(received.len(), 1, "Should have published a single event");
+        let evt = Event::new(received[0].clone()).expect("Should be a valid event");
+        let Event::CommandExecuted(executed) = evt else {
+            panic!("Should have received a CommandExecuted event, got {evt:?}");
+        };
+        assert!(!executed.success, "Command should be marked as failed");
+        assert_eq!(executed.message, "failed to fetch component");
+        assert_eq!(executed.command_id, command.id());
+        assert_eq!(executed.command, command);
+    }
+
+    #[tokio::test]
+    async fn only_failures_and_link_successes_are_published_by_default() {
+        let nats = async_nats::ConnectOptions::new()
+            .retry_on_initial_connect()
+            .connect("127.0.0.1:1")
+            .await
+            .expect("Should be able to create a disconnected client");
+        let worker = CommandWorker::new(
+            wasmcloud_control_interface::ClientBuilder::new(nats).build(),
+            RecorderPublisher::<CloudEvent> {
+                received: Arc::new(RwLock::new(Vec::new())),
+            },
+            "wadm.evt.default.command_executed",
+        );
+        let scale = Command::ScaleComponent(ScaleComponent {
+            component_id: "component".to_string(),
+            host_id: "host".to_string(),
+            count: 1,
+            reference: "fakecloud.azurecr.io/echo:0.3.4".to_string(),
+            model_name: "model".to_string(),
+            ..Default::default()
+        });
+        let put_link = Command::PutLink(PutLink {
+            source_id: "component".to_string(),
+            target: "provider".to_string(),
+            name: "default".to_string(),
+            wit_namespace: "wasi".to_string(),
+            wit_package: "keyvalue".to_string(),
+            model_name: "model".to_string(),
+            ..Default::default()
+        });
+        let succeeded = Ok(CtlResponse::success("ok".to_string()));
+        let failed = Ok(CtlResponse::error("failed to fetch component"));
+
+        assert!(worker.should_publish(&command_executed(&scale, &failed)));
+        assert!(
+            !worker.should_publish(&command_executed(&scale, &succeeded)),
+            "Successful scale commands shouldn't be published by default"
+        );
+        assert!(
+            worker.should_publish(&command_executed(&put_link, &succeeded)),
+            "Successful link commands are needed to track link owners"
+        );
+
+        let worker = worker.with_success_results(true);
+        assert!(worker.should_publish(&command_executed(&scale, &succeeded)));
+    }
+
+    #[tokio::test]
+    async fn expired_commands_are_discarded() {
+        // Nothing is listening here, so any attempt to use the control interface fails
+        let nats = async_nats::ConnectOptions::new()
+            .retry_on_initial_connect()
+            .connect("127.0.0.1:1")
+            .await
+            .expect("Should be able to create a disconnected client");
+        let publisher = RecorderPublisher::<CloudEvent> {
+            received: Arc::new(RwLock::new(Vec::new())),
+        };
+        let worker = CommandWorker::new(
+            wasmcloud_control_interface::ClientBuilder::new(nats).build(),
+            publisher.clone(),
+            "wadm.evt.default.command_executed",
+        )
+        .with_command_ttl(Duration::from_secs(30));
+
+        let command = Command::ScaleComponent(ScaleComponent {
+            component_id: "component".to_string(),
+            host_id: "host".to_string(),
+            count: 1,
+            reference: "fakecloud.azurecr.io/echo:0.3.4".to_string(),
+            model_name: "model".to_string(),
+            ..Default::default()
+        });
+        tokio::time::timeout(
+            Duration::from_secs(1),
+            worker.do_work(ScopedMessage {
+                lattice_id: "default".to_string(),
+                inner: command,
+                acker: None,
+                ack_batcher: None,
+                published: Some(SystemTime::now() - Duration::from_secs(60)),
+            }),
+        )
+        .await
+        .expect("Expired command should be handled without waiting on the lattice")
+        .expect("Expired command should be acked");
+
+        assert!(
+            publisher.received.read().await.is_empty(),
+            "Expired command should not be executed"
+        );
+        assert!(
+            worker.expired_age(Some(SystemTime::now())).is_none(),
+            "Fresh commands should not be expired"
+        );
+        assert!(
+            worker.expired_age(None).is_none(),
+            "Commands without a publish time should not be expired"
+        );
+    }
+
+    #[tokio::test]
+    async fn retries_transient_control_interface_failures() {
+        let policy = RetryPolicy::new(4, Duration::from_millis(5));
+        // Stands in for the control interface, failing to respond a set number of times before
+        // returning the given response
+        let flaky_client = |failures: usize, response: CtlResponse<()>| {
+            let calls = Arc::new(AtomicUsize::new(0));
+            let call = {
+                let calls = calls.clone();
+                move || {
+                    let response = response.clone();
+                    let attempt = calls.fetch_add(1, Ordering::SeqCst);
+                    async move {
+                        if attempt < failures {
+                            Err::<_, CtlError>("no responders".into())
+                        } else {
+                            Ok(response)
+                        }
+                    }
+                }
+            };
+            (calls, call)
+        };
+
+        let (calls, call) = flaky_client(3, CtlResponse::success("ok".to_string()));
+        let res = policy
+            .retry(call)
+            .await
+            .expect("Should succeed after retrying");
+        assert!(res.succeeded());
+        assert_eq!(
+            calls.load(Ordering::SeqCst),
+            4,
+            "Should retry until success"
+        );
+
+        let (calls, call) = flaky_client(10, CtlResponse::success("ok".to_string()));
+        policy
+            .retry(call)
+            .await
+            .expect_err("Should give up after the max attempts");
+        assert_eq!(
+            calls.load(Ordering::SeqCst),
+            4,
+            "Should stop at the max attempts"
+        );
+
+        let (calls, call) = flaky_client(0, CtlResponse::error("component not found"));
+        let res = policy
+            .retry(call)
+            .await
+            .expect("Host should have responded");
+        assert!(!res.succeeded());
+        assert_eq!(
+            calls.load(Ordering::SeqCst),
+            1,
+            "Failures reported by a host should not be retried"
+        );
+    }
+
+    #[tokio::test]
+    async fn batched_scales_keep_order_per_component() {
+        let scale = |component_id: &str, count: u32| ScopedMessage {
+            lattice_id: "default".to_string(),
+            inner: Command::ScaleComponent(ScaleComponent {
+                component_id: component_id.to_string(),
+                host_id: "host".to_string(),
+                count,
+                reference: format!("fakecloud.azurecr.io/{component_id}:0.1.0"),
+                model_name: "model".to_string(),
+                ..Default::default()
+            }),
+            acker: None,
+            ack_batcher: None,
+            published: None,
+        };
+        let messages = vec![
+            scale("echo", 0),
+            scale("kvcounter", 2),
+            scale("echo", 1),
+            scale("httpclient", 1),
+        ];
+
+        let executed = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let running = Arc::new(AtomicUsize::new(0));
+        let max_running = Arc::new(AtomicUsize::new(0));
+        let results = run_batch(messages, 4, |message| {
+            let executed = executed.clone();
+            let running = running.clone();
+            let max_running = max_running.clone();
+            async move {
+                let Command::ScaleComponent(scale) = message.as_ref() else {
+                    panic!("Only scale commands are in the batch");
+                };
+                let now = running.fetch_add(1, Ordering::SeqCst) + 1;
+                max_running.fetch_max(now, Ordering::SeqCst);
+                // Make the stop slow so a reordered start would finish first
+                let delay = if scale.count == 0 { 50 } else { 10 };
+                tokio::time::sleep(Duration::from_millis(delay)).await;
+                running.fetch_sub(1, Ordering::SeqCst);
+                executed
+                    .lock()
+                    .unwrap()
+                    .push((scale.component_id.clone(), scale.count));
+                (scale.component_id.clone(), scale.count)
+            }
+        })
+        .await;
+
+        assert_eq!(
+            results,
+            vec![
+                ("echo".to_string(), 0),
+                ("kvcounter".to_string(), 2),
+                ("echo".to_string(), 1),
+                ("httpclient".to_string(), 1),
+            ],
+            "Results should be in the same order as the commands"
+        );
+        let echo = executed
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|(id, _)| id == "echo")
+            .map(|(_, count)| *count)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            echo,
+            vec![0, 1],
+            "Stop and start of the same component should not be reordered"
+        );
+        assert!(
+            max_running.load(Ordering::SeqCst) > 1,
+            "Scales of different components should run concurrently"
+        );
+    }
 }
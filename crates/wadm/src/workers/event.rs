@@ -1,10 +1,16 @@
 use std::collections::BTreeMap;
 use std::collections::{hash_map::Entry, HashMap, HashSet};
-
-use anyhow::Result;
-use tracing::{debug, instrument, trace, warn};
-use wadm_types::api::{ScalerStatus, Status, StatusInfo};
-use wasmcloud_control_interface::{ComponentDescription, ProviderDescription};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use anyhow::{Context, Result};
+use tokio::sync::{RwLock, Semaphore};
+use tracing::{debug, info, instrument, trace, warn};
+use wadm_types::{
+    api::{ScalerStatus, Status, StatusInfo, StatusType},
+    Manifest,
+};
+use wasmcloud_control_interface::{ComponentDescription, HostInventory, ProviderDescription};
 
 use crate::commands::Command;
 use crate::consumers::{
@@ -14,17 +20,93 @@ use crate::consumers::{
 use crate::events::*;
 use crate::publisher::Publisher;
 use crate::scaler::manager::{ScalerList, ScalerManager};
-use crate::storage::{Component, Host, Provider, ProviderStatus, Store, WadmComponentInfo};
+use crate::storage::{
+    record_status, update_cas, BufferedStore, CasError, Component, Host, Link as LinkState,
+    Provider, ProviderHostStatus, ProviderStatus, Store, StoreError, WadmComponentInfo,
+    CAS_RETRIES,
+};
 use crate::APP_SPEC_ANNOTATION;
 
 use super::event_helpers::*;
 
-pub struct EventWorker<StateStore, C: Clone, P: Clone> {
+/// Handles lattice events, updating state and running scalers. The scalers can read from a
+/// different store than state is written to, which is only used to bulk ingest events (see
+/// [`EventWorker::ingest_events`])
+pub struct EventWorker<StateStore, C: Clone, P: Clone, ScalerStore = StateStore> {
     store: StateStore,
     ctl_client: C,
     command_publisher: CommandPublisher<P>,
     status_publisher: StatusPublisher<P>,
-    scalers: ScalerManager<StateStore, P, C>,
+    scalers: ScalerManager<ScalerStore, P, C>,
+    deploy_conflict_policy: DeployConflictPolicy,
+    /// The most recently published version of each model that is waiting on a previous deploy,
+    /// along with when it was queued
+    queued_deploys: Arc<RwLock<HashMap<String, (ManifestPublished, Instant)>>>,
+    /// How long a version can be queued before it preempts the previous deploy anyway
+    deploy_queue_timeout: Duration,
+    deploy_events: Option<DeployEventPublisher<P>>,
+    /// The version of each deployed model that hasn't become ready yet
+    pending_deploys: Arc<RwLock<HashMap<String, String>>>,
+    /// The manifest each model's current scalers were built from, used to only rebuild the scalers
+    /// affected by a new version
+    deployed_manifests: Arc<RwLock<HashMap<String, Manifest>>>,
+    /// Whether to check host inventory before removing stopped instances from state
+    confirm_stops: bool,
+    /// Whether the host, provider and component updates from a heartbeat run at the same time
+    concurrent_heartbeats: bool,
+    /// Whether commands are only logged rather than executed
+    dry_run: bool,
+    /// The number of status transitions to keep in the history of each model, if enabled
+    status_history: Option<usize>,
+    /// The most scalers that can handle an event at the same time when running all scalers, if
+    /// limited
+    scaler_concurrency: Option<usize>,
+}
+
+/// The default for how long a version can be queued behind a previous deploy
+pub const DEFAULT_DEPLOY_QUEUE_TIMEOUT: Duration = Duration::from_secs(300);
+
+/// The note added to the status of every model while an [`EventWorker`] is in dry run mode
+const DRY_RUN_STATUS_MESSAGE: &str = "Dry run, commands are not executed";
+
+/// What an [`EventWorker`] does when a manifest is published while the previous version of the same
+/// model is still converging
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum DeployConflictPolicy {
+    /// Switch to the new version right away, cancelling any backoff from the previous version's
+    /// scalers
+    #[default]
+    Preempt,
+    /// Hold the new version until the previous deploy has converged, or until the deploy queue
+    /// timeout has passed (see [`EventWorker::with_deploy_queue_timeout`]), after which it preempts
+    /// the previous deploy. If more versions are published in the meantime, only the latest one is
+    /// deployed.
+    ///
+    /// Queued versions are only kept in memory. The manifest store always has the latest deployed
+    /// version though, so if wadm restarts while a version is queued, the scalers are rebuilt from
+    /// the queued version, as if it had preempted the previous deploy
+    Queue,
+}
+
+impl std::fmt::Display for DeployConflictPolicy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DeployConflictPolicy::Preempt => write!(f, "preempt"),
+            DeployConflictPolicy::Queue => write!(f, "queue"),
+        }
+    }
+}
+
+impl std::str::FromStr for DeployConflictPolicy {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "preempt" => Ok(DeployConflictPolicy::Preempt),
+            "queue" => Ok(DeployConflictPolicy::Queue),
+            _ => anyhow::bail!("unknown deploy conflict policy '{s}', expected preempt or queue"),
+        }
+    }
 }
 
 impl<StateStore, C, P> EventWorker<StateStore, C, P>
@@ -55,6 +137,235 @@ where
             command_publisher,
             status_publisher,
             scalers: manager,
+            deploy_conflict_policy: DeployConflictPolicy::default(),
+            queued_deploys: Arc::default(),
+            deploy_queue_timeout: DEFAULT_DEPLOY_QUEUE_TIMEOUT,
+            deploy_events: None,
+            pending_deploys: Arc::default(),
+            deployed_manifests: Arc::default(),
+            confirm_stops: false,
+            concurrent_heartbeats: false,
+            dry_run: false,
+            status_history: None,
+            scaler_concurrency: None,
+        }
+    }
+
+    /// Sets what happens when a manifest is published while the previous version is still
+    /// converging. Defaults to [`DeployConflictPolicy::Preempt`]
+    pub fn with_deploy_conflict_policy(mut self, policy: DeployConflictPolicy) -> Self {
+        self.deploy_conflict_policy = policy;
+        self
+    }
+
+    /// Sets how long a version can be queued behind a previous deploy that hasn't converged with
+    /// [`DeployConflictPolicy::Queue`] before it preempts the previous deploy anyway. Queued
+    /// versions are checked whenever an event is handled, so one can wait a little longer than
+    /// this. Defaults to [`DEFAULT_DEPLOY_QUEUE_TIMEOUT`]
+    pub fn with_deploy_queue_timeout(mut self, timeout: Duration) -> Self {
+        self.deploy_queue_timeout = timeout;
+        self
+    }
+
+    /// Publishes a [`ModelDeployed`] event with the given publisher the first time each deployed
+    /// version of a model becomes ready
+    pub fn with_deploy_events(mut self, publisher: DeployEventPublisher<P>) -> Self {
+        self.deploy_events = Some(publisher);
+        self
+    }
+
+    /// Sets whether stop events are confirmed against the inventory of the host before the stopped
+    /// component or provider is removed from state. Disabled by default
+    pub fn with_stop_confirmation(mut self, confirm_stops: bool) -> Self {
+        self.confirm_stops = confirm_stops;
+        self
+    }
+
+    /// Sets whether the host, provider and component state from a heartbeat are updated at the
+    /// same time rather than one after another. They touch different kinds of state, so this only
+    /// speeds up handling heartbeats from large hosts. Disabled by default
+    pub fn with_concurrent_heartbeats(mut self, concurrent: bool) -> Self {
+        self.concurrent_heartbeats = concurrent;
+        self
+    }
+
+    /// Sets whether this worker runs in dry run mode. State is still updated from events and
+    /// scalers still run and publish their status, but the commands they return are only logged
+    /// and published under [`DRY_RUN_SUBJECT_PREFIX`] instead of being executed. Model statuses
+    /// note that dry run is enabled. This also applies to the clones of the command publisher,
+    /// like the one used by the scaler manager. Disabled by default
+    pub fn with_dry_run(mut self, dry_run: bool) -> Self {
+        self.dry_run = dry_run;
+        self.command_publisher = self.command_publisher.with_dry_run(dry_run);
+        self
+    }
+
+    /// Keeps a history of the last `max_transitions` status transitions of each model in the store,
+    /// which can be fetched with [`status_history`](crate::storage::status_history). Disabled by
+    /// default
+    pub fn with_status_history(mut self, max_transitions: usize) -> Self {
+        self.status_history = Some(max_transitions);
+        self
+    }
+
+    /// Limits how many scalers can handle an event at the same time when an event has to be
+    /// handled by the scalers of every model, like when a host or provider stops. This keeps large
+    /// lattices from hitting the store and control interface with hundreds of requests at once.
+    /// The commands and errors from the scalers are combined the same way as without a limit.
+    /// Unlimited by default
+    pub fn with_scaler_concurrency(mut self, limit: usize) -> Self {
+        // A limit of 0 would never let any scaler run
+        self.scaler_concurrency = Some(limit.max(1));
+        self
+    }
+
+    /// Rebuilds state from a batch of events without running any scalers or publishing any
+    /// commands. Events are handled the same way as in [`Worker::do_work`], but all of the changes
+    /// are collected in memory and written to the store in bulk once the whole batch has been
+    /// handled, which is much faster than writing after every event when replaying a lot of them.
+    /// Events that don't change state, like published manifests, are skipped
+    #[instrument(level = "debug", skip(self, events))]
+    pub async fn ingest_events<'a>(
+        &self,
+        lattice_id: &str,
+        events: impl IntoIterator<Item = &'a Event>,
+    ) -> anyhow::Result<()> {
+        let worker = EventWorker {
+            store: BufferedStore::new(self.store.clone()),
+            ctl_client: self.ctl_client.clone(),
+            command_publisher: self.command_publisher.clone(),
+            status_publisher: self.status_publisher.clone(),
+            scalers: self.scalers.clone(),
+            deploy_conflict_policy: self.deploy_conflict_policy,
+            queued_deploys: Arc::default(),
+            deploy_queue_timeout: self.deploy_queue_timeout,
+            deploy_events: None,
+            pending_deploys: Arc::default(),
+            deployed_manifests: Arc::default(),
+            confirm_stops: self.confirm_stops,
+            concurrent_heartbeats: self.concurrent_heartbeats,
+            dry_run: self.dry_run,
+            status_history: self.status_history,
+            scaler_concurrency: self.scaler_concurrency,
+        };
+        let mut num_events = 0;
+        for event in events {
+            worker.update_state(lattice_id, event).await?;
+            num_events += 1;
+        }
+        trace!(%num_events, "Flushing state from ingested events");
+        worker.store.flush().await.map_err(anyhow::Error::from)
+    }
+
+    /// Loads the current hosts, components and providers of the lattice from the control interface
+    /// instead of waiting for every host to heartbeat, so a freshly started wadm can take over a
+    /// running lattice right away. The inventory of each host is applied the same way as a
+    /// heartbeat, so this can be run more than once and while events are being handled. Hosts that
+    /// stop before their inventory is fetched are skipped. Returns the number of hosts loaded
+    #[instrument(level = "info", skip(self))]
+    pub async fn bootstrap_lattice(&self, lattice_id: &str) -> anyhow::Result<usize> {
+        let host_ids = self
+            .ctl_client
+            .get_host_ids()
+            .await
+            .context("Unable to list the hosts in the lattice")?;
+        let inventories = futures::future::join_all(
+            host_ids
+                .iter()
+                .map(|host_id| self.ctl_client.get_inventory(host_id)),
+        )
+        .await;
+        let mut num_hosts = 0;
+        for (host_id, inventory) in host_ids.iter().zip(inventories) {
+            match inventory {
+                Ok(inventory) => {
+                    self.handle_host_heartbeat(lattice_id, &HostHeartbeat::from(&inventory))
+                        .await?;
+                    num_hosts += 1;
+                }
+                Err(e) => {
+                    warn!(error = %e, %host_id, "Unable to fetch host inventory, skipping host");
+                }
+            }
+        }
+        info!(%num_hosts, "Loaded lattice state from the control interface");
+        Ok(num_hosts)
+    }
+}
+
+// The state handlers only use the state store, so they can run with any store for the scalers
+impl<StateStore, C, P, ScalerStore> EventWorker<StateStore, C, P, ScalerStore>
+where
+    StateStore: Store + Send + Sync + Clone + 'static,
+    C: ClaimsSource
+        + InventorySource
+        + LinkSource
+        + ConfigSource
+        + SecretSource
+        + Clone
+        + Send
+        + Sync
+        + 'static,
+    P: Publisher + Clone + Send + Sync + 'static,
+{
+    /// Fetches the inventory of the given host if stop confirmation is enabled. Returns None if it
+    /// is disabled or the inventory couldn't be fetched, in which case the stop event is trusted
+    async fn stop_confirmation_inventory(&self, host_id: &str) -> Option<HostInventory> {
+        if !self.confirm_stops {
+            return None;
+        }
+        trace!(%host_id, "Fetching inventory to confirm stop");
+        match self.ctl_client.get_inventory(host_id).await {
+            Ok(inventory) => Some(inventory),
+            Err(e) => {
+                warn!(error = %e, %host_id, "Unable to fetch inventory to confirm stop, trusting the stop event");
+                None
+            }
+        }
+    }
+
+    /// Updates state for the given event without running any scalers
+    async fn update_state(&self, lattice_id: &str, event: &Event) -> anyhow::Result<()> {
+        match event {
+            Event::ComponentScaled(component) => {
+                self.handle_component_scaled(lattice_id, component).await
+            }
+            Event::HostHeartbeat(host) => self.handle_host_heartbeat(lattice_id, host).await,
+            Event::HostStarted(host) => self.handle_host_started(lattice_id, host).await,
+            Event::HostStopped(host) => self.handle_host_stopped(lattice_id, host).await,
+            Event::ProviderStarted(provider) => {
+                self.handle_provider_started(lattice_id, provider).await
+            }
+            Event::ProviderStopped(provider) => {
+                self.handle_provider_stopped(lattice_id, provider).await
+            }
+            Event::ProviderHealthCheckStatus(ProviderHealthCheckStatus { data }) => {
+                self.handle_provider_health_check(lattice_id, data, None)
+                    .await
+            }
+            Event::ProviderHealthCheckPassed(ProviderHealthCheckPassed { data }) => {
+                self.handle_provider_health_check(lattice_id, data, Some(false))
+                    .await
+            }
+            Event::ProviderHealthCheckFailed(ProviderHealthCheckFailed { data }) => {
+                self.handle_provider_health_check(lattice_id, data, Some(true))
+                    .await
+            }
+            Event::CommandExecuted(executed) => {
+                self.handle_command_executed(lattice_id, executed).await
+            }
+            Event::LinkdefDeleted(link) => self
+                .handle_linkdef_deleted(lattice_id, link)
+                .await
+                .map(|_| ()),
+            Event::ManifestPublished(_)
+            | Event::ManifestUnpublished(_)
+            | Event::LinkdefSet(_)
+            | Event::ConfigSet(_)
+            | Event::ConfigDeleted(_)
+            | Event::ProviderStartFailed(_)
+            | Event::ComponentScaleFailed(_)
+            | Event::HostReaped(_) => Ok(()),
         }
     }
 
@@ -71,64 +382,50 @@ where
         component: &ComponentScaled,
     ) -> anyhow::Result<()> {
         trace!("Scaling component in store");
-        debug!("Fetching current data for component");
-
-        // Update component count in the component state, adding to the state if it didn't exist or removing
-        // if the scale is down to zero.
-        let mut component_data = Component::from(component);
-        if let Some(mut current) = self
-            .store
-            .get::<Component>(lattice_id, &component.component_id)
-            .await?
-        {
-            trace!(component = ?current, "Found existing component data");
 
-            match current.instances.get_mut(&component.host_id) {
-                // If the component is running and is now scaled down to zero, remove it
-                Some(current_instances) if component.max_instances == 0 => {
-                    current_instances.remove(&component.annotations);
-                }
-                // If a component is already running on a host, update the running count to the scaled max_instances value
-                Some(current_instances) => {
-                    current_instances.replace(WadmComponentInfo {
-                        count: component.max_instances,
-                        annotations: component.annotations.clone(),
-                    });
-                }
-                // Component is not running and now scaled to zero, no action required. This can happen if we
-                // update the state before we receive the ComponentScaled event
-                None if component.max_instances == 0 => (),
-                // If a component isn't running yet, add it with the scaled max_instances value
-                None => {
-                    current.instances.insert(
-                        component.host_id.clone(),
-                        HashSet::from([WadmComponentInfo {
-                            count: component.max_instances,
-                            annotations: component.annotations.clone(),
-                        }]),
-                    );
-                }
+        // A partially failed stop can leave instances running, so reconcile against what the host
+        // reports is running rather than removing the component from state
+        let still_running;
+        let component = match self
+            .stop_confirmation_inventory(&component.host_id)
+            .await
+            .filter(|_| component.max_instances == 0)
+            .and_then(|inventory| {
+                inventory
+                    .components()
+                    .iter()
+                    .find(|c| c.id() == component.component_id && c.max_instances() > 0)
+                    .map(|c| c.max_instances() as usize)
+            }) {
+            Some(running) => {
+                warn!(
+                    running,
+                    "Component was scaled to zero but the host is still running it, keeping running instances in state"
+                );
+                still_running = ComponentScaled {
+                    max_instances: running,
+                    ..component.clone()
+                };
+                &still_running
             }
+            None => component,
+        };
 
-            // If we stopped the last instance on a host, remove the host from the component data
-            if current
-                .instances
-                .get(&component.host_id)
-                .is_some_and(|instances| instances.is_empty())
-            {
-                current.instances.remove(&component.host_id);
-            }
+        debug!("Fetching current data for component");
 
-            // Take the updated counts and store them in the component data
-            component_data.instances = current.instances;
-        };
+        // Update component count in the component state, adding to the state if it didn't exist or removing
+        // if the scale is down to zero. This is retried if the component is written in the meantime
+        let updated = update_cas(
+            &self.store,
+            lattice_id,
+            &component.component_id,
+            |current| scaled_component_state(current, component),
+        )
+        .await?;
 
         // Update component count in the host state, removing the component if the scale is zero
-        if let Some(mut host) = self
-            .store
-            .get::<Host>(lattice_id, &component.host_id)
-            .await?
-        {
+        update_cas(&self.store, lattice_id, &component.host_id, |host| {
+            let mut host: Host = host?;
             trace!(host = ?host, "Found existing host data");
 
             if component.max_instances == 0 {
@@ -139,22 +436,17 @@ where
                     .and_modify(|count| *count = component.max_instances)
                     .or_insert(component.max_instances);
             }
+            Some(host)
+        })
+        .await?;
 
-            self.store
-                .store(lattice_id, host.id.to_owned(), host)
-                .await?
-        }
-
-        if component_data.instances.is_empty() {
+        if updated.is_none() {
             self.store
                 .delete::<Component>(lattice_id, &component.component_id)
                 .await
                 .map_err(anyhow::Error::from)
         } else {
-            self.store
-                .store(lattice_id, component.component_id.clone(), component_data)
-                .await
-                .map_err(anyhow::Error::from)
+            Ok(())
         }
     }
 
@@ -164,25 +456,118 @@ where
         lattice_id: &str,
         host: &HostHeartbeat,
     ) -> anyhow::Result<()> {
-        debug!("Updating store with current host heartbeat information");
-        let host_data = Host::from(host);
-        self.store
-            .store(lattice_id, host.host_id.clone(), host_data)
-            .await?;
-
-        // NOTE: We can return an error here and then nack because we'll just reupdate the host data
-        // with the exact same host heartbeat entry. There is no possibility of a duplicate
-        self.heartbeat_provider_update(lattice_id, host, &host.providers)
-            .await?;
+        let host_update = async {
+            debug!("Updating store with current host heartbeat information");
+            // Merging annotations can fetch the host inventory, so this can't use `update_cas` and
+            // retries the merge by hand if the host is written in the meantime
+            let mut attempt = 0;
+            loop {
+                let mut host_data = Host::from(host);
+                // Heartbeats don't always carry provider annotations, so make sure we don't lose
+                // the annotations we already know about when overwriting the host
+                let (current, revision) = self
+                    .store
+                    .get_with_revision::<Host>(lattice_id, &host.host_id)
+                    .await?;
+                let current_providers =
+                    current.map(|current| current.providers).unwrap_or_default();
+                host_data.providers = self
+                    .merge_provider_annotations(
+                        &host.host_id,
+                        host_data.providers,
+                        current_providers,
+                    )
+                    .await;
+                match self
+                    .store
+                    .store_cas(lattice_id, host.host_id.clone(), host_data, revision)
+                    .await
+                {
+                    Ok(_) => break,
+                    Err(CasError::Conflict { .. }) if attempt < CAS_RETRIES => {
+                        debug!("Host changed while applying heartbeat, retrying");
+                        attempt += 1;
+                    }
+                    Err(e) => return Err(e.into()),
+                }
+            }
+            anyhow::Ok(())
+        };
+        // NOTE: We can return an error from any of these and then nack because we'll just reupdate
+        // the data with the exact same host heartbeat entry. There is no possibility of a duplicate
+        let provider_update = self.heartbeat_provider_update(lattice_id, host, &host.providers);
+        let component_update = self.heartbeat_component_update(lattice_id, host, &host.components);
 
-        // NOTE: We can return an error here and then nack because we'll just reupdate the host data
-        // with the exact same host heartbeat entry. There is no possibility of a duplicate
-        self.heartbeat_component_update(lattice_id, host, &host.components)
-            .await?;
+        if self.concurrent_heartbeats {
+            tokio::try_join!(host_update, provider_update, component_update)?;
+        } else {
+            host_update.await?;
+            provider_update.await?;
+            component_update.await?;
+        }
 
         Ok(())
     }
 
+    /// Merges the annotations of the providers from a heartbeat with the annotations of the
+    /// providers we already had stored for the host. The heartbeat carries the inventory
+    /// descriptions of the providers, so any annotations it has are authoritative and replace the
+    /// stored ones. Otherwise the stored annotations are kept, and any provider that we haven't
+    /// seen before gets its annotations from the host inventory
+    async fn merge_provider_annotations(
+        &self,
+        host_id: &str,
+        heartbeat_providers: HashSet<ProviderInfo>,
+        mut current_providers: HashSet<ProviderInfo>,
+    ) -> HashSet<ProviderInfo> {
+        let mut inventory_annotations: Option<HashMap<String, BTreeMap<String, String>>> = None;
+        let mut merged = HashSet::with_capacity(heartbeat_providers.len());
+        for mut provider in heartbeat_providers {
+            match current_providers.take(&provider) {
+                Some(current) if provider.annotations.is_empty() => {
+                    provider.annotations = current.annotations;
+                }
+                // Replace rather than merge so annotations removed from the provider don't linger
+                Some(_) => (),
+                None if provider.annotations.is_empty() => {
+                    if inventory_annotations.is_none() {
+                        trace!("Fetching inventory for annotations of newly seen providers");
+                        inventory_annotations = Some(
+                            match self.ctl_client.get_inventory(host_id).await {
+                                Ok(inventory) => inventory
+                                    .providers()
+                                    .iter()
+                                    .map(|p| {
+                                        (
+                                            p.id().to_owned(),
+                                            p.annotations()
+                                                .map(ToOwned::to_owned)
+                                                .map(BTreeMap::from_iter)
+                                                .unwrap_or_default(),
+                                        )
+                                    })
+                                    .collect(),
+                                Err(e) => {
+                                    warn!(error = %e, "Unable to fetch inventory for provider annotations");
+                                    HashMap::new()
+                                }
+                            },
+                        );
+                    }
+                    if let Some(annotations) = inventory_annotations
+                        .as_ref()
+                        .and_then(|all| all.get(&provider.provider_id))
+                    {
+                        provider.annotations = annotations.clone();
+                    }
+                }
+                None => (),
+            }
+            merged.insert(provider);
+        }
+        merged
+    }
+
     #[instrument(level = "debug", skip(self, host), fields(host_id = %host.id))]
     async fn handle_host_started(
         &self,
@@ -216,22 +601,21 @@ where
             }
         };
 
-        trace!("Fetching components from store to remove stopped instances");
-        let all_components = self.store.list::<Component>(lattice_id).await?;
+        trace!("Fetching components running on the host from store to remove stopped instances");
+        let host_components = self
+            .store
+            .get_many::<Component, _>(lattice_id, current.components.keys().cloned())
+            .await?;
 
         #[allow(clippy::type_complexity)]
         let (components_to_update, components_to_delete): (
             Vec<(String, Component)>,
             Vec<(String, Component)>,
-        ) = all_components
+        ) = host_components
             .into_iter()
-            .filter_map(|(id, mut component)| {
-                if current.components.contains_key(&id) {
-                    component.instances.remove(&current.id);
-                    Some((id, component))
-                } else {
-                    None
-                }
+            .map(|(id, mut component)| {
+                component.instances.remove(&current.id);
+                (id, component)
             })
             .partition(|(_, component)| !component.instances.is_empty());
         trace!("Storing updated components in store");
@@ -247,8 +631,17 @@ where
             )
             .await?;
 
-        trace!("Fetching providers from store to remove stopped instances");
-        let all_providers = self.store.list::<Provider>(lattice_id).await?;
+        trace!("Fetching providers running on the host from store to remove stopped instances");
+        let host_providers = self
+            .store
+            .get_many::<Provider, _>(
+                lattice_id,
+                current
+                    .providers
+                    .iter()
+                    .map(|info| info.provider_id.clone()),
+            )
+            .await?;
 
         #[allow(clippy::type_complexity)]
         let (providers_to_update, providers_to_delete): (Vec<(String, Provider)>, Vec<(String, Provider)>) = current
@@ -257,9 +650,9 @@ where
             .filter_map(|info| {
                 let key = info.provider_id;
                 // NOTE: We can do this without cloning, but it led to some confusing code involving
-                // `remove` from the owned `all_providers` map. This is more readable at the expense of
-                // a clone for few providers
-                match all_providers.get(&key).cloned() {
+                // `remove` from the owned `host_providers` map. This is more readable at the
+                // expense of a clone for few providers
+                match host_providers.get(&key).cloned() {
                     // If we successfully remove the host, map it to the right type, otherwise we can
                     // continue onward
                     Some(mut prov) => prov.hosts.remove(&host.id).map(|_| (key, prov)),
@@ -308,64 +701,83 @@ where
     ) -> anyhow::Result<()> {
         debug!("Handling provider started event");
         let id = &provider.provider_id;
+        // Provider started events don't include the capabilities from the provider's claims
+        let capabilities = match self.ctl_client.get_claims().await {
+            Ok(mut claims) => claims
+                .remove(id)
+                .map(|claims| claims.capabilities)
+                .unwrap_or_default(),
+            Err(e) => {
+                warn!(error = %e, "Unable to fetch claims for provider capabilities");
+                Vec::new()
+            }
+        };
         trace!("Fetching current data from store");
         let mut needs_host_update = false;
-        let provider_data = if let Some(mut current) =
-            self.store.get::<Provider>(lattice_id, id).await?
-        {
-            // Using the entry api is a bit more efficient because we do a single key lookup
-            let mut prov = match current.hosts.entry(provider.host_id.clone()) {
-                Entry::Occupied(_) => {
-                    trace!("Found host entry for the provider already in store. Will not update");
-                    current
+        debug!("Storing updated provider in store");
+        update_cas(&self.store, lattice_id, id, |current: Option<Provider>| {
+            needs_host_update = false;
+            let provider_data = if let Some(mut current) = current {
+                // Using the entry api is a bit more efficient because we do a single key lookup
+                let mut prov = match current.hosts.entry(provider.host_id.clone()) {
+                    // Hosts that can run more than one instance of a provider send a started event
+                    // for each instance
+                    Entry::Occupied(mut entry) => {
+                        entry.get_mut().instances += 1;
+                        trace!(
+                            instances = entry.get().instances,
+                            "Found host entry for the provider already in store, counting another instance"
+                        );
+                        current
+                    }
+                    Entry::Vacant(entry) => {
+                        entry.insert(ProviderHostStatus::from(ProviderStatus::default()));
+                        needs_host_update = true;
+                        current
+                    }
+                };
+                // Update missing fields if they exist. Right now if we just discover a provider
+                // from health check, these will be empty
+                if prov.issuer.is_empty() || prov.reference.is_empty() {
+                    let new_prov = Provider::from(provider);
+                    prov.issuer = new_prov.issuer;
+                    prov.reference = new_prov.reference;
                 }
-                Entry::Vacant(entry) => {
-                    entry.insert(ProviderStatus::default());
-                    needs_host_update = true;
-                    current
+                if prov.capabilities.is_empty() {
+                    prov.capabilities = capabilities.clone();
                 }
+                prov
+            } else {
+                trace!("No current provider found in store");
+                let mut prov = Provider::from(provider);
+                prov.hosts = HashMap::from([(
+                    provider.host_id.clone(),
+                    ProviderHostStatus::from(ProviderStatus::default()),
+                )]);
+                prov.capabilities = capabilities.clone();
+                needs_host_update = true;
+                prov
             };
-            // Update missing fields if they exist. Right now if we just discover a provider from
-            // health check, these will be empty
-            if prov.issuer.is_empty() || prov.reference.is_empty() {
-                let new_prov = Provider::from(provider);
-                prov.issuer = new_prov.issuer;
-                prov.reference = new_prov.reference;
-            }
-            prov
-        } else {
-            trace!("No current provider found in store");
-            let mut prov = Provider::from(provider);
-            prov.hosts = HashMap::from([(provider.host_id.clone(), ProviderStatus::default())]);
-            needs_host_update = true;
-            prov
-        };
+            Some(provider_data)
+        })
+        .await?;
 
         // Insert provider into host map
-        if let (Some(mut host), true) = (
-            self.store
-                .get::<Host>(lattice_id, &provider.host_id)
-                .await?,
-            needs_host_update,
-        ) {
-            trace!(host = ?host, "Found existing host data");
-
-            host.providers.replace(ProviderInfo {
-                provider_id: id.to_owned(),
-                provider_ref: provider.image_ref.to_owned(),
-                annotations: provider.annotations.to_owned(),
-            });
-
-            self.store
-                .store(lattice_id, host.id.to_owned(), host)
-                .await?
+        if needs_host_update {
+            update_cas(&self.store, lattice_id, &provider.host_id, |host| {
+                let mut host: Host = host?;
+                trace!(host = ?host, "Found existing host data");
+
+                host.providers.replace(ProviderInfo {
+                    provider_id: id.to_owned(),
+                    provider_ref: provider.image_ref.to_owned(),
+                    annotations: provider.annotations.to_owned(),
+                });
+                Some(host)
+            })
+            .await?;
         }
-
-        debug!("Storing updated provider in store");
-        self.store
-            .store(lattice_id, id.to_owned(), provider_data)
-            .await
-            .map_err(anyhow::Error::from)
+        Ok(())
     }
 
     #[instrument(
@@ -382,50 +794,92 @@ where
     ) -> anyhow::Result<()> {
         debug!("Handling provider stopped event");
         let id = &provider.provider_id;
-        trace!("Fetching current data from store");
-
-        // Remove provider from host map
-        if let Some(mut host) = self
+        // The inventory of a host only says whether a provider is running, so it can only confirm
+        // the stop of the last instance of a provider on the host
+        let multiple_instances = self
             .store
-            .get::<Host>(lattice_id, &provider.host_id)
+            .get::<Provider>(lattice_id, id)
             .await?
+            .and_then(|current| current.hosts.get(&provider.host_id).map(|h| h.instances))
+            .is_some_and(|instances| instances > 1);
+        if !multiple_instances
+            && self
+                .stop_confirmation_inventory(&provider.host_id)
+                .await
+                .is_some_and(|inventory| inventory.providers().iter().any(|p| p.id() == id))
         {
-            trace!(host = ?host, "Found existing host data");
-
-            host.providers.remove(&ProviderInfo {
-                provider_id: provider.provider_id.to_owned(),
-                // We do not hash based on provider reference, so it can be blank here
-                provider_ref: "".to_string(),
-                // We don't have this information, nor do we need it since we don't hash based
-                // on annotations
-                annotations: BTreeMap::default(),
-            });
-
-            self.store
-                .store(lattice_id, host.id.to_owned(), host)
-                .await?
+            warn!(
+                host_id = %provider.host_id,
+                "Provider stopped but the host is still running it, keeping it in state"
+            );
+            return Ok(());
         }
+        trace!("Fetching current data from store");
 
-        if let Some(mut current) = self.store.get::<Provider>(lattice_id, id).await? {
-            if current.hosts.remove(&provider.host_id).is_none() {
-                trace!(host_id = %provider.host_id, "Did not find host entry in provider");
-                return Ok(());
+        let mut remove_provider = false;
+        // Whether the last instance on the host stopped. This is also the case if we didn't know
+        // about the provider at all, so the host is always cleaned up
+        let mut removed_from_host = true;
+        update_cas(&self.store, lattice_id, id, |current: Option<Provider>| {
+            remove_provider = false;
+            removed_from_host = true;
+            let Some(mut current) = current else {
+                trace!("No current provider found in store");
+                return None;
+            };
+            match current.hosts.entry(provider.host_id.clone()) {
+                Entry::Occupied(mut entry) if entry.get().instances > 1 => {
+                    entry.get_mut().instances -= 1;
+                    trace!(
+                        instances = entry.get().instances,
+                        "Instance of provider stopped, others are still running on the host"
+                    );
+                    removed_from_host = false;
+                    return Some(current);
+                }
+                Entry::Occupied(entry) => {
+                    entry.remove();
+                }
+                Entry::Vacant(_) => {
+                    trace!(host_id = %provider.host_id, "Did not find host entry in provider");
+                    return None;
+                }
             }
             if current.hosts.is_empty() {
-                debug!("Provider is no longer running on any hosts. Removing from store");
-                self.store
-                    .delete::<Provider>(lattice_id, id)
-                    .await
-                    .map_err(anyhow::Error::from)
-            } else {
-                debug!("Storing updated provider");
-                self.store
-                    .store(lattice_id, id.to_owned(), current)
-                    .await
-                    .map_err(anyhow::Error::from)
+                remove_provider = true;
+                return None;
             }
+            debug!("Storing updated provider");
+            Some(current)
+        })
+        .await?;
+
+        // Remove provider from host map
+        if removed_from_host {
+            update_cas(&self.store, lattice_id, &provider.host_id, |host| {
+                let mut host: Host = host?;
+                trace!(host = ?host, "Found existing host data");
+
+                host.providers.remove(&ProviderInfo {
+                    provider_id: provider.provider_id.to_owned(),
+                    // We do not hash based on provider reference, so it can be blank here
+                    provider_ref: "".to_string(),
+                    // We don't have this information, nor do we need it since we don't hash based
+                    // on annotations
+                    annotations: BTreeMap::default(),
+                });
+                Some(host)
+            })
+            .await?;
+        }
+
+        if remove_provider {
+            debug!("Provider is no longer running on any hosts. Removing from store");
+            self.store
+                .delete::<Provider>(lattice_id, id)
+                .await
+                .map_err(anyhow::Error::from)
         } else {
-            trace!("No current provider found in store");
             Ok(())
         }
     }
@@ -458,20 +912,26 @@ where
             }
         };
 
-        let status = match (current.hosts.get(host_id), failed) {
+        let entry = current.hosts.entry(host_id.to_owned()).or_default();
+        let status = match (&entry.status, failed) {
             // If the provider status changed from when we last saw it, modify status
             (_, Some(true)) => Some(ProviderStatus::Failed),
             (_, Some(false)) => Some(ProviderStatus::Running),
-            // If the provider is pending or we missed the initial start and we get a health check
-            // status, assume it's running fine.
-            (Some(ProviderStatus::Pending) | None, None) => Some(ProviderStatus::Running),
+            // If the provider is pending, went stale or we missed the initial start and we get a
+            // health check status, assume it's running fine. Entries that were just added are
+            // pending
+            (ProviderStatus::Pending | ProviderStatus::Stale, None) => {
+                Some(ProviderStatus::Running)
+            }
             _ => None,
         };
 
         if let Some(status) = status {
             debug!("Updating store with current status");
-            current.hosts.insert(host_id.to_owned(), status);
+            entry.status = status;
         }
+        // Any health check keeps the provider from being considered stale on this host
+        entry.last_health_check = Some(chrono::Utc::now());
 
         // TODO(thomastaylor312): Once we are able to fetch refmaps from the ctl client, we should
         // make it update any empty references with the data from the refmap
@@ -505,13 +965,26 @@ where
                     // Construct modified Component with new instances included
                     let mut new_instances = component.instances.clone();
                     new_instances.insert(host_id.to_owned(), instance);
+                    let mut host_references = component.host_references.clone();
+                    host_references.insert(
+                        host_id.to_owned(),
+                        component_description.image_ref().to_owned(),
+                    );
                     let component = Component {
                         instances: new_instances,
+                        host_references,
                         reference: component_description.image_ref().into(),
                         name: component_description
                             .name()
                             .unwrap_or(&component.name)
                             .into(),
+                        // Components added from scale events don't have their capabilities yet
+                        capabilities: match claims.get(component_description.id()) {
+                            Some(claim) if component.capabilities.is_empty() => {
+                                claim.capabilities.clone()
+                            }
+                            _ => component.capabilities.clone(),
+                        },
                         ..component.clone()
                     };
 
@@ -525,6 +998,11 @@ where
                             issuer: claim.issuer.to_owned(),
                             instances: HashMap::from_iter([(host_id.to_owned(), instance)]),
                             reference: component_description.image_ref().into(),
+                            host_references: HashMap::from_iter([(
+                                host_id.to_owned(),
+                                component_description.image_ref().to_owned(),
+                            )]),
+                            capabilities: claim.capabilities.clone(),
                         },
                     )
                 } else {
@@ -538,6 +1016,11 @@ where
                             issuer: "".to_owned(),
                             instances: HashMap::from_iter([(host_id.to_owned(), instance)]),
                             reference: component_description.image_ref().into(),
+                            host_references: HashMap::from_iter([(
+                                host_id.to_owned(),
+                                component_description.image_ref().to_owned(),
+                            )]),
+                            capabilities: Vec::new(),
                         },
                     )
                 }
@@ -552,8 +1035,16 @@ where
         host: &HostHeartbeat,
         inventory_components: &Vec<ComponentDescription>,
     ) -> anyhow::Result<()> {
-        debug!("Fetching current component state");
-        let components = self.store.list::<Component>(lattice_id).await?;
+        debug!("Fetching current state of the components on the host");
+        let components = self
+            .store
+            .get_many::<Component, _>(
+                lattice_id,
+                inventory_components
+                    .iter()
+                    .map(|component| component.id().to_owned()),
+            )
+            .await?;
 
         // Compare stored Components to the "true" list on this host, updating stored
         // Components when they differ from the authoratative heartbeat
@@ -565,8 +1056,13 @@ where
                     // NOTE(brooksmtownsend): This code maps the component to a boolean indicating if it's up-to-date with the heartbeat or not.
                     // If the component matches what the heartbeat says, we return None, otherwise we return Some(component_description).
                     .map(|component| {
-                        // If the stored reference isn't what we receive on the heartbeat, update
-                        component_description.image_ref() == component.reference
+                        // If the stored reference for this host isn't what we receive on the
+                        // heartbeat, update
+                        component_description.image_ref()
+                            == component
+                                .host_references
+                                .get(&host.host_id)
+                                .unwrap_or(&component.reference)
                             && component
                                 .instances
                                 .get(&host.host_id)
@@ -582,8 +1078,7 @@ where
                                             .cloned()
                                             .map(|a| a.into_iter().collect())
                                             .unwrap_or_default();
-                                    store_instances.get(&annotations).map_or(
-                                        false,
+                                    store_instances.get(&annotations).is_some_and(
                                         |store_instance| {
                                             component_description.max_instances() as usize
                                                 == store_instance.count
@@ -623,6 +1118,8 @@ where
         inventory_providers: &Vec<ProviderDescription>,
     ) -> anyhow::Result<()> {
         debug!("Fetching current provider state");
+        // Unlike components, every provider is needed here so providers that still list this host
+        // but have drifted out of its inventory can be found
         let providers = self.store.list::<Provider>(lattice_id).await?;
         let providers_to_update = inventory_providers.iter().filter_map(|info| {
             // NOTE: We can do this without cloning, but it led to some confusing code involving
@@ -640,7 +1137,7 @@ where
                         has_changes = true;
                     }
                     if let Entry::Vacant(entry) = prov.hosts.entry(heartbeat.host_id.clone()) {
-                        entry.insert(ProviderStatus::default());
+                        entry.insert(ProviderHostStatus::from(ProviderStatus::default()));
                         has_changes = true;
                     }
                     if has_changes {
@@ -656,7 +1153,11 @@ where
                         info.id().to_string(),
                         Provider {
                             id: info.id().to_string(),
-                            hosts: [(heartbeat.host_id.clone(), ProviderStatus::default())].into(),
+                            hosts: [(
+                                heartbeat.host_id.clone(),
+                                ProviderHostStatus::from(ProviderStatus::default()),
+                            )]
+                            .into(),
                             name: info.name().map(String::from).unwrap_or_default(),
                             reference: info.image_ref().map(String::from).unwrap_or_default(),
                             ..Default::default()
@@ -666,36 +1167,283 @@ where
             }
         });
 
+        // The heartbeat inventory is authoritative, so any provider that still lists this host
+        // but isn't in the inventory has drifted (usually from a missed stop event) and needs
+        // the host removed. Providers left without any hosts are no longer running anywhere
+        let (drifted_to_delete, drifted_to_update): (Vec<_>, Vec<_>) = providers
+            .iter()
+            .filter(|(id, prov)| {
+                prov.hosts.contains_key(&heartbeat.host_id)
+                    && !inventory_providers.iter().any(|info| info.id() == *id)
+            })
+            .map(|(id, prov)| {
+                let mut prov = prov.clone();
+                prov.hosts.remove(&heartbeat.host_id);
+                (id.to_owned(), prov)
+            })
+            .partition(|(_, prov)| prov.hosts.is_empty());
+        if !drifted_to_delete.is_empty() || !drifted_to_update.is_empty() {
+            debug!(
+                removed = drifted_to_delete.len() + drifted_to_update.len(),
+                "Repairing providers that listed host but were missing from its inventory"
+            );
+        }
+
         trace!("Updating providers with new status from host");
         self.store
-            .store_many(lattice_id, providers_to_update)
+            .store_many(lattice_id, providers_to_update.chain(drifted_to_update))
             .await?;
 
+        if !drifted_to_delete.is_empty() {
+            trace!("Removing providers that are no longer running on any host");
+            self.store
+                .delete_many::<Provider, _, _>(
+                    lattice_id,
+                    drifted_to_delete.into_iter().map(|(id, _)| id),
+                )
+                .await?;
+        }
+
         Ok(())
     }
 
-    #[instrument(level = "debug", skip(self, data), fields(name = %data.manifest.metadata.name))]
-    async fn handle_manifest_published(
+    /// Records which model owns a link once a link command has been accepted by the lattice, so
+    /// link scalers from other models can tell when they conflict with it
+    #[instrument(level = "debug", skip(self, executed), fields(command_id = %executed.command_id))]
+    async fn handle_command_executed(
         &self,
         lattice_id: &str,
-        data: &ManifestPublished,
+        executed: &CommandExecuted,
     ) -> anyhow::Result<()> {
-        debug!(name = %data.manifest.metadata.name, "Handling published manifest");
-
-        let old_scalers = self
-            .scalers
-            .remove_raw_scalers(&data.manifest.metadata.name)
-            .await;
-        let scalers = self.scalers.scalers_for_manifest(&data.manifest);
+        if !executed.success {
+            return Ok(());
+        }
+        match &executed.command {
+            Command::PutLink(put) => {
+                let link = LinkState::from(put);
+                let key = LinkState::key(
+                    &link.source_id,
+                    &link.wit_namespace,
+                    &link.wit_package,
+                    &link.name,
+                );
+                trace!(%key, model_name = %link.model_name, "Recording link owner");
+                self.store.store(lattice_id, key, link).await?;
+            }
+            Command::DeleteLink(delete) => {
+                let key = LinkState::key(
+                    &delete.source_id,
+                    &delete.wit_namespace,
+                    &delete.wit_package,
+                    &delete.link_name,
+                );
+                // Only the owner removing a link releases it, otherwise another model could
+                // release a link it never owned
+                let owned = self
+                    .store
+                    .get::<LinkState>(lattice_id, &key)
+                    .await?
+                    .is_some_and(|link| link.model_name == delete.model_name);
+                if owned {
+                    trace!(%key, "Removing link owner");
+                    self.store.delete::<LinkState>(lattice_id, &key).await?;
+                }
+            }
+            _ => (),
+        }
+        Ok(())
+    }
 
-        // Refresh the snapshot data before cleaning up and/or adding scalers
-        self.scalers.refresh_data().await?;
-        let cleanup_commands = if let Some(old_scalers) = old_scalers {
-            // This relies on the idea that an ID is a unique identifier for a scaler, and any
-            // change in the ID is indicative of the fact that the scaler is outdated and should be cleaned up.
-            let (_updated_component, outdated_component): (ScalerList, ScalerList) = old_scalers
-                .into_iter()
-                .partition(|old| scalers.iter().any(|new| new.id() == old.id()));
+    /// Removes a deleted link from state, returning the name of the model that put it, if it was
+    /// put by wadm
+    #[instrument(level = "debug", skip(self, link), fields(source_id = %link.source_id, name = %link.name))]
+    async fn handle_linkdef_deleted(
+        &self,
+        lattice_id: &str,
+        link: &LinkdefDeleted,
+    ) -> anyhow::Result<Option<String>> {
+        debug!("Handling link deleted event");
+        let key = LinkState::key(
+            &link.source_id,
+            &link.wit_namespace,
+            &link.wit_package,
+            &link.name,
+        );
+        let owner = self
+            .store
+            .get::<LinkState>(lattice_id, &key)
+            .await?
+            .map(|link| link.model_name);
+        self.store.delete::<LinkState>(lattice_id, &key).await?;
+        Ok(owner)
+    }
+}
+
+impl<StateStore, C, P> EventWorker<StateStore, C, P>
+where
+    StateStore: Store + Send + Sync + Clone + 'static,
+    C: ClaimsSource
+        + InventorySource
+        + LinkSource
+        + ConfigSource
+        + SecretSource
+        + Clone
+        + Send
+        + Sync
+        + 'static,
+    P: Publisher + Clone + Send + Sync + 'static,
+{
+    #[instrument(level = "debug", skip(self, data), fields(name = %data.manifest.metadata.name))]
+    async fn handle_manifest_published(
+        &self,
+        lattice_id: &str,
+        data: &ManifestPublished,
+    ) -> anyhow::Result<()> {
+        debug!(name = %data.manifest.metadata.name, "Handling published manifest");
+        let name = &data.manifest.metadata.name;
+        // A manifest written for a newer wadm could use fields this version doesn't understand, so
+        // refuse to deploy it at all rather than deploying whatever we managed to make of it
+        let running = semver::Version::parse(env!("CARGO_PKG_VERSION"))
+            .expect("wadm's own version should be valid semver");
+        if let Err(e) = check_min_wadm_version(&data.manifest, &running) {
+            warn!(error = %e, "Refusing to deploy manifest");
+            let info = StatusInfo::failed(&e.to_string());
+            self.record_status_history(lattice_id, name, &info).await;
+            if let Err(e) = self
+                .status_publisher
+                .publish_status(name, Status::new(info, Vec::new()))
+                .await
+            {
+                warn!("Failed to set manifest status: {e:}");
+            }
+            return Ok(());
+        }
+        if self.deploy_conflict_policy == DeployConflictPolicy::Queue
+            && self.is_deploy_in_flight(name).await
+        {
+            info!(version = %data.manifest.version(), "Previous deploy is still converging, queueing new version");
+            self.queued_deploys
+                .write()
+                .await
+                .entry(name.to_owned())
+                // Waiting starts with the first queued version, so a stream of new versions can't
+                // hold off deploying forever
+                .and_modify(|(queued, _)| *queued = data.clone())
+                .or_insert_with(|| (data.clone(), Instant::now()));
+            return Ok(());
+        }
+        // Anything queued is superseded by this version
+        self.queued_deploys.write().await.remove(name);
+        self.deploy_manifest(lattice_id, data).await
+    }
+
+    /// Returns true if any of the scalers for the given model are still reconciling
+    async fn is_deploy_in_flight(&self, name: &str) -> bool {
+        let Some(scalers) = self.scalers.get_scalers(name).await else {
+            return false;
+        };
+        futures::future::join_all(scalers.iter().map(|scaler| scaler.status()))
+            .await
+            .iter()
+            .any(|status| status.status_type == StatusType::Reconciling)
+    }
+
+    /// Deploys any queued manifests whose previous deploy has finished converging, or that have
+    /// waited longer than the deploy queue timeout
+    async fn deploy_queued(&self, lattice_id: &str) {
+        let queued = self
+            .queued_deploys
+            .read()
+            .await
+            .iter()
+            .map(|(name, (_, queued_at))| (name.clone(), queued_at.elapsed()))
+            .collect::<Vec<_>>();
+        for (name, waited) in queued {
+            let timed_out = waited >= self.deploy_queue_timeout;
+            if !timed_out && self.is_deploy_in_flight(&name).await {
+                continue;
+            }
+            let Some((data, _)) = self.queued_deploys.write().await.remove(&name) else {
+                continue;
+            };
+            if timed_out {
+                warn!(%name, version = %data.manifest.version(), waited = ?waited, "Previous deploy didn't converge in time, preempting it with queued version");
+            } else {
+                info!(%name, version = %data.manifest.version(), "Previous deploy converged, deploying queued version");
+            }
+            if let Err(e) = self.deploy_manifest(lattice_id, &data).await {
+                warn!(error = ?e, %name, "Errors occurred when deploying queued version, scalers will retry on the next event");
+            }
+        }
+    }
+
+    #[instrument(level = "debug", skip(self, data), fields(name = %data.manifest.metadata.name))]
+    async fn deploy_manifest(
+        &self,
+        lattice_id: &str,
+        data: &ManifestPublished,
+    ) -> anyhow::Result<()> {
+        // Resolve any variables before building scalers so the scalers only ever see the final
+        // manifest
+        let manifest = match template_manifest(&data.manifest, &data.variables) {
+            Ok(manifest) => manifest,
+            // Retrying won't fix a bad template, so report the failure in the manifest status
+            // rather than returning an error
+            Err(e) => {
+                warn!(error = %e, "Unable to resolve manifest variables");
+                let info = StatusInfo::failed(&e.to_string());
+                self.record_status_history(lattice_id, &data.manifest.metadata.name, &info)
+                    .await;
+                if let Err(e) = self
+                    .status_publisher
+                    .publish_status(&data.manifest.metadata.name, Status::new(info, Vec::new()))
+                    .await
+                {
+                    warn!("Failed to set manifest status: {e:}");
+                }
+                return Ok(());
+            }
+        };
+
+        let old_scalers = self
+            .scalers
+            .remove_raw_scalers(&manifest.metadata.name)
+            .await;
+        let previous = self
+            .deployed_manifests
+            .write()
+            .await
+            .insert(manifest.metadata.name.clone(), manifest.clone());
+        // Only the scalers of components that changed since the previous version are rebuilt. The
+        // rest are kept as is, along with any state they have. Kept scalers are also skipped in the
+        // initial reconcile of an update, unless the same version is being handled again (such as a
+        // redelivery after some scalers failed), in which case everything is retried
+        let mut kept = HashSet::new();
+        let (scalers, old_scalers) = match (old_scalers, previous) {
+            (Some(old_scalers), Some(previous)) => {
+                let is_update = previous != manifest;
+                if is_update {
+                    kept.extend(old_scalers.iter().map(|scaler| scaler.id().to_owned()));
+                }
+                let (scalers, replaced) =
+                    self.scalers
+                        .update_scalers_for_manifest(&previous, &manifest, old_scalers);
+                replaced.iter().for_each(|scaler| {
+                    kept.remove(scaler.id());
+                });
+                (scalers, Some(replaced))
+            }
+            (old_scalers, _) => (self.scalers.scalers_for_manifest(&manifest), old_scalers),
+        };
+
+        // Refresh the snapshot data before cleaning up and/or adding scalers
+        self.scalers.refresh_data().await?;
+        let cleanup_commands = if let Some(old_scalers) = old_scalers {
+            // This relies on the idea that an ID is a unique identifier for a scaler, and any
+            // change in the ID is indicative of the fact that the scaler is outdated and should be cleaned up.
+            let (_updated_component, outdated_component): (ScalerList, ScalerList) = old_scalers
+                .into_iter()
+                .partition(|old| scalers.iter().any(|new| new.id() == old.id()));
 
             // Clean up any resources from scalers that no longer exist
             let futs = outdated_component
@@ -723,24 +1471,49 @@ where
         // redelivered. When it is, the ones that succeeded will be in backoff mode and the ones
         // that failed will be retried.
 
-        let scalers = self.scalers.add_scalers(&data.manifest, scalers).await?;
+        let scalers = self.scalers.add_scalers(&manifest, scalers).await?;
 
         let (commands, res) = get_commands_and_result(
-            scalers.iter().map(|s| s.reconcile()),
+            scalers
+                .iter()
+                .filter(|s| !kept.contains(s.id()))
+                .map(|s| s.reconcile()),
             "Errors occurred during initial reconciliation",
         )
         .await;
 
         let status = detailed_scaler_status(&scalers).await;
 
+        if self.deploy_events.is_some() {
+            self.pending_deploys.write().await.insert(
+                manifest.metadata.name.clone(),
+                manifest.version().to_owned(),
+            );
+        }
+
         trace!(?status, "Setting status");
-        if let Err(e) = self
-            .status_publisher
-            .publish_status(data.manifest.metadata.name.as_ref(), status)
-            .await
-        {
-            warn!("Failed to set manifest status: {e:}");
-        };
+        self.publish_model_status(lattice_id, &manifest.metadata.name, status)
+            .await;
+
+        // Components that the new scalers update in place must not be stopped by the old ones
+        let updated: HashSet<(&str, &str)> = commands
+            .iter()
+            .filter_map(|command| match command {
+                Command::UpdateComponent(update) => {
+                    Some((update.host_id.as_str(), update.component_id.as_str()))
+                }
+                _ => None,
+            })
+            .collect();
+        let cleanup_commands: Vec<Command> = cleanup_commands
+            .into_iter()
+            .filter(|command| match command {
+                Command::ScaleComponent(scale) => {
+                    !updated.contains(&(scale.host_id.as_str(), scale.component_id.as_str()))
+                }
+                _ => true,
+            })
+            .collect();
 
         trace!(?commands, "Publishing commands");
         // Handle the result from initial reconciliation. This lets us handle the net new stuff
@@ -761,8 +1534,107 @@ where
         res
     }
 
-    #[instrument(level = "debug", skip(self))]
-    async fn run_scalers_with_hint(&self, event: &Event, name: &str) -> anyhow::Result<()> {
+    /// Records the given status in the history of the model, if status history is enabled. Failing
+    /// to record it is only logged, as the history is only used for debugging
+    async fn record_status_history(&self, lattice_id: &str, name: &str, status: &StatusInfo) {
+        let Some(max_transitions) = self.status_history else {
+            return;
+        };
+        if let Err(e) = record_status(&self.store, lattice_id, name, status, max_transitions).await
+        {
+            warn!(error = %e, %name, "Unable to record status history");
+        }
+    }
+
+    /// Publishes the status of the given model, sending a [`ModelDeployed`] event if this is the
+    /// first time the most recently deployed version is ready
+    async fn publish_model_status(&self, lattice_id: &str, name: &str, mut status: Status) {
+        let ready = status.info.status_type == StatusType::Deployed;
+        if self.dry_run {
+            status.info.message = if status.info.message.is_empty() {
+                DRY_RUN_STATUS_MESSAGE.to_string()
+            } else {
+                format!("{DRY_RUN_STATUS_MESSAGE}: {}", status.info.message)
+            };
+        }
+        self.record_status_history(lattice_id, name, &status.info)
+            .await;
+        if let Err(e) = self.status_publisher.publish_status(name, status).await {
+            warn!(error = ?e, "Failed to set status for scaler");
+        };
+        let Some(deploy_events) = self.deploy_events.as_ref().filter(|_| ready) else {
+            return;
+        };
+        let Some(version) = self.pending_deploys.write().await.remove(name) else {
+            return;
+        };
+        let resources = match self.deployed_resources(lattice_id, name).await {
+            Ok(resources) => resources,
+            Err(e) => {
+                warn!(error = ?e, %name, "Unable to count deployed resources");
+                DeployedResources::default()
+            }
+        };
+        let event = ModelDeployed {
+            model_name: name.to_owned(),
+            version,
+            lattice_id: lattice_id.to_owned(),
+            deployed_at: chrono::Utc::now(),
+            resources,
+        };
+        if let Err(e) = deploy_events.publish(&event).await {
+            warn!(error = ?e, %name, "Failed to publish model deployed event");
+        }
+    }
+
+    /// Counts everything currently running in the lattice that is managed by the given model
+    async fn deployed_resources(
+        &self,
+        lattice_id: &str,
+        name: &str,
+    ) -> anyhow::Result<DeployedResources> {
+        let is_managed = |annotations: &BTreeMap<String, String>| {
+            annotations.get(APP_SPEC_ANNOTATION).map(String::as_str) == Some(name)
+        };
+        let component_instances = self
+            .store
+            .list::<Component>(lattice_id)
+            .await?
+            .values()
+            .flat_map(|component| component.instances.values().flatten())
+            .filter(|info| is_managed(&info.annotations))
+            .map(|info| info.count)
+            .sum();
+        // Provider annotations are only tracked on the hosts they are running on
+        let providers = self
+            .store
+            .list::<Host>(lattice_id)
+            .await?
+            .values()
+            .flat_map(|host| host.providers.iter())
+            .filter(|provider| is_managed(&provider.annotations))
+            .count();
+        let links = self
+            .store
+            .list::<LinkState>(lattice_id)
+            .await?
+            .values()
+            .filter(|link| link.model_name == name)
+            .count();
+        Ok(DeployedResources {
+            component_instances,
+            providers,
+            links,
+        })
+    }
+
+    #[instrument(level = "debug", skip(self, event))]
+    async fn run_scalers_with_hint(
+        &self,
+        lattice_id: &str,
+        event: &Event,
+        name: &str,
+    ) -> anyhow::Result<()> {
         let scalers = match self.scalers.get_scalers(name).await {
             Some(scalers) => scalers,
             None => {
@@ -780,9 +1652,7 @@ where
 
         let status = detailed_scaler_status(&scalers).await;
         trace!(?status, "Setting status");
-        if let Err(e) = self.status_publisher.publish_status(name, status).await {
-            warn!(error = ?e, "Failed to set status for scaler");
-        };
+        self.publish_model_status(lattice_id, name, status).await;
 
         trace!(?commands, "Publishing commands");
         self.command_publisher.publish_commands(commands).await?;
@@ -790,14 +1660,23 @@ where
         res
     }
 
-    #[instrument(level = "debug", skip(self))]
-    async fn run_all_scalers(&self, event: &Event) -> anyhow::Result<()> {
+    #[instrument(level = "debug", skip(self, event))]
+    async fn run_all_scalers(&self, lattice_id: &str, event: &Event) -> anyhow::Result<()> {
         let scalers = self.scalers.get_all_scalers().await;
         // Refresh the snapshot data before running
         self.scalers.refresh_data().await?;
+        let permits = self.scaler_concurrency.map(Semaphore::new);
+        let permits = &permits;
         let futs = scalers.iter().map(|(name, scalers)| async move {
             let (commands, res) = get_commands_and_result(
-                scalers.iter().map(|scaler| scaler.handle_event(event)),
+                scalers.iter().map(|scaler| async move {
+                    // The semaphore is never closed, so this only waits for a permit
+                    let _permit = match permits {
+                        Some(permits) => permits.acquire().await.ok(),
+                        None => None,
+                    };
+                    scaler.handle_event(event).await
+                }),
                 "Errors occurred while handling event with all scalers",
             )
             .await;
@@ -805,9 +1684,7 @@ where
             let status = detailed_scaler_status(scalers).await;
 
             trace!(?status, "Setting status");
-            if let Err(e) = self.status_publisher.publish_status(name, status).await {
-                warn!(error = ?e, "Failed to set status for scaler");
-            };
+            self.publish_model_status(lattice_id, name, status).await;
 
             (commands, res)
         });
@@ -852,6 +1729,9 @@ where
 
     #[instrument(level = "debug", skip(self))]
     async fn do_work(&self, mut message: ScopedMessage<Self::Message>) -> WorkResult<()> {
+        let _timer = WorkTimer::new(message.raw_type());
+        // The model that put a deleted link, which the name hint borrows from
+        let link_owner;
         // Everything in this block returns a name hint for the success case and an error otherwise
         let res = match message.as_ref() {
             Event::ComponentScaled(component) => self
@@ -912,6 +1792,9 @@ where
                 .map(|_| None),
             Event::ManifestUnpublished(data) => {
                 debug!("Handling unpublished manifest");
+                self.queued_deploys.write().await.remove(&data.name);
+                self.pending_deploys.write().await.remove(&data.name);
+                self.deployed_manifests.write().await.remove(&data.name);
 
                 match self.scalers.remove_scalers(&data.name).await {
                     Some(Ok(_)) => {
@@ -924,35 +1807,140 @@ where
                     None => Ok(None),
                 }
             }
+            // A failed command is only relevant to the scalers for the model that issued it, which
+            // can update their status right away instead of waiting for a lattice event
+            Event::CommandExecuted(executed) => {
+                if let Err(e) = self
+                    .handle_command_executed(&message.lattice_id, executed)
+                    .await
+                {
+                    message.nack().await;
+                    return Err(WorkError::Other(e.into()));
+                }
+                match executed.command.model_name() {
+                    Some(name) if !executed.success => Ok(Some(name)),
+                    _ => {
+                        trace!(
+                            "Got command result that doesn't need handling. Not running scalers"
+                        );
+                        return message.ack().await.map_err(WorkError::from);
+                    }
+                }
+            }
+            // Only the model that put the link needs to put it back
+            Event::LinkdefDeleted(link) => {
+                match self.handle_linkdef_deleted(&message.lattice_id, link).await {
+                    Ok(owner) => {
+                        link_owner = owner;
+                        Ok(link_owner.as_deref())
+                    }
+                    Err(e) => Err(e),
+                }
+            }
             // All other events we don't care about for state. Explicitly mention them in order
             // to make sure we don't forget to handle them when new events are added.
             Event::LinkdefSet(_)
-            | Event::LinkdefDeleted(_)
             | Event::ConfigSet(_)
             | Event::ConfigDeleted(_)
             | Event::ProviderStartFailed(_)
-            | Event::ComponentScaleFailed(_) => {
+            | Event::ComponentScaleFailed(_)
+            // The reaper has already removed the host from state by the time this is received
+            | Event::HostReaped(_) => {
                 trace!("Got event we don't care about. Not modifying state.");
                 Ok(None)
             }
         };
 
+        let tearing_down = match event_model_name(&message) {
+            Some(name) => self.scalers.is_tearing_down(name).await,
+            None => false,
+        };
         let res = match res {
-            Ok(Some(name)) => self.run_scalers_with_hint(&message, name).await,
-            Ok(None) => self.run_all_scalers(&message).await,
+            // State has already been updated, but the model's scalers are gone and its teardown
+            // shouldn't make every other model reconcile
+            Ok(_) if tearing_down => {
+                trace!("Got event for a model that is being torn down. Not running scalers");
+                Ok(())
+            }
+            Ok(Some(name)) => {
+                self.run_scalers_with_hint(&message.lattice_id, &message, name)
+                    .await
+            }
+            Ok(None) => self.run_all_scalers(&message.lattice_id, &message).await,
             Err(e) => Err(e),
-        }
-        .map_err(Box::<dyn std::error::Error + Send + 'static>::from);
+        };
 
         if let Err(e) = res {
-            message.nack().await;
-            return Err(WorkError::Other(e));
+            if should_retry(&e) {
+                message.nack().await;
+            } else {
+                warn!(error = %e, "Store error can't be fixed by retrying, dropping event");
+                message.ack().await?;
+            }
+            return Err(WorkError::Other(e.into()));
         }
 
+        self.deploy_queued(&message.lattice_id).await;
+
         message.ack().await.map_err(WorkError::from)
     }
 }
 
+/// Records how long it took to handle an event when dropped, so every way out of
+/// [`EventWorker::do_work`] is timed
+struct WorkTimer {
+    event_type: &'static str,
+    start: Instant,
+}
+
+impl WorkTimer {
+    fn new(event_type: &'static str) -> WorkTimer {
+        WorkTimer {
+            event_type,
+            start: Instant::now(),
+        }
+    }
+}
+
+impl Drop for WorkTimer {
+    fn drop(&mut self) {
+        metrics::histogram!("wadm_event_work_duration_seconds", "event_type" => self.event_type)
+            .record(self.start.elapsed());
+    }
+}
+
+/// Returns an error if the manifest needs a newer version of wadm than the running one
+fn check_min_wadm_version(manifest: &Manifest, running: &semver::Version) -> Result<()> {
+    let Some(raw) = manifest.min_wadm_version() else {
+        return Ok(());
+    };
+    let required =
+        semver::Version::parse(raw.trim().trim_start_matches('v')).with_context(|| {
+            format!("Invalid minimum wadm version '{raw}', expected a version like 0.21.0")
+        })?;
+    if running < &required {
+        anyhow::bail!(
+            "Manifest requires wadm {required} or newer, but this is wadm {running}. Upgrade wadm to deploy this manifest"
+        );
+    }
+    Ok(())
+}
+
+/// Returns the name of the model an event is for, if it was caused by something wadm manages.
+/// Provider stopped events are never attributed to a model because scalers for every model may
+/// need to handle them
+fn event_model_name(event: &Event) -> Option<&str> {
+    match event {
+        Event::ComponentScaled(ComponentScaled { annotations, .. })
+        | Event::ComponentScaleFailed(ComponentScaleFailed { annotations, .. })
+        | Event::ProviderStarted(ProviderStarted { annotations, .. }) => {
+            annotations.get(APP_SPEC_ANNOTATION).map(String::as_str)
+        }
+        Event::CommandExecuted(executed) => executed.command.model_name(),
+        _ => None,
+    }
+}
+
 /// Helper that runs any iterable of futures and returns a list of commands and the proper result to
 /// use as a response (Ok if there were no errors, all of the errors combined otherwise)
 pub(crate) async fn get_commands_and_result<Fut, I>(
@@ -1006,6 +1994,14 @@ pub async fn detailed_scaler_status(scalers: &ScalerList) -> Status {
                 })
                 .collect::<Vec<_>>()
                 .join(", "),
+            // Only scalers that measure their progress count towards it, so the total is left
+            // unset when none of them do
+            progress: status
+                .iter()
+                .filter_map(|(_id, _name, _kind, s)| s.progress)
+                .reduce(|(achieved, desired), (a, d)| {
+                    (achieved.saturating_add(a), desired.saturating_add(d))
+                }),
         },
         status
             .into_iter()
@@ -1019,6 +2015,19 @@ pub async fn detailed_scaler_status(scalers: &ScalerList) -> Status {
     )
 }
 
+/// Returns whether an event that failed with the given error should be redelivered. Store errors
+/// are only retried if the store couldn't be reached, since anything else (like data that can't be
+/// decoded) fails the same way every time. All other errors are retried
+fn should_retry(e: &anyhow::Error) -> bool {
+    let store_error =
+        e.downcast_ref::<StoreError>()
+            .or_else(|| match e.downcast_ref::<CasError<StoreError>>() {
+                Some(CasError::Store(e)) => Some(e),
+                _ => None,
+            });
+    store_error.is_none_or(StoreError::is_retryable)
+}
+
 fn map_to_result(errors: Vec<anyhow::Error>, error_message: &str) -> Result<()> {
     if errors.is_empty() {
         Ok(())
@@ -1031,18 +2040,87 @@ fn map_to_result(errors: Vec<anyhow::Error>, error_message: &str) -> Result<()>
     }
 }
 
+/// Applies a component scaled event to the current state of the component, returning `None` if the
+/// component is no longer running anywhere
+fn scaled_component_state(
+    current: Option<Component>,
+    component: &ComponentScaled,
+) -> Option<Component> {
+    let mut component_data = Component::from(component);
+    let Some(mut current) = current else {
+        return Some(component_data).filter(|data| !data.instances.is_empty());
+    };
+    trace!(component = ?current, "Found existing component data");
+
+    match current.instances.get_mut(&component.host_id) {
+        // If the component is running and is now scaled down to zero, remove it
+        Some(current_instances) if component.max_instances == 0 => {
+            current_instances.remove(&component.annotations);
+        }
+        // If a component is already running on a host, update the running count to the scaled max_instances value
+        Some(current_instances) => {
+            current_instances.replace(WadmComponentInfo {
+                count: component.max_instances,
+                annotations: component.annotations.clone(),
+            });
+        }
+        // Component is not running and now scaled to zero, no action required. This can happen if we
+        // update the state before we receive the ComponentScaled event
+        None if component.max_instances == 0 => (),
+        // If a component isn't running yet, add it with the scaled max_instances value
+        None => {
+            current.instances.insert(
+                component.host_id.clone(),
+                HashSet::from([WadmComponentInfo {
+                    count: component.max_instances,
+                    annotations: component.annotations.clone(),
+                }]),
+            );
+        }
+    }
+
+    // If we stopped the last instance on a host, remove the host from the component data
+    if current
+        .instances
+        .get(&component.host_id)
+        .is_some_and(|instances| instances.is_empty())
+    {
+        current.instances.remove(&component.host_id);
+    }
+
+    // Take the updated counts and store them in the component data
+    component_data.instances = current.instances;
+
+    // Keep the references of the other hosts, only updating the one for this host
+    let mut host_references = current.host_references;
+    host_references.retain(|host_id, _| component_data.instances.contains_key(host_id));
+    if component_data.instances.contains_key(&component.host_id) {
+        host_references.insert(component.host_id.clone(), component.image_ref.clone());
+    }
+    component_data.host_references = host_references;
+    Some(component_data).filter(|data| !data.instances.is_empty())
+}
+
 #[cfg(test)]
 mod test {
     use std::sync::Arc;
 
     use tokio::sync::RwLock;
-    use wasmcloud_control_interface::{ComponentDescription, HostInventory, ProviderDescription};
+    use wasmcloud_control_interface::{ComponentDescription, ProviderDescription};
 
     use super::*;
 
+    use wadm_types::{api::StatusType, TraitProperty};
+
     use crate::{
-        storage::ReadStore,
-        test_util::{NoopPublisher, TestLatticeSource, TestStore},
+        scaler::{
+            manager::BoxedScaler,
+            registry::{ScalerContext, ScalerRegistry},
+            spreadscaler::{spreadscaler_annotations, SPREAD_SCALER_KIND},
+            Scaler,
+        },
+        storage::{ReadStore, StateKind},
+        test_util::{NoopPublisher, RecorderPublisher, TestLatticeSource, TestStore},
     };
 
     // NOTE: This test is rather long because we want to run through what an actual state generation
@@ -1452,6 +2530,7 @@ mod test {
                     uptime_seconds: 30,
                     version: semver::Version::parse("0.61.0").unwrap(),
                     host_id: host1_id.into(),
+                    utilization: None,
                 },
             )
             .await
@@ -1490,6 +2569,7 @@ mod test {
                     uptime_seconds: 30,
                     version: semver::Version::parse("0.61.0").unwrap(),
                     host_id: host2_id.into(),
+                    utilization: None,
                 },
             )
             .await
@@ -1668,6 +2748,7 @@ mod test {
                     uptime_seconds: 60,
                     version: semver::Version::parse("0.61.0").unwrap(),
                     host_id: host1_id.into(),
+                    utilization: None,
                 },
             )
             .await
@@ -1697,6 +2778,7 @@ mod test {
                     uptime_seconds: 60,
                     version: semver::Version::parse("0.61.0").unwrap(),
                     host_id: host2_id.into(),
+                    utilization: None,
                 },
             )
             .await
@@ -1881,6 +2963,7 @@ mod test {
                     uptime_seconds: 60,
                     version: semver::Version::parse("0.61.0").unwrap(),
                     host_id: host_id.into(),
+                    utilization: None,
                 },
             )
             .await
@@ -1998,7 +3081,8 @@ mod test {
             matches!(
                 prov.hosts
                     .get(host_id)
-                    .expect("Should find status for host"),
+                    .expect("Should find status for host")
+                    .status,
                 ProviderStatus::Running
             ),
             "Provider should have a running status"
@@ -2026,7 +3110,8 @@ mod test {
             matches!(
                 prov.hosts
                     .get(host_id)
-                    .expect("Should find status for host"),
+                    .expect("Should find status for host")
+                    .status,
                 ProviderStatus::Failed
             ),
             "Provider should have a running status"
@@ -2034,15 +3119,10 @@ mod test {
     }
 
     #[tokio::test]
-    async fn test_heartbeat_updates_stale_data() {
+    async fn test_provider_instances_per_host() {
         let store = Arc::new(TestStore::default());
-        let inventory = Arc::new(RwLock::new(HashMap::default()));
-        let lattice_source = TestLatticeSource {
-            inventory: inventory.clone(),
-            ..Default::default()
-        };
-        let lattice_id = "update_data";
-
+        let lattice_source = TestLatticeSource::default();
+        let lattice_id = "provider_instances";
         let command_publisher = CommandPublisher::new(NoopPublisher, "doesntmatter");
         let status_publisher = StatusPublisher::new(NoopPublisher, None, "doesntmatter");
         let worker = EventWorker::new(
@@ -2061,47 +3141,226 @@ mod test {
             .await,
         );
 
-        let host_id = "jabbaspalace";
+        let host_id = "CLOUDCITY";
+        let provider = ProviderStarted {
+            claims: None,
+            image_ref: "bespin.lando.inc/tibanna:0.1.0".into(),
+            provider_id: "GAS".into(),
+            host_id: host_id.into(),
+            annotations: BTreeMap::default(),
+        };
+        let stopped = ProviderStopped {
+            annotations: BTreeMap::default(),
+            provider_id: provider.provider_id.clone(),
+            reason: String::new(),
+            host_id: host_id.into(),
+        };
+        let instances = || async {
+            store
+                .get::<Provider>(lattice_id, &provider.provider_id)
+                .await
+                .unwrap()
+                .and_then(|prov| prov.hosts.get(host_id).map(|host| host.instances))
+        };
 
-        // Store existing components and providers as-if they had the minimum
-        // amount of information
-        store
-            .store(
-                lattice_id,
-                "jabba".to_string(),
-                Component {
-                    id: "jabba".to_string(),
-                    instances: HashMap::from([(
-                        host_id.to_string(),
-                        HashSet::from_iter([WadmComponentInfo {
-                            count: 1,
-                            annotations: BTreeMap::default(),
-                        }]),
-                    )]),
-                    ..Default::default()
-                },
-            )
-            .await
-            .unwrap();
-        store
-            .store(
-                lattice_id,
-                "jabbatheprovider".to_string(),
-                Provider {
-                    id: "jabbatheprovider".to_string(),
-                    hosts: HashMap::from_iter([(host_id.to_string(), ProviderStatus::Pending)]),
-                    ..Default::default()
-                },
-            )
+        for _ in 0..2 {
+            worker
+                .handle_provider_started(lattice_id, &provider)
+                .await
+                .expect("Should be able to handle provider started event");
+        }
+        assert_eq!(instances().await, Some(2), "Each start should be counted");
+
+        worker
+            .handle_provider_stopped(lattice_id, &stopped)
             .await
-            .unwrap();
+            .expect("Should be able to handle provider stopped event");
+        assert_eq!(
+            instances().await,
+            Some(1),
+            "Provider should still be on the host after stopping one instance"
+        );
 
-        // Now heartbeat and make sure stuff that isn't running is removed
         worker
-            .handle_host_heartbeat(
-                lattice_id,
-                &HostHeartbeat {
-                    components: vec![
+            .handle_provider_stopped(lattice_id, &stopped)
+            .await
+            .expect("Should be able to handle provider stopped event");
+        assert!(
+            store
+                .get::<Provider>(lattice_id, &provider.provider_id)
+                .await
+                .unwrap()
+                .is_none(),
+            "Provider should be removed once its last instance stops"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_bootstrap_lattice() {
+        let store = Arc::new(TestStore::default());
+        let lattice_id = "bootstrap";
+        let host_id = "JAKKU";
+        let inventory = HostInventory::builder()
+            .friendly_name("niima-outpost".into())
+            .components(vec![ComponentDescription::builder()
+                .id("BB8".into())
+                .image_ref("resistance.io/bb8:0.1.0".into())
+                .revision(0)
+                .max_instances(3)
+                .build()
+                .expect("failed to build description")])
+            .providers(vec![ProviderDescription::builder()
+                .id("SPEEDER")
+                .image_ref("resistance.io/speeder:0.1.0")
+                .revision(0)
+                .build()
+                .expect("failed to build provider description")])
+            .host_id(host_id.into())
+            .version("1.0.0".into())
+            .uptime_human("60s".into())
+            .uptime_seconds(60)
+            .build()
+            .expect("failed to build host inventory");
+        let lattice_source = TestLatticeSource {
+            claims: HashMap::from([(
+                "BB8".to_string(),
+                Claims {
+                    name: "BB-8".to_string(),
+                    capabilities: Vec::new(),
+                    issuer: "resistance".to_string(),
+                },
+            )]),
+            inventory: Arc::new(RwLock::new(HashMap::from([(
+                host_id.to_string(),
+                inventory,
+            )]))),
+            ..Default::default()
+        };
+        let command_publisher = CommandPublisher::new(NoopPublisher, "doesntmatter");
+        let status_publisher = StatusPublisher::new(NoopPublisher, None, "doesntmatter");
+        let worker = EventWorker::new(
+            store.clone(),
+            lattice_source.clone(),
+            command_publisher.clone(),
+            status_publisher.clone(),
+            ScalerManager::test_new(
+                NoopPublisher,
+                lattice_id,
+                store.clone(),
+                command_publisher,
+                status_publisher.clone(),
+                lattice_source,
+            )
+            .await,
+        );
+
+        // Running it again shouldn't change anything
+        for _ in 0..2 {
+            assert_eq!(
+                worker
+                    .bootstrap_lattice(lattice_id)
+                    .await
+                    .expect("Should be able to bootstrap lattice"),
+                1
+            );
+        }
+
+        let host = store
+            .get::<Host>(lattice_id, host_id)
+            .await
+            .unwrap()
+            .expect("Host should be loaded without a heartbeat");
+        assert_eq!(host.friendly_name, "niima-outpost");
+        assert_eq!(host.components, HashMap::from([("BB8".to_string(), 3)]));
+        assert_eq!(host.providers.len(), 1);
+
+        let components = store.list::<Component>(lattice_id).await.unwrap();
+        assert_component(&components, "BB8", &[(host_id, 3)]);
+        assert_eq!(
+            components["BB8"].name, "BB-8",
+            "Component name should come from its claims"
+        );
+
+        let provider = store
+            .get::<Provider>(lattice_id, "SPEEDER")
+            .await
+            .unwrap()
+            .expect("Provider should be loaded without a heartbeat");
+        assert!(provider.hosts.contains_key(host_id));
+    }
+
+    #[tokio::test]
+    async fn test_heartbeat_updates_stale_data() {
+        let store = Arc::new(TestStore::default());
+        let inventory = Arc::new(RwLock::new(HashMap::default()));
+        let lattice_source = TestLatticeSource {
+            inventory: inventory.clone(),
+            ..Default::default()
+        };
+        let lattice_id = "update_data";
+
+        let command_publisher = CommandPublisher::new(NoopPublisher, "doesntmatter");
+        let status_publisher = StatusPublisher::new(NoopPublisher, None, "doesntmatter");
+        let worker = EventWorker::new(
+            store.clone(),
+            lattice_source.clone(),
+            command_publisher.clone(),
+            status_publisher.clone(),
+            ScalerManager::test_new(
+                NoopPublisher,
+                lattice_id,
+                store.clone(),
+                command_publisher,
+                status_publisher.clone(),
+                lattice_source,
+            )
+            .await,
+        );
+
+        let host_id = "jabbaspalace";
+
+        // Store existing components and providers as-if they had the minimum
+        // amount of information
+        store
+            .store(
+                lattice_id,
+                "jabba".to_string(),
+                Component {
+                    id: "jabba".to_string(),
+                    instances: HashMap::from([(
+                        host_id.to_string(),
+                        HashSet::from_iter([WadmComponentInfo {
+                            count: 1,
+                            annotations: BTreeMap::default(),
+                        }]),
+                    )]),
+                    ..Default::default()
+                },
+            )
+            .await
+            .unwrap();
+        store
+            .store(
+                lattice_id,
+                "jabbatheprovider".to_string(),
+                Provider {
+                    id: "jabbatheprovider".to_string(),
+                    hosts: HashMap::from_iter([(
+                        host_id.to_string(),
+                        ProviderStatus::Pending.into(),
+                    )]),
+                    ..Default::default()
+                },
+            )
+            .await
+            .unwrap();
+
+        // Now heartbeat and make sure stuff that isn't running is removed
+        worker
+            .handle_host_heartbeat(
+                lattice_id,
+                &HostHeartbeat {
+                    components: vec![
                         ComponentDescription::builder()
                             .id("jabba".into())
                             .image_ref("jabba.tatooinecr.io/jabba:latest".into())
@@ -2141,6 +3400,7 @@ mod test {
                     uptime_seconds: 60,
                     version: semver::Version::parse("0.61.0").unwrap(),
                     host_id: host_id.to_string(),
+                    utilization: None,
                 },
             )
             .await
@@ -2213,53 +3473,2852 @@ mod test {
         );
     }
 
-    fn assert_component(
-        components: &HashMap<String, Component>,
-        component_id: &str,
-        expected_counts: &[(&str, usize)],
-    ) {
-        let component = components
-            .get(component_id)
-            .expect("Component should exist in store");
+    #[tokio::test]
+    async fn test_heartbeat_repairs_provider_host_drift() {
+        let store = Arc::new(TestStore::default());
+        let lattice_source = TestLatticeSource::default();
+        let lattice_id = "provider_drift";
+
+        let command_publisher = CommandPublisher::new(NoopPublisher, "doesntmatter");
+        let status_publisher = StatusPublisher::new(NoopPublisher, None, "doesntmatter");
+        let worker = EventWorker::new(
+            store.clone(),
+            lattice_source.clone(),
+            command_publisher.clone(),
+            status_publisher.clone(),
+            ScalerManager::test_new(
+                NoopPublisher,
+                lattice_id,
+                store.clone(),
+                command_publisher,
+                status_publisher.clone(),
+                lattice_source,
+            )
+            .await,
+        );
+
+        let host_id = "mos-eisley";
+
+        // Seed state where the provider map and the host disagree in both directions: two
+        // providers still claim the host even though it isn't running them, and one provider
+        // the host is running doesn't list the host at all
+        let provider_info = |id: &str| ProviderInfo {
+            provider_id: id.to_string(),
+            provider_ref: format!("cantina.io/{id}:latest"),
+            annotations: BTreeMap::default(),
+        };
+        store
+            .store(
+                lattice_id,
+                host_id.to_string(),
+                Host {
+                    id: host_id.to_string(),
+                    providers: HashSet::from_iter([
+                        provider_info("stale"),
+                        provider_info("missing"),
+                    ]),
+                    last_seen: chrono::Utc::now(),
+                    ..Default::default()
+                },
+            )
+            .await
+            .unwrap();
+        store
+            .store_many(
+                lattice_id,
+                [
+                    (
+                        "stale".to_string(),
+                        Provider {
+                            id: "stale".to_string(),
+                            hosts: HashMap::from_iter([(
+                                host_id.to_string(),
+                                ProviderStatus::Running.into(),
+                            )]),
+                            ..Default::default()
+                        },
+                    ),
+                    (
+                        "elsewhere".to_string(),
+                        Provider {
+                            id: "elsewhere".to_string(),
+                            hosts: HashMap::from_iter([
+                                (host_id.to_string(), ProviderStatus::Running.into()),
+                                ("tatooine".to_string(), ProviderStatus::Running.into()),
+                            ]),
+                            ..Default::default()
+                        },
+                    ),
+                    (
+                        "missing".to_string(),
+                        Provider {
+                            id: "missing".to_string(),
+                            hosts: HashMap::from_iter([(
+                                "tatooine".to_string(),
+                                ProviderStatus::Running.into(),
+                            )]),
+                            ..Default::default()
+                        },
+                    ),
+                ],
+            )
+            .await
+            .unwrap();
+
+        worker
+            .handle_host_heartbeat(
+                lattice_id,
+                &HostHeartbeat {
+                    components: vec![],
+                    friendly_name: "cantina".to_string(),
+                    labels: HashMap::default(),
+                    issuer: "".to_string(),
+                    providers: vec![ProviderDescription::builder()
+                        .id("missing")
+                        .image_ref("cantina.io/missing:latest")
+                        .revision(0)
+                        .build()
+                        .expect("failed to build provider description")],
+                    uptime_human: "60s".into(),
+                    uptime_seconds: 60,
+                    version: semver::Version::parse("0.61.0").unwrap(),
+                    host_id: host_id.to_string(),
+                    utilization: None,
+                },
+            )
+            .await
+            .expect("Should be able to handle host heartbeat");
+
+        let providers = store.list::<Provider>(lattice_id).await.unwrap();
+        assert!(
+            !providers.contains_key("stale"),
+            "Provider that was only on the heartbeating host should be removed"
+        );
+        let elsewhere = providers.get("elsewhere").expect("Provider should exist");
         assert_eq!(
-            component.id, component_id,
-            "Component ID stored should be correct"
+            elsewhere.hosts.keys().collect::<Vec<_>>(),
+            vec!["tatooine"],
+            "Heartbeating host should be removed from the provider"
+        );
+        let missing = providers.get("missing").expect("Provider should exist");
+        assert!(
+            missing.hosts.contains_key(host_id) && missing.hosts.contains_key("tatooine"),
+            "Heartbeating host should be added to the provider"
         );
+
+        let host = store
+            .get::<Host>(lattice_id, host_id)
+            .await
+            .unwrap()
+            .expect("Host should exist");
         assert_eq!(
-            expected_counts.len(),
-            component.instances.len(),
-            "Should have the proper number of hosts the component is running on"
+            host.providers
+                .iter()
+                .map(|p| p.provider_id.as_str())
+                .collect::<Vec<_>>(),
+            vec!["missing"],
+            "Host should only list the providers from its inventory"
         );
-        for (expected_host, expected_count) in expected_counts.iter() {
-            assert_eq!(
-                component.count_for_host(expected_host),
-                *expected_count,
-                "Component count on host should be correct"
-            );
-        }
     }
 
-    fn assert_provider(
-        providers: &HashMap<String, Provider>,
-        event: &ProviderStarted,
-        running_on_hosts: &[&str],
-    ) {
-        let provider = providers
-            .get(&event.provider_id)
-            .expect("Correct provider should exist in store");
-        assert!(
-            event
-                .claims
-                .clone()
-                .is_some_and(|claims| claims.name == provider.name),
-            "Provider should have the correct data in state"
+    #[tokio::test]
+    async fn test_heartbeat_clears_reaping_warning() {
+        let store = Arc::new(TestStore::default());
+        let lattice_source = TestLatticeSource::default();
+        let lattice_id = "clear_warning";
+
+        let command_publisher = CommandPublisher::new(NoopPublisher, "doesntmatter");
+        let status_publisher = StatusPublisher::new(NoopPublisher, None, "doesntmatter");
+        let worker = EventWorker::new(
+            store.clone(),
+            lattice_source.clone(),
+            command_publisher.clone(),
+            status_publisher.clone(),
+            ScalerManager::test_new(
+                NoopPublisher,
+                lattice_id,
+                store.clone(),
+                command_publisher,
+                status_publisher.clone(),
+                lattice_source,
+            )
+            .await,
         );
-        assert!(
-            provider.hosts.len() == running_on_hosts.len()
-                && running_on_hosts
-                    .iter()
-                    .all(|host_id| provider.hosts.contains_key(*host_id)),
+
+        let host_id = "dagobah";
+        let last_seen = chrono::Utc::now() - chrono::Duration::seconds(45);
+        store
+            .store(
+                lattice_id,
+                host_id.to_string(),
+                Host {
+                    id: host_id.to_string(),
+                    last_seen,
+                    reaping_warning: true,
+                    ..Default::default()
+                },
+            )
+            .await
+            .unwrap();
+
+        worker
+            .handle_host_heartbeat(
+                lattice_id,
+                &HostHeartbeat {
+                    components: vec![],
+                    friendly_name: "swamp".to_string(),
+                    labels: HashMap::default(),
+                    issuer: "".to_string(),
+                    providers: vec![],
+                    uptime_human: "60s".into(),
+                    uptime_seconds: 60,
+                    version: semver::Version::parse("0.61.0").unwrap(),
+                    host_id: host_id.to_string(),
+                    utilization: None,
+                },
+            )
+            .await
+            .expect("Should be able to handle host heartbeat");
+
+        let host = store
+            .get::<Host>(lattice_id, host_id)
+            .await
+            .unwrap()
+            .expect("Host should exist");
+        assert!(
+            !host.reaping_warning,
+            "Heartbeat should clear the reaping warning"
+        );
+        assert!(
+            host.last_seen > last_seen,
+            "Heartbeat should update last seen"
+        );
+    }
+
+    fn assert_component(
+        components: &HashMap<String, Component>,
+        component_id: &str,
+        expected_counts: &[(&str, usize)],
+    ) {
+        let component = components
+            .get(component_id)
+            .expect("Component should exist in store");
+        assert_eq!(
+            component.id, component_id,
+            "Component ID stored should be correct"
+        );
+        assert_eq!(
+            expected_counts.len(),
+            component.instances.len(),
+            "Should have the proper number of hosts the component is running on"
+        );
+        for (expected_host, expected_count) in expected_counts.iter() {
+            assert_eq!(
+                component.count_for_host(expected_host),
+                *expected_count,
+                "Component count on host should be correct"
+            );
+        }
+    }
+
+    fn assert_provider(
+        providers: &HashMap<String, Provider>,
+        event: &ProviderStarted,
+        running_on_hosts: &[&str],
+    ) {
+        let provider = providers
+            .get(&event.provider_id)
+            .expect("Correct provider should exist in store");
+        assert!(
+            event
+                .claims
+                .clone()
+                .is_some_and(|claims| claims.name == provider.name),
+            "Provider should have the correct data in state"
+        );
+        assert!(
+            provider.hosts.len() == running_on_hosts.len()
+                && running_on_hosts
+                    .iter()
+                    .all(|host_id| provider.hosts.contains_key(*host_id)),
             "Provider should be set to the correct hosts"
         );
     }
+
+    #[tokio::test]
+    async fn test_heartbeat_retains_provider_annotations() {
+        let store = Arc::new(TestStore::default());
+        let inventory = Arc::new(RwLock::new(HashMap::default()));
+        let lattice_source = TestLatticeSource {
+            inventory: inventory.clone(),
+            ..Default::default()
+        };
+        let lattice_id = "heartbeat_provider_annotations";
+        let command_publisher = CommandPublisher::new(NoopPublisher, "doesntmatter");
+        let status_publisher = StatusPublisher::new(NoopPublisher, None, "doesntmatter");
+        let worker = EventWorker::new(
+            store.clone(),
+            lattice_source.clone(),
+            command_publisher.clone(),
+            status_publisher.clone(),
+            ScalerManager::test_new(
+                NoopPublisher,
+                lattice_id,
+                store.clone(),
+                command_publisher,
+                status_publisher.clone(),
+                lattice_source,
+            )
+            .await,
+        );
+
+        let host_id = "TATOOINE";
+        let known_annotations =
+            BTreeMap::from_iter([("wasmcloud.dev/appspec".to_string(), "known".to_string())]);
+        let new_annotations =
+            BTreeMap::from_iter([("wasmcloud.dev/appspec".to_string(), "new".to_string())]);
+
+        // The host already knows about one provider and its annotations
+        store
+            .store(
+                lattice_id,
+                host_id.to_string(),
+                Host {
+                    id: host_id.to_string(),
+                    providers: HashSet::from_iter([ProviderInfo {
+                        provider_id: "KNOWN".to_string(),
+                        provider_ref: "fakecloud.io/known:0.1.0".to_string(),
+                        annotations: known_annotations.clone(),
+                    }]),
+                    last_seen: chrono::Utc::now(),
+                    ..Default::default()
+                },
+            )
+            .await
+            .unwrap();
+
+        let bare_provider = |id: &str| {
+            ProviderDescription::builder()
+                .id(id)
+                .image_ref(&format!("fakecloud.io/{}:0.1.0", id.to_lowercase()))
+                .revision(0)
+                .build()
+                .expect("failed to build provider description")
+        };
+
+        // Only the inventory has the annotations for the newly started provider
+        *inventory.write().await = HashMap::from_iter([(
+            host_id.to_string(),
+            HostInventory::builder()
+                .friendly_name("tatooine".into())
+                .host_id(host_id.into())
+                .providers(vec![
+                    bare_provider("KNOWN"),
+                    ProviderDescription::builder()
+                        .id("NEW")
+                        .image_ref("fakecloud.io/new:0.1.0")
+                        .revision(0)
+                        .annotations(new_annotations.clone())
+                        .build()
+                        .expect("failed to build provider description"),
+                ])
+                .version(semver::Version::parse("0.61.0").unwrap().to_string())
+                .uptime_human("60s".into())
+                .uptime_seconds(60)
+                .build()
+                .expect("failed to build host inventory"),
+        )]);
+
+        worker
+            .handle_host_heartbeat(
+                lattice_id,
+                &HostHeartbeat {
+                    components: vec![],
+                    friendly_name: "tatooine".to_string(),
+                    labels: HashMap::default(),
+                    issuer: "".to_string(),
+                    providers: vec![bare_provider("KNOWN"), bare_provider("NEW")],
+                    uptime_human: "60s".into(),
+                    uptime_seconds: 60,
+                    version: semver::Version::parse("0.61.0").unwrap(),
+                    host_id: host_id.into(),
+                    utilization: None,
+                },
+            )
+            .await
+            .expect("Should be able to handle host heartbeat");
+
+        let host = store
+            .get::<Host>(lattice_id, host_id)
+            .await
+            .unwrap()
+            .expect("Host should exist");
+        let annotations_for = |id: &str| {
+            host.providers
+                .iter()
+                .find(|p| p.provider_id == id)
+                .map(|p| p.annotations.clone())
+                .expect("Provider should exist on host")
+        };
+        assert_eq!(
+            annotations_for("KNOWN"),
+            known_annotations,
+            "Annotations of a known provider should be retained"
+        );
+        assert_eq!(
+            annotations_for("NEW"),
+            new_annotations,
+            "Annotations of a newly seen provider should come from the inventory"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_heartbeat_inventory_replaces_provider_annotations() {
+        let store = Arc::new(TestStore::default());
+        let lattice_source = TestLatticeSource::default();
+        let lattice_id = "heartbeat_inventory_annotations";
+        let command_publisher = CommandPublisher::new(NoopPublisher, "doesntmatter");
+        let status_publisher = StatusPublisher::new(NoopPublisher, None, "doesntmatter");
+        let worker = EventWorker::new(
+            store.clone(),
+            lattice_source.clone(),
+            command_publisher.clone(),
+            status_publisher.clone(),
+            ScalerManager::test_new(
+                NoopPublisher,
+                lattice_id,
+                store.clone(),
+                command_publisher,
+                status_publisher.clone(),
+                lattice_source,
+            )
+            .await,
+        );
+
+        let host_id = "NABOO";
+        store
+            .store(
+                lattice_id,
+                host_id.to_string(),
+                Host {
+                    id: host_id.to_string(),
+                    providers: HashSet::from_iter([ProviderInfo {
+                        provider_id: "PROVIDER".to_string(),
+                        provider_ref: "fakecloud.io/provider:0.1.0".to_string(),
+                        annotations: BTreeMap::from_iter([
+                            ("wasmcloud.dev/appspec".to_string(), "old".to_string()),
+                            ("stale".to_string(), "value".to_string()),
+                        ]),
+                    }]),
+                    last_seen: chrono::Utc::now(),
+                    ..Default::default()
+                },
+            )
+            .await
+            .unwrap();
+
+        let inventory_annotations =
+            BTreeMap::from_iter([("wasmcloud.dev/appspec".to_string(), "new".to_string())]);
+        worker
+            .handle_host_heartbeat(
+                lattice_id,
+                &HostHeartbeat {
+                    components: vec![],
+                    friendly_name: "naboo".to_string(),
+                    labels: HashMap::default(),
+                    issuer: "".to_string(),
+                    providers: vec![ProviderDescription::builder()
+                        .id("PROVIDER")
+                        .image_ref("fakecloud.io/provider:0.1.0")
+                        .revision(0)
+                        .annotations(inventory_annotations.clone())
+                        .build()
+                        .expect("failed to build provider description")],
+                    uptime_human: "60s".into(),
+                    uptime_seconds: 60,
+                    version: semver::Version::parse("0.61.0").unwrap(),
+                    host_id: host_id.into(),
+                    utilization: None,
+                },
+            )
+            .await
+            .expect("Should be able to handle host heartbeat");
+
+        let host = store
+            .get::<Host>(lattice_id, host_id)
+            .await
+            .unwrap()
+            .expect("Host should exist");
+        let provider = host
+            .providers
+            .iter()
+            .find(|p| p.provider_id == "PROVIDER")
+            .expect("Provider should exist on host");
+        assert_eq!(
+            provider.annotations, inventory_annotations,
+            "Annotations from the inventory should replace the stored annotations"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_templated_manifest() {
+        let store = Arc::new(TestStore::default());
+        let lattice_source = TestLatticeSource::default();
+        let lattice_id = "templated_manifest";
+
+        let publisher = RecorderPublisher::<serde_json::Value> {
+            received: Arc::new(RwLock::new(Vec::new())),
+        };
+        let command_publisher = CommandPublisher::new(publisher.clone(), "doesntmatter");
+        let status_publisher = StatusPublisher::new(publisher.clone(), None, "doesntmatter");
+        let worker = EventWorker::new(
+            store.clone(),
+            lattice_source.clone(),
+            command_publisher.clone(),
+            status_publisher.clone(),
+            ScalerManager::test_new(
+                publisher.clone(),
+                lattice_id,
+                store.clone(),
+                command_publisher,
+                status_publisher.clone(),
+                lattice_source,
+            )
+            .await,
+        );
+
+        store
+            .store(
+                lattice_id,
+                "templatehost".to_string(),
+                Host {
+                    id: "templatehost".to_string(),
+                    last_seen: chrono::Utc::now(),
+                    ..Default::default()
+                },
+            )
+            .await
+            .unwrap();
+
+        let manifest: wadm_types::Manifest = serde_yaml::from_str(
+            r#"
+apiVersion: core.oam.dev/v1beta1
+kind: Application
+metadata:
+  name: templated
+  annotations:
+    description: 'A templated app'
+spec:
+  components:
+    - name: hello
+      type: component
+      properties:
+        image: ghcr.io/wasmcloud/components/http-hello-world-rust:${TAG}
+        id: http_hello_world
+      traits:
+        - type: spreadscaler
+          properties:
+            instances: 3
+"#,
+        )
+        .unwrap();
+
+        // Undefined variables should fail the manifest without creating any scalers
+        worker
+            .handle_manifest_published(
+                lattice_id,
+                &ManifestPublished {
+                    manifest: manifest.clone(),
+                    variables: BTreeMap::from([("NOTTAG".to_string(), "0.1.0".to_string())]),
+                },
+            )
+            .await
+            .expect("Should not error on undefined variables");
+        assert!(
+            worker.scalers.get_scalers("templated").await.is_none(),
+            "No scalers should be created for a manifest with undefined variables"
+        );
+        let status: Status = serde_json::from_value(
+            publisher
+                .received
+                .write()
+                .await
+                .pop()
+                .expect("Should have published a status"),
+        )
+        .unwrap();
+        assert_eq!(status.info.status_type, StatusType::Failed);
+        assert!(status.info.message.contains("TAG"));
+
+        // Placeholders are still checked when no variables are given at all
+        worker
+            .handle_manifest_published(
+                lattice_id,
+                &ManifestPublished {
+                    manifest: manifest.clone(),
+                    variables: BTreeMap::new(),
+                },
+            )
+            .await
+            .expect("Should not error on a manifest deployed without variables");
+        assert!(
+            worker.scalers.get_scalers("templated").await.is_none(),
+            "No scalers should be created for a templated manifest deployed without variables"
+        );
+        let status: Status = serde_json::from_value(
+            publisher
+                .received
+                .write()
+                .await
+                .pop()
+                .expect("Should have published a status"),
+        )
+        .unwrap();
+        assert_eq!(status.info.status_type, StatusType::Failed);
+        assert!(status.info.message.contains("TAG"));
+
+        worker
+            .handle_manifest_published(
+                lattice_id,
+                &ManifestPublished {
+                    manifest,
+                    variables: BTreeMap::from([("TAG".to_string(), "0.1.0".to_string())]),
+                },
+            )
+            .await
+            .expect("Should be able to handle templated manifest");
+
+        let commands = publisher
+            .received
+            .read()
+            .await
+            .iter()
+            .filter_map(|v| serde_json::from_value::<Command>(v.clone()).ok())
+            .collect::<Vec<_>>();
+        assert_eq!(commands.len(), 1, "Should have published a single command");
+        let Command::ScaleComponent(scale) = &commands[0] else {
+            panic!("Should have published a scale component command");
+        };
+        assert_eq!(
+            scale.reference,
+            "ghcr.io/wasmcloud/components/http-hello-world-rust:0.1.0"
+        );
+        assert_eq!(scale.count, 3);
+    }
+
+    /// A publisher that records everything it publishes along with the subject it was sent to
+    #[derive(Clone, Default)]
+    struct SubjectRecorder {
+        received: Arc<RwLock<Vec<(String, serde_json::Value)>>>,
+    }
+
+    #[async_trait::async_trait]
+    impl Publisher for SubjectRecorder {
+        async fn publish(&self, data: Vec<u8>, destination: Option<&str>) -> anyhow::Result<()> {
+            self.received.write().await.push((
+                destination.unwrap_or_default().to_owned(),
+                serde_json::from_slice(&data)?,
+            ));
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_dry_run() {
+        let store = Arc::new(TestStore::default());
+        let lattice_source = TestLatticeSource::default();
+        let lattice_id = "dry_run";
+
+        let publisher = SubjectRecorder::default();
+        let command_publisher = CommandPublisher::new(publisher.clone(), "wadm.cmd.dry_run");
+        let status_publisher = StatusPublisher::new(publisher.clone(), None, "doesntmatter");
+        let worker = EventWorker::new(
+            store.clone(),
+            lattice_source.clone(),
+            command_publisher.clone(),
+            status_publisher.clone(),
+            ScalerManager::test_new(
+                publisher.clone(),
+                lattice_id,
+                store.clone(),
+                command_publisher,
+                status_publisher.clone(),
+                lattice_source,
+            )
+            .await,
+        )
+        .with_dry_run(true);
+
+        store
+            .store(
+                lattice_id,
+                "dryrunhost".to_string(),
+                Host {
+                    id: "dryrunhost".to_string(),
+                    last_seen: chrono::Utc::now(),
+                    ..Default::default()
+                },
+            )
+            .await
+            .unwrap();
+
+        let manifest: wadm_types::Manifest = serde_yaml::from_str(
+            r#"
+apiVersion: core.oam.dev/v1beta1
+kind: Application
+metadata:
+  name: dryrun
+  annotations:
+    description: 'An app that is never deployed'
+spec:
+  components:
+    - name: hello
+      type: component
+      properties:
+        image: ghcr.io/wasmcloud/components/http-hello-world-rust:0.1.0
+        id: http_hello_world
+      traits:
+        - type: spreadscaler
+          properties:
+            instances: 3
+"#,
+        )
+        .unwrap();
+
+        worker
+            .handle_manifest_published(
+                lattice_id,
+                &ManifestPublished {
+                    manifest,
+                    variables: BTreeMap::new(),
+                },
+            )
+            .await
+            .expect("Should be able to handle manifest in dry run");
+
+        let received = publisher.received.read().await;
+        let commands = received
+            .iter()
+            .filter_map(|(subject, v)| {
+                serde_json::from_value::<Command>(v.clone())
+                    .ok()
+                    .map(|command| (subject, command))
+            })
+            .collect::<Vec<_>>();
+        assert!(
+            commands
+                .iter()
+                .any(|(_, command)| matches!(command, Command::ScaleComponent(_))),
+            "The commands the scalers would execute should still be published"
+        );
+        for (subject, command) in commands {
+            assert_eq!(
+                *subject,
+                format!("{DRY_RUN_SUBJECT_PREFIX}.wadm.cmd.dry_run"),
+                "Command {command:?} should only be published on the dry run subject"
+            );
+        }
+
+        let status = received
+            .iter()
+            .rev()
+            .find_map(|(_, v)| serde_json::from_value::<Status>(v.clone()).ok())
+            .expect("Should have published a status");
+        assert!(
+            status.info.message.starts_with(DRY_RUN_STATUS_MESSAGE),
+            "Status should note that dry run is enabled"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_manifest_requiring_newer_wadm() {
+        let store = Arc::new(TestStore::default());
+        let lattice_source = TestLatticeSource::default();
+        let lattice_id = "newer_wadm";
+
+        let publisher = RecorderPublisher::<serde_json::Value> {
+            received: Arc::new(RwLock::new(Vec::new())),
+        };
+        let command_publisher = CommandPublisher::new(publisher.clone(), "doesntmatter");
+        let status_publisher = StatusPublisher::new(publisher.clone(), None, "doesntmatter");
+        let worker = EventWorker::new(
+            store.clone(),
+            lattice_source.clone(),
+            command_publisher.clone(),
+            status_publisher.clone(),
+            ScalerManager::test_new(
+                publisher.clone(),
+                lattice_id,
+                store.clone(),
+                command_publisher,
+                status_publisher.clone(),
+                lattice_source,
+            )
+            .await,
+        );
+
+        let mut manifest: wadm_types::Manifest = serde_yaml::from_str(
+            r#"
+apiVersion: core.oam.dev/v1beta1
+kind: Application
+metadata:
+  name: fromthefuture
+  annotations:
+    description: 'An app for a newer wadm'
+    experimental.wasmcloud.dev/min-wadm-version: '999.0.0'
+spec:
+  components:
+    - name: hello
+      type: component
+      properties:
+        image: ghcr.io/wasmcloud/components/http-hello-world-rust:0.1.0
+        id: http_hello_world
+      traits:
+        - type: spreadscaler
+          properties:
+            instances: 1
+"#,
+        )
+        .unwrap();
+
+        worker
+            .handle_manifest_published(
+                lattice_id,
+                &ManifestPublished {
+                    manifest: manifest.clone(),
+                    variables: BTreeMap::new(),
+                },
+            )
+            .await
+            .expect("Should not error on a manifest for a newer wadm");
+        assert!(
+            worker.scalers.get_scalers("fromthefuture").await.is_none(),
+            "No scalers should be created for a manifest that needs a newer wadm"
+        );
+        let status: Status = serde_json::from_value(
+            publisher
+                .received
+                .write()
+                .await
+                .pop()
+                .expect("Should have published a status"),
+        )
+        .unwrap();
+        assert_eq!(status.info.status_type, StatusType::Failed);
+        assert_eq!(
+            status.info.message,
+            format!(
+                "Manifest requires wadm 999.0.0 or newer, but this is wadm {}. Upgrade wadm to deploy this manifest",
+                env!("CARGO_PKG_VERSION")
+            )
+        );
+
+        // A manifest for this version of wadm or older deploys as usual
+        manifest.metadata.annotations.insert(
+            wadm_types::MIN_WADM_VERSION_ANNOTATION_KEY.to_string(),
+            "0.1.0".to_string(),
+        );
+        worker
+            .handle_manifest_published(
+                lattice_id,
+                &ManifestPublished {
+                    manifest,
+                    variables: BTreeMap::new(),
+                },
+            )
+            .await
+            .expect("Should be able to handle manifest");
+        assert!(worker.scalers.get_scalers("fromthefuture").await.is_some());
+    }
+
+    #[tokio::test]
+    async fn test_queued_deploy() {
+        let store = Arc::new(TestStore::default());
+        let lattice_source = TestLatticeSource::default();
+        let lattice_id = "queued_deploy";
+
+        let publisher = RecorderPublisher::<serde_json::Value> {
+            received: Arc::new(RwLock::new(Vec::new())),
+        };
+        let command_publisher = CommandPublisher::new(publisher.clone(), "doesntmatter");
+        let status_publisher = StatusPublisher::new(publisher.clone(), None, "doesntmatter");
+        let worker = EventWorker::new(
+            store.clone(),
+            lattice_source.clone(),
+            command_publisher.clone(),
+            status_publisher.clone(),
+            ScalerManager::test_new(
+                publisher.clone(),
+                lattice_id,
+                store.clone(),
+                command_publisher,
+                status_publisher.clone(),
+                lattice_source,
+            )
+            .await,
+        )
+        .with_deploy_conflict_policy(DeployConflictPolicy::Queue);
+
+        store
+            .store(
+                lattice_id,
+                "queuehost".to_string(),
+                Host {
+                    id: "queuehost".to_string(),
+                    last_seen: chrono::Utc::now(),
+                    ..Default::default()
+                },
+            )
+            .await
+            .unwrap();
+
+        let manifest = |tag: &str| ManifestPublished {
+            manifest: serde_yaml::from_str(&format!(
+                r#"
+apiVersion: core.oam.dev/v1beta1
+kind: Application
+metadata:
+  name: queued
+  annotations:
+    version: {tag}
+spec:
+  components:
+    - name: hello
+      type: component
+      properties:
+        image: ghcr.io/wasmcloud/components/http-hello-world-rust:{tag}
+        id: http_hello_world
+      traits:
+        - type: spreadscaler
+          properties:
+            instances: 3
+"#
+            ))
+            .unwrap(),
+            variables: BTreeMap::new(),
+        };
+        let scale_commands = || async {
+            publisher
+                .received
+                .read()
+                .await
+                .iter()
+                .filter_map(|v| match serde_json::from_value::<Command>(v.clone()) {
+                    Ok(Command::ScaleComponent(scale)) => Some((scale.reference, scale.count)),
+                    _ => None,
+                })
+                .collect::<Vec<_>>()
+        };
+        let hello = |tag: &str| format!("ghcr.io/wasmcloud/components/http-hello-world-rust:{tag}");
+
+        worker
+            .handle_manifest_published(lattice_id, &manifest("0.1.0"))
+            .await
+            .expect("Should be able to deploy the first version");
+        assert!(worker.is_deploy_in_flight("queued").await);
+
+        // Re-putting while the first version is still converging shouldn't touch the scalers
+        worker
+            .handle_manifest_published(lattice_id, &manifest("0.2.0"))
+            .await
+            .expect("Should be able to queue the second version");
+        assert_eq!(scale_commands().await, vec![(hello("0.1.0"), 3)]);
+        assert!(worker.queued_deploys.read().await.contains_key("queued"));
+
+        // The first version converges, which lets the queued version deploy
+        let mut annotations = worker
+            .scalers
+            .get_scalers("queued")
+            .await
+            .expect("Scalers should exist")
+            .iter()
+            .find(|scaler| scaler.kind() == SPREAD_SCALER_KIND)
+            .map(|scaler| spreadscaler_annotations("default", scaler.id()))
+            .expect("Should have a spread scaler");
+        insert_managed_annotations(&mut annotations, "queued");
+        worker
+            .do_work(ScopedMessage {
+                lattice_id: lattice_id.to_string(),
+                inner: Event::ComponentScaled(ComponentScaled {
+                    annotations,
+                    claims: None,
+                    image_ref: hello("0.1.0"),
+                    max_instances: 3,
+                    component_id: "http_hello_world".to_string(),
+                    host_id: "queuehost".to_string(),
+                }),
+                acker: None,
+                ack_batcher: None,
+                published: None,
+            })
+            .await
+            .expect("Should be able to handle the scaled event");
+
+        assert!(worker.queued_deploys.read().await.is_empty());
+        // Only the image changed, so the running instances are updated in place rather than
+        // replaced
+        assert_eq!(scale_commands().await, vec![(hello("0.1.0"), 3)]);
+        let updates = publisher
+            .received
+            .read()
+            .await
+            .iter()
+            .filter_map(|v| match serde_json::from_value::<Command>(v.clone()) {
+                Ok(Command::UpdateComponent(update)) => {
+                    Some((update.host_id, update.new_reference))
+                }
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            updates,
+            vec![("queuehost".to_string(), hello("0.2.0"))],
+            "The queued version should replace the first version once it converged"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_queued_deploy_preempts_after_timeout() {
+        let store = Arc::new(TestStore::default());
+        let lattice_source = TestLatticeSource::default();
+        let lattice_id = "queued_deploy_timeout";
+
+        let publisher = RecorderPublisher::<serde_json::Value> {
+            received: Arc::new(RwLock::new(Vec::new())),
+        };
+        let command_publisher = CommandPublisher::new(publisher.clone(), "doesntmatter");
+        let status_publisher = StatusPublisher::new(publisher.clone(), None, "doesntmatter");
+        let worker = EventWorker::new(
+            store.clone(),
+            lattice_source.clone(),
+            command_publisher.clone(),
+            status_publisher.clone(),
+            ScalerManager::test_new(
+                publisher.clone(),
+                lattice_id,
+                store.clone(),
+                command_publisher,
+                status_publisher.clone(),
+                lattice_source,
+            )
+            .await,
+        )
+        .with_deploy_conflict_policy(DeployConflictPolicy::Queue)
+        .with_deploy_queue_timeout(Duration::from_millis(50));
+
+        store
+            .store(
+                lattice_id,
+                "queuehost".to_string(),
+                Host {
+                    id: "queuehost".to_string(),
+                    last_seen: chrono::Utc::now(),
+                    ..Default::default()
+                },
+            )
+            .await
+            .unwrap();
+
+        let manifest = |tag: &str| ManifestPublished {
+            manifest: serde_yaml::from_str(&format!(
+                r#"
+apiVersion: core.oam.dev/v1beta1
+kind: Application
+metadata:
+  name: stuck
+  annotations:
+    version: {tag}
+spec:
+  components:
+    - name: hello
+      type: component
+      properties:
+        image: ghcr.io/wasmcloud/components/http-hello-world-rust:{tag}
+        id: http_hello_world
+      traits:
+        - type: spreadscaler
+          properties:
+            instances: 3
+"#
+            ))
+            .unwrap(),
+            variables: BTreeMap::new(),
+        };
+
+        worker
+            .handle_manifest_published(lattice_id, &manifest("0.1.0"))
+            .await
+            .expect("Should be able to deploy the first version");
+        worker
+            .handle_manifest_published(lattice_id, &manifest("0.2.0"))
+            .await
+            .expect("Should be able to queue the second version");
+        assert!(worker.queued_deploys.read().await.contains_key("stuck"));
+
+        // The first version never converges, so the queued version eventually preempts it
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        worker
+            .do_work(ScopedMessage {
+                lattice_id: lattice_id.to_string(),
+                inner: Event::ComponentScaled(ComponentScaled {
+                    annotations: BTreeMap::new(),
+                    claims: None,
+                    image_ref: "ghcr.io/wasmcloud/components/unrelated:0.1.0".to_string(),
+                    max_instances: 1,
+                    component_id: "unrelated".to_string(),
+                    host_id: "queuehost".to_string(),
+                }),
+                acker: None,
+                ack_batcher: None,
+                published: None,
+            })
+            .await
+            .expect("Should be able to handle an unrelated event");
+
+        assert!(worker.queued_deploys.read().await.is_empty());
+        let deployed = worker
+            .deployed_manifests
+            .read()
+            .await
+            .get("stuck")
+            .map(|manifest| manifest.version().to_owned());
+        assert_eq!(
+            deployed.as_deref(),
+            Some("0.2.0"),
+            "The queued version should be deployed once it waited too long"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_model_deployed_event() {
+        let store = Arc::new(TestStore::default());
+        let lattice_source = TestLatticeSource::default();
+        let lattice_id = "model_deployed";
+
+        let publisher = RecorderPublisher::<serde_json::Value> {
+            received: Arc::new(RwLock::new(Vec::new())),
+        };
+        let command_publisher = CommandPublisher::new(publisher.clone(), "doesntmatter");
+        let status_publisher = StatusPublisher::new(publisher.clone(), None, "doesntmatter");
+        let worker = EventWorker::new(
+            store.clone(),
+            lattice_source.clone(),
+            command_publisher.clone(),
+            status_publisher.clone(),
+            ScalerManager::test_new(
+                publisher.clone(),
+                lattice_id,
+                store.clone(),
+                command_publisher,
+                status_publisher.clone(),
+                lattice_source,
+            )
+            .await,
+        )
+        .with_deploy_events(DeployEventPublisher::new(
+            publisher.clone(),
+            DEPLOYED_SUBJECT_PREFIX,
+        ));
+
+        store
+            .store(
+                lattice_id,
+                "deployhost".to_string(),
+                Host {
+                    id: "deployhost".to_string(),
+                    last_seen: chrono::Utc::now(),
+                    ..Default::default()
+                },
+            )
+            .await
+            .unwrap();
+
+        let image_ref = "ghcr.io/wasmcloud/components/http-hello-world-rust:0.1.0";
+        let manifest = ManifestPublished {
+            manifest: serde_yaml::from_str(&format!(
+                r#"
+apiVersion: core.oam.dev/v1beta1
+kind: Application
+metadata:
+  name: deployed
+  annotations:
+    version: v0.1.0
+spec:
+  components:
+    - name: hello
+      type: component
+      properties:
+        image: {image_ref}
+        id: http_hello_world
+      traits:
+        - type: spreadscaler
+          properties:
+            instances: 3
+"#
+            ))
+            .unwrap(),
+            variables: BTreeMap::new(),
+        };
+        let deployed_events = || async {
+            publisher
+                .received
+                .read()
+                .await
+                .iter()
+                .filter_map(|v| serde_json::from_value::<ModelDeployed>(v.clone()).ok())
+                .collect::<Vec<_>>()
+        };
+
+        worker
+            .handle_manifest_published(lattice_id, &manifest)
+            .await
+            .expect("Should be able to deploy the manifest");
+        assert!(
+            deployed_events().await.is_empty(),
+            "No event should be sent before the model is ready"
+        );
+
+        let mut annotations = worker
+            .scalers
+            .get_scalers("deployed")
+            .await
+            .expect("Scalers should exist")
+            .iter()
+            .find(|scaler| scaler.kind() == SPREAD_SCALER_KIND)
+            .map(|scaler| spreadscaler_annotations("default", scaler.id()))
+            .expect("Should have a spread scaler");
+        insert_managed_annotations(&mut annotations, "deployed");
+        let scaled = Event::ComponentScaled(ComponentScaled {
+            annotations,
+            claims: None,
+            image_ref: image_ref.to_string(),
+            max_instances: 3,
+            component_id: "http_hello_world".to_string(),
+            host_id: "deployhost".to_string(),
+        });
+        worker
+            .do_work(ScopedMessage {
+                lattice_id: lattice_id.to_string(),
+                inner: scaled.clone(),
+                acker: None,
+                ack_batcher: None,
+                published: None,
+            })
+            .await
+            .expect("Should be able to handle the scaled event");
+
+        // Further events while the model stays ready shouldn't send the event again
+        worker
+            .run_all_scalers(lattice_id, &scaled)
+            .await
+            .expect("Should be able to run scalers");
+
+        let events = deployed_events().await;
+        assert_eq!(events.len(), 1, "Exactly one deployed event should be sent");
+        let event = &events[0];
+        assert_eq!(event.model_name, "deployed");
+        assert_eq!(event.version, "v0.1.0");
+        assert_eq!(event.lattice_id, lattice_id);
+        assert_eq!(
+            event.resources,
+            DeployedResources {
+                component_instances: 3,
+                providers: 0,
+                links: 0,
+            }
+        );
+    }
+
+    /// A scaler that does nothing except report the greeting it was configured with
+    struct GreetingScaler {
+        id: String,
+        greeting: String,
+    }
+
+    #[async_trait::async_trait]
+    impl Scaler for GreetingScaler {
+        fn id(&self) -> &str {
+            &self.id
+        }
+
+        fn kind(&self) -> &str {
+            "GreetingScaler"
+        }
+
+        async fn status(&self) -> StatusInfo {
+            StatusInfo::deployed(&self.greeting)
+        }
+
+        async fn update_config(&mut self, _config: TraitProperty) -> Result<Vec<Command>> {
+            Ok(Vec::new())
+        }
+
+        async fn handle_event(&self, _event: &Event) -> Result<Vec<Command>> {
+            Ok(Vec::new())
+        }
+
+        async fn reconcile(&self) -> Result<Vec<Command>> {
+            Ok(Vec::new())
+        }
+
+        async fn cleanup(&self) -> Result<Vec<Command>> {
+            Ok(Vec::new())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_out_of_band_link_deletion() {
+        let store = Arc::new(TestStore::default());
+        let lattice_source = TestLatticeSource::default();
+        let lattice_id = "link_deleted";
+
+        let publisher = RecorderPublisher::<serde_json::Value> {
+            received: Arc::new(RwLock::new(Vec::new())),
+        };
+        let command_publisher = CommandPublisher::new(publisher.clone(), "doesntmatter");
+        let status_publisher = StatusPublisher::new(publisher.clone(), None, "doesntmatter");
+        let worker = EventWorker::new(
+            store.clone(),
+            lattice_source.clone(),
+            command_publisher.clone(),
+            status_publisher.clone(),
+            ScalerManager::test_new(
+                publisher.clone(),
+                lattice_id,
+                store.clone(),
+                command_publisher,
+                status_publisher.clone(),
+                lattice_source,
+            )
+            .await,
+        );
+
+        let manifest = ManifestPublished {
+            manifest: serde_yaml::from_str(
+                r#"
+apiVersion: core.oam.dev/v1beta1
+kind: Application
+metadata:
+  name: linked
+  annotations:
+    version: v0.1.0
+spec:
+  components:
+    - name: hello
+      type: component
+      properties:
+        image: ghcr.io/wasmcloud/components/http-hello-world-rust:0.1.0
+      traits:
+        - type: link
+          properties:
+            target: other
+            namespace: wasi
+            package: keyvalue
+            interfaces: [store]
+    - name: other
+      type: component
+      properties:
+        image: ghcr.io/wasmcloud/components/keyvalue-rust:0.1.0
+"#,
+            )
+            .unwrap(),
+            variables: BTreeMap::new(),
+        };
+        let put_links = || async {
+            publisher
+                .received
+                .read()
+                .await
+                .iter()
+                .filter_map(|v| match serde_json::from_value::<Command>(v.clone()) {
+                    Ok(Command::PutLink(put)) => Some(put),
+                    _ => None,
+                })
+                .collect::<Vec<_>>()
+        };
+
+        worker
+            .handle_manifest_published(lattice_id, &manifest)
+            .await
+            .expect("Should be able to deploy the manifest");
+        let put = put_links()
+            .await
+            .pop()
+            .expect("Deploying should put the link");
+        let key = LinkState::key(&put.source_id, "wasi", "keyvalue", &put.name);
+        store
+            .store(lattice_id, key.clone(), LinkState::from(&put))
+            .await
+            .unwrap();
+        publisher.received.write().await.clear();
+
+        // Something other than wadm deletes the link
+        worker
+            .do_work(ScopedMessage {
+                lattice_id: lattice_id.to_string(),
+                inner: Event::LinkdefDeleted(LinkdefDeleted {
+                    source_id: put.source_id.clone(),
+                    name: put.name.clone(),
+                    wit_namespace: "wasi".to_string(),
+                    wit_package: "keyvalue".to_string(),
+                }),
+                acker: None,
+                ack_batcher: None,
+                published: None,
+            })
+            .await
+            .expect("Should be able to handle the link deleted event");
+
+        assert!(
+            store
+                .get::<LinkState>(lattice_id, &key)
+                .await
+                .unwrap()
+                .is_none(),
+            "The deleted link should be removed from state"
+        );
+        assert_eq!(
+            put_links().await,
+            vec![put],
+            "The model that owned the link should put it back"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_custom_scaler_registry() {
+        let store = Arc::new(TestStore::default());
+        let lattice_source = TestLatticeSource::default();
+        let lattice_id = "custom_scaler";
+
+        let mut registry = ScalerRegistry::default();
+        registry.register("greeter", |ctx: ScalerContext<'_>| {
+            let TraitProperty::Custom(props) = &ctx.scaler_trait.properties else {
+                anyhow::bail!("greeter trait requires custom properties");
+            };
+            let greeting = props
+                .get("greeting")
+                .and_then(|g| g.as_str())
+                .ok_or_else(|| anyhow::anyhow!("greeter trait requires a greeting"))?;
+            Ok(Box::new(GreetingScaler {
+                id: format!("{}-{}-greeter", ctx.manifest_name, ctx.component.name),
+                greeting: greeting.to_owned(),
+            }) as BoxedScaler)
+        });
+
+        let command_publisher = CommandPublisher::new(NoopPublisher, "doesntmatter");
+        let status_publisher = StatusPublisher::new(NoopPublisher, None, "doesntmatter");
+        let worker = EventWorker::new(
+            store.clone(),
+            lattice_source.clone(),
+            command_publisher.clone(),
+            status_publisher.clone(),
+            ScalerManager::test_new(
+                NoopPublisher,
+                lattice_id,
+                store.clone(),
+                command_publisher,
+                status_publisher.clone(),
+                lattice_source,
+            )
+            .await
+            .with_scaler_registry(registry),
+        );
+
+        let manifest: wadm_types::Manifest = serde_yaml::from_str(
+            r#"
+apiVersion: core.oam.dev/v1beta1
+kind: Application
+metadata:
+  name: greetings
+  annotations:
+    description: 'An app with a custom scaler'
+spec:
+  components:
+    - name: hello
+      type: component
+      properties:
+        image: ghcr.io/wasmcloud/components/http-hello-world-rust:0.1.0
+        id: http_hello_world
+      traits:
+        - type: spreadscaler
+          properties:
+            instances: 1
+        - type: greeter
+          properties:
+            greeting: hello there
+"#,
+        )
+        .unwrap();
+
+        worker
+            .handle_manifest_published(
+                lattice_id,
+                &ManifestPublished {
+                    manifest,
+                    variables: BTreeMap::new(),
+                },
+            )
+            .await
+            .expect("Should be able to handle manifest with a custom trait");
+
+        let scalers = worker
+            .scalers
+            .get_scalers("greetings")
+            .await
+            .expect("Scalers should exist for the manifest");
+        assert_eq!(
+            scalers.len(),
+            2,
+            "Should have the built in scaler and the custom scaler"
+        );
+        let custom = scalers
+            .iter()
+            .find(|s| s.kind() == "GreetingScaler")
+            .expect("Custom scaler should have been created by the factory");
+        assert_eq!(custom.id(), "greetings-hello-greeter");
+        assert_eq!(custom.status().await.message, "hello there");
+    }
+
+    #[tokio::test]
+    async fn test_stop_confirmed_with_inventory() {
+        let lattice_id = "confirm_stops";
+        let host_id = "stubbornhost";
+        let component_id = "stubborn_component";
+        let provider_id = "stubborn_provider";
+        let store = Arc::new(TestStore::default());
+        let inventory = Arc::new(RwLock::new(HashMap::default()));
+        let lattice_source = TestLatticeSource {
+            inventory: inventory.clone(),
+            ..Default::default()
+        };
+        let worker = EventWorker::new(
+            store.clone(),
+            lattice_source.clone(),
+            CommandPublisher::new(NoopPublisher, "doesntmatter"),
+            StatusPublisher::new(NoopPublisher, None, "doesntmatter"),
+            ScalerManager::test_new(
+                NoopPublisher,
+                lattice_id,
+                store.clone(),
+                CommandPublisher::new(NoopPublisher, "doesntmatter"),
+                StatusPublisher::new(NoopPublisher, None, "doesntmatter"),
+                lattice_source,
+            )
+            .await,
+        )
+        .with_stop_confirmation(true);
+
+        let mut annotations = BTreeMap::new();
+        insert_managed_annotations(&mut annotations, "stubborn");
+        store
+            .store(
+                lattice_id,
+                component_id.to_string(),
+                Component {
+                    id: component_id.to_string(),
+                    instances: HashMap::from_iter([(
+                        host_id.to_string(),
+                        HashSet::from_iter([WadmComponentInfo {
+                            annotations: annotations.clone(),
+                            count: 2,
+                        }]),
+                    )]),
+                    ..Default::default()
+                },
+            )
+            .await
+            .unwrap();
+        store
+            .store(
+                lattice_id,
+                provider_id.to_string(),
+                Provider {
+                    id: provider_id.to_string(),
+                    hosts: HashMap::from_iter([(
+                        host_id.to_string(),
+                        ProviderStatus::Running.into(),
+                    )]),
+                    ..Default::default()
+                },
+            )
+            .await
+            .unwrap();
+
+        // The stop only partially succeeded, so the host is still running everything
+        *inventory.write().await = HashMap::from_iter([(
+            host_id.to_string(),
+            HostInventory::builder()
+                .friendly_name("stubborn".into())
+                .components(vec![ComponentDescription::builder()
+                    .id(component_id.into())
+                    .image_ref("ref".into())
+                    .revision(0)
+                    .max_instances(1)
+                    .build()
+                    .expect("failed to build description")])
+                .host_id(host_id.into())
+                .providers(vec![ProviderDescription::builder()
+                    .id(provider_id)
+                    .revision(0)
+                    .build()
+                    .expect("failed to build provider description")])
+                .version(semver::Version::parse("0.61.0").unwrap().to_string())
+                .uptime_human("60s".into())
+                .uptime_seconds(60)
+                .build()
+                .expect("failed to build host inventory"),
+        )]);
+
+        worker
+            .handle_component_scaled(
+                lattice_id,
+                &ComponentScaled {
+                    annotations: annotations.clone(),
+                    claims: None,
+                    image_ref: "ref".to_string(),
+                    max_instances: 0,
+                    component_id: component_id.to_string(),
+                    host_id: host_id.to_string(),
+                },
+            )
+            .await
+            .expect("Should be able to handle component scaled event");
+        worker
+            .handle_provider_stopped(
+                lattice_id,
+                &ProviderStopped {
+                    annotations: annotations.clone(),
+                    provider_id: provider_id.to_string(),
+                    reason: String::new(),
+                    host_id: host_id.to_string(),
+                },
+            )
+            .await
+            .expect("Should be able to handle provider stopped event");
+
+        let component = store
+            .get::<Component>(lattice_id, component_id)
+            .await
+            .unwrap()
+            .expect("Component still running on the host should not be removed from state");
+        assert_eq!(
+            component
+                .instances
+                .get(host_id)
+                .and_then(|instances| instances.iter().next())
+                .map(|info| info.count),
+            Some(1),
+            "State should be reconciled with the running count from the inventory"
+        );
+        let provider = store
+            .get::<Provider>(lattice_id, provider_id)
+            .await
+            .unwrap()
+            .expect("Provider still running on the host should not be removed from state");
+        assert!(provider.hosts.contains_key(host_id));
+
+        // Once the inventory agrees the stop happened, the state is removed
+        *inventory.write().await = HashMap::from_iter([(
+            host_id.to_string(),
+            HostInventory::builder()
+                .friendly_name("stubborn".into())
+                .host_id(host_id.into())
+                .version(semver::Version::parse("0.61.0").unwrap().to_string())
+                .uptime_human("60s".into())
+                .uptime_seconds(60)
+                .build()
+                .expect("failed to build host inventory"),
+        )]);
+        worker
+            .handle_provider_stopped(
+                lattice_id,
+                &ProviderStopped {
+                    annotations,
+                    provider_id: provider_id.to_string(),
+                    reason: String::new(),
+                    host_id: host_id.to_string(),
+                },
+            )
+            .await
+            .expect("Should be able to handle provider stopped event");
+        assert!(store
+            .get::<Provider>(lattice_id, provider_id)
+            .await
+            .unwrap()
+            .is_none());
+    }
+
+    #[tokio::test]
+    async fn test_patch_only_rebuilds_affected_scalers() {
+        let store = Arc::new(TestStore::default());
+        let lattice_source = TestLatticeSource::default();
+        let lattice_id = "patch_manifest";
+
+        let publisher = RecorderPublisher::<serde_json::Value> {
+            received: Arc::new(RwLock::new(Vec::new())),
+        };
+        let command_publisher = CommandPublisher::new(publisher.clone(), "doesntmatter");
+        let status_publisher = StatusPublisher::new(publisher.clone(), None, "doesntmatter");
+        let worker = EventWorker::new(
+            store.clone(),
+            lattice_source.clone(),
+            command_publisher.clone(),
+            status_publisher.clone(),
+            ScalerManager::test_new(
+                publisher.clone(),
+                lattice_id,
+                store.clone(),
+                command_publisher,
+                status_publisher.clone(),
+                lattice_source,
+            )
+            .await,
+        );
+
+        store
+            .store(
+                lattice_id,
+                "patchhost".to_string(),
+                Host {
+                    id: "patchhost".to_string(),
+                    last_seen: chrono::Utc::now(),
+                    ..Default::default()
+                },
+            )
+            .await
+            .unwrap();
+
+        let manifest: wadm_types::Manifest = serde_yaml::from_str(
+            r#"
+apiVersion: core.oam.dev/v1beta1
+kind: Application
+metadata:
+  name: patched
+  annotations:
+    version: v0.0.1
+spec:
+  components:
+    - name: hello
+      type: component
+      properties:
+        image: ghcr.io/wasmcloud/components/http-hello-world-rust:0.1.0
+      traits:
+        - type: spreadscaler
+          properties:
+            instances: 3
+    - name: world
+      type: component
+      properties:
+        image: ghcr.io/wasmcloud/components/http-keyvalue-counter-rust:0.1.0
+      traits:
+        - type: spreadscaler
+          properties:
+            instances: 2
+"#,
+        )
+        .unwrap();
+
+        // The address of each scaler, keyed by its name, so we can tell whether it was rebuilt
+        let scaler_addresses = || async {
+            worker
+                .scalers
+                .get_scalers("patched")
+                .await
+                .expect("Scalers should exist")
+                .iter()
+                .map(|scaler| {
+                    (
+                        scaler.name(),
+                        &**scaler as *const (dyn Scaler + Send + Sync) as *const () as usize,
+                    )
+                })
+                .collect::<HashMap<_, _>>()
+        };
+
+        worker
+            .handle_manifest_published(
+                lattice_id,
+                &ManifestPublished {
+                    manifest: manifest.clone(),
+                    variables: BTreeMap::new(),
+                },
+            )
+            .await
+            .expect("Should be able to handle manifest");
+        let before = scaler_addresses().await;
+        publisher.received.write().await.clear();
+
+        let mut patched = manifest
+            .patched(&[wadm_types::api::ComponentPatch {
+                name: "hello".to_string(),
+                instances: Some(5),
+                ..Default::default()
+            }])
+            .expect("Should be able to patch manifest");
+        patched.metadata.annotations.insert(
+            wadm_types::VERSION_ANNOTATION_KEY.to_string(),
+            "v0.0.2".to_string(),
+        );
+        worker
+            .handle_manifest_published(
+                lattice_id,
+                &ManifestPublished {
+                    manifest: patched,
+                    variables: BTreeMap::new(),
+                },
+            )
+            .await
+            .expect("Should be able to handle patched manifest");
+        let after = scaler_addresses().await;
+
+        let hello_id = crate::scaler::compute_component_id("patched", None, "hello");
+        let world_id = crate::scaler::compute_component_id("patched", None, "world");
+        assert_eq!(
+            before.keys().collect::<HashSet<_>>(),
+            after.keys().collect::<HashSet<_>>(),
+            "The same scalers should exist after the patch"
+        );
+        assert_ne!(
+            before[&hello_id], after[&hello_id],
+            "The patched component's scaler should be rebuilt"
+        );
+        assert_eq!(
+            before[&world_id], after[&world_id],
+            "Other scalers should be left untouched"
+        );
+
+        let commands = publisher
+            .received
+            .read()
+            .await
+            .iter()
+            .filter_map(|v| serde_json::from_value::<Command>(v.clone()).ok())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            commands.len(),
+            1,
+            "Only the patched scaler should publish commands, got {commands:?}"
+        );
+        let Command::ScaleComponent(scale) = &commands[0] else {
+            panic!("Should have published a scale component command");
+        };
+        assert_eq!(scale.component_id, hello_id);
+        assert_eq!(scale.count, 5);
+    }
+
+    /// A scaler that only counts how many times it was reconciled
+    struct ReconcileCountingScaler {
+        id: String,
+        reconciled: Arc<std::sync::atomic::AtomicUsize>,
+    }
+
+    #[async_trait::async_trait]
+    impl Scaler for ReconcileCountingScaler {
+        fn id(&self) -> &str {
+            &self.id
+        }
+
+        async fn status(&self) -> StatusInfo {
+            StatusInfo::deployed("")
+        }
+
+        async fn update_config(&mut self, _config: TraitProperty) -> Result<Vec<Command>> {
+            Ok(Vec::new())
+        }
+
+        async fn handle_event(&self, _event: &Event) -> Result<Vec<Command>> {
+            Ok(Vec::new())
+        }
+
+        async fn reconcile(&self) -> Result<Vec<Command>> {
+            self.reconciled
+                .fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+            Ok(Vec::new())
+        }
+
+        async fn cleanup(&self) -> Result<Vec<Command>> {
+            Ok(Vec::new())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_json_patch_leaves_unchanged_scalers_untouched() {
+        let store = Arc::new(TestStore::default());
+        let lattice_source = TestLatticeSource::default();
+        let lattice_id = "json_patch_manifest";
+
+        let reconciled = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let mut registry = ScalerRegistry::default();
+        let counter = reconciled.clone();
+        registry.register("reconcilecounter", move |ctx: ScalerContext<'_>| {
+            Ok(Box::new(ReconcileCountingScaler {
+                id: format!("{}-{}-counter", ctx.manifest_name, ctx.component.name),
+                reconciled: counter.clone(),
+            }) as BoxedScaler)
+        });
+
+        let publisher = RecorderPublisher::<serde_json::Value> {
+            received: Arc::new(RwLock::new(Vec::new())),
+        };
+        let command_publisher = CommandPublisher::new(publisher.clone(), "doesntmatter");
+        let status_publisher = StatusPublisher::new(publisher.clone(), None, "doesntmatter");
+        let worker = EventWorker::new(
+            store.clone(),
+            lattice_source.clone(),
+            command_publisher.clone(),
+            status_publisher.clone(),
+            ScalerManager::test_new(
+                publisher.clone(),
+                lattice_id,
+                store.clone(),
+                command_publisher,
+                status_publisher.clone(),
+                lattice_source,
+            )
+            .await
+            .with_scaler_registry(registry),
+        );
+
+        store
+            .store(
+                lattice_id,
+                "patchhost".to_string(),
+                Host {
+                    id: "patchhost".to_string(),
+                    last_seen: chrono::Utc::now(),
+                    ..Default::default()
+                },
+            )
+            .await
+            .unwrap();
+
+        let manifest: wadm_types::Manifest = serde_yaml::from_str(
+            r#"
+apiVersion: core.oam.dev/v1beta1
+kind: Application
+metadata:
+  name: jsonpatched
+  annotations:
+    version: v0.0.1
+spec:
+  components:
+    - name: hello
+      type: component
+      properties:
+        image: ghcr.io/wasmcloud/components/http-hello-world-rust:0.1.0
+      traits:
+        - type: spreadscaler
+          properties:
+            instances: 1
+    - name: world
+      type: component
+      properties:
+        image: ghcr.io/wasmcloud/components/http-keyvalue-counter-rust:0.1.0
+      traits:
+        - type: reconcilecounter
+          properties:
+            unused: true
+"#,
+        )
+        .unwrap();
+
+        worker
+            .handle_manifest_published(
+                lattice_id,
+                &ManifestPublished {
+                    manifest: manifest.clone(),
+                    variables: BTreeMap::new(),
+                },
+            )
+            .await
+            .expect("Should be able to handle manifest");
+        let reconciled_before = reconciled.load(std::sync::atomic::Ordering::SeqCst);
+        assert!(reconciled_before > 0, "New scalers should be reconciled");
+        // The address of the spreadscaler, so we can tell whether it was recreated
+        let hello_address = || async {
+            let scalers = worker.scalers.get_scalers("jsonpatched").await.unwrap();
+            let scaler = scalers
+                .iter()
+                .find(|scaler| scaler.id() != "jsonpatched-world-counter")
+                .expect("Should have a spreadscaler");
+            &**scaler as *const (dyn Scaler + Send + Sync) as *const () as usize
+        };
+        let hello_before = hello_address().await;
+        publisher.received.write().await.clear();
+
+        let patched = manifest
+            .json_patched(&[
+                wadm_types::api::JsonPatchOperation::Replace {
+                    path: "/spec/components/0/traits/0/properties/instances".to_string(),
+                    value: serde_json::json!(3),
+                },
+                wadm_types::api::JsonPatchOperation::Replace {
+                    path: "/metadata/annotations/version".to_string(),
+                    value: serde_json::json!("v0.0.2"),
+                },
+            ])
+            .expect("Should be able to apply JSON patch");
+        worker
+            .handle_manifest_published(
+                lattice_id,
+                &ManifestPublished {
+                    manifest: patched,
+                    variables: BTreeMap::new(),
+                },
+            )
+            .await
+            .expect("Should be able to handle patched manifest");
+
+        assert_eq!(
+            reconciled.load(std::sync::atomic::Ordering::SeqCst),
+            reconciled_before,
+            "Scalers that weren't rebuilt should not be reconciled again"
+        );
+        assert_ne!(
+            hello_address().await,
+            hello_before,
+            "The patched spreadscaler should be recreated"
+        );
+
+        let commands = publisher
+            .received
+            .read()
+            .await
+            .iter()
+            .filter_map(|v| serde_json::from_value::<Command>(v.clone()).ok())
+            .collect::<Vec<_>>();
+        let [Command::ScaleComponent(scale)] = commands.as_slice() else {
+            panic!("Only the patched scaler should publish commands, got {commands:?}");
+        };
+        assert_eq!(scale.count, 3);
+    }
+
+    /// A scaler that takes a while to handle events, tracking how many are handled at once
+    struct SlowScaler {
+        id: String,
+        in_flight: Arc<std::sync::atomic::AtomicUsize>,
+        max_in_flight: Arc<std::sync::atomic::AtomicUsize>,
+    }
+
+    #[async_trait::async_trait]
+    impl Scaler for SlowScaler {
+        fn id(&self) -> &str {
+            &self.id
+        }
+
+        async fn status(&self) -> StatusInfo {
+            StatusInfo::deployed("")
+        }
+
+        async fn update_config(&mut self, _config: TraitProperty) -> Result<Vec<Command>> {
+            Ok(Vec::new())
+        }
+
+        async fn handle_event(&self, _event: &Event) -> Result<Vec<Command>> {
+            use std::sync::atomic::Ordering;
+            let current = self.in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+            self.max_in_flight.fetch_max(current, Ordering::SeqCst);
+            tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+            self.in_flight.fetch_sub(1, Ordering::SeqCst);
+            Ok(vec![Command::from(crate::commands::ScaleComponent {
+                component_id: self.id.clone(),
+                ..Default::default()
+            })])
+        }
+
+        async fn reconcile(&self) -> Result<Vec<Command>> {
+            Ok(Vec::new())
+        }
+
+        async fn cleanup(&self) -> Result<Vec<Command>> {
+            Ok(Vec::new())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_scaler_concurrency_is_limited() {
+        let store = Arc::new(TestStore::default());
+        let lattice_source = TestLatticeSource::default();
+        let lattice_id = "scaler_concurrency";
+
+        let publisher = RecorderPublisher::<serde_json::Value> {
+            received: Arc::new(RwLock::new(Vec::new())),
+        };
+        let command_publisher = CommandPublisher::new(publisher.clone(), "doesntmatter");
+        let status_publisher = StatusPublisher::new(publisher.clone(), None, "doesntmatter");
+        let worker = EventWorker::new(
+            store.clone(),
+            lattice_source.clone(),
+            command_publisher.clone(),
+            status_publisher.clone(),
+            ScalerManager::test_new(
+                publisher.clone(),
+                lattice_id,
+                store.clone(),
+                command_publisher,
+                status_publisher.clone(),
+                lattice_source,
+            )
+            .await,
+        )
+        .with_scaler_concurrency(2);
+
+        let in_flight = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let max_in_flight = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        // Four slow scalers split between two models, as the limit applies across all models
+        for name in ["first", "second"] {
+            let manifest: wadm_types::Manifest = serde_yaml::from_str(&format!(
+                r#"
+apiVersion: core.oam.dev/v1beta1
+kind: Application
+metadata:
+  name: {name}
+  annotations:
+    description: 'A model with slow scalers'
+spec:
+  components: []
+"#
+            ))
+            .unwrap();
+            let scalers = (0..2)
+                .map(|idx| {
+                    Box::new(SlowScaler {
+                        id: format!("{name}-{idx}"),
+                        in_flight: in_flight.clone(),
+                        max_in_flight: max_in_flight.clone(),
+                    }) as BoxedScaler
+                })
+                .collect();
+            worker
+                .scalers
+                .add_scalers(&manifest, scalers)
+                .await
+                .unwrap();
+        }
+        publisher.received.write().await.clear();
+
+        worker
+            .run_all_scalers(
+                lattice_id,
+                &Event::ProviderStopped(ProviderStopped {
+                    annotations: BTreeMap::new(),
+                    provider_id: "gone".to_string(),
+                    reason: "stopped".to_string(),
+                    host_id: "host".to_string(),
+                }),
+            )
+            .await
+            .expect("Should be able to run all scalers");
+
+        assert_eq!(
+            max_in_flight.load(std::sync::atomic::Ordering::SeqCst),
+            2,
+            "At most two scalers should handle the event at once"
+        );
+        let mut handled = publisher
+            .received
+            .read()
+            .await
+            .iter()
+            .filter_map(|v| match serde_json::from_value::<Command>(v.clone()) {
+                Ok(Command::ScaleComponent(scale)) => Some(scale.component_id),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        handled.sort();
+        assert_eq!(
+            handled,
+            vec!["first-0", "first-1", "second-0", "second-1"],
+            "Commands from every scaler should still be published"
+        );
+    }
+
+    /// A scaler that only counts the events it was asked to handle
+    struct CountingScaler {
+        handled: Arc<std::sync::atomic::AtomicUsize>,
+    }
+
+    #[async_trait::async_trait]
+    impl Scaler for CountingScaler {
+        fn id(&self) -> &str {
+            "counting"
+        }
+
+        fn kind(&self) -> &str {
+            "CountingScaler"
+        }
+
+        async fn status(&self) -> StatusInfo {
+            StatusInfo::deployed("")
+        }
+
+        async fn update_config(&mut self, _config: TraitProperty) -> Result<Vec<Command>> {
+            Ok(Vec::new())
+        }
+
+        async fn handle_event(&self, _event: &Event) -> Result<Vec<Command>> {
+            self.handled
+                .fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+            Ok(Vec::new())
+        }
+
+        async fn reconcile(&self) -> Result<Vec<Command>> {
+            Ok(Vec::new())
+        }
+
+        async fn cleanup(&self) -> Result<Vec<Command>> {
+            Ok(Vec::new())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_teardown_events_skip_scalers() {
+        let store = Arc::new(TestStore::default());
+        let lattice_source = TestLatticeSource::default();
+        let lattice_id = "teardown_events";
+
+        let command_publisher = CommandPublisher::new(NoopPublisher, "doesntmatter");
+        let status_publisher = StatusPublisher::new(NoopPublisher, None, "doesntmatter");
+        let worker = EventWorker::new(
+            store.clone(),
+            lattice_source.clone(),
+            command_publisher.clone(),
+            status_publisher.clone(),
+            ScalerManager::test_new(
+                NoopPublisher,
+                lattice_id,
+                store.clone(),
+                command_publisher,
+                status_publisher.clone(),
+                lattice_source,
+            )
+            .await
+            .with_teardown_period(std::time::Duration::from_secs(30)),
+        );
+
+        let manifest = |name: &str| -> wadm_types::Manifest {
+            serde_yaml::from_str(&format!(
+                r#"
+apiVersion: core.oam.dev/v1beta1
+kind: Application
+metadata:
+  name: {name}
+  annotations:
+    description: 'A model without any components'
+spec:
+  components: []
+"#
+            ))
+            .unwrap()
+        };
+        let handled = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        worker
+            .scalers
+            .add_scalers(
+                &manifest("bystander"),
+                vec![Box::new(CountingScaler {
+                    handled: handled.clone(),
+                }) as BoxedScaler],
+            )
+            .await
+            .unwrap();
+        worker
+            .scalers
+            .add_scalers(&manifest("leaving"), Vec::new())
+            .await
+            .unwrap();
+        worker
+            .scalers
+            .remove_scalers("leaving")
+            .await
+            .expect("Scalers should exist for the model")
+            .expect("Should be able to remove scalers");
+        assert!(worker.scalers.is_tearing_down("leaving").await);
+
+        let scale_failed = |model_name: &str| {
+            let mut annotations = BTreeMap::new();
+            insert_managed_annotations(&mut annotations, model_name);
+            ScopedMessage {
+                lattice_id: lattice_id.to_string(),
+                inner: Event::ComponentScaleFailed(ComponentScaleFailed {
+                    annotations,
+                    claims: None,
+                    image_ref: "ghcr.io/wasmcloud/components/leaving:0.1.0".to_string(),
+                    max_instances: 1,
+                    component_id: "leaving_component".to_string(),
+                    host_id: "teardownhost".to_string(),
+                    error: "host is shutting down".to_string(),
+                }),
+                acker: None,
+                ack_batcher: None,
+                published: None,
+            }
+        };
+        worker
+            .do_work(scale_failed("leaving"))
+            .await
+            .expect("Event for a model being torn down should be acked");
+        assert_eq!(
+            handled.load(std::sync::atomic::Ordering::SeqCst),
+            0,
+            "Events for a model being torn down should not run all scalers"
+        );
+
+        worker
+            .do_work(scale_failed("elsewhere"))
+            .await
+            .expect("Should be able to handle the event");
+        assert_eq!(
+            handled.load(std::sync::atomic::Ordering::SeqCst),
+            1,
+            "Other events should still be handled by all scalers"
+        );
+
+        worker
+            .scalers
+            .add_scalers(&manifest("leaving"), Vec::new())
+            .await
+            .unwrap();
+        assert!(
+            !worker.scalers.is_tearing_down("leaving").await,
+            "Deploying the model again should end its teardown"
+        );
+    }
+
+    /// A store that counts how many times it has been written to and records which kinds of data
+    /// have been listed
+    #[derive(Clone, Default)]
+    struct CountingStore {
+        inner: Arc<TestStore>,
+        writes: Arc<std::sync::atomic::AtomicUsize>,
+        listed: Arc<std::sync::Mutex<Vec<&'static str>>>,
+    }
+
+    impl CountingStore {
+        fn writes(&self) -> usize {
+            self.writes.load(std::sync::atomic::Ordering::SeqCst)
+        }
+
+        fn listed(&self) -> Vec<&'static str> {
+            self.listed.lock().unwrap().clone()
+        }
+    }
+
+    #[async_trait::async_trait]
+    impl ReadStore for CountingStore {
+        async fn get<T>(&self, lattice_id: &str, id: &str) -> Result<Option<T>, StoreError>
+        where
+            T: serde::de::DeserializeOwned + StateKind,
+        {
+            self.inner.get(lattice_id, id).await
+        }
+
+        async fn list<T>(&self, lattice_id: &str) -> Result<HashMap<String, T>, StoreError>
+        where
+            T: serde::de::DeserializeOwned + StateKind,
+        {
+            self.listed.lock().unwrap().push(T::KIND);
+            self.inner.list(lattice_id).await
+        }
+    }
+
+    #[async_trait::async_trait]
+    impl Store for CountingStore {
+        async fn store_many<T, D>(&self, lattice_id: &str, data: D) -> Result<(), StoreError>
+        where
+            T: serde::Serialize + serde::de::DeserializeOwned + StateKind + Send + Sync + Clone,
+            D: IntoIterator<Item = (String, T)> + Send,
+        {
+            self.writes
+                .fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+            self.inner.store_many(lattice_id, data).await
+        }
+
+        async fn delete_many<T, D, K>(&self, lattice_id: &str, data: D) -> Result<(), StoreError>
+        where
+            T: serde::Serialize + serde::de::DeserializeOwned + StateKind + Send + Sync,
+            D: IntoIterator<Item = K> + Send,
+            K: AsRef<str>,
+        {
+            self.writes
+                .fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+            self.inner.delete_many::<T, _, _>(lattice_id, data).await
+        }
+    }
+
+    /// Returns all hosts, components and providers in the store, without the host timestamps that
+    /// differ between runs
+    async fn comparable_state(store: &CountingStore, lattice_id: &str) -> serde_json::Value {
+        let mut hosts =
+            serde_json::to_value(store.list::<Host>(lattice_id).await.unwrap()).unwrap();
+        for host in hosts.as_object_mut().unwrap().values_mut() {
+            host.as_object_mut().unwrap().remove("last_seen");
+        }
+        serde_json::json!({
+            "hosts": hosts,
+            "components": store.list::<Component>(lattice_id).await.unwrap(),
+            "providers": store.list::<Provider>(lattice_id).await.unwrap(),
+        })
+    }
+
+    #[tokio::test]
+    async fn test_bulk_ingest_matches_per_event_state() {
+        let lattice_id = "bulk_ingest";
+        let lattice_source = TestLatticeSource::default();
+        let command_publisher = CommandPublisher::new(NoopPublisher, "doesntmatter");
+        let status_publisher = StatusPublisher::new(NoopPublisher, None, "doesntmatter");
+        let new_worker = |store: CountingStore| {
+            let command_publisher = command_publisher.clone();
+            let status_publisher = status_publisher.clone();
+            let lattice_source = lattice_source.clone();
+            async move {
+                let scalers = ScalerManager::test_new(
+                    NoopPublisher,
+                    lattice_id,
+                    store.clone(),
+                    command_publisher.clone(),
+                    status_publisher.clone(),
+                    lattice_source.clone(),
+                )
+                .await;
+                EventWorker::new(
+                    store,
+                    lattice_source,
+                    command_publisher,
+                    status_publisher,
+                    scalers,
+                )
+            }
+        };
+
+        let host_started = |id: &str| {
+            Event::HostStarted(HostStarted {
+                friendly_name: format!("{id}-friendly"),
+                id: id.to_string(),
+                labels: HashMap::from([("region".to_string(), "us-brooks-1".to_string())]),
+            })
+        };
+        let component_scaled = |host_id: &str, max_instances: usize| {
+            Event::ComponentScaled(ComponentScaled {
+                claims: None,
+                image_ref: "ghcr.io/wasmcloud/components/echo:0.1.0".to_string(),
+                component_id: "echo".to_string(),
+                host_id: host_id.to_string(),
+                annotations: BTreeMap::default(),
+                max_instances,
+            })
+        };
+        let events = vec![
+            host_started("host1"),
+            host_started("host2"),
+            component_scaled("host1", 1),
+            component_scaled("host1", 5),
+            component_scaled("host2", 2),
+            Event::ProviderStarted(ProviderStarted {
+                claims: None,
+                image_ref: "ghcr.io/wasmcloud/httpserver:0.1.0".to_string(),
+                provider_id: "httpserver".to_string(),
+                host_id: "host1".to_string(),
+                annotations: BTreeMap::default(),
+            }),
+            component_scaled("host2", 0),
+            Event::HostStopped(HostStopped {
+                labels: HashMap::default(),
+                id: "host2".to_string(),
+            }),
+        ];
+
+        let single_store = CountingStore::default();
+        let single = new_worker(single_store.clone()).await;
+        for event in events.iter().cloned() {
+            single
+                .do_work(ScopedMessage {
+                    lattice_id: lattice_id.to_string(),
+                    inner: event,
+                    acker: None,
+                    ack_batcher: None,
+                    published: None,
+                })
+                .await
+                .expect("Should be able to handle event");
+        }
+
+        let bulk_store = CountingStore::default();
+        let bulk = new_worker(bulk_store.clone()).await;
+        bulk.ingest_events(lattice_id, &events)
+            .await
+            .expect("Should be able to ingest events");
+
+        assert_eq!(
+            comparable_state(&single_store, lattice_id).await,
+            comparable_state(&bulk_store, lattice_id).await,
+            "State should match after ingesting events"
+        );
+
+        let hosts = bulk_store.inner.list::<Host>(lattice_id).await.unwrap();
+        assert_eq!(hosts.len(), 1, "Stopped host should have been removed");
+        assert_eq!(
+            hosts["host1"].components.get("echo"),
+            Some(&5),
+            "Latest component count should be stored"
+        );
+        assert!(
+            bulk_store.writes() < single_store.writes(),
+            "Bulk ingestion should write to the store less often ({} >= {})",
+            bulk_store.writes(),
+            single_store.writes()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_heartbeat_matches_sequential_state() {
+        let lattice_id = "concurrent_heartbeat";
+        let host_id = "host1";
+        let lattice_source = TestLatticeSource::default();
+        let command_publisher = CommandPublisher::new(NoopPublisher, "doesntmatter");
+        let status_publisher = StatusPublisher::new(NoopPublisher, None, "doesntmatter");
+        let new_worker = |store: CountingStore, concurrent: bool| {
+            let command_publisher = command_publisher.clone();
+            let status_publisher = status_publisher.clone();
+            let lattice_source = lattice_source.clone();
+            async move {
+                let scalers = ScalerManager::test_new(
+                    NoopPublisher,
+                    lattice_id,
+                    store.clone(),
+                    command_publisher.clone(),
+                    status_publisher.clone(),
+                    lattice_source.clone(),
+                )
+                .await;
+                EventWorker::new(
+                    store,
+                    lattice_source,
+                    command_publisher,
+                    status_publisher,
+                    scalers,
+                )
+                .with_concurrent_heartbeats(concurrent)
+            }
+        };
+        // Both stores start out with the same stale state that the heartbeat has to fix up
+        let seeded = || async {
+            let store = CountingStore::default();
+            store
+                .store(
+                    lattice_id,
+                    "echo".to_string(),
+                    Component {
+                        id: "echo".to_string(),
+                        instances: HashMap::from([(
+                            host_id.to_string(),
+                            HashSet::from_iter([WadmComponentInfo {
+                                count: 1,
+                                annotations: BTreeMap::default(),
+                            }]),
+                        )]),
+                        ..Default::default()
+                    },
+                )
+                .await
+                .unwrap();
+            store
+                .store(
+                    lattice_id,
+                    "stale".to_string(),
+                    Provider {
+                        id: "stale".to_string(),
+                        hosts: HashMap::from([(
+                            host_id.to_string(),
+                            ProviderStatus::Running.into(),
+                        )]),
+                        ..Default::default()
+                    },
+                )
+                .await
+                .unwrap();
+            store
+        };
+        let heartbeat = HostHeartbeat {
+            components: vec![ComponentDescription::builder()
+                .id("echo".into())
+                .image_ref("ghcr.io/wasmcloud/components/echo:0.1.0".into())
+                .revision(0)
+                .max_instances(4)
+                .build()
+                .expect("failed to build description")],
+            friendly_name: "big-host".to_string(),
+            labels: HashMap::default(),
+            issuer: "".to_string(),
+            providers: vec![ProviderDescription::builder()
+                .annotations(BTreeMap::from_iter([(
+                    "one".to_string(),
+                    "two".to_string(),
+                )]))
+                .id("httpserver")
+                .image_ref("ghcr.io/wasmcloud/httpserver:0.1.0")
+                .revision(0)
+                .build()
+                .expect("failed to build provider description")],
+            uptime_human: "60s".into(),
+            uptime_seconds: 60,
+            version: semver::Version::parse("0.61.0").unwrap(),
+            host_id: host_id.to_string(),
+            utilization: None,
+        };
+
+        let sequential_store = seeded().await;
+        new_worker(sequential_store.clone(), false)
+            .await
+            .handle_host_heartbeat(lattice_id, &heartbeat)
+            .await
+            .expect("Should be able to handle host heartbeat");
+
+        let concurrent_store = seeded().await;
+        new_worker(concurrent_store.clone(), true)
+            .await
+            .handle_host_heartbeat(lattice_id, &heartbeat)
+            .await
+            .expect("Should be able to handle host heartbeat");
+        assert!(
+            !concurrent_store.listed().contains(&Component::KIND),
+            "Heartbeats should only fetch the components running on the host"
+        );
+
+        let state = comparable_state(&concurrent_store, lattice_id).await;
+        assert_eq!(
+            comparable_state(&sequential_store, lattice_id).await,
+            state,
+            "State should match when handling heartbeats concurrently"
+        );
+        assert_eq!(
+            state["components"]["echo"]["instances"][host_id][0]["count"],
+            4
+        );
+        assert!(state["providers"].get("stale").is_none());
+        assert!(state["providers"].get("httpserver").is_some());
+    }
+
+    /// A store with a single revision for all of its data, that writes the given host behind the
+    /// back of the first compare-and-swap of a host, as if another worker updated it concurrently
+    #[derive(Clone, Default)]
+    struct InterleavingStore {
+        inner: Arc<TestStore>,
+        revision: Arc<std::sync::atomic::AtomicU64>,
+        interleaved_host: Arc<std::sync::Mutex<Option<Host>>>,
+        conflicts: Arc<std::sync::atomic::AtomicUsize>,
+    }
+
+    #[async_trait::async_trait]
+    impl ReadStore for InterleavingStore {
+        async fn get<T>(&self, lattice_id: &str, id: &str) -> Result<Option<T>, StoreError>
+        where
+            T: serde::de::DeserializeOwned + StateKind,
+        {
+            self.inner.get(lattice_id, id).await
+        }
+
+        async fn list<T>(&self, lattice_id: &str) -> Result<HashMap<String, T>, StoreError>
+        where
+            T: serde::de::DeserializeOwned + StateKind,
+        {
+            self.inner.list(lattice_id).await
+        }
+    }
+
+    #[async_trait::async_trait]
+    impl Store for InterleavingStore {
+        async fn store_many<T, D>(&self, lattice_id: &str, data: D) -> Result<(), StoreError>
+        where
+            T: serde::Serialize + serde::de::DeserializeOwned + StateKind + Send + Sync + Clone,
+            D: IntoIterator<Item = (String, T)> + Send,
+        {
+            self.revision
+                .fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+            self.inner.store_many(lattice_id, data).await
+        }
+
+        async fn delete_many<T, D, K>(&self, lattice_id: &str, data: D) -> Result<(), StoreError>
+        where
+            T: serde::Serialize + serde::de::DeserializeOwned + StateKind + Send + Sync,
+            D: IntoIterator<Item = K> + Send,
+            K: AsRef<str>,
+        {
+            self.revision
+                .fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+            self.inner.delete_many::<T, _, _>(lattice_id, data).await
+        }
+
+        async fn get_with_revision<T>(
+            &self,
+            lattice_id: &str,
+            id: &str,
+        ) -> Result<(Option<T>, u64), StoreError>
+        where
+            T: serde::de::DeserializeOwned + StateKind + Send,
+        {
+            let revision = self.revision.load(std::sync::atomic::Ordering::SeqCst);
+            Ok((self.inner.get(lattice_id, id).await?, revision))
+        }
+
+        async fn store_cas<T>(
+            &self,
+            lattice_id: &str,
+            id: String,
+            data: T,
+            expected_revision: u64,
+        ) -> Result<u64, CasError<StoreError>>
+        where
+            T: serde::Serialize + serde::de::DeserializeOwned + StateKind + Send + Sync + Clone,
+        {
+            if T::KIND == Host::KIND {
+                let interleaved = self.interleaved_host.lock().unwrap().take();
+                if let Some(host) = interleaved {
+                    self.store(lattice_id, host.id.clone(), host)
+                        .await
+                        .map_err(CasError::Store)?;
+                }
+            }
+            if self.revision.load(std::sync::atomic::Ordering::SeqCst) != expected_revision {
+                self.conflicts
+                    .fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+                return Err(CasError::Conflict {
+                    expected: expected_revision,
+                });
+            }
+            self.store(lattice_id, id, data)
+                .await
+                .map_err(CasError::Store)?;
+            Ok(self.revision.load(std::sync::atomic::Ordering::SeqCst))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_component_scaled_keeps_concurrent_host_update() {
+        let lattice_id = "concurrent_host_update";
+        let host_id = "host1";
+        let store = InterleavingStore::default();
+        let lattice_source = TestLatticeSource::default();
+        let worker = EventWorker::new(
+            store.clone(),
+            lattice_source.clone(),
+            CommandPublisher::new(NoopPublisher, "doesntmatter"),
+            StatusPublisher::new(NoopPublisher, None, "doesntmatter"),
+            ScalerManager::test_new(
+                NoopPublisher,
+                lattice_id,
+                store.clone(),
+                CommandPublisher::new(NoopPublisher, "doesntmatter"),
+                StatusPublisher::new(NoopPublisher, None, "doesntmatter"),
+                lattice_source,
+            )
+            .await,
+        );
+
+        let host = Host {
+            id: host_id.to_string(),
+            last_seen: chrono::Utc::now(),
+            ..Default::default()
+        };
+        store
+            .store(lattice_id, host_id.to_string(), host.clone())
+            .await
+            .unwrap();
+        // Another worker adds a label to the host between our read and write of it
+        *store.interleaved_host.lock().unwrap() = Some(Host {
+            labels: HashMap::from([("zone".to_string(), "us-brooks-1".to_string())]),
+            ..host
+        });
+
+        worker
+            .handle_component_scaled(
+                lattice_id,
+                &ComponentScaled {
+                    annotations: BTreeMap::new(),
+                    claims: None,
+                    image_ref: "fakecloud.io/echo:0.1.0".to_string(),
+                    max_instances: 2,
+                    component_id: "echo".to_string(),
+                    host_id: host_id.to_string(),
+                },
+            )
+            .await
+            .expect("Should be able to handle event");
+
+        assert_eq!(
+            store.conflicts.load(std::sync::atomic::Ordering::SeqCst),
+            1,
+            "The stale host write should have conflicted once"
+        );
+        let host = store
+            .get::<Host>(lattice_id, host_id)
+            .await
+            .unwrap()
+            .expect("Host should exist");
+        assert_eq!(
+            host.labels.get("zone").map(String::as_str),
+            Some("us-brooks-1"),
+            "The concurrent update should not be lost"
+        );
+        assert_eq!(host.components.get("echo"), Some(&2));
+        assert!(store
+            .get::<Component>(lattice_id, "echo")
+            .await
+            .unwrap()
+            .is_some());
+    }
+
+    #[tokio::test]
+    async fn test_detailed_status_sums_progress() {
+        let scaler = |id: &str, status: StatusInfo| {
+            Box::new(crate::scaler::statusscaler::StatusScaler::new(
+                id, "Test", id, status,
+            )) as BoxedScaler
+        };
+        let without_progress = vec![scaler("config", StatusInfo::deployed(""))];
+        assert_eq!(
+            detailed_scaler_status(&without_progress)
+                .await
+                .info
+                .progress,
+            None,
+            "Progress should be left unset when no scaler reports it"
+        );
+
+        let scalers = vec![
+            scaler("spread", StatusInfo::reconciling("").with_progress(3, 6)),
+            scaler("daemon", StatusInfo::deployed("").with_progress(4, 4)),
+            scaler("config", StatusInfo::deployed("")),
+        ];
+        assert_eq!(
+            detailed_scaler_status(&scalers).await.info.progress,
+            Some((7, 10))
+        );
+    }
+
+    #[test]
+    fn test_only_retries_reachable_store_errors() {
+        let connection = StoreError::Connection(Box::new(std::io::Error::other("timed out")));
+        assert!(should_retry(&anyhow::Error::from(connection)));
+
+        let decode = serde_json::from_str::<Host>("not json").unwrap_err();
+        assert!(!should_retry(&anyhow::Error::from(StoreError::from(
+            decode
+        ))));
+        assert!(
+            !should_retry(
+                &anyhow::Error::from(StoreError::NotFound("host".to_string()))
+                    .context("Unable to update host")
+            ),
+            "Context shouldn't hide the store error"
+        );
+        assert!(!should_retry(&anyhow::Error::from(CasError::Store(
+            StoreError::Other("invalid key".to_string())
+        ))));
+
+        assert!(
+            should_retry(&anyhow::anyhow!("Unable to publish commands")),
+            "Errors that didn't come from the store should still be retried"
+        );
+    }
 }
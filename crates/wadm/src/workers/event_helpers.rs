@@ -1,14 +1,27 @@
 use anyhow::{bail, Context};
 use async_nats::jetstream::stream::Stream;
-use std::collections::{BTreeMap, HashMap};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::fmt::Debug;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
 use wasmcloud_secrets_types::SecretConfig;
 
-use tracing::{debug, instrument, trace, warn};
-use wadm_types::api::Status;
+use tracing::{debug, info, instrument, trace, warn};
+use wadm_types::{api::Status, Manifest, TraitProperty, DEPLOYED_AT_ANNOTATION_KEY};
 use wasmcloud_control_interface::{HostInventory, Link};
 
-use crate::{commands::Command, publisher::Publisher, APP_SPEC_ANNOTATION};
+use crate::{
+    commands::Command,
+    publisher::Publisher,
+    sink::{SharedSink, StateChange},
+    storage::{update_cas, CasError, CommandClaim, RecordedCommand, Store},
+    APP_SPEC_ANNOTATION,
+};
+
+use super::LeaseKeeper;
 
 /// A subset of needed claims to help populate state
 #[derive(Debug, Clone)]
@@ -35,6 +48,9 @@ pub trait ClaimsSource {
 #[async_trait::async_trait]
 pub trait InventorySource {
     async fn get_inventory(&self, host_id: &str) -> anyhow::Result<HostInventory>;
+
+    /// Returns the IDs of all hosts currently running in the lattice
+    async fn get_host_ids(&self) -> anyhow::Result<Vec<String>>;
 }
 
 /// A trait for anything that can fetch the links in a lattice
@@ -140,6 +156,17 @@ impl InventorySource for wasmcloud_control_interface::Client {
             )),
         }
     }
+
+    async fn get_host_ids(&self) -> anyhow::Result<Vec<String>> {
+        Ok(self
+            .get_hosts()
+            .await
+            .map_err(|e| anyhow::anyhow!("{e:?}"))?
+            .into_iter()
+            .filter_map(|ctl_resp| ctl_resp.into_data())
+            .map(|host| host.id().to_owned())
+            .collect())
+    }
 }
 
 // NOTE(thomastaylor312): A future improvement here that would make things more efficient is if this
@@ -210,6 +237,81 @@ impl SecretSource for wasmcloud_control_interface::Client {
     }
 }
 
+/// The placeholder in a [`SubjectTemplate`] that is replaced with the lattice ID
+pub const LATTICE_ID_PLACEHOLDER: &str = "{lattice_id}";
+/// The placeholder in a [`SubjectTemplate`] that is replaced with the model name
+pub const MODEL_NAME_PLACEHOLDER: &str = "{model_name}";
+/// The token used in place of the model name for commands that don't belong to a model
+const NO_MODEL_TOKEN: &str = "_";
+
+/// A template for deriving the NATS subject a command or status is published on, e.g.
+/// `wadm.cmd.{lattice_id}.{model_name}`. Each placeholder must be a whole subject token and the
+/// lattice ID placeholder is required so that each lattice's messages stay separate. Publishing
+/// each model on its own subject allows NATS permissions to be scoped per tenant
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SubjectTemplate(String);
+
+impl SubjectTemplate {
+    /// Returns true if the template contains the model name placeholder
+    pub fn has_model_name(&self) -> bool {
+        self.0
+            .split('.')
+            .any(|token| token == MODEL_NAME_PLACEHOLDER)
+    }
+
+    /// Renders the subject for the given lattice and model. Messages that don't belong to a model
+    /// use `_` in place of the model name
+    pub fn render(&self, lattice_id: &str, model_name: Option<&str>) -> String {
+        self.render_tokens(lattice_id, model_name.unwrap_or(NO_MODEL_TOKEN))
+    }
+
+    /// Returns the subject filter matching every message for the given lattice
+    pub fn lattice_filter(&self, lattice_id: &str) -> String {
+        self.render_tokens(lattice_id, "*")
+    }
+
+    /// Returns the subject filter matching every message for all lattices, used for the stream
+    pub fn stream_subject(&self) -> String {
+        self.render_tokens("*", "*")
+    }
+
+    fn render_tokens(&self, lattice_id: &str, model_name: &str) -> String {
+        self.0
+            .split('.')
+            .map(|token| match token {
+                LATTICE_ID_PLACEHOLDER => lattice_id,
+                MODEL_NAME_PLACEHOLDER => model_name,
+                token => token,
+            })
+            .collect::<Vec<_>>()
+            .join(".")
+    }
+}
+
+impl std::str::FromStr for SubjectTemplate {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let template = s.trim().trim_matches('.');
+        let tokens = template.split('.').collect::<Vec<_>>();
+        if tokens.iter().any(|token| token.is_empty()) {
+            bail!("Subject template '{s}' contains an empty token");
+        }
+        if let Some(token) = tokens.iter().find(|token| {
+            **token != LATTICE_ID_PLACEHOLDER
+                && **token != MODEL_NAME_PLACEHOLDER
+                && (token.contains(['{', '}', '*', '>']) || token.contains(char::is_whitespace))
+        }) {
+            // Placeholders must be a whole token, e.g. `lattice-{lattice_id}` isn't allowed
+            bail!("Invalid token '{token}' in subject template '{s}'");
+        }
+        if !tokens.contains(&LATTICE_ID_PLACEHOLDER) {
+            bail!("Subject template '{s}' must contain {LATTICE_ID_PLACEHOLDER}");
+        }
+        Ok(SubjectTemplate(template.to_owned()))
+    }
+}
+
 /// A struct for publishing status updates
 #[derive(Clone)]
 pub struct StatusPublisher<Pub> {
@@ -218,6 +320,10 @@ pub struct StatusPublisher<Pub> {
     status_stream: Option<Stream>,
     // Topic prefix, e.g. wadm.status.default
     topic_prefix: String,
+    // The lattice and template used to derive the topic instead of the prefix, if configured
+    subject: Option<(String, SubjectTemplate)>,
+    // The lattice and sink that changed statuses are also sent to, if configured
+    sink: Option<(String, SharedSink)>,
 }
 
 impl<Pub> StatusPublisher<Pub> {
@@ -232,6 +338,33 @@ impl<Pub> StatusPublisher<Pub> {
             publisher,
             status_stream,
             topic_prefix: topic_prefix.to_owned(),
+            subject: None,
+            sink: None,
+        }
+    }
+
+    /// Configures this publisher to publish the status of each model on the subject rendered from
+    /// the given template for the given lattice, instead of using the topic prefix
+    pub fn with_subject_template(
+        mut self,
+        lattice_id: &str,
+        template: SubjectTemplate,
+    ) -> StatusPublisher<Pub> {
+        self.subject = Some((lattice_id.to_owned(), template));
+        self
+    }
+
+    /// Configures this publisher to also send every changed status in the given lattice to the
+    /// given sink. Failing to send to the sink is logged and doesn't fail publishing
+    pub fn with_sink(mut self, lattice_id: &str, sink: SharedSink) -> StatusPublisher<Pub> {
+        self.sink = Some((lattice_id.to_owned(), sink));
+        self
+    }
+
+    fn topic(&self, name: &str) -> String {
+        match &self.subject {
+            Some((lattice_id, template)) => template.render(lattice_id, Some(name)),
+            None => format!("{}.{name}", self.topic_prefix),
         }
     }
 }
@@ -239,7 +372,7 @@ impl<Pub> StatusPublisher<Pub> {
 impl<Pub: Publisher> StatusPublisher<Pub> {
     #[instrument(level = "trace", skip(self))]
     pub async fn publish_status(&self, name: &str, status: Status) -> anyhow::Result<()> {
-        let topic = format!("{}.{name}", self.topic_prefix);
+        let topic = self.topic(name);
 
         // NOTE(brooksmtownsend): This direct get may not always query the jetstream leader. In the
         // worst case where the last message isn't all the way updated, we may publish a duplicate
@@ -264,9 +397,270 @@ impl<Pub: Publisher> StatusPublisher<Pub> {
             _ => {
                 self.publisher
                     .publish(serde_json::to_vec(&status)?, Some(&topic))
+                    .await?;
+                if let Some((lattice_id, sink)) = &self.sink {
+                    let change = StateChange::Status {
+                        lattice_id: lattice_id.clone(),
+                        model_name: name.to_owned(),
+                        status,
+                    };
+                    if let Err(e) = sink.send(&change).await {
+                        warn!(error = %e, %name, "Failed to send status change to sink");
+                    }
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+/// The subject prefix that [`ModelDeployed`] events are published to. The full subject is
+/// `wadm.deployed.{lattice_id}.{model_name}`
+pub const DEPLOYED_SUBJECT_PREFIX: &str = "wadm.deployed";
+
+/// The subject prefix that commands are published to instead of their real subject when dry run
+/// is enabled. The full subject is the real subject of the command appended to this prefix, such as
+/// `wadm.dry_run.wadm.cmd.{lattice_id}`. wadm keeps everything published under this prefix in a
+/// stream so the commands can be reviewed later
+pub const DRY_RUN_SUBJECT_PREFIX: &str = "wadm.dry_run";
+
+/// The number of resources running for a model at the time it was deployed
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DeployedResources {
+    /// The total number of component instances across all hosts
+    pub component_instances: usize,
+    /// The total number of providers across all hosts
+    pub providers: usize,
+    pub links: usize,
+}
+
+/// An event sent once when a deployed version of a model first becomes ready
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ModelDeployed {
+    pub model_name: String,
+    pub version: String,
+    pub lattice_id: String,
+    pub deployed_at: DateTime<Utc>,
+    pub resources: DeployedResources,
+}
+
+/// A struct for publishing [`ModelDeployed`] events on their own subject, separate from the status
+/// updates sent by the [`StatusPublisher`]
+#[derive(Clone)]
+pub struct DeployEventPublisher<Pub> {
+    publisher: Pub,
+    // Subject prefix, e.g. wadm.deployed
+    prefix: String,
+}
+
+impl<Pub> DeployEventPublisher<Pub> {
+    /// Creates a new publisher that sends events to subjects starting with the given prefix
+    pub fn new(publisher: Pub, prefix: &str) -> DeployEventPublisher<Pub> {
+        DeployEventPublisher {
+            publisher,
+            prefix: prefix.to_owned(),
+        }
+    }
+}
+
+impl<Pub: Publisher> DeployEventPublisher<Pub> {
+    #[instrument(level = "trace", skip(self, event), fields(name = %event.model_name))]
+    pub async fn publish(&self, event: &ModelDeployed) -> anyhow::Result<()> {
+        let subject = format!("{}.{}.{}", self.prefix, event.lattice_id, event.model_name);
+        self.publisher
+            .publish(serde_json::to_vec(event)?, Some(&subject))
+            .await
+    }
+}
+
+/// A trait for anything that can claim commands so that only one wadm instance publishes a given
+/// command
+#[async_trait::async_trait]
+pub trait CommandClaimer {
+    /// Attempts to claim the given command IDs, returning the set of IDs that were successfully
+    /// claimed by this instance and should be published
+    async fn claim(&self, command_ids: &[String]) -> anyhow::Result<HashSet<String>>;
+}
+
+/// A [`CommandClaimer`] that stores short-lived claims in the lattice state store. A claim that is
+/// owned by another instance blocks publishing the command until it expires. Claims are written
+/// with [`update_cas`], so if multiple instances try to claim the same command at once, only one of
+/// them wins
+pub struct StoreCommandClaimer<S> {
+    store: S,
+    lattice_id: String,
+    owner: String,
+    ttl: chrono::Duration,
+    // When expired claims were last cleaned up
+    last_cleanup: std::sync::Mutex<DateTime<Utc>>,
+}
+
+impl<S> StoreCommandClaimer<S> {
+    /// Creates a new claimer for the given lattice. The `owner` should be a unique identifier for
+    /// this wadm instance and `ttl` is how long a claim blocks other instances from publishing
+    pub fn new(store: S, lattice_id: &str, owner: &str, ttl: std::time::Duration) -> Self {
+        StoreCommandClaimer {
+            store,
+            lattice_id: lattice_id.to_owned(),
+            owner: owner.to_owned(),
+            ttl: chrono::Duration::from_std(ttl).unwrap_or(chrono::Duration::MAX),
+            last_cleanup: std::sync::Mutex::new(Utc::now()),
+        }
+    }
+}
+
+impl<S: Store + Send + Sync> StoreCommandClaimer<S> {
+    /// Removes claims that expired without the command being claimed again. This lists every
+    /// claim, so it only runs once per TTL
+    async fn cleanup_expired(&self, now: DateTime<Utc>) -> anyhow::Result<()> {
+        {
+            let mut last_cleanup = self.last_cleanup.lock().unwrap();
+            if now < *last_cleanup + self.ttl {
+                return Ok(());
+            }
+            *last_cleanup = now;
+        }
+        let expired = self
+            .store
+            .list::<CommandClaim>(&self.lattice_id)
+            .await?
+            .into_iter()
+            .filter(|(_, claim)| claim.expires_at <= now)
+            .map(|(id, _)| id);
+        for id in expired {
+            // Only delete the claim if nobody claimed the command again since it was read
+            let (claim, revision) = self
+                .store
+                .get_with_revision::<CommandClaim>(&self.lattice_id, &id)
+                .await?;
+            if claim.is_some_and(|claim| claim.expires_at <= now) {
+                match self
+                    .store
+                    .delete_cas::<CommandClaim>(&self.lattice_id, &id, revision)
                     .await
+                {
+                    Ok(_) | Err(CasError::Conflict { .. }) => (),
+                    Err(CasError::Store(e)) => return Err(e.into()),
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[async_trait::async_trait]
+impl<S: Store + Send + Sync> CommandClaimer for StoreCommandClaimer<S> {
+    #[instrument(level = "trace", skip_all, fields(lattice_id = %self.lattice_id))]
+    async fn claim(&self, command_ids: &[String]) -> anyhow::Result<HashSet<String>> {
+        let now = Utc::now();
+        let mut claimed = HashSet::with_capacity(command_ids.len());
+        for id in command_ids {
+            let claim = update_cas(&self.store, &self.lattice_id, id, |current| match current {
+                Some(CommandClaim { owner, expires_at })
+                    if expires_at > now && owner != self.owner =>
+                {
+                    None
+                }
+                _ => Some(CommandClaim {
+                    owner: self.owner.clone(),
+                    expires_at: now + self.ttl,
+                }),
+            })
+            .await?;
+            if claim.is_some() {
+                claimed.insert(id.to_owned());
             }
         }
+
+        if let Err(e) = self.cleanup_expired(now).await {
+            warn!(error = %e, "Unable to clean up expired command claims");
+        }
+        Ok(claimed)
+    }
+}
+
+/// Limits used when batching commands before publishing them
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CommandBatchConfig {
+    /// The number of queued commands that will cause the batch to be published immediately
+    pub max_size: usize,
+    /// The longest a command can be queued before the batch is published, even if it isn't full
+    pub max_age: Duration,
+}
+
+struct CommandBatch {
+    config: CommandBatchConfig,
+    pending: tokio::sync::Mutex<PendingBatch>,
+}
+
+/// The commands queued for the next batch and everyone waiting on it to be published
+#[derive(Default)]
+struct PendingBatch {
+    commands: Vec<Command>,
+    waiters: Vec<tokio::sync::oneshot::Sender<Result<(), String>>>,
+    /// Incremented every time a batch is taken, so a max age timer only publishes the batch it was
+    /// started for
+    generation: u64,
+}
+
+impl PendingBatch {
+    /// Takes the queued batch to publish, leaving an empty batch for the next generation
+    fn take(&mut self) -> PendingBatch {
+        let next = PendingBatch {
+            generation: self.generation.wrapping_add(1),
+            ..Default::default()
+        };
+        std::mem::replace(self, next)
+    }
+}
+
+/// Limits on how fast commands are published, enforced with a token bucket
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CommandRateLimit {
+    /// The number of commands that can be published each second once the burst has been used up
+    pub per_second: f64,
+    /// The number of commands that can be published at once before they start being delayed
+    pub burst: u32,
+}
+
+struct TokenBucket {
+    per_second: f64,
+    burst: f64,
+    // The number of tokens left and when they were last refilled. Tokens go negative when
+    // publishers are waiting on them
+    state: tokio::sync::Mutex<(f64, tokio::time::Instant)>,
+}
+
+impl TokenBucket {
+    fn new(limit: CommandRateLimit) -> TokenBucket {
+        let burst = f64::from(limit.burst.max(1));
+        TokenBucket {
+            per_second: limit.per_second,
+            burst,
+            state: tokio::sync::Mutex::new((burst, tokio::time::Instant::now())),
+        }
+    }
+
+    /// Waits until a command can be published. Tokens are reserved in the order this is called,
+    /// so callers are let through in order
+    async fn acquire(&self) {
+        let wait = {
+            let mut state = self.state.lock().await;
+            let (tokens, refilled) = &mut *state;
+            let now = tokio::time::Instant::now();
+            *tokens = (*tokens + (now - *refilled).as_secs_f64() * self.per_second).min(self.burst);
+            *refilled = now;
+            *tokens -= 1.0;
+            if *tokens >= 0.0 {
+                Duration::ZERO
+            } else {
+                Duration::from_secs_f64(-*tokens / self.per_second)
+            }
+        };
+        if !wait.is_zero() {
+            trace!(?wait, "Command rate limit reached, delaying command");
+            tokio::time::sleep(wait).await;
+        }
     }
 }
 
@@ -275,6 +669,18 @@ impl<Pub: Publisher> StatusPublisher<Pub> {
 pub struct CommandPublisher<Pub> {
     publisher: Pub,
     topic: String,
+    claimer: Option<Arc<dyn CommandClaimer + Send + Sync>>,
+    // Returns true if commands should not be published because this instance is isolated
+    read_only: Option<Arc<dyn Fn() -> bool + Send + Sync>>,
+    batch: Option<Arc<CommandBatch>>,
+    // Shared between clones so the limit applies to everything published for the lattice
+    rate_limit: Option<Arc<TokenBucket>>,
+    // The lattice and template used to derive the topic for each command, if configured
+    subject: Option<(String, SubjectTemplate)>,
+    // The lattice and sink that published commands are also sent to, if configured
+    sink: Option<(String, SharedSink)>,
+    // Shared between clones so all of the publishers for a lattice switch at the same time
+    dry_run: Arc<AtomicBool>,
 }
 
 impl<Pub> CommandPublisher<Pub> {
@@ -284,31 +690,320 @@ impl<Pub> CommandPublisher<Pub> {
         CommandPublisher {
             publisher,
             topic: topic.to_owned(),
+            claimer: None,
+            read_only: None,
+            batch: None,
+            rate_limit: None,
+            subject: None,
+            sink: None,
+            dry_run: Arc::default(),
+        }
+    }
+
+    /// Configures this publisher to publish each command on the subject rendered from the given
+    /// template for the given lattice and the model that issued the command
+    pub fn with_subject_template(
+        mut self,
+        lattice_id: &str,
+        template: SubjectTemplate,
+    ) -> CommandPublisher<Pub> {
+        self.subject = Some((lattice_id.to_owned(), template));
+        self
+    }
+
+    /// Configures this publisher to also send every published command in the given lattice to the
+    /// given sink. Failing to send to the sink is logged and doesn't fail publishing
+    pub fn with_sink(mut self, lattice_id: &str, sink: SharedSink) -> CommandPublisher<Pub> {
+        self.sink = Some((lattice_id.to_owned(), sink));
+        self
+    }
+
+    /// Configures this publisher to only log commands and publish them on a subject under
+    /// [`DRY_RUN_SUBJECT_PREFIX`] rather than actually executing them. Clones of this publisher
+    /// share the setting, including ones made before this was called
+    pub fn with_dry_run(self, dry_run: bool) -> CommandPublisher<Pub> {
+        self.dry_run.store(dry_run, Ordering::Relaxed);
+        self
+    }
+
+    /// Returns whether this publisher is in dry run mode
+    pub fn is_dry_run(&self) -> bool {
+        self.dry_run.load(Ordering::Relaxed)
+    }
+
+    fn topic(&self, command: &Command) -> String {
+        match &self.subject {
+            Some((lattice_id, template)) => template.render(lattice_id, command.model_name()),
+            None => self.topic.clone(),
+        }
+    }
+
+    /// Configures this publisher to only publish commands that it was able to claim with the given
+    /// claimer. This is used to deduplicate commands across multiple wadm instances
+    pub fn with_claimer(
+        mut self,
+        claimer: impl CommandClaimer + Send + Sync + 'static,
+    ) -> CommandPublisher<Pub> {
+        self.claimer = Some(Arc::new(claimer));
+        self
+    }
+
+    /// Configures this publisher to drop all commands while the given lease keeper says this
+    /// instance is read-only
+    pub fn with_lease<S: Send + Sync + 'static>(
+        mut self,
+        lease: LeaseKeeper<S>,
+    ) -> CommandPublisher<Pub> {
+        self.read_only = Some(Arc::new(move || lease.is_read_only()));
+        self
+    }
+
+    /// Configures this publisher to queue commands and publish them in batches. A batch is
+    /// published as soon as it is full or once its oldest command has been queued for the
+    /// configured max age, whichever comes first. Publishing commands waits until the batch they
+    /// were queued in has been published and fails if the batch does, so the max age should stay
+    /// well under the ack wait of the event that caused the commands. Clones of this publisher
+    /// share the same batch
+    pub fn with_batching(mut self, config: CommandBatchConfig) -> CommandPublisher<Pub> {
+        self.batch = Some(Arc::new(CommandBatch {
+            config,
+            pending: tokio::sync::Mutex::default(),
+        }));
+        self
+    }
+
+    /// Configures this publisher to spread commands out over time instead of publishing them all
+    /// at once. Commands over the limit are delayed, never dropped, and are published one at a
+    /// time in order. Clones of this publisher share the same limit. A limit that isn't a
+    /// positive number of commands per second is ignored.
+    ///
+    /// Delayed commands hold up handling the event that caused them, so consumers send in progress
+    /// acks for the event while it waits rather than letting it be redelivered
+    pub fn with_rate_limit(mut self, limit: CommandRateLimit) -> CommandPublisher<Pub> {
+        if limit.per_second.is_finite() && limit.per_second > 0.0 {
+            self.rate_limit = Some(Arc::new(TokenBucket::new(limit)));
+        } else {
+            warn!(?limit, "Ignoring invalid command rate limit");
         }
+        self
     }
 }
 
-impl<Pub: Publisher> CommandPublisher<Pub> {
+impl<Pub: Publisher + Clone + Send + Sync + 'static> CommandPublisher<Pub> {
+    /// Publishes the given commands. If batching is enabled, the commands are queued and this only
+    /// returns once the batch is full or has reached its max age and has been published
     #[instrument(level = "trace", skip(self))]
     pub async fn publish_commands(&self, commands: Vec<Command>) -> anyhow::Result<()> {
-        futures::future::join_all(
-            commands
-                .into_iter()
-                // Generally commands are purely internal to wadm and so shouldn't have an error serializing. If it does, warn and continue onward
-                .filter_map(|command| {
-                    match serde_json::to_vec(&command) {
-                        Ok(data) => Some(data),
-                        Err(e) => {
-                            warn!(error = %e, ?command, "Got malformed command when trying to serialize. Skipping this command");
-                            None
-                        }
+        if self.is_dry_run() {
+            self.publish_dry_run(commands).await;
+            return Ok(());
+        }
+        if !commands.is_empty() && self.read_only.as_ref().is_some_and(|check| check()) {
+            warn!(
+                num_commands = commands.len(),
+                "This instance is read-only while it is isolated, dropping commands"
+            );
+            return Ok(());
+        }
+        let commands = match &self.claimer {
+            Some(claimer) if !commands.is_empty() => {
+                let ids = commands.iter().map(Command::id).collect::<Vec<_>>();
+                match claimer.claim(&ids).await {
+                    Ok(claimed) => commands
+                        .into_iter()
+                        .zip(ids)
+                        .filter_map(|(command, id)| {
+                            if claimed.contains(&id) {
+                                Some(command)
+                            } else {
+                                trace!(?command, "Command claimed by another instance, skipping");
+                                None
+                            }
+                        })
+                        .collect(),
+                    // Publishing a duplicate command is better than not publishing it at all
+                    Err(e) => {
+                        warn!(error = %e, "Unable to claim commands, publishing all commands");
+                        commands
                     }
-                })
-                .map(|data| self.publisher.publish(data, Some(&self.topic))),
-        )
-        .await
-        .into_iter()
-        .collect::<anyhow::Result<()>>()
+                }
+            }
+            _ => commands,
+        };
+        match &self.batch {
+            Some(batch) => self.queue_commands(batch, commands).await,
+            None => self.publish_now(commands).await,
+        }
+    }
+
+    /// Sends the given recorded commands again, in order. This goes through the same steps as
+    /// [`publish_commands`](CommandPublisher::publish_commands), so replayed commands are still
+    /// batched, claimed and sent to the sink
+    pub async fn replay_commands(
+        &self,
+        recorded: impl IntoIterator<Item = RecordedCommand>,
+    ) -> anyhow::Result<()> {
+        let commands = recorded
+            .into_iter()
+            .map(|recorded| recorded.command)
+            .collect::<Vec<_>>();
+        info!(num_commands = commands.len(), "Replaying recorded commands");
+        self.publish_commands(commands).await
+    }
+
+    async fn queue_commands(
+        &self,
+        batch: &Arc<CommandBatch>,
+        commands: Vec<Command>,
+    ) -> anyhow::Result<()> {
+        if commands.is_empty() {
+            return Ok(());
+        }
+        let (sender, published) = tokio::sync::oneshot::channel();
+        let (ready, start_timer) = {
+            let mut pending = batch.pending.lock().await;
+            let start_timer = pending.commands.is_empty().then_some(pending.generation);
+            pending.commands.extend(commands);
+            pending.waiters.push(sender);
+            if pending.commands.len() >= batch.config.max_size {
+                (Some(pending.take()), None)
+            } else {
+                (None, start_timer)
+            }
+        };
+
+        // The first command in a batch starts the clock on publishing it. If the batch fills up
+        // before then, the timer is left with nothing to do so the next batch gets its own max age
+        if let Some(generation) = start_timer {
+            let publisher = self.clone();
+            let batch = batch.clone();
+            tokio::spawn(async move {
+                tokio::time::sleep(batch.config.max_age).await;
+                let pending = {
+                    let mut pending = batch.pending.lock().await;
+                    if pending.generation != generation || pending.commands.is_empty() {
+                        return;
+                    }
+                    pending.take()
+                };
+                trace!(num_commands = %pending.commands.len(), "Publishing command batch after reaching max age");
+                publisher.publish_batch(pending).await;
+            });
+        }
+        if let Some(pending) = ready {
+            self.publish_batch(pending).await;
+        }
+
+        published
+            .await
+            .map_err(|_| anyhow::anyhow!("Command batch was dropped before it was published"))?
+            .map_err(|e| anyhow::anyhow!("Failed to publish command batch: {e}"))
+    }
+
+    /// Publishes a full batch and lets everyone who queued commands in it know how it went
+    async fn publish_batch(&self, pending: PendingBatch) {
+        let result = self
+            .publish_now(pending.commands)
+            .await
+            .map_err(|e| format!("{e:#}"));
+        for waiter in pending.waiters {
+            // The caller may have stopped waiting, which is fine
+            let _ = waiter.send(result.clone());
+        }
+    }
+
+    /// Logs the given commands and publishes them on their dry run subject. Nothing acts on these,
+    /// so failing to publish is only logged
+    async fn publish_dry_run(&self, commands: Vec<Command>) {
+        for command in commands {
+            let topic = format!("{DRY_RUN_SUBJECT_PREFIX}.{}", self.topic(&command));
+            info!(?command, %topic, "Dry run enabled, not executing command");
+            let published = match serde_json::to_vec(&command) {
+                Ok(data) => self.publisher.publish(data, Some(&topic)).await,
+                Err(e) => Err(e.into()),
+            };
+            if let Err(e) = published {
+                warn!(error = %e, %topic, "Unable to publish dry run command");
+            }
+        }
+    }
+
+    async fn publish_now(&self, commands: Vec<Command>) -> anyhow::Result<()> {
+        let sent = self.sink.as_ref().map(|_| commands.clone());
+        let messages = commands
+            .into_iter()
+            // Generally commands are purely internal to wadm and so shouldn't have an error serializing. If it does, warn and continue onward
+            .filter_map(|command| {
+                match serde_json::to_vec(&command) {
+                    Ok(data) => Some((self.topic(&command), data)),
+                    Err(e) => {
+                        warn!(error = %e, ?command, "Got malformed command when trying to serialize. Skipping this command");
+                        None
+                    }
+                }
+            });
+        match &self.rate_limit {
+            // Rate limited commands are published one at a time so they stay in order
+            Some(bucket) => {
+                let mut results = Vec::new();
+                for (topic, data) in messages {
+                    bucket.acquire().await;
+                    results.push(self.publisher.publish(data, Some(&topic)).await);
+                }
+                results.into_iter().collect::<anyhow::Result<()>>()?;
+            }
+            None => {
+                self.publisher
+                    .publish_batch(messages.map(|(topic, data)| (data, topic)).collect())
+                    .await?;
+            }
+        }
+
+        if let (Some((lattice_id, sink)), Some(commands)) = (&self.sink, sent) {
+            for command in commands {
+                let change = StateChange::Command {
+                    lattice_id: lattice_id.clone(),
+                    command,
+                };
+                if let Err(e) = sink.send(&change).await {
+                    warn!(error = %e, "Failed to send command to sink");
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The annotation key for the version of the manifest that started a component or provider
+pub const MANIFEST_VERSION_ANNOTATION: &str = "wasmcloud.dev/manifest-version";
+
+/// Extra annotations added to every component and provider wadm starts, alongside the managed
+/// annotations, so downstream tooling can tell more about where an instance came from
+#[derive(Debug, Clone, Default)]
+pub struct InstanceAnnotations {
+    /// Annotations added to everything as is, e.g. a team or cost center
+    pub static_annotations: BTreeMap<String, String>,
+    /// Whether to also add the version of the manifest and the time it was deployed
+    pub deploy_metadata: bool,
+}
+
+impl InstanceAnnotations {
+    /// Returns the annotations to add to everything started for the given manifest
+    pub fn for_manifest(&self, manifest: &Manifest) -> BTreeMap<String, String> {
+        let mut annotations = self.static_annotations.clone();
+        if self.deploy_metadata {
+            annotations.insert(
+                MANIFEST_VERSION_ANNOTATION.to_owned(),
+                manifest.version().to_owned(),
+            );
+            if let Some(deployed_at) = manifest.deployed_at() {
+                annotations.insert(
+                    DEPLOYED_AT_ANNOTATION_KEY.to_owned(),
+                    deployed_at.to_owned(),
+                );
+            }
+        }
+        annotations
     }
 }
 
@@ -322,3 +1017,554 @@ pub fn insert_managed_annotations(annotations: &mut BTreeMap<String, String>, mo
         (APP_SPEC_ANNOTATION.to_owned(), model_name.to_owned()),
     ])
 }
+
+/// Substitutes all `${VAR}` placeholders found in string values of the given manifest with the
+/// matching value from `variables`, returning the resolved manifest. Returns an error listing all
+/// variables that were referenced in the manifest but not defined, so a manifest with placeholders
+/// can't be deployed without variables.
+///
+/// Placeholders are always strings, so a trait with a templated numeric field such as `instances`
+/// doesn't parse as its real property type. If a trait's properties only parse as a built in
+/// property type once placeholders that make up a whole value are replaced with numbers or
+/// booleans, those values are used instead of strings
+pub(crate) fn template_manifest(
+    manifest: &Manifest,
+    variables: &BTreeMap<String, String>,
+) -> anyhow::Result<Manifest> {
+    let mut value = serde_json::to_value(manifest).context("Unable to serialize manifest")?;
+    let mut undefined = Vec::new();
+    // Trait properties are taken out and resolved on their own so they can be typed
+    let pointers = manifest
+        .spec
+        .components
+        .iter()
+        .enumerate()
+        .flat_map(|(component, c)| {
+            (0..c.traits.as_ref().map_or(0, Vec::len))
+                .map(move |t| format!("/spec/components/{component}/traits/{t}/properties"))
+        })
+        .collect::<Vec<_>>();
+    let mut trait_properties = pointers
+        .iter()
+        .map(|pointer| value.pointer_mut(pointer).map(std::mem::take))
+        .collect::<Vec<_>>();
+    substitute_value(&mut value, variables, &mut undefined, false);
+    for (pointer, properties) in pointers.iter().zip(trait_properties.iter_mut()) {
+        if let (Some(properties), Some(slot)) = (properties.take(), value.pointer_mut(pointer)) {
+            *slot = properties;
+            resolve_trait_properties(slot, variables, &mut undefined);
+        }
+    }
+    if !undefined.is_empty() {
+        undefined.sort();
+        undefined.dedup();
+        bail!(
+            "Manifest references undefined variables: {}",
+            undefined.join(", ")
+        )
+    }
+    serde_json::from_value(value).context("Templated manifest is no longer a valid manifest")
+}
+
+fn resolve_trait_properties(
+    properties: &mut serde_json::Value,
+    variables: &BTreeMap<String, String>,
+    undefined: &mut Vec<String>,
+) {
+    let mut typed = properties.clone();
+    substitute_value(properties, variables, undefined, false);
+    substitute_value(&mut typed, variables, &mut Vec::new(), true);
+    let is_custom = |value: &serde_json::Value| {
+        matches!(
+            serde_json::from_value::<TraitProperty>(value.clone()),
+            Ok(TraitProperty::Custom(_))
+        )
+    };
+    if typed != *properties && is_custom(properties) && !is_custom(&typed) {
+        *properties = typed;
+    }
+}
+
+/// Substitutes placeholders in all strings in the given value. If `typed` is set, strings that
+/// are a single placeholder for a numeric or boolean variable are replaced with that number or
+/// boolean
+fn substitute_value(
+    value: &mut serde_json::Value,
+    variables: &BTreeMap<String, String>,
+    undefined: &mut Vec<String>,
+    typed: bool,
+) {
+    match value {
+        serde_json::Value::String(s) => {
+            let replacement = s
+                .strip_prefix("${")
+                .and_then(|rest| rest.strip_suffix('}'))
+                .filter(|name| typed && !name.contains('}'))
+                .and_then(|name| variables.get(name))
+                .and_then(|val| serde_json::from_str::<serde_json::Value>(val).ok())
+                .filter(|val| val.is_number() || val.is_boolean());
+            match replacement {
+                Some(replacement) => *value = replacement,
+                None => *s = substitute_str(s, variables, undefined),
+            }
+        }
+        serde_json::Value::Array(values) => values
+            .iter_mut()
+            .for_each(|v| substitute_value(v, variables, undefined, typed)),
+        serde_json::Value::Object(map) => map
+            .values_mut()
+            .for_each(|v| substitute_value(v, variables, undefined, typed)),
+        _ => (),
+    }
+}
+
+fn substitute_str(
+    input: &str,
+    variables: &BTreeMap<String, String>,
+    undefined: &mut Vec<String>,
+) -> String {
+    let mut output = String::with_capacity(input.len());
+    let mut rest = input;
+    while let Some(start) = rest.find("${") {
+        let Some(len) = rest[start + 2..].find('}') else {
+            break;
+        };
+        let name = &rest[start + 2..start + 2 + len];
+        output.push_str(&rest[..start]);
+        match variables.get(name) {
+            Some(val) => output.push_str(val),
+            None => {
+                undefined.push(name.to_owned());
+                output.push_str(&rest[start..start + 3 + len]);
+            }
+        }
+        rest = &rest[start + 3 + len..];
+    }
+    output.push_str(rest);
+    output
+}
+
+#[cfg(test)]
+mod test {
+    use tokio::sync::RwLock;
+
+    use super::*;
+    use crate::{
+        commands::ScaleComponent,
+        test_util::{RecorderPublisher, TestStore},
+    };
+
+    #[tokio::test]
+    async fn test_command_dedup_across_instances() {
+        let store = Arc::new(TestStore::default());
+        let lattice_id = "command_dedup";
+        let ttl = std::time::Duration::from_secs(30);
+
+        let publisher_one = RecorderPublisher::<Command> {
+            received: Arc::new(RwLock::new(Vec::new())),
+        };
+        let publisher_two = RecorderPublisher::<Command> {
+            received: Arc::new(RwLock::new(Vec::new())),
+        };
+        let instance_one =
+            CommandPublisher::new(publisher_one.clone(), "doesntmatter").with_claimer(
+                StoreCommandClaimer::new(store.clone(), lattice_id, "wadm-one", ttl),
+            );
+        let instance_two =
+            CommandPublisher::new(publisher_two.clone(), "doesntmatter").with_claimer(
+                StoreCommandClaimer::new(store.clone(), lattice_id, "wadm-two", ttl),
+            );
+
+        let command = Command::ScaleComponent(ScaleComponent {
+            component_id: "component".to_string(),
+            host_id: "host".to_string(),
+            count: 1,
+            reference: "fakecloud.azurecr.io/echo:0.3.4".to_string(),
+            model_name: "model".to_string(),
+            ..Default::default()
+        });
+        let other_command = Command::ScaleComponent(ScaleComponent {
+            host_id: "otherhost".to_string(),
+            ..match &command {
+                Command::ScaleComponent(scale) => scale.clone(),
+                _ => unreachable!(),
+            }
+        });
+
+        // Both instances computed the same command during an overlapping reconcile
+        instance_one
+            .publish_commands(vec![command.clone()])
+            .await
+            .unwrap();
+        instance_two
+            .publish_commands(vec![command.clone(), other_command.clone()])
+            .await
+            .unwrap();
+
+        assert_eq!(*publisher_one.received.read().await, vec![command.clone()]);
+        assert_eq!(
+            *publisher_two.received.read().await,
+            vec![other_command],
+            "Second instance should only publish the command it was able to claim"
+        );
+
+        // The owner of a claim can still publish the command again
+        instance_one
+            .publish_commands(vec![command.clone()])
+            .await
+            .unwrap();
+        assert_eq!(publisher_one.received.read().await.len(), 2);
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_concurrent_claimers_never_claim_the_same_command() {
+        let store = Arc::new(TestStore::default());
+        let lattice_id = "concurrent_claims";
+        let ttl = std::time::Duration::from_secs(30);
+        let ids = (0..50).map(|id| id.to_string()).collect::<Vec<_>>();
+        let barrier = Arc::new(tokio::sync::Barrier::new(2));
+
+        let claim_all = |owner: &'static str| {
+            let claimer = StoreCommandClaimer::new(store.clone(), lattice_id, owner, ttl);
+            let ids = ids.clone();
+            let barrier = barrier.clone();
+            tokio::spawn(async move {
+                barrier.wait().await;
+                let mut claimed = HashSet::new();
+                for id in ids {
+                    claimed.extend(claimer.claim(&[id]).await.expect("Claim should succeed"));
+                }
+                claimed
+            })
+        };
+        let (one, two) = tokio::join!(claim_all("wadm-one"), claim_all("wadm-two"));
+        let (one, two) = (one.unwrap(), two.unwrap());
+
+        assert!(
+            one.is_disjoint(&two),
+            "No command should be claimed by both instances: {:?}",
+            one.intersection(&two).collect::<Vec<_>>()
+        );
+        assert_eq!(
+            one.len() + two.len(),
+            ids.len(),
+            "Every command should be claimed by one of the instances"
+        );
+    }
+
+    #[test]
+    fn test_template_manifest_with_numeric_placeholders() {
+        let manifest: Manifest = serde_yaml::from_str(
+            r#"
+apiVersion: core.oam.dev/v1beta1
+kind: Application
+metadata:
+  name: templated
+  annotations:
+    version: "${VERSION}"
+spec:
+  components:
+    - name: http-component
+      type: component
+      properties:
+        image: ghcr.io/wasmcloud/components/http-hello-world-rust:${TAG}
+      traits:
+        - type: spreadscaler
+          properties:
+            instances: ${REPLICAS}
+"#,
+        )
+        .expect("Templated manifest should parse");
+
+        let resolved = template_manifest(
+            &manifest,
+            &BTreeMap::from([
+                ("VERSION".to_string(), "2".to_string()),
+                ("TAG".to_string(), "0.1.0".to_string()),
+                ("REPLICAS".to_string(), "5".to_string()),
+            ]),
+        )
+        .expect("Manifest should resolve");
+
+        // A placeholder for a string field stays a string, even if the value looks like a number
+        assert_eq!(resolved.version(), "2");
+        let component = &resolved.spec.components[0];
+        let wadm_types::Properties::Component { properties } = &component.properties else {
+            panic!("Should be a component");
+        };
+        assert_eq!(
+            properties.image.as_deref(),
+            Some("ghcr.io/wasmcloud/components/http-hello-world-rust:0.1.0")
+        );
+        let traits = component.traits.as_ref().expect("Should have traits");
+        let TraitProperty::SpreadScaler(spread) = &traits[0].properties else {
+            panic!(
+                "Spreadscaler should parse once instances is a number, got {:?}",
+                traits[0].properties
+            );
+        };
+        assert_eq!(spread.instances, 5);
+
+        let err = template_manifest(
+            &manifest,
+            &BTreeMap::from([("TAG".to_string(), "0.1.0".to_string())]),
+        )
+        .expect_err("Undefined variables should fail");
+        assert!(err.to_string().contains("REPLICAS, VERSION"), "{err}");
+    }
+
+    #[tokio::test]
+    async fn test_command_batch_max_age() {
+        let publisher = RecorderPublisher::<Command> {
+            received: Arc::new(RwLock::new(Vec::new())),
+        };
+        let command_publisher = CommandPublisher::new(publisher.clone(), "doesntmatter")
+            .with_batching(CommandBatchConfig {
+                max_size: 10,
+                max_age: Duration::from_millis(100),
+            });
+
+        let command = Command::ScaleComponent(ScaleComponent {
+            component_id: "component".to_string(),
+            host_id: "host".to_string(),
+            count: 1,
+            reference: "fakecloud.azurecr.io/echo:0.3.4".to_string(),
+            model_name: "model".to_string(),
+            ..Default::default()
+        });
+
+        let queued = tokio::spawn({
+            let command_publisher = command_publisher.clone();
+            let command = command.clone();
+            async move { command_publisher.publish_commands(vec![command]).await }
+        });
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        assert!(
+            publisher.received.read().await.is_empty(),
+            "Command should be queued while the batch isn't full"
+        );
+        assert!(
+            !queued.is_finished(),
+            "Publishing should wait until the batch has been published"
+        );
+
+        tokio::time::timeout(Duration::from_millis(300), queued)
+            .await
+            .expect("Batch should be published once it reaches its max age")
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            *publisher.received.read().await,
+            vec![command],
+            "Command should be published once the batch reaches its max age"
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_command_batch_max_age_resets_when_batch_fills() {
+        let publisher = RecorderPublisher::<Command> {
+            received: Arc::new(RwLock::new(Vec::new())),
+        };
+        let command_publisher = CommandPublisher::new(publisher.clone(), "doesntmatter")
+            .with_batching(CommandBatchConfig {
+                max_size: 2,
+                max_age: Duration::from_millis(100),
+            });
+        let command = |component_id: &str| {
+            Command::ScaleComponent(ScaleComponent {
+                component_id: component_id.to_string(),
+                host_id: "host".to_string(),
+                count: 1,
+                model_name: "model".to_string(),
+                ..Default::default()
+            })
+        };
+
+        // Filling the first batch publishes it right away, leaving its timer running
+        let (first, second) = tokio::join!(
+            command_publisher.publish_commands(vec![command("first")]),
+            command_publisher.publish_commands(vec![command("second")]),
+        );
+        first.unwrap();
+        second.unwrap();
+
+        tokio::time::sleep(Duration::from_millis(80)).await;
+        let queued = tokio::spawn({
+            let command_publisher = command_publisher.clone();
+            let command = command("third");
+            async move { command_publisher.publish_commands(vec![command]).await }
+        });
+
+        // The first batch's timer has fired by now, but the new batch has its own max age
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert_eq!(
+            publisher.received.read().await.len(),
+            2,
+            "The next batch shouldn't be published by the previous batch's timer"
+        );
+        assert!(!queued.is_finished(), "Command should still be queued");
+
+        tokio::time::sleep(Duration::from_millis(60)).await;
+        queued.await.unwrap().unwrap();
+        assert_eq!(
+            publisher.received.read().await.last(),
+            Some(&command("third")),
+            "Command should be published once its own batch reaches its max age"
+        );
+    }
+
+    /// A publisher that fails every publish
+    #[derive(Clone)]
+    struct FailingPublisher;
+
+    #[async_trait::async_trait]
+    impl Publisher for FailingPublisher {
+        async fn publish(&self, _: Vec<u8>, _: Option<&str>) -> anyhow::Result<()> {
+            anyhow::bail!("publish failed")
+        }
+    }
+
+    #[tokio::test]
+    async fn test_command_batch_failure_fails_every_caller() {
+        let command_publisher = CommandPublisher::new(FailingPublisher, "doesntmatter")
+            .with_batching(CommandBatchConfig {
+                max_size: 2,
+                max_age: Duration::from_secs(60),
+            });
+        let command = |component_id: &str| {
+            Command::ScaleComponent(ScaleComponent {
+                component_id: component_id.to_string(),
+                host_id: "host".to_string(),
+                count: 1,
+                model_name: "model".to_string(),
+                ..Default::default()
+            })
+        };
+
+        // The second command fills the batch, which publishes it for both callers
+        let (first, second) = tokio::join!(
+            command_publisher.publish_commands(vec![command("first")]),
+            command_publisher.publish_commands(vec![command("second")]),
+        );
+        assert!(
+            first.is_err() && second.is_err(),
+            "Both callers should see the batch fail so their events aren't acked"
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_command_rate_limit() {
+        let publisher = RecorderPublisher::<Command> {
+            received: Arc::new(RwLock::new(Vec::new())),
+        };
+        let command_publisher = CommandPublisher::new(publisher.clone(), "doesntmatter")
+            .with_rate_limit(CommandRateLimit {
+                per_second: 10.0,
+                burst: 10,
+            });
+
+        let commands = (1..=100)
+            .map(|count| {
+                Command::ScaleComponent(ScaleComponent {
+                    component_id: "component".to_string(),
+                    host_id: "host".to_string(),
+                    count,
+                    model_name: "model".to_string(),
+                    ..Default::default()
+                })
+            })
+            .collect::<Vec<_>>();
+
+        let start = tokio::time::Instant::now();
+        command_publisher
+            .publish_commands(commands.clone())
+            .await
+            .unwrap();
+        let elapsed = start.elapsed();
+
+        // The first 10 commands use up the burst and the other 90 go out at 10 per second
+        assert!(
+            elapsed >= Duration::from_millis(8900) && elapsed <= Duration::from_millis(9500),
+            "Publishing should take about 9 seconds, took {elapsed:?}"
+        );
+        assert_eq!(
+            *publisher.received.read().await,
+            commands,
+            "Every command should be published, in order"
+        );
+    }
+
+    /// A publisher that only records the subject of everything it publishes
+    #[derive(Clone, Default)]
+    struct SubjectRecorder {
+        subjects: Arc<RwLock<Vec<String>>>,
+    }
+
+    #[async_trait::async_trait]
+    impl Publisher for SubjectRecorder {
+        async fn publish(&self, _data: Vec<u8>, destination: Option<&str>) -> anyhow::Result<()> {
+            self.subjects
+                .write()
+                .await
+                .push(destination.unwrap_or_default().to_owned());
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_per_model_subjects() {
+        let publisher = SubjectRecorder::default();
+        let command_template: SubjectTemplate = "tenant.{lattice_id}.cmd.{model_name}"
+            .parse()
+            .expect("Should parse command template");
+        let status_template: SubjectTemplate = "tenant.{lattice_id}.status.{model_name}"
+            .parse()
+            .expect("Should parse status template");
+        let command_publisher = CommandPublisher::new(publisher.clone(), "doesntmatter")
+            .with_subject_template("default", command_template.clone());
+        let status_publisher = StatusPublisher::new(publisher.clone(), None, "doesntmatter")
+            .with_subject_template("default", status_template);
+
+        let scale = |model_name: &str| {
+            Command::ScaleComponent(ScaleComponent {
+                component_id: "component".to_string(),
+                host_id: "host".to_string(),
+                count: 1,
+                model_name: model_name.to_string(),
+                ..Default::default()
+            })
+        };
+        command_publisher
+            .publish_commands(vec![scale("tenant_a"), scale("tenant_b")])
+            .await
+            .unwrap();
+        status_publisher
+            .publish_status("tenant_a", Status::default())
+            .await
+            .unwrap();
+        status_publisher
+            .publish_status("tenant_b", Status::default())
+            .await
+            .unwrap();
+
+        assert_eq!(
+            *publisher.subjects.read().await,
+            vec![
+                "tenant.default.cmd.tenant_a",
+                "tenant.default.cmd.tenant_b",
+                "tenant.default.status.tenant_a",
+                "tenant.default.status.tenant_b",
+            ],
+            "Each model should publish on its own subjects"
+        );
+        assert_eq!(
+            command_template.lattice_filter("default"),
+            "tenant.default.cmd.*"
+        );
+        assert_eq!(command_template.stream_subject(), "tenant.*.cmd.*");
+        assert!("wadm.cmd.{model_name}".parse::<SubjectTemplate>().is_err());
+        assert!("wadm.cmd.lattice-{lattice_id}"
+            .parse::<SubjectTemplate>()
+            .is_err());
+    }
+}
@@ -0,0 +1,412 @@
+//! Contains the instance leases used to detect when wadm instances lose contact with each other
+
+use std::{
+    collections::HashSet,
+    sync::{Arc, Mutex, Weak},
+    time::Duration,
+};
+
+use chrono::{DateTime, Utc};
+use tracing::{info, instrument, warn};
+
+use crate::storage::{InstanceLease, Store};
+
+/// How many TTLs a lease can be expired for before it is removed from the store
+const LEASE_RETENTION_TTLS: i32 = 10;
+
+/// What a wadm instance does when it can't renew its lease, meaning the other instances will
+/// consider it lost and keep managing the lattice without it
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SplitBrainPolicy {
+    /// Log that the instance is isolated and keep publishing commands as normal
+    #[default]
+    Log,
+    /// Stop publishing commands until the lease can be renewed again, leaving the lattice to the
+    /// instances that are still in contact with each other
+    ReadOnly,
+}
+
+impl std::fmt::Display for SplitBrainPolicy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SplitBrainPolicy::Log => write!(f, "log"),
+            SplitBrainPolicy::ReadOnly => write!(f, "read-only"),
+        }
+    }
+}
+
+impl std::str::FromStr for SplitBrainPolicy {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        match s {
+            "log" => Ok(SplitBrainPolicy::Log),
+            "read-only" => Ok(SplitBrainPolicy::ReadOnly),
+            _ => anyhow::bail!("unknown split-brain policy '{s}', expected log or read-only"),
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct LeaseState {
+    /// The last time this instance renewed its own lease
+    last_renewed: DateTime<Utc>,
+    /// Whether this instance has gone longer than the TTL without renewing its lease
+    isolated: bool,
+    /// Other instances with a live lease
+    peers: HashSet<String>,
+    /// Other instances whose lease expired while we were watching it
+    lost_peers: HashSet<String>,
+}
+
+struct LeaseInner<S> {
+    store: S,
+    lattice_id: String,
+    instance_id: String,
+    ttl: chrono::Duration,
+    policy: SplitBrainPolicy,
+    state: Mutex<LeaseState>,
+}
+
+/// Keeps this instance's [`InstanceLease`] for a lattice renewed and watches the leases of all
+/// other wadm instances. Losing contact with another instance is always logged. If this instance
+/// can't renew its own lease, it is the one that has been cut off and the configured
+/// [`SplitBrainPolicy`] decides whether it keeps issuing commands. Clones share the same state
+///
+/// NOTE: Like the [`StoreCommandClaimer`](super::StoreCommandClaimer), this relies on the store
+/// rather than a consensus protocol, so it narrows the window for conflicting commands rather than
+/// ruling them out completely
+pub struct LeaseKeeper<S> {
+    inner: Arc<LeaseInner<S>>,
+}
+
+impl<S> Clone for LeaseKeeper<S> {
+    fn clone(&self) -> Self {
+        LeaseKeeper {
+            inner: self.inner.clone(),
+        }
+    }
+}
+
+impl<S> LeaseKeeper<S> {
+    /// Creates a new lease keeper for the given lattice. The `instance_id` should be a unique
+    /// identifier for this wadm instance and `ttl` is how long a lease is valid after it is renewed
+    pub fn new(
+        store: S,
+        lattice_id: &str,
+        instance_id: &str,
+        ttl: Duration,
+        policy: SplitBrainPolicy,
+    ) -> Self {
+        LeaseKeeper {
+            inner: Arc::new(LeaseInner {
+                store,
+                lattice_id: lattice_id.to_owned(),
+                instance_id: instance_id.to_owned(),
+                ttl: chrono::Duration::from_std(ttl).unwrap_or(chrono::Duration::MAX),
+                policy,
+                // Give a new instance a full TTL to renew its lease for the first time
+                state: Mutex::new(LeaseState {
+                    last_renewed: Utc::now(),
+                    ..Default::default()
+                }),
+            }),
+        }
+    }
+
+    /// Returns true if this instance should not publish any commands because it has lost contact
+    /// with the other instances and is configured to degrade to read-only
+    pub fn is_read_only(&self) -> bool {
+        self.inner.policy == SplitBrainPolicy::ReadOnly
+            && self
+                .inner
+                .state
+                .lock()
+                .map(|state| state.isolated)
+                // If the lock is poisoned, default to not publishing to be safe
+                .unwrap_or(true)
+    }
+
+    /// Returns the IDs of the other instances this instance has lost contact with
+    pub fn lost_peers(&self) -> HashSet<String> {
+        self.inner
+            .state
+            .lock()
+            .map(|state| state.lost_peers.clone())
+            .unwrap_or_default()
+    }
+}
+
+impl<S: Store + Send + Sync + 'static> LeaseKeeper<S> {
+    /// Renews this instance's lease and checks the leases of all other instances
+    #[instrument(level = "trace", skip(self), fields(lattice_id = %self.inner.lattice_id))]
+    pub async fn renew(&self) {
+        let inner = &self.inner;
+        let now = Utc::now();
+        let renewed = match inner
+            .store
+            .store(
+                &inner.lattice_id,
+                inner.instance_id.clone(),
+                InstanceLease {
+                    expires_at: now + inner.ttl,
+                },
+            )
+            .await
+        {
+            Ok(()) => true,
+            Err(e) => {
+                warn!(error = %e, instance_id = %inner.instance_id, "Unable to renew instance lease");
+                false
+            }
+        };
+        let leases = match inner.store.list::<InstanceLease>(&inner.lattice_id).await {
+            Ok(leases) => Some(leases),
+            Err(e) => {
+                warn!(error = %e, "Unable to fetch the leases of other wadm instances");
+                None
+            }
+        };
+
+        let mut expired = Vec::new();
+        {
+            let Ok(mut state) = inner.state.lock() else {
+                return;
+            };
+            if renewed {
+                state.last_renewed = now;
+            }
+            let isolated = now - state.last_renewed >= inner.ttl;
+            match (state.isolated, isolated) {
+                (false, true) if inner.policy == SplitBrainPolicy::ReadOnly => warn!(
+                    instance_id = %inner.instance_id,
+                    "Lost contact with other wadm instances, pausing commands until the lease can be renewed"
+                ),
+                (false, true) => warn!(
+                    instance_id = %inner.instance_id,
+                    "Lost contact with other wadm instances, which may also be managing this lattice"
+                ),
+                (true, false) => info!(
+                    instance_id = %inner.instance_id,
+                    "Regained contact with other wadm instances"
+                ),
+                _ => (),
+            }
+            state.isolated = isolated;
+
+            for (id, lease) in leases.into_iter().flatten() {
+                if id == inner.instance_id {
+                    continue;
+                }
+                if lease.expires_at > now {
+                    if state.lost_peers.remove(&id) {
+                        info!(peer_id = %id, "Regained contact with wadm instance");
+                    }
+                    state.peers.insert(id);
+                } else {
+                    if state.peers.remove(&id) {
+                        warn!(
+                            peer_id = %id,
+                            "Lost contact with wadm instance. It may be partitioned from this instance and managing the lattice on its own"
+                        );
+                        state.lost_peers.insert(id.clone());
+                    }
+                    if now - lease.expires_at >= inner.ttl * LEASE_RETENTION_TTLS {
+                        state.lost_peers.remove(&id);
+                        expired.push(id);
+                    }
+                }
+            }
+        }
+
+        if !expired.is_empty() {
+            if let Err(e) = inner
+                .store
+                .delete_many::<InstanceLease, _, _>(&inner.lattice_id, expired)
+                .await
+            {
+                warn!(error = %e, "Unable to remove expired instance leases");
+            }
+        }
+    }
+
+    /// Spawns a task that renews the lease every `interval` until all clones of this keeper are
+    /// dropped
+    pub fn spawn_renewal(&self, interval: Duration) {
+        let weak: Weak<LeaseInner<S>> = Arc::downgrade(&self.inner);
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+            loop {
+                ticker.tick().await;
+                let Some(inner) = weak.upgrade() else {
+                    break;
+                };
+                LeaseKeeper { inner }.renew().await;
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{
+        collections::HashMap,
+        sync::atomic::{AtomicBool, Ordering},
+    };
+
+    use async_trait::async_trait;
+    use serde::{de::DeserializeOwned, Serialize};
+    use tokio::sync::RwLock;
+
+    use super::*;
+    use crate::{
+        commands::{Command, DeleteConfig},
+        storage::{ReadStore, StateKind, StoreError},
+        test_util::{RecorderPublisher, TestStore},
+        workers::CommandPublisher,
+    };
+
+    /// A store that fails every operation while it is partitioned
+    #[derive(Clone, Default)]
+    struct PartitionedStore {
+        inner: Arc<TestStore>,
+        partitioned: Arc<AtomicBool>,
+    }
+
+    impl PartitionedStore {
+        fn check(&self) -> Result<(), StoreError> {
+            if self.partitioned.load(Ordering::Relaxed) {
+                Err(StoreError::Connection(Box::new(std::io::Error::other(
+                    "partitioned",
+                ))))
+            } else {
+                Ok(())
+            }
+        }
+    }
+
+    #[async_trait]
+    impl ReadStore for PartitionedStore {
+        async fn get<T>(&self, lattice_id: &str, id: &str) -> Result<Option<T>, StoreError>
+        where
+            T: DeserializeOwned + StateKind,
+        {
+            self.check()?;
+            Ok(self.inner.get(lattice_id, id).await.unwrap())
+        }
+
+        async fn list<T>(&self, lattice_id: &str) -> Result<HashMap<String, T>, StoreError>
+        where
+            T: DeserializeOwned + StateKind,
+        {
+            self.check()?;
+            Ok(self.inner.list(lattice_id).await.unwrap())
+        }
+    }
+
+    #[async_trait]
+    impl Store for PartitionedStore {
+        async fn store_many<T, D>(&self, lattice_id: &str, data: D) -> Result<(), StoreError>
+        where
+            T: Serialize + DeserializeOwned + StateKind + Send + Sync + Clone,
+            D: IntoIterator<Item = (String, T)> + Send,
+        {
+            self.check()?;
+            self.inner.store_many(lattice_id, data).await.unwrap();
+            Ok(())
+        }
+
+        async fn delete_many<T, D, K>(&self, lattice_id: &str, data: D) -> Result<(), StoreError>
+        where
+            T: Serialize + DeserializeOwned + StateKind + Send + Sync,
+            D: IntoIterator<Item = K> + Send,
+            K: AsRef<str>,
+        {
+            self.check()?;
+            self.inner
+                .delete_many::<T, _, _>(lattice_id, data)
+                .await
+                .unwrap();
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn isolated_instance_degrades_to_read_only() {
+        let lattice_id = "split_brain";
+        let ttl = Duration::from_millis(200);
+        let shared = Arc::new(TestStore::default());
+        let store_a = PartitionedStore {
+            inner: shared.clone(),
+            ..Default::default()
+        };
+        let store_b = PartitionedStore {
+            inner: shared,
+            ..Default::default()
+        };
+        let a = LeaseKeeper::new(store_a, lattice_id, "a", ttl, SplitBrainPolicy::Log);
+        let b = LeaseKeeper::new(
+            store_b.clone(),
+            lattice_id,
+            "b",
+            ttl,
+            SplitBrainPolicy::ReadOnly,
+        );
+
+        a.renew().await;
+        b.renew().await;
+        a.renew().await;
+        assert!(a.lost_peers().is_empty());
+        assert!(!b.is_read_only());
+
+        // Cut b off from everything and wait for its lease to expire
+        store_b.partitioned.store(true, Ordering::Relaxed);
+        tokio::time::sleep(ttl + Duration::from_millis(50)).await;
+        a.renew().await;
+        b.renew().await;
+
+        assert_eq!(
+            a.lost_peers(),
+            HashSet::from_iter(["b".to_string()]),
+            "a should detect that it lost contact with b"
+        );
+        assert!(
+            !a.is_read_only(),
+            "a is not isolated, so it should keep going"
+        );
+        assert!(
+            b.is_read_only(),
+            "b can't renew its lease, so it should degrade to read-only"
+        );
+
+        let publisher = RecorderPublisher::<Command> {
+            received: Arc::new(RwLock::new(Vec::new())),
+        };
+        let commands = vec![Command::DeleteConfig(DeleteConfig {
+            config_name: "config".to_string(),
+        })];
+        CommandPublisher::new(publisher.clone(), "doesntmatter")
+            .with_lease(b.clone())
+            .publish_commands(commands.clone())
+            .await
+            .unwrap();
+        assert!(
+            publisher.received.read().await.is_empty(),
+            "A read-only instance should not publish commands"
+        );
+
+        // Once the partition heals, both sides should recover
+        store_b.partitioned.store(false, Ordering::Relaxed);
+        b.renew().await;
+        a.renew().await;
+        assert!(!b.is_read_only());
+        assert!(a.lost_peers().is_empty());
+        CommandPublisher::new(publisher.clone(), "doesntmatter")
+            .with_lease(b)
+            .publish_commands(commands.clone())
+            .await
+            .unwrap();
+        assert_eq!(*publisher.received.read().await, commands);
+    }
+}
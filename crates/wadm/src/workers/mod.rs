@@ -5,8 +5,10 @@
 mod command;
 mod event;
 mod event_helpers;
+mod lease;
 
-pub use command::CommandWorker;
+pub use command::{CommandWorker, ControlInterfaceBreaker, HostConcurrencyLimit, RetryPolicy};
 pub(crate) use event::get_commands_and_result;
-pub use event::EventWorker;
+pub use event::{DeployConflictPolicy, EventWorker};
 pub use event_helpers::*;
+pub use lease::{LeaseKeeper, SplitBrainPolicy};
@@ -744,6 +744,7 @@ async fn test_deploy() {
             "default.model.deploy.rust-sqldb-postgres-query",
             serde_json::to_vec(&DeployModelRequest {
                 version: Some("v0.0.1".to_string()),
+                ..Default::default()
             })
             .unwrap(),
             None,
@@ -775,6 +776,7 @@ async fn test_deploy() {
             "default.model.deploy.rust-sqldb-postgres-query",
             serde_json::to_vec(&DeployModelRequest {
                 version: Some("latest".to_string()),
+                ..Default::default()
             })
             .unwrap(),
             None,
@@ -25,7 +25,11 @@ async fn test_commands() {
     let mut wrapper = StreamWrapper::new("commands_integration".into(), nats_client.clone()).await;
 
     let ctl_client = wasmcloud_control_interface::ClientBuilder::new(nats_client.clone()).build();
-    let worker = CommandWorker::new(ctl_client.clone());
+    let worker = CommandWorker::new(
+        ctl_client.clone(),
+        nats_client.clone(),
+        "wadm.evt.default.command_executed",
+    );
 
     let host_id = ctl_client
         .get_hosts()
@@ -333,7 +337,11 @@ async fn test_annotation_stop() {
     let mut wrapper = StreamWrapper::new("annotation_stop".into(), nats_client.clone()).await;
 
     let ctl_client = wasmcloud_control_interface::ClientBuilder::new(nats_client.clone()).build();
-    let worker = CommandWorker::new(ctl_client.clone());
+    let worker = CommandWorker::new(
+        ctl_client.clone(),
+        nats_client.clone(),
+        "wadm.evt.default.command_executed",
+    );
 
     let mut sub = nats_client
         .subscribe("wasmbus.evt.default.>".to_string())
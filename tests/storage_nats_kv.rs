@@ -40,6 +40,7 @@ async fn test_round_trip() {
             }]),
         )]),
         reference: "fake.oci.repo/testcomponent:0.1.0".to_string(),
+        ..Default::default()
     };
 
     let component2 = Component {
@@ -54,6 +55,7 @@ async fn test_round_trip() {
             }]),
         )]),
         reference: "fake.oci.repo/anothercomponent:0.1.0".to_string(),
+        ..Default::default()
     };
 
     let host = Host {
@@ -75,7 +77,8 @@ async fn test_round_trip() {
         name: "Test Provider".to_string(),
         issuer: "afakekey".to_string(),
         reference: "fake.oci.repo/testprovider:0.1.0".to_string(),
-        hosts: [("testhost".to_string(), ProviderStatus::default())].into(),
+        hosts: [("testhost".to_string(), ProviderStatus::default().into())].into(),
+        capabilities: Vec::new(),
     };
 
     store
@@ -238,6 +241,7 @@ async fn test_multiple_lattice() {
             }]),
         )]),
         reference: "fake.oci.repo/testcomponent:0.1.0".to_string(),
+        ..Default::default()
     };
 
     let component2 = Component {
@@ -252,6 +256,7 @@ async fn test_multiple_lattice() {
             }]),
         )]),
         reference: "fake.oci.repo/anothercomponent:0.1.0".to_string(),
+        ..Default::default()
     };
 
     // Store both components first with the different lattice id
@@ -325,6 +330,7 @@ async fn test_store_and_delete_many() {
             }]),
         )]),
         reference: "fake.oci.repo/testcomponent:0.1.0".to_string(),
+        ..Default::default()
     };
 
     let component2 = Component {
@@ -339,6 +345,7 @@ async fn test_store_and_delete_many() {
             }]),
         )]),
         reference: "fake.oci.repo/anothercomponent:0.1.0".to_string(),
+        ..Default::default()
     };
 
     store